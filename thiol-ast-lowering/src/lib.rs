@@ -2,10 +2,16 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use std::collections::{BTreeMap, HashSet};
+
 use id_arena::Id;
 use thiol_hir as hir;
 use thiol_syntax::{ast, FileLocation, Loc};
 
+use namespaces::{Declarations, Namespace};
+
+mod namespaces;
+
 pub fn lower(
     ctx: &mut hir::Context,
     file: &ast::File,
@@ -13,34 +19,19 @@ pub fn lower(
     let mut t = Translator {
         ctx,
         errs: Vec::new(),
+        declarations: Declarations::collect(&file.items),
+        path_names: &file.path_names,
+        module: vec![],
+        locals: vec![],
     };
 
     let mut module = hir::Module::default();
-
-    for item in &file.items {
-        match item {
-            ast::Item::Function(f) => {
-                if let Ok(id) = t.function(f) {
-                    module.functions.push(id);
-                }
-            }
-            ast::Item::Consts(c) => {
-                if let Ok(ids) = t.consts(c) {
-                    module.consts.extend(ids);
-                }
-            }
-            ast::Item::Types(tys) => {
-                if let Ok(tys) = t.types(tys) {
-                    module.types.extend(tys);
-                }
-            }
-            ast::Item::Program(p) => {
-                if let Ok(id) = t.program(p) {
-                    module.programs.push(id);
-                }
-            }
-        }
-    }
+    t.items(&file.items, &mut module);
+    t.ctx.reserved_words.extend(
+        file.reserved_words
+            .iter()
+            .map(|word| (word.value.clone(), word.loc)),
+    );
 
     if t.errs.is_empty() {
         Ok(module)
@@ -49,6 +40,30 @@ pub fn lower(
     }
 }
 
+/// Lower an expression that isn't part of the file, like the value of a
+/// constant given when compiling, as if it was written in the module of the
+/// file with the path `module`.
+pub fn lower_expression(
+    ctx: &mut hir::Context,
+    file: &ast::File,
+    module: &[String],
+    expr: &Loc<ast::Expression>,
+) -> core::result::Result<Id<hir::Expression>, Vec<Error>> {
+    let mut t = Translator {
+        ctx,
+        errs: Vec::new(),
+        declarations: Declarations::collect(&file.items),
+        path_names: &file.path_names,
+        module: module.to_vec(),
+        locals: vec![],
+    };
+
+    match t.expr(expr) {
+        Ok(id) if t.errs.is_empty() => Ok(id),
+        _ => Err(t.errs),
+    }
+}
+
 type Result<T> = core::result::Result<T, ()>;
 
 #[derive(Debug, Clone)]
@@ -65,27 +80,137 @@ pub enum Error {
     TypeConstructorInInvalidPosition {
         where_: FileLocation,
     },
+    IntegerLiteralTooLarge {
+        literal: FileLocation,
+    },
+    /// a `use` or `prelude` with a path that refers to nothing
+    UnresolvedPath {
+        kind: &'static str,
+        path: String,
+        loc: FileLocation,
+    },
+    /// a private item or import of a module used outside of it
+    PrivateItem {
+        kind: &'static str,
+        name: String,
+        reference: FileLocation,
+        declaration: FileLocation,
+    },
 }
 
 struct Translator<'a> {
     ctx: &'a mut hir::Context,
     errs: Vec<Error>,
+    declarations: Declarations,
+    /// see [`ast::File::path_names`]
+    path_names: &'a BTreeMap<FileLocation, FileLocation>,
+    /// the path of the module the items being lowered are declared in
+    module: Vec<String>,
+    /// the names of locals and generic parameters in scope, which hide the
+    /// items of modules
+    locals: Vec<HashSet<String>>,
 }
 
 impl<'a> Translator<'a> {
+    fn items(&mut self, items: &[ast::Item], module: &mut hir::Module) {
+        for item in items {
+            match item {
+                ast::Item::Function(f) => {
+                    if let Ok(id) = self.function(f) {
+                        module.functions.push(id);
+                    }
+                }
+                ast::Item::Consts(c) => {
+                    if let Ok(ids) = self.consts(c) {
+                        module.consts.extend(ids);
+                    }
+                }
+                ast::Item::Types(tys) => {
+                    if let Ok(tys) = self.types(tys) {
+                        module.types.extend(tys);
+                    }
+                }
+                ast::Item::Program(p) => {
+                    if let Ok(id) = self.program(p) {
+                        module.programs.push(id);
+                    }
+                }
+                ast::Item::Space(s) => module.spaces.push(self.space(s)),
+                ast::Item::StaticAssert(a) => {
+                    if let Ok(id) = self.static_assert(a) {
+                        module.static_asserts.push(id);
+                    }
+                }
+                ast::Item::Module(m) => {
+                    self.module.push(m.value.name.value.clone());
+                    self.items(&m.value.items, module);
+                    self.module.pop();
+                }
+                ast::Item::Use(u) => {
+                    let path = &u.value.path;
+                    match self.declarations.import(&self.module, &path.value) {
+                        Some(item) => module.uses.push(self.ident_loc(&item, path.loc)),
+                        None => self.errs.push(Error::UnresolvedPath {
+                            kind: "item",
+                            path: path.value.clone(),
+                            loc: path.loc,
+                        }),
+                    }
+                }
+                ast::Item::Prelude(p) => {
+                    let path = &p.value.module;
+                    if self
+                        .declarations
+                        .module(&self.module, &path.value)
+                        .is_none()
+                    {
+                        self.errs.push(Error::UnresolvedPath {
+                            kind: "module",
+                            path: path.value.clone(),
+                            loc: path.loc,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
     fn function(&mut self, f: &Loc<ast::Function>) -> Result<Id<hir::Function>> {
+        let scope = f
+            .value
+            .generics
+            .iter()
+            .chain(f.value.args.iter().map(|(name, _, _)| name))
+            .map(|name| name.value.clone())
+            .collect();
+        self.locals.push(scope);
+        let func = self.function_in_scope(f);
+        self.locals.pop();
+        func
+    }
+
+    fn function_in_scope(&mut self, f: &Loc<ast::Function>) -> Result<Id<hir::Function>> {
         let func = hir::Function {
-            name: self.ident(&f.value.name),
+            attrs: self.attributes(&f.value.attributes)?,
+            visibility: visibility(f.value.visibility),
+            name: self.declared_ident(&f.value.name),
+            generics: f.value.generics.iter().map(|g| self.ident(g)).collect(),
             args: f
                 .value
                 .args
                 .iter()
-                .map(|(name, ty)| {
+                .map(|(name, ty, mode)| {
                     let name = self.ident(name);
                     let ty = self.type_reference(ty);
-                    (name, ty)
+                    (name, ty, param_mode(*mode))
                 })
                 .collect(),
+            arg_attrs: f
+                .value
+                .arg_attributes
+                .iter()
+                .map(|attrs| self.attributes(attrs))
+                .collect::<Result<_>>()?,
             ret_type: self.type_reference(&f.value.ret_type),
             body: self.block(&f.value.body)?,
         };
@@ -96,8 +221,24 @@ impl<'a> Translator<'a> {
     }
 
     fn program(&mut self, p: &Loc<ast::Program>) -> Result<Id<hir::Program>> {
+        let scope = p
+            .value
+            .inputs
+            .iter()
+            .chain(&p.value.outputs)
+            .chain(&p.value.workgroup)
+            .map(|var| var.value.name.value.clone())
+            .collect();
+        self.locals.push(scope);
+        let prog = self.program_in_scope(p);
+        self.locals.pop();
+        prog
+    }
+
+    fn program_in_scope(&mut self, p: &Loc<ast::Program>) -> Result<Id<hir::Program>> {
         let prog = hir::Program {
-            name: self.ident(&p.value.name),
+            attrs: self.attributes(&p.value.attributes)?,
+            name: self.declared_ident(&p.value.name),
             inputs: p
                 .value
                 .inputs
@@ -110,6 +251,12 @@ impl<'a> Translator<'a> {
                 .iter()
                 .map(|var| self.variable_def(&var.value, var.loc))
                 .collect::<Result<_>>()?,
+            workgroup: p
+                .value
+                .workgroup
+                .iter()
+                .map(|var| self.variable_def(&var.value, var.loc))
+                .collect::<Result<_>>()?,
             body: self.block(&p.value.body)?,
         };
         let id = self.ctx.programs.alloc(prog);
@@ -117,11 +264,40 @@ impl<'a> Translator<'a> {
         Ok(id)
     }
 
+    fn space(&mut self, s: &Loc<ast::SpaceDefinition>) -> Id<hir::SpaceDefinition> {
+        let space = hir::SpaceDefinition {
+            visibility: visibility(s.value.visibility),
+            name: self.declared_ident(&s.value.name),
+            parent: s.value.parent.as_ref().map(|(parent, via)| {
+                (
+                    self.item_ident(Namespace::Space, parent),
+                    self.item_ident(Namespace::Constant, via),
+                )
+            }),
+        };
+        let id = self.ctx.spaces.alloc(space);
+        self.ctx.space_fcs.insert(id, s.loc);
+        id
+    }
+
+    fn static_assert(&mut self, a: &Loc<ast::StaticAssert>) -> Result<Id<hir::StaticAssert>> {
+        let assert = hir::StaticAssert {
+            condition: self.expr(&a.value.condition)?,
+            message: a.value.message.value.clone(),
+        };
+        let id = self.ctx.static_asserts.alloc(assert);
+        self.ctx.static_assert_fcs.insert(id, a.loc);
+        Ok(id)
+    }
+
     fn consts(&mut self, c: &Loc<ast::Consts>) -> Result<Vec<Id<hir::VariableDef>>> {
         c.value
             .vars
             .iter()
-            .map(|v| self.variable_def(&v.value, v.loc))
+            .map(|v| {
+                let name = self.declared_ident(&v.value.name);
+                self.variable_def_named(&v.value, v.loc, name)
+            })
             .collect()
     }
 
@@ -134,6 +310,18 @@ impl<'a> Translator<'a> {
     }
 
     fn type_definition(&mut self, t: &Loc<ast::TypeDefinition>) -> Result<Id<hir::TypeDefinition>> {
+        let scope = t.value.generics.iter().map(|g| g.value.clone()).collect();
+        self.locals.push(scope);
+        let def = self.type_definition_in_scope(t);
+        self.locals.pop();
+        def
+    }
+
+    fn type_definition_in_scope(
+        &mut self,
+        t: &Loc<ast::TypeDefinition>,
+    ) -> Result<Id<hir::TypeDefinition>> {
+        let attrs = self.attributes(&t.value.attributes)?;
         let rhs = match &t.value.rhs.value {
             ast::TypeDefinitionRhs::Distinct(ty) => {
                 hir::TypeDefinitionRhs::Distinct(self.type_reference(ty))
@@ -152,7 +340,9 @@ impl<'a> Translator<'a> {
         self.ctx.type_def_rhs_fcs.insert(rhs_id, t.value.rhs.loc);
 
         let def = hir::TypeDefinition {
-            name: self.ident(&t.value.name),
+            attrs,
+            visibility: visibility(t.value.visibility),
+            name: self.declared_ident(&t.value.name),
             generics: t.value.generics.iter().map(|i| self.ident(i)).collect(),
             rhs: rhs_id,
         };
@@ -164,7 +354,13 @@ impl<'a> Translator<'a> {
 
     fn statement(&mut self, st: &Loc<ast::Statement>) -> Result<Id<hir::Statement>> {
         let stmt = match &st.value {
-            ast::Statement::Var(v) => hir::Statement::Var(self.variable_def(v, st.loc)?),
+            ast::Statement::Var(v) => {
+                let def = self.variable_def(v, st.loc)?;
+                if let Some(scope) = self.locals.last_mut() {
+                    scope.insert(v.name.value.clone());
+                }
+                hir::Statement::Var(def)
+            }
             ast::Statement::Becomes { lhs, rhs } => {
                 // TODO check if lhs is a valid l-value
                 let lhs_id = self.expr(lhs)?;
@@ -174,13 +370,23 @@ impl<'a> Translator<'a> {
                     rhs: rhs_id,
                 }
             }
+            ast::Statement::Destructure { targets, rhs } => {
+                let lhs = targets
+                    .iter()
+                    .map(|target| self.expr(target))
+                    .collect::<Result<Vec<_>>>()?;
+                let rhs = self.expr(rhs)?;
+                hir::Statement::Destructure { lhs, rhs }
+            }
             ast::Statement::Return(None) => hir::Statement::Return(None),
             ast::Statement::Return(Some(e)) => {
                 let e = self.expr(e)?;
                 hir::Statement::Return(Some(e))
             }
+            ast::Statement::Expr(e) => hir::Statement::Expr(self.expr(e)?),
             ast::Statement::Break => hir::Statement::Break,
             ast::Statement::Continue => hir::Statement::Continue,
+            ast::Statement::Discard => hir::Statement::Discard,
             ast::Statement::Branch { branches, else_ } => {
                 // the `branches()` method calculates the Loc but does not add it to the top
                 // most branch, the loc information from the AST is better, so it's added here.
@@ -194,6 +400,7 @@ impl<'a> Translator<'a> {
                 to,
                 body,
             } => {
+                let iter_name_s = &iter_name.value;
                 let iter_name = self.ident(iter_name);
                 let loop_type = match loop_type {
                     ast::ForLoopType::Up => hir::ForLoopType::Up,
@@ -202,7 +409,10 @@ impl<'a> Translator<'a> {
                 let from = self.expr(from)?;
                 let to = self.expr(to)?;
 
-                let body = self.block(body)?;
+                self.locals.push(HashSet::from([iter_name_s.clone()]));
+                let body = self.block(body);
+                self.locals.pop();
+                let body = body?;
 
                 hir::Statement::For {
                     iter_name,
@@ -212,19 +422,40 @@ impl<'a> Translator<'a> {
                     body,
                 }
             }
+            ast::Statement::Match { value, arms } => {
+                let value = self.expr(value)?;
+                let mut hir_arms = Vec::with_capacity(arms.len());
+                for (pattern, body) in arms {
+                    let (hir_pattern, locals) = match &pattern.value {
+                        ast::MatchPattern::Some(name) => (
+                            hir::MatchPattern::Some(self.ident(name)),
+                            HashSet::from([name.value.clone()]),
+                        ),
+                        ast::MatchPattern::None => (hir::MatchPattern::None, HashSet::new()),
+                    };
+                    self.locals.push(locals);
+                    let body = self.block(body);
+                    self.locals.pop();
+                    hir_arms.push(hir::MatchArm {
+                        pattern: hir_pattern,
+                        loc: pattern.loc,
+                        body: body?,
+                    });
+                }
+                hir::Statement::Match {
+                    value,
+                    arms: hir_arms,
+                }
+            }
         };
         let id = self.ctx.statements.alloc(stmt);
         self.ctx.statement_fcs.insert(id, st.loc);
         Ok(id)
     }
 
-    fn variable_def(
-        &mut self,
-        v: &ast::VariableDef,
-        loc: FileLocation,
-    ) -> Result<Id<hir::VariableDef>> {
+    fn attributes(&mut self, attrs_ast: &[Loc<ast::Attribute>]) -> Result<Vec<Id<hir::Attribute>>> {
         let mut attrs = vec![];
-        for attr in &v.attributes {
+        for attr in attrs_ast {
             let mut hir_attr = hir::Attribute {
                 name: self.ident(&attr.value.name),
                 pos_args: vec![],
@@ -261,7 +492,25 @@ impl<'a> Translator<'a> {
             attrs.push(id);
         }
 
+        Ok(attrs)
+    }
+
+    fn variable_def(
+        &mut self,
+        v: &ast::VariableDef,
+        loc: FileLocation,
+    ) -> Result<Id<hir::VariableDef>> {
         let name = self.ident(&v.name);
+        self.variable_def_named(v, loc, name)
+    }
+
+    fn variable_def_named(
+        &mut self,
+        v: &ast::VariableDef,
+        loc: FileLocation,
+        name: Id<hir::Identifier>,
+    ) -> Result<Id<hir::VariableDef>> {
+        let attrs = self.attributes(&v.attributes)?;
         let ty = self.type_reference(&v.type_);
         let rhs = if let Some(e) = &v.rhs {
             Some(self.expr(e)?)
@@ -271,6 +520,7 @@ impl<'a> Translator<'a> {
 
         let def = hir::VariableDef {
             attrs,
+            visibility: visibility(v.visibility),
             name,
             type_: ty,
             rhs,
@@ -282,16 +532,38 @@ impl<'a> Translator<'a> {
     }
 
     fn expr(&mut self, e: &Loc<ast::Expression>) -> Result<Id<hir::Expression>> {
+        let mut args_loc = None;
         let expr = match &e.value {
+            // parentheses only change the precedence, the expression in them
+            // gets the span with the outermost ones as well
+            ast::Expression::Parenthesized(inner) => {
+                let id = self.expr(inner)?;
+                self.ctx.parenthesized_fcs.insert(id, e.loc);
+                return Ok(id);
+            }
             ast::Expression::Literal(l) => {
                 let lit = match l {
-                    ast::Literal::Integer(i) => hir::Literal::Integer(*i),
-                    ast::Literal::Float(f) => hir::Literal::Float(*f),
+                    ast::Literal::Integer(Some(i), suffix) => {
+                        hir::Literal::Integer(*i, suffix.as_ref().map(literal_suffix))
+                    }
+                    ast::Literal::Integer(None, _) => {
+                        self.errs
+                            .push(Error::IntegerLiteralTooLarge { literal: e.loc });
+                        return Err(());
+                    }
+                    ast::Literal::Float(f, suffix) => {
+                        hir::Literal::Float(*f, suffix.as_ref().map(literal_suffix))
+                    }
+                    ast::Literal::Bool(b) => hir::Literal::Bool(*b),
+                    ast::Literal::String(s) => {
+                        hir::Literal::String(self.ctx.strings.alloc(s.clone()))
+                    }
                 };
                 hir::Expression::Literal(lit)
             }
             ast::Expression::Variable(name) => {
-                let id = self.ident_loc(name, e.loc);
+                let name = self.resolve(&[Namespace::Constant], name, e.loc);
+                let id = self.ident_loc(&name, e.loc);
                 hir::Expression::Variable(id)
             }
             ast::Expression::PrimitiveTypeConstructor(_) => {
@@ -299,7 +571,12 @@ impl<'a> Translator<'a> {
                     .push(Error::TypeConstructorInInvalidPosition { where_: e.loc });
                 return Err(());
             }
-            ast::Expression::Call { base, args } => {
+            ast::Expression::Call {
+                base,
+                args,
+                args_loc: loc,
+            } => {
+                args_loc = Some(*loc);
                 let mut pos_args = vec![];
                 let mut nam_args = vec![];
 
@@ -329,9 +606,12 @@ impl<'a> Translator<'a> {
                     }
                 }
 
-                match &base.value {
+                let callee = base.unparenthesized();
+                match &callee.value {
                     ast::Expression::Variable(v) => {
-                        let name = self.ident_loc(v, base.loc);
+                        let namespaces = [Namespace::Function, Namespace::Type];
+                        let v = self.resolve_item(&namespaces, v, callee.loc);
+                        let name = self.ident_loc(&v, callee.loc);
                         hir::Expression::Call {
                             name,
                             pos_args,
@@ -346,20 +626,26 @@ impl<'a> Translator<'a> {
                         };
 
                         let prim_id = self.ctx.prim_ops.alloc(prim);
-                        self.ctx.prim_op_fcs.insert(prim_id, base.loc);
+                        self.ctx.prim_op_fcs.insert(prim_id, callee.loc);
                         hir::Expression::PrimitiveOp(prim_id)
                     }
                     _ => {
                         self.errs.push(Error::CallOnNonFunction {
                             item: e.loc,
-                            base: base.loc,
+                            base: callee.loc,
                         });
                         return Err(());
                     }
                 }
             }
-            ast::Expression::DotCall { base, name, args } => {
-                let name = self.ident(name);
+            ast::Expression::DotCall {
+                base,
+                name,
+                args,
+                args_loc: loc,
+            } => {
+                args_loc = Some(*loc);
+                let name = self.item_ident(Namespace::Function, name);
                 let mut pos_args = vec![self.expr(base)?];
                 let mut nam_args = vec![];
 
@@ -442,14 +728,40 @@ impl<'a> Translator<'a> {
                 let index = self.expr(index)?;
                 hir::Expression::Index { base, index }
             }
+            ast::Expression::Slice { base, lo, hi } => {
+                let base = self.expr(base)?;
+                let lo = self.expr(lo)?;
+                let hi = self.expr(hi)?;
+                hir::Expression::Slice { base, lo, hi }
+            }
             ast::Expression::As { base, ty } => {
                 let base = self.expr(base)?;
                 let ty = self.type_reference(ty);
                 hir::Expression::As { base, ty }
             }
+            ast::Expression::LayoutQuery { query, ty, rules } => {
+                let query = match query {
+                    ast::LayoutQuery::Size => hir::LayoutQuery::Size,
+                    ast::LayoutQuery::Align => hir::LayoutQuery::Align,
+                    ast::LayoutQuery::Offset(field) => hir::LayoutQuery::Offset(self.ident(field)),
+                };
+                let ty = self.type_reference(ty);
+                let rules = rules.as_ref().map(|rules| self.ident(rules));
+                hir::Expression::LayoutQuery { query, ty, rules }
+            }
+            ast::Expression::Array(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.expr(element))
+                    .collect::<Result<_>>()?;
+                hir::Expression::Array(elements)
+            }
         };
         let id = self.ctx.expressions.alloc(expr);
         self.ctx.expression_fcs.insert(id, e.loc);
+        if let Some(loc) = args_loc {
+            self.ctx.argument_list_fcs.insert(id, loc);
+        }
         Ok(id)
     }
 
@@ -459,16 +771,56 @@ impl<'a> Translator<'a> {
                 hir::TypeReference::Primitive(self.prim_type(&prim.value))
             }
             ast::TypeReference::Named { name, generics } => hir::TypeReference::Named {
-                name: self.ident(&name),
+                name: {
+                    let resolved = self.resolve(&[Namespace::Type], &name.value, name.loc);
+                    self.ident_loc(&resolved, name.loc)
+                },
                 generics: generics.iter().map(|g| self.type_reference(g)).collect(),
             },
             ast::TypeReference::Array { base, size } => hir::TypeReference::Array {
                 base: self.type_reference(base),
-                size: size.value,
+                size: match &size.unparenthesized().value {
+                    ast::Expression::Literal(ast::Literal::Integer(Some(size), None)) => {
+                        hir::ArraySize::Literal(*size as usize)
+                    }
+                    // the error is reported, the size doesn't matter
+                    _ => self
+                        .expr(size)
+                        .map_or(hir::ArraySize::Literal(0), hir::ArraySize::Expression),
+                },
             },
             ast::TypeReference::OpenArray { base } => {
                 hir::TypeReference::OpenArray(self.type_reference(base))
             }
+            ast::TypeReference::Normalized { base } => {
+                hir::TypeReference::Normalized(self.type_reference(base))
+            }
+            ast::TypeReference::Optional { base } => {
+                hir::TypeReference::Optional(self.type_reference(base))
+            }
+            ast::TypeReference::Record { fields } => hir::TypeReference::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, ty)| (self.ident(name), self.type_reference(ty)))
+                    .collect(),
+            },
+            ast::TypeReference::Image {
+                dim,
+                format,
+                access,
+            } => hir::TypeReference::Image {
+                dim: image_dim(*dim),
+                format: self.ident(format),
+                access: self.ident(access),
+            },
+            ast::TypeReference::Texture { dim, sampled } => hir::TypeReference::Texture {
+                dim: texture_dim(*dim),
+                sampled: sampled.as_ref().map(|ty| self.type_reference(ty)),
+            },
+            ast::TypeReference::Sampler { comparison } => hir::TypeReference::Sampler {
+                comparison: *comparison,
+            },
+            ast::TypeReference::AccelerationStructure => hir::TypeReference::AccelerationStructure,
         };
         let id = self.ctx.type_refs.alloc(hir_ty);
         self.ctx.type_ref_fcs.insert(id, ty.loc);
@@ -476,17 +828,73 @@ impl<'a> Translator<'a> {
     }
 
     fn ident(&mut self, ident: &Loc<ast::Identifier>) -> Id<hir::Identifier> {
-        let id = self.ctx.identifiers.alloc(ident.value.clone());
-        self.ctx.identifier_fcs.insert(id, ident.loc);
-        id
+        self.ident_loc(&ident.value, ident.loc)
     }
 
+    /// An identifier at `loc`, a name written as a path is at its last name.
     fn ident_loc(&mut self, ident: &str, loc: FileLocation) -> Id<hir::Identifier> {
         let id = self.ctx.identifiers.alloc(ident.to_string());
-        self.ctx.identifier_fcs.insert(id, loc);
+        match self.path_names.get(&loc) {
+            Some(&name) => {
+                self.ctx.identifier_fcs.insert(id, name);
+                self.ctx.path_fcs.insert(id, loc);
+            }
+            None => {
+                self.ctx.identifier_fcs.insert(id, loc);
+            }
+        }
         id
     }
 
+    /// The name of an item declared in the current module.
+    fn declared_ident(&mut self, ident: &Loc<ast::Identifier>) -> Id<hir::Identifier> {
+        let name = namespaces::qualify(&self.module, &ident.value);
+        self.ident_loc(&name, ident.loc)
+    }
+
+    /// A name that refers to an item, qualified with its module.
+    fn item_ident(
+        &mut self,
+        namespace: Namespace,
+        ident: &Loc<ast::Identifier>,
+    ) -> Id<hir::Identifier> {
+        let name = self.resolve_item(&[namespace], &ident.value, ident.loc);
+        self.ident_loc(&name, ident.loc)
+    }
+
+    /// The qualified name of a name used in the current module, locals hide
+    /// items. Names that refer to nothing are kept.
+    fn resolve(&mut self, namespaces: &[Namespace], name: &str, loc: FileLocation) -> String {
+        if self.locals.iter().any(|scope| scope.contains(name)) {
+            return name.to_string();
+        }
+        self.resolve_item(namespaces, name, loc)
+    }
+
+    fn resolve_item(&mut self, namespaces: &[Namespace], name: &str, loc: FileLocation) -> String {
+        let resolved = namespaces
+            .iter()
+            .find_map(|ns| self.declarations.resolve(*ns, &self.module, name));
+        let resolved = match resolved {
+            Some(resolved) => resolved,
+            None => return name.to_string(),
+        };
+        // the first step that isn't accessible is reported
+        let private = resolved
+            .steps
+            .iter()
+            .find(|step| !namespaces::accessible(step.visibility, &step.name, &step.from));
+        if let Some(step) = private {
+            self.errs.push(Error::PrivateItem {
+                kind: step.kind,
+                name: step.name.clone(),
+                reference: loc,
+                declaration: step.loc,
+            });
+        }
+        resolved.qualified
+    }
+
     fn branches(
         &mut self,
         br: &[(Loc<ast::Expression>, ast::Block)],
@@ -540,7 +948,10 @@ impl<'a> Translator<'a> {
     }
 
     fn block(&mut self, block: &[Loc<ast::Statement>]) -> Result<Vec<Id<hir::Statement>>> {
-        block.iter().map(|s| self.statement(s)).collect()
+        self.locals.push(HashSet::new());
+        let block = block.iter().map(|s| self.statement(s)).collect();
+        self.locals.pop();
+        block
     }
 
     fn prim_type(&mut self, p: &ast::PrimitiveType) -> hir::PrimitiveType {
@@ -548,8 +959,15 @@ impl<'a> Translator<'a> {
             ast::PrimitiveType::Bool => hir::PrimitiveType::Bool,
             ast::PrimitiveType::Int => hir::PrimitiveType::Int,
             ast::PrimitiveType::UInt => hir::PrimitiveType::UInt,
+            ast::PrimitiveType::Long => hir::PrimitiveType::Long,
+            ast::PrimitiveType::ULong => hir::PrimitiveType::ULong,
             ast::PrimitiveType::Float => hir::PrimitiveType::Float,
             ast::PrimitiveType::Double => hir::PrimitiveType::Double,
+            ast::PrimitiveType::Half => hir::PrimitiveType::Half,
+            ast::PrimitiveType::Radians => hir::PrimitiveType::Radians,
+            ast::PrimitiveType::Degrees => hir::PrimitiveType::Degrees,
+            ast::PrimitiveType::AtomicInt => hir::PrimitiveType::AtomicInt,
+            ast::PrimitiveType::AtomicUInt => hir::PrimitiveType::AtomicUInt,
             ast::PrimitiveType::BoolVec { components } => hir::PrimitiveType::BoolVec {
                 components: vec_size(components),
             },
@@ -560,7 +978,7 @@ impl<'a> Translator<'a> {
             } => hir::PrimitiveType::IntVec {
                 components: vec_size(components),
                 vtype: vtype.as_ref().map(|s| self.vec_type(s)),
-                space: space.as_ref().map(|i| self.ident(i)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
             },
             ast::PrimitiveType::UIntVec {
                 components,
@@ -569,7 +987,25 @@ impl<'a> Translator<'a> {
             } => hir::PrimitiveType::UIntVec {
                 components: vec_size(components),
                 vtype: vtype.as_ref().map(|s| self.vec_type(s)),
-                space: space.as_ref().map(|i| self.ident(i)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
+            },
+            ast::PrimitiveType::LongVec {
+                components,
+                vtype,
+                space,
+            } => hir::PrimitiveType::LongVec {
+                components: vec_size(components),
+                vtype: vtype.as_ref().map(|s| self.vec_type(s)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
+            },
+            ast::PrimitiveType::ULongVec {
+                components,
+                vtype,
+                space,
+            } => hir::PrimitiveType::ULongVec {
+                components: vec_size(components),
+                vtype: vtype.as_ref().map(|s| self.vec_type(s)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
             },
             ast::PrimitiveType::FloatVec {
                 components,
@@ -578,7 +1014,7 @@ impl<'a> Translator<'a> {
             } => hir::PrimitiveType::FloatVec {
                 components: vec_size(components),
                 vtype: vtype.as_ref().map(|s| self.vec_type(s)),
-                space: space.as_ref().map(|i| self.ident(i)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
             },
             ast::PrimitiveType::DoubleVec {
                 components,
@@ -587,7 +1023,16 @@ impl<'a> Translator<'a> {
             } => hir::PrimitiveType::DoubleVec {
                 components: vec_size(components),
                 vtype: vtype.as_ref().map(|s| self.vec_type(s)),
-                space: space.as_ref().map(|i| self.ident(i)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
+            },
+            ast::PrimitiveType::HalfVec {
+                components,
+                vtype,
+                space,
+            } => hir::PrimitiveType::HalfVec {
+                components: vec_size(components),
+                vtype: vtype.as_ref().map(|s| self.vec_type(s)),
+                space: space.as_ref().map(|i| self.item_ident(Namespace::Space, i)),
             },
             ast::PrimitiveType::FloatMat {
                 cols,
@@ -596,9 +1041,12 @@ impl<'a> Translator<'a> {
             } => hir::PrimitiveType::FloatMat {
                 cols: vec_size(cols),
                 rows: vec_size(rows),
-                transform: transform
-                    .as_ref()
-                    .map(|(a, b)| (self.ident(a), self.ident(b))),
+                transform: transform.as_ref().map(|(a, b)| {
+                    (
+                        self.item_ident(Namespace::Space, a),
+                        self.item_ident(Namespace::Space, b),
+                    )
+                }),
             },
             ast::PrimitiveType::DoubleMat {
                 cols,
@@ -607,9 +1055,15 @@ impl<'a> Translator<'a> {
             } => hir::PrimitiveType::DoubleMat {
                 cols: vec_size(cols),
                 rows: vec_size(rows),
-                transform: transform
-                    .as_ref()
-                    .map(|(a, b)| (self.ident(a), self.ident(b))),
+                transform: transform.as_ref().map(|(a, b)| {
+                    (
+                        self.item_ident(Namespace::Space, a),
+                        self.item_ident(Namespace::Space, b),
+                    )
+                }),
+            },
+            ast::PrimitiveType::Packed { format } => hir::PrimitiveType::Packed {
+                format: packed_format(format),
             },
         }
     }
@@ -619,6 +1073,7 @@ impl<'a> Translator<'a> {
             ast::VecType::Point => hir::VecType::Point,
             ast::VecType::Vector => hir::VecType::Vector,
             ast::VecType::Colour => hir::VecType::Colour,
+            ast::VecType::SrgbColour => hir::VecType::SrgbColour,
         };
         let id = self.ctx.vec_types.alloc(hir_ty);
         self.ctx.vec_type_fcs.insert(id, ty.loc);
@@ -633,3 +1088,156 @@ fn vec_size(components: &ast::VecSize) -> hir::VecSize {
         ast::VecSize::VS4 => hir::VecSize::VS4,
     }
 }
+
+fn literal_suffix(suffix: &ast::LiteralSuffix) -> hir::LiteralSuffix {
+    match suffix {
+        ast::LiteralSuffix::Int => hir::LiteralSuffix::Int,
+        ast::LiteralSuffix::UInt => hir::LiteralSuffix::UInt,
+        ast::LiteralSuffix::Long => hir::LiteralSuffix::Long,
+        ast::LiteralSuffix::ULong => hir::LiteralSuffix::ULong,
+        ast::LiteralSuffix::Float => hir::LiteralSuffix::Float,
+        ast::LiteralSuffix::Double => hir::LiteralSuffix::Double,
+        ast::LiteralSuffix::Radians => hir::LiteralSuffix::Radians,
+        ast::LiteralSuffix::Degrees => hir::LiteralSuffix::Degrees,
+    }
+}
+
+fn texture_dim(dim: ast::TextureDim) -> hir::TextureDim {
+    match dim {
+        ast::TextureDim::D1 => hir::TextureDim::D1,
+        ast::TextureDim::D2 => hir::TextureDim::D2,
+        ast::TextureDim::D3 => hir::TextureDim::D3,
+        ast::TextureDim::Cube => hir::TextureDim::Cube,
+        ast::TextureDim::D2Array => hir::TextureDim::D2Array,
+    }
+}
+
+fn image_dim(dim: ast::ImageDim) -> hir::ImageDim {
+    match dim {
+        ast::ImageDim::D1 => hir::ImageDim::D1,
+        ast::ImageDim::D2 => hir::ImageDim::D2,
+        ast::ImageDim::D3 => hir::ImageDim::D3,
+        ast::ImageDim::D2Array => hir::ImageDim::D2Array,
+    }
+}
+
+fn packed_format(format: &ast::PackedFormat) -> hir::PackedFormat {
+    match format {
+        ast::PackedFormat::Unorm8x4 => hir::PackedFormat::Unorm8x4,
+        ast::PackedFormat::Snorm8x4 => hir::PackedFormat::Snorm8x4,
+        ast::PackedFormat::Uint8x4 => hir::PackedFormat::Uint8x4,
+        ast::PackedFormat::Sint8x4 => hir::PackedFormat::Sint8x4,
+        ast::PackedFormat::Unorm16x2 => hir::PackedFormat::Unorm16x2,
+        ast::PackedFormat::Snorm16x2 => hir::PackedFormat::Snorm16x2,
+        ast::PackedFormat::Float16x2 => hir::PackedFormat::Float16x2,
+        ast::PackedFormat::Float16x4 => hir::PackedFormat::Float16x4,
+        ast::PackedFormat::Rgb10a2 => hir::PackedFormat::Rgb10a2,
+    }
+}
+
+fn visibility(vis: ast::Visibility) -> hir::Visibility {
+    match vis {
+        ast::Visibility::Private => hir::Visibility::Private,
+        ast::Visibility::Public => hir::Visibility::Public,
+    }
+}
+
+fn param_mode(mode: ast::ParamMode) -> hir::ParamMode {
+    match mode {
+        ast::ParamMode::In => hir::ParamMode::In,
+        ast::ParamMode::Out => hir::ParamMode::Out,
+        ast::ParamMode::InOut => hir::ParamMode::InOut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hir::{Node, Part};
+
+    const SOURCE: &str = "@profile(\"fog\")
+function fog(depth: float) returns float
+begin
+    var d: float := ((depth) * 2.0);
+    return min(d , 1.0);
+end
+";
+
+    fn lowered() -> (hir::Context, hir::Module) {
+        let file = thiol_syntax::parser::parse_file(0, SOURCE).unwrap();
+        let mut ctx = hir::Context::default();
+        let module = lower(&mut ctx, &file).unwrap();
+        (ctx, module)
+    }
+
+    fn text(loc: Option<FileLocation>) -> &'static str {
+        let loc = loc.unwrap();
+        &SOURCE[loc.start..loc.end]
+    }
+
+    #[test]
+    fn spans_of_declarations() {
+        let (ctx, module) = lowered();
+        let fog = Node::Function(module.functions[0]);
+        assert_eq!(text(ctx.part_span(fog, Part::Name)), "fog");
+        assert_eq!(
+            text(ctx.part_span(fog, Part::Attributes)),
+            "@profile(\"fog\")"
+        );
+        assert_eq!(text(ctx.part_span(fog, Part::Type)), "float");
+        assert_eq!(ctx.part_span(fog, Part::Arguments), None);
+
+        let stmt = ctx.functions[module.functions[0]].body[0];
+        let def = match &ctx.statements[stmt] {
+            hir::Statement::Var(def) => *def,
+            stmt => panic!("expected a variable, found {:?}", stmt),
+        };
+        assert_eq!(text(ctx.part_span(Node::VariableDef(def), Part::Name)), "d");
+        assert_eq!(
+            text(ctx.part_span(Node::VariableDef(def), Part::Type)),
+            "float"
+        );
+        assert_eq!(
+            text(ctx.part_span(Node::Statement(stmt), Part::Value)),
+            "((depth) * 2.0)"
+        );
+    }
+
+    #[test]
+    fn spans_of_expressions() {
+        let (ctx, module) = lowered();
+        let body = &ctx.functions[module.functions[0]].body;
+
+        let value = match &ctx.statements[body[0]] {
+            hir::Statement::Var(def) => ctx.variable_defs[*def].rhs.unwrap(),
+            stmt => panic!("expected a variable, found {:?}", stmt),
+        };
+        assert_eq!(
+            text(ctx.full_span(Node::Expression(value))),
+            "((depth) * 2.0)"
+        );
+        assert_eq!(text(Some(ctx.expression_fcs[&value])), "(depth) * 2.0");
+        let depth = match &ctx.expressions[value] {
+            hir::Expression::PrimitiveOp(op) => match &ctx.prim_ops[*op] {
+                hir::PrimitiveOp::Mul(lhs, _) => *lhs,
+                op => panic!("expected a product, found {:?}", op),
+            },
+            expr => panic!("expected an operator, found {:?}", expr),
+        };
+        assert_eq!(text(ctx.full_span(Node::Expression(depth))), "(depth)");
+
+        let call = match &ctx.statements[body[1]] {
+            hir::Statement::Return(Some(call)) => *call,
+            stmt => panic!("expected a return, found {:?}", stmt),
+        };
+        assert_eq!(text(ctx.full_span(Node::Expression(call))), "min(d , 1.0)");
+        assert_eq!(
+            text(ctx.part_span(Node::Expression(call), Part::Arguments)),
+            "(d , 1.0)"
+        );
+        assert_eq!(
+            text(ctx.part_span(Node::Expression(call), Part::Name)),
+            "min"
+        );
+    }
+}
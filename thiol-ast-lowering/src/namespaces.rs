@@ -0,0 +1,415 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Items declared in modules.
+//!
+//! Modules don't exist in the HIR: the items in them are lowered into the
+//! module of the file with their qualified name, like `geometry::Ray`.
+//! Names used in a module are qualified while lowering, a name refers to the
+//! item declared in the innermost module around it that has an item with
+//! that name, or else to the item of the file. Locals and generic parameters
+//! are never qualified.
+//!
+//! Items are private unless they are declared `pub`: a private item can be
+//! used in its module and the modules in it, but not outside of it.
+//!
+//! `use geometry::Ray;` in a module names the item `Ray` of `geometry` in
+//! it, as if it was declared there, and `pub use` re-exports it from the
+//! module. A name that no module around it declares is looked up in the
+//! preludes of these modules last, the innermost first, so a library can
+//! gather the items it exports in a module of re-exports that is used with
+//! `prelude`.
+
+use std::collections::{HashMap, HashSet};
+
+use thiol_syntax::{ast, FileLocation, Loc};
+
+/// How many imports a name is followed through, imports that import each
+/// other refer to nothing
+const MAX_IMPORTS: usize = 16;
+
+/// The kinds of items that are looked up separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Namespace {
+    Type,
+    Constant,
+    Function,
+    Space,
+}
+
+impl Namespace {
+    pub(crate) const ALL: &'static [Namespace] = &[
+        Namespace::Type,
+        Namespace::Constant,
+        Namespace::Function,
+        Namespace::Space,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Namespace::Type => "type",
+            Namespace::Constant => "constant",
+            Namespace::Function => "function",
+            Namespace::Space => "space",
+        }
+    }
+}
+
+/// An item declared in a module
+#[derive(Debug, Clone, Copy)]
+struct Declaration {
+    visibility: ast::Visibility,
+    loc: FileLocation,
+}
+
+/// A `use` declaration, by the name it declares
+#[derive(Debug, Clone)]
+struct Import {
+    /// the path as written
+    target: String,
+    /// the module the path is looked up from
+    module: Vec<String>,
+    visibility: ast::Visibility,
+    loc: FileLocation,
+}
+
+/// The items of a file, by their qualified names
+#[derive(Debug, Default)]
+pub(crate) struct Declarations {
+    items: HashMap<(Namespace, String), Declaration>,
+    modules: HashSet<String>,
+    imports: HashMap<String, Import>,
+    /// the paths of the preludes of each module, as written
+    preludes: HashMap<String, Vec<String>>,
+}
+
+/// An item or import a name is resolved through, which has to be accessible
+/// from the module it is used in
+#[derive(Debug, Clone)]
+pub(crate) struct Step {
+    /// `import`, or the kind of the item
+    pub(crate) kind: &'static str,
+    pub(crate) name: String,
+    pub(crate) visibility: ast::Visibility,
+    pub(crate) loc: FileLocation,
+    /// the module the name is used in
+    pub(crate) from: Vec<String>,
+}
+
+/// The item a name refers to
+#[derive(Debug, Clone)]
+pub(crate) struct Resolution {
+    pub(crate) qualified: String,
+    /// the imports followed, then the item
+    pub(crate) steps: Vec<Step>,
+}
+
+impl Declarations {
+    pub(crate) fn collect(items: &[ast::Item]) -> Self {
+        let mut decls = Declarations::default();
+        decls.add_items(&[], items);
+        decls
+    }
+
+    fn add_items(&mut self, module: &[String], items: &[ast::Item]) {
+        for item in items {
+            match item {
+                ast::Item::Function(f) => self.add(
+                    Namespace::Function,
+                    module,
+                    &f.value.name,
+                    f.value.visibility,
+                ),
+                ast::Item::Consts(c) => {
+                    for var in &c.value.vars {
+                        let vis = var.value.visibility;
+                        self.add(Namespace::Constant, module, &var.value.name, vis);
+                    }
+                }
+                ast::Item::Types(t) => {
+                    for ty in &t.value.types {
+                        self.add(Namespace::Type, module, &ty.value.name, ty.value.visibility);
+                    }
+                }
+                ast::Item::Space(s) => {
+                    self.add(Namespace::Space, module, &s.value.name, s.value.visibility)
+                }
+                ast::Item::Program(_) | ast::Item::StaticAssert(_) => {}
+                ast::Item::Module(m) => {
+                    let mut inner = module.to_vec();
+                    inner.push(m.value.name.value.clone());
+                    self.modules.insert(qualify(module, &m.value.name.value));
+                    self.add_items(&inner, &m.value.items);
+                }
+                ast::Item::Use(u) => self.add_import(module, &u.value, u.loc),
+                ast::Item::Prelude(p) => self
+                    .preludes
+                    .entry(module.join("::"))
+                    .or_default()
+                    .push(p.value.module.value.clone()),
+            }
+        }
+    }
+
+    fn add(
+        &mut self,
+        namespace: Namespace,
+        module: &[String],
+        name: &Loc<ast::Identifier>,
+        visibility: ast::Visibility,
+    ) {
+        let decl = Declaration {
+            visibility,
+            loc: name.loc,
+        };
+        self.items
+            .insert((namespace, qualify(module, &name.value)), decl);
+    }
+
+    fn add_import(&mut self, module: &[String], use_: &ast::UseDeclaration, loc: FileLocation) {
+        let target = &use_.path.value;
+        let name = target.rsplit("::").next().unwrap_or(target);
+        let import = Import {
+            target: target.clone(),
+            module: module.to_vec(),
+            visibility: use_.visibility,
+            loc,
+        };
+        self.imports.insert(qualify(module, name), import);
+    }
+
+    /// The item `name` refers to in `module`, `None` if no item has that
+    /// name.
+    pub(crate) fn resolve(
+        &self,
+        namespace: Namespace,
+        module: &[String],
+        name: &str,
+    ) -> Option<Resolution> {
+        self.lookup(namespace, module, name, module, MAX_IMPORTS)
+            .or_else(|| {
+                self.preludes_of(module).into_iter().find_map(|prelude| {
+                    let qualified = qualify(&prelude, name);
+                    self.path(namespace, &qualified, module, MAX_IMPORTS)
+                })
+            })
+    }
+
+    /// The qualified name of the item the path of a `use` in `module` refers
+    /// to, `None` if it refers to nothing.
+    pub(crate) fn import(&self, module: &[String], path: &str) -> Option<String> {
+        Namespace::ALL.iter().find_map(|ns| {
+            self.lookup(*ns, module, path, module, MAX_IMPORTS)
+                .map(|res| res.qualified)
+        })
+    }
+
+    /// The module the path of a `prelude` in `module` refers to.
+    pub(crate) fn module(&self, module: &[String], path: &str) -> Option<Vec<String>> {
+        (0..=module.len())
+            .rev()
+            .map(|depth| qualify(&module[..depth], path))
+            .find(|qualified| self.modules.contains(qualified))
+            .map(|qualified| qualified.split("::").map(str::to_string).collect())
+    }
+
+    /// The modules of the preludes used in `module`, the innermost first.
+    fn preludes_of(&self, module: &[String]) -> Vec<Vec<String>> {
+        (0..=module.len())
+            .rev()
+            .flat_map(|depth| {
+                let scope = &module[..depth];
+                let paths = self.preludes.get(&scope.join("::"));
+                paths
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |path| self.module(scope, path))
+            })
+            .collect()
+    }
+
+    /// Look `name` up in `scope` and the modules around it, for a name used
+    /// in `from`.
+    fn lookup(
+        &self,
+        namespace: Namespace,
+        scope: &[String],
+        name: &str,
+        from: &[String],
+        imports: usize,
+    ) -> Option<Resolution> {
+        (0..=scope.len()).rev().find_map(|depth| {
+            let qualified = qualify(&scope[..depth], name);
+            self.path(namespace, &qualified, from, imports)
+        })
+    }
+
+    /// The item with the qualified name, or that an import with that name
+    /// refers to.
+    fn path(
+        &self,
+        namespace: Namespace,
+        qualified: &str,
+        from: &[String],
+        imports: usize,
+    ) -> Option<Resolution> {
+        if let Some(decl) = self.items.get(&(namespace, qualified.to_string())) {
+            return Some(Resolution {
+                qualified: qualified.to_string(),
+                steps: vec![Step {
+                    kind: namespace.name(),
+                    name: qualified.to_string(),
+                    visibility: decl.visibility,
+                    loc: decl.loc,
+                    from: from.to_vec(),
+                }],
+            });
+        }
+
+        let import = self.imports.get(qualified)?;
+        let imports = imports.checked_sub(1)?;
+        // `use Ray;` in a module names the `Ray` around it, not itself
+        let module = &import.module;
+        let mut res = (0..=module.len()).rev().find_map(|depth| {
+            let target = qualify(&module[..depth], &import.target);
+            (target != qualified)
+                .then(|| self.path(namespace, &target, module, imports))
+                .flatten()
+        })?;
+        res.steps.insert(
+            0,
+            Step {
+                kind: "import",
+                name: qualified.to_string(),
+                visibility: import.visibility,
+                loc: import.loc,
+                from: from.to_vec(),
+            },
+        );
+        Some(res)
+    }
+}
+
+/// Whether the item named `qualified` with `visibility` can be used in
+/// `module`.
+pub(crate) fn accessible(visibility: ast::Visibility, qualified: &str, module: &[String]) -> bool {
+    let path = qualified.split("::").collect::<Vec<_>>();
+    let declared_in = &path[..path.len() - 1];
+    visibility == ast::Visibility::Public
+        || (declared_in.len() <= module.len()
+            && declared_in.iter().zip(module).all(|(a, b)| a == b))
+}
+
+/// The name of an item declared in `module`
+pub(crate) fn qualify(module: &[String], name: &str) -> String {
+    module
+        .iter()
+        .map(String::as_str)
+        .chain(Some(name))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOC: FileLocation = FileLocation {
+        file: 0,
+        start: 0,
+        end: 0,
+    };
+
+    fn module(path: &[&str]) -> Vec<String> {
+        path.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn name(name: &str) -> Loc<String> {
+        Loc::new(LOC, name.to_string())
+    }
+
+    fn resolved(
+        decls: &Declarations,
+        ns: Namespace,
+        module: &[String],
+        name: &str,
+    ) -> Option<String> {
+        decls.resolve(ns, module, name).map(|res| res.qualified)
+    }
+
+    #[test]
+    fn innermost_declaration() {
+        let mut decls = Declarations::default();
+        let vis = ast::Visibility::Private;
+        decls.add(Namespace::Type, &module(&[]), &name("Ray"), vis);
+        decls.add(Namespace::Type, &module(&["geometry"]), &name("Ray"), vis);
+        decls.add(Namespace::Type, &module(&["geometry"]), &name("Hit"), vis);
+
+        let inner = module(&["geometry", "bvh"]);
+        assert_eq!(
+            resolved(&decls, Namespace::Type, &inner, "Ray").as_deref(),
+            Some("geometry::Ray")
+        );
+        assert_eq!(
+            resolved(&decls, Namespace::Type, &[], "Ray").as_deref(),
+            Some("Ray")
+        );
+        assert_eq!(
+            resolved(&decls, Namespace::Type, &inner, "geometry::Hit").as_deref(),
+            Some("geometry::Hit")
+        );
+        assert_eq!(resolved(&decls, Namespace::Constant, &inner, "Ray"), None);
+    }
+
+    #[test]
+    fn imports_and_preludes() {
+        let mut decls = Declarations::default();
+        let vis = ast::Visibility::Public;
+        decls.add(Namespace::Type, &module(&["geometry"]), &name("Ray"), vis);
+        decls.modules.insert("geometry".to_string());
+        decls.modules.insert("lib".to_string());
+        let reexport = ast::UseDeclaration {
+            visibility: vis,
+            path: name("geometry::Ray"),
+        };
+        decls.add_import(&module(&["lib"]), &reexport, LOC);
+        let cycle = ast::UseDeclaration {
+            visibility: vis,
+            path: name("Loop"),
+        };
+        decls.add_import(&module(&["lib"]), &cycle, LOC);
+        decls
+            .preludes
+            .insert(String::new(), vec!["lib".to_string()]);
+
+        let res = decls.resolve(Namespace::Type, &[], "lib::Ray").unwrap();
+        assert_eq!(res.qualified, "geometry::Ray");
+        let kinds = res.steps.iter().map(|s| s.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, ["import", "type"]);
+        assert_eq!(
+            resolved(&decls, Namespace::Type, &module(&["shading"]), "Ray").as_deref(),
+            Some("geometry::Ray")
+        );
+        assert_eq!(resolved(&decls, Namespace::Type, &[], "Loop"), None);
+        assert_eq!(
+            decls.import(&module(&["lib"]), "geometry::Ray").as_deref(),
+            Some("geometry::Ray")
+        );
+        assert_eq!(decls.import(&module(&["lib"]), "Loop"), None);
+    }
+
+    #[test]
+    fn private_items() {
+        use ast::Visibility::*;
+
+        let geometry = ["geometry".to_string()];
+        let bvh = ["geometry".to_string(), "bvh".to_string()];
+        assert!(accessible(Private, "Ray", &[]));
+        assert!(accessible(Private, "geometry::Ray", &geometry));
+        assert!(accessible(Private, "geometry::Ray", &bvh));
+        assert!(!accessible(Private, "geometry::Ray", &[]));
+        assert!(!accessible(Private, "geometry::bvh::Node", &geometry));
+        assert!(accessible(Public, "geometry::bvh::Node", &[]));
+    }
+}
@@ -0,0 +1,384 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Events for engines that swap pipelines while they are running.
+//!
+//! Whenever a module is compiled again, every entry point in its artifacts
+//! becomes an [`ArtifactEvent`] with the code of the entry point and the
+//! buffers it binds. Events are sent between processes in the format of
+//! [`ArtifactEvent::encode`]: a little endian `u32` with the length of the
+//! rest of the event, then the fields in order. Strings and byte strings are
+//! written as a `u32` length followed by the bytes, the resources as a `u32`
+//! count followed by the name, class, set, binding, a byte that is 1 if
+//! the resource is written, the format of storage images and the type name
+//! of textures and samplers, see [`TextureResource::type_name`]. Push
+//! constants have the set and binding `u32::MAX`, programs without a stage
+//! the stage `""` and other resources the format or type name `""`. The
+//! resources are
+//! followed by a `u32` count of mangled names, each written as the name in
+//! the code followed by the name in the source, and a `u32` count of
+//! profiling labels, each written as the label followed by the source name
+//! of the function or program that has it.
+
+use std::convert::TryInto;
+
+use thiol_typeck as typeck;
+
+use typeck::{BufferClass, Callable, ImageFormat, Stage, TextureResource};
+
+use crate::mangle::MangledName;
+use crate::{Artifact, Input};
+
+/// The new code of an entry point
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactEvent {
+    /// name of the module the entry point is declared in
+    pub module: String,
+    pub entry_point: String,
+    pub stage: Option<Stage>,
+    /// name of the artifact the code is in, an artifact can contain several
+    /// entry points
+    pub artifact: String,
+    pub bytes: Vec<u8>,
+    pub reflection: Reflection,
+}
+
+/// What a pipeline needs to know about an entry point to bind its resources
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reflection {
+    pub resources: Vec<Resource>,
+    /// the source names of the items named in the code
+    pub names: Vec<MangledName>,
+    /// the profiling labels of the entry point and the functions it calls
+    pub profile_labels: Vec<ProfileLabel>,
+}
+
+/// A function or program with a `@profile` attribute, see
+/// [`typeck::profiling`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileLabel {
+    pub label: String,
+    /// the name of the function or program in the source
+    pub source: String,
+}
+
+/// A buffer, storage image, texture or sampler an entry point accesses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    pub name: String,
+    /// the class of the attribute the resource is bound with, textures and
+    /// samplers are bound like uniform buffers
+    pub class: BufferClass,
+    /// set and binding, `None` for push constants
+    pub binding: Option<(u32, u32)>,
+    pub written: bool,
+    /// the texel format of a storage image, `None` for other resources
+    pub format: Option<ImageFormat>,
+    /// the dimensions and sampled type of a texture or the kind of a
+    /// sampler, `None` for other resources
+    pub texture: Option<TextureResource>,
+}
+
+/// The events for the artifacts a backend produced for a module.
+pub fn events(input: &Input<'_>, artifacts: &[Artifact]) -> Vec<ArtifactEvent> {
+    let mut events = vec![];
+    for artifact in artifacts {
+        for entry_point in &artifact.entry_points {
+            let program = input
+                .module
+                .programs
+                .iter()
+                .copied()
+                .find(|id| input.hir.identifiers[input.hir.programs[*id].name] == *entry_point);
+            let program = match program {
+                Some(program) => program,
+                None => continue,
+            };
+            let resources = input
+                .resources(program)
+                .into_iter()
+                .map(|usage| Resource {
+                    name: usage.name,
+                    class: usage.class,
+                    binding: usage
+                        .binding
+                        .map(|binding| (binding.binding.set, binding.binding.binding)),
+                    written: usage.written,
+                    format: usage.image.map(|(_, format, _)| format),
+                    texture: usage.texture,
+                })
+                .collect();
+            let profile_labels = input
+                .ty
+                .program_profile_labels(program)
+                .into_iter()
+                .map(|(label, callable)| {
+                    let name = match callable {
+                        Callable::Function(id) => input.hir.functions[id].name,
+                        Callable::Program(id) => input.hir.programs[id].name,
+                    };
+                    ProfileLabel {
+                        label: label.to_string(),
+                        source: input.hir.identifiers[name].clone(),
+                    }
+                })
+                .collect();
+            events.push(ArtifactEvent {
+                module: input.name.to_string(),
+                entry_point: entry_point.clone(),
+                stage: typeck::stages::program_stage(input.hir, program),
+                artifact: artifact.name.clone(),
+                bytes: artifact.contents.clone(),
+                reflection: Reflection {
+                    resources,
+                    names: artifact.names.clone(),
+                    profile_labels,
+                },
+            });
+        }
+    }
+    events
+}
+
+fn stage_name(stage: Option<Stage>) -> &'static str {
+    stage.map_or("", Stage::attribute)
+}
+
+fn class_name(class: BufferClass) -> &'static str {
+    match class {
+        BufferClass::Uniform => "Uniform",
+        BufferClass::Storage => "Storage",
+        BufferClass::PushConstant => "PushConstant",
+    }
+}
+
+impl ArtifactEvent {
+    /// The event in the format described in the module documentation,
+    /// including the length in front.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![0; 4];
+        let number = |out: &mut Vec<u8>, n: u32| out.extend_from_slice(&n.to_le_bytes());
+        let bytes = |out: &mut Vec<u8>, b: &[u8]| {
+            number(out, b.len() as u32);
+            out.extend_from_slice(b);
+        };
+        bytes(&mut out, self.module.as_bytes());
+        bytes(&mut out, self.entry_point.as_bytes());
+        bytes(&mut out, stage_name(self.stage).as_bytes());
+        bytes(&mut out, self.artifact.as_bytes());
+        bytes(&mut out, &self.bytes);
+        number(&mut out, self.reflection.resources.len() as u32);
+        for resource in &self.reflection.resources {
+            bytes(&mut out, resource.name.as_bytes());
+            bytes(&mut out, class_name(resource.class).as_bytes());
+            let (set, binding) = resource.binding.unwrap_or((u32::MAX, u32::MAX));
+            number(&mut out, set);
+            number(&mut out, binding);
+            out.push(resource.written as u8);
+            bytes(
+                &mut out,
+                resource.format.map_or("", ImageFormat::name).as_bytes(),
+            );
+            bytes(
+                &mut out,
+                resource
+                    .texture
+                    .map_or(String::new(), TextureResource::type_name)
+                    .as_bytes(),
+            );
+        }
+        number(&mut out, self.reflection.names.len() as u32);
+        for name in &self.reflection.names {
+            bytes(&mut out, name.mangled.as_bytes());
+            bytes(&mut out, name.source.as_bytes());
+        }
+        number(&mut out, self.reflection.profile_labels.len() as u32);
+        for label in &self.reflection.profile_labels {
+            bytes(&mut out, label.label.as_bytes());
+            bytes(&mut out, label.source.as_bytes());
+        }
+        let len = (out.len() - 4) as u32;
+        out[..4].copy_from_slice(&len.to_le_bytes());
+        out
+    }
+
+    /// Read an event written by [`encode`](Self::encode), returning the
+    /// event and the number of bytes it took, or `None` if the bytes don't
+    /// start with a complete event.
+    pub fn decode(bytes: &[u8]) -> Option<(Self, usize)> {
+        let mut reader = Reader(bytes);
+        let len = reader.number()? as usize;
+        let mut reader = Reader(reader.0.get(..len)?);
+
+        let module = reader.string()?;
+        let entry_point = reader.string()?;
+        let stage = match reader.bytes()? {
+            b"" => None,
+            name => Some(Stage::from_attribute(std::str::from_utf8(name).ok()?)?),
+        };
+        let artifact = reader.string()?;
+        let code = reader.bytes()?.to_vec();
+        let resources = (0..reader.number()?)
+            .map(|_| {
+                let name = reader.string()?;
+                let class = BufferClass::from_attribute(&reader.string()?)?;
+                let binding = match (reader.number()?, reader.number()?) {
+                    (u32::MAX, u32::MAX) => None,
+                    binding => Some(binding),
+                };
+                let (written, rest) = reader.0.split_first()?;
+                reader.0 = rest;
+                let format = match reader.bytes()? {
+                    b"" => None,
+                    name => Some(ImageFormat::from_name(std::str::from_utf8(name).ok()?)?),
+                };
+                let texture = match reader.string()?.as_str() {
+                    "" => None,
+                    name => Some(TextureResource::from_type_name(name)?),
+                };
+                Some(Resource {
+                    name,
+                    class,
+                    binding,
+                    written: *written != 0,
+                    format,
+                    texture,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let names = (0..reader.number()?)
+            .map(|_| {
+                Some(MangledName {
+                    mangled: reader.string()?,
+                    source: reader.string()?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let profile_labels = (0..reader.number()?)
+            .map(|_| {
+                Some(ProfileLabel {
+                    label: reader.string()?,
+                    source: reader.string()?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        if !reader.0.is_empty() {
+            return None;
+        }
+
+        let event = ArtifactEvent {
+            module,
+            entry_point,
+            stage,
+            artifact,
+            bytes: code,
+            reflection: Reflection {
+                resources,
+                names,
+                profile_labels,
+            },
+        };
+        Some((event, 4 + len))
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn number(&mut self) -> Option<u32> {
+        let (number, rest) = self.0.split_at_checked(4)?;
+        self.0 = rest;
+        Some(u32::from_le_bytes(number.try_into().ok()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.number()? as usize;
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use typeck::{SampledType, TextureDim};
+
+    #[test]
+    fn encode_and_decode() {
+        let event = ArtifactEvent {
+            module: "sky".to_string(),
+            entry_point: "sky_vs".to_string(),
+            stage: Some(Stage::Vertex),
+            artifact: "sky_vs.vert".to_string(),
+            bytes: b"#version 300 es\n".to_vec(),
+            reflection: Reflection {
+                resources: vec![
+                    Resource {
+                        name: "camera".to_string(),
+                        class: BufferClass::Uniform,
+                        binding: Some((0, 1)),
+                        written: false,
+                        format: None,
+                        texture: None,
+                    },
+                    Resource {
+                        name: "lighting".to_string(),
+                        class: BufferClass::Storage,
+                        binding: Some((0, 2)),
+                        written: true,
+                        format: Some(ImageFormat::Rgba16Float),
+                        texture: None,
+                    },
+                    Resource {
+                        name: "time".to_string(),
+                        class: BufferClass::PushConstant,
+                        binding: None,
+                        written: false,
+                        format: None,
+                        texture: None,
+                    },
+                    Resource {
+                        name: "albedo".to_string(),
+                        class: BufferClass::Uniform,
+                        binding: Some((1, 0)),
+                        written: false,
+                        format: None,
+                        texture: Some(TextureResource::Texture {
+                            dim: TextureDim::Cube,
+                            sampled: SampledType::Depth,
+                        }),
+                    },
+                    Resource {
+                        name: "shadows".to_string(),
+                        class: BufferClass::Uniform,
+                        binding: Some((1, 1)),
+                        written: false,
+                        format: None,
+                        texture: Some(TextureResource::Sampler { comparison: true }),
+                    },
+                ],
+                names: vec![MangledName {
+                    mangled: "geometry_Ray".to_string(),
+                    source: "geometry::Ray".to_string(),
+                }],
+                profile_labels: vec![ProfileLabel {
+                    label: "sky gradient".to_string(),
+                    source: "gradient".to_string(),
+                }],
+            },
+        };
+        let mut bytes = event.encode();
+        let len = bytes.len();
+        bytes.extend_from_slice(&[1, 2]);
+
+        assert_eq!(ArtifactEvent::decode(&bytes), Some((event, len)));
+        assert_eq!(ArtifactEvent::decode(&bytes[..len - 1]), None);
+    }
+}
@@ -0,0 +1,250 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The interface between the compiler and the code generators.
+//!
+//! A backend gets a type checked module together with the information the
+//! type checker collected about it, like the bindings of buffers and the
+//! resources every program uses, and turns it into artifacts for a target.
+//! Backends are looked up by name in a [`Registry`], which the driver fills
+//! with the backends of thiol and those of crates embedding it.
+//!
+//! With the `spirv-val` feature, SPIR-V artifacts can be checked with the
+//! validator of SPIRV-Tools, see the `spirv` module. The [`hot_reload`]
+//! module turns artifacts into events for engines that reload pipelines,
+//! and backends name the items of a module with the [`mangle`] module.
+
+use codespan_reporting::diagnostic::{Diagnostic, Severity};
+use thiol_hir as hir;
+use thiol_typeck as typeck;
+
+use hir::{FileId, FileLocation, Program};
+use id_arena::Id;
+use mangle::MangledName;
+use typeck::ResourceUsage;
+
+pub mod hot_reload;
+pub mod mangle;
+#[cfg(feature = "spirv-val")]
+pub mod spirv;
+
+/// A type checked module and what the type checker knows about it
+#[derive(Clone, Copy)]
+pub struct Input<'a> {
+    /// name of the module, used for the names of the artifacts
+    pub name: &'a str,
+    pub hir: &'a hir::Context,
+    pub ty: &'a typeck::Context,
+    pub module: &'a hir::Module,
+}
+
+impl Input<'_> {
+    /// The buffers a program accesses, directly or through the functions it
+    /// calls.
+    pub fn resources(&self, program: Id<Program>) -> Vec<ResourceUsage> {
+        self.ty.program_resources(self.hir, self.module, program)
+    }
+}
+
+/// A file produced by a backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// file name of the artifact, unique among the artifacts of a module
+    pub name: String,
+    pub contents: Vec<u8>,
+    /// names of the programs whose code is in the artifact
+    pub entry_points: Vec<String>,
+    /// byte offsets into the contents where the code generated for a source
+    /// location starts, in increasing order
+    pub source_map: Vec<(usize, FileLocation)>,
+    /// the source names of the items named in the contents
+    pub names: Vec<MangledName>,
+}
+
+impl Artifact {
+    pub fn text(name: impl Into<String>, source: String) -> Self {
+        Artifact {
+            name: name.into(),
+            contents: source.into_bytes(),
+            entry_points: vec![],
+            source_map: vec![],
+            names: vec![],
+        }
+    }
+
+    pub fn with_entry_points(mut self, entry_points: Vec<String>) -> Self {
+        self.entry_points = entry_points;
+        self
+    }
+
+    pub fn with_names(mut self, names: Vec<MangledName>) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// The source location of the code at a byte offset of the contents.
+    pub fn source_location(&self, offset: usize) -> Option<FileLocation> {
+        let i = self
+            .source_map
+            .partition_point(|(start, _)| *start <= offset);
+        Some(self.source_map.get(i.checked_sub(1)?)?.1)
+    }
+}
+
+/// The artifacts and diagnostics of a backend, the artifacts are only
+/// complete if none of the diagnostics is an error
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    pub artifacts: Vec<Artifact>,
+    pub diagnostics: Vec<Diagnostic<FileId>>,
+}
+
+impl Output {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diag| diag.severity >= Severity::Error)
+    }
+}
+
+impl<E> From<Result<Vec<Artifact>, Vec<E>>> for Output
+where
+    E: Into<Diagnostic<FileId>>,
+{
+    fn from(result: Result<Vec<Artifact>, Vec<E>>) -> Self {
+        match result {
+            Ok(artifacts) => Output {
+                artifacts,
+                diagnostics: vec![],
+            },
+            Err(errs) => Output {
+                artifacts: vec![],
+                diagnostics: errs.into_iter().map(Into::into).collect(),
+            },
+        }
+    }
+}
+
+/// A code generator for a target
+pub trait Backend {
+    /// The name the backend is selected with, like `msl`.
+    fn name(&self) -> &str;
+
+    /// A short description of the target, shown when listing the backends.
+    fn description(&self) -> &str;
+
+    /// Translate a module that was type checked without errors.
+    fn emit(&self, input: Input<'_>) -> Output;
+}
+
+/// The available backends, at most one for every name
+#[derive(Default)]
+pub struct Registry {
+    backends: Vec<Box<dyn Backend>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a backend, replacing the one with the same name if there is one.
+    pub fn register(&mut self, backend: impl Backend + 'static) {
+        self.insert(Box::new(backend));
+    }
+
+    /// Add all backends of another registry, they replace the backends of
+    /// this one with the same names.
+    pub fn extend(&mut self, other: Registry) {
+        for backend in other.backends {
+            self.insert(backend);
+        }
+    }
+
+    fn insert(&mut self, backend: Box<dyn Backend>) {
+        match self
+            .backends
+            .iter_mut()
+            .find(|b| b.name() == backend.name())
+        {
+            Some(existing) => *existing = backend,
+            None => self.backends.push(backend),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends
+            .iter()
+            .find(|b| b.name() == name)
+            .map(|b| b.as_ref())
+    }
+
+    /// The backends in the order they were first registered.
+    pub fn backends(&self) -> impl Iterator<Item = &dyn Backend> {
+        self.backends.iter().map(|b| b.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(&'static str, &'static str);
+
+    impl Backend for Fixed {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            self.1
+        }
+
+        fn emit(&self, _: Input<'_>) -> Output {
+            Output::default()
+        }
+    }
+
+    #[test]
+    fn source_locations() {
+        let loc = |start| FileLocation {
+            file: 0,
+            start,
+            end: start + 1,
+        };
+        let mut artifact = Artifact::text("a.spv", String::new());
+        artifact.source_map = vec![(20, loc(3)), (44, loc(9))];
+
+        assert_eq!(artifact.source_location(0), None);
+        assert_eq!(artifact.source_location(20), Some(loc(3)));
+        assert_eq!(artifact.source_location(43), Some(loc(3)));
+        assert_eq!(artifact.source_location(100), Some(loc(9)));
+    }
+
+    #[test]
+    fn replace_by_name() {
+        let mut registry = Registry::new();
+        registry.register(Fixed("msl", "Metal"));
+        registry.register(Fixed("glsl", "GLSL"));
+
+        let mut custom = Registry::new();
+        custom.register(Fixed("console", "in-house console"));
+        custom.register(Fixed("msl", "patched Metal"));
+        registry.extend(custom);
+
+        let names = registry
+            .backends()
+            .map(|b| (b.name(), b.description()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("msl", "patched Metal"),
+                ("glsl", "GLSL"),
+                ("console", "in-house console")
+            ]
+        );
+        assert!(registry.get("spirv").is_none());
+    }
+}
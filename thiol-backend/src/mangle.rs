@@ -0,0 +1,354 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Names of items in the generated code.
+//!
+//! Thiol names can't always be used as they are: items of modules have
+//! qualified names like `geometry::Ray`, instances of generic records have
+//! generic arguments like `Box<float>`, and a name can be a keyword of the
+//! target. A [`Mangler`] turns every item into a name that is valid on the
+//! target and unique among the items, and remembers the source name of
+//! every mangled name so that tools can map them back.
+//!
+//! Mangling is deterministic: the name of an item only depends on its own
+//! name and on the items that were mangled before it. The parts of a name
+//! that aren't identifier characters become `_`, so `geometry::Ray` becomes
+//! `geometry_Ray` and `Box<float>` becomes `Box_float`, and `'` becomes
+//! `_prime`. Characters outside of ASCII, which GLSL doesn't allow in names,
+//! become their code point, so `café` becomes `caf_u00e9`, which a name in
+//! the source can spell as well.
+//! Keywords of the target, names starting with one of its reserved
+//! prefixes, and thiol keywords and reserved words, which raw identifiers
+//! like `r#float` can spell, get a `_` appended, see [`keywords`]. An item
+//! whose name is taken by another item, like a type and a function with the
+//! same name or `geometry::Ray` and `geometry_Ray`, gets the first free name
+//! of `name_1`, `name_2` and so on.
+//!
+//! The fields of a record get names that are unique among the fields, and
+//! local variables and parameters are named in scopes. The name of a local
+//! is not used by an item or by a local of a scope around it, because the
+//! targets put the new name in scope before its initializer, where thiol
+//! still refers to the name it shadows.
+
+use std::collections::{BTreeMap, HashSet};
+
+use thiol_hir::keywords;
+use thiol_typeck::{Context, Symbol, TypeId};
+
+/// An item that gets a name in the generated code
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Entity {
+    /// a type, with its generic arguments if it is an instance of a generic
+    /// record
+    Type(String),
+    Function(String),
+    Constant(String),
+    Program(String),
+}
+
+impl Entity {
+    /// The item a symbol with the name refers to, `None` for symbols that
+    /// aren't items, like local variables.
+    pub fn from_symbol(sym: Symbol, name: &str) -> Option<Entity> {
+        let name = name.to_string();
+        match sym {
+            Symbol::Type(_) => Some(Entity::Type(name)),
+            Symbol::Function(_) => Some(Entity::Function(name)),
+            Symbol::Constant(_) => Some(Entity::Constant(name)),
+            Symbol::Program(_) => Some(Entity::Program(name)),
+            _ => None,
+        }
+    }
+
+    /// A function, with the generic arguments of an instance of a generic
+    /// function.
+    pub fn function(ty_ctx: &Context, name: &str, generics: &[TypeId]) -> Entity {
+        if generics.is_empty() {
+            Entity::Function(name.to_string())
+        } else {
+            Entity::Function(ty_ctx.function_instance_name(name, generics))
+        }
+    }
+
+    /// The name of the item in the source.
+    pub fn source_name(&self) -> &str {
+        match self {
+            Entity::Type(name)
+            | Entity::Function(name)
+            | Entity::Constant(name)
+            | Entity::Program(name) => name,
+        }
+    }
+}
+
+/// A name in the generated code and the name of its item in the source
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MangledName {
+    pub mangled: String,
+    pub source: String,
+}
+
+/// The names of the items of a module on one target
+#[derive(Debug, Clone)]
+pub struct Mangler {
+    /// keywords and builtins of the target that are valid thiol identifiers
+    reserved: &'static [&'static str],
+    reserved_prefixes: &'static [&'static str],
+    names: BTreeMap<Entity, String>,
+    taken: HashSet<String>,
+    locals: BTreeMap<Symbol, String>,
+    /// the names of the locals of every scope around the code being emitted
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Mangler {
+    pub fn new(
+        reserved: &'static [&'static str],
+        reserved_prefixes: &'static [&'static str],
+    ) -> Self {
+        Mangler {
+            reserved,
+            reserved_prefixes,
+            names: BTreeMap::new(),
+            taken: HashSet::new(),
+            locals: BTreeMap::new(),
+            scopes: vec![],
+        }
+    }
+
+    /// A name that is valid on the target, which isn't necessarily unique.
+    pub fn escape(&self, name: &str) -> String {
+        let mut ascii = String::new();
+        for c in name.chars() {
+            match c {
+                '\'' => ascii.push_str("_prime"),
+                c if c.is_ascii() => ascii.push(c),
+                c => {
+                    if !(ascii.is_empty() || ascii.ends_with('_')) {
+                        ascii.push('_');
+                    }
+                    ascii.push_str(&format!("u{:04x}", u32::from(c)));
+                }
+            }
+        }
+        let name = ascii
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_");
+        let reserved = self.reserved.contains(&name.as_str())
+            || keywords::is_keyword(&name)
+            || keywords::is_reserved(&name)
+            || self
+                .reserved_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix));
+        if reserved {
+            format!("{}_", name)
+        } else {
+            name
+        }
+    }
+
+    /// The name of an item, which is chosen the first time the item is
+    /// named.
+    pub fn item(&mut self, entity: Entity) -> String {
+        if let Some(name) = self.names.get(&entity) {
+            return name.clone();
+        }
+
+        let escaped = self.escape(entity.source_name());
+        let mut name = escaped.clone();
+        let mut n = 0;
+        while self.taken.contains(&name) {
+            n += 1;
+            name = format!("{}_{}", escaped, n);
+        }
+        self.taken.insert(name.clone());
+        self.names.insert(entity, name.clone());
+        name
+    }
+
+    /// The names of the fields of a record, in the order of the fields. A
+    /// field whose name is taken by a field before it gets the first free
+    /// name like an item.
+    pub fn fields<'n>(&self, names: impl IntoIterator<Item = &'n str>) -> Vec<String> {
+        let mut fields: Vec<String> = vec![];
+        for name in names {
+            let escaped = self.escape(name);
+            let mut name = escaped.clone();
+            let mut n = 0;
+            while fields.contains(&name) {
+                n += 1;
+                name = format!("{}_{}", escaped, n);
+            }
+            fields.push(name);
+        }
+        fields
+    }
+
+    /// Start a scope for the locals declared in it.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashSet::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// The name of a local variable or parameter declared in the innermost
+    /// scope. A local keeps the name it got the first time, so that the
+    /// instances of a generic function use the same names.
+    pub fn local(&mut self, sym: Symbol, name: &str) -> String {
+        let name = match self.locals.get(&sym) {
+            Some(name) => name.clone(),
+            None => {
+                let escaped = self.escape(name);
+                let mut name = escaped.clone();
+                let mut n = 0;
+                while self.taken.contains(&name)
+                    || self.scopes.iter().any(|scope| scope.contains(&name))
+                {
+                    n += 1;
+                    name = format!("{}_{}", escaped, n);
+                }
+                self.locals.insert(sym, name.clone());
+                name
+            }
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.clone());
+        }
+        name
+    }
+
+    /// The name of a local that was declared before.
+    pub fn get_local(&self, sym: Symbol) -> Option<&str> {
+        self.locals.get(&sym).map(String::as_str)
+    }
+
+    /// The name of an item that was named before.
+    pub fn get(&self, entity: &Entity) -> Option<&str> {
+        self.names.get(entity).map(String::as_str)
+    }
+
+    /// The source names of all mangled names, ordered by the mangled name.
+    pub fn table(&self) -> Vec<MangledName> {
+        let mut table = self
+            .names
+            .iter()
+            .map(|(entity, mangled)| MangledName {
+                mangled: mangled.clone(),
+                source: entity.source_name().to_string(),
+            })
+            .collect::<Vec<_>>();
+        table.sort();
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id_arena::Arena;
+    use thiol_hir::Statement;
+
+    #[test]
+    fn unique_names() {
+        let mut mangler = Mangler::new(&["main", "float"], &["gl_"]);
+        assert_eq!(mangler.item(Entity::Type("Box<float>".into())), "Box_float");
+        assert_eq!(mangler.item(Entity::Function("main".into())), "main_");
+        assert_eq!(mangler.item(Entity::Constant("gl_Foo".into())), "gl_Foo_");
+        assert_eq!(
+            mangler.item(Entity::Type("geometry::Ray".into())),
+            "geometry_Ray"
+        );
+        assert_eq!(
+            mangler.item(Entity::Function("geometry_Ray".into())),
+            "geometry_Ray_1"
+        );
+        assert_eq!(
+            mangler.item(Entity::Constant("geometry::Ray".into())),
+            "geometry_Ray_2"
+        );
+        assert_eq!(
+            mangler.item(Entity::Type("geometry::Ray".into())),
+            "geometry_Ray"
+        );
+        assert_eq!(mangler.escape("x'"), "x_prime");
+        assert_eq!(mangler.escape("λ_max"), "u03bb_max");
+        assert_eq!(mangler.escape("café"), "caf_u00e9");
+        assert_eq!(mangler.escape("float"), "float_");
+        assert_eq!(mangler.escape("while"), "while_");
+
+        assert_eq!(
+            mangler.table()[..2],
+            [
+                MangledName {
+                    mangled: "Box_float".into(),
+                    source: "Box<float>".into(),
+                },
+                MangledName {
+                    mangled: "geometry_Ray".into(),
+                    source: "geometry::Ray".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn local_names() {
+        let mut mangler = Mangler::new(&["main"], &["gl_"]);
+        mangler.item(Entity::Constant("SCALE".into()));
+        let mut loops = Arena::<Statement>::new();
+        let mut local = || Symbol::LoopVariable(loops.alloc(Statement::Break));
+        let (x, y, inner, sibling) = (local(), local(), local(), local());
+
+        mangler.push_scope();
+        assert_eq!(mangler.local(x, "x'"), "x_prime");
+        assert_eq!(mangler.local(y, "x_prime"), "x_prime_1");
+        mangler.push_scope();
+        assert_eq!(mangler.local(inner, "SCALE"), "SCALE_1");
+        mangler.pop_scope();
+        mangler.push_scope();
+        assert_eq!(mangler.local(sibling, "SCALE"), "SCALE_1");
+        mangler.pop_scope();
+        mangler.pop_scope();
+
+        assert_eq!(mangler.get_local(y), Some("x_prime_1"));
+        mangler.push_scope();
+        assert_eq!(mangler.local(y, "x_prime"), "x_prime_1");
+        mangler.pop_scope();
+
+        assert_eq!(
+            mangler.fields(vec!["x'", "x_prime", "main"]),
+            ["x_prime", "x_prime_1", "main_"]
+        );
+    }
+
+    #[test]
+    fn escaped_code_points() {
+        let mut mangler = Mangler::new(&[], &[]);
+        assert_eq!(mangler.item(Entity::Function("café".into())), "caf_u00e9");
+        assert_eq!(
+            mangler.item(Entity::Constant("caf_u00e9".into())),
+            "caf_u00e9_1"
+        );
+
+        let mut loops = Arena::<Statement>::new();
+        let (a, b) = (
+            Symbol::LoopVariable(loops.alloc(Statement::Break)),
+            Symbol::LoopVariable(loops.alloc(Statement::Break)),
+        );
+        mangler.push_scope();
+        assert_eq!(mangler.local(a, "thé"), "th_u00e9");
+        assert_eq!(mangler.local(b, "th_u00e9"), "th_u00e9_1");
+        mangler.pop_scope();
+
+        assert_eq!(
+            mangler.fields(vec!["café", "caf_u00e9"]),
+            ["caf_u00e9", "caf_u00e9_1"]
+        );
+    }
+}
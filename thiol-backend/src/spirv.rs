@@ -0,0 +1,96 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Validation of SPIR-V artifacts with `spirv-val` of SPIRV-Tools.
+//!
+//! The validator reports the word of the module at which it found a problem,
+//! which is looked up in the source map of the artifact to point the
+//! diagnostic at the code it was generated for.
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use spirv_tools::val::Validator;
+
+pub use spirv_tools::TargetEnv;
+
+use crate::{Artifact, FileId};
+
+/// Whether an artifact is a SPIR-V module, going by its extension.
+pub fn is_spirv(artifact: &Artifact) -> bool {
+    artifact.name.ends_with(".spv")
+}
+
+/// Validate a SPIR-V module for a Vulkan environment, the diagnostics are
+/// empty if the module is valid.
+pub fn validate(artifact: &Artifact, env: TargetEnv) -> Vec<Diagnostic<FileId>> {
+    if !artifact.contents.len().is_multiple_of(4) {
+        return vec![Diagnostic::error().with_message(format!(
+            "`{}` is not a SPIR-V module, its size is not a multiple of 4 bytes",
+            artifact.name
+        ))];
+    }
+    let words = artifact
+        .contents
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect::<Vec<_>>();
+
+    let err = match spirv_tools::val::create(Some(env)).validate(&words, None) {
+        Ok(()) => return vec![],
+        Err(err) => err,
+    };
+    let (message, word) = match err.diagnostic {
+        Some(diag) => (diag.message, Some(diag.index)),
+        None => (err.inner.to_string(), None),
+    };
+
+    let mut diag = Diagnostic::error()
+        .with_message(format!("invalid SPIR-V generated in `{}`", artifact.name))
+        .with_notes(vec![format!("spirv-val: {}", message)]);
+    if let Some(loc) = word.and_then(|word| artifact.source_location(word * 4)) {
+        diag = diag.with_labels(vec![
+            Label::primary(loc.file, loc.range()).with_message("generated for this code")
+        ]);
+    }
+    vec![diag]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(instructions: &[u32]) -> Artifact {
+        let header = [0x0723_0203, 0x0001_0000, 0, 1, 0];
+        let contents = header
+            .iter()
+            .chain(instructions)
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        Artifact {
+            name: "test.spv".to_string(),
+            contents,
+            entry_points: vec![],
+            source_map: vec![],
+        }
+    }
+
+    // OpCapability Shader, OpCapability Linkage
+    const CAPABILITIES: [u32; 4] = [0x0002_0011, 1, 0x0002_0011, 5];
+    // OpMemoryModel Logical GLSL450
+    const MEMORY_MODEL: [u32; 3] = [0x0003_000e, 0, 1];
+
+    #[test]
+    fn valid_module() {
+        let artifact = module(&[&CAPABILITIES[..], &MEMORY_MODEL[..]].concat());
+        assert!(is_spirv(&artifact));
+        assert!(validate(&artifact, TargetEnv::Universal_1_0).is_empty());
+    }
+
+    #[test]
+    fn missing_memory_model() {
+        let artifact = module(&CAPABILITIES);
+        let diags = validate(&artifact, TargetEnv::Universal_1_0);
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].notes[0].starts_with("spirv-val: "));
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use thiol_hir::FileId;
+
+use crate::Error;
+
+impl From<Error> for Diagnostic<FileId> {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::UnsupportedProfile { profile } => Diagnostic::error()
+                .with_message(format!(
+                    "GLSL ES shaders can't be emitted for the `{}` profile",
+                    profile
+                ))
+                .with_notes(vec!["check the module with `--profile gles3`".to_string()]),
+            Error::ProgramWithoutStage { name, loc } => {
+                let prim =
+                    Label::primary(loc.file, loc.range()).with_message("program without a stage");
+                Diagnostic::error()
+                    .with_message(format!("program `{}` has no stage", name))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "add `@vertex` or `@fragment` to the program".to_string()
+                    ])
+            }
+            Error::MissingPosition { program, loc } => {
+                let prim = Label::primary(loc.file, loc.range()).with_message("vertex program");
+                Diagnostic::error()
+                    .with_message(format!(
+                        "vertex program `{}` has no position output",
+                        program
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "add the `[Position]` attribute to the clip space position output"
+                            .to_string(),
+                    ])
+            }
+            Error::MixedMatrixLayouts { name, loc } => {
+                let prim = Label::primary(loc.file, loc.range()).with_message("uniform buffer");
+                Diagnostic::error()
+                    .with_message(format!(
+                        "uniform buffer `{}` has both row-major and column-major matrices",
+                        name
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "GLSL ES has one matrix layout per uniform block, give the fields of its records the layout of the buffer"
+                            .to_string(),
+                    ])
+            }
+            Error::TrapUnsupported { loc } => {
+                let prim =
+                    Label::primary(loc.file, loc.range()).with_message("index checked at runtime");
+                Diagnostic::error()
+                    .with_message("GLSL ES cannot trap on indices out of bounds")
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "use the `clamp` or `undefined` bounds check".to_string()
+                    ])
+            }
+        }
+    }
+}
@@ -0,0 +1,1872 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! GLSL ES 3.0 backend for WebGL 2 and mobile devices.
+//!
+//! Every program becomes a shader of its own, containing the functions it
+//! calls. The program body is a function named after the program that `main`
+//! calls. Vertex inputs are read from the attribute location given by their
+//! `Location` attribute, or else the position of the input in the
+//! declaration. Varyings are matched by name between the stages, as GLSL ES
+//! 3.0 has no locations for them, and fragment outputs are written to the
+//! location of their `Location` attribute. Inputs with one of these
+//! attributes are read from builtins instead:
+//!
+//! - `VertexIndex` (vertex): `gl_VertexID`
+//! - `InstanceIndex` (vertex): `gl_InstanceID`
+//! - `Position` (fragment): `gl_FragCoord`
+//! - `FrontFacing` (fragment): `gl_FrontFacing`
+//!
+//! The output of a vertex program with a `Position` attribute is written to
+//! `gl_Position`. Varyings get the `flat` and `centroid` qualifiers of their
+//! interpolation.
+//!
+//! Uniform buffers become `std140` uniform blocks named after the constant
+//! with a `_block` suffix. GLSL ES 3.0 can't declare their bindings, so the
+//! set and binding are given in a comment for the host to bind the block
+//! with `glUniformBlockBinding`. Blocks whose matrices are stored row by
+//! row get the `row_major` qualifier, which also applies to the structs in
+//! them, so all matrices of a block have to have the same layout.
+//!
+//! Floats and ints default to `highp`, values with relaxed precision and
+//! `half` values are `mediump`.
+//!
+//! Indices into slices are clamped to the slice, and indices into arrays
+//! with a size to the array with the `clamp` bounds check. GLSL ES has no
+//! way to stop an invocation, so the `trap` bounds check is an error.
+//!
+//! GLSL ES 3.0 doesn't promise IEEE 754 semantics and has no way to ask for
+//! them, so the float modes of functions and programs don't change their
+//! code.
+//!
+//! Integer arithmetic wraps around in GLSL ES 3.0. Saturating sums and
+//! differences call helpers that clamp the second operand to the values
+//! that don't overflow.
+//!
+//! `select` with a boolean vector mask is `mix` for floats, GLSL ES 3.0
+//! only mixes floats with boolean vectors, so other vectors call helpers
+//! that select each component.
+//!
+//! Optionals are structs with a `has_value` flag next to the value. GLSL
+//! ES has no default values of structs, `none()` of each optional type calls
+//! a helper that leaves the value undefined. Locals declared without a value
+//! get a `has_value` of `false`.
+//!
+//! GLSL ES 3.0 has no debug output, assertions and debug prints are left
+//! out. It has no timestamps in shaders either, functions and programs with
+//! a profiling label start with a comment of the label.
+//!
+//! Casts are constructor calls. `bitcast` of floats calls `floatBitsToInt`
+//! and its relatives, integers keep their bits when they are converted.
+//!
+//! The module has to be checked with the `gles3` profile, which rejects the
+//! features GLSL ES 3.0 doesn't have.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use thiol_backend::mangle::{Entity, MangledName, Mangler};
+use thiol_backend::{Artifact, Input, Output};
+use thiol_hir as hir;
+use thiol_typeck as typeck;
+
+use hir::{
+    Expression, FileLocation, Function, Identifier, MatchPattern, ParamMode, Program, Statement,
+    VariableDef,
+};
+use id_arena::Id;
+use typeck::consteval::{self, Constant, Evaluator};
+use typeck::derives::Derive;
+use typeck::layout::buffer_class;
+use typeck::luts::{LookupTable, LutDomain};
+use typeck::{
+    BoundsCheck, BufferClass, Callable, Instance, IntegerOverflow, InterpolationMode, Intrinsic,
+    MatrixLayout, PackedFormat, Profile, Resolution, SpaceTransform, Stage, Symbol, Type, TypeId,
+};
+
+mod diagnostics;
+
+/// The source of the shader for one program
+#[derive(Debug, Clone)]
+pub struct Shader {
+    pub program: Identifier,
+    pub stage: Stage,
+    pub source: String,
+    /// the source names of the items named in the source
+    pub names: Vec<MangledName>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// the module was checked with a profile other than `gles3`
+    UnsupportedProfile {
+        profile: Profile,
+    },
+    ProgramWithoutStage {
+        name: Identifier,
+        loc: FileLocation,
+    },
+    /// a vertex program with outputs but none of them is the position
+    MissingPosition {
+        program: Identifier,
+        loc: FileLocation,
+    },
+    /// a uniform buffer with both row-major and column-major matrices, GLSL
+    /// ES can't give the fields of structs a layout of their own
+    MixedMatrixLayouts {
+        name: Identifier,
+        loc: FileLocation,
+    },
+    /// an index that would trap when out of bounds, GLSL ES can't stop an
+    /// invocation
+    TrapUnsupported {
+        loc: FileLocation,
+    },
+}
+
+/// Translate the programs of a type checked module to GLSL ES 3.0 shaders.
+pub fn emit(
+    hir_ctx: &hir::Context,
+    ty_ctx: &typeck::Context,
+    module: &hir::Module,
+) -> Result<Vec<Shader>, Vec<Error>> {
+    if ty_ctx.profile != Profile::GlslEs3 {
+        return Err(vec![Error::UnsupportedProfile {
+            profile: ty_ctx.profile,
+        }]);
+    }
+
+    let mut shaders = vec![];
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let mut e = Emitter {
+            hir: hir_ctx,
+            ty: ty_ctx,
+            module,
+            instance: None,
+            names: Mangler::new(RESERVED, &["gl_", "thiol_"]),
+            types: String::new(),
+            structs: BTreeMap::new(),
+            ret: None,
+            colours: false,
+            saturating: BTreeSet::new(),
+            selects: BTreeSet::new(),
+            errs: vec![],
+        };
+        // items are named in the order of the module, so that their names
+        // don't depend on the order they are used in
+        for id in &module.consts {
+            let name = &hir_ctx.identifiers[hir_ctx.variable_defs[*id].name];
+            e.names.item(Entity::Constant(name.clone()));
+        }
+        for id in &module.functions {
+            let name = &hir_ctx.identifiers[hir_ctx.functions[*id].name];
+            if hir_ctx.functions[*id].generics.is_empty() {
+                e.names.item(Entity::function(ty_ctx, name, &[]));
+            }
+            for instance in ty_ctx.instances.iter().filter(|i| i.func == *id) {
+                e.names
+                    .item(Entity::function(ty_ctx, name, &instance.generics));
+            }
+        }
+        for id in &module.programs {
+            let name = &hir_ctx.identifiers[hir_ctx.programs[*id].name];
+            e.names.item(Entity::Program(name.clone()));
+        }
+        if let Some(shader) = e.program(*id) {
+            shaders.push(shader);
+        }
+        errs.extend(e.errs);
+    }
+
+    if errs.is_empty() {
+        Ok(shaders)
+    } else {
+        Err(errs)
+    }
+}
+
+/// The GLSL ES backend, producing a shader for every program named after
+/// the program with the extension `.vert` or `.frag`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlslBackend;
+
+impl thiol_backend::Backend for GlslBackend {
+    fn name(&self) -> &str {
+        "glsl"
+    }
+
+    fn description(&self) -> &str {
+        "GLSL ES 3.0, needs the `gles3` profile"
+    }
+
+    fn emit(&self, input: Input<'_>) -> Output {
+        emit(input.hir, input.ty, input.module)
+            .map(|shaders| {
+                shaders
+                    .into_iter()
+                    .map(|shader| {
+                        let extension = match shader.stage {
+                            Stage::Vertex => "vert",
+                            Stage::Fragment => "frag",
+                            Stage::Compute => "comp",
+                            Stage::RayGeneration => "rgen",
+                            Stage::ClosestHit => "rchit",
+                            Stage::AnyHit => "rahit",
+                            Stage::Miss => "rmiss",
+                            Stage::Task => "task",
+                            Stage::Mesh => "mesh",
+                            Stage::TessellationControl => "tesc",
+                            Stage::TessellationEvaluation => "tese",
+                            Stage::Geometry => "geom",
+                        };
+                        let name = format!("{}.{}", shader.program, extension);
+                        Artifact::text(name, shader.source)
+                            .with_entry_points(vec![shader.program])
+                            .with_names(shader.names)
+                    })
+                    .collect()
+            })
+            .into()
+    }
+}
+
+const INDENT: &str = "    ";
+
+/// The conversions between sRGB encoded and linear colours, the alpha of
+/// RGBA colours is linear in both.
+const COLOURS: &str = "\
+vec3 thiol_srgb_to_linear(vec3 c)
+{
+    return mix(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, lessThanEqual(c, vec3(0.04045)));
+}
+
+vec4 thiol_srgb_to_linear(vec4 c)
+{
+    return vec4(thiol_srgb_to_linear(c.rgb), c.a);
+}
+
+vec3 thiol_linear_to_srgb(vec3 c)
+{
+    return mix(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, lessThanEqual(c, vec3(0.0031308)));
+}
+
+vec4 thiol_linear_to_srgb(vec4 c)
+{
+    return vec4(thiol_linear_to_srgb(c.rgb), c.a);
+}
+";
+
+/// The helper for a saturating sum or difference of values of an integer
+/// type like `int` or `uvec2`, which clamps the second operand to the values
+/// that don't overflow.
+fn saturating_helper(func: &str, ty: &str) -> String {
+    let signed = ty.starts_with('i');
+    let result = match (func, signed) {
+        ("add", true) => "a + clamp(b, (-2147483647 - 1) - min(a, 0), 2147483647 - max(a, 0))",
+        ("sub", true) => "a - clamp(b, max(a, -1) - 2147483647, (min(a, -1) + 1) + 2147483647)",
+        ("add", false) => "a + min(b, 4294967295u - a)",
+        _ => "a - min(b, a)",
+    };
+    format!(
+        "{0} thiol_saturating_{1}({0} a, {0} b)\n{{\n    return {2};\n}}\n",
+        ty, func, result
+    )
+}
+
+/// The helper for `select` of the components of vectors of the type `ty`
+/// with `n` components, which aren't floats.
+fn select_helper(n: usize, ty: &str) -> String {
+    let components = ["x", "y", "z", "w"][..n]
+        .iter()
+        .map(|c| format!("m.{0} ? a.{0} : b.{0}", c))
+        .collect::<Vec<_>>();
+    format!(
+        "{0} thiol_select(bvec{1} m, {0} a, {0} b)\n{{\n    return {0}({2});\n}}\n",
+        ty,
+        n,
+        components.join(", ")
+    )
+}
+
+/// Keywords and type names of GLSL ES that are valid thiol identifiers.
+const RESERVED: &[&str] = &[
+    "active",
+    "asm",
+    "attribute",
+    "bvec2",
+    "bvec3",
+    "bvec4",
+    "buffer",
+    "case",
+    "cast",
+    "centroid",
+    "class",
+    "coherent",
+    "common",
+    "const",
+    "default",
+    "discard",
+    "enum",
+    "extern",
+    "external",
+    "filter",
+    "fixed",
+    "flat",
+    "goto",
+    "highp",
+    "inline",
+    "input",
+    "interface",
+    "invariant",
+    "ivec2",
+    "ivec3",
+    "ivec4",
+    "layout",
+    "long",
+    "lowp",
+    "main",
+    "mat2",
+    "mat2x2",
+    "mat2x3",
+    "mat2x4",
+    "mat3",
+    "mat3x2",
+    "mat3x3",
+    "mat3x4",
+    "mat4",
+    "mat4x2",
+    "mat4x3",
+    "mat4x4",
+    "mediump",
+    "namespace",
+    "noinline",
+    "output",
+    "packed",
+    "partition",
+    "patch",
+    "precision",
+    "public",
+    "readonly",
+    "resource",
+    "restrict",
+    "sample",
+    "shared",
+    "short",
+    "sizeof",
+    "smooth",
+    "static",
+    "struct",
+    "subroutine",
+    "superp",
+    "switch",
+    "template",
+    "this",
+    "typedef",
+    "uniform",
+    "union",
+    "unsigned",
+    "using",
+    "uvec2",
+    "uvec3",
+    "uvec4",
+    "varying",
+    "vec2",
+    "vec3",
+    "vec4",
+    "void",
+    "volatile",
+    "while",
+    "writeonly",
+];
+
+/// Names starting with `gl_` are reserved for builtins and names starting
+/// with `thiol_` for the helpers of the generated code, names of the module
+/// that clash with them or keywords get an underscore appended.
+/// The scalar type of the components of a numeric type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Bool,
+    Int,
+    UInt,
+    Float,
+}
+
+struct Emitter<'a> {
+    hir: &'a hir::Context,
+    ty: &'a typeck::Context,
+    module: &'a hir::Module,
+    /// the instance of a generic function being emitted
+    instance: Option<&'a Instance>,
+    names: Mangler,
+    /// declarations of the record types, in an order where every struct
+    /// comes after the types of its fields
+    types: String,
+    structs: BTreeMap<TypeId, String>,
+    /// the return type of the function being emitted
+    ret: Option<TypeId>,
+    /// whether the helpers for colour conversions are used
+    colours: bool,
+    /// the saturating helpers that are used, `add` or `sub` with the type of
+    /// their operands
+    saturating: BTreeSet<(&'static str, String)>,
+    /// the `select` helpers that are used, with the number of components
+    /// and the type of the vectors they select from
+    selects: BTreeSet<(usize, String)>,
+    errs: Vec<Error>,
+}
+
+impl<'a> Emitter<'a> {
+    /// The name of an identifier in the code, the names of items and
+    /// locals are mangled.
+    fn name(&self, id: Id<Identifier>) -> String {
+        let name = &self.hir.identifiers[id];
+        let sym = self
+            .hir
+            .identifier_fcs
+            .get(&id)
+            .and_then(|loc| self.ty.references.symbol(*loc));
+        let mangled = sym.and_then(|sym| match Entity::from_symbol(sym, name) {
+            Some(item) => self.names.get(&item),
+            None => self.names.get_local(sym),
+        });
+        match mangled {
+            Some(mangled) => mangled.to_string(),
+            None => self.names.escape(name),
+        }
+    }
+
+    /// Declare a local in the innermost scope.
+    fn local(&mut self, sym: Symbol, id: Id<Identifier>) -> String {
+        self.names.local(sym, &self.hir.identifiers[id])
+    }
+
+    fn type_name(&mut self, ty: TypeId) -> String {
+        let t = match self.ty.types.get(ty) {
+            Some(t) => t.clone(),
+            None => return "void".to_string(),
+        };
+        match t {
+            Type::Bool => "bool".to_string(),
+            Type::Int => "int".to_string(),
+            Type::UInt => "uint".to_string(),
+            Type::Float | Type::Half | Type::Radians | Type::Degrees => "float".to_string(),
+            Type::BoolVec { components } => format!("bvec{}", size(components)),
+            Type::IntVec { components, .. } => format!("ivec{}", size(components)),
+            Type::UIntVec { components, .. } => format!("uvec{}", size(components)),
+            Type::FloatVec { components, .. } | Type::HalfVec { components, .. } => {
+                format!("vec{}", size(components))
+            }
+            Type::FloatMat { cols, rows, .. } if cols == rows => format!("mat{}", size(cols)),
+            Type::FloatMat { cols, rows, .. } => format!("mat{}x{}", size(cols), size(rows)),
+            Type::Packed { format } => packed_type(format).to_string(),
+            Type::Array { base, size } => format!("{}[{}]", self.type_name(base), size),
+            Type::OpenArray { base } => format!("{}[]", self.type_name(base)),
+            Type::Record { .. } => self.record(ty, ty),
+            Type::Distinct { inner, .. } => match self.ty.types.get(inner) {
+                Some(Type::Record { .. }) => self.record(ty, inner),
+                _ => self.type_name(inner),
+            },
+            Type::Normalized { inner } => self.type_name(inner),
+            Type::Optional { inner } => self.optional(ty, inner),
+            Type::GenericParam { name, .. } => self.names.escape(&name),
+            // rejected by the profile
+            Type::Double
+            | Type::DoubleVec { .. }
+            | Type::DoubleMat { .. }
+            | Type::Long
+            | Type::ULong
+            | Type::LongVec { .. }
+            | Type::ULongVec { .. }
+            | Type::AtomicInt
+            | Type::AtomicUInt
+            | Type::Image { .. }
+            | Type::Texture { .. }
+            | Type::Sampler { .. }
+            | Type::AccelerationStructure
+            | Type::Var(_)
+            | Type::Error => "void".to_string(),
+        }
+    }
+
+    /// The name of the struct for a record type, the struct is declared when
+    /// the type is used for the first time.
+    fn record(&mut self, ty: TypeId, record: TypeId) -> String {
+        if let Some(name) = self.structs.get(&ty) {
+            return name.clone();
+        }
+
+        // every instance of a generic record is a struct of its own
+        let name = self.names.item(Entity::Type(self.ty.instance_name(ty)));
+        self.structs.insert(ty, name.clone());
+
+        let fields = match self.ty.types.get(record) {
+            Some(Type::Record { fields }) => fields.clone(),
+            _ => vec![],
+        };
+        let field_defs = self.field_defs(ty);
+        let names = self.field_names(ty);
+        let mut decl = format!("struct {}\n{{\n", name);
+        for (index, ((_, field_ty), name)) in fields.into_iter().zip(names).enumerate() {
+            let relaxed = field_defs
+                .get(index)
+                .is_some_and(|def| self.ty.relaxed_precision.contains(def));
+            let field = self.declaration(field_ty, &name, relaxed);
+            writeln!(decl, "{}{};", INDENT, field).unwrap();
+        }
+        decl.push_str("};\n");
+        if !self.types.is_empty() {
+            self.types.push('\n');
+        }
+        self.types.push_str(&decl);
+        name
+    }
+
+    /// The name of the struct for an optional type, the struct is declared
+    /// with the helpers for `none()` and `unwrap_or` when the type is used
+    /// for the first time.
+    fn optional(&mut self, ty: TypeId, inner: TypeId) -> String {
+        if let Some(name) = self.structs.get(&ty) {
+            return name.clone();
+        }
+
+        let name = self.names.item(Entity::Type(self.ty.instance_name(ty)));
+        self.structs.insert(ty, name.clone());
+
+        let value = self.declaration(inner, "value", false);
+        let inner_name = self.type_name(inner);
+        let mut decl = format!(
+            "struct {}\n{{\n{}bool has_value;\n{}{};\n}};\n",
+            name, INDENT, INDENT, value
+        );
+        // GLSL ES has no default values of structs to construct an empty one
+        write!(
+            decl,
+            "\n{} thiol_{}_none()\n{{\n{}{} o;\n{}o.has_value = false;\n{}return o;\n}}\n",
+            name, name, INDENT, name, INDENT, INDENT
+        )
+        .unwrap();
+        write!(
+            decl,
+            "\n{} thiol_unwrap_or({} o, {} d)\n{{\n{}return o.has_value ? o.value : d;\n}}\n",
+            inner_name, name, inner_name, INDENT
+        )
+        .unwrap();
+        if !self.types.is_empty() {
+            self.types.push('\n');
+        }
+        self.types.push_str(&decl);
+        name
+    }
+
+    /// The definitions of the fields of a record type, in order.
+    fn field_defs(&self, ty: TypeId) -> Vec<Id<VariableDef>> {
+        let distinct_id = match self.ty.types.get(ty) {
+            Some(Type::Distinct { distinct_id, .. }) => *distinct_id,
+            _ => return vec![],
+        };
+        let def = match self.ty.distinct_defs.get(&distinct_id) {
+            Some(def) => self.hir.type_defs[*def].rhs,
+            None => return vec![],
+        };
+        match &self.hir.type_def_rhss[def] {
+            hir::TypeDefinitionRhs::Record { fields } => fields.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// A variable of the type, values with relaxed precision and `half`
+    /// values get the `mediump` qualifier.
+    fn declaration(&mut self, ty: TypeId, name: &str, relaxed: bool) -> String {
+        format!(
+            "{}{} {}",
+            self.precision(ty, relaxed),
+            self.type_name(ty),
+            name
+        )
+    }
+
+    fn precision(&self, ty: TypeId, relaxed: bool) -> &'static str {
+        if !self.ty.allows_relaxed_precision(ty) {
+            return "";
+        }
+        if relaxed || self.is_half(ty) {
+            "mediump "
+        } else {
+            ""
+        }
+    }
+
+    fn is_half(&self, ty: TypeId) -> bool {
+        match self.ty.types.get(ty) {
+            Some(Type::Half) | Some(Type::HalfVec { .. }) => true,
+            Some(Type::Array { base, .. }) | Some(Type::OpenArray { base }) => self.is_half(*base),
+            Some(Type::Distinct { inner, .. }) | Some(Type::Normalized { inner }) => {
+                self.is_half(*inner)
+            }
+            _ => false,
+        }
+    }
+
+    fn scalar(&self, ty: TypeId) -> Option<Scalar> {
+        match self.ty.types.get(ty)? {
+            Type::Bool | Type::BoolVec { .. } => Some(Scalar::Bool),
+            Type::Int | Type::IntVec { .. } => Some(Scalar::Int),
+            Type::UInt | Type::UIntVec { .. } => Some(Scalar::UInt),
+            Type::Float
+            | Type::Half
+            | Type::Radians
+            | Type::Degrees
+            | Type::FloatVec { .. }
+            | Type::HalfVec { .. }
+            | Type::FloatMat { .. } => Some(Scalar::Float),
+            Type::Distinct { inner, .. } | Type::Normalized { inner } => self.scalar(*inner),
+            _ => None,
+        }
+    }
+
+    fn expr_scalar(&self, e: Id<Expression>) -> Option<Scalar> {
+        self.scalar(self.expr_type(e)?)
+    }
+
+    fn symbol_type(&self, sym: Symbol) -> TypeId {
+        let ty = self.ty.references.symbol_type(sym).unwrap_or_else(|| {
+            self.ty
+                .types
+                .id_of(&Type::Error)
+                .expect("symbols without a type only exist after errors")
+        });
+        self.concrete(ty)
+    }
+
+    /// The names of the fields of a record type.
+    fn field_names(&self, ty: TypeId) -> Vec<String> {
+        let fields = self.ty.record_fields(ty).unwrap_or_default();
+        self.names
+            .fields(fields.iter().map(|(name, _)| name.as_str()))
+    }
+
+    /// The name of a field of a value of the type, or of the components of
+    /// a vector.
+    fn field_name(&self, ty: Option<TypeId>, field: &str) -> String {
+        let fields = ty.and_then(|ty| self.ty.record_fields(ty));
+        let index = fields.and_then(|fields| fields.iter().position(|(name, _)| name == field));
+        match (ty, index) {
+            (Some(ty), Some(index)) => self.field_names(ty).swap_remove(index),
+            _ => self.names.escape(field),
+        }
+    }
+
+    fn expr_type(&self, id: Id<Expression>) -> Option<TypeId> {
+        let ty = self.ty.expr_types.get(&id)?;
+        Some(self.concrete(*ty))
+    }
+
+    /// The type a type of the function being emitted has in the instance
+    /// being emitted.
+    fn concrete(&self, ty: TypeId) -> TypeId {
+        match self.instance {
+            Some(instance) => instance.concrete(ty),
+            None => ty,
+        }
+    }
+
+    fn constant_type(&self, id: Id<VariableDef>) -> TypeId {
+        let name = &self.hir.identifiers[self.hir.variable_defs[id].name];
+        match self.ty.consts.get(name) {
+            Some(sig) => sig.type_,
+            None => self.symbol_type(Symbol::Constant(id)),
+        }
+    }
+
+    /// The comment marking a function or program with a profiling label,
+    /// empty for the others.
+    fn profile_marker(&self, callable: Callable) -> String {
+        match self.ty.profile_labels.get(&callable) {
+            Some(label) => format!("{}// profile: {:?}\n", INDENT, label),
+            None => String::new(),
+        }
+    }
+
+    /// The functions a program calls, directly or indirectly, in the order
+    /// they are declared. Generic functions are emitted once for every
+    /// instance.
+    fn reachable_functions(
+        &self,
+        program: Id<Program>,
+    ) -> Vec<(Id<Function>, Option<&'a Instance>)> {
+        let mut callables = vec![Callable::Program(program)];
+        let mut i = 0;
+        while i < callables.len() {
+            for (callee, _) in self.ty.call_graph.dependencies(callables[i]) {
+                if !callables.contains(&callee) {
+                    callables.push(callee);
+                }
+            }
+            i += 1;
+        }
+        self.module
+            .functions
+            .iter()
+            .copied()
+            .filter(|id| callables.contains(&Callable::Function(*id)))
+            .flat_map(|id| {
+                if self.hir.functions[id].generics.is_empty() {
+                    return vec![(id, None)];
+                }
+                let instances = self.ty.instances.iter().filter(|i| i.func == id);
+                instances.map(|instance| (id, Some(instance))).collect()
+            })
+            .collect()
+    }
+
+    fn program(&mut self, id: Id<Program>) -> Option<Shader> {
+        let prog = &self.hir.programs[id];
+        let name = self.name(prog.name);
+        let stage = match typeck::stages::program_stage(self.hir, id) {
+            Some(stage) => stage,
+            None => {
+                self.errs.push(Error::ProgramWithoutStage {
+                    name: self.hir.identifiers[prog.name].clone(),
+                    loc: self.hir.identifier_fcs[&prog.name],
+                });
+                return None;
+            }
+        };
+
+        let mut items = vec![];
+
+        let mut blocks = String::new();
+        for resource in self.ty.program_resources(self.hir, self.module, id) {
+            if resource.class != BufferClass::Uniform {
+                continue;
+            }
+            if !blocks.is_empty() {
+                blocks.push('\n');
+            }
+            if let Some(binding) = resource.binding {
+                writeln!(
+                    blocks,
+                    "// set {}, binding {}",
+                    binding.binding.set, binding.binding.binding
+                )
+                .unwrap();
+            }
+            let buffer = self.names.item(Entity::Constant(resource.name.clone()));
+            let ty = self.constant_type(resource.constant);
+            let member = self.declaration(ty, &buffer, false);
+            let matrices = self
+                .ty
+                .matrix_layouts_in(ty, self.ty.matrix_layout_of(resource.constant));
+            let qualifiers = if !matrices.contains(&MatrixLayout::RowMajor) {
+                "std140"
+            } else if matrices.contains(&MatrixLayout::ColumnMajor) {
+                let def = &self.hir.variable_defs[resource.constant];
+                self.errs.push(Error::MixedMatrixLayouts {
+                    name: resource.name.clone(),
+                    loc: self.hir.identifier_fcs[&def.name],
+                });
+                "std140"
+            } else {
+                "std140, row_major"
+            };
+            writeln!(
+                blocks,
+                "layout({}) uniform {}_block\n{{\n{}{};\n}};",
+                qualifiers, buffer, INDENT, member
+            )
+            .unwrap();
+        }
+        if !blocks.is_empty() {
+            items.push(blocks);
+        }
+
+        // inputs and outputs
+        let mut interface = String::new();
+        let mut prologue = String::new();
+        let mut epilogue = String::new();
+        self.names.push_scope();
+        for (index, input) in prog.inputs.iter().enumerate() {
+            let def = &self.hir.variable_defs[*input];
+            let input_name = self.local(Symbol::Local(*input), def.name);
+            let ty = self.symbol_type(Symbol::Local(*input));
+            let relaxed = self.ty.relaxed_precision.contains(input);
+            let decl = self.declaration(ty, &input_name, relaxed);
+
+            if let Some(builtin) = self.builtin(*input, stage) {
+                writeln!(interface, "{};", decl).unwrap();
+                let ty_name = self.type_name(ty);
+                writeln!(
+                    prologue,
+                    "{}{} = {}({});",
+                    INDENT, input_name, ty_name, builtin
+                )
+                .unwrap();
+                continue;
+            }
+            match stage {
+                Stage::Vertex => {
+                    let location = self.location(*input).unwrap_or(index);
+                    writeln!(interface, "layout(location = {}) in {};", location, decl).unwrap()
+                }
+                _ => writeln!(interface, "{}in {};", self.interpolation(*input), decl).unwrap(),
+            }
+        }
+
+        let mut position = false;
+        for (index, output) in prog.outputs.iter().enumerate() {
+            let def = &self.hir.variable_defs[*output];
+            let output_name = self.local(Symbol::Local(*output), def.name);
+            let ty = self.symbol_type(Symbol::Local(*output));
+            let relaxed = self.ty.relaxed_precision.contains(output);
+            let decl = self.declaration(ty, &output_name, relaxed);
+            match stage {
+                Stage::Vertex if self.has_attribute(*output, "Position") => {
+                    position = true;
+                    writeln!(interface, "{};", decl).unwrap();
+                    writeln!(epilogue, "{}gl_Position = {};", INDENT, output_name).unwrap();
+                }
+                Stage::Vertex => {
+                    writeln!(interface, "{}out {};", self.interpolation(*output), decl).unwrap()
+                }
+                _ => {
+                    let location = self.location(*output).unwrap_or(index);
+                    writeln!(interface, "layout(location = {}) out {};", location, decl).unwrap()
+                }
+            }
+        }
+        if stage == Stage::Vertex && !prog.outputs.is_empty() && !position {
+            self.errs.push(Error::MissingPosition {
+                program: self.hir.identifiers[prog.name].clone(),
+                loc: self.hir.identifier_fcs[&prog.name],
+            });
+        }
+        if !interface.is_empty() {
+            items.push(interface);
+        }
+
+        let hir_ctx = self.hir;
+        let consts = self
+            .module
+            .consts
+            .iter()
+            .filter(|id| buffer_class(hir_ctx, **id).is_none())
+            .map(|id| self.constant(*id))
+            .collect::<String>();
+        if !consts.is_empty() {
+            items.push(consts);
+        }
+
+        let functions = self.reachable_functions(id);
+        if !functions.is_empty() {
+            let sigs = functions
+                .iter()
+                .map(|(func, instance)| {
+                    self.instance = *instance;
+                    (*func, *instance, self.function_signature(*func))
+                })
+                .collect::<Vec<_>>();
+            items.push(
+                sigs.iter()
+                    .map(|(_, _, sig)| format!("{};\n", sig))
+                    .collect(),
+            );
+            for (func, instance, sig) in sigs {
+                self.instance = instance;
+                items.push(self.function(func, sig));
+            }
+            self.instance = None;
+        }
+
+        let mut body = format!("void {}()\n{{\n", name);
+        body.push_str(&self.profile_marker(Callable::Program(id)));
+        self.ret = None;
+        self.block(&mut body, &prog.body, 1);
+        self.names.pop_scope();
+        body.push_str("}\n");
+        items.push(body);
+        items.push(format!(
+            "void main()\n{{\n{}{}{}();\n{}}}\n",
+            prologue, INDENT, name, epilogue
+        ));
+
+        let mut source =
+            String::from("#version 300 es\n\nprecision highp float;\nprecision highp int;\n");
+        if self.colours {
+            source.push('\n');
+            source.push_str(COLOURS);
+        }
+        for (func, ty) in &self.saturating {
+            source.push('\n');
+            source.push_str(&saturating_helper(func, ty));
+        }
+        for (n, ty) in &self.selects {
+            source.push('\n');
+            source.push_str(&select_helper(*n, ty));
+        }
+        if !self.types.is_empty() {
+            source.push('\n');
+            source.push_str(&self.types);
+        }
+        for item in items {
+            source.push('\n');
+            source.push_str(&item);
+        }
+        Some(Shader {
+            program: self.hir.identifiers[prog.name].clone(),
+            stage,
+            source,
+            names: self.names.table(),
+        })
+    }
+
+    /// The interpolation qualifiers of a varying, with a space after them.
+    fn interpolation(&self, def: Id<VariableDef>) -> String {
+        let interpolation = match self.ty.interpolation.get(&def) {
+            Some(interpolation) => interpolation,
+            None => return String::new(),
+        };
+        let mode = match interpolation.mode {
+            InterpolationMode::Perspective => "",
+            InterpolationMode::Flat => "flat ",
+            // rejected by the `gles3` profile
+            InterpolationMode::Linear => "noperspective ",
+        };
+        let centroid = if interpolation.centroid {
+            "centroid "
+        } else {
+            ""
+        };
+        format!("{}{}", mode, centroid)
+    }
+
+    fn has_attribute(&self, def: Id<VariableDef>, name: &str) -> bool {
+        self.hir.variable_defs[def]
+            .attrs
+            .iter()
+            .any(|attr| self.hir.identifiers[self.hir.attributes[*attr].name] == name)
+    }
+
+    /// The builtin an input of a program is read from.
+    fn builtin(&self, def: Id<VariableDef>, stage: Stage) -> Option<&'static str> {
+        let builtins: &[(&str, &str)] = match stage {
+            Stage::Vertex => &[
+                ("VertexIndex", "gl_VertexID"),
+                ("InstanceIndex", "gl_InstanceID"),
+            ],
+            Stage::Fragment => &[
+                ("Position", "gl_FragCoord"),
+                ("FrontFacing", "gl_FrontFacing"),
+            ],
+            Stage::Compute
+            | Stage::RayGeneration
+            | Stage::ClosestHit
+            | Stage::AnyHit
+            | Stage::Miss
+            | Stage::Task
+            | Stage::Mesh
+            | Stage::TessellationControl
+            | Stage::TessellationEvaluation
+            | Stage::Geometry => &[],
+        };
+        builtins
+            .iter()
+            .find(|(attr, _)| self.has_attribute(def, attr))
+            .map(|(_, builtin)| *builtin)
+    }
+
+    /// The argument of the `Location` attribute of an input or output.
+    fn location(&self, def: Id<VariableDef>) -> Option<usize> {
+        self.hir.variable_defs[def].attrs.iter().find_map(|attr| {
+            let attr = &self.hir.attributes[*attr];
+            if self.hir.identifiers[attr.name] != "Location" {
+                return None;
+            }
+            match self.hir.expressions[*attr.pos_args.first()?] {
+                Expression::Literal(hir::Literal::Integer(n, _)) => Some(n as usize),
+                _ => None,
+            }
+        })
+    }
+
+    fn constant(&mut self, id: Id<VariableDef>) -> String {
+        let def = &self.hir.variable_defs[id];
+        let ty = self.constant_type(id);
+        let relaxed = self.ty.relaxed_precision.contains(&id);
+        let decl = self.declaration(ty, &self.name(def.name), relaxed);
+        match def.rhs {
+            Some(rhs) if consteval::is_evaluated_constant(self.ty, self.hir, id) => {
+                let rhs = match Evaluator::new(self.ty, self.hir).eval_constant(rhs) {
+                    Ok(value) => self.composite(ty, &value),
+                    Err(_) => self.typed_expr(rhs, None),
+                };
+                format!("const {} = {};\n", decl, rhs)
+            }
+            Some(rhs) => {
+                let rhs = self.typed_expr(rhs, self.scalar(ty));
+                format!("const {} = {};\n", decl, rhs)
+            }
+            None => format!("{};\n", decl),
+        }
+    }
+
+    /// A value known when compiling, vectors, records and arrays as calls of
+    /// their constructors.
+    fn composite(&mut self, ty: TypeId, value: &Constant) -> String {
+        let values = match value {
+            Constant::Scalar(value) => return value.to_string(),
+            Constant::Composite(values) => values,
+        };
+        let types = self
+            .ty
+            .composite_types(ty)
+            .unwrap_or_else(|| vec![ty; values.len()]);
+        let values = types
+            .iter()
+            .zip(values)
+            .map(|(ty, value)| self.composite(*ty, value))
+            .collect::<Vec<_>>();
+        format!("{}({})", self.type_name(ty), values.join(", "))
+    }
+
+    fn function_signature(&mut self, id: Id<Function>) -> String {
+        let func = &self.hir.functions[id];
+        let sig = self.ty.function_sigs[&self.hir.identifiers[func.name]].clone();
+
+        let mut params = vec![];
+        self.names.push_scope();
+        for (index, (param, _, mode)) in func.args.iter().enumerate() {
+            let param = self.local(Symbol::Parameter { func: id, index }, *param);
+            let decl = self.declaration(self.concrete(sig.args[index].1), &param, false);
+            params.push(match mode {
+                ParamMode::In => decl,
+                ParamMode::Out => format!("out {}", decl),
+                ParamMode::InOut => format!("inout {}", decl),
+            });
+        }
+        self.names.pop_scope();
+        let ret = self.concrete(sig.ret);
+        let generics = self.instance.map_or(&[][..], |instance| &instance.generics);
+        let name = &self.hir.identifiers[func.name];
+        let name = self.names.item(Entity::function(self.ty, name, generics));
+        format!(
+            "{}{} {}({})",
+            self.precision(ret, false),
+            self.type_name(ret),
+            name,
+            params.join(", ")
+        )
+    }
+
+    fn function(&mut self, id: Id<Function>, sig: String) -> String {
+        let func = &self.hir.functions[id];
+        self.ret = self
+            .ty
+            .function_sigs
+            .get(&self.hir.identifiers[func.name])
+            .map(|sig| self.concrete(sig.ret));
+        if let Some(table) = self.ty.lookup_tables.get(&id) {
+            return self.lookup_table(id, table, sig);
+        }
+        let mut src = format!("{}\n{{\n", sig);
+        src.push_str(&self.profile_marker(Callable::Function(id)));
+        self.names.push_scope();
+        for (index, (param, _, _)) in func.args.iter().enumerate() {
+            self.local(Symbol::Parameter { func: id, index }, *param);
+        }
+        self.block(&mut src, &func.body, 1);
+        self.names.pop_scope();
+        src.push_str("}\n");
+        src
+    }
+
+    /// A function with a `@lut` attribute, as a constant array of its values
+    /// and a lookup of the entry of its argument.
+    fn lookup_table(&mut self, id: Id<Function>, table: &LookupTable, sig: String) -> String {
+        let func = &self.hir.functions[id];
+        let ret = self
+            .ret
+            .expect("functions with lookup tables return a value");
+        let size = table.values.len();
+        let name = &self.hir.identifiers[func.name];
+        let lut = self.names.item(Entity::Constant(format!("{}_lut", name)));
+        let values = table
+            .values
+            .iter()
+            .map(|value| self.composite(ret, value))
+            .collect::<Vec<_>>();
+        let arg = self.name(func.args[0].0);
+        let index = match table.domain {
+            LutDomain::Int => format!("clamp({}, 0, {})", arg, size - 1),
+            LutDomain::UInt => format!("min({}, {}u)", arg, size - 1),
+            LutDomain::Float => format!(
+                "int(round(clamp({}, 0.0, 1.0) * {:?}))",
+                arg,
+                (size - 1) as f32
+            ),
+        };
+        let ty = self.type_name(ret);
+        format!(
+            "const {}{}[{}] {} = {}[{}]({});\n{}\n{{\n{}return {}[{}];\n}}\n",
+            self.precision(ret, false),
+            ty,
+            size,
+            lut,
+            ty,
+            size,
+            values.join(", "),
+            sig,
+            INDENT,
+            lut,
+            index
+        )
+    }
+
+    fn block(&mut self, src: &mut String, block: &[Id<Statement>], depth: usize) {
+        self.names.push_scope();
+        for stmt in block {
+            self.statement(src, *stmt, depth);
+        }
+        self.names.pop_scope();
+    }
+
+    fn statement(&mut self, src: &mut String, id: Id<Statement>, depth: usize) {
+        let indent = INDENT.repeat(depth);
+        match &self.hir.statements[id] {
+            Statement::Var(var) => {
+                let def = &self.hir.variable_defs[*var];
+                let ty = self.symbol_type(Symbol::Local(*var));
+                let relaxed = self.ty.relaxed_precision.contains(var);
+                let name = self.local(Symbol::Local(*var), def.name);
+                let decl = self.declaration(ty, &name, relaxed);
+                match def.rhs {
+                    Some(rhs) => {
+                        let rhs = self.typed_expr(rhs, self.scalar(ty));
+                        writeln!(src, "{}{} = {};", indent, decl, rhs).unwrap()
+                    }
+                    None => writeln!(src, "{}{};", indent, decl).unwrap(),
+                }
+                // optionals without a value start as `none()`
+                if def.rhs.is_none() && self.ty.optional_inner(ty).is_some() {
+                    writeln!(src, "{}{}.has_value = false;", indent, name).unwrap();
+                }
+            }
+            Statement::Becomes { lhs, rhs } => {
+                let rhs = self.typed_expr(*rhs, self.expr_scalar(*lhs));
+                writeln!(src, "{}{} = {};", indent, self.expr(*lhs), rhs).unwrap();
+            }
+            Statement::Destructure { lhs, rhs } => self.destructure(src, lhs, *rhs, depth),
+            Statement::Match { value, arms } => self.match_(src, id, *value, arms, depth),
+            Statement::Return(Some(e)) => {
+                let e = self.typed_expr(*e, self.ret.and_then(|ty| self.scalar(ty)));
+                writeln!(src, "{}return {};", indent, e).unwrap()
+            }
+            Statement::Return(None) => writeln!(src, "{}return;", indent).unwrap(),
+            Statement::Expr(e) => match self.ty.call_intrinsics.get(e) {
+                // GLSL ES has no debug output
+                Some(Intrinsic::Assert | Intrinsic::DebugPrint) => {}
+                _ => writeln!(src, "{}{};", indent, self.expr(*e)).unwrap(),
+            },
+            Statement::Break => writeln!(src, "{}break;", indent).unwrap(),
+            Statement::Continue => writeln!(src, "{}continue;", indent).unwrap(),
+            Statement::Discard => writeln!(src, "{}discard;", indent).unwrap(),
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                writeln!(src, "{}if ({})", indent, self.expr(*cond)).unwrap();
+                writeln!(src, "{}{{", indent).unwrap();
+                self.block(src, then_body, depth + 1);
+                writeln!(src, "{}}}", indent).unwrap();
+                if !else_body.is_empty() {
+                    writeln!(src, "{}else", indent).unwrap();
+                    writeln!(src, "{}{{", indent).unwrap();
+                    self.block(src, else_body, depth + 1);
+                    writeln!(src, "{}}}", indent).unwrap();
+                }
+            }
+            Statement::For {
+                iter_name,
+                loop_type,
+                from,
+                to,
+                body,
+            } => {
+                let ty = self.symbol_type(Symbol::LoopVariable(id));
+                let scalar = self.scalar(ty);
+                let ty = self.type_name(ty);
+                let from = self.typed_expr(*from, scalar);
+                let to = self.typed_expr(*to, scalar);
+                self.names.push_scope();
+                let name = self.local(Symbol::LoopVariable(id), *iter_name);
+                // both bounds are included
+                let (cmp, step) = match loop_type {
+                    hir::ForLoopType::Up => ("<=", "++"),
+                    hir::ForLoopType::Down => (">=", "--"),
+                };
+                writeln!(
+                    src,
+                    "{}for ({} {} = {}; {} {} {}; {}{})",
+                    indent, ty, name, from, name, cmp, to, name, step
+                )
+                .unwrap();
+                writeln!(src, "{}{{", indent).unwrap();
+                self.block(src, body, depth + 1);
+                writeln!(src, "{}}}", indent).unwrap();
+                self.names.pop_scope();
+            }
+        }
+    }
+
+    /// Assign the fields of a record to the targets, the record is stored
+    /// in a variable of a block of its own so that it's computed once.
+    fn destructure(
+        &mut self,
+        src: &mut String,
+        lhs: &[Id<Expression>],
+        rhs: Id<Expression>,
+        depth: usize,
+    ) {
+        let indent = INDENT.repeat(depth);
+        let inner = INDENT.repeat(depth + 1);
+        let ty = match self.expr_type(rhs) {
+            Some(ty) => ty,
+            None => return,
+        };
+        let fields = self.field_names(ty);
+
+        let decl = self.declaration(ty, "thiol_values", false);
+        writeln!(src, "{}{{", indent).unwrap();
+        writeln!(src, "{}{} = {};", inner, decl, self.expr(rhs)).unwrap();
+        for (target, field) in lhs.iter().zip(fields) {
+            let target = self.expr(*target);
+            writeln!(src, "{}{} = thiol_values.{};", inner, target, field).unwrap();
+        }
+        writeln!(src, "{}}}", indent).unwrap();
+    }
+
+    /// Run the arm for the case of an optional, the optional is stored in a
+    /// variable of a block of its own so that it's computed once.
+    fn match_(
+        &mut self,
+        src: &mut String,
+        id: Id<Statement>,
+        value: Id<Expression>,
+        arms: &[hir::MatchArm],
+        depth: usize,
+    ) {
+        let indent = INDENT.repeat(depth);
+        let inner = INDENT.repeat(depth + 1);
+        let ty = match self.expr_type(value) {
+            Some(ty) => ty,
+            None => return,
+        };
+        let some = arms
+            .iter()
+            .find(|arm| matches!(arm.pattern, MatchPattern::Some(_)));
+        let none = arms
+            .iter()
+            .find(|arm| matches!(arm.pattern, MatchPattern::None));
+
+        let decl = self.declaration(ty, "thiol_optional", false);
+        writeln!(src, "{}{{", indent).unwrap();
+        writeln!(src, "{}{} = {};", inner, decl, self.expr(value)).unwrap();
+        writeln!(src, "{}if (thiol_optional.has_value)", inner).unwrap();
+        writeln!(src, "{}{{", inner).unwrap();
+        if let Some(arm) = some {
+            self.names.push_scope();
+            if let MatchPattern::Some(name) = arm.pattern {
+                let binding_ty = self.symbol_type(Symbol::MatchBinding(id));
+                let name = self.local(Symbol::MatchBinding(id), name);
+                let binding = self.declaration(binding_ty, &name, false);
+                writeln!(
+                    src,
+                    "{}{}{} = thiol_optional.value;",
+                    inner, INDENT, binding
+                )
+                .unwrap();
+            }
+            self.block(src, &arm.body, depth + 2);
+            self.names.pop_scope();
+        }
+        writeln!(src, "{}}}", inner).unwrap();
+        if let Some(arm) = none.filter(|arm| !arm.body.is_empty()) {
+            writeln!(src, "{}else", inner).unwrap();
+            writeln!(src, "{}{{", inner).unwrap();
+            self.block(src, &arm.body, depth + 2);
+            writeln!(src, "{}}}", inner).unwrap();
+        }
+        writeln!(src, "{}}}", indent).unwrap();
+    }
+
+    fn expr(&mut self, id: Id<Expression>) -> String {
+        self.typed_expr(id, None)
+    }
+
+    /// An expression where a value with components of type `expected` is
+    /// needed. GLSL ES has no implicit conversions, so literals are written
+    /// with the type of the value they are combined with.
+    fn typed_expr(&mut self, id: Id<Expression>, expected: Option<Scalar>) -> String {
+        let value = self.expr_value(id, expected);
+        match self.ty.space_transforms.get(&id) {
+            Some(transform) => self.space_transform(value, transform),
+            None => value,
+        }
+    }
+
+    /// Apply the transforms between two spaces to a vector.
+    fn space_transform(&mut self, value: String, transform: &SpaceTransform) -> String {
+        let mut value = match transform.extended {
+            Some((ty, w)) => format!("{}({}, {:?})", self.type_name(ty), value, w),
+            None => value,
+        };
+        for step in &transform.steps {
+            let matrix = self.names.item(Entity::Constant(step.via.clone()));
+            value = if step.inverse {
+                format!("(inverse({}) * {})", matrix, value)
+            } else {
+                format!("({} * {})", matrix, value)
+            };
+        }
+        match transform.extended {
+            Some(_) => format!("{}.{}", value, &"xyzw"[..transform.components]),
+            None => value,
+        }
+    }
+
+    fn expr_value(&mut self, id: Id<Expression>, expected: Option<Scalar>) -> String {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            Expression::Literal(hir::Literal::Integer(n, suffix)) => {
+                let scalar = match suffix {
+                    Some(_) => self.expr_scalar(id),
+                    None => expected.or_else(|| self.expr_scalar(id)),
+                };
+                match scalar {
+                    Some(Scalar::UInt) => format!("{}u", n),
+                    Some(Scalar::Float) => format!("{}.0", n),
+                    _ => n.to_string(),
+                }
+            }
+            Expression::Literal(hir::Literal::Float(f, _)) => format!("{:?}", f),
+            Expression::Literal(hir::Literal::Bool(b)) => b.to_string(),
+            Expression::Literal(hir::Literal::String(s)) => format!("{:?}", self.hir.strings[*s]),
+            Expression::Variable(name) => self.name(*name),
+            Expression::PrimitiveOp(op) => {
+                let binary = |e: &mut Self, a: Id<Expression>, op: &str, b: Id<Expression>| {
+                    let (sa, sb) = (e.expr_scalar(a), e.expr_scalar(b));
+                    let a = e.typed_expr(a, sb.or(expected));
+                    let b = e.typed_expr(b, sa.or(expected));
+                    format!("({} {} {})", a, op, b)
+                };
+                // vectors are compared component by component with functions
+                let compare = |e: &mut Self, a: Id<Expression>, op: &str, func: &str, b| {
+                    let ty = e.expr_type(id).and_then(|ty| e.ty.types.get(ty));
+                    if !matches!(ty, Some(Type::BoolVec { .. })) {
+                        return binary(e, a, op, b);
+                    }
+                    let (a, b) = (e.typed_expr(a, None), e.typed_expr(b, None));
+                    format!("{}({}, {})", func, a, b)
+                };
+                match &self.hir.prim_ops[*op] {
+                    PO::Neg(e) => format!("(-{})", self.typed_expr(*e, expected)),
+                    PO::Pos(e) => self.typed_expr(*e, expected),
+                    PO::Add(a, b) => self.arithmetic(*a, "+", *b, expected),
+                    PO::Sub(a, b) => self.arithmetic(*a, "-", *b, expected),
+                    PO::Mul(a, b) => self.arithmetic(*a, "*", *b, expected),
+                    PO::Div(a, b) => binary(self, *a, "/", *b),
+                    PO::Mod(a, b) => {
+                        let float = self.expr_scalar(*a) == Some(Scalar::Float)
+                            || self.expr_scalar(*b) == Some(Scalar::Float);
+                        if float {
+                            let a = self.typed_expr(*a, Some(Scalar::Float));
+                            let b = self.typed_expr(*b, Some(Scalar::Float));
+                            format!("mod({}, {})", a, b)
+                        } else {
+                            binary(self, *a, "%", *b)
+                        }
+                    }
+                    PO::Gt(a, b) => compare(self, *a, ">", "greaterThan", *b),
+                    PO::Gte(a, b) => compare(self, *a, ">=", "greaterThanEqual", *b),
+                    PO::Lt(a, b) => compare(self, *a, "<", "lessThan", *b),
+                    PO::Lte(a, b) => compare(self, *a, "<=", "lessThanEqual", *b),
+                    PO::Eq(a, b) => compare(self, *a, "==", "equal", *b),
+                    PO::Neq(a, b) => compare(self, *a, "!=", "notEqual", *b),
+                    PO::Constructor {
+                        ty,
+                        pos_args,
+                        nam_args,
+                    } => {
+                        let (ty, scalar) = primitive_type(ty);
+                        let args = pos_args
+                            .iter()
+                            .chain(nam_args.iter().map(|(_, e)| e))
+                            .map(|e| self.typed_expr(*e, scalar))
+                            .collect::<Vec<_>>();
+                        format!("{}({})", ty, args.join(", "))
+                    }
+                }
+            }
+            Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => {
+                if let Some(intrinsic) = self.ty.call_intrinsics.get(&id) {
+                    return self.intrinsic(id, *intrinsic, pos_args);
+                }
+                match (self.ty.resolutions.get(*name), pos_args.as_slice()) {
+                    (
+                        Some(Resolution::Derived {
+                            derive: Derive::New,
+                            ..
+                        }),
+                        _,
+                    ) => return self.record_constructor(id, pos_args, nam_args),
+                    // `==` compares structs and arrays by their fields and
+                    // elements
+                    (
+                        Some(Resolution::Derived {
+                            derive: Derive::Eq, ..
+                        }),
+                        [a, b],
+                    ) => return format!("({} == {})", self.expr(*a), self.expr(*b)),
+                    _ => {}
+                }
+                let func = match self.ty.references.symbol(self.hir.identifier_fcs[name]) {
+                    Some(Symbol::Function(func)) => func,
+                    Some(Symbol::Type(_)) => {
+                        return self.record_constructor(id, pos_args, nam_args)
+                    }
+                    _ => return format!("{}()", self.name(*name)),
+                };
+                let sig = self.ty.function_sigs[&self.hir.identifiers[*name]].clone();
+                let params = &self.hir.functions[func].args;
+                let mut args = vec![String::new(); params.len()];
+                for (index, e) in pos_args.iter().enumerate().take(params.len()) {
+                    let scalar = self.scalar(self.concrete(sig.args[index].1));
+                    args[index] = self.typed_expr(*e, scalar);
+                }
+                for (arg_name, e) in nam_args {
+                    let index = params.iter().position(|(n, _, _)| {
+                        self.hir.identifiers[*n] == self.hir.identifiers[*arg_name]
+                    });
+                    if let Some(index) = index {
+                        let scalar = self.scalar(self.concrete(sig.args[index].1));
+                        args[index] = self.typed_expr(*e, scalar);
+                    }
+                }
+                let callee = match self.ty.call_generics.get(&id) {
+                    Some(generics) => {
+                        let generics = generics
+                            .iter()
+                            .map(|ty| self.concrete(*ty))
+                            .collect::<Vec<_>>();
+                        let name = &self.hir.identifiers[*name];
+                        self.names.item(Entity::function(self.ty, name, &generics))
+                    }
+                    None => self.name(*name),
+                };
+                format!("{}({})", callee, args.join(", "))
+            }
+            Expression::Field { base, name } => {
+                let field = self.field_name(self.expr_type(*base), &self.hir.identifiers[*name]);
+                format!("{}.{}", self.expr(*base), field)
+            }
+            Expression::Index { base, index } => {
+                if let Some((array, lo, hi)) = self.slice_view(*base) {
+                    self.check_trap(id);
+                    let index = self.int_expr(*index);
+                    return format!(
+                        "{}[{} + clamp({}, 0, {} - {} - 1)]",
+                        array, lo, index, hi, lo
+                    );
+                }
+                let len = self.expr_type(*base).and_then(|ty| self.ty.array_len(ty));
+                let literal = matches!(self.hir.expressions[*index], Expression::Literal(_));
+                match (self.ty.bounds_check, len) {
+                    (BoundsCheck::Clamp, Some(len)) if !literal => {
+                        let index = self.int_expr(*index);
+                        format!("{}[clamp({}, 0, {})]", self.expr(*base), index, len - 1)
+                    }
+                    (BoundsCheck::Trap, Some(_)) if !literal => {
+                        self.check_trap(id);
+                        format!("{}[{}]", self.expr(*base), self.expr(*index))
+                    }
+                    _ => format!("{}[{}]", self.expr(*base), self.expr(*index)),
+                }
+            }
+            // the type checker only allows slices that are indexed
+            Expression::Slice { base, .. } => self.expr(*base),
+            Expression::As { base, .. } => {
+                let ty = match self.expr_type(id) {
+                    Some(ty) => self.type_name(ty),
+                    None => "void".to_string(),
+                };
+                format!("{}({})", ty, self.expr(*base))
+            }
+            Expression::LayoutQuery { .. } => {
+                // queries that can't be answered are reported by the checker
+                match Evaluator::new(self.ty, self.hir).eval(id) {
+                    Ok(value) => value.to_string(),
+                    Err(_) => "0u".to_string(),
+                }
+            }
+            Expression::Array(elements) => {
+                let ty = match self.expr_type(id) {
+                    Some(ty) => ty,
+                    None => return "void".to_string(),
+                };
+                let scalar = match self.ty.types.get(ty) {
+                    Some(Type::Array { base, .. }) => self.scalar(*base),
+                    _ => None,
+                };
+                let elements = elements
+                    .iter()
+                    .map(|e| self.typed_expr(*e, scalar))
+                    .collect::<Vec<_>>();
+                format!("{}({})", self.type_name(ty), elements.join(", "))
+            }
+        }
+    }
+
+    /// The constructor of a record, which takes the values of the fields in
+    /// the order they are declared.
+    fn record_constructor(
+        &mut self,
+        id: Id<Expression>,
+        pos_args: &[Id<Expression>],
+        nam_args: &[(Id<Identifier>, Id<Expression>)],
+    ) -> String {
+        let ty = match self.expr_type(id) {
+            Some(ty) => ty,
+            None => return "void".to_string(),
+        };
+        let fields = self.ty.record_fields(ty).unwrap_or_default();
+        let mut args = vec![String::new(); fields.len()];
+        let named = nam_args.iter().filter_map(|(name, e)| {
+            let name = &self.hir.identifiers[*name];
+            let index = fields.iter().position(|(field, _)| field == name)?;
+            Some((index, *e))
+        });
+        let values = pos_args.iter().copied().enumerate().chain(named);
+        for (index, e) in values.collect::<Vec<_>>() {
+            if let Some((_, field_ty)) = fields.get(index) {
+                args[index] = self.typed_expr(e, self.scalar(*field_ty));
+            }
+        }
+        format!("{}({})", self.type_name(ty), args.join(", "))
+    }
+
+    /// Report an index that is checked with the `trap` policy.
+    fn check_trap(&mut self, index: Id<Expression>) {
+        if self.ty.bounds_check == BoundsCheck::Trap {
+            self.errs.push(Error::TrapUnsupported {
+                loc: self.hir.expression_fcs[&index],
+            });
+        }
+    }
+
+    /// The array a slice is a view into and the bounds of the view in it.
+    fn slice_view(&mut self, id: Id<Expression>) -> Option<(String, String, String)> {
+        let (base, lo, hi) = match self.hir.expressions[id] {
+            Expression::Slice { base, lo, hi } => (base, lo, hi),
+            _ => return None,
+        };
+        let (lo, hi) = (self.int_expr(lo), self.int_expr(hi));
+        Some(match self.slice_view(base) {
+            Some((array, outer_lo, outer_hi)) => (
+                array,
+                format!("({} + {})", outer_lo, lo),
+                format!("min({} + {}, {})", outer_lo, hi, outer_hi),
+            ),
+            None => (self.expr(base), lo, hi),
+        })
+    }
+
+    /// An index or a bound of a slice as an `int`.
+    fn int_expr(&mut self, e: Id<Expression>) -> String {
+        let value = self.typed_expr(e, Some(Scalar::Int));
+        match self.expr_scalar(e) {
+            Some(Scalar::Int) => value,
+            _ => format!("int({})", value),
+        }
+    }
+
+    /// A sum, difference or product. Integer arithmetic wraps around in GLSL
+    /// ES, integer sums and differences saturate with the `saturate`
+    /// overflow policy.
+    fn arithmetic(
+        &mut self,
+        a: Id<Expression>,
+        op: &str,
+        b: Id<Expression>,
+        expected: Option<Scalar>,
+    ) -> String {
+        let (sa, sb) = (self.expr_scalar(a), self.expr_scalar(b));
+        let operands = [
+            (self.typed_expr(a, sb.or(expected)), self.expr_type(a)),
+            (self.typed_expr(b, sa.or(expected)), self.expr_type(b)),
+        ];
+        let integer = self
+            .ty
+            .integer_arithmetic_type(operands[0].1, operands[1].1)
+            .is_some();
+        match (self.ty.integer_overflow, op) {
+            (IntegerOverflow::Saturate, "+") if integer => self.saturating("add", operands),
+            (IntegerOverflow::Saturate, "-") if integer => self.saturating("sub", operands),
+            _ => format!("({} {} {})", operands[0].0, op, operands[1].0),
+        }
+    }
+
+    /// A call of a saturating helper, which takes operands of the same type,
+    /// so scalars combined with vectors are converted to the vector.
+    fn saturating(
+        &mut self,
+        func: &'static str,
+        operands: [(String, Option<TypeId>); 2],
+    ) -> String {
+        let ty = match self
+            .ty
+            .integer_arithmetic_type(operands[0].1, operands[1].1)
+        {
+            Some(ty) => ty,
+            None => return "void()".to_string(),
+        };
+        let ty_name = self.type_name(ty);
+        let [a, b] = operands.map(|(value, value_ty)| {
+            if value_ty == Some(ty) {
+                value
+            } else {
+                format!("{}({})", ty_name, value)
+            }
+        });
+        self.saturating.insert((func, ty_name));
+        format!("thiol_saturating_{}({}, {})", func, a, b)
+    }
+
+    fn intrinsic(
+        &mut self,
+        id: Id<Expression>,
+        intrinsic: Intrinsic,
+        args: &[Id<Expression>],
+    ) -> String {
+        let arg_types = args.iter().map(|e| self.expr_type(*e)).collect::<Vec<_>>();
+        let args = args.iter().map(|e| self.expr(*e)).collect::<Vec<_>>();
+        match intrinsic {
+            Intrinsic::Unpack => match arg_types[0].and_then(|ty| self.ty.types.get(ty)) {
+                Some(Type::Packed { format }) => unpack(*format, &args[0]),
+                _ => args[0].clone(),
+            },
+            Intrinsic::Dpdx => format!("dFdx({})", args[0]),
+            Intrinsic::Dpdy => format!("dFdy({})", args[0]),
+            Intrinsic::Fwidth => format!("fwidth({})", args[0]),
+            Intrinsic::Transpose | Intrinsic::Inverse | Intrinsic::Determinant => {
+                format!("{}({})", intrinsic.name(), args[0])
+            }
+            Intrinsic::Normalize
+            | Intrinsic::Reflect
+            | Intrinsic::Refract
+            | Intrinsic::Sin
+            | Intrinsic::Cos
+            | Intrinsic::Tan
+            | Intrinsic::Asin
+            | Intrinsic::Acos
+            | Intrinsic::Atan => format!("{}({})", intrinsic.name(), args.join(", ")),
+            Intrinsic::Atan2 => format!("atan({})", args.join(", ")),
+            Intrinsic::ToRadians => format!("radians({})", args[0]),
+            Intrinsic::ToDegrees => format!("degrees({})", args[0]),
+            Intrinsic::Identity => match self.expr_type(id) {
+                Some(ty) => format!("{}(1.0)", self.type_name(ty)),
+                None => "void()".to_string(),
+            },
+            Intrinsic::Some => match self.expr_type(id) {
+                Some(ty) => format!("{}(true, {})", self.type_name(ty), args[0]),
+                None => "void()".to_string(),
+            },
+            Intrinsic::None => match self.expr_type(id) {
+                Some(ty) => format!("thiol_{}_none()", self.type_name(ty)),
+                None => "void()".to_string(),
+            },
+            Intrinsic::UnwrapOr => format!("thiol_unwrap_or({})", args.join(", ")),
+            // left out as statements, they have no value
+            Intrinsic::Assert | Intrinsic::DebugPrint => "void()".to_string(),
+            Intrinsic::SrgbToLinear | Intrinsic::LinearToSrgb => {
+                self.colours = true;
+                format!("thiol_{}({})", intrinsic.name(), args[0])
+            }
+            Intrinsic::ApproxEq => {
+                let distance = format!("abs({} - {})", args[0], args[1]);
+                match arg_types[0] {
+                    Some(ty) if self.ty.is_float_vector(ty) => format!(
+                        "all(lessThanEqual({}, {}({})))",
+                        distance,
+                        self.type_name(ty),
+                        args[2]
+                    ),
+                    _ => format!("({} <= {})", distance, args[2]),
+                }
+            }
+            Intrinsic::Bitcast => {
+                let to = match self.expr_type(id) {
+                    Some(ty) => ty,
+                    None => return args[0].clone(),
+                };
+                let from_float = arg_types[0].is_some_and(|ty| self.ty.is_float(ty));
+                let func = match (from_float, self.ty.integer_scalar(to)) {
+                    (true, Some(Type::Int)) => "floatBitsToInt".to_string(),
+                    (true, Some(Type::UInt)) => "floatBitsToUint".to_string(),
+                    _ if !self.ty.is_float(to) => self.type_name(to),
+                    _ => match arg_types[0].and_then(|ty| self.ty.integer_scalar(ty)) {
+                        Some(Type::UInt) => "uintBitsToFloat".to_string(),
+                        _ => "intBitsToFloat".to_string(),
+                    },
+                };
+                format!("{}({})", func, args[0])
+            }
+            Intrinsic::Select => {
+                let n = arg_types[0].and_then(|ty| self.ty.vector_size(ty));
+                match (n, arg_types[1]) {
+                    (Some(_), Some(ty)) if self.ty.is_float(ty) => {
+                        format!("mix({}, {}, {})", args[2], args[1], args[0])
+                    }
+                    (Some(n), Some(ty)) => {
+                        let ty_name = self.type_name(ty);
+                        self.selects.insert((size(n), ty_name));
+                        format!("thiol_select({})", args.join(", "))
+                    }
+                    _ => format!("({} ? {} : {})", args[0], args[1], args[2]),
+                }
+            }
+            Intrinsic::Any | Intrinsic::All => format!("{}({})", intrinsic.name(), args[0]),
+            Intrinsic::WrappingAdd => format!("({} + {})", args[0], args[1]),
+            Intrinsic::WrappingSub => format!("({} - {})", args[0], args[1]),
+            Intrinsic::WrappingMul => format!("({} * {})", args[0], args[1]),
+            Intrinsic::SaturatingAdd | Intrinsic::SaturatingSub => {
+                let func = match intrinsic {
+                    Intrinsic::SaturatingAdd => "add",
+                    _ => "sub",
+                };
+                let operands = [
+                    (args[0].clone(), arg_types[0]),
+                    (args[1].clone(), arg_types[1]),
+                ];
+                self.saturating(func, operands)
+            }
+            // atomics, barriers and the workgroup memory they synchronize,
+            // the storage bindings of images, separate textures and samplers
+            // and ray tracing are rejected by the profile
+            Intrinsic::AtomicAdd
+            | Intrinsic::AtomicMin
+            | Intrinsic::AtomicMax
+            | Intrinsic::AtomicExchange
+            | Intrinsic::AtomicCompareExchange
+            | Intrinsic::WorkgroupBarrier
+            | Intrinsic::StorageBarrier
+            | Intrinsic::ImageLoad
+            | Intrinsic::ImageStore
+            | Intrinsic::Sample
+            | Intrinsic::SampleLod
+            | Intrinsic::SampleGrad
+            | Intrinsic::SampleCompare
+            | Intrinsic::Gather
+            | Intrinsic::Fetch
+            | Intrinsic::TraceRay
+            | Intrinsic::EmitVertex
+            | Intrinsic::EndPrimitive
+            | Intrinsic::SubgroupBallot
+            | Intrinsic::SubgroupShuffle
+            | Intrinsic::SubgroupBroadcast
+            | Intrinsic::SubgroupAdd
+            | Intrinsic::SubgroupMin
+            | Intrinsic::SubgroupMax => format!("{}({})", intrinsic.name(), args.join(", ")),
+        }
+    }
+}
+
+fn size(s: typeck::VecSize) -> usize {
+    match s {
+        typeck::VecSize::VS2 => 2,
+        typeck::VecSize::VS3 => 3,
+        typeck::VecSize::VS4 => 4,
+    }
+}
+
+/// The name of a primitive type and the type of its components.
+fn primitive_type(ty: &hir::PrimitiveType) -> (String, Option<Scalar>) {
+    use hir::PrimitiveType as PT;
+
+    let n = |s: &hir::VecSize| size((*s).into());
+    match ty {
+        PT::Bool => ("bool".to_string(), Some(Scalar::Bool)),
+        PT::Int => ("int".to_string(), Some(Scalar::Int)),
+        PT::UInt => ("uint".to_string(), Some(Scalar::UInt)),
+        PT::Float | PT::Half | PT::Radians | PT::Degrees => {
+            ("float".to_string(), Some(Scalar::Float))
+        }
+        PT::BoolVec { components } => (format!("bvec{}", n(components)), Some(Scalar::Bool)),
+        PT::IntVec { components, .. } => (format!("ivec{}", n(components)), Some(Scalar::Int)),
+        PT::UIntVec { components, .. } => (format!("uvec{}", n(components)), Some(Scalar::UInt)),
+        PT::FloatVec { components, .. } | PT::HalfVec { components, .. } => {
+            (format!("vec{}", n(components)), Some(Scalar::Float))
+        }
+        PT::FloatMat { cols, rows, .. } => {
+            let name = if n(cols) == n(rows) {
+                format!("mat{}", n(cols))
+            } else {
+                format!("mat{}x{}", n(cols), n(rows))
+            };
+            (name, Some(Scalar::Float))
+        }
+        PT::Packed { format } => (
+            packed_type((*format).into()).to_string(),
+            Some(Scalar::UInt),
+        ),
+        // rejected by the profile
+        PT::Double
+        | PT::DoubleVec { .. }
+        | PT::DoubleMat { .. }
+        | PT::Long
+        | PT::ULong
+        | PT::LongVec { .. }
+        | PT::ULongVec { .. }
+        | PT::AtomicInt
+        | PT::AtomicUInt => ("void".to_string(), None),
+    }
+}
+
+/// The integer type a packed value is stored in.
+fn packed_type(format: PackedFormat) -> &'static str {
+    match format.size() {
+        8 => "uvec2",
+        _ => "uint",
+    }
+}
+
+/// GLSL ES 3.0 only has builtins for the 16 bit formats, the 8 bit formats
+/// are unpacked with shifts.
+fn unpack(format: PackedFormat, arg: &str) -> String {
+    let bytes = format!("((uvec4({}) >> uvec4(0u, 8u, 16u, 24u)) & 255u)", arg);
+    let signed_bytes = format!("(ivec4(uvec4({}) << uvec4(24u, 16u, 8u, 0u)) >> 24)", arg);
+    match format {
+        PackedFormat::Unorm8x4 => format!("(vec4({}) / 255.0)", bytes),
+        PackedFormat::Snorm8x4 => format!("max(vec4({}) / 127.0, -1.0)", signed_bytes),
+        PackedFormat::Uint8x4 => bytes,
+        PackedFormat::Sint8x4 => signed_bytes,
+        PackedFormat::Unorm16x2 => format!("unpackUnorm2x16({})", arg),
+        PackedFormat::Snorm16x2 => format!("unpackSnorm2x16({})", arg),
+        PackedFormat::Float16x2 => format!("unpackHalf2x16({})", arg),
+        PackedFormat::Float16x4 => format!(
+            "vec4(unpackHalf2x16({}.x), unpackHalf2x16({}.y))",
+            arg, arg
+        ),
+        PackedFormat::Rgb10a2 => format!(
+            "(vec4((uvec4({}) >> uvec4(0u, 10u, 20u, 30u)) & uvec4(1023u, 1023u, 1023u, 3u)) / vec4(1023.0, 1023.0, 1023.0, 3.0))",
+            arg
+        ),
+    }
+}
@@ -6,7 +6,12 @@ use std::collections::HashMap;
 
 use id_arena::{Arena, Id};
 
-pub use thiol_syntax::FileLocation;
+pub use thiol_syntax::{keywords, FileId, FileLocation};
+
+pub mod spans;
+pub mod stable;
+pub use spans::{Node, Part};
+pub use stable::{Declaration, DeclarationDiff, DeclarationKind, StableId};
 
 pub type Identifier = String;
 
@@ -18,25 +23,41 @@ pub struct Context {
     pub type_refs: Arena<TypeReference>,
     pub functions: Arena<Function>,
     pub programs: Arena<Program>,
+    pub spaces: Arena<SpaceDefinition>,
     pub attributes: Arena<Attribute>,
     pub variable_defs: Arena<VariableDef>,
     pub statements: Arena<Statement>,
     pub expressions: Arena<Expression>,
     pub prim_ops: Arena<PrimitiveOp>,
     pub vec_types: Arena<VecType>,
+    pub strings: Arena<String>,
+    pub static_asserts: Arena<StaticAssert>,
 
     pub identifier_fcs: HashMap<Id<Identifier>, FileLocation>,
+    /// the whole paths of identifiers written as paths, `identifier_fcs`
+    /// has their last name
+    pub path_fcs: HashMap<Id<Identifier>, FileLocation>,
     pub type_def_fcs: HashMap<Id<TypeDefinition>, FileLocation>,
     pub type_def_rhs_fcs: HashMap<Id<TypeDefinitionRhs>, FileLocation>,
     pub type_ref_fcs: HashMap<Id<TypeReference>, FileLocation>,
     pub function_fcs: HashMap<Id<Function>, FileLocation>,
     pub program_fcs: HashMap<Id<Program>, FileLocation>,
+    pub space_fcs: HashMap<Id<SpaceDefinition>, FileLocation>,
     pub attribute_fcs: HashMap<Id<Attribute>, FileLocation>,
     pub variable_def_fcs: HashMap<Id<VariableDef>, FileLocation>,
     pub statement_fcs: HashMap<Id<Statement>, FileLocation>,
     pub expression_fcs: HashMap<Id<Expression>, FileLocation>,
     pub prim_op_fcs: HashMap<Id<PrimitiveOp>, FileLocation>,
     pub vec_type_fcs: HashMap<Id<VecType>, FileLocation>,
+    pub static_assert_fcs: HashMap<Id<StaticAssert>, FileLocation>,
+    /// the spans of expressions written in parentheses, with the outermost
+    /// parentheses, see [`spans`]
+    pub parenthesized_fcs: HashMap<Id<Expression>, FileLocation>,
+    /// the argument lists of calls with their parentheses
+    pub argument_list_fcs: HashMap<Id<Expression>, FileLocation>,
+    /// the names that are reserved words written without `r#`, see
+    /// [`keywords`]
+    pub reserved_words: Vec<(Identifier, FileLocation)>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -45,10 +66,16 @@ pub struct Module {
     pub consts: Vec<Id<VariableDef>>,
     pub functions: Vec<Id<Function>>,
     pub programs: Vec<Id<Program>>,
+    pub spaces: Vec<Id<SpaceDefinition>>,
+    pub static_asserts: Vec<Id<StaticAssert>>,
+    /// the items named by `use` declarations, with their qualified names
+    pub uses: Vec<Id<Identifier>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TypeDefinition {
+    pub attrs: Vec<Id<Attribute>>,
+    pub visibility: Visibility,
     pub name: Id<Identifier>,
     pub generics: Vec<Id<Identifier>>,
     pub rhs: Id<TypeDefinitionRhs>,
@@ -63,21 +90,67 @@ pub enum TypeDefinitionRhs {
 
 #[derive(Debug, Clone)]
 pub struct Function {
+    pub attrs: Vec<Id<Attribute>>,
+    pub visibility: Visibility,
     pub name: Id<Identifier>,
-    pub args: Vec<(Id<Identifier>, Id<TypeReference>)>,
+    pub generics: Vec<Id<Identifier>>,
+    pub args: Vec<(Id<Identifier>, Id<TypeReference>, ParamMode)>,
+    /// the attributes of each argument, in the order of `args`
+    pub arg_attrs: Vec<Vec<Id<Attribute>>>,
     pub ret_type: Id<TypeReference>,
 
     pub body: Vec<Id<Statement>>,
 }
 
+/// Whether an item declared in a module can be used outside of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// only in the module and the modules in it
+    Private,
+    /// everywhere
+    Public,
+}
+
+/// How an argument is passed to a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamMode {
+    /// the value is copied into the parameter
+    In,
+    /// the parameter is copied back into the argument when the function returns
+    Out,
+    /// the value is copied in and back out again
+    InOut,
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub attrs: Vec<Id<Attribute>>,
     pub name: Id<Identifier>,
     pub inputs: Vec<Id<VariableDef>>,
     pub outputs: Vec<Id<VariableDef>>,
+    /// variables shared by the invocations of a compute workgroup
+    pub workgroup: Vec<Id<VariableDef>>,
     pub body: Vec<Id<Statement>>,
 }
 
+/// A condition that is evaluated when compiling, the module doesn't compile
+/// if it is false
+#[derive(Debug, Clone)]
+pub struct StaticAssert {
+    pub condition: Id<Expression>,
+    pub message: String,
+}
+
+/// A coordinate space, which vectors and transforms refer to
+#[derive(Debug, Clone)]
+pub struct SpaceDefinition {
+    pub visibility: Visibility,
+    pub name: Id<Identifier>,
+    /// the space this one is placed in, and the constant transforming from
+    /// the parent space to this one
+    pub parent: Option<(Id<Identifier>, Id<Identifier>)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Attribute {
     pub name: Id<Identifier>,
@@ -88,6 +161,8 @@ pub struct Attribute {
 #[derive(Debug, Clone)]
 pub struct VariableDef {
     pub attrs: Vec<Id<Attribute>>,
+    /// always private for variables that aren't constants
+    pub visibility: Visibility,
     pub name: Id<Identifier>,
     pub type_: Id<TypeReference>,
     pub rhs: Option<Id<Expression>>,
@@ -100,9 +175,19 @@ pub enum Statement {
         lhs: Id<Expression>,
         rhs: Id<Expression>,
     },
+    /// assigns the fields of the record `rhs` to the targets in `lhs`, in the
+    /// order of the fields
+    Destructure {
+        lhs: Vec<Id<Expression>>,
+        rhs: Id<Expression>,
+    },
     Return(Option<Id<Expression>>),
+    /// An expression evaluated for its effects, like a call
+    Expr(Id<Expression>),
     Break,
     Continue,
+    /// stops the invocation of a fragment without writing its outputs
+    Discard,
     If {
         cond: Id<Expression>,
         then_body: Vec<Id<Statement>>,
@@ -116,6 +201,29 @@ pub enum Statement {
 
         body: Vec<Id<Statement>>,
     },
+    /// the arms for the cases of the optional `value`, see
+    /// [`MatchPattern`]
+    Match {
+        value: Id<Expression>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    /// the location of the pattern
+    pub loc: FileLocation,
+    pub body: Vec<Id<Statement>>,
+}
+
+/// The case of an optional value an arm of a `match` handles
+#[derive(Debug, Clone, Copy)]
+pub enum MatchPattern {
+    /// a value, with the name it has in the body of the arm
+    Some(Id<Identifier>),
+    /// no value
+    None,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -142,10 +250,34 @@ pub enum Expression {
         base: Id<Expression>,
         index: Id<Expression>,
     },
+    /// a view of the elements of an array from `lo` up to `hi`
+    Slice {
+        base: Id<Expression>,
+        lo: Id<Expression>,
+        hi: Id<Expression>,
+    },
     As {
         base: Id<Expression>,
         ty: Id<TypeReference>,
     },
+    /// the size or alignment of a type in a buffer, or the offset of one of
+    /// its fields, with the layout rules named by `rules` or the rules of
+    /// storage buffers
+    LayoutQuery {
+        query: LayoutQuery,
+        ty: Id<TypeReference>,
+        rules: Option<Id<Identifier>>,
+    },
+    /// an array of the elements
+    Array(Vec<Id<Expression>>),
+}
+
+#[derive(Debug, Clone)]
+pub enum LayoutQuery {
+    Size,
+    Align,
+    /// the offset of a field of a record
+    Offset(Id<Identifier>),
 }
 
 #[derive(Debug, Clone)]
@@ -178,12 +310,66 @@ pub enum TypeReference {
     OpenArray(Id<TypeReference>),
     Array {
         base: Id<TypeReference>,
-        size: usize,
+        size: ArraySize,
     },
     Named {
         name: Id<Identifier>,
         generics: Vec<Id<TypeReference>>,
     },
+    /// a vector known to have a length of one
+    Normalized(Id<TypeReference>),
+    /// a value that may be missing
+    Optional(Id<TypeReference>),
+    /// a record without a name, with the names and types of its fields
+    Record {
+        fields: Vec<(Id<Identifier>, Id<TypeReference>)>,
+    },
+    /// a storage image, the format and access are checked when resolving the
+    /// type
+    Image {
+        dim: ImageDim,
+        format: Id<Identifier>,
+        access: Id<Identifier>,
+    },
+    /// a texture read through a sampler, the sampled type is `None` for
+    /// depth textures
+    Texture {
+        dim: TextureDim,
+        sampled: Option<Id<TypeReference>>,
+    },
+    /// a sampler, or a comparison sampler for depth textures
+    Sampler {
+        comparison: bool,
+    },
+    /// the geometry rays are traced against
+    AccelerationStructure,
+}
+
+/// The dimensions of a texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureDim {
+    D1,
+    D2,
+    D3,
+    Cube,
+    D2Array,
+}
+
+/// The dimensions of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageDim {
+    D1,
+    D2,
+    D3,
+    D2Array,
+}
+
+/// The number of elements of an array type
+#[derive(Debug, Clone, Copy)]
+pub enum ArraySize {
+    Literal(usize),
+    /// an expression evaluated when compiling, like `sizeof(Light) / 4`
+    Expression(Id<Expression>),
 }
 
 #[derive(Debug, Clone)]
@@ -191,8 +377,19 @@ pub enum PrimitiveType {
     Bool,
     Int,
     UInt,
+    /// a 64 bit `int`
+    Long,
+    /// a 64 bit `uint`
+    ULong,
     Float,
     Double,
+    Half,
+    /// a `float` angle in radians
+    Radians,
+    /// a `float` angle in degrees
+    Degrees,
+    AtomicInt,
+    AtomicUInt,
 
     BoolVec {
         components: VecSize,
@@ -207,6 +404,16 @@ pub enum PrimitiveType {
         vtype: Option<Id<VecType>>,
         space: Option<Id<Identifier>>,
     },
+    LongVec {
+        components: VecSize,
+        vtype: Option<Id<VecType>>,
+        space: Option<Id<Identifier>>,
+    },
+    ULongVec {
+        components: VecSize,
+        vtype: Option<Id<VecType>>,
+        space: Option<Id<Identifier>>,
+    },
 
     FloatVec {
         components: VecSize,
@@ -218,6 +425,11 @@ pub enum PrimitiveType {
         vtype: Option<Id<VecType>>,
         space: Option<Id<Identifier>>,
     },
+    HalfVec {
+        components: VecSize,
+        vtype: Option<Id<VecType>>,
+        space: Option<Id<Identifier>>,
+    },
 
     FloatMat {
         cols: VecSize,
@@ -229,13 +441,20 @@ pub enum PrimitiveType {
         rows: VecSize,
         transform: Option<(Id<Identifier>, Id<Identifier>)>,
     },
+
+    Packed {
+        format: PackedFormat,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum VecType {
     Point,
     Vector,
+    /// a linear colour
     Colour,
+    /// an sRGB encoded colour
+    SrgbColour,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -245,8 +464,38 @@ pub enum VecSize {
     VS4,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum PackedFormat {
+    Unorm8x4,
+    Snorm8x4,
+    Uint8x4,
+    Sint8x4,
+    Unorm16x2,
+    Snorm16x2,
+    Float16x2,
+    Float16x4,
+    Rgb10a2,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Literal {
-    Integer(i128),
-    Float(f64),
+    Integer(i128, Option<LiteralSuffix>),
+    Float(f64, Option<LiteralSuffix>),
+    Bool(bool),
+    /// only allowed in attribute arguments
+    String(Id<String>),
+}
+
+/// The type a literal is written with, literals without one get their type
+/// from where they are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteralSuffix {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    Float,
+    Double,
+    Radians,
+    Degrees,
 }
@@ -0,0 +1,145 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The spans of the source the nodes of the HIR were lowered from.
+//!
+//! The `*_fcs` maps of the [`Context`] hold the span diagnostics report a
+//! node at, which leaves out the parentheses around expressions. Tools that
+//! underline or rewrite code need the complete source of a node instead,
+//! [`Context::full_span`], and the spans of its parts, like the argument
+//! list of a call, [`Context::part_span`].
+
+use id_arena::Id;
+
+use crate::{
+    Attribute, Context, Expression, FileLocation, Function, Identifier, Program, SpaceDefinition,
+    Statement, StaticAssert, TypeDefinition, TypeReference, VariableDef,
+};
+
+/// A node of the HIR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Node {
+    Identifier(Id<Identifier>),
+    TypeDefinition(Id<TypeDefinition>),
+    TypeReference(Id<TypeReference>),
+    Function(Id<Function>),
+    Program(Id<Program>),
+    Space(Id<SpaceDefinition>),
+    Attribute(Id<Attribute>),
+    VariableDef(Id<VariableDef>),
+    Statement(Id<Statement>),
+    Expression(Id<Expression>),
+    StaticAssert(Id<StaticAssert>),
+}
+
+/// A part of the source of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Part {
+    /// the name of a declaration or attribute, the callee of a call or the
+    /// field of a field access
+    Name,
+    /// the attributes in front of a declaration
+    Attributes,
+    /// the arguments of a call with their parentheses
+    Arguments,
+    /// the declared type of a variable, the return type of a function or the
+    /// type of a cast
+    Type,
+    /// the value of a variable, an assignment, a `return` or an expression
+    /// statement
+    Value,
+}
+
+impl Context {
+    /// The complete span of the source of a node, `None` for nodes that
+    /// weren't written in the source.
+    pub fn full_span(&self, node: Node) -> Option<FileLocation> {
+        let loc = match node {
+            Node::Identifier(id) => self.identifier_fcs.get(&id),
+            Node::TypeDefinition(id) => self.type_def_fcs.get(&id),
+            Node::TypeReference(id) => self.type_ref_fcs.get(&id),
+            Node::Function(id) => self.function_fcs.get(&id),
+            Node::Program(id) => self.program_fcs.get(&id),
+            Node::Space(id) => self.space_fcs.get(&id),
+            Node::Attribute(id) => self.attribute_fcs.get(&id),
+            Node::VariableDef(id) => self.variable_def_fcs.get(&id),
+            Node::Statement(id) => self.statement_fcs.get(&id),
+            Node::Expression(id) => self
+                .parenthesized_fcs
+                .get(&id)
+                .or_else(|| self.expression_fcs.get(&id)),
+            Node::StaticAssert(id) => self.static_assert_fcs.get(&id),
+        };
+        loc.copied()
+    }
+
+    /// The span of a part of a node, `None` if the node doesn't have the
+    /// part.
+    pub fn part_span(&self, node: Node, part: Part) -> Option<FileLocation> {
+        match part {
+            Part::Name => self.full_span(Node::Identifier(self.name_of(node)?)),
+            Part::Attributes => {
+                let attrs = match node {
+                    Node::TypeDefinition(id) => &self.type_defs[id].attrs,
+                    Node::Function(id) => &self.functions[id].attrs,
+                    Node::Program(id) => &self.programs[id].attrs,
+                    Node::VariableDef(id) => &self.variable_defs[id].attrs,
+                    _ => return None,
+                };
+                attrs
+                    .iter()
+                    .filter_map(|attr| self.full_span(Node::Attribute(*attr)))
+                    .reduce(|a, b| a.merge(b))
+            }
+            Part::Arguments => match node {
+                Node::Expression(id) => self.argument_list_fcs.get(&id).copied(),
+                _ => None,
+            },
+            Part::Type => {
+                let ty = match node {
+                    Node::VariableDef(id) => self.variable_defs[id].type_,
+                    Node::Function(id) => self.functions[id].ret_type,
+                    Node::Expression(id) => match &self.expressions[id] {
+                        Expression::As { ty, .. } => *ty,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                self.full_span(Node::TypeReference(ty))
+            }
+            Part::Value => {
+                let value = match node {
+                    Node::VariableDef(id) => self.variable_defs[id].rhs?,
+                    Node::Statement(id) => match &self.statements[id] {
+                        Statement::Var(def) => self.variable_defs[*def].rhs?,
+                        Statement::Becomes { rhs, .. } | Statement::Destructure { rhs, .. } => *rhs,
+                        Statement::Return(value) => (*value)?,
+                        Statement::Expr(e) => *e,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                self.full_span(Node::Expression(value))
+            }
+        }
+    }
+
+    /// The identifier naming a node, see [`Part::Name`].
+    fn name_of(&self, node: Node) -> Option<Id<Identifier>> {
+        let name = match node {
+            Node::TypeDefinition(id) => self.type_defs[id].name,
+            Node::Function(id) => self.functions[id].name,
+            Node::Program(id) => self.programs[id].name,
+            Node::Space(id) => self.spaces[id].name,
+            Node::Attribute(id) => self.attributes[id].name,
+            Node::VariableDef(id) => self.variable_defs[id].name,
+            Node::Expression(id) => match &self.expressions[id] {
+                Expression::Call { name, .. } | Expression::Field { name, .. } => *name,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(name)
+    }
+}
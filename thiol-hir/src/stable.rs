@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Ids of declarations that survive edits of the file.
+//!
+//! Arena ids depend on the order things are lowered in, so they change as
+//! soon as a declaration is added in front of another one. A [`StableId`]
+//! only depends on the kind and name of a declaration, and the content hash
+//! of a [`Declaration`] only on its tokens, so incremental tools can match
+//! the declarations of two parses and redo the work for the changed ones.
+
+use std::collections::HashMap;
+
+use crate::{Context, FileLocation, Identifier, Module};
+
+/// What a declaration declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeclarationKind {
+    Type,
+    Constant,
+    Function,
+    Program,
+    Space,
+}
+
+/// An id derived from the kind and name of a declaration. Declarations that
+/// are declared more than once are told apart by the order they appear in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StableId(u64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    pub id: StableId,
+    pub kind: DeclarationKind,
+    pub name: Identifier,
+    pub loc: FileLocation,
+    /// hash of the tokens of the declaration, whitespace and comments don't
+    /// change it
+    pub content: u64,
+}
+
+/// How the declarations of a file changed between two parses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeclarationDiff {
+    pub added: Vec<Declaration>,
+    pub removed: Vec<Declaration>,
+    /// the old and the new version of declarations whose content changed
+    pub changed: Vec<(Declaration, Declaration)>,
+}
+
+/// FNV-1a, which is the same in every build, so ids can be stored
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // separate the parts, so that moving bytes between them changes
+        // the hash
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+}
+
+impl Module {
+    /// The declarations of the module in source order, `source` is the text
+    /// of the file the module was lowered from.
+    pub fn declarations(&self, ctx: &Context, source: &str) -> Vec<Declaration> {
+        use DeclarationKind as K;
+
+        let types = self.types.iter().map(|id| {
+            let name = ctx.type_defs[*id].name;
+            (K::Type, name, ctx.type_def_fcs[id])
+        });
+        let consts = self.consts.iter().map(|id| {
+            let name = ctx.variable_defs[*id].name;
+            (K::Constant, name, ctx.variable_def_fcs[id])
+        });
+        let functions = self.functions.iter().map(|id| {
+            let name = ctx.functions[*id].name;
+            (K::Function, name, ctx.function_fcs[id])
+        });
+        let programs = self.programs.iter().map(|id| {
+            let name = ctx.programs[*id].name;
+            (K::Program, name, ctx.program_fcs[id])
+        });
+        let spaces = self.spaces.iter().map(|id| {
+            let name = ctx.spaces[*id].name;
+            (K::Space, name, ctx.space_fcs[id])
+        });
+        let mut items = types
+            .chain(consts)
+            .chain(functions)
+            .chain(programs)
+            .chain(spaces)
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(_, _, loc)| loc.start);
+
+        let mut seen = HashMap::new();
+        items
+            .into_iter()
+            .map(|(kind, name, loc)| {
+                let name = ctx.identifiers[name].clone();
+                let occurrence = seen.entry((kind, name.clone())).or_insert(0u32);
+                let mut id = Fnv::new();
+                id.write(&[kind as u8]);
+                id.write(name.as_bytes());
+                id.write(&occurrence.to_le_bytes());
+                *occurrence += 1;
+
+                let mut content = Fnv::new();
+                let text = &source[loc.range()];
+                for token in thiol_syntax::lexer::tokenise(loc.file, text) {
+                    content.write(text[token.loc.range()].as_bytes());
+                }
+
+                Declaration {
+                    id: StableId(id.0),
+                    kind,
+                    name,
+                    loc,
+                    content: content.0,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Match the declarations of two parses of the same file by their ids.
+pub fn diff(old: &[Declaration], new: &[Declaration]) -> DeclarationDiff {
+    let old_by_id = old
+        .iter()
+        .map(|decl| (decl.id, decl))
+        .collect::<HashMap<_, _>>();
+    let new_by_id = new
+        .iter()
+        .map(|decl| (decl.id, decl))
+        .collect::<HashMap<_, _>>();
+
+    let mut diff = DeclarationDiff::default();
+    for decl in new {
+        match old_by_id.get(&decl.id) {
+            None => diff.added.push(decl.clone()),
+            Some(prev) if prev.content != decl.content => {
+                diff.changed.push(((*prev).clone(), decl.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|decl| !new_by_id.contains_key(&decl.id))
+        .cloned()
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decl(kind: DeclarationKind, name: &str, start: usize, content: u64) -> Declaration {
+        let mut id = Fnv::new();
+        id.write(&[kind as u8]);
+        id.write(name.as_bytes());
+        Declaration {
+            id: StableId(id.0),
+            kind,
+            name: name.to_string(),
+            loc: FileLocation {
+                file: 0,
+                start,
+                end: start + 10,
+            },
+            content,
+        }
+    }
+
+    #[test]
+    fn moved_changed_added_and_removed() {
+        use DeclarationKind::*;
+
+        let old = [
+            decl(Type, "Light", 0, 1),
+            decl(Function, "shade", 20, 2),
+            decl(Constant, "PI", 40, 3),
+        ];
+        let new = [
+            decl(Constant, "TAU", 0, 4),
+            decl(Type, "Light", 20, 1),
+            decl(Function, "shade", 40, 5),
+        ];
+
+        assert_eq!(
+            diff(&old, &new),
+            DeclarationDiff {
+                added: vec![new[0].clone()],
+                removed: vec![old[2].clone()],
+                changed: vec![(old[1].clone(), new[2].clone())],
+            }
+        );
+    }
+}
@@ -0,0 +1,665 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use id_arena::Id;
+use thiol_hir as hir;
+use thiol_syntax::FileLocation;
+use thiol_typeck::{unify, Symbol, Type, TypeId, VecSize};
+
+use crate::Analysis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Type,
+    GenericParam,
+    Function,
+    Constant,
+    Variable,
+    Field,
+    Swizzle,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// The type of the value, or the return type for functions
+    pub ty: Option<TypeId>,
+}
+
+/// Identifier inserted at the cursor so the file can be analysed with a
+/// complete expression (or type reference) in place.
+const PLACEHOLDER: &str = "__completion__";
+
+/// Text appended after the placeholder to try to turn an incomplete statement
+/// into one that parses. They are tried in order.
+const REPAIRS: &[&str] = &["", ";", " := 0;"];
+
+impl Analysis {
+    /// Completion candidates for the cursor at `offset`.
+    ///
+    /// The identifier that is currently being typed (up to the cursor) is
+    /// used as a prefix filter.
+    pub fn completions(&self, offset: usize) -> Vec<CompletionItem> {
+        let start = identifier_start(&self.source, offset);
+        let end = identifier_end(&self.source, offset);
+        let prefix = &self.source[start..offset];
+
+        let repaired = REPAIRS.iter().find_map(|repair| {
+            let source = format!(
+                "{}{}{}{}",
+                &self.source[..start],
+                PLACEHOLDER,
+                repair,
+                &self.source[end..]
+            );
+            let analysis = Analysis::new(self.file, &source);
+            if analysis.module.is_some() {
+                Some(analysis)
+            } else {
+                None
+            }
+        });
+
+        let placeholder = FileLocation {
+            file: self.file,
+            start,
+            end: start + PLACEHOLDER.len(),
+        };
+
+        let mut items = match &repaired {
+            Some(analysis) => analysis.completions_at(placeholder),
+            None => self.global_completions(None),
+        };
+
+        items.retain(|item| item.label.starts_with(prefix) && item.label != PLACEHOLDER);
+        items
+    }
+
+    fn completions_at(&self, placeholder: FileLocation) -> Vec<CompletionItem> {
+        let module = match &self.module {
+            Some(module) => module,
+            None => return vec![],
+        };
+
+        let mut locator = Locator {
+            analysis: self,
+            target: placeholder,
+            scopes: vec![],
+            generics: vec![],
+            ret: None,
+            found: None,
+        };
+        locator.module(module);
+
+        match locator.found {
+            Some((Position::Member(base), _)) => self.member_completions(base),
+            Some((Position::Type, generics)) => {
+                let mut items = generics
+                    .into_iter()
+                    .map(|name| CompletionItem {
+                        label: self.hir.identifiers[name].clone(),
+                        kind: CompletionKind::GenericParam,
+                        ty: None,
+                    })
+                    .collect::<Vec<_>>();
+                items.extend(self.types.defs.keys().map(|name| CompletionItem {
+                    label: name.clone(),
+                    kind: CompletionKind::Type,
+                    ty: self.types.complete_types.get(name).copied(),
+                }));
+                items
+            }
+            Some((Position::Expression { expected }, locals)) => {
+                let mut items = vec![];
+                for name in locals.into_iter().rev() {
+                    let loc = self.hir.identifier_fcs[&name];
+                    let label = &self.hir.identifiers[name];
+                    if items.iter().any(|i: &CompletionItem| &i.label == label) {
+                        continue;
+                    }
+                    let ty = self
+                        .types
+                        .references
+                        .symbol(loc)
+                        .and_then(|sym| self.types.references.symbol_type(sym));
+                    items.push(CompletionItem {
+                        label: label.clone(),
+                        kind: CompletionKind::Variable,
+                        ty,
+                    });
+                }
+                items.extend(self.global_completions(expected));
+                if let Some(expected) = expected {
+                    items.retain(|item| {
+                        item.ty
+                            .is_some_and(|ty| unify::unifiable(&self.types, ty, expected))
+                    });
+                }
+                items
+            }
+            None => self.global_completions(None),
+        }
+    }
+
+    /// Constants and functions
+    fn global_completions(&self, expected: Option<TypeId>) -> Vec<CompletionItem> {
+        let consts = self.types.consts.iter().map(|(name, sig)| CompletionItem {
+            label: name.clone(),
+            kind: CompletionKind::Constant,
+            ty: Some(sig.type_),
+        });
+        let functions = self
+            .types
+            .function_sigs
+            .iter()
+            .map(|(name, sig)| CompletionItem {
+                label: name.clone(),
+                kind: CompletionKind::Function,
+                ty: Some(sig.ret),
+            });
+
+        consts
+            .chain(functions)
+            .filter(|item| match (item.ty, expected) {
+                (Some(ty), Some(expected)) => unify::unifiable(&self.types, ty, expected),
+                (None, Some(_)) => false,
+                (_, None) => true,
+            })
+            .collect()
+    }
+
+    fn member_completions(&self, base: Id<hir::Expression>) -> Vec<CompletionItem> {
+        let mut ty = match self.types.expr_types.get(&base) {
+            Some(ty) => *ty,
+            None => return vec![],
+        };
+
+        loop {
+            match self.types.types.get(ty) {
+                Some(Type::Distinct { inner, .. }) | Some(Type::Normalized { inner }) => {
+                    ty = *inner
+                }
+                Some(Type::Record { fields }) => {
+                    return fields
+                        .iter()
+                        .map(|(name, ty)| CompletionItem {
+                            label: self.types.types.name(*name).to_string(),
+                            kind: CompletionKind::Field,
+                            ty: Some(*ty),
+                        })
+                        .collect()
+                }
+                Some(Type::BoolVec { components })
+                | Some(Type::IntVec { components, .. })
+                | Some(Type::UIntVec { components, .. })
+                | Some(Type::LongVec { components, .. })
+                | Some(Type::ULongVec { components, .. })
+                | Some(Type::FloatVec { components, .. })
+                | Some(Type::DoubleVec { components, .. }) => return swizzles(*components),
+                _ => return vec![],
+            }
+        }
+    }
+}
+
+fn swizzles(components: VecSize) -> Vec<CompletionItem> {
+    let n = match components {
+        VecSize::VS2 => 2,
+        VecSize::VS3 => 3,
+        VecSize::VS4 => 4,
+    };
+
+    let mut labels = vec![];
+    for set in &["xyzw", "rgba"] {
+        labels.extend(set[..n].chars().map(|c| c.to_string()));
+    }
+    for set in &["xyzw", "rgba"] {
+        labels.extend((2..=n).map(|len| set[..len].to_string()));
+    }
+
+    labels
+        .into_iter()
+        .map(|label| CompletionItem {
+            label,
+            kind: CompletionKind::Swizzle,
+            ty: None,
+        })
+        .collect()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+fn identifier_start(source: &str, offset: usize) -> usize {
+    source[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_char(*c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(offset)
+}
+
+fn identifier_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_identifier_char(*c))
+        .map(|(i, _)| offset + i)
+        .unwrap_or_else(|| source.len())
+}
+
+enum Position {
+    /// Field access or swizzle on the given base expression
+    Member(Id<hir::Expression>),
+    Type,
+    Expression {
+        expected: Option<TypeId>,
+    },
+}
+
+/// Finds the syntactic position of the placeholder identifier together with
+/// the names visible at that point.
+struct Locator<'a> {
+    analysis: &'a Analysis,
+    target: FileLocation,
+    scopes: Vec<Vec<Id<hir::Identifier>>>,
+    generics: Vec<Id<hir::Identifier>>,
+    ret: Option<TypeId>,
+    found: Option<(Position, Vec<Id<hir::Identifier>>)>,
+}
+
+impl<'a> Locator<'a> {
+    fn module(&mut self, module: &hir::Module) {
+        let hir = &self.analysis.hir;
+
+        for id in &module.types {
+            let def = &hir.type_defs[*id];
+            self.generics = def.generics.clone();
+            match &hir.type_def_rhss[def.rhs] {
+                hir::TypeDefinitionRhs::Distinct(ty) | hir::TypeDefinitionRhs::Alias(ty) => {
+                    self.type_ref(*ty)
+                }
+                hir::TypeDefinitionRhs::Record { fields } => {
+                    for field in fields {
+                        self.type_ref(hir.variable_defs[*field].type_);
+                    }
+                }
+            }
+            self.generics.clear();
+        }
+
+        for id in &module.consts {
+            let def = &hir.variable_defs[*id];
+            self.type_ref(def.type_);
+            if let Some(rhs) = def.rhs {
+                let expected = self.symbol_type(def.name);
+                self.expr(rhs, expected);
+            }
+        }
+
+        for id in &module.functions {
+            let func = &hir.functions[*id];
+            self.ret = self
+                .analysis
+                .types
+                .function_sigs
+                .get(&hir.identifiers[func.name])
+                .filter(|sig| sig.func_id == *id)
+                .map(|sig| sig.ret);
+
+            self.generics = func.generics.clone();
+            self.scopes.push(vec![]);
+            for (name, ty, _) in &func.args {
+                self.type_ref(*ty);
+                self.declare(*name);
+            }
+            self.type_ref(func.ret_type);
+            self.block(&func.body);
+            self.scopes.pop();
+            self.generics.clear();
+        }
+        self.ret = None;
+
+        for id in &module.programs {
+            let prog = &hir.programs[*id];
+
+            self.scopes.push(vec![]);
+            for var in prog
+                .inputs
+                .iter()
+                .chain(&prog.outputs)
+                .chain(&prog.workgroup)
+            {
+                self.variable_def(*var);
+            }
+            self.block(&prog.body);
+            self.scopes.pop();
+        }
+    }
+
+    fn variable_def(&mut self, id: Id<hir::VariableDef>) {
+        let def = &self.analysis.hir.variable_defs[id];
+        self.type_ref(def.type_);
+        if let Some(rhs) = def.rhs {
+            let expected = self.symbol_type(def.name);
+            self.expr(rhs, expected);
+        }
+        self.declare(def.name);
+    }
+
+    fn block(&mut self, block: &[Id<hir::Statement>]) {
+        self.scopes.push(vec![]);
+        for stmt in block {
+            self.statement(*stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, id: Id<hir::Statement>) {
+        match &self.analysis.hir.statements[id] {
+            hir::Statement::Var(var) => self.variable_def(*var),
+            hir::Statement::Becomes { lhs, rhs } => {
+                self.expr(*lhs, None);
+                let expected = self.analysis.types.expr_types.get(lhs).copied();
+                self.expr(*rhs, expected);
+            }
+            hir::Statement::Destructure { lhs, rhs } => {
+                for e in lhs {
+                    self.expr(*e, None);
+                }
+                self.expr(*rhs, None);
+            }
+            hir::Statement::Return(e) => {
+                if let Some(e) = e {
+                    self.expr(*e, self.ret);
+                }
+            }
+            hir::Statement::Expr(e) => self.expr(*e, None),
+            hir::Statement::Break | hir::Statement::Continue | hir::Statement::Discard => {}
+            hir::Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                self.expr(*cond, None);
+                self.block(then_body);
+                self.block(else_body);
+            }
+            hir::Statement::Match { value, arms } => {
+                self.expr(*value, None);
+                for arm in arms {
+                    match arm.pattern {
+                        hir::MatchPattern::Some(name) => self.scopes.push(vec![name]),
+                        hir::MatchPattern::None => self.scopes.push(vec![]),
+                    }
+                    self.block(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            hir::Statement::For {
+                iter_name,
+                loop_type: _,
+                from,
+                to,
+                body,
+            } => {
+                self.expr(*from, None);
+                self.expr(*to, None);
+                self.scopes.push(vec![*iter_name]);
+                self.block(body);
+                self.scopes.pop();
+            }
+        }
+    }
+
+    fn expr(&mut self, id: Id<hir::Expression>, expected: Option<TypeId>) {
+        let hir = &self.analysis.hir;
+        match &hir.expressions[id] {
+            hir::Expression::Literal(_) => {}
+            hir::Expression::Variable(name) => {
+                if self.is_target(*name) {
+                    self.found(Position::Expression { expected });
+                }
+            }
+            hir::Expression::PrimitiveOp(op) => {
+                use hir::PrimitiveOp as PO;
+                match &hir.prim_ops[*op] {
+                    PO::Neg(e) | PO::Pos(e) => self.expr(*e, None),
+                    PO::Add(a, b)
+                    | PO::Sub(a, b)
+                    | PO::Mul(a, b)
+                    | PO::Div(a, b)
+                    | PO::Mod(a, b)
+                    | PO::Gt(a, b)
+                    | PO::Gte(a, b)
+                    | PO::Lt(a, b)
+                    | PO::Lte(a, b)
+                    | PO::Eq(a, b)
+                    | PO::Neq(a, b) => {
+                        self.expr(*a, None);
+                        self.expr(*b, None);
+                    }
+                    PO::Constructor {
+                        ty: _,
+                        pos_args,
+                        nam_args,
+                    } => {
+                        for e in pos_args {
+                            self.expr(*e, None);
+                        }
+                        for (_, e) in nam_args {
+                            self.expr(*e, None);
+                        }
+                    }
+                }
+            }
+            hir::Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => {
+                if self.is_target(*name) {
+                    self.found(Position::Expression { expected });
+                }
+
+                let sig = self
+                    .analysis
+                    .types
+                    .function_sigs
+                    .get(&hir.identifiers[*name]);
+                for (i, e) in pos_args.iter().enumerate() {
+                    let expected = sig.and_then(|sig| sig.args.get(i)).map(|(_, ty)| *ty);
+                    self.expr(*e, expected);
+                }
+                for (arg_name, e) in nam_args {
+                    let arg_name = &hir.identifiers[*arg_name];
+                    let expected = sig
+                        .and_then(|sig| sig.args.iter().find(|(n, _)| n == arg_name))
+                        .map(|(_, ty)| *ty);
+                    self.expr(*e, expected);
+                }
+            }
+            hir::Expression::Field { base, name } => {
+                if self.is_target(*name) {
+                    self.found(Position::Member(*base));
+                }
+                self.expr(*base, None);
+            }
+            hir::Expression::Index { base, index } => {
+                self.expr(*base, None);
+                self.expr(*index, None);
+            }
+            hir::Expression::Slice { base, lo, hi } => {
+                self.expr(*base, None);
+                self.expr(*lo, None);
+                self.expr(*hi, None);
+            }
+            hir::Expression::As { base, ty } => {
+                self.expr(*base, None);
+                self.type_ref(*ty);
+            }
+            hir::Expression::LayoutQuery { ty, .. } => self.type_ref(*ty),
+            hir::Expression::Array(elements) => {
+                let types = &self.analysis.types;
+                let element = expected.and_then(|ty| match types.types.get(ty) {
+                    Some(Type::Array { base, .. }) => Some(*base),
+                    _ => None,
+                });
+                for e in elements {
+                    self.expr(*e, element);
+                }
+            }
+        }
+    }
+
+    fn type_ref(&mut self, id: Id<hir::TypeReference>) {
+        match &self.analysis.hir.type_refs[id] {
+            hir::TypeReference::Primitive(_)
+            | hir::TypeReference::Image { .. }
+            | hir::TypeReference::Texture { sampled: None, .. }
+            | hir::TypeReference::Sampler { .. }
+            | hir::TypeReference::AccelerationStructure => {}
+            hir::TypeReference::OpenArray(base)
+            | hir::TypeReference::Normalized(base)
+            | hir::TypeReference::Optional(base)
+            | hir::TypeReference::Texture {
+                sampled: Some(base),
+                ..
+            } => self.type_ref(*base),
+            hir::TypeReference::Record { fields } => {
+                for (_, ty) in fields {
+                    self.type_ref(*ty);
+                }
+            }
+            hir::TypeReference::Array { base, size } => {
+                if let hir::ArraySize::Expression(size) = size {
+                    self.expr(*size, None);
+                }
+                self.type_ref(*base)
+            }
+            hir::TypeReference::Named { name, generics } => {
+                if self.is_target(*name) {
+                    self.found = Some((Position::Type, self.generics.clone()));
+                }
+                for gen in generics {
+                    self.type_ref(*gen);
+                }
+            }
+        }
+    }
+
+    fn declare(&mut self, name: Id<hir::Identifier>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name);
+        }
+    }
+
+    fn symbol_type(&self, name: Id<hir::Identifier>) -> Option<TypeId> {
+        let refs = &self.analysis.types.references;
+        let sym: Symbol = refs.symbol(self.analysis.hir.identifier_fcs[&name])?;
+        refs.symbol_type(sym)
+    }
+
+    fn is_target(&self, name: Id<hir::Identifier>) -> bool {
+        self.analysis.hir.identifier_fcs[&name] == self.target
+    }
+
+    fn found(&mut self, pos: Position) {
+        let locals = self.scopes.iter().flatten().copied().collect();
+        self.found = Some((pos, locals));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"
+type
+    Light = record
+        position: float3;
+        intensity: float;
+    end
+
+const
+    AMBIENT: float := 0.1;
+    ORIGIN: float3 := float3(0, 0, 0);
+
+function attenuate(intensity: float, dist: float) returns float
+begin
+    return intensity / (dist * dist);
+end
+
+function shade(light: Light, pos: float3) returns float
+begin
+    var dist: float := 1.0;
+    CURSOR
+end
+"#;
+
+    fn complete(line: &str) -> Vec<CompletionItem> {
+        let src = SRC.replace("CURSOR", line);
+        let offset = src.find('|').unwrap();
+        let src = src.replacen('|', "", 1);
+        Analysis::new(0, &src).completions(offset)
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|i| i.label.as_str()).collect()
+    }
+
+    #[test]
+    fn complete_record_fields() {
+        let items = complete("var x: float := light.|;");
+        assert_eq!(labels(&items), vec!["position", "intensity"]);
+        assert!(items.iter().all(|i| i.kind == CompletionKind::Field));
+
+        let items = complete("var x: float := light.int|");
+        assert_eq!(labels(&items), vec!["intensity"]);
+    }
+
+    #[test]
+    fn complete_swizzles() {
+        let items = complete("var x: float := light.position.|;");
+        let labels = labels(&items);
+        assert!(labels.contains(&"x"));
+        assert!(labels.contains(&"b"));
+        assert!(labels.contains(&"xyz"));
+        assert!(!labels.contains(&"w"));
+        assert!(!labels.contains(&"xyzw"));
+    }
+
+    #[test]
+    fn complete_types() {
+        let src = SRC.replace("CURSOR", "var l: Li;");
+        let offset = src.find("Li;").unwrap() + 2;
+        let items = Analysis::new(0, &src).completions(offset);
+        assert_eq!(labels(&items), vec!["Light"]);
+        assert_eq!(items[0].kind, CompletionKind::Type);
+    }
+
+    #[test]
+    fn complete_expressions() {
+        let items = complete("return |");
+        let labels = labels(&items);
+        assert!(labels.contains(&"dist"));
+        assert!(labels.contains(&"AMBIENT"));
+        assert!(labels.contains(&"attenuate"));
+        // return type is float
+        assert!(!labels.contains(&"ORIGIN"));
+        assert!(!labels.contains(&"light"));
+    }
+
+    #[test]
+    fn complete_call_arguments_by_type() {
+        let items = complete("return attenuate(light.intensity, |);");
+        let labels = labels(&items);
+        assert!(labels.contains(&"dist"));
+        assert!(labels.contains(&"AMBIENT"));
+        assert!(!labels.contains(&"pos"));
+        assert!(!labels.contains(&"ORIGIN"));
+    }
+}
@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use thiol_hir as hir;
+use thiol_syntax::{lexer::Token, parser::ParseError, FileId};
+
+pub mod completion;
+pub mod rename;
+pub mod semantic_tokens;
+pub use completion::{CompletionItem, CompletionKind};
+pub use rename::{RenameError, TextEdit};
+pub use semantic_tokens::semantic_tokens;
+
+/// The result of running the front-end over a single source file.
+///
+/// Editor tooling has to deal with broken code all the time, so every stage
+/// is run as far as it gets and the partial results are kept around.
+pub struct Analysis {
+    pub file: FileId,
+    pub source: String,
+    pub tokens: Vec<Token>,
+    pub hir: hir::Context,
+    /// the syntax error that stopped the parser
+    pub parse_error: Option<ParseError>,
+    /// `None` if the file failed to parse or to lower to HIR
+    pub module: Option<hir::Module>,
+    pub types: thiol_typeck::Context,
+    pub type_errors: Vec<thiol_typeck::Error>,
+}
+
+impl Analysis {
+    pub fn new(file: FileId, source: &str) -> Self {
+        let tokens = thiol_syntax::lexer::tokenise(file, source).collect();
+
+        let mut hir = hir::Context::default();
+        let mut types = thiol_typeck::Context::default();
+
+        let (ast, parse_error) = match thiol_syntax::parser::parse_file(file, source) {
+            Ok(ast) => (Some(ast), None),
+            Err(err) => (None, Some(err)),
+        };
+        let module = ast.and_then(|ast| thiol_ast_lowering::lower(&mut hir, &ast).ok());
+
+        let mut type_errors = vec![];
+        if let Some(module) = &module {
+            if let Err(errs) = thiol_typeck::type_check(&mut types, &hir, module) {
+                type_errors = errs;
+            }
+        }
+
+        Self {
+            file,
+            source: source.to_string(),
+            tokens,
+            hir,
+            parse_error,
+            module,
+            types,
+            type_errors,
+        }
+    }
+
+    /// The declarations of the file with ids that stay the same across
+    /// edits, empty if the file didn't parse.
+    pub fn declarations(&self) -> Vec<hir::Declaration> {
+        match &self.module {
+            Some(module) => module.declarations(&self.hir, &self.source),
+            None => vec![],
+        }
+    }
+
+    /// The declarations that were added, removed or changed since an older
+    /// analysis of the same file.
+    pub fn changes_since(&self, older: &Analysis) -> hir::DeclarationDiff {
+        hir::stable::diff(&older.declarations(), &self.declarations())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_between_edits() {
+        let old = Analysis::new(
+            0,
+            "const SCALE: float := 2.0;\n\
+             function scale(x: float) returns float begin return x * SCALE; end",
+        );
+        let new = Analysis::new(
+            0,
+            "const OFFSET: float := 1.0;\n\
+             const SCALE: float := 2.0; // unchanged\n\
+             function scale(x: float) returns float begin return x * SCALE + OFFSET; end",
+        );
+
+        let (old_decls, new_decls) = (old.declarations(), new.declarations());
+        assert_eq!(old_decls[0].id, new_decls[1].id);
+        assert_eq!(old_decls[0].content, new_decls[1].content);
+
+        let changes = new.changes_since(&old);
+        let names =
+            |decls: &[hir::Declaration]| decls.iter().map(|d| d.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&changes.added), ["OFFSET"]);
+        assert!(changes.removed.is_empty());
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].1.name, "scale");
+    }
+}
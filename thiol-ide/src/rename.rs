@@ -0,0 +1,340 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use thiol_syntax::{lexer::TokenKind as TK, FileLocation};
+use thiol_typeck::{Error, Symbol};
+
+use crate::Analysis;
+
+/// Replacement of the text at `location` by `new_text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub location: FileLocation,
+    pub new_text: String,
+}
+
+pub enum RenameError {
+    /// The new name is not a valid identifier (for example a keyword)
+    InvalidIdentifier,
+    /// There is no renameable symbol at the given position
+    NoSymbol,
+    /// Renaming would lead to the redefinition errors given
+    Conflict(Vec<Error>),
+}
+
+impl Analysis {
+    /// Compute the edits needed to rename a symbol and all of its uses.
+    ///
+    /// The renamed source is checked again and the rename is rejected if any of
+    /// the renamed identifiers is involved in a redefinition error afterwards.
+    pub fn rename(&self, sym: Symbol, new_name: &str) -> Result<Vec<TextEdit>, RenameError> {
+        if !is_identifier(new_name) {
+            return Err(RenameError::InvalidIdentifier);
+        }
+
+        let index = &self.types.references;
+        let def = index.definition(sym).ok_or(RenameError::NoSymbol)?;
+
+        let mut locations = vec![def];
+        locations.extend_from_slice(index.references(sym));
+        locations.sort();
+        locations.dedup();
+
+        let edits = locations
+            .into_iter()
+            .map(|location| TextEdit {
+                location,
+                new_text: new_name.to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        let renamed = Analysis::new(self.file, &apply_edits(&self.source, &edits));
+        let new_locations = shifted_locations(&edits);
+
+        let conflicts = renamed
+            .type_errors
+            .into_iter()
+            .filter(|err| {
+                redefined_names(err)
+                    .iter()
+                    .any(|loc| new_locations.contains(loc))
+            })
+            .collect::<Vec<_>>();
+
+        if conflicts.is_empty() {
+            Ok(edits)
+        } else {
+            Err(RenameError::Conflict(conflicts))
+        }
+    }
+
+    /// Rename the symbol referred to by the identifier at `offset`.
+    pub fn rename_at(&self, offset: usize, new_name: &str) -> Result<Vec<TextEdit>, RenameError> {
+        let (_, sym) = self
+            .types
+            .references
+            .symbol_at(self.file, offset)
+            .ok_or(RenameError::NoSymbol)?;
+        self.rename(sym, new_name)
+    }
+}
+
+/// Apply non-overlapping edits to a source text.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut edits = edits.iter().collect::<Vec<_>>();
+    edits.sort_by_key(|e| e.location);
+
+    let mut res = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in edits {
+        res.push_str(&source[pos..edit.location.start]);
+        res.push_str(&edit.new_text);
+        pos = edit.location.end;
+    }
+    res.push_str(&source[pos..]);
+    res
+}
+
+/// The locations of the edited text after the edits have been applied.
+fn shifted_locations(edits: &[TextEdit]) -> Vec<FileLocation> {
+    let mut offset = 0isize;
+    edits
+        .iter()
+        .map(|edit| {
+            let start = (edit.location.start as isize + offset) as usize;
+            offset += edit.new_text.len() as isize - edit.location.range().len() as isize;
+            FileLocation {
+                file: edit.location.file,
+                start,
+                end: start + edit.new_text.len(),
+            }
+        })
+        .collect()
+}
+
+/// Whether the name is one identifier, which can be a raw identifier like
+/// `r#type`.
+fn is_identifier(name: &str) -> bool {
+    let mut toks = thiol_syntax::lexer::tokenise(0, name);
+    matches!(
+        (toks.next(), toks.next()),
+        (Some(tok), None) if tok.loc.range() == (0..name.len())
+            && matches!(tok.value, TK::Identifier(_) | TK::RawIdentifier(_))
+    )
+}
+
+fn redefined_names(err: &Error) -> Vec<FileLocation> {
+    match err {
+        Error::TypeRedefinition {
+            previous_name,
+            redefinition_name,
+            ..
+        }
+        | Error::FieldRedefinition {
+            previous_name,
+            redefinition_name,
+            ..
+        }
+        | Error::FunctionRedefinition {
+            previous_name,
+            redefinition_name,
+            ..
+        }
+        | Error::ConstantRedefinition {
+            previous_name,
+            redefinition_name,
+            ..
+        }
+        | Error::SpaceRedefinition {
+            previous_name,
+            redefinition_name,
+        } => vec![*previous_name, *redefinition_name],
+        Error::GenericParamaterRedefinition {
+            previous_name,
+            redefinition,
+        } => vec![*previous_name, *redefinition],
+        Error::LocalRedefinition {
+            previous,
+            redefinition,
+            ..
+        } => vec![*previous, *redefinition],
+        Error::NameConflict {
+            previous, conflict, ..
+        } => vec![*previous, *conflict],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_at(source: &str, needle: &str, new_name: &str) -> Result<String, RenameError> {
+        let analysis = Analysis::new(0, source);
+        let offset = source.find(needle).unwrap();
+        let edits = analysis.rename_at(offset, new_name)?;
+        Ok(apply_edits(source, &edits))
+    }
+
+    const SRC: &str = r#"
+type
+    Pair<T> = record
+        first: T;
+        second: T;
+    end
+    Other = record end
+
+function swap(p: Pair<float>) returns Pair<float>
+begin
+    var res: Pair<float>;
+    res.first := p.second;
+    res.second := p.first;
+    return res;
+end
+"#;
+
+    #[test]
+    fn rename_generic_param() {
+        let renamed = rename_at(SRC, "T>", "Elem").ok().unwrap();
+        assert!(renamed.contains("Pair<Elem> = record"));
+        assert!(renamed.contains("first: Elem;"));
+        assert!(renamed.contains("second: Elem;"));
+    }
+
+    #[test]
+    fn rename_field_through_accesses() {
+        let renamed = rename_at(SRC, "first", "x").ok().unwrap();
+        assert!(renamed.contains("        x: T;"));
+        assert!(renamed.contains("res.x := p.second;"));
+        assert!(renamed.contains("res.second := p.x;"));
+        assert!(!renamed.contains("first"));
+    }
+
+    #[test]
+    fn rename_type() {
+        let renamed = rename_at(SRC, "Pair", "Tuple").ok().unwrap();
+        assert_eq!(renamed.matches("Tuple").count(), 4);
+        assert!(!renamed.contains("Pair"));
+    }
+
+    #[test]
+    fn rename_conflicts() {
+        assert!(matches!(
+            rename_at(SRC, "Pair", "Other"),
+            Err(RenameError::Conflict(errs)) if errs.len() == 1
+        ));
+        assert!(matches!(
+            rename_at(SRC, "first", "second"),
+            Err(RenameError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn rename_into_other_namespace() {
+        let src = "const SCALE: float := 2.0;\n\
+                   function scale2(x: float) returns float begin return x * SCALE; end";
+        assert!(matches!(
+            rename_at(src, "scale2", "SCALE"),
+            Err(RenameError::Conflict(errs)) if errs.len() == 1
+        ));
+        assert!(rename_at(src, "scale2", "scale").is_ok());
+    }
+
+    #[test]
+    fn rename_space() {
+        let src = "space World;\nspace Model: parent World via world_to_model;\n\
+                   const world_to_model: float4x4 from World to Model := float4x4(1.0);";
+        assert!(matches!(
+            rename_at(src, "Model:", "World"),
+            Err(RenameError::Conflict(errs)) if errs.len() == 1
+        ));
+        let renamed = rename_at(src, "Model:", "Object").ok().unwrap();
+        assert_eq!(renamed.matches("Object").count(), 2);
+    }
+
+    #[test]
+    fn rename_local() {
+        let src = "function f(x: float) returns float\nbegin\n    var a: float := x;\n    \
+                   var b: float := a;\n    return b;\nend";
+        assert!(matches!(
+            rename_at(src, "b:", "a"),
+            Err(RenameError::Conflict(errs)) if errs.len() == 1
+        ));
+        let renamed = rename_at(src, "b:", "c").ok().unwrap();
+        assert!(renamed.contains("var c: float := a;"));
+        assert!(renamed.contains("return c;"));
+    }
+
+    #[test]
+    fn rename_through_path() {
+        let src = r#"
+module geometry
+    pub function g(x: float) returns float
+    begin
+        return x;
+    end
+end
+
+function f(x: float) returns float
+begin
+    return geometry::g(x);
+end
+"#;
+        for needle in &["g(x: float)", "g(x);"] {
+            let renamed = rename_at(src, needle, "h").ok().unwrap();
+            assert!(renamed.contains("pub function h(x: float)"));
+            assert!(renamed.contains("return geometry::h(x);"));
+        }
+    }
+
+    #[test]
+    fn rename_through_use() {
+        let src = r#"
+module geometry
+    pub function g(x: float) returns float
+    begin
+        return x;
+    end
+end
+
+module lib
+    use geometry::g;
+
+    function f(x: float) returns float
+    begin
+        return g(x);
+    end
+end
+"#;
+        for needle in &["g(x: float)", "g;"] {
+            let renamed = rename_at(src, needle, "h").ok().unwrap();
+            assert!(renamed.contains("pub function h(x: float)"));
+            assert!(renamed.contains("use geometry::h;"));
+            assert!(renamed.contains("return h(x);"));
+        }
+    }
+
+    #[test]
+    fn rename_to_raw_identifier() {
+        let renamed = rename_at(SRC, "first", "r#in").ok().unwrap();
+        assert!(renamed.contains("        r#in: T;"));
+        assert!(renamed.contains("res.second := p.r#in;"));
+        assert!(matches!(
+            rename_at(SRC, "first", "r#two words"),
+            Err(RenameError::InvalidIdentifier)
+        ));
+    }
+
+    #[test]
+    fn rename_invalid_name() {
+        assert!(matches!(
+            rename_at(SRC, "Pair", "record"),
+            Err(RenameError::InvalidIdentifier)
+        ));
+        assert!(matches!(
+            rename_at(SRC, "Pair", "two words"),
+            Err(RenameError::InvalidIdentifier)
+        ));
+    }
+}
@@ -0,0 +1,797 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use id_arena::Id;
+use thiol_hir as hir;
+use thiol_syntax::{
+    lexer::{Token, TokenKind as TK},
+    FileLocation,
+};
+use thiol_typeck::Symbol;
+
+use crate::Analysis;
+
+/// Classification of a token for syntax highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenKind {
+    Keyword,
+    Number,
+    String,
+    Operator,
+
+    Type,
+    GenericParam,
+    Function,
+    Constant,
+    Variable,
+    Parameter,
+    Field,
+    Attribute,
+    Space,
+    /// the name of a module
+    Namespace,
+}
+
+/// Classify all tokens of a source file.
+///
+/// Identifiers are classified based on what they refer to, which requires
+/// the file to parse and lower to HIR. If that fails, all identifiers are
+/// reported as [`TokenKind::Variable`]. Module names are found from the
+/// tokens: they come before a `::`, after `module` or in a `prelude` path.
+///
+/// The returned locations use the file id `0`.
+pub fn semantic_tokens(source: &str) -> Vec<(FileLocation, TokenKind)> {
+    Analysis::new(0, source).semantic_tokens()
+}
+
+impl Analysis {
+    pub fn semantic_tokens(&self) -> Vec<(FileLocation, TokenKind)> {
+        let mut classifier = Classifier {
+            hir: &self.hir,
+            ty: &self.types,
+            consts: HashSet::new(),
+            fn_generics: HashSet::new(),
+            scopes: vec![],
+            classes: BTreeMap::new(),
+        };
+
+        if let Some(module) = &self.module {
+            classifier.module(module);
+        }
+
+        let is_name = |tok: Option<&Token>| {
+            matches!(
+                tok.map(|tok| &tok.value),
+                Some(TK::Identifier(_)) | Some(TK::RawIdentifier(_))
+            )
+        };
+        // `prelude` is only a keyword when a path follows it
+        let is_prelude = |i: usize| {
+            matches!(&self.tokens[i].value, TK::Identifier(word) if word == "prelude")
+                && is_name(self.tokens.get(i + 1))
+        };
+
+        let mut in_prelude = false;
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(i, tok)| {
+                let prev = i.checked_sub(1).map(|i| &self.tokens[i].value);
+                let next = self.tokens.get(i + 1).map(|tok| &tok.value);
+                let kind = match &tok.value {
+                    TK::Identifier(_) | TK::RawIdentifier(_) if is_prelude(i) => {
+                        in_prelude = true;
+                        Some(TokenKind::Keyword)
+                    }
+                    TK::Identifier(_) | TK::RawIdentifier(_) => {
+                        let module = in_prelude
+                            || matches!(prev, Some(TK::Module))
+                            || matches!(next, Some(TK::PathSep));
+                        Some(match classifier.classes.get(&tok.loc) {
+                            Some(kind) => *kind,
+                            None if module => TokenKind::Namespace,
+                            None => TokenKind::Variable,
+                        })
+                    }
+                    kind => {
+                        if let TK::SemiColon = kind {
+                            in_prelude = false;
+                        }
+                        lexical_class(kind)
+                    }
+                };
+                kind.map(|kind| (tok.loc, kind))
+            })
+            .collect()
+    }
+}
+
+fn lexical_class(kind: &TK) -> Option<TokenKind> {
+    match kind {
+        TK::Const
+        | TK::Type
+        | TK::Record
+        | TK::Distinct
+        | TK::Array
+        | TK::Of
+        | TK::In
+        | TK::Out
+        | TK::Is
+        | TK::From
+        | TK::To
+        | TK::As
+        | TK::DownTo
+        | TK::Point
+        | TK::Vector
+        | TK::Colour
+        | TK::SrgbColour
+        | TK::Function
+        | TK::Program
+        | TK::Space
+        | TK::Module
+        | TK::Pub
+        | TK::Use
+        | TK::Var
+        | TK::Begin
+        | TK::End
+        | TK::Return
+        | TK::Break
+        | TK::Continue
+        | TK::Discard
+        | TK::If
+        | TK::Then
+        | TK::Else
+        | TK::ElseIf
+        | TK::Match
+        | TK::For
+        | TK::Do
+        | TK::Returns
+        | TK::Input
+        | TK::Output
+        | TK::Workgroup
+        | TK::True
+        | TK::False => Some(TokenKind::Keyword),
+
+        TK::TyBool
+        | TK::TyInt
+        | TK::TyUInt
+        | TK::TyLong
+        | TK::TyULong
+        | TK::TyFloat
+        | TK::TyDouble
+        | TK::TyHalf
+        | TK::TyRadians
+        | TK::TyDegrees
+        | TK::TyAtomic
+        | TK::TyBoolVec(_)
+        | TK::TyIntVec(_)
+        | TK::TyUIntVec(_)
+        | TK::TyLongVec(_)
+        | TK::TyULongVec(_)
+        | TK::TyFloatVec(_)
+        | TK::TyDoubleVec(_)
+        | TK::TyHalfVec(_)
+        | TK::TyFloatMat(_)
+        | TK::TyDoubleMat(_)
+        | TK::TyPacked(_) => Some(TokenKind::Type),
+
+        TK::Identifier(_) | TK::RawIdentifier(_) => Some(TokenKind::Variable),
+
+        TK::Integer(_) | TK::Float(_) => Some(TokenKind::Number),
+
+        TK::String(_) => Some(TokenKind::String),
+
+        TK::Plus
+        | TK::Minus
+        | TK::Star
+        | TK::Slash
+        | TK::Equals
+        | TK::NotEquals
+        | TK::Mod
+        | TK::GreaterThan
+        | TK::GreaterThanEqual
+        | TK::LessThan
+        | TK::LessThanEqual
+        | TK::Becomes => Some(TokenKind::Operator),
+
+        TK::ParenOpen
+        | TK::ParenClose
+        | TK::BracketOpen
+        | TK::BracketClose
+        | TK::BraceOpen
+        | TK::BraceClose
+        | TK::Comma
+        | TK::Colon
+        | TK::PathSep
+        | TK::SemiColon
+        | TK::Dot
+        | TK::DotDot
+        | TK::At
+        | TK::Comment
+        | TK::Whitespace
+        | TK::Error
+        | TK::Root
+        | TK::BinaryExpr
+        | TK::PrefixExpr => None,
+    }
+}
+
+struct Classifier<'a> {
+    hir: &'a hir::Context,
+    ty: &'a thiol_typeck::Context,
+    /// names of all constants in the module, including those that failed to type check
+    consts: HashSet<&'a str>,
+    /// generic parameters of the function being classified
+    fn_generics: HashSet<&'a str>,
+    scopes: Vec<HashMap<&'a str, TokenKind>>,
+    classes: BTreeMap<FileLocation, TokenKind>,
+}
+
+impl<'a> Classifier<'a> {
+    fn module(&mut self, module: &hir::Module) {
+        for c in &module.consts {
+            let def = &self.hir.variable_defs[*c];
+            self.consts.insert(self.hir.identifiers[def.name].as_str());
+        }
+
+        for id in &module.uses {
+            match self.ty.resolutions.symbol(*id) {
+                Some(Symbol::Type(_)) => self.ident(*id, TokenKind::Type),
+                Some(Symbol::Function(_)) => self.ident(*id, TokenKind::Function),
+                Some(Symbol::Constant(_)) => self.ident(*id, TokenKind::Constant),
+                _ => {
+                    let name = &self.hir.identifiers[*id];
+                    if module
+                        .spaces
+                        .iter()
+                        .any(|s| self.hir.identifiers[self.hir.spaces[*s].name] == *name)
+                    {
+                        self.ident(*id, TokenKind::Space);
+                    }
+                }
+            }
+        }
+
+        for ty in &module.types {
+            self.type_definition(*ty);
+        }
+
+        for space in &module.spaces {
+            let space = &self.hir.spaces[*space];
+            self.ident(space.name, TokenKind::Space);
+            if let Some((parent, via)) = space.parent {
+                self.ident(parent, TokenKind::Space);
+                self.ident(via, TokenKind::Constant);
+            }
+        }
+
+        for c in &module.consts {
+            self.variable_def(*c, TokenKind::Constant, &HashSet::new());
+        }
+
+        for f in &module.functions {
+            let func = &self.hir.functions[*f];
+            self.attributes(&func.attrs);
+            self.ident(func.name, TokenKind::Function);
+
+            let mut generics = HashSet::new();
+            for gen in &func.generics {
+                self.ident(*gen, TokenKind::GenericParam);
+                generics.insert(self.hir.identifiers[*gen].as_str());
+            }
+
+            self.scopes.push(HashMap::new());
+            for ((name, ty, _), attrs) in func.args.iter().zip(&func.arg_attrs) {
+                self.attributes(attrs);
+                self.ident(*name, TokenKind::Parameter);
+                self.type_ref(*ty, &generics);
+                self.declare(*name, TokenKind::Parameter);
+            }
+            self.type_ref(func.ret_type, &generics);
+            self.fn_generics = generics;
+            self.block(&func.body);
+            self.fn_generics.clear();
+            self.scopes.pop();
+        }
+
+        for p in &module.programs {
+            let prog = &self.hir.programs[*p];
+            self.attributes(&prog.attrs);
+            self.ident(prog.name, TokenKind::Function);
+
+            self.scopes.push(HashMap::new());
+            for var in prog.inputs.iter().chain(&prog.outputs) {
+                self.variable_def(*var, TokenKind::Parameter, &HashSet::new());
+                self.declare(self.hir.variable_defs[*var].name, TokenKind::Parameter);
+            }
+            for var in &prog.workgroup {
+                self.variable_def(*var, TokenKind::Variable, &HashSet::new());
+                self.declare(self.hir.variable_defs[*var].name, TokenKind::Variable);
+            }
+            self.block(&prog.body);
+            self.scopes.pop();
+        }
+    }
+
+    fn type_definition(&mut self, id: Id<hir::TypeDefinition>) {
+        let def = &self.hir.type_defs[id];
+        self.attributes(&def.attrs);
+        self.ident(def.name, TokenKind::Type);
+
+        let mut generics = HashSet::new();
+        for gen in &def.generics {
+            self.ident(*gen, TokenKind::GenericParam);
+            generics.insert(self.hir.identifiers[*gen].as_str());
+        }
+
+        match &self.hir.type_def_rhss[def.rhs] {
+            hir::TypeDefinitionRhs::Distinct(ty) | hir::TypeDefinitionRhs::Alias(ty) => {
+                self.type_ref(*ty, &generics)
+            }
+            hir::TypeDefinitionRhs::Record { fields } => {
+                for field in fields {
+                    self.variable_def(*field, TokenKind::Field, &generics);
+                }
+            }
+        }
+    }
+
+    fn attributes(&mut self, attrs: &[Id<hir::Attribute>]) {
+        for attr in attrs {
+            let attr = &self.hir.attributes[*attr];
+            self.ident(attr.name, TokenKind::Attribute);
+            for e in &attr.pos_args {
+                self.expr(*e);
+            }
+            for (name, e) in &attr.nam_args {
+                self.ident(*name, TokenKind::Parameter);
+                self.expr(*e);
+            }
+        }
+    }
+
+    fn variable_def(
+        &mut self,
+        id: Id<hir::VariableDef>,
+        kind: TokenKind,
+        generics: &HashSet<&'a str>,
+    ) {
+        let def = &self.hir.variable_defs[id];
+        self.attributes(&def.attrs);
+        self.ident(def.name, kind);
+        self.type_ref(def.type_, generics);
+        if let Some(rhs) = def.rhs {
+            self.expr(rhs);
+        }
+    }
+
+    fn type_ref(&mut self, id: Id<hir::TypeReference>, generics: &HashSet<&'a str>) {
+        match &self.hir.type_refs[id] {
+            hir::TypeReference::Primitive(prim) => self.prim_type(prim),
+            // the format and access of images are fixed names, like keywords
+            hir::TypeReference::Image { format, access, .. } => {
+                self.ident(*format, TokenKind::Keyword);
+                self.ident(*access, TokenKind::Keyword);
+            }
+            hir::TypeReference::Texture { sampled: None, .. }
+            | hir::TypeReference::Sampler { .. }
+            | hir::TypeReference::AccelerationStructure => {}
+            hir::TypeReference::OpenArray(base)
+            | hir::TypeReference::Normalized(base)
+            | hir::TypeReference::Optional(base)
+            | hir::TypeReference::Texture {
+                sampled: Some(base),
+                ..
+            } => self.type_ref(*base, generics),
+            hir::TypeReference::Record { fields } => {
+                for (field, ty) in fields {
+                    self.ident(*field, TokenKind::Field);
+                    self.type_ref(*ty, generics);
+                }
+            }
+            hir::TypeReference::Array { base, size } => {
+                if let hir::ArraySize::Expression(size) = size {
+                    self.expr(*size);
+                }
+                self.type_ref(*base, generics)
+            }
+            hir::TypeReference::Named {
+                name,
+                generics: args,
+            } => {
+                let kind = if generics.contains(self.hir.identifiers[*name].as_str()) {
+                    TokenKind::GenericParam
+                } else {
+                    TokenKind::Type
+                };
+                self.ident(*name, kind);
+
+                for arg in args {
+                    self.type_ref(*arg, generics);
+                }
+            }
+        }
+    }
+
+    fn prim_type(&mut self, prim: &hir::PrimitiveType) {
+        use hir::PrimitiveType as PT;
+
+        match prim {
+            PT::IntVec { space, .. }
+            | PT::UIntVec { space, .. }
+            | PT::LongVec { space, .. }
+            | PT::ULongVec { space, .. }
+            | PT::FloatVec { space, .. }
+            | PT::DoubleVec { space, .. }
+            | PT::HalfVec { space, .. } => {
+                if let Some(space) = space {
+                    self.ident(*space, TokenKind::Space);
+                }
+            }
+            PT::FloatMat { transform, .. } | PT::DoubleMat { transform, .. } => {
+                if let Some((from, to)) = transform {
+                    self.ident(*from, TokenKind::Space);
+                    self.ident(*to, TokenKind::Space);
+                }
+            }
+            PT::Bool
+            | PT::Int
+            | PT::UInt
+            | PT::Long
+            | PT::ULong
+            | PT::Float
+            | PT::Double
+            | PT::Half
+            | PT::Radians
+            | PT::Degrees
+            | PT::AtomicInt
+            | PT::AtomicUInt
+            | PT::BoolVec { .. }
+            | PT::Packed { .. } => {}
+        }
+    }
+
+    fn block(&mut self, block: &[Id<hir::Statement>]) {
+        self.scopes.push(HashMap::new());
+        for stmt in block {
+            self.statement(*stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, id: Id<hir::Statement>) {
+        match &self.hir.statements[id] {
+            hir::Statement::Var(var) => {
+                let generics = self.fn_generics.clone();
+                self.variable_def(*var, TokenKind::Variable, &generics);
+                self.declare(self.hir.variable_defs[*var].name, TokenKind::Variable);
+            }
+            hir::Statement::Becomes { lhs, rhs } => {
+                self.expr(*lhs);
+                self.expr(*rhs);
+            }
+            hir::Statement::Destructure { lhs, rhs } => {
+                for e in lhs {
+                    self.expr(*e);
+                }
+                self.expr(*rhs);
+            }
+            hir::Statement::Return(e) => {
+                if let Some(e) = e {
+                    self.expr(*e);
+                }
+            }
+            hir::Statement::Expr(e) => self.expr(*e),
+            hir::Statement::Break | hir::Statement::Continue | hir::Statement::Discard => {}
+            hir::Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                self.expr(*cond);
+                self.block(then_body);
+                self.block(else_body);
+            }
+            hir::Statement::Match { value, arms } => {
+                self.expr(*value);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    if let hir::MatchPattern::Some(name) = arm.pattern {
+                        self.declare(name, TokenKind::Variable);
+                    }
+                    self.block(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            hir::Statement::For {
+                iter_name,
+                loop_type: _,
+                from,
+                to,
+                body,
+            } => {
+                self.expr(*from);
+                self.expr(*to);
+
+                self.scopes.push(HashMap::new());
+                self.declare(*iter_name, TokenKind::Variable);
+                self.block(body);
+                self.scopes.pop();
+            }
+        }
+    }
+
+    fn expr(&mut self, id: Id<hir::Expression>) {
+        match &self.hir.expressions[id] {
+            hir::Expression::Literal(_) => {}
+            hir::Expression::Variable(name) => {
+                let kind = self.lookup(&self.hir.identifiers[*name]);
+                self.ident(*name, kind);
+            }
+            hir::Expression::PrimitiveOp(op) => self.prim_op(*op),
+            hir::Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => {
+                // the constructor of a record takes the values of its fields
+                if let Some(Symbol::Type(_)) = self.ty.resolutions.symbol(*name) {
+                    self.ident(*name, TokenKind::Type);
+                    for e in pos_args {
+                        self.expr(*e);
+                    }
+                    for (field, e) in nam_args {
+                        self.ident(*field, TokenKind::Field);
+                        self.expr(*e);
+                    }
+                } else {
+                    self.ident(*name, TokenKind::Function);
+                    self.args(pos_args, nam_args);
+                }
+            }
+            hir::Expression::Field { base, name } => {
+                self.expr(*base);
+                self.ident(*name, TokenKind::Field);
+            }
+            hir::Expression::Index { base, index } => {
+                self.expr(*base);
+                self.expr(*index);
+            }
+            hir::Expression::Slice { base, lo, hi } => {
+                self.expr(*base);
+                self.expr(*lo);
+                self.expr(*hi);
+            }
+            hir::Expression::As { base, ty } => {
+                self.expr(*base);
+                let generics = self.fn_generics.clone();
+                self.type_ref(*ty, &generics);
+            }
+            hir::Expression::LayoutQuery { query, ty, rules } => {
+                let generics = self.fn_generics.clone();
+                self.type_ref(*ty, &generics);
+                if let hir::LayoutQuery::Offset(field) = query {
+                    self.ident(*field, TokenKind::Field);
+                }
+                if let Some(rules) = rules {
+                    self.ident(*rules, TokenKind::Keyword);
+                }
+            }
+            hir::Expression::Array(elements) => {
+                for e in elements {
+                    self.expr(*e);
+                }
+            }
+        }
+    }
+
+    fn prim_op(&mut self, id: Id<hir::PrimitiveOp>) {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.prim_ops[id] {
+            PO::Neg(e) | PO::Pos(e) => self.expr(*e),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                self.expr(*a);
+                self.expr(*b);
+            }
+            PO::Constructor {
+                ty,
+                pos_args,
+                nam_args,
+            } => {
+                self.prim_type(ty);
+                self.args(pos_args, nam_args);
+            }
+        }
+    }
+
+    fn args(
+        &mut self,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<hir::Identifier>, Id<hir::Expression>)],
+    ) {
+        for e in pos_args {
+            self.expr(*e);
+        }
+        for (name, e) in nam_args {
+            self.ident(*name, TokenKind::Parameter);
+            self.expr(*e);
+        }
+    }
+
+    fn declare(&mut self, name: Id<hir::Identifier>, kind: TokenKind) {
+        let name = self.hir.identifiers[name].as_str();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, kind);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> TokenKind {
+        if let Some(kind) = self.scopes.iter().rev().find_map(|s| s.get(name)) {
+            *kind
+        } else if self.ty.consts.contains_key(name) || self.consts.contains(name) {
+            TokenKind::Constant
+        } else {
+            TokenKind::Variable
+        }
+    }
+
+    fn ident(&mut self, id: Id<hir::Identifier>, kind: TokenKind) {
+        self.classes.insert(self.hir.identifier_fcs[&id], kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds_of(source: &str, name: &str) -> Vec<TokenKind> {
+        semantic_tokens(source)
+            .into_iter()
+            .filter(|(loc, _)| &source[loc.range()] == name)
+            .map(|(_, kind)| kind)
+            .collect()
+    }
+
+    #[test]
+    fn classify_items() {
+        let src = r#"
+type
+    Pair<T> = record
+        first: T;
+        second: T;
+    end
+
+const
+    SCALE: float := 2.0;
+
+function scaled(x: float) returns float
+begin
+    var y: float := x * SCALE;
+    return y;
+end
+
+program main
+begin
+    var p: Pair<float>;
+    p.first := scaled(p.second);
+end
+"#;
+
+        assert_eq!(kinds_of(src, "Pair"), vec![TokenKind::Type; 2]);
+        assert_eq!(kinds_of(src, "T"), vec![TokenKind::GenericParam; 3]);
+        assert_eq!(kinds_of(src, "first"), vec![TokenKind::Field; 2]);
+        assert_eq!(kinds_of(src, "SCALE"), vec![TokenKind::Constant; 2]);
+        assert_eq!(kinds_of(src, "scaled"), vec![TokenKind::Function; 2]);
+        assert_eq!(kinds_of(src, "x"), vec![TokenKind::Parameter; 2]);
+        assert_eq!(kinds_of(src, "y"), vec![TokenKind::Variable; 2]);
+        assert_eq!(kinds_of(src, "float"), vec![TokenKind::Type; 5]);
+        assert_eq!(kinds_of(src, "2.0"), vec![TokenKind::Number]);
+    }
+
+    #[test]
+    fn record_constructors() {
+        let src = r#"
+type
+    Light = record
+        position: float3;
+        range: float;
+    end
+
+const
+    SUN: Light := Light(position: float3(0.0), range: 10.0);
+"#;
+
+        assert_eq!(kinds_of(src, "Light"), vec![TokenKind::Type; 3]);
+        assert_eq!(kinds_of(src, "range"), vec![TokenKind::Field; 2]);
+    }
+
+    #[test]
+    fn function_generics() {
+        let src = r#"
+function first<T>(xs: array of T, fallback: T) returns T
+begin
+    var x: T := fallback;
+    return x;
+end
+"#;
+        assert_eq!(kinds_of(src, "T"), vec![TokenKind::GenericParam; 5]);
+    }
+
+    #[test]
+    fn locals_shadow_constants() {
+        let src = r#"
+const
+    A: int := 1;
+
+function f() returns int
+begin
+    var A: int := 2;
+    return A;
+end
+"#;
+        assert_eq!(
+            kinds_of(src, "A"),
+            vec![
+                TokenKind::Constant,
+                TokenKind::Variable,
+                TokenKind::Variable
+            ]
+        );
+    }
+
+    #[test]
+    fn qualified_paths() {
+        let src = r#"
+module geometry
+    type
+        pub Ray = record
+            start: float3;
+        end
+
+    pub function origin(ray: Ray) returns float3
+    begin
+        return ray.start;
+    end
+end
+
+module lib
+    use geometry::origin;
+    prelude geometry;
+
+    function trace(ray: geometry::Ray) returns float3
+    begin
+        return geometry::origin(ray);
+    end
+end
+"#;
+        assert_eq!(kinds_of(src, "geometry"), vec![TokenKind::Namespace; 5]);
+        assert_eq!(kinds_of(src, "lib"), vec![TokenKind::Namespace]);
+        assert_eq!(kinds_of(src, "prelude"), vec![TokenKind::Keyword]);
+        assert_eq!(kinds_of(src, "Ray"), vec![TokenKind::Type; 3]);
+        assert_eq!(kinds_of(src, "origin"), vec![TokenKind::Function; 3]);
+    }
+
+    #[test]
+    fn broken_source_falls_back_to_lexical() {
+        let tokens = semantic_tokens("function f( returns");
+        let kinds = tokens.into_iter().map(|(_, k)| k).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![TokenKind::Keyword, TokenKind::Variable, TokenKind::Keyword]
+        );
+    }
+}
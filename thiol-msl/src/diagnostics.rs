@@ -0,0 +1,142 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use thiol_hir::FileId;
+
+use crate::Error;
+
+impl From<Error> for Diagnostic<FileId> {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::DoublePrecision { loc } => {
+                let prim =
+                    Label::primary(loc.file, loc.range()).with_message("double precision type");
+                Diagnostic::error()
+                    .with_message("Metal does not support double precision")
+                    .with_labels(vec![prim])
+            }
+            Error::ProgramWithoutStage { name, loc } => {
+                let prim =
+                    Label::primary(loc.file, loc.range()).with_message("program without a stage");
+                Diagnostic::error()
+                    .with_message(format!("program `{}` has no stage", name))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "add `@vertex`, `@fragment` or `@compute` to the program".to_string(),
+                    ])
+            }
+            Error::MissingPosition { program, loc } => {
+                let prim = Label::primary(loc.file, loc.range()).with_message("vertex program");
+                Diagnostic::error()
+                    .with_message(format!(
+                        "vertex program `{}` has no position output",
+                        program
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "add the `[Position]` attribute to the clip space position output"
+                            .to_string(),
+                    ])
+            }
+            Error::ComputeInputWithoutBuiltin { name, input } => {
+                let prim = Label::primary(input.file, input.range()).with_message("input");
+                Diagnostic::error()
+                    .with_message(format!("compute program input `{}` is not a builtin", name))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "inputs of compute programs need one of the attributes `GlobalInvocationId`, \
+                         `LocalInvocationId`, `LocalInvocationIndex`, `WorkgroupId`, \
+                         `SubgroupInvocationId` or `SubgroupSize`"
+                            .to_string(),
+                    ])
+            }
+            Error::ComputeProgramOutput { name, output } => {
+                let prim = Label::primary(output.file, output.range()).with_message("output");
+                Diagnostic::error()
+                    .with_message(format!(
+                        "compute program output `{}` can't be written",
+                        name
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "compute programs write their results to storage buffers".to_string(),
+                    ])
+            }
+            Error::RowMajorWriteInPlace { loc } => {
+                let prim = Label::primary(loc.file, loc.range())
+                    .with_message("row-major matrix written in place");
+                Diagnostic::error()
+                    .with_message("Metal can only assign row-major matrices as a whole")
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "Metal stores the matrix transposed, change a copy in a local variable and assign the whole matrix"
+                            .to_string(),
+                    ])
+            }
+            Error::TrapUnsupported { loc } => {
+                let prim =
+                    Label::primary(loc.file, loc.range()).with_message("index checked at runtime");
+                Diagnostic::error()
+                    .with_message("Metal cannot trap on indices out of bounds")
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "use the `clamp` or `undefined` bounds check".to_string()
+                    ])
+            }
+            Error::RayTracingProgram { name, stage, loc } => {
+                let prim = Label::primary(loc.file, loc.range())
+                    .with_message(format!("{} program", stage));
+                Diagnostic::error()
+                    .with_message(format!(
+                        "Metal has no stage for ray tracing program `{}`",
+                        name
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "Metal traces rays with intersectors in compute kernels, which thiol doesn't emit"
+                            .to_string(),
+                    ])
+            }
+            Error::MeshShadingProgram { name, stage, loc } => {
+                let prim = Label::primary(loc.file, loc.range())
+                    .with_message(format!("{} program", stage));
+                Diagnostic::error()
+                    .with_message(format!("Metal cannot compile {} program `{}`", stage, name))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "Metal mesh functions write their output to a `mesh` argument, which thiol doesn't emit"
+                            .to_string(),
+                    ])
+            }
+            Error::TessellationProgram { name, stage, loc } => {
+                let prim = Label::primary(loc.file, loc.range())
+                    .with_message(format!("{} program", stage));
+                Diagnostic::error()
+                    .with_message(format!(
+                        "Metal has no stage for tessellation program `{}`",
+                        name
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "Metal computes tessellation factors in compute kernels and evaluates patches in vertex functions, which thiol doesn't emit"
+                            .to_string(),
+                    ])
+            }
+            Error::GeometryProgram { name, loc } => {
+                let prim = Label::primary(loc.file, loc.range()).with_message("geometry program");
+                Diagnostic::error()
+                    .with_message(format!(
+                        "Metal has no stage for geometry program `{}`",
+                        name
+                    ))
+                    .with_labels(vec![prim])
+                    .with_notes(vec![
+                        "build the primitives in a compute kernel or a mesh function instead"
+                            .to_string(),
+                    ])
+            }
+        }
+    }
+}
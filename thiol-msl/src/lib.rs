@@ -0,0 +1,2658 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Metal Shading Language backend.
+//!
+//! Programs become `vertex`, `fragment` and `kernel` functions, ray tracing
+//! programs are an error as Metal has no stages for them, and so are task,
+//! mesh and tessellation programs. The inputs of
+//! vertex and fragment programs are passed in a `[[stage_in]]` struct named
+//! after the program with an `_in` suffix, the outputs are returned in a
+//! struct with an `_out` suffix. Vertex inputs are read from the vertex
+//! attribute and varyings from the user location given by their `Location`
+//! attribute, or else the position of the input in the declaration. Inputs
+//! with one of these attributes are passed as builtins instead:
+//!
+//! - `Position` (fragment): `[[position]]`
+//! - `FrontFacing` (fragment): `[[front_facing]]`
+//! - `VertexIndex` (vertex): `[[vertex_id]]`
+//! - `InstanceIndex` (vertex): `[[instance_id]]`
+//! - `GlobalInvocationId` (compute): `[[thread_position_in_grid]]`
+//! - `LocalInvocationId` (compute): `[[thread_position_in_threadgroup]]`
+//! - `LocalInvocationIndex` (compute): `[[thread_index_in_threadgroup]]`
+//! - `WorkgroupId` (compute): `[[threadgroup_position_in_grid]]`
+//! - `SubgroupInvocationId` (compute): `[[thread_index_in_simdgroup]]`
+//! - `SubgroupSize` (compute): `[[threads_per_simdgroup]]`
+//!
+//! Vertex programs write the clip space position to the output with a
+//! `Position` attribute. `discard` is `discard_fragment()`. Fragment inputs that are not interpolated with
+//! perspective correction at the center get the sampling and interpolation
+//! attribute of their interpolation, like `[[flat]]`.
+//!
+//! Buffers are bound to the Metal buffer indices of the entry point in the
+//! order of their set and binding, followed by the push constants. With
+//! argument buffers every set becomes a struct `Set<n>` bound at buffer index
+//! `n`, its members have the binding of the buffer as their id, and push
+//! constants follow the highest set of the module. Functions get the buffers
+//! they access, directly or through the functions they call, as additional
+//! reference parameters.
+//!
+//! Storage images are textures, bound to the texture indices of the entry
+//! point in the order of their set and binding, or passed in the argument
+//! buffer of their set like buffers. Sampled textures share the texture
+//! indices with them, samplers are bound to the sampler indices. The layer
+//! of a texture array is the last component of the coordinates in thiol and
+//! an argument of its own in Metal.
+//!
+//! Metal only stores matrices column by column, so row-major matrices in
+//! buffers and records are declared with the transposed type and transposed
+//! whenever they are read or written. Only whole matrices can be written,
+//! not their columns or elements.
+//!
+//! `long` and `ulong` are the 64 bit integers of Metal 2.2, literals of them
+//! get an `l` or `ul` suffix. Metal has no 64 bit floats, so `double` values
+//! are an error.
+//!
+//! Metal compiles with fast math unless the host disables it. Functions and
+//! programs whose float mode assumes no NaNs or infinities set the `fast`
+//! math mode with a pragma, ones that only reassociate the `relaxed` mode.
+//!
+//! Signed integer overflow is undefined in Metal, so wrapping arithmetic on
+//! signed integers is computed on their bits as unsigned integers and cast
+//! back with `as_type`. Saturating sums and differences are `addsat` and
+//! `subsat`.
+//!
+//! Metal has no timestamps in shaders, functions and programs with a
+//! profiling label start with a comment of the label, which GPU captures
+//! show with the code.
+//!
+//! Transforms the type checker inserts between spaces are multiplications
+//! with the constants, Metal has no matrix inverse so inverse transforms and
+//! the `inverse` intrinsic call a helper, as do the conversions between
+//! sRGB encoded and linear colours.
+//!
+//! Optionals are structs with a `has_value` flag next to the value, the
+//! default value of the struct is `none()`, which locals declared without a
+//! value start as.
+//!
+//! Assertions and debug prints write to the default log of Metal 3.2 with
+//! `log_error` and `log`, vectors are printed as their components in
+//! parentheses. They are left out in release builds.
+//!
+//! Indices into slices are clamped to the slice, and indices into arrays
+//! with a size to the array with the `clamp` bounds check. Metal has no way
+//! to stop an invocation, so the `trap` bounds check is an error.
+//!
+//! Names starting with `thiol_` are used by the generated code, names of the
+//! module that clash with them or with keywords of Metal get an underscore
+//! appended.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use thiol_backend::mangle::{Entity, MangledName, Mangler};
+use thiol_backend::{Artifact, Input, Output};
+use thiol_hir as hir;
+use thiol_typeck as typeck;
+
+use hir::{
+    Expression, FileLocation, Function, Identifier, MatchPattern, ParamMode, Program, Statement,
+    VariableDef,
+};
+use id_arena::Id;
+use typeck::consteval::{self, Constant, Evaluator};
+use typeck::debug_output::{self, FormatPiece, PrintScalar};
+use typeck::derives::{Derive, DerivedFunction};
+use typeck::images::{ImageAccess, ImageDim, ImageFormat, TexelScalar};
+use typeck::layout::buffer_class;
+use typeck::luts::{LookupTable, LutDomain};
+use typeck::textures::{SampledType, TextureDim};
+use typeck::{
+    BoundsCheck, BufferClass, Callable, Instance, IntegerOverflow, InterpolationMode, Intrinsic,
+    MatrixLayout, PackedFormat, Resolution, SpaceTransform, Stage, Symbol, Type, TypeId,
+};
+
+mod diagnostics;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// bind the buffers of each set through an argument buffer instead of
+    /// binding every buffer on its own
+    pub argument_buffers: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// Metal has no 64 bit floating point types
+    DoublePrecision {
+        loc: FileLocation,
+    },
+    ProgramWithoutStage {
+        name: Identifier,
+        loc: FileLocation,
+    },
+    /// a vertex program with outputs but none of them is the position
+    MissingPosition {
+        program: Identifier,
+        loc: FileLocation,
+    },
+    /// an input of a compute program that is not one of the builtins
+    ComputeInputWithoutBuiltin {
+        name: Identifier,
+        input: FileLocation,
+    },
+    ComputeProgramOutput {
+        name: Identifier,
+        output: FileLocation,
+    },
+    /// a column or element of a row-major matrix that is written, or the
+    /// matrix passed to an `out` parameter, Metal stores the matrix
+    /// transposed
+    RowMajorWriteInPlace {
+        loc: FileLocation,
+    },
+    /// an index that would trap when out of bounds, Metal can't stop an
+    /// invocation
+    TrapUnsupported {
+        loc: FileLocation,
+    },
+    /// Metal traces rays with intersectors in compute kernels, it has no
+    /// ray tracing stages
+    RayTracingProgram {
+        name: Identifier,
+        stage: Stage,
+        loc: FileLocation,
+    },
+    /// Metal object and mesh functions output meshes through a `mesh`
+    /// argument, not arrays per vertex and per primitive
+    MeshShadingProgram {
+        name: Identifier,
+        stage: Stage,
+        loc: FileLocation,
+    },
+    /// Metal computes the tessellation factors of patches in compute
+    /// kernels, it has no tessellation stages
+    TessellationProgram {
+        name: Identifier,
+        stage: Stage,
+        loc: FileLocation,
+    },
+    /// Metal has no geometry stage
+    GeometryProgram {
+        name: Identifier,
+        loc: FileLocation,
+    },
+}
+
+/// The source of a Metal library
+#[derive(Debug, Clone)]
+pub struct Library {
+    pub source: String,
+    /// the source names of the items named in the source
+    pub names: Vec<MangledName>,
+}
+
+/// The Metal backend, producing a library named after the module with the
+/// extension `.metal`
+#[derive(Debug, Clone, Default)]
+pub struct MslBackend {
+    pub options: Options,
+}
+
+impl thiol_backend::Backend for MslBackend {
+    fn name(&self) -> &str {
+        "msl"
+    }
+
+    fn description(&self) -> &str {
+        "Metal Shading Language"
+    }
+
+    fn emit(&self, input: Input<'_>) -> Output {
+        let programs = input
+            .module
+            .programs
+            .iter()
+            .map(|id| input.hir.identifiers[input.hir.programs[*id].name].clone())
+            .collect();
+        emit(input.hir, input.ty, input.module, &self.options)
+            .map(|library| {
+                let name = format!("{}.metal", input.name);
+                vec![Artifact::text(name, library.source)
+                    .with_entry_points(programs)
+                    .with_names(library.names)]
+            })
+            .into()
+    }
+}
+
+/// Translate a type checked module to the source of a Metal library.
+pub fn emit(
+    hir_ctx: &hir::Context,
+    ty_ctx: &typeck::Context,
+    module: &hir::Module,
+    options: &Options,
+) -> Result<Library, Vec<Error>> {
+    let mut e = Emitter {
+        hir: hir_ctx,
+        ty: ty_ctx,
+        module,
+        options,
+        instance: None,
+        names: Mangler::new(RESERVED, &["thiol_"]),
+        types: String::new(),
+        structs: BTreeMap::new(),
+        resources: HashMap::new(),
+        compare_exchange: false,
+        inverse: false,
+        colours: false,
+        ballot: false,
+        logging: false,
+        errs: vec![],
+    };
+
+    // items are named in the order of the module, so that their names don't
+    // depend on the order they are used in
+    for id in &module.consts {
+        let name = &hir_ctx.identifiers[hir_ctx.variable_defs[*id].name];
+        e.names.item(Entity::Constant(name.clone()));
+    }
+    for (id, instance) in functions(ty_ctx, hir_ctx, module) {
+        let name = &hir_ctx.identifiers[hir_ctx.functions[id].name];
+        let generics = instance.map_or(&[][..], |instance| &instance.generics);
+        e.names.item(Entity::function(ty_ctx, name, generics));
+    }
+    for (name, function) in &ty_ctx.derived_functions {
+        if function.derive == Derive::Eq {
+            e.names.item(Entity::function(ty_ctx, name, &[]));
+        }
+    }
+    for id in &module.programs {
+        let name = &hir_ctx.identifiers[hir_ctx.programs[*id].name];
+        e.names.item(Entity::Program(name.clone()));
+    }
+
+    for id in &module.functions {
+        let resources = e.function_resources(*id);
+        e.resources.insert(*id, resources);
+    }
+
+    let mut items = vec![];
+    if options.argument_buffers {
+        items.extend(e.argument_buffers());
+    }
+    let consts = module
+        .consts
+        .iter()
+        .filter_map(|id| e.constant(*id))
+        .collect::<Vec<_>>();
+    if !consts.is_empty() {
+        items.push(consts.join(""));
+    }
+    for (name, function) in &ty_ctx.derived_functions {
+        if function.derive == Derive::Eq {
+            items.push(e.derived_eq(name, function));
+        }
+    }
+
+    let functions = functions(ty_ctx, hir_ctx, module)
+        .into_iter()
+        .filter_map(|(id, instance)| {
+            e.instance = instance;
+            Some((id, instance, e.function_signature(id)?))
+        })
+        .collect::<Vec<_>>();
+    if !functions.is_empty() {
+        let prototypes = functions
+            .iter()
+            .map(|(_, _, sig)| format!("{};\n", sig))
+            .collect::<String>();
+        items.push(prototypes);
+    }
+    for (id, instance, sig) in functions {
+        e.instance = instance;
+        items.push(e.function(id, sig));
+    }
+    e.instance = None;
+    for id in &module.programs {
+        items.extend(e.program(*id));
+    }
+
+    if !e.errs.is_empty() {
+        return Err(e.errs);
+    }
+
+    let mut src = String::from("#include <metal_stdlib>\n");
+    if e.logging {
+        src.push_str("#include <metal_logging>\n");
+    }
+    src.push_str("using namespace metal;\n");
+    if e.compare_exchange {
+        src.push('\n');
+        src.push_str(COMPARE_EXCHANGE);
+    }
+    if e.inverse {
+        src.push('\n');
+        src.push_str(INVERSE);
+    }
+    if e.colours {
+        src.push('\n');
+        src.push_str(COLOURS);
+    }
+    if e.ballot {
+        src.push('\n');
+        src.push_str(BALLOT);
+    }
+    if !e.types.is_empty() {
+        src.push('\n');
+        src.push_str(&e.types);
+    }
+    for item in items {
+        src.push('\n');
+        src.push_str(&item);
+    }
+    Ok(Library {
+        source: src,
+        names: e.names.table(),
+    })
+}
+
+/// The functions to emit, generic functions once for every instance.
+fn functions<'a>(
+    ty_ctx: &'a typeck::Context,
+    hir_ctx: &hir::Context,
+    module: &hir::Module,
+) -> Vec<(Id<Function>, Option<&'a Instance>)> {
+    let mut functions = vec![];
+    for id in &module.functions {
+        if hir_ctx.functions[*id].generics.is_empty() {
+            functions.push((*id, None));
+        } else {
+            let instances = ty_ctx.instances.iter().filter(|i| i.func == *id);
+            functions.extend(instances.map(|instance| (*id, Some(instance))));
+        }
+    }
+    functions
+}
+
+/// `atomic_compare_exchange` gives the previous value like the other atomics,
+/// Metal only reports whether the exchange happened.
+const COMPARE_EXCHANGE: &str = "\
+template <typename A, typename T>
+T thiol_atomic_compare_exchange(A a, T cmp, T v)
+{
+    T expected = cmp;
+    while (!atomic_compare_exchange_weak_explicit(a, &expected, v, memory_order_relaxed, memory_order_relaxed) && expected == cmp)
+    {
+    }
+    return expected;
+}
+";
+
+/// `subgroup_ballot` gives the bits of the invocations in a `uint4`, the
+/// SIMD groups of Metal have at most 64 threads.
+const BALLOT: &str = "\
+uint4 thiol_subgroup_ballot(bool b)
+{
+    ulong bits = ulong(simd_ballot(b));
+    return uint4(uint(bits), uint(bits >> 32), 0, 0);
+}
+";
+
+/// The conversions between sRGB encoded and linear colours. The alpha of
+/// RGBA colours is linear in both encodings.
+const COLOURS: &str = "\
+template <typename V>
+V thiol_srgb_to_linear(V c)
+{
+    return select(pow((c + V(0.055)) / V(1.055), V(2.4)), c / V(12.92), c <= V(0.04045));
+}
+
+float4 thiol_srgb_to_linear(float4 c)
+{
+    return float4(thiol_srgb_to_linear(c.rgb), c.a);
+}
+
+half4 thiol_srgb_to_linear(half4 c)
+{
+    return half4(thiol_srgb_to_linear(c.rgb), c.a);
+}
+
+template <typename V>
+V thiol_linear_to_srgb(V c)
+{
+    return select(V(1.055) * pow(c, V(1.0 / 2.4)) - V(0.055), c * V(12.92), c <= V(0.0031308));
+}
+
+float4 thiol_linear_to_srgb(float4 c)
+{
+    return float4(thiol_linear_to_srgb(c.rgb), c.a);
+}
+
+half4 thiol_linear_to_srgb(half4 c)
+{
+    return half4(thiol_linear_to_srgb(c.rgb), c.a);
+}
+";
+
+/// The inverse of a square matrix by Gauss-Jordan elimination, for inverse
+/// transforms between spaces.
+const INVERSE: &str = "\
+template <typename T, int N>
+matrix<T, N, N> thiol_inverse(matrix<T, N, N> m)
+{
+    matrix<T, N, N> inv = matrix<T, N, N>(1);
+    for (int c = 0; c < N; c++)
+    {
+        int p = c;
+        for (int r = c + 1; r < N; r++)
+        {
+            if (abs(m[c][r]) > abs(m[c][p]))
+            {
+                p = r;
+            }
+        }
+        for (int k = 0; k < N; k++)
+        {
+            T t = m[k][c];
+            m[k][c] = m[k][p];
+            m[k][p] = t;
+            t = inv[k][c];
+            inv[k][c] = inv[k][p];
+            inv[k][p] = t;
+        }
+        T d = m[c][c];
+        for (int k = 0; k < N; k++)
+        {
+            m[k][c] /= d;
+            inv[k][c] /= d;
+        }
+        for (int r = 0; r < N; r++)
+        {
+            if (r != c)
+            {
+                T f = m[c][r];
+                for (int k = 0; k < N; k++)
+                {
+                    m[k][r] -= f * m[k][c];
+                    inv[k][r] -= f * inv[k][c];
+                }
+            }
+        }
+    }
+    return inv;
+}
+";
+
+const INDENT: &str = "    ";
+
+/// Keywords of Metal and C++ that are valid thiol identifiers.
+const RESERVED: &[&str] = &[
+    "auto",
+    "case",
+    "char",
+    "class",
+    "const",
+    "constant",
+    "default",
+    "delete",
+    "device",
+    "enum",
+    "explicit",
+    "extern",
+    "fragment",
+    "goto",
+    "inline",
+    "kernel",
+    "long",
+    "main",
+    "namespace",
+    "new",
+    "operator",
+    "private",
+    "public",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread",
+    "threadgroup",
+    "typedef",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "vertex",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+struct Emitter<'a> {
+    hir: &'a hir::Context,
+    ty: &'a typeck::Context,
+    module: &'a hir::Module,
+    options: &'a Options,
+    /// the instance of a generic function being emitted
+    instance: Option<&'a Instance>,
+    names: Mangler,
+    /// declarations of the record types, in an order where every struct
+    /// comes after the types of its fields
+    types: String,
+    structs: BTreeMap<TypeId, String>,
+    /// the buffers accessed by each function and the functions it calls
+    resources: HashMap<Id<Function>, Vec<Id<VariableDef>>>,
+    /// whether the helper for `atomic_compare_exchange` is used
+    compare_exchange: bool,
+    /// whether the helper for inverse transforms is used
+    inverse: bool,
+    /// whether the helpers for colour conversions are used
+    colours: bool,
+    /// whether the helper for `subgroup_ballot` is used
+    ballot: bool,
+    /// whether assertions or debug prints write to the log
+    logging: bool,
+    errs: Vec<Error>,
+}
+
+impl Emitter<'_> {
+    /// The name of an identifier in the code, the names of items and
+    /// locals are mangled.
+    fn name(&self, id: Id<Identifier>) -> String {
+        let name = &self.hir.identifiers[id];
+        let sym = self
+            .hir
+            .identifier_fcs
+            .get(&id)
+            .and_then(|loc| self.ty.references.symbol(*loc));
+        let mangled = sym.and_then(|sym| match Entity::from_symbol(sym, name) {
+            Some(item) => self.names.get(&item),
+            None => self.names.get_local(sym),
+        });
+        match mangled {
+            Some(mangled) => mangled.to_string(),
+            None => self.names.escape(name),
+        }
+    }
+
+    /// Declare a local in the innermost scope.
+    fn local(&mut self, sym: Symbol, id: Id<Identifier>) -> String {
+        self.names.local(sym, &self.hir.identifiers[id])
+    }
+
+    fn type_name(&mut self, ty: TypeId, loc: FileLocation) -> String {
+        let t = match self.ty.types.get(ty) {
+            Some(t) => t.clone(),
+            None => return "void".to_string(),
+        };
+        match t {
+            Type::Bool => "bool".to_string(),
+            Type::Int => "int".to_string(),
+            Type::UInt => "uint".to_string(),
+            Type::Long => "long".to_string(),
+            Type::ULong => "ulong".to_string(),
+            Type::Float | Type::Radians | Type::Degrees => "float".to_string(),
+            Type::Half => "half".to_string(),
+            Type::AtomicInt => "atomic_int".to_string(),
+            Type::AtomicUInt => "atomic_uint".to_string(),
+            Type::Double | Type::DoubleVec { .. } | Type::DoubleMat { .. } => {
+                self.double_precision(loc);
+                "double".to_string()
+            }
+            Type::BoolVec { components } => format!("bool{}", size(components)),
+            Type::IntVec { components, .. } => format!("int{}", size(components)),
+            Type::UIntVec { components, .. } => format!("uint{}", size(components)),
+            Type::LongVec { components, .. } => format!("long{}", size(components)),
+            Type::ULongVec { components, .. } => format!("ulong{}", size(components)),
+            Type::FloatVec { components, .. } => format!("float{}", size(components)),
+            Type::HalfVec { components, .. } => format!("half{}", size(components)),
+            Type::FloatMat { cols, rows, .. } => format!("float{}x{}", size(cols), size(rows)),
+            Type::Packed { format } => packed_type(format).to_string(),
+            Type::Array { base, size } => {
+                format!("array<{}, {}>", self.type_name(base, loc), size)
+            }
+            // the size is added by `declaration`
+            Type::OpenArray { base } => self.type_name(base, loc),
+            Type::Record { .. } => self.record(ty, ty, loc),
+            Type::Distinct { inner, .. } => match self.ty.types.get(inner) {
+                Some(Type::Record { .. }) => self.record(ty, inner, loc),
+                _ => self.type_name(inner, loc),
+            },
+            Type::Normalized { inner } => self.type_name(inner, loc),
+            Type::Optional { inner } => self.optional(ty, inner, loc),
+            Type::GenericParam { name, .. } => self.names.escape(&name),
+            Type::Image {
+                dim,
+                format,
+                access,
+            } => texture_type(dim, format, access),
+            Type::Texture { dim, sampled } => sampled_texture_type(dim, sampled),
+            Type::Sampler { .. } => "sampler".to_string(),
+            Type::AccelerationStructure => "instance_acceleration_structure".to_string(),
+            Type::Var(_) | Type::Error => "void".to_string(),
+        }
+    }
+
+    /// The type a value is stored as, row-major matrices are stored
+    /// transposed.
+    fn storage_type_name(
+        &mut self,
+        ty: TypeId,
+        loc: FileLocation,
+        matrices: MatrixLayout,
+    ) -> String {
+        if matrices == MatrixLayout::ColumnMajor {
+            return self.type_name(ty, loc);
+        }
+        match self.ty.types.get(ty) {
+            Some(Type::FloatMat { cols, rows, .. }) => {
+                format!("float{}x{}", size(*rows), size(*cols))
+            }
+            Some(Type::Array { base, size }) => {
+                let (base, size) = (*base, *size);
+                format!(
+                    "array<{}, {}>",
+                    self.storage_type_name(base, loc, matrices),
+                    size
+                )
+            }
+            Some(Type::OpenArray { base }) => self.storage_type_name(*base, loc, matrices),
+            _ => self.type_name(ty, loc),
+        }
+    }
+
+    /// Report a double precision type, once for every location it is
+    /// used at.
+    fn double_precision(&mut self, loc: FileLocation) {
+        let reported = self
+            .errs
+            .iter()
+            .any(|err| matches!(err, Error::DoublePrecision { loc: l } if *l == loc));
+        if !reported {
+            self.errs.push(Error::DoublePrecision { loc });
+        }
+    }
+
+    /// The name of the struct for a record type, the struct is declared when
+    /// the type is used for the first time.
+    fn record(&mut self, ty: TypeId, record: TypeId, loc: FileLocation) -> String {
+        if let Some(name) = self.structs.get(&ty) {
+            return name.clone();
+        }
+
+        // every instance of a generic record is a struct of its own
+        let name = self.names.item(Entity::Type(self.ty.instance_name(ty)));
+        self.structs.insert(ty, name.clone());
+
+        let fields = match self.ty.types.get(record) {
+            Some(Type::Record { fields }) => fields.clone(),
+            _ => vec![],
+        };
+        let field_defs = self.field_defs(ty);
+        let names = self.field_names(ty);
+        let mut decl = format!("struct {}\n{{\n", name);
+        for (index, ((_, field_ty), name)) in fields.into_iter().zip(names).enumerate() {
+            let matrices = match field_defs.get(index) {
+                Some(def) => self.ty.matrix_layout_of(*def),
+                None => self.ty.matrix_layout,
+            };
+            let field = self.storage_declaration(field_ty, &name, loc, matrices);
+            writeln!(decl, "{}{};", INDENT, field).unwrap();
+        }
+        decl.push_str("};\n");
+        if !self.types.is_empty() {
+            self.types.push('\n');
+        }
+        self.types.push_str(&decl);
+        name
+    }
+
+    /// The name of the struct for an optional type, the struct is declared
+    /// with the helper for `unwrap_or` when the type is used for the first
+    /// time.
+    fn optional(&mut self, ty: TypeId, inner: TypeId, loc: FileLocation) -> String {
+        if let Some(name) = self.structs.get(&ty) {
+            return name.clone();
+        }
+
+        let name = self.names.item(Entity::Type(self.ty.instance_name(ty)));
+        self.structs.insert(ty, name.clone());
+
+        let value = self.declaration(inner, "value", loc);
+        let inner_name = self.type_name(inner, loc);
+        let mut decl = format!(
+            "struct {}\n{{\n{}bool has_value;\n{}{};\n}};\n",
+            name, INDENT, INDENT, value
+        );
+        write!(
+            decl,
+            "\n{} thiol_unwrap_or({} o, {} d)\n{{\n{}return o.has_value ? o.value : d;\n}}\n",
+            inner_name, name, inner_name, INDENT
+        )
+        .unwrap();
+        if !self.types.is_empty() {
+            self.types.push('\n');
+        }
+        self.types.push_str(&decl);
+        name
+    }
+
+    /// The definitions of the fields of a record type.
+    fn field_defs(&self, ty: TypeId) -> Vec<Id<VariableDef>> {
+        let distinct_id = match self.ty.types.get(ty) {
+            Some(Type::Distinct { distinct_id, .. }) => *distinct_id,
+            _ => return vec![],
+        };
+        let def = match self.ty.distinct_defs.get(&distinct_id) {
+            Some(def) => self.hir.type_defs[*def].rhs,
+            None => return vec![],
+        };
+        match &self.hir.type_def_rhss[def] {
+            hir::TypeDefinitionRhs::Record { fields } => fields.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// A variable of the type, runtime sized arrays are declared with one
+    /// element so that they can be the last field of a buffer struct.
+    fn declaration(&mut self, ty: TypeId, name: &str, loc: FileLocation) -> String {
+        self.storage_declaration(ty, name, loc, MatrixLayout::ColumnMajor)
+    }
+
+    /// A buffer or field whose matrices are stored in the given layout.
+    fn storage_declaration(
+        &mut self,
+        ty: TypeId,
+        name: &str,
+        loc: FileLocation,
+        matrices: MatrixLayout,
+    ) -> String {
+        let ty_name = self.storage_type_name(ty, loc, matrices);
+        match self.ty.types.get(ty) {
+            Some(Type::OpenArray { .. }) => format!("{} {}[1]", ty_name, name),
+            _ => format!("{} {}", ty_name, name),
+        }
+    }
+
+    fn symbol_type(&self, sym: Symbol) -> TypeId {
+        let ty = self.ty.references.symbol_type(sym).unwrap_or_else(|| {
+            self.ty
+                .types
+                .id_of(&Type::Error)
+                .expect("symbols without a type only exist after errors")
+        });
+        self.concrete(ty)
+    }
+
+    /// The names of the fields of a record type.
+    fn field_names(&self, ty: TypeId) -> Vec<String> {
+        let fields = self.ty.record_fields(ty).unwrap_or_default();
+        self.names
+            .fields(fields.iter().map(|(name, _)| name.as_str()))
+    }
+
+    /// The name of a field of a value of the type, or of the components of
+    /// a vector.
+    fn field_name(&self, ty: Option<TypeId>, field: &str) -> String {
+        let fields = ty.and_then(|ty| self.ty.record_fields(ty));
+        let index = fields.and_then(|fields| fields.iter().position(|(name, _)| name == field));
+        match (ty, index) {
+            (Some(ty), Some(index)) => self.field_names(ty).swap_remove(index),
+            _ => self.names.escape(field),
+        }
+    }
+
+    fn expr_type(&self, id: Id<Expression>) -> Option<TypeId> {
+        let ty = self.ty.expr_types.get(&id)?;
+        Some(self.concrete(*ty))
+    }
+
+    /// The type a type of the function being emitted has in the instance
+    /// being emitted.
+    fn concrete(&self, ty: TypeId) -> TypeId {
+        match self.instance {
+            Some(instance) => instance.concrete(ty),
+            None => ty,
+        }
+    }
+
+    fn constant_type(&self, id: Id<VariableDef>) -> TypeId {
+        let name = &self.hir.identifiers[self.hir.variable_defs[id].name];
+        match self.ty.consts.get(name) {
+            Some(sig) => sig.type_,
+            None => self.symbol_type(Symbol::Constant(id)),
+        }
+    }
+
+    /// A constant that is not a buffer, as a program scope constant.
+    fn constant(&mut self, id: Id<VariableDef>) -> Option<String> {
+        if buffer_class(self.hir, id).is_some() {
+            return None;
+        }
+        let def = &self.hir.variable_defs[id];
+        let loc = self.hir.type_ref_fcs[&def.type_];
+        let ty = self.constant_type(id);
+        let decl = self.declaration(ty, &self.name(def.name), loc);
+        let value = match def.rhs {
+            Some(rhs) if consteval::is_evaluated_constant(self.ty, self.hir, id) => {
+                match Evaluator::new(self.ty, self.hir).eval_constant(rhs) {
+                    Ok(value) => self.composite(ty, &value, loc),
+                    Err(_) => self.expr(rhs),
+                }
+            }
+            Some(rhs) => self.expr(rhs),
+            None => return Some(format!("constant {};\n", decl)),
+        };
+        Some(format!("constant {} = {};\n", decl, value))
+    }
+
+    /// A value known when compiling, records and arrays as aggregate
+    /// initializers.
+    fn composite(&mut self, ty: TypeId, value: &Constant, loc: FileLocation) -> String {
+        let values = match value {
+            Constant::Scalar(value) => return value.to_string(),
+            Constant::Composite(values) => values,
+        };
+        let name = self.type_name(ty, loc);
+        match self.ty.composite_types(ty) {
+            Some(types) => {
+                let values = types
+                    .iter()
+                    .zip(values)
+                    .map(|(ty, value)| self.composite(*ty, value, loc))
+                    .collect::<Vec<_>>();
+                format!("{}{{{}}}", name, values.join(", "))
+            }
+            // the components of vectors
+            None => {
+                let values = values
+                    .iter()
+                    .map(|value| self.composite(ty, value, loc))
+                    .collect::<Vec<_>>();
+                format!("{}({})", name, values.join(", "))
+            }
+        }
+    }
+
+    /// A buffer as a reference parameter, or as a member of an argument
+    /// buffer if `member` is set. Runtime sized buffers are pointers to
+    /// their first element either way.
+    fn buffer(&mut self, id: Id<VariableDef>, member: bool) -> String {
+        let def = &self.hir.variable_defs[id];
+        // textures and samplers are passed by value
+        if self.is_opaque(id) {
+            let loc = self.hir.type_ref_fcs[&def.type_];
+            let ty = self.type_name(self.constant_type(id), loc);
+            return format!("{} {}", ty, self.name(def.name));
+        }
+        let space = match buffer_class(self.hir, id) {
+            Some(BufferClass::Storage) => "device",
+            _ => "constant",
+        };
+        let loc = self.hir.type_ref_fcs[&def.type_];
+        let matrices = self.ty.matrix_layout_of(id);
+        let constant_type = self.constant_type(id);
+        let ty = self.storage_type_name(constant_type, loc, matrices);
+        let open = matches!(
+            self.ty.types.get(constant_type),
+            Some(Type::OpenArray { .. })
+        );
+        let kind = if member || open { '*' } else { '&' };
+        format!("{} {}{} {}", space, ty, kind, self.name(def.name))
+    }
+
+    /// Whether a constant is bound as a storage image or sampled texture,
+    /// which are textures, or as a sampler.
+    fn is_opaque(&self, id: Id<VariableDef>) -> bool {
+        let ty = self.constant_type(id);
+        self.ty.image(ty).is_some() || self.ty.texture(ty).is_some() || self.is_sampler(id)
+    }
+
+    fn is_sampler(&self, id: Id<VariableDef>) -> bool {
+        self.ty.sampler(self.constant_type(id)).is_some()
+    }
+
+    /// The structs of the argument buffers of all sets used by the module.
+    fn argument_buffers(&mut self) -> Vec<String> {
+        let mut sets = BTreeMap::<u32, Vec<_>>::new();
+        for (id, binding) in &self.ty.bindings {
+            sets.entry(binding.binding.set)
+                .or_default()
+                .push((binding.binding.binding, *id));
+        }
+
+        let mut structs = vec![];
+        for (set, mut buffers) in sets {
+            buffers.sort();
+            let mut decl = format!("struct Set{}\n{{\n", set);
+            for (binding, id) in buffers {
+                let member = self.buffer(id, true);
+                writeln!(decl, "{}{} [[id({})]];", INDENT, member, binding).unwrap();
+            }
+            decl.push_str("};\n");
+            structs.push(decl);
+        }
+        structs
+    }
+
+    /// The buffers a function accesses, directly or through the functions it
+    /// calls, in the order they are declared.
+    fn function_resources(&self, id: Id<Function>) -> Vec<Id<VariableDef>> {
+        let mut callables = vec![Callable::Function(id)];
+        let mut i = 0;
+        while i < callables.len() {
+            for (callee, _) in self.ty.call_graph.dependencies(callables[i]) {
+                if !callables.contains(&callee) {
+                    callables.push(callee);
+                }
+            }
+            i += 1;
+        }
+        let spans = callables
+            .iter()
+            .filter_map(|callable| match callable {
+                Callable::Function(id) => Some(self.hir.function_fcs[id]),
+                Callable::Program(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        self.module
+            .consts
+            .iter()
+            .copied()
+            .filter(|id| buffer_class(self.hir, *id).is_some())
+            .filter(|id| {
+                self.ty
+                    .references
+                    .references(Symbol::Constant(*id))
+                    .iter()
+                    .any(|loc| {
+                        spans.iter().any(|span| {
+                            span.file == loc.file && span.start <= loc.start && loc.end <= span.end
+                        })
+                    })
+            })
+            .collect()
+    }
+
+    fn function_signature(&mut self, id: Id<Function>) -> Option<String> {
+        let func = &self.hir.functions[id];
+        let name = &self.hir.identifiers[func.name];
+        let sig = self.ty.function_sigs.get(name)?;
+        if sig.func_id != id {
+            return None;
+        }
+
+        let mut params = vec![];
+        self.names.push_scope();
+        for (index, (param, ty_ref, mode)) in func.args.iter().enumerate() {
+            let loc = self.hir.type_ref_fcs[ty_ref];
+            let ty = self.type_name(self.concrete(sig.args[index].1), loc);
+            let param = self.local(Symbol::Parameter { func: id, index }, *param);
+            params.push(match mode {
+                ParamMode::In => format!("{} {}", ty, param),
+                ParamMode::Out | ParamMode::InOut => format!("thread {}& {}", ty, param),
+            });
+        }
+        self.names.pop_scope();
+        for buffer in self.resources[&id].clone() {
+            params.push(self.buffer(buffer, false));
+        }
+
+        let loc = self.hir.type_ref_fcs[&func.ret_type];
+        let ret = self.type_name(self.concrete(sig.ret), loc);
+        let generics = self.instance.map_or(&[][..], |instance| &instance.generics);
+        let name = self.names.item(Entity::function(self.ty, name, generics));
+        Some(format!("{} {}({})", ret, name, params.join(", ")))
+    }
+
+    fn function(&mut self, id: Id<Function>, sig: String) -> String {
+        if let Some(table) = self.ty.lookup_tables.get(&id) {
+            return self.lookup_table(id, table, sig);
+        }
+        let mut src = format!("{}\n{{\n", sig);
+        src.push_str(&self.profile_marker(Callable::Function(id)));
+        src.push_str(&self.math_mode(Callable::Function(id)));
+        let func = &self.hir.functions[id];
+        self.names.push_scope();
+        for (index, (param, _, _)) in func.args.iter().enumerate() {
+            self.local(Symbol::Parameter { func: id, index }, *param);
+        }
+        self.block(&mut src, &func.body, 1, None);
+        self.names.pop_scope();
+        src.push_str("}\n");
+        src
+    }
+
+    /// A function with a `@lut` attribute, as a program scope constant array
+    /// of its values and a lookup of the entry of its argument.
+    fn lookup_table(&mut self, id: Id<Function>, table: &LookupTable, sig: String) -> String {
+        let func = &self.hir.functions[id];
+        let name = &self.hir.identifiers[func.name];
+        let ret = self.concrete(self.ty.function_sigs[name].ret);
+        let loc = self.hir.type_ref_fcs[&func.ret_type];
+        let size = table.values.len();
+        let lut = self.names.item(Entity::Constant(format!("{}_lut", name)));
+        let values = table
+            .values
+            .iter()
+            .map(|value| self.composite(ret, value, loc))
+            .collect::<Vec<_>>();
+        let arg = self.name(func.args[0].0);
+        let index = match table.domain {
+            LutDomain::Int => format!("clamp({}, 0, {})", arg, size - 1),
+            LutDomain::UInt => format!("min({}, {}u)", arg, size - 1),
+            LutDomain::Float => format!("int(rint(saturate({}) * {:?}))", arg, (size - 1) as f32),
+        };
+        let ty = format!("array<{}, {}>", self.type_name(ret, loc), size);
+        format!(
+            "constant {} {} = {}{{{}}};\n{}\n{{\n{}return {}[{}];\n}}\n",
+            ty,
+            lut,
+            ty,
+            values.join(", "),
+            sig,
+            INDENT,
+            lut,
+            index
+        )
+    }
+
+    /// The entry point of a program and the structs of its inputs and
+    /// outputs.
+    fn program(&mut self, id: Id<Program>) -> Vec<String> {
+        let prog = &self.hir.programs[id];
+        let name = self.name(prog.name);
+        let stage = match typeck::stages::program_stage(self.hir, id) {
+            Some(stage) if stage.is_ray_tracing() => {
+                self.errs.push(Error::RayTracingProgram {
+                    name: self.hir.identifiers[prog.name].clone(),
+                    stage,
+                    loc: self.hir.identifier_fcs[&prog.name],
+                });
+                return vec![];
+            }
+            Some(stage @ Stage::Task) | Some(stage @ Stage::Mesh) => {
+                self.errs.push(Error::MeshShadingProgram {
+                    name: self.hir.identifiers[prog.name].clone(),
+                    stage,
+                    loc: self.hir.identifier_fcs[&prog.name],
+                });
+                return vec![];
+            }
+            Some(stage) if stage.is_tessellation() => {
+                self.errs.push(Error::TessellationProgram {
+                    name: self.hir.identifiers[prog.name].clone(),
+                    stage,
+                    loc: self.hir.identifier_fcs[&prog.name],
+                });
+                return vec![];
+            }
+            Some(Stage::Geometry) => {
+                self.errs.push(Error::GeometryProgram {
+                    name: self.hir.identifiers[prog.name].clone(),
+                    loc: self.hir.identifier_fcs[&prog.name],
+                });
+                return vec![];
+            }
+            Some(stage) => stage,
+            None => {
+                self.errs.push(Error::ProgramWithoutStage {
+                    name: self.hir.identifiers[prog.name].clone(),
+                    loc: self.hir.identifier_fcs[&prog.name],
+                });
+                return vec![];
+            }
+        };
+
+        let qualifier = match stage {
+            Stage::Vertex => "vertex",
+            Stage::Fragment => "fragment",
+            Stage::Compute => "kernel",
+            Stage::RayGeneration
+            | Stage::ClosestHit
+            | Stage::AnyHit
+            | Stage::Miss
+            | Stage::Task
+            | Stage::Mesh
+            | Stage::TessellationControl
+            | Stage::TessellationEvaluation
+            | Stage::Geometry => return vec![],
+        };
+
+        let mut items = vec![];
+        let mut params = vec![];
+        let mut prologue = String::new();
+        self.names.push_scope();
+
+        // inputs
+        let mut stage_in = String::new();
+        for (index, input) in prog.inputs.iter().enumerate() {
+            let def = &self.hir.variable_defs[*input];
+            let input_name = self.local(Symbol::Local(*input), def.name);
+            let ty = self.symbol_type(Symbol::Local(*input));
+            let loc = self.hir.type_ref_fcs[&def.type_];
+            let ty_name = self.type_name(ty, loc);
+
+            if let Some(builtin) = self.builtin(*input, stage) {
+                let builtin_ty = match builtin {
+                    "position" => "float4".to_string(),
+                    "front_facing" => "bool".to_string(),
+                    _ => match self.ty.types.get(ty) {
+                        Some(Type::IntVec { components, .. })
+                        | Some(Type::UIntVec { components, .. }) => {
+                            format!("uint{}", size(*components))
+                        }
+                        _ => "uint".to_string(),
+                    },
+                };
+                params.push(format!(
+                    "{} thiol_{} [[{}]]",
+                    builtin_ty, input_name, builtin
+                ));
+                writeln!(
+                    prologue,
+                    "{}{} {} = static_cast<{}>(thiol_{});",
+                    INDENT, ty_name, input_name, ty_name, input_name
+                )
+                .unwrap();
+                continue;
+            }
+
+            let location = self.location(*input).unwrap_or(index);
+            let attribute = match stage {
+                Stage::Vertex => format!("attribute({})", location),
+                Stage::Fragment => match self.interpolation(*input) {
+                    Some(interpolation) => format!("user(locn{}), {}", location, interpolation),
+                    None => format!("user(locn{})", location),
+                },
+                Stage::Compute => {
+                    self.errs.push(Error::ComputeInputWithoutBuiltin {
+                        name: self.hir.identifiers[def.name].clone(),
+                        input: self.hir.variable_def_fcs[input],
+                    });
+                    continue;
+                }
+                // ray tracing programs were rejected above
+                Stage::RayGeneration
+                | Stage::ClosestHit
+                | Stage::AnyHit
+                | Stage::Miss
+                | Stage::Task
+                | Stage::Mesh
+                | Stage::TessellationControl
+                | Stage::TessellationEvaluation
+                | Stage::Geometry => continue,
+            };
+            writeln!(
+                stage_in,
+                "{}{} [[{}]];",
+                INDENT,
+                self.declaration(ty, &input_name, loc),
+                attribute
+            )
+            .unwrap();
+            writeln!(
+                prologue,
+                "{}{} = in.{};",
+                INDENT,
+                self.declaration(ty, &input_name, loc),
+                input_name
+            )
+            .unwrap();
+        }
+        if !stage_in.is_empty() {
+            items.push(format!("struct {}_in\n{{\n{}}};\n", name, stage_in));
+            params.insert(0, format!("{}_in in [[stage_in]]", name));
+        }
+
+        // buffers
+        let mut resources = self.ty.program_resources(self.hir, self.module, id);
+        // push constants have no binding and come last
+        resources.sort_by_key(|r| (r.binding.is_none(), r.binding.map(|b| b.binding)));
+        if self.options.argument_buffers {
+            let sets = resources
+                .iter()
+                .filter_map(|r| r.binding)
+                .map(|b| b.binding.set)
+                .collect::<BTreeSet<_>>();
+            for set in &sets {
+                params.push(format!(
+                    "constant Set{}& set{} [[buffer({})]]",
+                    set, set, set
+                ));
+            }
+            let mut push_constant_index = self
+                .ty
+                .bindings
+                .values()
+                .map(|b| b.binding.set + 1)
+                .max()
+                .unwrap_or(0);
+            for resource in &resources {
+                let buffer = self.buffer(resource.constant, false);
+                let deref = if self.is_opaque(resource.constant) {
+                    ""
+                } else {
+                    "*"
+                };
+                match resource.binding {
+                    Some(binding) => writeln!(
+                        prologue,
+                        "{}{} = {}set{}.{};",
+                        INDENT,
+                        buffer,
+                        deref,
+                        binding.binding.set,
+                        self.names.item(Entity::Constant(resource.name.clone()))
+                    )
+                    .unwrap(),
+                    None => {
+                        params.push(format!("{} [[buffer({})]]", buffer, push_constant_index));
+                        push_constant_index += 1;
+                    }
+                }
+            }
+        } else {
+            // textures and samplers are numbered apart from buffers
+            let (opaque, buffers) = resources
+                .iter()
+                .partition::<Vec<_>, _>(|resource| self.is_opaque(resource.constant));
+            let (samplers, textures) = opaque
+                .into_iter()
+                .partition::<Vec<_>, _>(|resource| self.is_sampler(resource.constant));
+            for (index, resource) in buffers.into_iter().enumerate() {
+                let buffer = self.buffer(resource.constant, false);
+                params.push(format!("{} [[buffer({})]]", buffer, index));
+            }
+            for (index, resource) in textures.into_iter().enumerate() {
+                let texture = self.buffer(resource.constant, false);
+                params.push(format!("{} [[texture({})]]", texture, index));
+            }
+            for (index, resource) in samplers.into_iter().enumerate() {
+                let sampler = self.buffer(resource.constant, false);
+                params.push(format!("{} [[sampler({})]]", sampler, index));
+            }
+        }
+
+        for var in &prog.workgroup {
+            let def = &self.hir.variable_defs[*var];
+            let loc = self.hir.type_ref_fcs[&def.type_];
+            let ty = self.symbol_type(Symbol::Local(*var));
+            let name = self.local(Symbol::Local(*var), def.name);
+            let decl = self.declaration(ty, &name, loc);
+            writeln!(prologue, "{}threadgroup {};", INDENT, decl).unwrap();
+        }
+
+        // outputs
+        let mut stage_out = String::new();
+        let mut position = false;
+        for (index, output) in prog.outputs.iter().enumerate() {
+            let def = &self.hir.variable_defs[*output];
+            let output_name = self.local(Symbol::Local(*output), def.name);
+            let ty = self.symbol_type(Symbol::Local(*output));
+            let loc = self.hir.type_ref_fcs[&def.type_];
+            let location = self.location(*output).unwrap_or(index);
+            let attribute = match stage {
+                Stage::Vertex if self.has_attribute(*output, "Position") => {
+                    position = true;
+                    "position".to_string()
+                }
+                Stage::Vertex => format!("user(locn{})", location),
+                Stage::Fragment => format!("color({})", location),
+                Stage::Compute => {
+                    self.errs.push(Error::ComputeProgramOutput {
+                        name: self.hir.identifiers[def.name].clone(),
+                        output: self.hir.variable_def_fcs[output],
+                    });
+                    continue;
+                }
+                Stage::RayGeneration
+                | Stage::ClosestHit
+                | Stage::AnyHit
+                | Stage::Miss
+                | Stage::Task
+                | Stage::Mesh
+                | Stage::TessellationControl
+                | Stage::TessellationEvaluation
+                | Stage::Geometry => continue,
+            };
+            let decl = self.declaration(ty, &output_name, loc);
+            writeln!(stage_out, "{}{} [[{}]];", INDENT, decl, attribute).unwrap();
+            let ty_name = self.type_name(ty, loc);
+            writeln!(
+                prologue,
+                "{}thread {}& {} = out.{};",
+                INDENT, ty_name, output_name, output_name
+            )
+            .unwrap();
+        }
+        if stage == Stage::Vertex && !stage_out.is_empty() && !position {
+            self.errs.push(Error::MissingPosition {
+                program: self.hir.identifiers[prog.name].clone(),
+                loc: self.hir.identifier_fcs[&prog.name],
+            });
+        }
+        let ret = if stage_out.is_empty() {
+            "void".to_string()
+        } else {
+            items.push(format!("struct {}_out\n{{\n{}}};\n", name, stage_out));
+            prologue.insert_str(0, &format!("{}{}_out out = {{}};\n", INDENT, name));
+            format!("{}_out", name)
+        };
+        let mut src = format!(
+            "{} {} {}({})\n{{\n{}{}{}",
+            qualifier,
+            ret,
+            name,
+            params.join(", "),
+            self.profile_marker(Callable::Program(id)),
+            self.math_mode(Callable::Program(id)),
+            prologue
+        );
+        let exit = if stage_out.is_empty() {
+            "return;"
+        } else {
+            "return out;"
+        };
+        self.block(&mut src, &prog.body, 1, Some(exit));
+        self.names.pop_scope();
+        if !stage_out.is_empty() {
+            writeln!(src, "{}{}", INDENT, exit).unwrap();
+        }
+        src.push_str("}\n");
+        items.push(src);
+        items
+    }
+
+    /// The comment marking a function or program with a profiling label,
+    /// empty for the others.
+    fn profile_marker(&self, callable: Callable) -> String {
+        match self.ty.profile_labels.get(&callable) {
+            Some(label) => format!("{}// profile: {:?}\n", INDENT, label),
+            None => String::new(),
+        }
+    }
+
+    /// The pragma that sets the math mode of a function or program whose
+    /// float mode isn't strict, empty for the others.
+    fn math_mode(&self, callable: Callable) -> String {
+        let mode = self.ty.float_mode(callable);
+        let math_mode = if mode.no_nans || mode.no_infs {
+            "fast"
+        } else if mode.reassociate {
+            "relaxed"
+        } else {
+            return String::new();
+        };
+        format!("{}#pragma METAL fp math_mode({})\n", INDENT, math_mode)
+    }
+
+    /// The attribute for the interpolation of a fragment input, `None` for
+    /// the default perspective interpolation at the center.
+    fn interpolation(&self, def: Id<VariableDef>) -> Option<&'static str> {
+        let interpolation = self.ty.interpolation.get(&def)?;
+        let attribute = match (interpolation.mode, interpolation.centroid) {
+            (InterpolationMode::Flat, _) => "flat",
+            (InterpolationMode::Perspective, false) => return None,
+            (InterpolationMode::Perspective, true) => "centroid_perspective",
+            (InterpolationMode::Linear, false) => "center_no_perspective",
+            (InterpolationMode::Linear, true) => "centroid_no_perspective",
+        };
+        Some(attribute)
+    }
+
+    fn has_attribute(&self, def: Id<VariableDef>, name: &str) -> bool {
+        self.hir.variable_defs[def]
+            .attrs
+            .iter()
+            .any(|attr| self.hir.identifiers[self.hir.attributes[*attr].name] == name)
+    }
+
+    /// The builtin an input of a program is bound to.
+    fn builtin(&self, def: Id<VariableDef>, stage: Stage) -> Option<&'static str> {
+        let builtins: &[(&str, &str)] = match stage {
+            Stage::Vertex => &[
+                ("VertexIndex", "vertex_id"),
+                ("InstanceIndex", "instance_id"),
+            ],
+            Stage::Fragment => &[("Position", "position"), ("FrontFacing", "front_facing")],
+            Stage::Compute => &[
+                ("GlobalInvocationId", "thread_position_in_grid"),
+                ("LocalInvocationId", "thread_position_in_threadgroup"),
+                ("LocalInvocationIndex", "thread_index_in_threadgroup"),
+                ("WorkgroupId", "threadgroup_position_in_grid"),
+                ("SubgroupInvocationId", "thread_index_in_simdgroup"),
+                ("SubgroupSize", "threads_per_simdgroup"),
+            ],
+            Stage::RayGeneration
+            | Stage::ClosestHit
+            | Stage::AnyHit
+            | Stage::Miss
+            | Stage::Task
+            | Stage::Mesh
+            | Stage::TessellationControl
+            | Stage::TessellationEvaluation
+            | Stage::Geometry => &[],
+        };
+        builtins
+            .iter()
+            .find(|(attr, _)| self.has_attribute(def, attr))
+            .map(|(_, builtin)| *builtin)
+    }
+
+    /// The argument of the `Location` attribute of an input or output.
+    fn location(&self, def: Id<VariableDef>) -> Option<usize> {
+        self.hir.variable_defs[def].attrs.iter().find_map(|attr| {
+            let attr = &self.hir.attributes[*attr];
+            if self.hir.identifiers[attr.name] != "Location" {
+                return None;
+            }
+            match self.hir.expressions[*attr.pos_args.first()?] {
+                Expression::Literal(hir::Literal::Integer(n, _)) => Some(n as usize),
+                _ => None,
+            }
+        })
+    }
+
+    /// Statements of a block, `exit` replaces `return` in program bodies.
+    fn block(
+        &mut self,
+        src: &mut String,
+        block: &[Id<Statement>],
+        depth: usize,
+        exit: Option<&str>,
+    ) {
+        self.names.push_scope();
+        for stmt in block {
+            self.statement(src, *stmt, depth, exit);
+        }
+        self.names.pop_scope();
+    }
+
+    fn statement(&mut self, src: &mut String, id: Id<Statement>, depth: usize, exit: Option<&str>) {
+        let indent = INDENT.repeat(depth);
+        match &self.hir.statements[id] {
+            Statement::Var(var) => {
+                let def = &self.hir.variable_defs[*var];
+                let ty = self.symbol_type(Symbol::Local(*var));
+                let loc = self.hir.type_ref_fcs[&def.type_];
+                let name = self.local(Symbol::Local(*var), def.name);
+                let decl = self.declaration(ty, &name, loc);
+                match def.rhs {
+                    Some(rhs) => writeln!(src, "{}{} = {};", indent, decl, self.expr(rhs)).unwrap(),
+                    // optionals without a value start as `none()`
+                    None if self.ty.optional_inner(ty).is_some() => {
+                        writeln!(src, "{}{} = {{}};", indent, decl).unwrap()
+                    }
+                    None => writeln!(src, "{}{};", indent, decl).unwrap(),
+                }
+            }
+            Statement::Becomes { lhs, rhs } => {
+                let (lhs, rhs) = if self.row_major_storage(*lhs) {
+                    let rhs = format!("transpose({})", self.expr(*rhs));
+                    (self.expr_value(*lhs), rhs)
+                } else {
+                    self.check_row_major_write(*lhs);
+                    (self.expr(*lhs), self.expr(*rhs))
+                };
+                writeln!(src, "{}{} = {};", indent, lhs, rhs).unwrap();
+            }
+            Statement::Destructure { lhs, rhs } => self.destructure(src, lhs, *rhs, depth),
+            Statement::Match { value, arms } => self.match_(src, id, *value, arms, depth, exit),
+            Statement::Return(e) => match (e, exit) {
+                (_, Some(exit)) => writeln!(src, "{}{}", indent, exit).unwrap(),
+                (Some(e), None) => writeln!(src, "{}return {};", indent, self.expr(*e)).unwrap(),
+                (None, None) => writeln!(src, "{}return;", indent).unwrap(),
+            },
+            Statement::Expr(e) => match self.ty.call_intrinsics.get(e) {
+                Some(intrinsic @ (Intrinsic::Assert | Intrinsic::DebugPrint)) => {
+                    self.debug_output(src, *e, *intrinsic, depth)
+                }
+                _ => writeln!(src, "{}{};", indent, self.expr(*e)).unwrap(),
+            },
+            Statement::Break => writeln!(src, "{}break;", indent).unwrap(),
+            Statement::Continue => writeln!(src, "{}continue;", indent).unwrap(),
+            Statement::Discard => writeln!(src, "{}discard_fragment();", indent).unwrap(),
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                writeln!(src, "{}if ({})", indent, self.expr(*cond)).unwrap();
+                writeln!(src, "{}{{", indent).unwrap();
+                self.block(src, then_body, depth + 1, exit);
+                writeln!(src, "{}}}", indent).unwrap();
+                if !else_body.is_empty() {
+                    writeln!(src, "{}else", indent).unwrap();
+                    writeln!(src, "{}{{", indent).unwrap();
+                    self.block(src, else_body, depth + 1, exit);
+                    writeln!(src, "{}}}", indent).unwrap();
+                }
+            }
+            Statement::For {
+                iter_name,
+                loop_type,
+                from,
+                to,
+                body,
+            } => {
+                let ty = self.symbol_type(Symbol::LoopVariable(id));
+                let ty = self.type_name(ty, self.hir.identifier_fcs[iter_name]);
+                let (from, to) = (self.expr(*from), self.expr(*to));
+                self.names.push_scope();
+                let name = self.local(Symbol::LoopVariable(id), *iter_name);
+                // both bounds are included
+                let (cmp, step) = match loop_type {
+                    hir::ForLoopType::Up => ("<=", "++"),
+                    hir::ForLoopType::Down => (">=", "--"),
+                };
+                writeln!(
+                    src,
+                    "{}for ({} {} = {}; {} {} {}; {}{})",
+                    indent, ty, name, from, name, cmp, to, name, step
+                )
+                .unwrap();
+                writeln!(src, "{}{{", indent).unwrap();
+                self.block(src, body, depth + 1, exit);
+                writeln!(src, "{}}}", indent).unwrap();
+                self.names.pop_scope();
+            }
+        }
+    }
+
+    /// Run the arm for the case of an optional, the optional is stored in a
+    /// variable of a block of its own so that it's computed once.
+    fn match_(
+        &mut self,
+        src: &mut String,
+        id: Id<Statement>,
+        value: Id<Expression>,
+        arms: &[hir::MatchArm],
+        depth: usize,
+        exit: Option<&str>,
+    ) {
+        let indent = INDENT.repeat(depth);
+        let inner = INDENT.repeat(depth + 1);
+        let ty = match self.expr_type(value) {
+            Some(ty) => ty,
+            None => return,
+        };
+        let some = arms
+            .iter()
+            .find(|arm| matches!(arm.pattern, MatchPattern::Some(_)));
+        let none = arms
+            .iter()
+            .find(|arm| matches!(arm.pattern, MatchPattern::None));
+
+        let loc = self.hir.expression_fcs[&value];
+        let decl = self.declaration(ty, "thiol_optional", loc);
+        writeln!(src, "{}{{", indent).unwrap();
+        writeln!(src, "{}{} = {};", inner, decl, self.expr(value)).unwrap();
+        writeln!(src, "{}if (thiol_optional.has_value)", inner).unwrap();
+        writeln!(src, "{}{{", inner).unwrap();
+        if let Some(arm) = some {
+            self.names.push_scope();
+            if let MatchPattern::Some(name) = arm.pattern {
+                let binding_ty = self.symbol_type(Symbol::MatchBinding(id));
+                let name = self.local(Symbol::MatchBinding(id), name);
+                let binding = self.declaration(binding_ty, &name, loc);
+                writeln!(
+                    src,
+                    "{}{}{} = thiol_optional.value;",
+                    inner, INDENT, binding
+                )
+                .unwrap();
+            }
+            self.block(src, &arm.body, depth + 2, exit);
+            self.names.pop_scope();
+        }
+        writeln!(src, "{}}}", inner).unwrap();
+        if let Some(arm) = none.filter(|arm| !arm.body.is_empty()) {
+            writeln!(src, "{}else", inner).unwrap();
+            writeln!(src, "{}{{", inner).unwrap();
+            self.block(src, &arm.body, depth + 2, exit);
+            writeln!(src, "{}}}", inner).unwrap();
+        }
+        writeln!(src, "{}}}", indent).unwrap();
+    }
+
+    /// An assertion or debug print written to the log, or nothing in
+    /// release builds. Vectors are printed component by component, so
+    /// vector values are stored in variables of a block of its own.
+    fn debug_output(
+        &mut self,
+        src: &mut String,
+        id: Id<Expression>,
+        intrinsic: Intrinsic,
+        depth: usize,
+    ) {
+        if self.ty.release {
+            return;
+        }
+        let args = match &self.hir.expressions[id] {
+            Expression::Call { pos_args, .. } => pos_args,
+            _ => return,
+        };
+        let string = |e: &Self, arg: Option<&Id<Expression>>| match arg
+            .map(|arg| &e.hir.expressions[*arg])
+        {
+            Some(Expression::Literal(hir::Literal::String(s))) => Some(e.hir.strings[*s].clone()),
+            _ => None,
+        };
+        self.logging = true;
+        let indent = INDENT.repeat(depth);
+        if intrinsic == Intrinsic::Assert {
+            let message =
+                string(self, args.get(1)).unwrap_or_else(|| "assertion failed".to_string());
+            writeln!(src, "{}if (!{})", indent, self.expr(args[0])).unwrap();
+            writeln!(src, "{}{{", indent).unwrap();
+            writeln!(
+                src,
+                "{}{}os_log_default.log_error({:?});",
+                indent,
+                INDENT,
+                message.replace('%', "%%")
+            )
+            .unwrap();
+            writeln!(src, "{}}}", indent).unwrap();
+            return;
+        }
+
+        let pieces = string(self, args.first())
+            .and_then(|format| debug_output::format_pieces(&format))
+            .unwrap_or_default();
+        let mut format = String::new();
+        let mut values = vec![];
+        let mut temporaries = vec![];
+        let mut args = args[1..].iter();
+        for piece in pieces {
+            let arg = match piece {
+                FormatPiece::Text(text) => {
+                    format.push_str(&text.replace('%', "%%"));
+                    continue;
+                }
+                FormatPiece::Value => match args.next() {
+                    Some(arg) => *arg,
+                    None => continue,
+                },
+            };
+            let ty = match self.expr_type(arg) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            let (scalar, components) = match self.ty.printable(ty) {
+                Some(printable) => printable,
+                None => continue,
+            };
+            let (specifier, cast) = match scalar {
+                PrintScalar::Bool => ("%d", Some("int")),
+                PrintScalar::Int => ("%d", None),
+                PrintScalar::UInt => ("%u", None),
+                PrintScalar::Long => ("%ld", None),
+                PrintScalar::ULong => ("%lu", None),
+                PrintScalar::Float | PrintScalar::Double => ("%f", None),
+                PrintScalar::Half => ("%f", Some("float")),
+            };
+            let value = self.expr(arg);
+            let cast = |value: String| match cast {
+                Some(cast) => format!("{}({})", cast, value),
+                None => value,
+            };
+            let components = match components {
+                Some(components) => size(components),
+                None => {
+                    format.push_str(specifier);
+                    values.push(cast(value));
+                    continue;
+                }
+            };
+            let name = format!("thiol_value_{}", temporaries.len());
+            let loc = self.hir.expression_fcs[&arg];
+            temporaries.push(format!("{} = {};", self.declaration(ty, &name, loc), value));
+            format.push('(');
+            format.push_str(&vec![specifier; components].join(", "));
+            format.push(')');
+            for component in &["x", "y", "z", "w"][..components] {
+                values.push(cast(format!("{}.{}", name, component)));
+            }
+        }
+
+        let log = std::iter::once(format!("{:?}", format))
+            .chain(values)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if temporaries.is_empty() {
+            writeln!(src, "{}os_log_default.log({});", indent, log).unwrap();
+            return;
+        }
+        writeln!(src, "{}{{", indent).unwrap();
+        for temporary in temporaries {
+            writeln!(src, "{}{}{}", indent, INDENT, temporary).unwrap();
+        }
+        writeln!(src, "{}{}os_log_default.log({});", indent, INDENT, log).unwrap();
+        writeln!(src, "{}}}", indent).unwrap();
+    }
+
+    /// Assign the fields of a record to the targets, the record is stored
+    /// in a variable of a block of its own so that it's computed once.
+    fn destructure(
+        &mut self,
+        src: &mut String,
+        lhs: &[Id<Expression>],
+        rhs: Id<Expression>,
+        depth: usize,
+    ) {
+        let indent = INDENT.repeat(depth);
+        let inner = INDENT.repeat(depth + 1);
+        let ty = match self.expr_type(rhs) {
+            Some(ty) => ty,
+            None => return,
+        };
+        let fields = self.ty.record_fields(ty).unwrap_or_default();
+        let names = self.field_names(ty);
+        let field_defs = self.field_defs(ty);
+
+        let decl = self.declaration(ty, "thiol_values", self.hir.expression_fcs[&rhs]);
+        writeln!(src, "{}{{", indent).unwrap();
+        writeln!(src, "{}{} = {};", inner, decl, self.expr(rhs)).unwrap();
+        for (index, (target, ((_, field_ty), field))) in
+            lhs.iter().zip(fields.into_iter().zip(names)).enumerate()
+        {
+            let mut value = format!("thiol_values.{}", field);
+            // matrices of row-major fields are stored transposed
+            let matrices = match field_defs.get(index) {
+                Some(def) => self.ty.matrix_layout_of(*def),
+                None => self.ty.matrix_layout,
+            };
+            let matrix = matches!(self.ty.types.get(field_ty), Some(Type::FloatMat { .. }));
+            if matrix && matrices == MatrixLayout::RowMajor {
+                value = format!("transpose({})", value);
+            }
+            let (target, value) = if self.row_major_storage(*target) {
+                (self.expr_value(*target), format!("transpose({})", value))
+            } else {
+                self.check_row_major_write(*target);
+                (self.expr(*target), value)
+            };
+            writeln!(src, "{}{} = {};", inner, target, value).unwrap();
+        }
+        writeln!(src, "{}}}", indent).unwrap();
+    }
+
+    fn expr(&mut self, id: Id<Expression>) -> String {
+        let mut value = self.expr_value(id);
+        if self.row_major_storage(id) {
+            value = format!("transpose({})", value);
+        }
+        match self.ty.space_transforms.get(&id) {
+            Some(transform) => self.space_transform(id, value, transform),
+            None => value,
+        }
+    }
+
+    /// Apply the transforms between two spaces to a vector.
+    fn space_transform(
+        &mut self,
+        id: Id<Expression>,
+        value: String,
+        transform: &SpaceTransform,
+    ) -> String {
+        let mut value = match transform.extended {
+            Some((ty, w)) => {
+                let loc = self.hir.expression_fcs[&id];
+                format!("{}({}, {:?})", self.type_name(ty, loc), value, w)
+            }
+            None => value,
+        };
+        for step in &transform.steps {
+            let mut matrix = self.names.item(Entity::Constant(step.via.clone()));
+            let row_major = self.ty.consts.get(&step.via).is_some_and(|sig| {
+                buffer_class(self.hir, sig.const_id).is_some()
+                    && self.ty.matrix_layout_of(sig.const_id) == MatrixLayout::RowMajor
+            });
+            if row_major {
+                matrix = format!("transpose({})", matrix);
+            }
+            value = if step.inverse {
+                self.inverse = true;
+                format!("(thiol_inverse({}) * {})", matrix, value)
+            } else {
+                format!("({} * {})", matrix, value)
+            };
+        }
+        match transform.extended {
+            Some(_) => format!("{}.{}", value, &"xyzw"[..transform.components]),
+            None => value,
+        }
+    }
+
+    /// Whether the expression is a matrix stored row by row in a buffer or
+    /// record, which Metal stores transposed.
+    fn row_major_storage(&self, id: Id<Expression>) -> bool {
+        let matrix = self
+            .expr_type(id)
+            .is_some_and(|ty| matches!(self.ty.types.get(ty), Some(Type::FloatMat { .. })));
+        matrix && self.row_major_place(id)
+    }
+
+    fn row_major_place(&self, id: Id<Expression>) -> bool {
+        let layout = match &self.hir.expressions[id] {
+            Expression::Variable(name) => {
+                match self.ty.references.symbol(self.hir.identifier_fcs[name]) {
+                    Some(Symbol::Constant(def)) if buffer_class(self.hir, def).is_some() => {
+                        self.ty.matrix_layout_of(def)
+                    }
+                    _ => return false,
+                }
+            }
+            Expression::Field { name, .. } => {
+                match self.ty.references.symbol(self.hir.identifier_fcs[name]) {
+                    Some(Symbol::Field { field, .. }) => self.ty.matrix_layout_of(field),
+                    _ => return false,
+                }
+            }
+            // an element of an array of matrices
+            Expression::Index { base, .. } | Expression::Slice { base, .. } => {
+                return self.row_major_place(*base)
+            }
+            _ => return false,
+        };
+        layout == MatrixLayout::RowMajor
+    }
+
+    /// Report writes to the columns and elements of row-major matrices.
+    fn check_row_major_write(&mut self, place: Id<Expression>) {
+        let mut current = place;
+        loop {
+            if self.row_major_storage(current) {
+                self.errs.push(Error::RowMajorWriteInPlace {
+                    loc: self.hir.expression_fcs[&place],
+                });
+                return;
+            }
+            current = match &self.hir.expressions[current] {
+                Expression::Field { base, .. }
+                | Expression::Index { base, .. }
+                | Expression::Slice { base, .. } => *base,
+                _ => return,
+            };
+        }
+    }
+
+    fn expr_value(&mut self, id: Id<Expression>) -> String {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            Expression::Literal(hir::Literal::Integer(n, _)) => {
+                let ty = self.expr_type(id).and_then(|ty| self.ty.types.get(ty));
+                match ty {
+                    Some(Type::UInt) => format!("{}u", n),
+                    Some(Type::Long) => format!("{}l", n),
+                    Some(Type::ULong) => format!("{}ul", n),
+                    Some(Type::Float) | Some(Type::Half) | Some(Type::Double)
+                    | Some(Type::Radians) | Some(Type::Degrees) => {
+                        format!("{}.0", n)
+                    }
+                    _ => n.to_string(),
+                }
+            }
+            Expression::Literal(hir::Literal::Float(f, _)) => format!("{:?}", f),
+            Expression::Literal(hir::Literal::Bool(b)) => b.to_string(),
+            Expression::Literal(hir::Literal::String(s)) => format!("{:?}", self.hir.strings[*s]),
+            Expression::Variable(name) => self.name(*name),
+            Expression::PrimitiveOp(op) => {
+                let binary = |e: &mut Self, a: Id<Expression>, op: &str, b: Id<Expression>| {
+                    format!("({} {} {})", e.expr(a), op, e.expr(b))
+                };
+                match &self.hir.prim_ops[*op] {
+                    PO::Neg(e) => format!("(-{})", self.expr(*e)),
+                    PO::Pos(e) => self.expr(*e),
+                    PO::Add(a, b) => self.arithmetic(id, *a, "+", *b),
+                    PO::Sub(a, b) => self.arithmetic(id, *a, "-", *b),
+                    PO::Mul(a, b) => self.arithmetic(id, *a, "*", *b),
+                    PO::Div(a, b) => binary(self, *a, "/", *b),
+                    PO::Mod(a, b) => {
+                        if self.is_float(*a) || self.is_float(*b) {
+                            format!("fmod({}, {})", self.expr(*a), self.expr(*b))
+                        } else {
+                            binary(self, *a, "%", *b)
+                        }
+                    }
+                    PO::Gt(a, b) => binary(self, *a, ">", *b),
+                    PO::Gte(a, b) => binary(self, *a, ">=", *b),
+                    PO::Lt(a, b) => binary(self, *a, "<", *b),
+                    PO::Lte(a, b) => binary(self, *a, "<=", *b),
+                    PO::Eq(a, b) => binary(self, *a, "==", *b),
+                    PO::Neq(a, b) => binary(self, *a, "!=", *b),
+                    PO::Constructor {
+                        ty,
+                        pos_args,
+                        nam_args,
+                    } => {
+                        let loc = self.hir.prim_op_fcs[op];
+                        let ty = self.primitive_type(ty, loc);
+                        let args = pos_args
+                            .iter()
+                            .chain(nam_args.iter().map(|(_, e)| e))
+                            .map(|e| self.expr(*e))
+                            .collect::<Vec<_>>();
+                        format!("{}({})", ty, args.join(", "))
+                    }
+                }
+            }
+            Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => {
+                if let Some(intrinsic) = self.ty.call_intrinsics.get(&id) {
+                    return self.intrinsic(id, *intrinsic, pos_args);
+                }
+                match self.ty.resolutions.get(*name) {
+                    Some(Resolution::Derived {
+                        derive: Derive::New,
+                        ..
+                    }) => return self.record_constructor(id, pos_args, nam_args),
+                    Some(Resolution::Derived {
+                        derive: Derive::Eq, ..
+                    }) => {
+                        let name = &self.hir.identifiers[*name];
+                        let callee = self.names.item(Entity::function(self.ty, name, &[]));
+                        let args = pos_args.iter().map(|e| self.expr(*e)).collect::<Vec<_>>();
+                        return format!("{}({})", callee, args.join(", "));
+                    }
+                    _ => {}
+                }
+                let func = match self.ty.references.symbol(self.hir.identifier_fcs[name]) {
+                    Some(Symbol::Function(func)) => func,
+                    Some(Symbol::Type(_)) => {
+                        return self.record_constructor(id, pos_args, nam_args)
+                    }
+                    _ => return format!("{}()", self.name(*name)),
+                };
+                let params = &self.hir.functions[func].args;
+                let mut args = vec![String::new(); params.len().max(pos_args.len())];
+                let named = nam_args
+                    .iter()
+                    .filter_map(|(arg_name, e)| {
+                        let index = params.iter().position(|(n, _, _)| {
+                            self.hir.identifiers[*n] == self.hir.identifiers[*arg_name]
+                        });
+                        Some((index?, *e))
+                    })
+                    .collect::<Vec<_>>();
+                for (index, e) in pos_args.iter().copied().enumerate().chain(named) {
+                    let out = params
+                        .get(index)
+                        .is_some_and(|(_, _, mode)| *mode != ParamMode::In);
+                    if out && self.row_major_storage(e) {
+                        // the matrix is passed as it is stored
+                        self.errs.push(Error::RowMajorWriteInPlace {
+                            loc: self.hir.expression_fcs[&e],
+                        });
+                    } else if out {
+                        self.check_row_major_write(e);
+                    }
+                    args[index] = self.expr(e);
+                }
+                args.truncate(params.len());
+                for buffer in &self.resources[&func] {
+                    args.push(self.name(self.hir.variable_defs[*buffer].name));
+                }
+                let callee = match self.ty.call_generics.get(&id) {
+                    Some(generics) => {
+                        let generics = generics
+                            .iter()
+                            .map(|ty| self.concrete(*ty))
+                            .collect::<Vec<_>>();
+                        let name = &self.hir.identifiers[*name];
+                        self.names.item(Entity::function(self.ty, name, &generics))
+                    }
+                    None => self.name(*name),
+                };
+                format!("{}({})", callee, args.join(", "))
+            }
+            Expression::Field { base, name } => {
+                let field = self.field_name(self.expr_type(*base), &self.hir.identifiers[*name]);
+                format!("{}.{}", self.expr(*base), field)
+            }
+            Expression::Index { base, index } => {
+                if let Some((array, lo, hi)) = self.slice_view(*base) {
+                    self.check_trap(id);
+                    let index = self.int_expr(*index);
+                    return format!(
+                        "{}[{} + clamp({}, 0, {} - {} - 1)]",
+                        array, lo, index, hi, lo
+                    );
+                }
+                let len = self.expr_type(*base).and_then(|ty| self.ty.array_len(ty));
+                let literal = matches!(self.hir.expressions[*index], Expression::Literal(_));
+                match (self.ty.bounds_check, len) {
+                    (BoundsCheck::Clamp, Some(len)) if !literal => {
+                        let index = self.int_expr(*index);
+                        format!("{}[clamp({}, 0, {})]", self.expr(*base), index, len - 1)
+                    }
+                    (BoundsCheck::Trap, Some(_)) if !literal => {
+                        self.check_trap(id);
+                        format!("{}[{}]", self.expr(*base), self.expr(*index))
+                    }
+                    _ => format!("{}[{}]", self.expr(*base), self.expr(*index)),
+                }
+            }
+            // the type checker only allows slices that are indexed
+            Expression::Slice { base, .. } => self.expr(*base),
+            Expression::As { base, ty } => {
+                let loc = self.hir.type_ref_fcs[ty];
+                let ty = match self.expr_type(id) {
+                    Some(ty) => self.type_name(ty, loc),
+                    None => "void".to_string(),
+                };
+                format!("static_cast<{}>({})", ty, self.expr(*base))
+            }
+            Expression::LayoutQuery { .. } => {
+                // queries that can't be answered are reported by the checker
+                match Evaluator::new(self.ty, self.hir).eval(id) {
+                    Ok(value) => value.to_string(),
+                    Err(_) => "0u".to_string(),
+                }
+            }
+            Expression::Array(elements) => {
+                let ty = match self.expr_type(id) {
+                    Some(ty) => self.type_name(ty, self.hir.expression_fcs[&id]),
+                    None => "void".to_string(),
+                };
+                let elements = elements.iter().map(|e| self.expr(*e)).collect::<Vec<_>>();
+                format!("{}{{{}}}", ty, elements.join(", "))
+            }
+        }
+    }
+
+    /// The constructor of a record as an aggregate initializer of its
+    /// struct, with the values of the fields in the order they are declared.
+    fn record_constructor(
+        &mut self,
+        id: Id<Expression>,
+        pos_args: &[Id<Expression>],
+        nam_args: &[(Id<Identifier>, Id<Expression>)],
+    ) -> String {
+        let ty = match self.expr_type(id) {
+            Some(ty) => ty,
+            None => return "void()".to_string(),
+        };
+        let fields = self.ty.record_fields(ty).unwrap_or_default();
+        let mut args = vec![String::new(); fields.len()];
+        let named = nam_args.iter().filter_map(|(name, e)| {
+            let name = &self.hir.identifiers[*name];
+            let index = fields.iter().position(|(field, _)| field == name)?;
+            Some((index, *e))
+        });
+        let values = pos_args.iter().copied().enumerate().chain(named);
+        for (index, e) in values.collect::<Vec<_>>() {
+            if let Some(arg) = args.get_mut(index) {
+                *arg = self.expr(e);
+            }
+        }
+        let name = self.type_name(ty, self.hir.expression_fcs[&id]);
+        format!("{}{{{}}}", name, args.join(", "))
+    }
+
+    /// The function `eq` derives for a record, which compares the fields of
+    /// two records.
+    fn derived_eq(&mut self, name: &str, function: &DerivedFunction) -> String {
+        let loc = self.hir.type_def_fcs[&function.def];
+        let record = self.type_name(function.record, loc);
+        let callee = self.names.item(Entity::function(self.ty, name, &[]));
+        let same = self.equality(function.record, "a", "b", MatrixLayout::ColumnMajor);
+        format!(
+            "bool {}({} a, {} b)\n{{\n{}return {};\n}}\n",
+            callee, record, record, INDENT, same
+        )
+    }
+
+    /// Whether two values of the type are the same, comparing the fields of
+    /// records, the elements of arrays and the columns of matrices, which
+    /// `==` doesn't.
+    fn equality(&self, ty: TypeId, a: &str, b: &str, matrices: MatrixLayout) -> String {
+        match self.ty.types.get(ty) {
+            Some(Type::Distinct { inner, .. }) => self.equality(*inner, a, b, matrices),
+            Some(Type::Normalized { inner }) => self.equality(*inner, a, b, matrices),
+            Some(Type::Record { fields }) => {
+                let field_defs = self.field_defs(ty);
+                let names = self.field_names(ty);
+                let fields = fields
+                    .iter()
+                    .zip(names)
+                    .enumerate()
+                    .map(|(index, ((_, field_ty), field))| {
+                        let matrices = match field_defs.get(index) {
+                            Some(def) => self.ty.matrix_layout_of(*def),
+                            None => self.ty.matrix_layout,
+                        };
+                        let a = format!("{}.{}", a, field);
+                        let b = format!("{}.{}", b, field);
+                        self.equality(*field_ty, &a, &b, matrices)
+                    })
+                    .collect::<Vec<_>>();
+                match fields.is_empty() {
+                    true => "true".to_string(),
+                    false => fields.join(" && "),
+                }
+            }
+            Some(Type::Array { base, size }) => {
+                let elements = (0..*size)
+                    .map(|i| {
+                        let a = format!("{}[{}]", a, i);
+                        let b = format!("{}[{}]", b, i);
+                        self.equality(*base, &a, &b, matrices)
+                    })
+                    .collect::<Vec<_>>();
+                match elements.is_empty() {
+                    true => "true".to_string(),
+                    false => elements.join(" && "),
+                }
+            }
+            Some(Type::FloatMat { cols, rows, .. } | Type::DoubleMat { cols, rows, .. }) => {
+                // row-major matrices are stored transposed
+                let cols = match matrices {
+                    MatrixLayout::ColumnMajor => size(*cols),
+                    MatrixLayout::RowMajor => size(*rows),
+                };
+                (0..cols)
+                    .map(|i| format!("all({}[{}] == {}[{}])", a, i, b, i))
+                    .collect::<Vec<_>>()
+                    .join(" && ")
+            }
+            Some(
+                Type::BoolVec { .. }
+                | Type::IntVec { .. }
+                | Type::UIntVec { .. }
+                | Type::LongVec { .. }
+                | Type::ULongVec { .. }
+                | Type::FloatVec { .. }
+                | Type::HalfVec { .. }
+                | Type::DoubleVec { .. },
+            ) => format!("all({} == {})", a, b),
+            Some(Type::Packed { format }) if format.size() == 8 => {
+                format!("all({} == {})", a, b)
+            }
+            _ => format!("{} == {}", a, b),
+        }
+    }
+
+    /// Report an index that is checked with the `trap` policy.
+    fn check_trap(&mut self, index: Id<Expression>) {
+        if self.ty.bounds_check == BoundsCheck::Trap {
+            self.errs.push(Error::TrapUnsupported {
+                loc: self.hir.expression_fcs[&index],
+            });
+        }
+    }
+
+    /// The array a slice is a view into and the bounds of the view in it.
+    fn slice_view(&mut self, id: Id<Expression>) -> Option<(String, String, String)> {
+        let (base, lo, hi) = match self.hir.expressions[id] {
+            Expression::Slice { base, lo, hi } => (base, lo, hi),
+            _ => return None,
+        };
+        let (lo, hi) = (self.int_expr(lo), self.int_expr(hi));
+        Some(match self.slice_view(base) {
+            Some((array, outer_lo, outer_hi)) => (
+                array,
+                format!("({} + {})", outer_lo, lo),
+                format!("min({} + {}, {})", outer_lo, hi, outer_hi),
+            ),
+            None => (self.expr(base), lo, hi),
+        })
+    }
+
+    /// An index or a bound of a slice as an `int`.
+    fn int_expr(&mut self, e: Id<Expression>) -> String {
+        let value = self.expr(e);
+        let int = self
+            .expr_type(e)
+            .is_some_and(|ty| self.ty.types.get(ty) == Some(&Type::Int));
+        if int {
+            value
+        } else {
+            format!("int({})", value)
+        }
+    }
+
+    fn is_float(&self, e: Id<Expression>) -> bool {
+        let ty = match self.expr_type(e) {
+            Some(ty) => ty,
+            None => return false,
+        };
+        matches!(
+            self.ty.types.get(ty),
+            Some(Type::Float)
+                | Some(Type::Half)
+                | Some(Type::Radians)
+                | Some(Type::Degrees)
+                | Some(Type::FloatVec { .. })
+                | Some(Type::HalfVec { .. })
+        )
+    }
+
+    /// A sum, difference or product, of integers as the overflow policy
+    /// says.
+    fn arithmetic(
+        &mut self,
+        id: Id<Expression>,
+        a: Id<Expression>,
+        op: &str,
+        b: Id<Expression>,
+    ) -> String {
+        let operands = [
+            (self.expr(a), self.expr_type(a)),
+            (self.expr(b), self.expr_type(b)),
+        ];
+        let integer = self
+            .ty
+            .integer_arithmetic_type(operands[0].1, operands[1].1)
+            .is_some();
+        match self.ty.integer_overflow {
+            IntegerOverflow::Saturate if integer && op == "+" => {
+                self.saturating(id, "addsat", operands)
+            }
+            IntegerOverflow::Saturate if integer && op == "-" => {
+                self.saturating(id, "subsat", operands)
+            }
+            IntegerOverflow::Wrap | IntegerOverflow::Saturate if integer => {
+                self.wrapping(id, op, operands)
+            }
+            _ => format!("({} {} {})", operands[0].0, op, operands[1].0),
+        }
+    }
+
+    /// Integer arithmetic that wraps around on overflow. Unsigned integers
+    /// wrap around in Metal, signed integers are computed with their bits as
+    /// unsigned integers of the same size.
+    fn wrapping(
+        &mut self,
+        id: Id<Expression>,
+        op: &str,
+        operands: [(String, Option<TypeId>); 2],
+    ) -> String {
+        let signed = |e: &Self, ty: Option<TypeId>| {
+            matches!(
+                ty.and_then(|ty| e.ty.integer_scalar(ty)),
+                Some(Type::Int | Type::Long)
+            )
+        };
+        let [(a, a_ty), (b, b_ty)] = operands;
+        let ty = self.ty.integer_arithmetic_type(a_ty, b_ty);
+        if !signed(self, ty) {
+            return format!("({} {} {})", a, op, b);
+        }
+        let loc = self.hir.expression_fcs[&id];
+        let mut bits = |value: String, ty: Option<TypeId>| match ty {
+            Some(ty) if signed(self, Some(ty)) => {
+                format!("as_type<u{}>({})", self.type_name(ty, loc), value)
+            }
+            _ => value,
+        };
+        let (a, b) = (bits(a, a_ty), bits(b, b_ty));
+        let ty = self.type_name(ty.unwrap(), loc);
+        format!("as_type<{}>({} {} {})", ty, a, op, b)
+    }
+
+    /// A call of `addsat` or `subsat`, which take operands of the same type,
+    /// so scalars combined with vectors are converted to the vector.
+    fn saturating(
+        &mut self,
+        id: Id<Expression>,
+        func: &str,
+        operands: [(String, Option<TypeId>); 2],
+    ) -> String {
+        let ty = self
+            .ty
+            .integer_arithmetic_type(operands[0].1, operands[1].1);
+        let ty_name = match ty {
+            Some(ty) => self.type_name(ty, self.hir.expression_fcs[&id]),
+            None => "void".to_string(),
+        };
+        let [a, b] = operands.map(|(value, value_ty)| {
+            if value_ty == ty {
+                value
+            } else {
+                format!("{}({})", ty_name, value)
+            }
+        });
+        format!("{}({}, {})", func, a, b)
+    }
+
+    fn intrinsic(
+        &mut self,
+        id: Id<Expression>,
+        intrinsic: Intrinsic,
+        args: &[Id<Expression>],
+    ) -> String {
+        let arg_types = args.iter().map(|e| self.expr_type(*e)).collect::<Vec<_>>();
+        let args = args.iter().map(|e| self.expr(*e)).collect::<Vec<_>>();
+        let atomic = |op: &str| {
+            format!(
+                "atomic_{}_explicit(&{}, memory_order_relaxed)",
+                op,
+                args.join(", ")
+            )
+        };
+        match intrinsic {
+            Intrinsic::Unpack => {
+                let format = match arg_types[0].and_then(|ty| self.ty.types.get(ty)) {
+                    Some(Type::Packed { format }) => *format,
+                    _ => return args[0].clone(),
+                };
+                unpack(format, &args[0])
+            }
+            Intrinsic::AtomicAdd => atomic("fetch_add"),
+            Intrinsic::AtomicMin => atomic("fetch_min"),
+            Intrinsic::AtomicMax => atomic("fetch_max"),
+            Intrinsic::AtomicExchange => atomic("exchange"),
+            Intrinsic::AtomicCompareExchange => {
+                self.compare_exchange = true;
+                format!("thiol_atomic_compare_exchange(&{})", args.join(", "))
+            }
+            Intrinsic::WorkgroupBarrier => {
+                "threadgroup_barrier(mem_flags::mem_threadgroup)".to_string()
+            }
+            Intrinsic::StorageBarrier => "threadgroup_barrier(mem_flags::mem_device)".to_string(),
+            Intrinsic::ImageLoad | Intrinsic::ImageStore => {
+                let dim = match arg_types[0].and_then(|ty| self.ty.image(ty)) {
+                    Some((dim, _, _)) => dim,
+                    None => return "void()".to_string(),
+                };
+                let coords = texture_coordinates(dim, &args[1]);
+                match intrinsic {
+                    Intrinsic::ImageLoad => format!("{}.read({})", args[0], coords),
+                    _ => format!("{}.write({}, {})", args[0], args[2], coords),
+                }
+            }
+            Intrinsic::Sample
+            | Intrinsic::SampleLod
+            | Intrinsic::SampleGrad
+            | Intrinsic::SampleCompare
+            | Intrinsic::Gather
+            | Intrinsic::Fetch => {
+                let dim = match arg_types[0].and_then(|ty| self.ty.texture(ty)) {
+                    Some((dim, _)) => dim,
+                    None => return "void()".to_string(),
+                };
+                let (texture, sampler) = (&args[0], &args[1]);
+                let coords = || sample_coordinates(dim, &args[2]);
+                match intrinsic {
+                    Intrinsic::Sample => format!("{}.sample({}, {})", texture, sampler, coords()),
+                    Intrinsic::SampleLod => format!(
+                        "{}.sample({}, {}, level({}))",
+                        texture,
+                        sampler,
+                        coords(),
+                        args[3]
+                    ),
+                    Intrinsic::SampleGrad => format!(
+                        "{}.sample({}, {}, {}({}, {}))",
+                        texture,
+                        sampler,
+                        coords(),
+                        gradient(dim),
+                        args[3],
+                        args[4]
+                    ),
+                    Intrinsic::SampleCompare => format!(
+                        "{}.sample_compare({}, {}, {})",
+                        texture,
+                        sampler,
+                        coords(),
+                        args[3]
+                    ),
+                    Intrinsic::Gather => format!("{}.gather({}, {})", texture, sampler, coords()),
+                    _ => format!(
+                        "{}.read({}, uint({}))",
+                        texture,
+                        texel_coordinates(dim, &args[1]),
+                        args[2]
+                    ),
+                }
+            }
+            // only ray tracing programs, which are rejected, trace rays, and
+            // only geometry programs emit primitives
+            Intrinsic::TraceRay | Intrinsic::EmitVertex | Intrinsic::EndPrimitive => {
+                format!("{}({})", intrinsic.name(), args.join(", "))
+            }
+            Intrinsic::SubgroupBallot => {
+                self.ballot = true;
+                format!("thiol_subgroup_ballot({})", args[0])
+            }
+            Intrinsic::SubgroupShuffle => format!("simd_shuffle({}, ushort({}))", args[0], args[1]),
+            Intrinsic::SubgroupBroadcast => {
+                format!("simd_broadcast({}, ushort({}))", args[0], args[1])
+            }
+            Intrinsic::SubgroupAdd => format!("simd_sum({})", args[0]),
+            Intrinsic::SubgroupMin => format!("simd_min({})", args[0]),
+            Intrinsic::SubgroupMax => format!("simd_max({})", args[0]),
+            Intrinsic::WrappingAdd | Intrinsic::WrappingSub | Intrinsic::WrappingMul => {
+                let op = match intrinsic {
+                    Intrinsic::WrappingAdd => "+",
+                    Intrinsic::WrappingSub => "-",
+                    _ => "*",
+                };
+                let operands = [
+                    (args[0].clone(), arg_types[0]),
+                    (args[1].clone(), arg_types[1]),
+                ];
+                self.wrapping(id, op, operands)
+            }
+            Intrinsic::SaturatingAdd => format!("addsat({}, {})", args[0], args[1]),
+            Intrinsic::SaturatingSub => format!("subsat({}, {})", args[0], args[1]),
+            Intrinsic::ApproxEq => {
+                let close = format!("abs({} - {}) <= {}", args[0], args[1], args[2]);
+                match arg_types[0] {
+                    Some(ty) if self.ty.is_float_vector(ty) => format!("all({})", close),
+                    _ => format!("({})", close),
+                }
+            }
+            Intrinsic::Bitcast => match self.expr_type(id) {
+                Some(ty) => {
+                    let loc = self.hir.expression_fcs[&id];
+                    format!("as_type<{}>({})", self.type_name(ty, loc), args[0])
+                }
+                None => args[0].clone(),
+            },
+            // `select` of Metal takes the value for false first
+            Intrinsic::Select => match arg_types[0].and_then(|ty| self.ty.vector_size(ty)) {
+                Some(_) => format!("select({}, {}, {})", args[2], args[1], args[0]),
+                None => format!("({} ? {} : {})", args[0], args[1], args[2]),
+            },
+            Intrinsic::Any | Intrinsic::All => format!("{}({})", intrinsic.name(), args[0]),
+            Intrinsic::Dpdx => format!("dfdx({})", args[0]),
+            Intrinsic::Dpdy => format!("dfdy({})", args[0]),
+            Intrinsic::Fwidth => format!("fwidth({})", args[0]),
+            Intrinsic::Transpose => format!("transpose({})", args[0]),
+            Intrinsic::Determinant => format!("determinant({})", args[0]),
+            Intrinsic::Normalize
+            | Intrinsic::Reflect
+            | Intrinsic::Refract
+            | Intrinsic::Sin
+            | Intrinsic::Cos
+            | Intrinsic::Tan
+            | Intrinsic::Asin
+            | Intrinsic::Acos
+            | Intrinsic::Atan
+            | Intrinsic::Atan2 => format!("{}({})", intrinsic.name(), args.join(", ")),
+            Intrinsic::ToRadians => format!("({} * 0.0174532924)", args[0]),
+            Intrinsic::ToDegrees => format!("({} * 57.2957795)", args[0]),
+            Intrinsic::Inverse => {
+                self.inverse = true;
+                format!("thiol_inverse({})", args[0])
+            }
+            Intrinsic::Identity => match self.expr_type(id) {
+                Some(ty) => {
+                    let loc = self.hir.expression_fcs[&id];
+                    format!("{}(1.0)", self.type_name(ty, loc))
+                }
+                None => "void()".to_string(),
+            },
+            Intrinsic::Some => match self.expr_type(id) {
+                Some(ty) => {
+                    let loc = self.hir.expression_fcs[&id];
+                    format!("{}{{true, {}}}", self.type_name(ty, loc), args[0])
+                }
+                None => "void()".to_string(),
+            },
+            // the default value of the struct has no value
+            Intrinsic::None => match self.expr_type(id) {
+                Some(ty) => {
+                    let loc = self.hir.expression_fcs[&id];
+                    format!("{}{{}}", self.type_name(ty, loc))
+                }
+                None => "void()".to_string(),
+            },
+            Intrinsic::UnwrapOr => format!("thiol_unwrap_or({})", args.join(", ")),
+            // emitted as statements, they have no value
+            Intrinsic::Assert | Intrinsic::DebugPrint => "void()".to_string(),
+            Intrinsic::SrgbToLinear | Intrinsic::LinearToSrgb => {
+                self.colours = true;
+                format!("thiol_{}({})", intrinsic.name(), args[0])
+            }
+        }
+    }
+
+    fn primitive_type(&mut self, ty: &hir::PrimitiveType, loc: FileLocation) -> String {
+        use hir::PrimitiveType as PT;
+
+        match ty {
+            PT::Bool => "bool".to_string(),
+            PT::Int => "int".to_string(),
+            PT::UInt => "uint".to_string(),
+            PT::Long => "long".to_string(),
+            PT::ULong => "ulong".to_string(),
+            PT::Float | PT::Radians | PT::Degrees => "float".to_string(),
+            PT::Half => "half".to_string(),
+            PT::AtomicInt => "atomic_int".to_string(),
+            PT::AtomicUInt => "atomic_uint".to_string(),
+            PT::Double | PT::DoubleVec { .. } | PT::DoubleMat { .. } => {
+                self.double_precision(loc);
+                "double".to_string()
+            }
+            PT::BoolVec { components } => format!("bool{}", size((*components).into())),
+            PT::IntVec { components, .. } => format!("int{}", size((*components).into())),
+            PT::UIntVec { components, .. } => format!("uint{}", size((*components).into())),
+            PT::LongVec { components, .. } => format!("long{}", size((*components).into())),
+            PT::ULongVec { components, .. } => format!("ulong{}", size((*components).into())),
+            PT::FloatVec { components, .. } => format!("float{}", size((*components).into())),
+            PT::HalfVec { components, .. } => format!("half{}", size((*components).into())),
+            PT::FloatMat { cols, rows, .. } => {
+                format!("float{}x{}", size((*cols).into()), size((*rows).into()))
+            }
+            PT::Packed { format } => packed_type((*format).into()).to_string(),
+        }
+    }
+}
+
+fn size(s: typeck::VecSize) -> usize {
+    match s {
+        typeck::VecSize::VS2 => 2,
+        typeck::VecSize::VS3 => 3,
+        typeck::VecSize::VS4 => 4,
+    }
+}
+
+/// The Metal texture type of a storage image.
+fn texture_type(dim: ImageDim, format: ImageFormat, access: ImageAccess) -> String {
+    let texture = match dim {
+        ImageDim::D1 => "texture1d",
+        ImageDim::D2 => "texture2d",
+        ImageDim::D3 => "texture3d",
+        ImageDim::D2Array => "texture2d_array",
+    };
+    let scalar = match format.texel_scalar() {
+        TexelScalar::Float => "float",
+        TexelScalar::UInt => "uint",
+        TexelScalar::Int => "int",
+    };
+    format!("{}<{}, access::{}>", texture, scalar, access.name())
+}
+
+/// The coordinates of a texel as the arguments of `read` and `write`, the
+/// layer of an array is an argument of its own.
+fn texture_coordinates(dim: ImageDim, coords: &str) -> String {
+    match dim {
+        ImageDim::D1 => format!("uint({})", coords),
+        ImageDim::D2 => format!("uint2({})", coords),
+        ImageDim::D3 => format!("uint3({})", coords),
+        ImageDim::D2Array => format!("uint2({0}.xy), uint({0}.z)", coords),
+    }
+}
+
+/// The Metal texture type of a sampled texture, depth textures have their
+/// own types.
+fn sampled_texture_type(dim: TextureDim, sampled: SampledType) -> String {
+    let (texture, depth) = match dim {
+        TextureDim::D1 => ("texture1d", "depth1d"),
+        TextureDim::D2 => ("texture2d", "depth2d"),
+        TextureDim::D3 => ("texture3d", "depth3d"),
+        TextureDim::Cube => ("texturecube", "depthcube"),
+        TextureDim::D2Array => ("texture2d_array", "depth2d_array"),
+    };
+    match sampled.scalar_name() {
+        Some(scalar) => format!("{}<{}>", texture, scalar),
+        None => format!("{}<float>", depth),
+    }
+}
+
+/// The coordinates a texture is sampled at as the arguments of `sample`,
+/// `sample_compare` and `gather`.
+fn sample_coordinates(dim: TextureDim, coords: &str) -> String {
+    match dim {
+        TextureDim::D2Array => format!("{0}.xy, uint({0}.z)", coords),
+        _ => coords.to_string(),
+    }
+}
+
+/// The coordinates of a texel as the arguments of `read`.
+fn texel_coordinates(dim: TextureDim, coords: &str) -> String {
+    match dim {
+        TextureDim::D1 => format!("uint({})", coords),
+        TextureDim::D2 => format!("uint2({})", coords),
+        TextureDim::D3 | TextureDim::Cube => format!("uint3({})", coords),
+        TextureDim::D2Array => format!("uint2({0}.xy), uint({0}.z)", coords),
+    }
+}
+
+/// The gradient option of `sample` for textures with the dimensions.
+fn gradient(dim: TextureDim) -> &'static str {
+    match dim {
+        TextureDim::D3 => "gradient3d",
+        TextureDim::Cube => "gradientcube",
+        _ => "gradient2d",
+    }
+}
+
+/// The integer type a packed value is stored in.
+fn packed_type(format: PackedFormat) -> &'static str {
+    match format.size() {
+        8 => "uint2",
+        _ => "uint",
+    }
+}
+
+fn unpack(format: PackedFormat, arg: &str) -> String {
+    match format {
+        PackedFormat::Unorm8x4 => format!("unpack_unorm4x8_to_float({})", arg),
+        PackedFormat::Snorm8x4 => format!("unpack_snorm4x8_to_float({})", arg),
+        PackedFormat::Uint8x4 => format!("uint4(as_type<uchar4>({}))", arg),
+        PackedFormat::Sint8x4 => format!("int4(as_type<char4>({}))", arg),
+        PackedFormat::Unorm16x2 => format!("unpack_unorm2x16_to_float({})", arg),
+        PackedFormat::Snorm16x2 => format!("unpack_snorm2x16_to_float({})", arg),
+        PackedFormat::Float16x2 => format!("float2(as_type<half2>({}))", arg),
+        PackedFormat::Float16x4 => format!("float4(as_type<half4>({}))", arg),
+        PackedFormat::Rgb10a2 => format!("unpack_unorm10a2_to_float({})", arg),
+    }
+}
@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Large synthetic modules for the benchmarks.
+//!
+//! The modules are valid thiol, so the benchmarks of later phases measure
+//! the same work as for real code. Every record type uses the types before
+//! it through instances of generic records nested `depth` levels deep, like
+//! `Box<Pair<Box<T3>, T2>>`, which makes every level a distinct type to
+//! resolve and intern.
+
+use std::fmt::Write;
+
+/// The generic records the generated types are built from.
+const GENERICS: &str = "type
+    Box<T> = record
+        value: T;
+        weight: float;
+    end
+    Pair<A, B> = record
+        first: A;
+        second: B;
+    end
+";
+
+/// The nested generic instance of level `depth` around `T{index}`.
+fn nested(index: usize, depth: usize) -> String {
+    (0..depth).fold(format!("T{}", index), |inner, level| {
+        if level % 2 == 0 {
+            format!("Box<{}>", inner)
+        } else {
+            format!("Pair<{}, T{}>", inner, index.saturating_sub(level))
+        }
+    })
+}
+
+/// A module with `types` record types whose fields nest generic instances
+/// `depth` levels deep.
+pub fn types(types: usize, depth: usize) -> String {
+    let mut src = GENERICS.to_string();
+    src.push_str("type\n    T0 = record\n        x: float3;\n        y: int;\n    end\n");
+    for i in 1..types {
+        let _ = write!(
+            src,
+            "    T{i} = record
+        previous: T{prev};
+        nested: {nested};
+        values: array[4] of T{prev};
+        x: float3;
+        y: int;
+    end
+",
+            i = i,
+            prev = i - 1,
+            nested = nested(i - 1, depth),
+        );
+    }
+    src
+}
+
+/// A module with the types of [`types`] and a function for every type,
+/// with statements and expressions for the parser and the checks of
+/// bodies.
+pub fn module(types: usize, depth: usize) -> String {
+    let mut src = self::types(types, depth);
+    for i in 0..types {
+        let _ = write!(
+            src,
+            "
+function sum{i}(v: T{i}, n: int) returns float
+begin
+    var total: float := 0.0;
+    for k in 0 to n do
+        total := total + v.x.x * 2.0 - (v.y + k) as float;
+    end
+    if total > 10.0 then
+        return total / 2.0;
+    end
+    return total;
+end
+",
+            i = i,
+        );
+    }
+    src
+}
@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use thiol_syntax::{cst, lexer, parser};
+
+mod generate;
+
+const SIZES: &[usize] = &[100, 1_000];
+
+fn bench_parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    for &size in SIZES {
+        let src = generate::module(size, 6);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+        group.bench_with_input(BenchmarkId::new("tokenise", size), &src, |b, src| {
+            b.iter(|| lexer::tokenise(0, src).count())
+        });
+        group.bench_with_input(BenchmarkId::new("parse_file", size), &src, |b, src| {
+            b.iter(|| parser::parse_file(0, src).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("cst", size), &src, |b, src| {
+            b.iter(|| cst::SourceFile::parse(0, src))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parser);
+criterion_main!(benches);
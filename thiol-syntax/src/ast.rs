@@ -2,24 +2,51 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use crate::Loc;
+use std::collections::BTreeMap;
+
+use crate::trivia::Trivia;
+use crate::{FileLocation, Loc};
 
 pub type Identifier = String;
 
 #[derive(Debug, Clone)]
 pub struct File {
     pub items: Vec<Item>,
+    /// the whitespace and comments around the items
+    pub trivia: Trivia,
+    /// the names that are reserved words written without `r#`, see
+    /// [`crate::keywords`]
+    pub reserved_words: Vec<Loc<Identifier>>,
+    /// the location of the last name of every path of several names, like
+    /// `Ray` in `geometry::Ray`, by the location of the whole path
+    pub path_names: BTreeMap<FileLocation, FileLocation>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
+    pub attributes: Vec<Loc<Attribute>>,
+    pub visibility: Visibility,
     pub name: Loc<Identifier>,
-    pub args: Vec<(Loc<Identifier>, Loc<TypeReference>)>,
+    pub generics: Vec<Loc<Identifier>>,
+    pub args: Vec<(Loc<Identifier>, Loc<TypeReference>, ParamMode)>,
+    /// the attributes of each argument, in the order of `args`
+    pub arg_attributes: Vec<Vec<Loc<Attribute>>>,
     pub ret_type: Loc<TypeReference>,
 
     pub body: Block,
 }
 
+/// How an argument is passed to a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamMode {
+    /// the value is copied into the parameter
+    In,
+    /// the parameter is copied back into the argument when the function returns
+    Out,
+    /// the value is copied in and back out again
+    InOut,
+}
+
 #[derive(Debug, Clone)]
 pub struct Consts {
     pub vars: Vec<Loc<VariableDef>>,
@@ -32,6 +59,8 @@ pub struct Types {
 
 #[derive(Debug, Clone)]
 pub struct TypeDefinition {
+    pub attributes: Vec<Loc<Attribute>>,
+    pub visibility: Visibility,
     pub name: Loc<Identifier>,
     pub generics: Vec<Loc<Identifier>>,
     pub rhs: Loc<TypeDefinitionRhs>,
@@ -50,19 +79,80 @@ pub enum Item {
     Consts(Loc<Consts>),
     Types(Loc<Types>),
     Program(Loc<Program>),
+    Space(Loc<SpaceDefinition>),
+    Module(Loc<ModuleDefinition>),
+    Use(Loc<UseDeclaration>),
+    Prelude(Loc<Prelude>),
+    StaticAssert(Loc<StaticAssert>),
+}
+
+/// A namespace for the items in it, which are named `module::item` outside
+/// of it
+#[derive(Debug, Clone)]
+pub struct ModuleDefinition {
+    pub name: Loc<Identifier>,
+    pub items: Vec<Item>,
+}
+
+/// `use path;` names the item at `path` by its last name in the module,
+/// `pub use` also re-exports it from the module
+#[derive(Debug, Clone)]
+pub struct UseDeclaration {
+    pub visibility: Visibility,
+    pub path: Loc<Identifier>,
+}
+
+/// `prelude path;` makes the items of the module at `path` usable by their
+/// names in the module and the modules in it
+#[derive(Debug, Clone)]
+pub struct Prelude {
+    pub module: Loc<Identifier>,
+}
+
+/// `static_assert(condition, "message");` fails the compilation with the
+/// message if the condition, evaluated at compile time, is false
+#[derive(Debug, Clone)]
+pub struct StaticAssert {
+    pub condition: Loc<Expression>,
+    pub message: Loc<String>,
+}
+
+/// Whether an item declared in a module can be used outside of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    /// only in the module and the modules in it
+    #[default]
+    Private,
+    /// declared `pub`, everywhere
+    Public,
+}
+
+/// A coordinate space, which vectors and transforms refer to
+#[derive(Debug, Clone)]
+pub struct SpaceDefinition {
+    pub visibility: Visibility,
+    pub name: Loc<Identifier>,
+    /// the space this one is placed in, and the constant transforming from
+    /// the parent space to this one
+    pub parent: Option<(Loc<Identifier>, Loc<Identifier>)>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub attributes: Vec<Loc<Attribute>>,
     pub name: Loc<Identifier>,
     pub inputs: Vec<Loc<VariableDef>>,
     pub outputs: Vec<Loc<VariableDef>>,
+    /// variables shared by the invocations of a compute workgroup
+    pub workgroup: Vec<Loc<VariableDef>>,
     pub body: Block,
 }
 
 #[derive(Debug, Clone)]
 pub struct VariableDef {
     pub attributes: Vec<Loc<Attribute>>,
+    /// only constants declared in a `const` block can be `pub`
+    pub visibility: Visibility,
     pub name: Loc<Identifier>,
     pub type_: Loc<TypeReference>,
     pub rhs: Option<Loc<Expression>>,
@@ -82,11 +172,15 @@ pub enum Expression {
     Call {
         base: Box<Loc<Expression>>,
         args: Vec<(Option<Loc<Identifier>>, Loc<Expression>)>,
+        /// the arguments with their parentheses
+        args_loc: FileLocation,
     },
     DotCall {
         base: Box<Loc<Expression>>,
         name: Loc<Identifier>,
         args: Vec<(Option<Loc<Identifier>>, Loc<Expression>)>,
+        /// the arguments with their parentheses
+        args_loc: FileLocation,
     },
     InfixOp {
         op: InfixOp,
@@ -104,10 +198,46 @@ pub enum Expression {
         base: Box<Loc<Expression>>,
         index: Box<Loc<Expression>>,
     },
+    /// `base[lo..hi]`, a view of the elements from `lo` up to `hi`
+    Slice {
+        base: Box<Loc<Expression>>,
+        lo: Box<Loc<Expression>>,
+        hi: Box<Loc<Expression>>,
+    },
     As {
         base: Box<Loc<Expression>>,
         ty: Loc<TypeReference>,
     },
+    /// `sizeof(T)`, `alignof(T)` or `offsetof(T, field)`, optionally with
+    /// the layout rules as the last argument, like `sizeof(T, std140)`
+    LayoutQuery {
+        query: LayoutQuery,
+        ty: Loc<TypeReference>,
+        rules: Option<Loc<Identifier>>,
+    },
+    /// `[a, b, c]`, an array of the elements
+    Array(Vec<Loc<Expression>>),
+    /// `(e)`, which only changes the precedence
+    Parenthesized(Box<Loc<Expression>>),
+}
+
+impl Loc<Expression> {
+    /// The expression inside any parentheses around it.
+    pub fn unparenthesized(&self) -> &Loc<Expression> {
+        match &self.value {
+            Expression::Parenthesized(inner) => inner.unparenthesized(),
+            _ => self,
+        }
+    }
+}
+
+/// What a layout query asks about a type
+#[derive(Debug, Clone)]
+pub enum LayoutQuery {
+    Size,
+    Align,
+    /// the offset of a field of a record
+    Offset(Loc<Identifier>),
 }
 
 pub type Block = Vec<Loc<Statement>>;
@@ -120,9 +250,20 @@ pub enum Statement {
         lhs: Loc<Expression>,
         rhs: Loc<Expression>,
     },
+    /// `(lo, hi) := bounds(xs);`, assigns the fields of a record to the
+    /// targets in the order of the fields
+    Destructure {
+        targets: Vec<Loc<Expression>>,
+        rhs: Loc<Expression>,
+    },
     Return(Option<Loc<Expression>>),
+    /// An expression evaluated for its effects, like a call
+    Expr(Loc<Expression>),
     Break,
     Continue,
+    /// `discard;`, stops the invocation of a fragment without writing its
+    /// outputs
+    Discard,
     Branch {
         // guaranteed to have length of at least 1
         branches: Vec<(Loc<Expression>, Block)>,
@@ -136,6 +277,21 @@ pub enum Statement {
 
         body: Block,
     },
+    /// `match hit some(h) then ... none then ... end`, the arms for the
+    /// cases of an optional value
+    Match {
+        value: Loc<Expression>,
+        arms: Vec<(Loc<MatchPattern>, Block)>,
+    },
+}
+
+/// The case of an optional value an arm of a `match` handles
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    /// `some(h)`, with the name of the value
+    Some(Loc<Identifier>),
+    /// `none`
+    None,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -153,11 +309,108 @@ pub enum TypeReference {
     },
     Array {
         base: Box<Loc<TypeReference>>,
-        size: Loc<usize>,
+        size: Box<Loc<Expression>>,
     },
     OpenArray {
         base: Box<Loc<TypeReference>>,
     },
+    /// `normalized<float3>`, a vector known to have a length of one
+    Normalized {
+        base: Box<Loc<TypeReference>>,
+    },
+    /// `optional<Hit>`, a value that may be missing
+    Optional {
+        base: Box<Loc<TypeReference>>,
+    },
+    /// `record { x: float, y: float }`, a record without a name, which is
+    /// the same type wherever it has the same fields
+    Record {
+        fields: Vec<(Loc<Identifier>, Loc<TypeReference>)>,
+    },
+    /// `image2d<rgba8unorm, write>`, a storage image with the format and
+    /// access of its texels
+    Image {
+        dim: ImageDim,
+        format: Loc<Identifier>,
+        access: Loc<Identifier>,
+    },
+    /// `texture2d<float>`, a texture read through a sampler, or
+    /// `texture_depth2d`, a depth texture which has no sampled type
+    Texture {
+        dim: TextureDim,
+        sampled: Option<Box<Loc<TypeReference>>>,
+    },
+    /// `sampler`, or `sampler_comparison` for comparisons with a depth
+    Sampler {
+        comparison: bool,
+    },
+    /// `acceleration_structure`, the geometry rays are traced against
+    AccelerationStructure,
+}
+
+/// The dimensions of a texture, given by the name of its type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureDim {
+    /// `texture1d`
+    D1,
+    /// `texture2d`, `texture_depth2d`
+    D2,
+    /// `texture3d`
+    D3,
+    /// `texture_cube`, `texture_depth_cube`
+    Cube,
+    /// `texture2d_array`, `texture_depth2d_array`
+    D2Array,
+}
+
+impl TextureDim {
+    /// The dimensions of a texture type with a sampled type and this name.
+    pub fn from_type_name(name: &str) -> Option<Self> {
+        match name {
+            "texture1d" => Some(TextureDim::D1),
+            "texture2d" => Some(TextureDim::D2),
+            "texture3d" => Some(TextureDim::D3),
+            "texture_cube" => Some(TextureDim::Cube),
+            "texture2d_array" => Some(TextureDim::D2Array),
+            _ => None,
+        }
+    }
+
+    /// The dimensions of a depth texture type with this name.
+    pub fn from_depth_type_name(name: &str) -> Option<Self> {
+        match name {
+            "texture_depth2d" => Some(TextureDim::D2),
+            "texture_depth_cube" => Some(TextureDim::Cube),
+            "texture_depth2d_array" => Some(TextureDim::D2Array),
+            _ => None,
+        }
+    }
+}
+
+/// The dimensions of an image, given by the name of its type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageDim {
+    /// `image1d`
+    D1,
+    /// `image2d`
+    D2,
+    /// `image3d`
+    D3,
+    /// `image2d_array`
+    D2Array,
+}
+
+impl ImageDim {
+    /// The dimensions of an image type with this name.
+    pub fn from_type_name(name: &str) -> Option<Self> {
+        match name {
+            "image1d" => Some(ImageDim::D1),
+            "image2d" => Some(ImageDim::D2),
+            "image3d" => Some(ImageDim::D3),
+            "image2d_array" => Some(ImageDim::D2Array),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -165,8 +418,19 @@ pub enum PrimitiveType {
     Bool,
     Int,
     UInt,
+    /// a 64 bit `int`
+    Long,
+    /// a 64 bit `uint`
+    ULong,
     Float,
     Double,
+    Half,
+    /// a `float` angle in radians
+    Radians,
+    /// a `float` angle in degrees
+    Degrees,
+    AtomicInt,
+    AtomicUInt,
 
     BoolVec {
         components: VecSize,
@@ -181,6 +445,16 @@ pub enum PrimitiveType {
         vtype: Option<Loc<VecType>>,
         space: Option<Loc<Identifier>>,
     },
+    LongVec {
+        components: VecSize,
+        vtype: Option<Loc<VecType>>,
+        space: Option<Loc<Identifier>>,
+    },
+    ULongVec {
+        components: VecSize,
+        vtype: Option<Loc<VecType>>,
+        space: Option<Loc<Identifier>>,
+    },
 
     FloatVec {
         components: VecSize,
@@ -192,6 +466,11 @@ pub enum PrimitiveType {
         vtype: Option<Loc<VecType>>,
         space: Option<Loc<Identifier>>,
     },
+    HalfVec {
+        components: VecSize,
+        vtype: Option<Loc<VecType>>,
+        space: Option<Loc<Identifier>>,
+    },
 
     FloatMat {
         cols: VecSize,
@@ -203,6 +482,10 @@ pub enum PrimitiveType {
         rows: VecSize,
         transform: Option<(Loc<Identifier>, Loc<Identifier>)>,
     },
+
+    Packed {
+        format: PackedFormat,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -212,17 +495,59 @@ pub enum VecSize {
     VS4,
 }
 
+/// Formats that store a vector in fewer bits than its full precision type,
+/// as used for vertex attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackedFormat {
+    Unorm8x4,
+    Snorm8x4,
+    Uint8x4,
+    Sint8x4,
+    Unorm16x2,
+    Snorm16x2,
+    Float16x2,
+    Float16x4,
+    Rgb10a2,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VecType {
     Point,
     Vector,
+    /// a linear colour
     Colour,
+    /// an sRGB encoded colour
+    SrgbColour,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum Literal {
-    Integer(i128),
-    Float(f64),
+    /// `None` if the literal doesn't fit into an `i128`
+    Integer(Option<i128>, Option<LiteralSuffix>),
+    Float(f64, Option<LiteralSuffix>),
+    Bool(bool),
+    String(String),
+}
+
+/// The type a literal is written with, like the `u` in `1u`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteralSuffix {
+    /// `i`
+    Int,
+    /// `u`
+    UInt,
+    /// `l`
+    Long,
+    /// `ul`
+    ULong,
+    /// `f`
+    Float,
+    /// `lf`
+    Double,
+    /// `rad`
+    Radians,
+    /// `deg`
+    Degrees,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -0,0 +1,629 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A lossless syntax tree for tools that edit source code.
+//!
+//! The [`ast`](crate::ast) drops whitespace and comments and doesn't exist
+//! for a file with a syntax error anywhere. The concrete syntax tree keeps
+//! every byte of the file: the root holds one node per item, and the tokens
+//! and trivia of the item inside it. Items that don't parse become `ERROR`
+//! nodes, so the rest of the file stays usable while the user types.
+//!
+//! Items are found from the tokens alone: an item starts at `const`, `type`
+//! or `space`, or at the attributes in front of `function` or `program`. A
+//! `module` is one item with the items in it, if it parses. Green
+//! nodes don't know their position, so [`SourceFile::reparse`] keeps the
+//! nodes of the items an edit doesn't touch and only lexes and parses the
+//! text between them again.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use rowan::{GreenNode, GreenNodeData, GreenToken, GreenTokenData, Language as _, NodeOrToken};
+
+use crate::{
+    ast, keywords,
+    lexer::{self, Token, TokenKind as TK},
+    parser::{self, ParseError},
+    trivia::{Trivia, TriviaKind},
+    FileId, FileLocation, Loc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+#[allow(non_camel_case_types)]
+pub enum SyntaxKind {
+    // tokens
+    WHITESPACE,
+    COMMENT,
+    IDENT,
+    KEYWORD,
+    LITERAL,
+    PUNCT,
+    /// text the lexer doesn't know
+    ERROR_TOKEN,
+
+    // nodes
+    ROOT,
+    FUNCTION,
+    CONSTS,
+    TYPES,
+    PROGRAM,
+    SPACE,
+    MODULE,
+    USE,
+    PRELUDE,
+    STATIC_ASSERT,
+    /// an item that doesn't parse
+    ERROR,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::WHITESPACE | SyntaxKind::COMMENT)
+    }
+
+    fn of_token(token: &TK, text: &str) -> Self {
+        match token {
+            TK::Identifier(_) | TK::RawIdentifier(_) => SyntaxKind::IDENT,
+            TK::Integer(_) | TK::Float(_) | TK::String(_) | TK::True | TK::False => {
+                SyntaxKind::LITERAL
+            }
+            TK::Error => SyntaxKind::ERROR_TOKEN,
+            _ if text.starts_with(char::is_alphabetic) => SyntaxKind::KEYWORD,
+            _ => SyntaxKind::PUNCT,
+        }
+    }
+
+    fn of_item(item: &ast::Item) -> Self {
+        match item {
+            ast::Item::Function(_) => SyntaxKind::FUNCTION,
+            ast::Item::Consts(_) => SyntaxKind::CONSTS,
+            ast::Item::Types(_) => SyntaxKind::TYPES,
+            ast::Item::Program(_) => SyntaxKind::PROGRAM,
+            ast::Item::Space(_) => SyntaxKind::SPACE,
+            ast::Item::Module(_) => SyntaxKind::MODULE,
+            ast::Item::Use(_) => SyntaxKind::USE,
+            ast::Item::Prelude(_) => SyntaxKind::PRELUDE,
+            ast::Item::StaticAssert(_) => SyntaxKind::STATIC_ASSERT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Language {}
+
+impl rowan::Language for Language {
+    type Kind = SyntaxKind;
+
+    fn kind_from_raw(raw: rowan::SyntaxKind) -> SyntaxKind {
+        use SyntaxKind::*;
+        const KINDS: [SyntaxKind; 18] = [
+            WHITESPACE,
+            COMMENT,
+            IDENT,
+            KEYWORD,
+            LITERAL,
+            PUNCT,
+            ERROR_TOKEN,
+            ROOT,
+            FUNCTION,
+            CONSTS,
+            TYPES,
+            PROGRAM,
+            SPACE,
+            MODULE,
+            USE,
+            PRELUDE,
+            STATIC_ASSERT,
+            ERROR,
+        ];
+        KINDS[raw.0 as usize]
+    }
+
+    fn kind_to_raw(kind: SyntaxKind) -> rowan::SyntaxKind {
+        rowan::SyntaxKind(kind as u16)
+    }
+}
+
+pub type SyntaxNode = rowan::SyntaxNode<Language>;
+pub type SyntaxToken = rowan::SyntaxToken<Language>;
+
+/// Replace the text in `range` with `insert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub insert: String,
+}
+
+/// The concrete syntax tree of a file
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    file: FileId,
+    green: GreenNode,
+    /// the item of every item node, for the nodes that didn't move since
+    /// they were parsed
+    parsed: Vec<Option<Result<ast::Item, ParseError>>>,
+}
+
+type Child = NodeOrToken<GreenNode, GreenToken>;
+
+impl SourceFile {
+    pub fn parse(file: FileId, input: &str) -> Self {
+        let toks = lexer::tokenise(file, input).collect::<Vec<_>>();
+        let (children, parsed) = children(
+            file,
+            input,
+            &toks,
+            &segments(&toks),
+            0..input.len(),
+            &HashMap::new(),
+        );
+        SourceFile {
+            file,
+            green: GreenNode::new(SyntaxKind::ROOT.into(), children),
+            parsed,
+        }
+    }
+
+    /// Apply an edit. Only the text from the item before the edit to the
+    /// item after it is lexed again, and only the items in between are
+    /// parsed again. The other item nodes are shared with `self`, and so are
+    /// their parsed items unless the edit moved them.
+    pub fn reparse(&self, edit: &Edit) -> Self {
+        let mut text = self.text();
+        text.replace_range(edit.range.clone(), &edit.insert);
+        let moved = |pos: usize| pos - edit.range.len() + edit.insert.len();
+
+        let old = self.green.children().collect::<Vec<_>>();
+        let mut ranges = vec![];
+        let mut pos = 0;
+        for child in &old {
+            let len = usize::from(child.text_len());
+            ranges.push(pos..pos + len);
+            pos += len;
+        }
+        let items = (0..old.len())
+            .filter(|i| old[*i].as_node().is_some())
+            .collect::<Vec<_>>();
+
+        // the items the edit touches or is next to, so that the lookahead of
+        // the lexer at their ends can't see it
+        let mut lo = items
+            .iter()
+            .position(|i| ranges[*i].end + 1 >= edit.range.start)
+            .unwrap_or(items.len());
+        let mut hi = items
+            .iter()
+            .rposition(|i| ranges[*i].start <= edit.range.end + 1)
+            .map_or(lo, |k| (k + 1).max(lo));
+        // a `module` that didn't parse may parse now and take the items up
+        // to the edit with it
+        let unterminated = items[..lo].iter().position(|i| {
+            let node = old[*i].into_node().unwrap();
+            node.kind() == SyntaxKind::ERROR.into()
+                && node
+                    .children()
+                    .next()
+                    .and_then(NodeOrToken::into_token)
+                    .map(|tok| tok.text())
+                    == Some("module")
+        });
+        lo = unterminated.unwrap_or(lo);
+
+        // lex from the start of the item before the window to the end of the
+        // item after it, until both of them are lexed and split off as
+        // before, which makes the window bigger at most up to the whole file
+        let (before, after, toks, segs) = loop {
+            let before = lo.checked_sub(1).map(|k| items[k]);
+            let after = items.get(hi).copied();
+            let start = before.map_or(0, |i| ranges[i].start);
+            let end = after.map_or(text.len(), |i| moved(ranges[i].end));
+            let toks = lexer::tokenise(self.file, &text[start..end])
+                .map(|tok| Token {
+                    loc: FileLocation {
+                        file: self.file,
+                        start: tok.loc.start + start,
+                        end: tok.loc.end + start,
+                    },
+                    ..tok
+                })
+                .collect::<Vec<_>>();
+            let segs = segments(&toks);
+
+            let kept_before = before.is_none_or(|i| {
+                let n = token_count(old[i].into_node().unwrap());
+                n <= toks.len()
+                    && same_tokens(&text, &toks[..n], old[i].into_node().unwrap(), start)
+                    && segs.contains(&(0..n))
+            });
+            let kept_after = after.is_none_or(|i| {
+                let node = old[i].into_node().unwrap();
+                let first = toks.len().saturating_sub(token_count(node));
+                // a `module` that doesn't parse up to here may parse with the
+                // items after it
+                let unterminated = segs.iter().any(|seg| {
+                    toks[seg.start].value == TK::Module
+                        && parser::item_len(&toks[seg.start..]).is_none()
+                });
+                same_tokens(&text, &toks[first..], node, moved(ranges[i].start))
+                    && segs.contains(&(first..toks.len()))
+                    && !unterminated
+            });
+            if kept_before && kept_after {
+                break (before, after, toks, segs);
+            }
+            if !kept_before {
+                lo -= 1;
+            }
+            if !kept_after {
+                hi += 1;
+            }
+        };
+
+        // items of the window whose text didn't change keep their nodes
+        let old_items = items[lo..hi]
+            .iter()
+            .filter_map(|i| old[*i].into_node())
+            .map(|node| (node.to_string(), node.to_owned()))
+            .collect();
+        let window = segs
+            .iter()
+            .filter(|seg| {
+                (before.is_none() || seg.start > 0) && (after.is_none() || seg.end < toks.len())
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let start = before.map_or(0, |i| ranges[i].end);
+        let end = after.map_or(text.len(), |i| moved(ranges[i].start));
+        let (new, new_parsed) = children(self.file, &text, &toks, &window, start..end, &old_items);
+
+        let own = |child: &NodeOrToken<&GreenNodeData, &GreenTokenData>| match child {
+            NodeOrToken::Node(node) => NodeOrToken::Node((*node).to_owned()),
+            NodeOrToken::Token(token) => NodeOrToken::Token((*token).to_owned()),
+        };
+        let (head, tail) = (before.map_or(0, |i| i + 1), after.unwrap_or(old.len()));
+        let children = old[..head]
+            .iter()
+            .map(own)
+            .chain(new)
+            .chain(old[tail..].iter().map(own))
+            .collect::<Vec<_>>();
+        let unmoved = edit.range.len() == edit.insert.len();
+        let parsed = self.parsed[..lo]
+            .iter()
+            .cloned()
+            .chain(new_parsed)
+            .chain(
+                self.parsed[hi..]
+                    .iter()
+                    .map(|item| item.clone().filter(|_| unmoved)),
+            )
+            .collect();
+
+        SourceFile {
+            file: self.file,
+            green: GreenNode::new(SyntaxKind::ROOT.into(), children),
+            parsed,
+        }
+    }
+
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.green
+    }
+
+    /// The text of the file, exactly as it was parsed.
+    pub fn text(&self) -> String {
+        self.green.to_string()
+    }
+
+    /// The item and `ERROR` nodes, in source order.
+    pub fn items(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.syntax().children()
+    }
+
+    /// The syntax errors of all items that don't parse.
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.items()
+            .zip(&self.parsed)
+            .filter_map(|(node, parsed)| match parsed {
+                Some(parsed) => parsed.clone().err(),
+                None if node.kind() == SyntaxKind::ERROR => self.parse_item(&node).err(),
+                None => None,
+            })
+            .collect()
+    }
+
+    /// The typed AST of the items that parse. Trivia and reserved words are
+    /// collected from the whole text.
+    pub fn ast(&self) -> ast::File {
+        let items = self
+            .items()
+            .zip(&self.parsed)
+            .filter_map(|(node, parsed)| match parsed {
+                Some(parsed) => parsed.clone().ok(),
+                None if node.kind() != SyntaxKind::ERROR => self.parse_item(&node).ok(),
+                None => None,
+            })
+            .collect();
+        let text = self.text();
+        ast::File {
+            items,
+            trivia: Trivia::new(self.file, &text),
+            reserved_words: keywords::reserved_words(self.file, &text),
+            path_names: parser::path_names(&lexer::tokenise(self.file, &text).collect::<Vec<_>>()),
+        }
+    }
+
+    fn parse_item(&self, node: &SyntaxNode) -> Result<ast::Item, ParseError> {
+        let start = usize::from(node.text_range().start());
+        let text = node.to_string();
+        let toks = lexer::tokenise(self.file, &text)
+            .map(|tok| {
+                let loc = FileLocation {
+                    file: self.file,
+                    start: tok.loc.start + start,
+                    end: tok.loc.end + start,
+                };
+                Loc::new(loc, tok.value)
+            })
+            .collect::<Vec<_>>();
+        let eof = FileLocation {
+            file: self.file,
+            start: start + text.len(),
+            end: start + text.len(),
+        };
+        parser::parse_item(&toks, eof)
+    }
+}
+
+/// The children of the root for the text in `range`, with a node for each of
+/// the `segments` of the tokens, and the items of the nodes that were
+/// parsed. Nodes in `old_items` with the same text are used again.
+fn children(
+    file: FileId,
+    input: &str,
+    toks: &[Token],
+    segments: &[Range<usize>],
+    range: Range<usize>,
+    old_items: &HashMap<String, GreenNode>,
+) -> (Vec<Child>, Vec<Option<Result<ast::Item, ParseError>>>) {
+    let mut children = vec![];
+    let mut parsed = vec![];
+    let mut pos = range.start;
+    for range in segments {
+        let (first, last) = (toks[range.start].loc, toks[range.end - 1].loc);
+        children.extend(trivia(file, input, pos, first.start));
+
+        let text = &input[first.start..last.end];
+        let node = match old_items.get(text) {
+            Some(node) => {
+                parsed.push(None);
+                node.clone()
+            }
+            None => {
+                let eof = FileLocation {
+                    file,
+                    start: last.end,
+                    end: last.end,
+                };
+                let item = parser::parse_item(&toks[range.clone()], eof);
+                let kind = match &item {
+                    Ok(item) => SyntaxKind::of_item(item),
+                    Err(_) => SyntaxKind::ERROR,
+                };
+                parsed.push(Some(item));
+                let mut tokens = vec![];
+                let mut pos = first.start;
+                for tok in &toks[range.clone()] {
+                    tokens.extend(trivia(file, input, pos, tok.loc.start));
+                    let text = &input[tok.loc.range()];
+                    let kind = SyntaxKind::of_token(&tok.value, text);
+                    tokens.push(NodeOrToken::Token(GreenToken::new(kind.into(), text)));
+                    pos = tok.loc.end;
+                }
+                GreenNode::new(kind.into(), tokens)
+            }
+        };
+        children.push(NodeOrToken::Node(node));
+        pos = last.end;
+    }
+    children.extend(trivia(file, input, pos, range.end));
+    (children, parsed)
+}
+
+/// The number of tokens of an item node that aren't trivia.
+fn token_count(node: &GreenNodeData) -> usize {
+    node.children()
+        .filter(|child| !Language::kind_from_raw(child.kind()).is_trivia())
+        .count()
+}
+
+/// Whether the tokens are the tokens of the item node at `start`.
+fn same_tokens(input: &str, toks: &[Token], node: &GreenNodeData, start: usize) -> bool {
+    let mut toks = toks.iter();
+    let mut pos = start;
+    for child in node.children() {
+        if let NodeOrToken::Token(token) = child {
+            if !Language::kind_from_raw(token.kind()).is_trivia() {
+                match toks.next() {
+                    Some(tok)
+                        if tok.loc.start == pos && input[tok.loc.range()] == *token.text() => {}
+                    _ => return false,
+                }
+            }
+        }
+        pos += usize::from(child.text_len());
+    }
+    toks.next().is_none()
+}
+
+impl From<SyntaxKind> for rowan::SyntaxKind {
+    fn from(kind: SyntaxKind) -> Self {
+        <Language as rowan::Language>::kind_to_raw(kind)
+    }
+}
+
+/// Split the tokens of a file into items. Tokens in front of the first item
+/// are an item of their own.
+fn segments(toks: &[Token]) -> Vec<Range<usize>> {
+    let mut starts = vec![];
+    // the end of the module the tokens are in
+    let mut module_end = 0;
+    for (i, tok) in toks.iter().enumerate() {
+        if i < module_end {
+            continue;
+        }
+        let start = match tok.value {
+            TK::Module => {
+                if let Some(len) = parser::item_len(&toks[i..]) {
+                    module_end = i + len;
+                }
+                i
+            }
+            TK::Const | TK::Type | TK::Space => i,
+            TK::Function | TK::Program => {
+                // include the attributes, which follow the end of the
+                // previous item
+                let previous = starts.last().copied().unwrap_or(0);
+                let mut start = i;
+                while start > previous && !matches!(toks[start - 1].value, TK::SemiColon | TK::End)
+                {
+                    start -= 1;
+                }
+                start
+            }
+            _ => continue,
+        };
+        if starts.last() != Some(&start) {
+            starts.push(start);
+        }
+    }
+    if !toks.is_empty() && starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+
+    let ends = starts.iter().skip(1).copied().chain(Some(toks.len()));
+    starts
+        .iter()
+        .copied()
+        .zip(ends)
+        .map(|(s, e)| s..e)
+        .collect()
+}
+
+fn trivia(
+    file: FileId,
+    input: &str,
+    start: usize,
+    end: usize,
+) -> impl Iterator<Item = NodeOrToken<GreenNode, GreenToken>> + '_ {
+    crate::trivia::pieces(file, input, start, end)
+        .into_iter()
+        .map(move |piece| {
+            let kind = match piece.value {
+                TriviaKind::Whitespace => SyntaxKind::WHITESPACE,
+                TriviaKind::Comment => SyntaxKind::COMMENT,
+            };
+            NodeOrToken::Token(GreenToken::new(kind.into(), &input[piece.loc.range()]))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "// constants\nconst A: int := 1;\n\n\
+        function f(x: float) returns float begin return x * ; end\n\
+        @stage(compute) program p begin end // the end\n";
+
+    #[test]
+    fn lossless_with_errors() {
+        let file = SourceFile::parse(0, INPUT);
+        assert_eq!(file.text(), INPUT);
+
+        let kinds = file.items().map(|node| node.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [SyntaxKind::CONSTS, SyntaxKind::ERROR, SyntaxKind::PROGRAM]
+        );
+        assert_eq!(file.ast().items.len(), 2);
+
+        let errors = file.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location().start, INPUT.find("; end").unwrap());
+    }
+
+    #[test]
+    fn modules_are_one_item() {
+        let input =
+            "module m\n    const A: int := 1;\n    type T = int;\nend\nconst B: int := 2;\n";
+        let file = SourceFile::parse(0, input);
+        let kinds = file.items().map(|node| node.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, [SyntaxKind::MODULE, SyntaxKind::CONSTS]);
+    }
+
+    #[test]
+    fn reparse_keeps_untouched_items() {
+        let file = SourceFile::parse(0, INPUT);
+        let start = INPUT.find("x * ;").unwrap();
+        let edit = Edit {
+            range: start + 4..start + 4,
+            insert: "2.0".to_string(),
+        };
+        let new = file.reparse(&edit);
+
+        let mut expected = INPUT.to_string();
+        expected.insert_str(start + 4, "2.0");
+        assert_eq!(new.text(), expected);
+        assert!(new.errors().is_empty());
+        assert_eq!(new.ast().items.len(), 3);
+
+        // the item before the edit didn't move, the one after it did
+        assert!(new.parsed[0].is_some());
+        assert!(new.parsed[1].as_ref().is_some_and(Result::is_ok));
+        assert!(new.parsed[2].is_none());
+
+        let items = |file: &SourceFile| {
+            file.green()
+                .children()
+                .filter_map(NodeOrToken::into_node)
+                .map(|node| node as *const _)
+                .collect::<Vec<_>>()
+        };
+        let (old, new) = (items(&file), items(&new));
+        assert_eq!(old[0], new[0]);
+        assert_ne!(old[1], new[1]);
+        assert_eq!(old[2], new[2]);
+    }
+
+    #[test]
+    fn reparse_is_parse() {
+        let input = "const A: int := 1;\nmodule m\n    type T = int;\nend\n\
+            @stage(compute) program p begin end\nfunction f() returns int begin return 1; end\n";
+        let file = SourceFile::parse(0, input);
+        for start in 0..=input.len() {
+            for end in [start, (start + 3).min(input.len())] {
+                for insert in ["", "x", " ", ";", "end ", "module n ", "@stage(vertex) "] {
+                    let edit = Edit {
+                        range: start..end,
+                        insert: insert.to_string(),
+                    };
+                    let mut text = input.to_string();
+                    text.replace_range(start..end, insert);
+
+                    let (new, parsed) = (file.reparse(&edit), SourceFile::parse(0, &text));
+                    assert_eq!(new.green(), parsed.green(), "{:?}", edit);
+                    assert_eq!(new.errors(), parsed.errors(), "{:?}", edit);
+                    let items = |file: &SourceFile| format!("{:?}", file.ast().items);
+                    assert_eq!(items(&new), items(&parsed), "{:?}", edit);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,858 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A description of the grammar the parser accepts.
+//!
+//! [`grammar`] describes the rules of [`crate::parser`] as data, for
+//! documentation and editor plugins that need the grammar without running
+//! the parser. [`Grammar::to_ebnf`] writes it as ISO 14977 EBNF, and
+//! [`Grammar::to_railroad`] in the W3C notation that railroad diagram
+//! generators read.
+//!
+//! The description follows the parser rule by rule, except for the
+//! precedence levels of expressions, which get a rule each, and the helper
+//! rules of the parser for lists and contextual keywords, which are written
+//! out where they are used. The tests compare the rule names with the
+//! parser and the tokens with [`KEYWORDS`](crate::keywords::KEYWORDS), so
+//! that the description can't fall behind the parser unnoticed.
+
+use std::fmt::Write;
+
+/// A part of the right hand side of a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// a token with fixed text, like a keyword or `:=`
+    Token(String),
+    /// a class of tokens: `IDENTIFIER`, `RAW_IDENTIFIER`, `INTEGER`, `FLOAT`
+    /// or `STRING`
+    Terminal(&'static str),
+    /// an identifier that is a keyword in one place only, like `via`
+    Contextual(&'static str),
+    Rule(&'static str),
+    Seq(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Optional(Box<Expr>),
+    /// zero or more repetitions
+    Many(Box<Expr>),
+    /// one or more repetitions
+    Many1(Box<Expr>),
+}
+
+/// A rule of the grammar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub name: &'static str,
+    pub body: Expr,
+}
+
+/// The rules of the grammar, starting with the rule of a whole file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grammar {
+    pub rules: Vec<Rule>,
+}
+
+fn tok(text: &str) -> Expr {
+    Expr::Token(text.to_string())
+}
+
+fn rule(name: &'static str) -> Expr {
+    Expr::Rule(name)
+}
+
+fn word(word: &'static str) -> Expr {
+    Expr::Contextual(word)
+}
+
+fn seq(exprs: Vec<Expr>) -> Expr {
+    Expr::Seq(exprs)
+}
+
+fn choice(exprs: Vec<Expr>) -> Expr {
+    Expr::Choice(exprs)
+}
+
+fn opt(expr: Expr) -> Expr {
+    Expr::Optional(Box::new(expr))
+}
+
+fn many(expr: Expr) -> Expr {
+    Expr::Many(Box::new(expr))
+}
+
+fn many1(expr: Expr) -> Expr {
+    Expr::Many1(Box::new(expr))
+}
+
+/// Zero or more `expr` separated by commas, with an optional comma at the
+/// end.
+fn comma_list(expr: Expr) -> Expr {
+    opt(seq(vec![
+        expr.clone(),
+        many(seq(vec![tok(","), expr])),
+        opt(tok(",")),
+    ]))
+}
+
+/// The rules of the parser.
+pub fn grammar() -> Grammar {
+    let attributes = || many(rule("attribute"));
+    let body = || seq(vec![tok("begin"), rule("block"), tok("end")]);
+
+    let vectors =
+        |scalar: &str| choice((2..=4).map(|n| tok(&format!("{}{}", scalar, n))).collect());
+    let matrices = |scalar: &str| {
+        let sizes = (2..=4).flat_map(|cols| (2..=4).map(move |rows| (cols, rows)));
+        choice(
+            sizes
+                .map(|(cols, rows)| tok(&format!("{}{}x{}", scalar, cols, rows)))
+                .collect(),
+        )
+    };
+
+    let mut primitives = [
+        "bool", "int", "uint", "long", "ulong", "float", "double", "half", "radians", "degrees",
+    ]
+    .iter()
+    .map(|name| tok(name))
+    .collect::<Vec<_>>();
+    primitives.push(seq(vec![
+        tok("atomic"),
+        tok("<"),
+        choice(vec![tok("int"), tok("uint")]),
+        tok(">"),
+    ]));
+    primitives.push(vectors("bool"));
+    for scalar in ["int", "uint", "long", "ulong", "float", "double", "half"] {
+        primitives.push(seq(vec![vectors(scalar), opt(rule("type_prim_vec_annot"))]));
+    }
+    for scalar in ["float", "double"] {
+        primitives.push(seq(vec![
+            matrices(scalar),
+            opt(rule("type_prim_mat_annot")),
+        ]));
+    }
+    primitives.push(choice(
+        [
+            "unorm8x4",
+            "snorm8x4",
+            "uint8x4",
+            "sint8x4",
+            "unorm16x2",
+            "snorm16x2",
+            "float16x2",
+            "float16x4",
+            "rgb10a2",
+        ]
+        .iter()
+        .map(|format| tok(format))
+        .collect(),
+    ));
+
+    let rules = vec![
+        // file and items
+        ("file", many(rule("item"))),
+        (
+            "item",
+            choice(vec![
+                rule("function"),
+                rule("consts"),
+                rule("types"),
+                rule("program"),
+                rule("space"),
+                rule("module"),
+                rule("use_declaration"),
+                rule("prelude"),
+                rule("static_assert"),
+            ]),
+        ),
+        (
+            "module",
+            seq(vec![
+                tok("module"),
+                rule("identifier"),
+                many(rule("item")),
+                tok("end"),
+            ]),
+        ),
+        (
+            "use_declaration",
+            seq(vec![
+                opt(rule("visibility")),
+                tok("use"),
+                rule("path"),
+                tok(";"),
+            ]),
+        ),
+        (
+            "prelude",
+            seq(vec![word("prelude"), rule("path"), tok(";")]),
+        ),
+        (
+            "static_assert",
+            seq(vec![
+                word("static_assert"),
+                tok("("),
+                rule("expression"),
+                tok(","),
+                Expr::Terminal("STRING"),
+                tok(")"),
+                tok(";"),
+            ]),
+        ),
+        // programs
+        (
+            "program",
+            seq(vec![
+                attributes(),
+                tok("program"),
+                rule("identifier"),
+                opt(rule("program_inputs")),
+                opt(rule("program_outputs")),
+                opt(rule("program_workgroup")),
+                body(),
+            ]),
+        ),
+        (
+            "program_inputs",
+            seq(vec![tok("input"), many(rule("variable_def"))]),
+        ),
+        (
+            "program_outputs",
+            seq(vec![tok("output"), many(rule("variable_def"))]),
+        ),
+        (
+            "program_workgroup",
+            seq(vec![tok("workgroup"), many(rule("variable_def"))]),
+        ),
+        // spaces
+        (
+            "space",
+            seq(vec![
+                opt(rule("visibility")),
+                tok("space"),
+                rule("identifier"),
+                opt(seq(vec![
+                    tok(":"),
+                    word("parent"),
+                    rule("path"),
+                    word("via"),
+                    rule("path"),
+                ])),
+                tok(";"),
+            ]),
+        ),
+        // functions
+        (
+            "function",
+            seq(vec![
+                attributes(),
+                opt(rule("visibility")),
+                tok("function"),
+                rule("identifier"),
+                opt(rule("function_generics")),
+                tok("("),
+                comma_list(rule("function_arg")),
+                tok(")"),
+                tok("returns"),
+                rule("type_reference"),
+                body(),
+            ]),
+        ),
+        (
+            "function_generics",
+            seq(vec![tok("<"), comma_list(rule("identifier")), tok(">")]),
+        ),
+        (
+            "function_arg",
+            seq(vec![
+                attributes(),
+                rule("identifier"),
+                tok(":"),
+                opt(rule("param_mode")),
+                rule("type_reference"),
+            ]),
+        ),
+        (
+            "param_mode",
+            choice(vec![
+                seq(vec![tok("in"), tok("out")]),
+                tok("in"),
+                tok("out"),
+            ]),
+        ),
+        // constants and types
+        ("consts", seq(vec![tok("const"), many1(rule("const_def"))])),
+        (
+            "const_def",
+            seq(vec![
+                attributes(),
+                opt(rule("visibility")),
+                rule("identifier"),
+                tok(":"),
+                rule("type_reference"),
+                opt(seq(vec![tok(":="), rule("expression")])),
+                tok(";"),
+            ]),
+        ),
+        (
+            "types",
+            seq(vec![tok("type"), many1(rule("type_definition"))]),
+        ),
+        (
+            "type_definition",
+            seq(vec![
+                attributes(),
+                opt(rule("visibility")),
+                rule("identifier"),
+                opt(seq(vec![
+                    tok("<"),
+                    comma_list(rule("identifier")),
+                    tok(">"),
+                ])),
+                tok("="),
+                rule("type_def_rhs"),
+            ]),
+        ),
+        (
+            "type_def_rhs",
+            choice(vec![
+                seq(vec![tok("record"), many(rule("variable_def")), tok("end")]),
+                seq(vec![tok("distinct"), rule("type_reference"), tok(";")]),
+                seq(vec![rule("type_reference"), tok(";")]),
+            ]),
+        ),
+        (
+            "variable_def",
+            seq(vec![
+                attributes(),
+                rule("identifier"),
+                tok(":"),
+                rule("type_reference"),
+                opt(seq(vec![tok(":="), rule("expression")])),
+                tok(";"),
+            ]),
+        ),
+        ("visibility", tok("pub")),
+        (
+            "attribute",
+            choice(vec![
+                seq(vec![
+                    tok("["),
+                    rule("identifier"),
+                    opt(seq(vec![tok("("), rule("arglist"), tok(")")])),
+                    tok("]"),
+                ]),
+                seq(vec![
+                    tok("@"),
+                    rule("identifier"),
+                    opt(seq(vec![tok("("), rule("arglist"), tok(")")])),
+                ]),
+            ]),
+        ),
+        // statements
+        (
+            "statement",
+            choice(vec![
+                seq(vec![tok("var"), rule("variable_def")]),
+                seq(vec![tok("var"), rule("identifier"), rule("variable_def")]),
+                seq(vec![
+                    rule("expression_atom"),
+                    tok(":="),
+                    rule("expression"),
+                    tok(";"),
+                ]),
+                seq(vec![
+                    tok("("),
+                    rule("expression_atom"),
+                    many1(seq(vec![tok(","), rule("expression_atom")])),
+                    opt(tok(",")),
+                    tok(")"),
+                    tok(":="),
+                    rule("expression"),
+                    tok(";"),
+                ]),
+                seq(vec![tok("return"), opt(rule("expression")), tok(";")]),
+                seq(vec![rule("expression"), tok(";")]),
+                seq(vec![tok("break"), tok(";")]),
+                seq(vec![tok("continue"), tok(";")]),
+                seq(vec![tok("discard"), tok(";")]),
+                seq(vec![
+                    tok("if"),
+                    rule("expression"),
+                    tok("then"),
+                    rule("block"),
+                    many(rule("elseif_branch")),
+                    opt(seq(vec![tok("else"), rule("block")])),
+                    tok("end"),
+                ]),
+                seq(vec![
+                    tok("for"),
+                    rule("identifier"),
+                    tok("in"),
+                    rule("expression_atom"),
+                    choice(vec![tok("to"), tok("downto")]),
+                    rule("expression_atom"),
+                    tok("do"),
+                    rule("block"),
+                    tok("end"),
+                ]),
+                seq(vec![
+                    tok("match"),
+                    rule("expression"),
+                    many(rule("match_arm")),
+                    tok("end"),
+                ]),
+            ]),
+        ),
+        (
+            "elseif_branch",
+            seq(vec![
+                tok("elseif"),
+                rule("expression"),
+                tok("then"),
+                rule("block"),
+            ]),
+        ),
+        (
+            "match_arm",
+            seq(vec![rule("match_pattern"), tok("then"), rule("block")]),
+        ),
+        (
+            "match_pattern",
+            choice(vec![
+                seq(vec![word("some"), tok("("), rule("identifier"), tok(")")]),
+                word("none"),
+            ]),
+        ),
+        ("block", many(rule("statement"))),
+        // expressions, from the loosest to the tightest binding operators
+        ("expression", rule("comparison")),
+        (
+            "comparison",
+            seq(vec![
+                rule("sum"),
+                many(seq(vec![
+                    choice(vec![
+                        tok("="),
+                        tok("<>"),
+                        tok("<"),
+                        tok("<="),
+                        tok(">"),
+                        tok(">="),
+                    ]),
+                    rule("sum"),
+                ])),
+            ]),
+        ),
+        (
+            "sum",
+            seq(vec![
+                rule("product"),
+                many(seq(vec![choice(vec![tok("+"), tok("-")]), rule("product")])),
+            ]),
+        ),
+        (
+            "product",
+            seq(vec![
+                rule("cast"),
+                many(seq(vec![
+                    choice(vec![tok("*"), tok("/"), tok("mod")]),
+                    rule("cast"),
+                ])),
+            ]),
+        ),
+        (
+            "cast",
+            seq(vec![
+                rule("unary"),
+                many(seq(vec![tok("as"), rule("type_reference")])),
+            ]),
+        ),
+        (
+            "unary",
+            choice(vec![
+                seq(vec![choice(vec![tok("+"), tok("-")]), rule("unary")]),
+                rule("expression_atom"),
+            ]),
+        ),
+        (
+            "expression_atom",
+            seq(vec![
+                rule("primary"),
+                many(choice(vec![
+                    seq(vec![
+                        tok("["),
+                        rule("expression"),
+                        tok(".."),
+                        rule("expression"),
+                        tok("]"),
+                    ]),
+                    seq(vec![tok("["), rule("expression"), tok("]")]),
+                    seq(vec![
+                        tok("."),
+                        rule("identifier"),
+                        opt(seq(vec![tok("("), rule("arglist"), tok(")")])),
+                    ]),
+                    seq(vec![tok("("), rule("arglist"), tok(")")]),
+                ])),
+            ]),
+        ),
+        (
+            "primary",
+            choice(vec![
+                rule("layout_query"),
+                rule("path"),
+                rule("literal"),
+                seq(vec![tok("("), rule("expression"), tok(")")]),
+                seq(vec![tok("["), comma_list(rule("expression")), tok("]")]),
+                rule("type_primitive"),
+            ]),
+        ),
+        (
+            "layout_query",
+            seq(vec![
+                choice(vec![word("sizeof"), word("alignof"), word("offsetof")]),
+                tok("("),
+                rule("type_reference"),
+                many(seq(vec![tok(","), rule("identifier")])),
+                tok(")"),
+            ]),
+        ),
+        (
+            "call_arg",
+            seq(vec![
+                opt(seq(vec![rule("identifier"), tok(":")])),
+                rule("expression"),
+            ]),
+        ),
+        ("arglist", comma_list(rule("call_arg"))),
+        // type references
+        (
+            "type_reference",
+            choice(vec![
+                seq(vec![tok("("), rule("type_reference"), tok(")")]),
+                rule("type_primitive"),
+                seq(vec![
+                    tok("array"),
+                    opt(seq(vec![tok("["), rule("expression"), tok("]")])),
+                    tok("of"),
+                    rule("type_reference"),
+                ]),
+                seq(vec![
+                    tok("record"),
+                    tok("{"),
+                    comma_list(rule("record_field")),
+                    tok("}"),
+                ]),
+                seq(vec![
+                    rule("identifier"),
+                    tok("<"),
+                    rule("identifier"),
+                    tok(","),
+                    rule("identifier"),
+                    tok(">"),
+                ]),
+                seq(vec![
+                    rule("path"),
+                    opt(seq(vec![
+                        tok("<"),
+                        comma_list(rule("type_reference")),
+                        tok(">"),
+                    ])),
+                ]),
+            ]),
+        ),
+        (
+            "record_field",
+            seq(vec![rule("identifier"), tok(":"), rule("type_reference")]),
+        ),
+        ("type_primitive", choice(primitives)),
+        (
+            "type_prim_vec_annot",
+            choice(vec![
+                seq(vec![
+                    tok("is"),
+                    rule("type_vec_type"),
+                    opt(seq(vec![tok("in"), rule("path")])),
+                ]),
+                seq(vec![tok("in"), rule("path")]),
+            ]),
+        ),
+        (
+            "type_prim_mat_annot",
+            seq(vec![tok("from"), rule("path"), tok("to"), rule("path")]),
+        ),
+        (
+            "type_vec_type",
+            choice(vec![
+                tok("Point"),
+                tok("Vector"),
+                tok("Colour"),
+                tok("SrgbColour"),
+            ]),
+        ),
+        // terminals
+        (
+            "literal",
+            choice(vec![
+                Expr::Terminal("INTEGER"),
+                Expr::Terminal("FLOAT"),
+                tok("true"),
+                tok("false"),
+                Expr::Terminal("STRING"),
+            ]),
+        ),
+        (
+            "identifier",
+            choice(vec![
+                Expr::Terminal("IDENTIFIER"),
+                Expr::Terminal("RAW_IDENTIFIER"),
+            ]),
+        ),
+        (
+            "path",
+            seq(vec![
+                rule("identifier"),
+                many(seq(vec![tok("::"), rule("identifier")])),
+            ]),
+        ),
+    ];
+
+    Grammar {
+        rules: rules
+            .into_iter()
+            .map(|(name, body)| Rule { name, body })
+            .collect(),
+    }
+}
+
+/// How an expression is nested in another one, to know where it needs
+/// parentheses
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Top,
+    InSeq,
+    Postfix,
+}
+
+impl Grammar {
+    /// The rule with the name.
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    /// The grammar in ISO 14977 EBNF, like `path = identifier , { "::" ,
+    /// identifier } ;`.
+    pub fn to_ebnf(&self) -> String {
+        let mut out = String::new();
+        for rule in &self.rules {
+            let _ = writeln!(out, "{} = {} ;", rule.name, ebnf(&rule.body));
+        }
+        out
+    }
+
+    /// The grammar in the W3C notation of railroad diagram generators, like
+    /// `path ::= identifier ( '::' identifier )*`.
+    pub fn to_railroad(&self) -> String {
+        let mut out = String::new();
+        for rule in &self.rules {
+            let _ = writeln!(
+                out,
+                "{} ::= {}",
+                rule.name,
+                railroad(&rule.body, Position::Top)
+            );
+        }
+        out
+    }
+}
+
+fn single_quoted(text: &str) -> String {
+    if text.contains('\'') {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text)
+    }
+}
+
+fn quoted(text: &str) -> String {
+    if text.contains('"') {
+        format!("'{}'", text)
+    } else {
+        format!("\"{}\"", text)
+    }
+}
+
+/// An expression that is part of a sequence.
+fn ebnf_item(expr: &Expr) -> String {
+    match expr {
+        Expr::Choice(_) => format!("( {} )", ebnf(expr)),
+        _ => ebnf(expr),
+    }
+}
+
+fn ebnf(expr: &Expr) -> String {
+    match expr {
+        Expr::Token(text) => quoted(text),
+        Expr::Contextual(text) => quoted(text),
+        Expr::Terminal(name) | Expr::Rule(name) => name.to_string(),
+        Expr::Seq(exprs) => exprs.iter().map(ebnf_item).collect::<Vec<_>>().join(" , "),
+        Expr::Choice(exprs) => exprs.iter().map(ebnf).collect::<Vec<_>>().join(" | "),
+        Expr::Optional(e) => format!("[ {} ]", ebnf(e)),
+        Expr::Many(e) => format!("{{ {} }}", ebnf(e)),
+        Expr::Many1(e) => format!("{} , {{ {} }}", ebnf_item(e), ebnf(e)),
+    }
+}
+
+fn railroad(expr: &Expr, pos: Position) -> String {
+    let text = match expr {
+        Expr::Token(text) => single_quoted(text),
+        Expr::Contextual(text) => single_quoted(text),
+        Expr::Terminal(name) | Expr::Rule(name) => name.to_string(),
+        Expr::Seq(exprs) => {
+            let text = exprs
+                .iter()
+                .map(|e| railroad(e, Position::InSeq))
+                .collect::<Vec<_>>()
+                .join(" ");
+            return match pos {
+                Position::Postfix if exprs.len() > 1 => format!("( {} )", text),
+                _ => text,
+            };
+        }
+        Expr::Choice(exprs) => {
+            let text = exprs
+                .iter()
+                .map(|e| railroad(e, Position::Top))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            return match pos {
+                Position::Top => text,
+                _ => format!("( {} )", text),
+            };
+        }
+        Expr::Optional(e) => format!("{}?", railroad(e, Position::Postfix)),
+        Expr::Many(e) => format!("{}*", railroad(e, Position::Postfix)),
+        Expr::Many1(e) => format!("{}+", railroad(e, Position::Postfix)),
+    };
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::keywords::KEYWORDS;
+
+    /// Rules of the parser that the grammar writes out where they are used.
+    const PARSER_ONLY: &[&str] = &[
+        "item_len",
+        "variable_def_in",
+        "contextual",
+        "first",
+        "sep_trailing",
+    ];
+    /// Rules of the grammar that are part of another rule in the parser.
+    const GRAMMAR_ONLY: &[&str] = &[
+        "const_def",
+        "comparison",
+        "sum",
+        "product",
+        "cast",
+        "unary",
+        "primary",
+    ];
+
+    fn tokens<'a>(expr: &'a Expr, found: &mut BTreeSet<&'a str>) {
+        match expr {
+            Expr::Token(text) => {
+                found.insert(text);
+            }
+            Expr::Terminal(_) | Expr::Contextual(_) | Expr::Rule(_) => {}
+            Expr::Seq(exprs) | Expr::Choice(exprs) => {
+                for e in exprs {
+                    tokens(e, found);
+                }
+            }
+            Expr::Optional(e) | Expr::Many(e) | Expr::Many1(e) => tokens(e, found),
+        }
+    }
+
+    fn used_rules(expr: &Expr, found: &mut BTreeSet<&'static str>) {
+        match expr {
+            Expr::Rule(name) => {
+                found.insert(name);
+            }
+            Expr::Token(_) | Expr::Terminal(_) | Expr::Contextual(_) => {}
+            Expr::Seq(exprs) | Expr::Choice(exprs) => {
+                for e in exprs {
+                    used_rules(e, found);
+                }
+            }
+            Expr::Optional(e) | Expr::Many(e) | Expr::Many1(e) => used_rules(e, found),
+        }
+    }
+
+    #[test]
+    fn rules_match_parser() {
+        let source = include_str!("parser.rs");
+        let parser_rules = source
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_start();
+                let line = line.strip_prefix("pub ").unwrap_or(line);
+                let rest = line.strip_prefix("rule ")?;
+                let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+                Some(&rest[..end])
+            })
+            .filter(|name| !PARSER_ONLY.contains(name))
+            .collect::<BTreeSet<_>>();
+
+        let grammar = grammar();
+        let described = grammar
+            .rules
+            .iter()
+            .map(|rule| rule.name)
+            .filter(|name| !GRAMMAR_ONLY.contains(name))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(parser_rules, described);
+
+        let mut used = BTreeSet::new();
+        for rule in &grammar.rules {
+            used_rules(&rule.body, &mut used);
+        }
+        for name in used {
+            assert!(grammar.rule(name).is_some(), "`{}` isn't described", name);
+        }
+    }
+
+    #[test]
+    fn tokens_match_keywords() {
+        let grammar = grammar();
+        let mut found = BTreeSet::new();
+        for rule in &grammar.rules {
+            tokens(&rule.body, &mut found);
+        }
+        let words = found
+            .into_iter()
+            .filter(|text| text.starts_with(char::is_alphabetic))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(words, KEYWORDS.iter().copied().collect());
+    }
+
+    #[test]
+    fn notations() {
+        let grammar = grammar();
+        let ebnf = grammar.to_ebnf();
+        let railroad = grammar.to_railroad();
+        assert!(ebnf.contains("path = identifier , { \"::\" , identifier } ;\n"));
+        assert!(railroad.contains("path ::= identifier ( '::' identifier )*\n"));
+        assert!(ebnf.contains("consts = \"const\" , const_def , { const_def } ;\n"));
+        assert!(railroad.contains("consts ::= 'const' const_def+\n"));
+        assert!(railroad.contains("param_mode ::= 'in' 'out' | 'in' | 'out'\n"));
+        assert!(ebnf.contains("sum = product , { ( \"+\" | \"-\" ) , product } ;\n"));
+        assert_eq!(ebnf.lines().count(), grammar.rules.len());
+    }
+}
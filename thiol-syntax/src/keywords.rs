@@ -0,0 +1,201 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Words that can't be used as names as they are.
+//!
+//! [`KEYWORDS`] are the words the lexer reads as keywords, and [`RESERVED`]
+//! are words kept free for keywords of later versions of the language. Both
+//! can still be used as names by writing them as raw identifiers, like
+//! `r#type`, which the lexer reads as the identifier `type`. Reserved words
+//! written without `r#` are identifiers, which the type checker reports in
+//! the `reserved-words` lint group so that code using them keeps working
+//! until they become keywords.
+//!
+//! The backends escape both kinds of words like keywords of the target,
+//! since a raw identifier can spell a type or statement keyword of the
+//! target language that is also a thiol keyword.
+
+use crate::{lexer::TokenKind, FileId, Loc};
+
+/// The keywords of the language
+pub const KEYWORDS: &[&str] = &[
+    "Colour",
+    "Point",
+    "SrgbColour",
+    "Vector",
+    "array",
+    "as",
+    "atomic",
+    "begin",
+    "bool",
+    "bool2",
+    "bool3",
+    "bool4",
+    "break",
+    "const",
+    "continue",
+    "degrees",
+    "discard",
+    "distinct",
+    "do",
+    "double",
+    "double2",
+    "double2x2",
+    "double2x3",
+    "double2x4",
+    "double3",
+    "double3x2",
+    "double3x3",
+    "double3x4",
+    "double4",
+    "double4x2",
+    "double4x3",
+    "double4x4",
+    "downto",
+    "else",
+    "elseif",
+    "end",
+    "false",
+    "float",
+    "float16x2",
+    "float16x4",
+    "float2",
+    "float2x2",
+    "float2x3",
+    "float2x4",
+    "float3",
+    "float3x2",
+    "float3x3",
+    "float3x4",
+    "float4",
+    "float4x2",
+    "float4x3",
+    "float4x4",
+    "for",
+    "from",
+    "function",
+    "half",
+    "half2",
+    "half3",
+    "half4",
+    "if",
+    "in",
+    "input",
+    "int",
+    "int2",
+    "int3",
+    "int4",
+    "is",
+    "long",
+    "long2",
+    "long3",
+    "long4",
+    "match",
+    "mod",
+    "module",
+    "of",
+    "out",
+    "output",
+    "program",
+    "pub",
+    "radians",
+    "record",
+    "return",
+    "returns",
+    "rgb10a2",
+    "sint8x4",
+    "snorm16x2",
+    "snorm8x4",
+    "space",
+    "then",
+    "to",
+    "true",
+    "type",
+    "uint",
+    "uint2",
+    "uint3",
+    "uint4",
+    "uint8x4",
+    "ulong",
+    "ulong2",
+    "ulong3",
+    "ulong4",
+    "unorm16x2",
+    "unorm8x4",
+    "use",
+    "var",
+    "workgroup",
+];
+
+/// Words reserved for keywords of later versions of the language
+pub const RESERVED: &[&str] = &[
+    "case", "enum", "fn", "impl", "let", "loop", "mut", "self", "static", "struct", "switch",
+    "trait", "typeof", "union", "where", "while", "yield",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.binary_search(&word).is_ok()
+}
+
+pub fn is_reserved(word: &str) -> bool {
+    RESERVED.binary_search(&word).is_ok()
+}
+
+/// How the name is written in the source: as a raw identifier if it is a
+/// keyword or reserved word.
+pub fn source_spelling(name: &str) -> String {
+    if is_keyword(name) || is_reserved(name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// The reserved words of a file that are used as names without `r#`.
+pub fn reserved_words(file: FileId, input: &str) -> Vec<Loc<String>> {
+    crate::lexer::tokenise(file, input)
+        .filter_map(|tok| match tok.value {
+            TokenKind::Identifier(name) if is_reserved(&name) => Some(Loc::new(tok.loc, name)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexed(input: &str) -> TokenKind {
+        crate::lexer::tokenise(0, input).next().unwrap().value
+    }
+
+    #[test]
+    fn tables() {
+        for table in [KEYWORDS, RESERVED] {
+            assert!(table.windows(2).all(|w| w[0] < w[1]), "unsorted table");
+        }
+        for word in KEYWORDS {
+            assert!(
+                !matches!(lexed(word), TokenKind::Identifier(_)),
+                "`{}` isn't a keyword",
+                word
+            );
+            let raw = format!("r#{}", word);
+            assert_eq!(lexed(&raw), TokenKind::RawIdentifier(word.to_string()));
+        }
+        for word in RESERVED {
+            assert_eq!(lexed(word), TokenKind::Identifier(word.to_string()));
+        }
+        assert_eq!(source_spelling("type"), "r#type");
+        assert_eq!(source_spelling("types"), "types");
+    }
+
+    #[test]
+    fn reserved_names() {
+        let words = reserved_words(0, "var r#while: int := while + 1;");
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].value, "while");
+        assert_eq!(words[0].loc.range(), 20..25);
+    }
+}
@@ -3,8 +3,13 @@
 // SPDX-License-Identifier: EUPL-1.2
 
 use logos::Logos;
+use unicode_normalization::UnicodeNormalization;
 
-use crate::{ast::VecSize, FileId, FileLocation, Loc};
+use crate::{
+    ast::{LiteralSuffix, PackedFormat, VecSize},
+    trivia::TriviaKind,
+    FileId, FileLocation, Loc,
+};
 
 #[derive(Debug, Clone, PartialEq, Logos)]
 pub enum TokenKind {
@@ -28,6 +33,8 @@ pub enum TokenKind {
     Of,
     #[token("in")]
     In,
+    #[token("out")]
+    Out,
     #[token("is")]
     Is,
     #[token("from")]
@@ -45,6 +52,8 @@ pub enum TokenKind {
     Vector,
     #[token("Colour")]
     Colour,
+    #[token("SrgbColour")]
+    SrgbColour,
 
     #[token("function")]
     Function,
@@ -52,6 +61,16 @@ pub enum TokenKind {
     #[token("program")]
     Program,
 
+    #[token("space")]
+    Space,
+
+    #[token("module")]
+    Module,
+    #[token("pub")]
+    Pub,
+    #[token("use")]
+    Use,
+
     #[token("var")]
     Var,
     #[token("begin")]
@@ -65,6 +84,8 @@ pub enum TokenKind {
     Break,
     #[token("continue")]
     Continue,
+    #[token("discard")]
+    Discard,
 
     #[token("if")]
     If,
@@ -74,6 +95,8 @@ pub enum TokenKind {
     Else,
     #[token("elseif")]
     ElseIf,
+    #[token("match")]
+    Match,
 
     #[token("for")]
     For,
@@ -87,6 +110,13 @@ pub enum TokenKind {
     Input,
     #[token("output")]
     Output,
+    #[token("workgroup")]
+    Workgroup,
+
+    #[token("true")]
+    True,
+    #[token("false")]
+    False,
 
     // primitive types
     #[token("bool")]
@@ -95,10 +125,22 @@ pub enum TokenKind {
     TyInt,
     #[token("uint")]
     TyUInt,
+    #[token("long")]
+    TyLong,
+    #[token("ulong")]
+    TyULong,
     #[token("float")]
     TyFloat,
     #[token("double")]
     TyDouble,
+    #[token("half")]
+    TyHalf,
+    #[token("radians")]
+    TyRadians,
+    #[token("degrees")]
+    TyDegrees,
+    #[token("atomic")]
+    TyAtomic,
 
     #[token("bool2", |_| VecSize::VS2)]
     #[token("bool3", |_| VecSize::VS3)]
@@ -115,6 +157,16 @@ pub enum TokenKind {
     #[token("uint4", |_| VecSize::VS4)]
     TyUIntVec(VecSize),
 
+    #[token("long2", |_| VecSize::VS2)]
+    #[token("long3", |_| VecSize::VS3)]
+    #[token("long4", |_| VecSize::VS4)]
+    TyLongVec(VecSize),
+
+    #[token("ulong2", |_| VecSize::VS2)]
+    #[token("ulong3", |_| VecSize::VS3)]
+    #[token("ulong4", |_| VecSize::VS4)]
+    TyULongVec(VecSize),
+
     #[token("float2", |_| VecSize::VS2)]
     #[token("float3", |_| VecSize::VS3)]
     #[token("float4", |_| VecSize::VS4)]
@@ -125,6 +177,11 @@ pub enum TokenKind {
     #[token("double4", |_| VecSize::VS4)]
     TyDoubleVec(VecSize),
 
+    #[token("half2", |_| VecSize::VS2)]
+    #[token("half3", |_| VecSize::VS3)]
+    #[token("half4", |_| VecSize::VS4)]
+    TyHalfVec(VecSize),
+
     #[token("float2x2", |_| { (VecSize::VS2, VecSize::VS2) })]
     #[token("float2x3", |_| { (VecSize::VS2, VecSize::VS3) })]
     #[token("float2x4", |_| { (VecSize::VS2, VecSize::VS4) })]
@@ -147,14 +204,39 @@ pub enum TokenKind {
     #[token("double4x4", |_| { (VecSize::VS4, VecSize::VS4) })]
     TyDoubleMat((VecSize, VecSize)),
 
-    #[regex(r"(\p{XID_Start}|_)(\p{XID_Continue}|')*", |lex| lex.slice().to_string())]
+    #[token("unorm8x4", |_| PackedFormat::Unorm8x4)]
+    #[token("snorm8x4", |_| PackedFormat::Snorm8x4)]
+    #[token("uint8x4", |_| PackedFormat::Uint8x4)]
+    #[token("sint8x4", |_| PackedFormat::Sint8x4)]
+    #[token("unorm16x2", |_| PackedFormat::Unorm16x2)]
+    #[token("snorm16x2", |_| PackedFormat::Snorm16x2)]
+    #[token("float16x2", |_| PackedFormat::Float16x2)]
+    #[token("float16x4", |_| PackedFormat::Float16x4)]
+    #[token("rgb10a2", |_| PackedFormat::Rgb10a2)]
+    TyPacked(PackedFormat),
+
+    /// a name as defined by UAX #31, in normalization form C
+    #[regex(r"(\p{XID_Start}|_)(\p{XID_Continue}|')*", |lex| normalize_identifier(lex.slice()))]
     Identifier(String),
-
-    #[regex(r"[0-9][0-9_]*", |lex| parse_integer_literal(lex.slice()))]
-    Integer(u128),
-
-    #[regex(r"[0-9][0-9_]*\.[0-9_]*", |lex| parse_float_literal(lex.slice()))]
-    Float(f64),
+    /// a name written as `r#name`, which can be a keyword, without the `r#`
+    #[regex(r"r#(\p{XID_Start}|_)(\p{XID_Continue}|')*", |lex| normalize_identifier(&lex.slice()[2..]))]
+    RawIdentifier(String),
+
+    /// the value, `None` if it doesn't fit into 128 bits, and the suffix
+    #[regex(r"[0-9][0-9_]*(i|u|l|ul)?", |lex| parse_integer_literal(lex.slice(), 10))]
+    #[regex(r"0x[0-9a-fA-F_]+(i|u|l|ul)?", |lex| parse_integer_literal(&lex.slice()[2..], 16))]
+    #[regex(r"0o[0-7_]+(i|u|l|ul)?", |lex| parse_integer_literal(&lex.slice()[2..], 8))]
+    #[regex(r"0b[01_]+(i|u|l|ul)?", |lex| parse_integer_literal(&lex.slice()[2..], 2))]
+    Integer((Option<u128>, Option<LiteralSuffix>)),
+
+    #[regex(r"[0-9][0-9_]*\.[0-9_]*(f|lf|rad|deg)?", |lex| parse_float_literal(lex.slice()))]
+    #[regex(r"[0-9][0-9_]*(rad|deg)", |lex| parse_float_literal(lex.slice()))]
+    Float((f64, Option<LiteralSuffix>)),
+
+    /// the text of the string with the escapes `\\`, `\"`, `\n` and `\t`
+    /// replaced
+    #[regex(r#""([^"\\\n]|\\.)*""#, |lex| parse_string_literal(lex.slice()))]
+    String(String),
 
     //
     // operators
@@ -201,15 +283,26 @@ pub enum TokenKind {
     #[token("]")]
     BracketClose,
 
+    #[token("{")]
+    BraceOpen,
+    #[token("}")]
+    BraceClose,
+
     #[token(",")]
     Comma,
     #[token(":")]
     Colon,
+    #[token("::")]
+    PathSep,
     #[token(";")]
     SemiColon,
 
     #[token(".")]
     Dot,
+    #[token("..")]
+    DotDot,
+    #[token("@")]
+    At,
 
     // Misc
     #[regex(r"//[^\n]*", logos::skip)]
@@ -225,26 +318,77 @@ pub enum TokenKind {
     PrefixExpr,
 }
 
-fn parse_integer_literal(s: &str) -> Option<u128> {
-    s.chars()
-        .filter(|c| *c != '_')
-        .collect::<String>()
-        .parse()
-        .ok()
+/// The name in normalization form C, so that names that only differ in how
+/// their characters are composed, like `é` and `e` with a combining acute
+/// accent, are the same name everywhere after the lexer.
+pub fn normalize_identifier(name: &str) -> String {
+    name.nfc().collect()
 }
 
-fn parse_float_literal(s: &str) -> Option<f64> {
-    s.chars()
+/// `None` makes the literal an error token, a value of `None` marks a
+/// literal that is too large.
+fn parse_integer_literal(s: &str, radix: u32) -> Option<(Option<u128>, Option<LiteralSuffix>)> {
+    let (s, suffix) = if let Some(s) = s.strip_suffix("ul") {
+        (s, Some(LiteralSuffix::ULong))
+    } else {
+        match s.as_bytes().last() {
+            Some(b'i') => (&s[..s.len() - 1], Some(LiteralSuffix::Int)),
+            Some(b'u') => (&s[..s.len() - 1], Some(LiteralSuffix::UInt)),
+            Some(b'l') => (&s[..s.len() - 1], Some(LiteralSuffix::Long)),
+            _ => (s, None),
+        }
+    };
+    let digits = s.chars().filter(|c| *c != '_').collect::<String>();
+    if digits.is_empty() {
+        return None;
+    }
+    Some((u128::from_str_radix(&digits, radix).ok(), suffix))
+}
+
+fn parse_string_literal(s: &str) -> Option<String> {
+    let mut string = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        string.push(match chars.next()? {
+            '\\' => '\\',
+            '"' => '"',
+            'n' => '\n',
+            't' => '\t',
+            _ => return None,
+        });
+    }
+    Some(string)
+}
+
+fn parse_float_literal(s: &str) -> Option<(f64, Option<LiteralSuffix>)> {
+    let (s, suffix) = if let Some(s) = s.strip_suffix("lf") {
+        (s, Some(LiteralSuffix::Double))
+    } else if let Some(s) = s.strip_suffix("rad") {
+        (s, Some(LiteralSuffix::Radians))
+    } else if let Some(s) = s.strip_suffix("deg") {
+        (s, Some(LiteralSuffix::Degrees))
+    } else if let Some(s) = s.strip_suffix('f') {
+        (s, Some(LiteralSuffix::Float))
+    } else {
+        (s, None)
+    };
+    let value = s
+        .chars()
         .filter(|c| *c != '_')
         .collect::<String>()
         .parse()
-        .ok()
+        .ok()?;
+    Some((value, suffix))
 }
 
 pub type Token = Loc<TokenKind>;
 
 pub fn tokenise(file_id: FileId, input: &'_ str) -> impl Iterator<Item = Token> + '_ {
-    TokenKind::lexer(input)
+    let mut toks = TokenKind::lexer(input)
         .spanned()
         .map(move |(kind, span)| Token {
             value: kind,
@@ -254,6 +398,76 @@ pub fn tokenise(file_id: FileId, input: &'_ str) -> impl Iterator<Item = Token>
                 end: span.end,
             },
         })
+        .peekable();
+    let mut pending = None;
+    std::iter::from_fn(move || {
+        if let Some(tok) = pending.take() {
+            return Some(tok);
+        }
+        let tok = toks.next()?;
+        // the float regex takes the first dot of `0..4`, which is the
+        // integer `0` in front of a `..`
+        let text = &input[tok.loc.range()];
+        let range_start = matches!(tok.value, TokenKind::Float(_))
+            && text.ends_with('.')
+            && toks
+                .peek()
+                .is_some_and(|next| next.value == TokenKind::Dot && next.loc.start == tok.loc.end);
+        if !range_start {
+            return Some(tok);
+        }
+        let dot = toks.next()?;
+        let integer = Token {
+            value: parse_integer_literal(&text[..text.len() - 1], 10)
+                .map_or(TokenKind::Error, TokenKind::Integer),
+            loc: FileLocation {
+                end: tok.loc.end - 1,
+                ..tok.loc
+            },
+        };
+        pending = Some(Token {
+            value: TokenKind::DotDot,
+            loc: FileLocation {
+                start: tok.loc.end - 1,
+                ..dot.loc
+            },
+        });
+        Some(integer)
+    })
+}
+
+/// All tokens of a text, including the whitespace and comments [`tokenise`]
+/// skips, so the tokens cover the text without gaps. The locations are in
+/// file `0`.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut pos = 0;
+    for tok in tokenise(0, input) {
+        tokens.extend(trivia(input, pos, tok.loc.start));
+        pos = tok.loc.end;
+        tokens.push(tok);
+    }
+    tokens.extend(trivia(input, pos, input.len()));
+    tokens
+}
+
+fn trivia(input: &str, start: usize, end: usize) -> impl Iterator<Item = Token> {
+    crate::trivia::pieces(0, input, start, end)
+        .into_iter()
+        .map(|piece| {
+            let kind = match piece.value {
+                TriviaKind::Whitespace => TokenKind::Whitespace,
+                TriviaKind::Comment => TokenKind::Comment,
+            };
+            Loc::new(piece.loc, kind)
+        })
+}
+
+impl TokenKind {
+    /// Whitespace and comments, which only [`tokenize`] returns
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
 }
 
 #[cfg(test)]
@@ -272,6 +486,12 @@ mod tests {
         check("_test", TokenKind::Identifier("_test".into()));
         check("x'", TokenKind::Identifier("x'".into()));
         check("klöße42", TokenKind::Identifier("klöße42".into()));
+        check("cafe\u{301}", TokenKind::Identifier("caf\u{e9}".into()));
+        check(
+            "r#cafe\u{301}",
+            TokenKind::RawIdentifier("caf\u{e9}".into()),
+        );
+        check("λ_max", TokenKind::Identifier("λ_max".into()));
         check(
             "TEST_CONSTANT",
             TokenKind::Identifier("TEST_CONSTANT".into()),
@@ -280,11 +500,95 @@ mod tests {
 
     #[test]
     fn lex_numbers() {
-        check("0", TokenKind::Integer(0));
-        check("1_234", TokenKind::Integer(1_234));
-
-        check("1.", TokenKind::Float(1.0));
+        check("0", TokenKind::Integer((Some(0), None)));
+        check("1_234", TokenKind::Integer((Some(1_234), None)));
+        check("0xFF", TokenKind::Integer((Some(0xff), None)));
+        check(
+            "0x_dead_beef",
+            TokenKind::Integer((Some(0xdead_beef), None)),
+        );
+        check("0o755", TokenKind::Integer((Some(0o755), None)));
+        check("0b1010", TokenKind::Integer((Some(0b1010), None)));
+        check("1_000_000", TokenKind::Integer((Some(1_000_000), None)));
+        check(&format!("{}0", u128::MAX), TokenKind::Integer((None, None)));
+        check("0x_", TokenKind::Error);
+        check("true", TokenKind::True);
+        check(r#""a \"b\"\n""#, TokenKind::String("a \"b\"\n".into()));
+        check(r#""\q""#, TokenKind::Error);
+        check("falsey", TokenKind::Identifier("falsey".into()));
+
+        check("1.", TokenKind::Float((1.0, None)));
         #[allow(clippy::approx_constant)]
-        check("3.1415", TokenKind::Float(3.1415));
+        check("3.1415", TokenKind::Float((3.1415, None)));
+    }
+
+    #[test]
+    fn lex_suffixes() {
+        use LiteralSuffix::*;
+
+        check("1u", TokenKind::Integer((Some(1), Some(UInt))));
+        check("0xFFi", TokenKind::Integer((Some(0xff), Some(Int))));
+        check("1l", TokenKind::Integer((Some(1), Some(Long))));
+        check("0xFFul", TokenKind::Integer((Some(0xff), Some(ULong))));
+        check("1.0f", TokenKind::Float((1.0, Some(Float))));
+        check("1.5lf", TokenKind::Float((1.5, Some(Double))));
+        check("2.f", TokenKind::Float((2.0, Some(Float))));
+        check("1.57rad", TokenKind::Float((1.57, Some(Radians))));
+        check("90deg", TokenKind::Float((90.0, Some(Degrees))));
+    }
+
+    #[test]
+    fn tokenize_keeps_trivia() {
+        let input = "x := 1; // one\n";
+        let tokens = tokenize(input);
+        let kinds = tokens
+            .iter()
+            .map(|tok| tok.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Identifier("x".into()),
+                TokenKind::Whitespace,
+                TokenKind::Becomes,
+                TokenKind::Whitespace,
+                TokenKind::Integer((Some(1), None)),
+                TokenKind::SemiColon,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::Whitespace,
+            ]
+        );
+        let text = tokens
+            .iter()
+            .map(|tok| &input[tok.loc.range()])
+            .collect::<String>();
+        assert_eq!(text, input);
+    }
+
+    #[test]
+    fn lex_ranges() {
+        let kinds = |input| {
+            tokenise(0, input)
+                .map(|tok| (tok.value, tok.loc.range()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("0..4"),
+            [
+                (TokenKind::Integer((Some(0), None)), 0..1),
+                (TokenKind::DotDot, 1..3),
+                (TokenKind::Integer((Some(4), None)), 3..4),
+            ]
+        );
+        assert_eq!(
+            kinds("lo..hi"),
+            [
+                (TokenKind::Identifier("lo".into()), 0..2),
+                (TokenKind::DotDot, 2..4),
+                (TokenKind::Identifier("hi".into()), 4..6),
+            ]
+        );
+        assert_eq!(kinds("1. .5")[0], (TokenKind::Float((1.0, None)), 0..2));
     }
 }
@@ -2,10 +2,16 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+pub mod keywords;
 pub mod lexer;
 pub mod parser;
 
 pub mod ast;
+pub mod cst;
+pub mod grammar;
+pub mod trivia;
+
+pub use lexer::{tokenize, Token, TokenKind};
 
 pub type FileId = usize;
 
@@ -4,50 +4,225 @@
 
 #![allow(clippy::redundant_closure_call)]
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
 use crate::{
     lexer::{Token, TokenKind as TK},
-    FileId, Loc,
+    FileId, FileLocation, Loc,
 };
 
 use crate::ast;
 
-#[derive(Debug, Clone)]
-pub struct ParseError {
-    pub location: crate::FileLocation,
-    pub expected_tokens: Vec<String>,
+/// A syntax error
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// a token the grammar doesn't allow where it is
+    UnexpectedToken {
+        location: FileLocation,
+        found: TK,
+        expected: BTreeSet<Expected>,
+    },
+    /// the input ended in the middle of an item
+    UnexpectedEof {
+        location: FileLocation,
+        expected: BTreeSet<Expected>,
+    },
 }
 
-pub fn parse_file(file_id: FileId, input: &str) -> Result<ast::File, ParseError> {
-    let toks = crate::lexer::tokenise(file_id, input).collect::<Vec<_>>();
+/// Something the parser would have accepted instead of the error
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Expected {
+    /// a token, by the name of its [`TokenKind`](TK) variant
+    Token(String),
+    /// a token the grammar names, like `identifier`
+    Production(String),
+}
+
+impl ParseError {
+    pub fn location(&self) -> FileLocation {
+        match self {
+            ParseError::UnexpectedToken { location, .. }
+            | ParseError::UnexpectedEof { location, .. } => *location,
+        }
+    }
+
+    pub fn expected(&self) -> &BTreeSet<Expected> {
+        match self {
+            ParseError::UnexpectedToken { expected, .. }
+            | ParseError::UnexpectedEof { expected, .. } => expected,
+        }
+    }
+}
 
-    fn extract_expected_token(s: &str) -> String {
-        s.trim_start_matches("[tok ! (TK :: ")
-            .split(|c: char| !c.is_alphanumeric())
-            .next()
-            .unwrap()
-            .to_string()
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Token(name) => write!(f, "{}", name),
+            Expected::Production(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { found, .. } => write!(f, "unexpected token {:?}", found)?,
+            ParseError::UnexpectedEof { .. } => write!(f, "unexpected end of file")?,
+        }
+        let expected = self.expected().iter().map(|e| e.to_string());
+        write!(
+            f,
+            ", expected one of {}",
+            expected.collect::<Vec<_>>().join(", ")
+        )
     }
+}
+
+pub fn parse_file(file_id: FileId, input: &str) -> Result<ast::File, ParseError> {
+    let toks = crate::lexer::tokenise(file_id, input).collect::<Vec<_>>();
 
     match parser::file(&toks) {
-        Ok(val) => Ok(val),
+        Ok(mut val) => {
+            val.trivia = crate::trivia::Trivia::new(file_id, input);
+            val.reserved_words = crate::keywords::reserved_words(file_id, input);
+            val.path_names = path_names(&toks);
+            Ok(val)
+        }
         Err(err) => {
-            let location = toks
-                .get(err.location)
-                .map(|t| t.loc)
-                .unwrap_or(crate::FileLocation {
-                    file: file_id,
-                    start: input.len(),
-                    end: input.len(),
-                });
-            let expected = err.expected.tokens().map(extract_expected_token).collect();
-            Err(ParseError {
-                location,
-                expected_tokens: expected,
+            let eof = FileLocation {
+                file: file_id,
+                start: input.len(),
+                end: input.len(),
+            };
+            Err(ParseError::new(&toks, eof, err))
+        }
+    }
+}
+
+/// The location of the last name of every path of several names, by the
+/// location of the whole path, see [`ast::File::path_names`].
+pub fn path_names(toks: &[Token]) -> BTreeMap<FileLocation, FileLocation> {
+    let is_name = |i: usize| {
+        matches!(
+            toks.get(i).map(|tok| &tok.value),
+            Some(TK::Identifier(_)) | Some(TK::RawIdentifier(_))
+        )
+    };
+    let mut names = BTreeMap::new();
+    let mut i = 0;
+    while i < toks.len() {
+        if !is_name(i) {
+            i += 1;
+            continue;
+        }
+        let first = i;
+        while matches!(toks.get(i + 1).map(|tok| &tok.value), Some(TK::PathSep)) && is_name(i + 2) {
+            i += 2;
+        }
+        if i > first {
+            names.insert(toks[first].loc.merge(toks[i].loc), toks[i].loc);
+        }
+        i += 1;
+    }
+    names
+}
+
+/// Parse a whole input as one expression, like a value given on the command
+/// line.
+pub fn parse_expression(file_id: FileId, input: &str) -> Result<Loc<ast::Expression>, ParseError> {
+    let toks = crate::lexer::tokenise(file_id, input).collect::<Vec<_>>();
+    let eof = FileLocation {
+        file: file_id,
+        start: input.len(),
+        end: input.len(),
+    };
+    parser::expression(&toks).map_err(|err| ParseError::new(&toks, eof, err))
+}
+
+/// Parse exactly one item, `toks` can be any slice of the tokens of a file.
+pub(crate) fn parse_item(toks: &[Token], eof: FileLocation) -> Result<ast::Item, ParseError> {
+    parser::item(toks).map_err(|err| ParseError::new(toks, eof, err))
+}
+
+/// The number of tokens of the item the tokens start with, if it parses.
+pub(crate) fn item_len(toks: &[Token]) -> Option<usize> {
+    parser::item_len(toks).ok()
+}
+
+/// Items are private unless `pub` was found.
+fn visibility(pub_: Option<FileLocation>) -> ast::Visibility {
+    match pub_ {
+        Some(_) => ast::Visibility::Public,
+        None => ast::Visibility::Private,
+    }
+}
+
+/// The layout query named `name`, if its arguments after the type fit it:
+/// the field of `offsetof` and then the optional layout rules.
+fn layout_query_args(
+    name: &str,
+    ty: Loc<ast::TypeReference>,
+    args: Vec<Loc<ast::Identifier>>,
+) -> Option<ast::Expression> {
+    let mut args = args.into_iter();
+    let query = match name {
+        "sizeof" => ast::LayoutQuery::Size,
+        "alignof" => ast::LayoutQuery::Align,
+        "offsetof" => ast::LayoutQuery::Offset(args.next()?),
+        _ => return None,
+    };
+    let rules = args.next();
+    if args.next().is_some() {
+        return None;
+    }
+    Some(ast::Expression::LayoutQuery { query, ty, rules })
+}
+
+impl ParseError {
+    /// `eof` is where errors at the end of the tokens are reported.
+    fn new(toks: &[Token], eof: FileLocation, err: peg::error::ParseError<usize>) -> Self {
+        // tokens are reported as the pattern that matches them, like
+        // `[tok!(TK::Minus, opl)]`, productions by their name
+        let expected = err
+            .expected
+            .tokens()
+            .map(|s| match s.find("TK") {
+                Some(i) => {
+                    let name = s[i + 2..].trim_start_matches(|c: char| !c.is_alphanumeric());
+                    let end = name
+                        .find(|c: char| !c.is_alphanumeric())
+                        .unwrap_or(name.len());
+                    Expected::Token(name[..end].to_string())
+                }
+                None => Expected::Production(s.to_string()),
             })
+            .collect();
+        match toks.get(err.location) {
+            Some(tok) => ParseError::UnexpectedToken {
+                location: tok.loc,
+                found: tok.value.clone(),
+                expected,
+            },
+            None => ParseError::UnexpectedEof {
+                location: eof,
+                expected,
+            },
         }
     }
 }
 
+/// An argument of a function with its attributes
+type FunctionArg = (
+    Vec<Loc<ast::Attribute>>,
+    (
+        Loc<ast::Identifier>,
+        Loc<ast::TypeReference>,
+        ast::ParamMode,
+    ),
+);
+
 macro_rules! tok {
     ($p:pat, $loc:ident) => {
         Token {
@@ -71,10 +246,13 @@ peg::parser! {
         = items:item()* ![_] {
             ast::File {
                 items,
+                trivia: Default::default(),
+                reserved_words: vec![],
+                path_names: BTreeMap::new(),
             }
         }
 
-        rule item() -> ast::Item
+        pub rule item() -> ast::Item
         =
             func:function() {
                 ast::Item::Function(func)
@@ -88,24 +266,91 @@ peg::parser! {
         /   prog:program() {
                 ast::Item::Program(prog)
             }
+        /   space:space() {
+                ast::Item::Space(space)
+            }
+        /   module:module() {
+                ast::Item::Module(module)
+            }
+        /   use_:use_declaration() {
+                ast::Item::Use(use_)
+            }
+        /   prelude:prelude() {
+                ast::Item::Prelude(prelude)
+            }
+        /   assert:static_assert() {
+                ast::Item::StaticAssert(assert)
+            }
+
+        /// The number of tokens of the item at the start of the tokens
+        pub rule item_len() -> usize
+        = item() len:position!() [_]* { len }
+
+        //
+        // Module
+        //
+        rule module() -> Loc<ast::ModuleDefinition>
+        =
+            [tok!(TK::Module, start)] name:identifier()
+                items:item()*
+            [tok!(TK::End, end)] {
+                Loc::new(start.merge(end), ast::ModuleDefinition { name, items })
+            }
+
+        rule use_declaration() -> Loc<ast::UseDeclaration>
+        =
+            pub_:visibility(true) [tok!(TK::Use, use_)] path:path() [tok!(TK::SemiColon, end)] {
+                let start = pub_.unwrap_or(use_);
+                Loc::new(
+                    start.merge(end),
+                    ast::UseDeclaration { visibility: visibility(pub_), path },
+                )
+            }
+
+        rule prelude() -> Loc<ast::Prelude>
+        =
+            word:identifier() module:path() [tok!(TK::SemiColon, end)] {?
+                if word.value == "prelude" {
+                    Ok(Loc::new(word.loc.merge(end), ast::Prelude { module }))
+                } else {
+                    Err("prelude")
+                }
+            }
+
+        rule static_assert() -> Loc<ast::StaticAssert>
+        =
+            word:identifier() [tok!(TK::ParenOpen)] condition:expression() [tok!(TK::Comma)]
+                [tok!(TK::String(message), message_loc)]
+            [tok!(TK::ParenClose)] [tok!(TK::SemiColon, end)] {?
+                if word.value == "static_assert" {
+                    let message = Loc::new(message_loc, message);
+                    Ok(Loc::new(word.loc.merge(end), ast::StaticAssert { condition, message }))
+                } else {
+                    Err("static_assert")
+                }
+            }
 
         //
         // Program
         //
         rule program() -> Loc<ast::Program>
         =
-            [tok!(TK::Program, start)] name:identifier()
+            attrs:attribute()* [tok!(TK::Program, program)] name:identifier()
                 inputs:program_inputs()?
                 outputs:program_outputs()?
+                workgroup:program_workgroup()?
             [tok!(TK::Begin)]
                 body:block()
             [tok!(TK::End, end)] {
+                let start = attrs.first().map(|l| l.loc).unwrap_or(program);
                 Loc::new(
                     start.merge(end),
                     ast::Program {
+                        attributes: attrs,
                         name,
                         inputs: inputs.unwrap_or_default(),
                         outputs: outputs.unwrap_or_default(),
+                        workgroup: workgroup.unwrap_or_default(),
                         body,
                     }
                 )
@@ -117,39 +362,81 @@ peg::parser! {
         rule program_outputs() -> Vec<Loc<ast::VariableDef>>
         = [tok!(TK::Output)] vars:variable_def()* { vars }
 
+        rule program_workgroup() -> Vec<Loc<ast::VariableDef>>
+        = [tok!(TK::Workgroup)] vars:variable_def()* { vars }
+
+        //
+        // Space
+        //
+        rule space() -> Loc<ast::SpaceDefinition>
+        =
+            pub_:visibility(true) [tok!(TK::Space, space)] name:identifier()
+            parent:(
+                [tok!(TK::Colon)] contextual("parent") parent:path()
+                contextual("via") via:path() { (parent, via) }
+            )?
+            [tok!(TK::SemiColon, end)] {
+                let start = pub_.unwrap_or(space);
+                Loc::new(
+                    start.merge(end),
+                    ast::SpaceDefinition { visibility: visibility(pub_), name, parent },
+                )
+            }
+
         //
         // function
         //
 
         pub rule function() -> Loc<ast::Function>
         =
-            [tok!(TK::Function, start)] name:identifier() [tok!(TK::ParenOpen)]
+            attrs:attribute()* pub_:visibility(true)
+            [tok!(TK::Function, function)] name:identifier() generics:function_generics()?
+            [tok!(TK::ParenOpen)]
                 args:sep_trailing(<function_arg()>, <[tok!(TK::Comma)]>)
             [tok!(TK::ParenClose)] [tok!(TK::Returns)] ret_ty:type_reference()
             [tok!(TK::Begin)]
                 body:block()
             [tok!(TK::End, end)] {
+                let start = attrs.first().map(|l| l.loc).or(pub_).unwrap_or(function);
+                let (arg_attributes, args) = args.into_iter().unzip();
                 Loc::new(
                     start.merge(end),
                     ast::Function {
+                        attributes: attrs,
+                        visibility: visibility(pub_),
                         name,
+                        generics: generics.unwrap_or_default(),
                         args,
+                        arg_attributes,
                         ret_type: ret_ty,
                         body,
                     }
                 )
             }
 
-        rule function_arg() -> (Loc<ast::Identifier>, Loc<ast::TypeReference>)
-        = name:identifier() [tok!(TK::Colon)] ty:type_reference() {
-            (name, ty)
-        }
+        rule function_generics() -> Vec<Loc<ast::Identifier>>
+        =
+            [tok!(TK::LessThan)]
+                generics:sep_trailing(<identifier()>, <[tok!(TK::Comma)]>)
+            [tok!(TK::GreaterThan)] { generics }
+
+        rule function_arg() -> FunctionArg
+        =
+            attrs:attribute()* name:identifier() [tok!(TK::Colon)] mode:param_mode()?
+            ty:type_reference() {
+                (attrs, (name, ty, mode.unwrap_or(ast::ParamMode::In)))
+            }
+
+        rule param_mode() -> ast::ParamMode
+        = [tok!(TK::In)] [tok!(TK::Out)] { ast::ParamMode::InOut }
+        / [tok!(TK::In)] { ast::ParamMode::In }
+        / [tok!(TK::Out)] { ast::ParamMode::Out }
 
         //
         // Consts
         //
         rule consts() -> Loc<ast::Consts>
-        = [tok!(TK::Const, start)] vars:variable_def()+ {
+        = [tok!(TK::Const, start)] vars:variable_def_in(true)+ {
             let end = vars.last().map(|l| l.loc).unwrap();
             Loc::new(start.merge(end), ast::Consts { vars })
         }
@@ -169,22 +456,29 @@ peg::parser! {
 
         rule type_definition() -> Loc<ast::TypeDefinition>
         =
-            name:identifier() [tok!(TK::LessThan)]
+            attrs:attribute()* pub_:visibility(true) name:identifier() [tok!(TK::LessThan)]
                 generics:sep_trailing(<identifier()>, <[tok!(TK::Comma)]>)
             [tok!(TK::GreaterThan)] [tok!(TK::Equals)] rhs:type_def_rhs() {
+                let start = attrs.first().map(|l| l.loc).or(pub_).unwrap_or(name.loc);
                 Loc::new(
-                    name.loc.merge(rhs.loc),
+                    start.merge(rhs.loc),
                     ast::TypeDefinition {
+                        attributes: attrs,
+                        visibility: visibility(pub_),
                         name,
                         generics,
                         rhs,
                     }
                 )
             }
-        /   name:identifier() [tok!(TK::Equals)] rhs:type_def_rhs() {
+        /   attrs:attribute()* pub_:visibility(true) name:identifier() [tok!(TK::Equals)]
+            rhs:type_def_rhs() {
+                let start = attrs.first().map(|l| l.loc).or(pub_).unwrap_or(name.loc);
                 Loc::new(
-                    name.loc.merge(rhs.loc),
+                    start.merge(rhs.loc),
                     ast::TypeDefinition {
+                        attributes: attrs,
+                        visibility: visibility(pub_),
                         name,
                         generics: vec![],
                         rhs,
@@ -208,29 +502,41 @@ peg::parser! {
 
 
         rule variable_def() -> Loc<ast::VariableDef>
+        = variable_def_in(false)
+
+        /// A variable, in an item that can be `pub` if `item` is set
+        rule variable_def_in(item: bool) -> Loc<ast::VariableDef>
         =
-            attrs:attribute()* name:identifier() [tok!(TK::Colon)]
+            attrs:attribute()* pub_:visibility(item) name:identifier() [tok!(TK::Colon)]
             type_:type_reference() [tok!(TK::SemiColon, end)] {
-                let start = attrs.first().map(|l| l.loc).unwrap_or(name.loc);
+                let start = attrs.first().map(|l| l.loc).or(pub_).unwrap_or(name.loc);
                 Loc::new(start.merge(end), ast::VariableDef {
                     attributes: attrs,
+                    visibility: visibility(pub_),
                     name,
                     type_,
                     rhs: None,
                 })
             }
-        /   attrs:attribute()* name:identifier() [tok!(TK::Colon)]
+        /   attrs:attribute()* pub_:visibility(item) name:identifier() [tok!(TK::Colon)]
             type_:type_reference()
             [tok!(TK::Becomes)] rhs:expression() [tok!(TK::SemiColon, end)] {
-                let start = attrs.first().map(|l| l.loc).unwrap_or(name.loc);
+                let start = attrs.first().map(|l| l.loc).or(pub_).unwrap_or(name.loc);
                 Loc::new(start.merge(end), ast::VariableDef {
                     attributes: attrs,
+                    visibility: visibility(pub_),
                     name,
                     type_,
                     rhs: Some(rhs),
                 })
             }
 
+        /// The location of `pub`, which only items can be declared
+        rule visibility(item: bool) -> Option<FileLocation>
+        =
+            [tok!(TK::Pub, loc)] {? if item { Ok(Some(loc)) } else { Err("an item") } }
+        /   { None }
+
         rule attribute() -> Loc<ast::Attribute>
         =
             [tok!(TK::BracketOpen, start)] name:identifier() [tok!(TK::BracketClose, end)] {
@@ -247,6 +553,14 @@ peg::parser! {
                     }
                 )
             }
+        /   [tok!(TK::At, start)] name:identifier() [tok!(TK::ParenOpen)]
+                args:arglist()
+            [tok!(TK::ParenClose, end)] {
+                Loc::new(start.merge(end), ast::Attribute { name, args })
+            }
+        /   [tok!(TK::At, start)] name:identifier() {
+                Loc::new(start.merge(name.loc), ast::Attribute { name, args: vec![] })
+            }
 
         //
         // Statement
@@ -276,18 +590,33 @@ peg::parser! {
                     },
                 )
             }
+            // (expr-lhs, expr-lhs) := expr;
+        /   [tok!(TK::ParenOpen, start)] first:expression_atom()
+                rest:([tok!(TK::Comma)] target:expression_atom() { target })+
+                [tok!(TK::Comma)]?
+            [tok!(TK::ParenClose)] [tok!(TK::Becomes)] rhs:expression() [tok!(TK::SemiColon, end)] {
+                let mut targets = vec![first];
+                targets.extend(rest);
+                Loc::new(start.merge(end), ast::Statement::Destructure { targets, rhs })
+            }
         /   [tok!(TK::Return, start)] expr:expression() [tok!(TK::SemiColon, end)] {
                 Loc::new(start.merge(end), ast::Statement::Return(Some(expr)))
             }
         /   [tok!(TK::Return, start)] [tok!(TK::SemiColon, end)] {
                 Loc::new(start.merge(end), ast::Statement::Return(None))
             }
+        /   expr:expression() [tok!(TK::SemiColon, end)] {
+                Loc::new(expr.loc.merge(end), ast::Statement::Expr(expr))
+            }
         /   [tok!(TK::Break, start)] [tok!(TK::SemiColon, end)] {
                 Loc::new(start.merge(end), ast::Statement::Break)
             }
         /   [tok!(TK::Continue, start)] [tok!(TK::SemiColon, end)] {
                 Loc::new(start.merge(end), ast::Statement::Continue)
             }
+        /   [tok!(TK::Discard, start)] [tok!(TK::SemiColon, end)] {
+                Loc::new(start.merge(end), ast::Statement::Discard)
+            }
         /   [tok!(TK::If, start)] cond:expression() [tok!(TK::Then)]
                 tb:block()
             eis:elseif_branch()*
@@ -353,6 +682,12 @@ peg::parser! {
                 )
             }
 
+        /   [tok!(TK::Match, start)] value:expression()
+                arms:match_arm()*
+            [tok!(TK::End, end)] {
+                Loc::new(start.merge(end), ast::Statement::Match { value, arms })
+            }
+
         rule elseif_branch() -> (Loc<ast::Expression>, ast::Block)
         =
             [tok!(TK::ElseIf)] cond:expression() [tok!(TK::Then, start)]
@@ -361,6 +696,26 @@ peg::parser! {
                 (cond, b)
             }
 
+        rule match_arm() -> (Loc<ast::MatchPattern>, ast::Block)
+        =
+            pattern:match_pattern() [tok!(TK::Then)] body:block() { (pattern, body) }
+
+        rule match_pattern() -> Loc<ast::MatchPattern>
+        =
+            word:identifier() [tok!(TK::ParenOpen)] name:identifier()
+            [tok!(TK::ParenClose, end)] {?
+                match word.value.as_str() {
+                    "some" => Ok(Loc::new(word.loc.merge(end), ast::MatchPattern::Some(name))),
+                    _ => Err("`some`"),
+                }
+            }
+        /   word:identifier() {?
+                match word.value.as_str() {
+                    "none" => Ok(Loc::new(word.loc, ast::MatchPattern::None)),
+                    _ => Err("`none`"),
+                }
+            }
+
         rule block() -> ast::Block
         = statement()*
 
@@ -457,6 +812,15 @@ peg::parser! {
         }
 
         rule expression_atom() -> Loc<ast::Expression> = precedence!{
+            base:@ [tok!(TK::BracketOpen)] lo:expression() [tok!(TK::DotDot)]
+            hi:expression() [tok!(TK::BracketClose, loc)]
+            {
+                Loc::new(base.loc.merge(loc), ast::Expression::Slice {
+                    base: Box::new(base),
+                    lo: Box::new(lo),
+                    hi: Box::new(hi),
+                })
+            }
             base:@ [tok!(TK::BracketOpen)] idx:expression() [tok!(TK::BracketClose, loc)]
             {
                 Loc::new(base.loc.merge(loc), ast::Expression::Index {
@@ -466,19 +830,21 @@ peg::parser! {
             }
             --
             base:@ [tok!(TK::Dot)] name:identifier()
-            [tok!(TK::ParenOpen)] args:arglist() [tok!(TK::ParenClose, loc)]
+            [tok!(TK::ParenOpen, open)] args:arglist() [tok!(TK::ParenClose, loc)]
             {
                 Loc::new(base.loc.merge(loc), ast::Expression::DotCall {
                     base: Box::new(base),
                     name,
                     args,
+                    args_loc: open.merge(loc),
                 })
             }
             --
-            base:@ [tok!(TK::ParenOpen)] args:arglist() [tok!(TK::ParenClose, loc)] {
+            base:@ [tok!(TK::ParenOpen, open)] args:arglist() [tok!(TK::ParenClose, loc)] {
                 Loc::new(base.loc.merge(loc), ast::Expression::Call {
                     base: Box::new(base),
                     args,
+                    args_loc: open.merge(loc),
                 })
             }
             --
@@ -489,14 +855,21 @@ peg::parser! {
                 )
             }
             --
-            ident:identifier() {
+            query:layout_query() { query }
+            ident:path() {
                 Loc::new(ident.loc, ast::Expression::Variable(ident.value))
             }
             l:literal() {
                 Loc::new(l.loc, ast::Expression::Literal(l.value))
             }
-            [tok!(TK::ParenOpen)] inner:expression() [tok!(TK::ParenClose)] {
-                inner
+            [tok!(TK::ParenOpen, open)] inner:expression() [tok!(TK::ParenClose, close)] {
+                Loc::new(open.merge(close), ast::Expression::Parenthesized(Box::new(inner)))
+            }
+            [tok!(TK::BracketOpen, start)]
+            elements:sep_trailing(<expression()>, <[tok!(TK::Comma)]>)
+            [tok!(TK::BracketClose, end)]
+            {
+                Loc::new(start.merge(end), ast::Expression::Array(elements))
             }
             prim:type_primitive() {
                 Loc::new(
@@ -506,6 +879,17 @@ peg::parser! {
             }
         }
 
+        rule layout_query() -> Loc<ast::Expression>
+        =
+            word:identifier() [tok!(TK::ParenOpen)] ty:type_reference()
+                args:([tok!(TK::Comma)] arg:identifier() { arg })*
+            [tok!(TK::ParenClose, loc)] {?
+                match layout_query_args(&word.value, ty, args) {
+                    Some(query) => Ok(Loc::new(word.loc.merge(loc), query)),
+                    None => Err("layout query"),
+                }
+            }
+
         rule call_arg() -> (Option<Loc<ast::Identifier>>, Loc<ast::Expression>)
         =
             ident:identifier()
@@ -532,14 +916,13 @@ peg::parser! {
         /   prim:type_primitive() {
                 Loc::new(prim.loc, ast::TypeReference::Primitive(prim))
             }
-        /   [tok!(TK::Array, al)] [tok!(TK::BracketOpen)]
-                [tok!(TK::Integer(size), sl)]
+        /   [tok!(TK::Array, al)] [tok!(TK::BracketOpen)] size:expression()
             [tok!(TK::BracketClose)] [tok!(TK::Of)] ty:type_reference() {
                 Loc::new(
                     al.merge(ty.loc),
                     ast::TypeReference::Array {
                         base: Box::new(ty),
-                        size: Loc::new(sl, size as usize),
+                        size: Box::new(size),
                     },
                 )
             }
@@ -551,7 +934,65 @@ peg::parser! {
                     },
                 )
             }
-        /   name:identifier() [tok!(TK::LessThan)]
+        /   [tok!(TK::Record, start)] [tok!(TK::BraceOpen)]
+                fields:sep_trailing(<record_field()>, <[tok!(TK::Comma)]>)
+            [tok!(TK::BraceClose, end)] {
+                Loc::new(start.merge(end), ast::TypeReference::Record { fields })
+            }
+        /   name:identifier() [tok!(TK::LessThan)] ty:type_reference()
+            [tok!(TK::GreaterThan, end)] {?
+                let loc = name.loc.merge(end);
+                if name.value == "normalized" {
+                    Ok(Loc::new(
+                        loc,
+                        ast::TypeReference::Normalized {
+                            base: Box::new(ty),
+                        },
+                    ))
+                } else if name.value == "optional" {
+                    Ok(Loc::new(
+                        loc,
+                        ast::TypeReference::Optional {
+                            base: Box::new(ty),
+                        },
+                    ))
+                } else if let Some(dim) = ast::TextureDim::from_type_name(&name.value) {
+                    Ok(Loc::new(
+                        loc,
+                        ast::TypeReference::Texture {
+                            dim,
+                            sampled: Some(Box::new(ty)),
+                        },
+                    ))
+                } else {
+                    Err("type")
+                }
+            }
+        /   name:identifier() [tok!(TK::LessThan)] format:identifier() [tok!(TK::Comma)]
+            access:identifier() [tok!(TK::GreaterThan, end)] {?
+                match ast::ImageDim::from_type_name(&name.value) {
+                    Some(dim) => Ok(Loc::new(
+                        name.loc.merge(end),
+                        ast::TypeReference::Image {
+                            dim,
+                            format,
+                            access,
+                        },
+                    )),
+                    None => Err("type"),
+                }
+            }
+        /   name:identifier() {?
+                let ty = match name.value.as_str() {
+                    "sampler" => Some(ast::TypeReference::Sampler { comparison: false }),
+                    "sampler_comparison" => Some(ast::TypeReference::Sampler { comparison: true }),
+                    "acceleration_structure" => Some(ast::TypeReference::AccelerationStructure),
+                    other => ast::TextureDim::from_depth_type_name(other)
+                        .map(|dim| ast::TypeReference::Texture { dim, sampled: None }),
+                };
+                ty.map(|ty| Loc::new(name.loc, ty)).ok_or("type")
+            }
+        /   name:path() [tok!(TK::LessThan)]
                 gens:sep_trailing(<type_reference()>, <[tok!(TK::Comma)]>)
             [tok!(TK::GreaterThan, end)] {
                 Loc::new(
@@ -562,7 +1003,7 @@ peg::parser! {
                     }
                 )
             }
-        /   name:identifier() {
+        /   name:path() {
                 Loc::new(
                     name.loc,
                     ast::TypeReference::Named {
@@ -572,13 +1013,27 @@ peg::parser! {
                 )
             }
 
+        rule record_field() -> (Loc<ast::Identifier>, Loc<ast::TypeReference>)
+        = name:identifier() [tok!(TK::Colon)] ty:type_reference() { (name, ty) }
+
         rule type_primitive() -> Loc<ast::PrimitiveType>
         =
             [tok!(TK::TyBool, loc)] { Loc::new(loc, ast::PrimitiveType::Bool) }
         /   [tok!(TK::TyInt, loc)] { Loc::new(loc, ast::PrimitiveType::Int) }
         /   [tok!(TK::TyUInt, loc)] { Loc::new(loc, ast::PrimitiveType::UInt) }
+        /   [tok!(TK::TyLong, loc)] { Loc::new(loc, ast::PrimitiveType::Long) }
+        /   [tok!(TK::TyULong, loc)] { Loc::new(loc, ast::PrimitiveType::ULong) }
         /   [tok!(TK::TyFloat, loc)] { Loc::new(loc, ast::PrimitiveType::Float) }
         /   [tok!(TK::TyDouble, loc)] { Loc::new(loc, ast::PrimitiveType::Double) }
+        /   [tok!(TK::TyHalf, loc)] { Loc::new(loc, ast::PrimitiveType::Half) }
+        /   [tok!(TK::TyRadians, loc)] { Loc::new(loc, ast::PrimitiveType::Radians) }
+        /   [tok!(TK::TyDegrees, loc)] { Loc::new(loc, ast::PrimitiveType::Degrees) }
+        /   [tok!(TK::TyAtomic, start)] [tok!(TK::LessThan)] [tok!(TK::TyInt)] [tok!(TK::GreaterThan, end)] {
+                Loc::new(start.merge(end), ast::PrimitiveType::AtomicInt)
+            }
+        /   [tok!(TK::TyAtomic, start)] [tok!(TK::LessThan)] [tok!(TK::TyUInt)] [tok!(TK::GreaterThan, end)] {
+                Loc::new(start.merge(end), ast::PrimitiveType::AtomicUInt)
+            }
 
         /   [tok!(TK::TyBoolVec(n), loc)] { Loc::new(loc, ast::PrimitiveType::BoolVec { components: n }) }
 
@@ -598,6 +1053,22 @@ peg::parser! {
                     space: annot.1,
                 })
             }
+        /   [tok!(TK::TyLongVec(n), loc)] annot:type_prim_vec_annot()? {
+                let annot = annot.unwrap_or((None, None));
+                Loc::new(loc, ast::PrimitiveType::LongVec {
+                    components: n,
+                    vtype: annot.0,
+                    space: annot.1,
+                })
+            }
+        /   [tok!(TK::TyULongVec(n), loc)] annot:type_prim_vec_annot()? {
+                let annot = annot.unwrap_or((None, None));
+                Loc::new(loc, ast::PrimitiveType::ULongVec {
+                    components: n,
+                    vtype: annot.0,
+                    space: annot.1,
+                })
+            }
         /   [tok!(TK::TyFloatVec(n), loc)] annot:type_prim_vec_annot()? {
                 let annot = annot.unwrap_or((None, None));
                 Loc::new(loc, ast::PrimitiveType::FloatVec {
@@ -614,6 +1085,14 @@ peg::parser! {
                     space: annot.1,
                 })
             }
+        /   [tok!(TK::TyHalfVec(n), loc)] annot:type_prim_vec_annot()? {
+                let annot = annot.unwrap_or((None, None));
+                Loc::new(loc, ast::PrimitiveType::HalfVec {
+                    components: n,
+                    vtype: annot.0,
+                    space: annot.1,
+                })
+            }
         /   [tok!(TK::TyFloatMat((col, row)), loc)] annot:type_prim_mat_annot()? {
                 Loc::new(loc, ast::PrimitiveType::FloatMat {
                     cols: col,
@@ -629,20 +1108,21 @@ peg::parser! {
                     transform: annot,
                 })
             }
+        /   [tok!(TK::TyPacked(format), loc)] { Loc::new(loc, ast::PrimitiveType::Packed { format }) }
 
         rule type_prim_vec_annot() -> (Option<Loc<ast::VecType>>, Option<Loc<ast::Identifier>>)
-        =   [tok!(TK::Is)] ty:type_vec_type() [tok!(TK::In)] space:identifier() {
+        =   [tok!(TK::Is)] ty:type_vec_type() [tok!(TK::In)] space:path() {
                 (Some(ty), Some(space))
             }
         /   [tok!(TK::Is)] ty:type_vec_type() {
                 (Some(ty), None)
             }
-        /   [tok!(TK::In)] space:identifier() {
+        /   [tok!(TK::In)] space:path() {
                 (None, Some(space))
             }
 
         rule type_prim_mat_annot() -> (Loc<ast::Identifier>, Loc<ast::Identifier>)
-        = [tok!(TK::From)] f:identifier() [tok!(TK::To)] t:identifier() {
+        = [tok!(TK::From)] f:path() [tok!(TK::To)] t:path() {
             (f, t)
         }
 
@@ -651,23 +1131,48 @@ peg::parser! {
         = [tok!(TK::Point, loc)] { Loc::new(loc, ast::VecType::Point) }
         / [tok!(TK::Vector, loc)] { Loc::new(loc, ast::VecType::Vector) }
         / [tok!(TK::Colour, loc)] { Loc::new(loc, ast::VecType::Colour) }
+        / [tok!(TK::SrgbColour, loc)] { Loc::new(loc, ast::VecType::SrgbColour) }
 
         //
         // Terminals
         //
 
         rule literal() -> Loc<ast::Literal>
-        = [tok!(TK::Integer(x), loc)]
-        {
-            Loc::new(loc, ast::Literal::Integer(x as i128))
-        } /
-        [tok!(TK::Float(x), loc)]
-        {
-            Loc::new(loc, ast::Literal::Float(x))
-        }
+        = quiet!{
+            [tok!(TK::Integer((x, suffix)), loc)]
+            {
+                let x = x.and_then(|x| i128::try_from(x).ok());
+                Loc::new(loc, ast::Literal::Integer(x, suffix))
+            } /
+            [tok!(TK::Float((x, suffix)), loc)]
+            {
+                Loc::new(loc, ast::Literal::Float(x, suffix))
+            } /
+            [tok!(TK::True, loc)] { Loc::new(loc, ast::Literal::Bool(true)) } /
+            [tok!(TK::False, loc)] { Loc::new(loc, ast::Literal::Bool(false)) } /
+            [tok!(TK::String(s), loc)] { Loc::new(loc, ast::Literal::String(s)) }
+        } / expected!("literal")
 
         rule identifier() -> Loc<ast::Identifier>
-        = [tok!(TK::Identifier(i), loc)] { Loc::new(loc, i) }
+        = quiet!{
+            [tok!(TK::Identifier(i), loc)] { Loc::new(loc, i) } /
+            [tok!(TK::RawIdentifier(i), loc)] { Loc::new(loc, i) }
+        }
+        / expected!("identifier")
+
+        // a name, qualified with the modules it is declared in like
+        // `geometry::Ray`
+        rule path() -> Loc<ast::Identifier>
+        = first:identifier() rest:([tok!(TK::PathSep)] i:identifier() { i })* {
+            rest.into_iter().fold(first, |path, name| {
+                Loc::new(path.loc.merge(name.loc), format!("{}::{}", path.value, name.value))
+            })
+        }
+
+        // an identifier that is a keyword in one place only, like `via`, but
+        // not when written as a raw identifier
+        rule contextual(word: &'static str)
+        = [tok!(TK::Identifier(i))] {? if i == word { Ok(()) } else { Err(word) } }
 
         //
         // Utils
@@ -767,6 +1272,25 @@ mod tests {
         assert!(printed.contains("InfixOp"));
     }
 
+    #[test]
+    fn expr_slice() {
+        let ast = check_expr_parses("x[0..n - 1][2]");
+        let printed = format!("{:?}", ast);
+
+        assert!(printed.contains("Slice"));
+        assert!(printed.contains("Index"));
+    }
+
+    #[test]
+    fn expr_array() {
+        let ast = check_expr_parses("[1, f(2), [3]][0]");
+        let printed = format!("{:?}", ast);
+
+        assert!(printed.contains("Index"));
+        assert!(printed.matches("Array").count() == 2);
+        check_expr_parses("[1, 2,]");
+    }
+
     #[test]
     fn expr_cast() {
         let ast = check_expr_parses("x as float4 is Point");
@@ -846,6 +1370,45 @@ mod tests {
         assert!(printed.contains("12"));
     }
 
+    #[test]
+    fn test_stmt_destructure() {
+        let s = check_statement_parses("(lo, hi.x, ) := bounds(xs);");
+        match s.value {
+            ast::Statement::Destructure { targets, .. } => assert_eq!(targets.len(), 2),
+            _ => panic!("expected a destructuring assignment"),
+        }
+
+        // one target in parentheses is a plain assignment
+        let s = check_statement_parses("(x) := 12;");
+        assert!(matches!(s.value, ast::Statement::Becomes { .. }));
+    }
+
+    #[test]
+    fn test_stmt_match() {
+        let s = check_statement_parses(
+            r#"
+        match intersect(ray, sphere)
+        some(hit) then
+            colour := hit.colour;
+        none then
+        end
+        "#,
+        );
+        match s.value {
+            ast::Statement::Match { arms, .. } => {
+                assert!(
+                    matches!(&arms[0].0.value, ast::MatchPattern::Some(name) if name.value == "hit")
+                );
+                assert_eq!(arms[0].1.len(), 1);
+                assert!(matches!(arms[1].0.value, ast::MatchPattern::None));
+                assert!(arms[1].1.is_empty());
+            }
+            _ => panic!("expected a match"),
+        }
+
+        check_statement_parses("match hit end");
+    }
+
     #[test]
     fn test_stmt_var_no_rhs() {
         let s = check_statement_parses("var x: int;");
@@ -951,6 +1514,409 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generic_function() {
+        check_file_parses(
+            r#"
+        function first<T, U>(a: T, b: U) returns T
+        begin
+            return a;
+        end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_field_attributes() {
+        check_file_parses(
+            r#"
+        type
+            Light = record
+                @align(16)
+                colour: float3;
+                @offset(32)
+                intensity: float;
+            end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_declaration_attributes() {
+        check_file_parses(
+            r#"
+        type
+            @opaque
+            Handle = distinct uint;
+            [Packed] Pair<T> = record
+                first: T;
+                second: T;
+            end
+
+        @inline
+        function blend(@relaxed a: float, [Unit] b: in out float) returns float
+        begin
+            return a * b;
+        end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_half_types() {
+        check_file_parses(
+            r#"
+        type
+            Surface = record
+                albedo: half3 is Colour;
+                roughness: half;
+                normal: half3 is Vector in TangentSpace;
+            end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_long_types() {
+        check_file_parses(
+            r#"
+        type
+            Stamp = record
+                ticks: ulong;
+                offsets: long3 is Vector;
+            end
+        const
+            EPOCH: ulong := 1_700_000_000ul;
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_atomic_types() {
+        check_file_parses(
+            r#"
+        type
+            Counters = record
+                hits: atomic<uint>;
+                balance: atomic<int>;
+            end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_compute_program() {
+        check_file_parses(
+            r#"
+        @compute
+        program reduce
+        input
+            index: uint;
+        workgroup
+            partial: array[64] of float;
+        begin
+            partial[index] := 0.0;
+            workgroupBarrier();
+        end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_spaces() {
+        let file = check_file_parses(
+            r#"
+        space Model;
+        space World: parent Model via model_to_world;
+        "#,
+        );
+        match &file.items[1] {
+            ast::Item::Space(space) => {
+                let (parent, via) = space.value.parent.as_ref().unwrap();
+                assert_eq!(space.value.name.value, "World");
+                assert_eq!(parent.value, "Model");
+                assert_eq!(via.value, "model_to_world");
+            }
+            _ => panic!("expected a space"),
+        }
+    }
+
+    #[test]
+    fn test_raw_identifiers() {
+        let file = check_file_parses(
+            r#"
+        function r#type(r#in: int) returns int
+        begin
+            return r#in;
+        end
+        "#,
+        );
+        match &file.items[0] {
+            ast::Item::Function(func) => {
+                assert_eq!(func.value.name.value, "type");
+                assert_eq!(func.value.args[0].0.value, "in");
+            }
+            _ => panic!("expected a function"),
+        }
+
+        let toks =
+            tokenise(0, "space World: parent Model r#via model_to_world;").collect::<Vec<_>>();
+        assert!(parser::file(&toks[..]).is_err());
+    }
+
+    #[test]
+    fn test_static_asserts() {
+        let file = check_file_parses(
+            r#"
+        const SIZE: int := 4;
+        static_assert(SIZE > 0, "SIZE must be positive");
+        "#,
+        );
+        match &file.items[1] {
+            ast::Item::StaticAssert(assert) => {
+                assert_eq!(assert.value.message.value, "SIZE must be positive");
+            }
+            _ => panic!("expected a static assertion"),
+        }
+    }
+
+    #[test]
+    fn test_layout_queries() {
+        let expr = parse_expression(0, "offsetof(Light, colour, std140)").unwrap();
+        match expr.value {
+            ast::Expression::LayoutQuery {
+                query: ast::LayoutQuery::Offset(field),
+                rules: Some(rules),
+                ..
+            } => {
+                assert_eq!(field.value, "colour");
+                assert_eq!(rules.value, "std140");
+            }
+            other => panic!("expected a layout query, found {:?}", other),
+        }
+        // functions with the same names are called as usual
+        let expr = parse_expression(0, "sizeof(x + 1)").unwrap();
+        assert!(matches!(expr.value, ast::Expression::Call { .. }));
+
+        check_file_parses("type Words = record words: array[sizeof(Light) / 4u] of uint; end");
+    }
+
+    #[test]
+    fn test_modules() {
+        let file = check_file_parses(
+            r#"
+        module geometry
+            type Ray = record origin: float3; end
+            function hit(r: Ray) returns bool begin return true; end
+        end
+        const R: geometry::Ray;
+        "#,
+        );
+        match &file.items[0] {
+            ast::Item::Module(module) => {
+                assert_eq!(module.value.name.value, "geometry");
+                assert_eq!(module.value.items.len(), 2);
+            }
+            _ => panic!("expected a module"),
+        }
+        match &file.items[1] {
+            ast::Item::Consts(consts) => match &consts.value.vars[0].value.type_.value {
+                ast::TypeReference::Named { name, .. } => assert_eq!(name.value, "geometry::Ray"),
+                _ => panic!("expected a named type"),
+            },
+            _ => panic!("expected constants"),
+        }
+    }
+
+    #[test]
+    fn test_visibility() {
+        let file = check_file_parses(
+            r#"
+        module geometry
+            type pub Ray = record origin: float3; end
+            const [Storage(set: 0, binding: 0)] pub RAYS: array of Ray;
+            pub space World;
+            pub function hit(r: Ray) returns bool begin return true; end
+            function miss(r: Ray) returns bool begin return false; end
+        end
+        "#,
+        );
+        let items = match &file.items[0] {
+            ast::Item::Module(module) => &module.value.items,
+            _ => panic!("expected a module"),
+        };
+        let visibilities = items
+            .iter()
+            .map(|item| match item {
+                ast::Item::Types(tys) => tys.value.types[0].value.visibility,
+                ast::Item::Consts(consts) => consts.value.vars[0].value.visibility,
+                ast::Item::Space(space) => space.value.visibility,
+                ast::Item::Function(f) => f.value.visibility,
+                _ => panic!("unexpected item"),
+            })
+            .collect::<Vec<_>>();
+        use ast::Visibility::*;
+        assert_eq!(visibilities, [Public, Public, Public, Public, Private]);
+
+        let toks =
+            tokenise(0, "function f() returns int begin var pub x: int; end").collect::<Vec<_>>();
+        assert!(parser::file(&toks[..]).is_err());
+    }
+
+    #[test]
+    fn test_param_modes() {
+        let file = check_file_parses(
+            r#"
+        function split(v: in float2, x: out float, y: in out float, z: float) returns int
+        begin
+            x := v.x;
+            y := y + v.y;
+            return 0;
+        end
+        "#,
+        );
+        match &file.items[0] {
+            ast::Item::Function(f) => {
+                let modes = f.value.args.iter().map(|(_, _, m)| *m).collect::<Vec<_>>();
+                assert_eq!(
+                    modes,
+                    [
+                        ast::ParamMode::In,
+                        ast::ParamMode::Out,
+                        ast::ParamMode::InOut,
+                        ast::ParamMode::In,
+                    ]
+                );
+            }
+            _ => panic!("expected a function"),
+        }
+    }
+
+    #[test]
+    fn test_packed_types() {
+        check_file_parses(
+            r#"
+        program main
+        input
+            position: float3;
+            colour: unorm8x4;
+            normal: rgb10a2;
+            uv: float16x2;
+        begin
+        end
+        "#,
+        );
+    }
+
+    #[test]
+    fn test_normalized_types() {
+        let file = check_file_parses("const UP: normalized<float3 is Vector>;");
+        match &file.items[0] {
+            ast::Item::Consts(consts) => assert!(matches!(
+                consts.value.vars[0].value.type_.value,
+                ast::TypeReference::Normalized { .. }
+            )),
+            _ => panic!("expected constants"),
+        }
+
+        // other generic types are still named types
+        check_file_parses("const B: Box<normalized<half3>>;");
+    }
+
+    #[test]
+    fn test_optional_types() {
+        let file = check_file_parses("const HIT: optional<array[2] of float>;");
+        match &file.items[0] {
+            ast::Item::Consts(consts) => assert!(matches!(
+                consts.value.vars[0].value.type_.value,
+                ast::TypeReference::Optional { .. }
+            )),
+            _ => panic!("expected constants"),
+        }
+    }
+
+    #[test]
+    fn test_record_types() {
+        let file = check_file_parses(
+            "function bounds(xs: array of float) returns record { lo: float, hi: float, } begin end",
+        );
+        match &file.items[0] {
+            ast::Item::Function(func) => match &func.value.ret_type.value {
+                ast::TypeReference::Record { fields } => {
+                    let names = fields.iter().map(|(name, _)| name.value.as_str());
+                    assert_eq!(names.collect::<Vec<_>>(), ["lo", "hi"]);
+                }
+                _ => panic!("expected a record type"),
+            },
+            _ => panic!("expected a function"),
+        }
+
+        check_file_parses(
+            "const EMPTY: record {}; const NESTED: array[2] of record { r: record { x: int } };",
+        );
+    }
+
+    #[test]
+    fn test_image_types() {
+        let file = check_file_parses("const OUTPUT: image2d_array<rgba16float, read_write>;");
+        match &file.items[0] {
+            ast::Item::Consts(consts) => match &consts.value.vars[0].value.type_.value {
+                ast::TypeReference::Image {
+                    dim,
+                    format,
+                    access,
+                } => {
+                    assert_eq!(*dim, ast::ImageDim::D2Array);
+                    assert_eq!(format.value, "rgba16float");
+                    assert_eq!(access.value, "read_write");
+                }
+                _ => panic!("expected an image type"),
+            },
+            _ => panic!("expected constants"),
+        }
+
+        // other types with two generic arguments are still named types
+        check_file_parses("const P: Pair<rgba8, read>;");
+    }
+
+    #[test]
+    fn test_texture_types() {
+        let file = check_file_parses(
+            "const A: texture_cube<half>; B: texture_depth2d_array; C: sampler_comparison;",
+        );
+        let types = match &file.items[0] {
+            ast::Item::Consts(consts) => consts
+                .value
+                .vars
+                .iter()
+                .map(|var| var.value.type_.value.clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected constants"),
+        };
+        match &types[..] {
+            [ast::TypeReference::Texture {
+                dim: ast::TextureDim::Cube,
+                sampled: Some(_),
+            }, ast::TypeReference::Texture {
+                dim: ast::TextureDim::D2Array,
+                sampled: None,
+            }, ast::TypeReference::Sampler { comparison: true }] => {}
+            _ => panic!("expected texture and sampler types, found {:?}", types),
+        }
+
+        // other names are still named types
+        check_file_parses("const S: samplers; T: texture2d_depth;");
+    }
+
+    #[test]
+    fn test_acceleration_structure_type() {
+        let t = check_type_parses("acceleration_structure");
+        assert!(matches!(t.value, ast::TypeReference::AccelerationStructure));
+
+        let t = check_type_parses("acceleration_structures");
+        assert!(matches!(t.value, ast::TypeReference::Named { .. }));
+    }
+
     #[test]
     fn test_const_decl() {
         check_file_parses("const TEST: float3 := float3(1, 1, 1);");
@@ -1039,4 +2005,30 @@ end
 
         check_file_parses(src);
     }
+
+    #[test]
+    fn structured_errors() {
+        let src = "const A: int := ;";
+        let err = parse_file(0, src).unwrap_err();
+        let semicolon = src.find(';').unwrap();
+        match &err {
+            ParseError::UnexpectedToken {
+                location, found, ..
+            } => {
+                assert_eq!(location.start, semicolon);
+                assert_eq!(*found, TK::SemiColon);
+            }
+            _ => panic!("expected an unexpected token, got {:?}", err),
+        }
+        assert!(err
+            .expected()
+            .contains(&Expected::Production("identifier".to_string())));
+        assert!(err
+            .expected()
+            .contains(&Expected::Token("ParenOpen".to_string())));
+
+        let err = parse_file(0, "const A: int").unwrap_err();
+        assert!(matches!(err, ParseError::UnexpectedEof { .. }));
+        assert_eq!(err.location().start, 12);
+    }
 }
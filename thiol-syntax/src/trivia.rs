@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Whitespace and comments, which the parser skips.
+//!
+//! Tools that rewrite source code need them back to keep what the user
+//! wrote. Every piece of trivia belongs to a token: a comment on the same
+//! line after a token is *trailing* trivia of that token, everything else is
+//! *leading* trivia of the token after it. A node of the AST owns the leading
+//! trivia of its first token and the trailing trivia of its last one, see
+//! [`Trivia::leading`] and [`Trivia::trailing`].
+
+use std::collections::BTreeMap;
+
+use crate::{lexer, FileId, FileLocation, Loc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// a `//` comment, without the line break after it
+    Comment,
+}
+
+pub type TriviaPiece = Loc<TriviaKind>;
+
+/// The trivia of a file, attached to the tokens around it
+#[derive(Debug, Clone, Default)]
+pub struct Trivia {
+    /// by the start of the token after the trivia, the end of the file for
+    /// the trivia after the last token
+    leading: BTreeMap<usize, Vec<TriviaPiece>>,
+    /// by the end of the token before the comment
+    trailing: BTreeMap<usize, TriviaPiece>,
+}
+
+impl Trivia {
+    pub fn new(file: FileId, input: &str) -> Self {
+        let mut trivia = Trivia::default();
+        let mut pending = vec![];
+        let mut last_token_end = None;
+        let mut pos = 0;
+
+        let tokens = lexer::tokenise(file, input).map(|tok| tok.loc);
+        let end = FileLocation {
+            file,
+            start: input.len(),
+            end: input.len(),
+        };
+        for loc in tokens.chain(Some(end)) {
+            for piece in pieces(file, input, pos, loc.start) {
+                let same_line = !input[pos..piece.loc.start].contains('\n');
+                match (piece.value, last_token_end) {
+                    (TriviaKind::Comment, Some(token_end)) if same_line && pending.len() <= 1 => {
+                        trivia.trailing.insert(token_end, piece);
+                        pending.clear();
+                    }
+                    _ => pending.push(piece),
+                }
+            }
+            if !pending.is_empty() {
+                trivia
+                    .leading
+                    .insert(loc.start, std::mem::take(&mut pending));
+            }
+            last_token_end = Some(loc.end);
+            pos = loc.end;
+        }
+        trivia
+    }
+
+    /// The whitespace and comments in front of a node.
+    pub fn leading(&self, node: FileLocation) -> &[TriviaPiece] {
+        self.leading
+            .get(&node.start)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The comment on the same line after a node.
+    pub fn trailing(&self, node: FileLocation) -> Option<&TriviaPiece> {
+        self.trailing.get(&node.end)
+    }
+
+    /// All comments of the file, in source order.
+    pub fn comments(&self) -> Vec<TriviaPiece> {
+        let leading = self.leading.values().flatten();
+        let mut comments = leading
+            .chain(self.trailing.values())
+            .filter(|piece| piece.value == TriviaKind::Comment)
+            .cloned()
+            .collect::<Vec<_>>();
+        comments.sort_by_key(|piece| piece.loc.start);
+        comments
+    }
+}
+
+/// Split the text between two tokens into whitespace and comments.
+pub(crate) fn pieces(file: FileId, input: &str, start: usize, end: usize) -> Vec<TriviaPiece> {
+    let mut pieces = vec![];
+    let mut pos = start;
+    while pos < end {
+        let rest = &input[pos..end];
+        let (kind, len) = if rest.starts_with("//") {
+            (TriviaKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else {
+            let len = rest.find("//").unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        };
+        let loc = FileLocation {
+            file,
+            start: pos,
+            end: pos + len,
+        };
+        pieces.push(Loc::new(loc, kind));
+        pos += len;
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_and_trailing_comments() {
+        let input = "// the scale\nconst S: float := 2.0; // doubled\n\n// end\n";
+        let trivia = Trivia::new(0, input);
+        let text = |piece: &TriviaPiece| &input[piece.loc.range()];
+
+        let start = input.find("const").unwrap();
+        let leading = trivia.leading(FileLocation {
+            file: 0,
+            start,
+            end: start + 5,
+        });
+        assert_eq!(
+            leading.iter().map(text).collect::<Vec<_>>(),
+            ["// the scale", "\n"]
+        );
+
+        let semicolon = input.find(';').unwrap();
+        let trailing = trivia.trailing(FileLocation {
+            file: 0,
+            start,
+            end: semicolon + 1,
+        });
+        assert_eq!(trailing.map(text), Some("// doubled"));
+
+        let comments = trivia.comments();
+        let comments = comments.iter().map(text).collect::<Vec<_>>();
+        assert_eq!(comments, ["// the scale", "// doubled", "// end"]);
+    }
+}
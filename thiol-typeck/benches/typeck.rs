@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use thiol_hir as hir;
+use thiol_typeck::{Context, Type, TypeTable};
+
+#[path = "../../thiol-syntax/benches/generate/mod.rs"]
+mod generate;
+
+/// Numbers of types of the generated modules. Modules with a function for
+/// every type take longer to check, so they are smaller.
+const TYPES: &[usize] = &[100, 1_000, 5_000];
+const MODULES: &[usize] = &[100, 300];
+
+/// Depths of nested arrays.
+const DEPTHS: &[usize] = &[100, 1_000, 5_000];
+
+fn lower(src: &str) -> (hir::Context, hir::Module) {
+    let file = thiol_syntax::parser::parse_file(0, src).unwrap();
+    let mut hir_ctx = hir::Context::default();
+    let module = thiol_ast_lowering::lower(&mut hir_ctx, &file).unwrap();
+    (hir_ctx, module)
+}
+
+/// Check the module once, so that the benchmark doesn't measure an early
+/// return on an error.
+fn checked(src: &str) -> (hir::Context, hir::Module) {
+    let (hir_ctx, module) = lower(src);
+    if let Err(errors) = thiol_typeck::type_check(&mut Context::default(), &hir_ctx, &module) {
+        panic!("the generated module doesn't type check: {:?}", errors);
+    }
+    (hir_ctx, module)
+}
+
+fn bench_type_check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("type_check");
+    group.sample_size(20);
+    for &size in TYPES {
+        // only types, for the resolution of type definitions
+        let (hir_ctx, module) = checked(&generate::types(size, 6));
+        group.bench_function(BenchmarkId::new("types", size), |b| {
+            b.iter_batched(
+                Context::default,
+                |mut ty_ctx| thiol_typeck::type_check(&mut ty_ctx, &hir_ctx, &module),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    for &size in MODULES {
+        let (hir_ctx, module) = checked(&generate::module(size, 6));
+        group.bench_function(BenchmarkId::new("module", size), |b| {
+            b.iter_batched(
+                Context::default,
+                |mut ty_ctx| thiol_typeck::type_check(&mut ty_ctx, &hir_ctx, &module),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Nested arrays `depth` levels deep around every scalar, each level a type
+/// of its own.
+fn nested_arrays(table: &mut TypeTable, depth: usize) {
+    for base in [Type::Float, Type::Int, Type::UInt, Type::Bool] {
+        (1..=depth).fold(table.intern(base), |base, size| {
+            table.intern(Type::Array { base, size })
+        });
+    }
+}
+
+fn bench_interning(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interning");
+    for &depth in DEPTHS {
+        group.bench_function(BenchmarkId::new("new", depth), |b| {
+            b.iter_batched(
+                TypeTable::default,
+                |mut table| {
+                    nested_arrays(&mut table, depth);
+                    table
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        let mut table = TypeTable::default();
+        nested_arrays(&mut table, depth);
+        group.bench_function(BenchmarkId::new("existing", depth), |b| {
+            b.iter(|| nested_arrays(&mut table, depth))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_type_check, bench_interning);
+criterion_main!(benches);
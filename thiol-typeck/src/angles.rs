@@ -0,0 +1,237 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Angles in radians and degrees.
+//!
+//! `radians` and `degrees` are `float`s that hold an angle. Literals are
+//! written with a suffix, like `1.57rad` and `90deg`, or take the unit of the
+//! angle they are used as. An angle in one unit can't be used where the other
+//! one or a plain `float` is expected, and a `float` can't be used as an
+//! angle; values are converted with `to_radians(d)` and `to_degrees(r)`, or
+//! cast with `as`. The trigonometric functions take and give radians.
+//!
+//! Arithmetic keeps the unit where it is meaningful:
+//!
+//! - the sum, difference and remainder of angles in the same unit
+//! - an angle multiplied with or divided by a `float`
+//! - the ratio of two angles in the same unit, which is a `float`
+//!
+//! Other arithmetic on angles gives a value without a unit.
+
+use hir::{Expression, FileLocation, Literal, LiteralSuffix, PrimitiveOp};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::{Context, Error, Type, TypeId};
+
+/// The unit of an angle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+}
+
+impl AngleUnit {
+    /// The name of the type of angles in the unit.
+    pub fn name(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "radians",
+            AngleUnit::Degrees => "degrees",
+        }
+    }
+
+    fn type_(self) -> Type {
+        match self {
+            AngleUnit::Radians => Type::Radians,
+            AngleUnit::Degrees => Type::Degrees,
+        }
+    }
+}
+
+impl Context {
+    /// The unit of an angle type, `None` for all other types.
+    pub fn angle_unit(&self, ty: TypeId) -> Option<AngleUnit> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Radians => Some(AngleUnit::Radians),
+            Type::Degrees => Some(AngleUnit::Degrees),
+            _ => None,
+        }
+    }
+
+    /// The type of angles in the unit.
+    pub fn angle_type(&mut self, unit: AngleUnit) -> TypeId {
+        self.add_or_get_type(unit.type_())
+    }
+
+    /// `Some(None)` for `float`, the unit of an angle, `None` for all other
+    /// types.
+    fn float_unit(&self, ty: TypeId) -> Option<Option<AngleUnit>> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Float => Some(None),
+            _ => self.angle_unit(ty).map(Some),
+        }
+    }
+}
+
+/// A value assigned, passed or returned where an angle or a `float` is
+/// expected, which has to be in the same unit. Angles don't convert to
+/// another unit or to `float` on their own.
+pub(crate) fn check_value(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+    found: TypeId,
+    expected: TypeId,
+) -> Option<Error> {
+    let expected = ty_ctx.float_unit(expected)?;
+    check(ty_ctx, hir_ctx, None, expr, found, expected)
+}
+
+/// An argument of an intrinsic that is an angle in the unit `expected` or a
+/// `float` if `expected` is `None`.
+pub(crate) fn check_argument(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    intrinsic: crate::Intrinsic,
+    arg: Id<Expression>,
+    found: TypeId,
+    expected: Option<AngleUnit>,
+) -> Option<Error> {
+    check(ty_ctx, hir_ctx, Some(intrinsic), arg, found, expected)
+}
+
+/// The argument of a `radians(..)` or `degrees(..)` constructor, which makes
+/// an angle from a `float` but doesn't convert angles.
+pub(crate) fn check_constructor_argument(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    arg: Id<Expression>,
+    found: TypeId,
+    constructed: TypeId,
+) -> Option<Error> {
+    ty_ctx.angle_unit(found)?;
+    check_value(ty_ctx, hir_ctx, arg, found, constructed)
+}
+
+fn check(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    intrinsic: Option<crate::Intrinsic>,
+    expr: Id<Expression>,
+    found: TypeId,
+    expected: Option<AngleUnit>,
+) -> Option<Error> {
+    let found = ty_ctx.float_unit(found)?;
+    if found == expected {
+        return None;
+    }
+    Some(Error::AngleUnitMismatch {
+        expr: hir_ctx.expression_fcs[&expr],
+        found,
+        expected,
+        intrinsic,
+    })
+}
+
+/// The type of the arithmetic operation `op` at `operation` on the operands
+/// `a` and `b`, `None` if the result has no unit.
+pub(crate) fn arithmetic_type(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+    op: &PrimitiveOp,
+    operation: FileLocation,
+    (a, a_ty): (Id<Expression>, TypeId),
+    (b, b_ty): (Id<Expression>, TypeId),
+) -> Result<Option<TypeId>, Error> {
+    use Operand::*;
+
+    let (a_op, b_op) = (
+        operand(ty_ctx, hir_ctx, a, a_ty),
+        operand(ty_ctx, hir_ctx, b, b_ty),
+    );
+    let unit = match (a_op, b_op) {
+        (Angle(a_unit), Angle(b_unit)) if a_unit != b_unit => {
+            let (radians, degrees) = match a_unit {
+                AngleUnit::Radians => (a, b),
+                AngleUnit::Degrees => (b, a),
+            };
+            return Err(Error::MixedAngleUnits {
+                operation,
+                radians: hir_ctx.expression_fcs[&radians],
+                degrees: hir_ctx.expression_fcs[&degrees],
+            });
+        }
+        // a `float` added to an angle has to be one as well
+        (Angle(unit), Float) | (Float, Angle(unit))
+            if matches!(op, PrimitiveOp::Add(..) | PrimitiveOp::Sub(..)) =>
+        {
+            let float = if a_op == Float { a } else { b };
+            return Err(Error::AngleUnitMismatch {
+                expr: hir_ctx.expression_fcs[&float],
+                found: None,
+                expected: Some(unit),
+                intrinsic: None,
+            });
+        }
+        (Angle(unit), Angle(_)) => match op {
+            PrimitiveOp::Add(..) | PrimitiveOp::Sub(..) | PrimitiveOp::Mod(..) => Some(unit),
+            PrimitiveOp::Div(..) => None,
+            _ => return Ok(None),
+        },
+        (Angle(unit), Unitless) | (Unitless, Angle(unit)) => match op {
+            PrimitiveOp::Add(..) | PrimitiveOp::Sub(..) | PrimitiveOp::Mul(..) => Some(unit),
+            PrimitiveOp::Div(..) | PrimitiveOp::Mod(..) if a_op == Angle(unit) => Some(unit),
+            _ => return Ok(None),
+        },
+        (Angle(unit), Float) => match op {
+            PrimitiveOp::Mul(..) | PrimitiveOp::Div(..) => Some(unit),
+            _ => return Ok(None),
+        },
+        (Float, Angle(unit)) => match op {
+            PrimitiveOp::Mul(..) => Some(unit),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let ty = match unit {
+        Some(unit) => unit.type_(),
+        // the ratio of two angles
+        None => Type::Float,
+    };
+    Ok(Some(ty_ctx.add_or_get_type(ty)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Angle(AngleUnit),
+    /// a literal without a unit, which takes the unit of the other operand
+    Unitless,
+    Float,
+    Other,
+}
+
+fn operand(ty_ctx: &Context, hir_ctx: &hir::Context, expr: Id<Expression>, ty: TypeId) -> Operand {
+    if is_unitless_literal(hir_ctx, expr) {
+        return Operand::Unitless;
+    }
+    match ty_ctx.float_unit(ty) {
+        Some(Some(unit)) => Operand::Angle(unit),
+        Some(None) => Operand::Float,
+        None => Operand::Other,
+    }
+}
+
+fn is_unitless_literal(hir_ctx: &hir::Context, expr: Id<Expression>) -> bool {
+    match &hir_ctx.expressions[expr] {
+        Expression::Literal(Literal::Float(_, suffix) | Literal::Integer(_, suffix)) => !matches!(
+            suffix,
+            Some(LiteralSuffix::Radians | LiteralSuffix::Degrees)
+        ),
+        Expression::PrimitiveOp(op) => match &hir_ctx.prim_ops[*op] {
+            PrimitiveOp::Neg(e) | PrimitiveOp::Pos(e) => is_unitless_literal(hir_ctx, *e),
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Atomic types, which can only be stored in memory shared between
+//! invocations: storage buffers and workgroup variables.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use thiol_hir as hir;
+
+use crate::{BufferClass, Context, Error, Symbol, Type, TypeId};
+
+/// Where an atomic type was used outside of shared memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicPlacement {
+    Parameter,
+    ReturnType,
+    Variable,
+    Constant,
+}
+
+impl fmt::Display for AtomicPlacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtomicPlacement::Parameter => write!(f, "function parameter"),
+            AtomicPlacement::ReturnType => write!(f, "return type of"),
+            AtomicPlacement::Variable => write!(f, "variable"),
+            AtomicPlacement::Constant => write!(f, "constant"),
+        }
+    }
+}
+
+impl Context {
+    /// Whether a value of the type contains an atomic.
+    pub fn contains_atomic(&self, ty: TypeId) -> bool {
+        self.types
+            .contains(ty, |ty| matches!(ty, Type::AtomicInt | Type::AtomicUInt))
+    }
+
+    /// The integer type an atomic type holds.
+    pub fn atomic_value_type(&mut self, ty: TypeId) -> Option<TypeId> {
+        let value = match self.types.get(self.strip_distinct(ty))? {
+            Type::AtomicInt => Type::Int,
+            Type::AtomicUInt => Type::UInt,
+            _ => return None,
+        };
+        Some(self.add_or_get_type(value))
+    }
+}
+
+/// Report atomics in parameters, return types, variables other than workgroup
+/// variables and constants that are not bound to a storage buffer.
+///
+/// Constants bound to other kinds of buffers are reported when validating the
+/// buffers.
+pub(crate) fn validate_atomic_placement(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+    let mut check = |name, type_ref, ty, placement| {
+        if ty_ctx.contains_atomic(ty) {
+            errs.push(Error::InvalidAtomicPlacement {
+                name,
+                type_: hir_ctx.type_ref_fcs[&type_ref],
+                placement,
+            });
+        }
+    };
+
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        let sig = match ty_ctx.function_sigs.get(&hir_ctx.identifiers[func.name]) {
+            Some(sig) if sig.func_id == *id => sig,
+            _ => continue,
+        };
+        for ((name, type_ref, _), (_, ty)) in func.args.iter().zip(&sig.args) {
+            let name = hir_ctx.identifiers[*name].clone();
+            check(name, *type_ref, *ty, AtomicPlacement::Parameter);
+        }
+        let name = hir_ctx.identifiers[func.name].clone();
+        check(name, func.ret_type, sig.ret, AtomicPlacement::ReturnType);
+    }
+
+    for id in &module.consts {
+        let def = &hir_ctx.variable_defs[*id];
+        let sig = match ty_ctx.consts.get(&hir_ctx.identifiers[def.name]) {
+            Some(sig) if sig.const_id == *id => sig,
+            _ => continue,
+        };
+        let bound = def.attrs.iter().any(|attr| {
+            BufferClass::from_attribute(&hir_ctx.identifiers[hir_ctx.attributes[*attr].name])
+                .is_some()
+        });
+        if !bound {
+            let name = hir_ctx.identifiers[def.name].clone();
+            check(name, def.type_, sig.type_, AtomicPlacement::Constant);
+        }
+    }
+
+    // atomics in workgroup variables are shared by the workgroup
+    let workgroup = module
+        .programs
+        .iter()
+        .flat_map(|id| &hir_ctx.programs[*id].workgroup)
+        .collect::<HashSet<_>>();
+
+    for (sym, _) in ty_ctx.references.symbols() {
+        let (id, ty) = match (sym, ty_ctx.references.symbol_type(sym)) {
+            (Symbol::Local(id), Some(ty)) if !workgroup.contains(&id) => (id, ty),
+            _ => continue,
+        };
+        let def = &hir_ctx.variable_defs[id];
+        let name = hir_ctx.identifiers[def.name].clone();
+        check(name, def.type_, ty, AtomicPlacement::Variable);
+    }
+
+    errs
+}
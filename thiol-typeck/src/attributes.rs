@@ -0,0 +1,293 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The attributes the compiler knows and the declarations they can be
+//! written on.
+//!
+//! Attributes can be attached to every declaration, but each of them only
+//! means something on some kinds of declarations. An attribute on the wrong
+//! kind of declaration is an error, an attribute nobody knows is only a
+//! warning, as it may be meant for another tool reading the source.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::Attribute;
+use id_arena::Id;
+
+use crate::{suggestions, Context, Error, Symbol, Warning};
+
+/// The kind of declaration an attribute is written on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AttributeTarget {
+    Type,
+    Field,
+    Constant,
+    Local,
+    Function,
+    Parameter,
+    Program,
+    Input,
+    Output,
+    Workgroup,
+}
+
+impl fmt::Display for AttributeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeTarget::Type => write!(f, "type definition"),
+            AttributeTarget::Field => write!(f, "field"),
+            AttributeTarget::Constant => write!(f, "constant"),
+            AttributeTarget::Local => write!(f, "local variable"),
+            AttributeTarget::Function => write!(f, "function"),
+            AttributeTarget::Parameter => write!(f, "parameter"),
+            AttributeTarget::Program => write!(f, "program"),
+            AttributeTarget::Input => write!(f, "program input"),
+            AttributeTarget::Output => write!(f, "program output"),
+            AttributeTarget::Workgroup => write!(f, "workgroup variable"),
+        }
+    }
+}
+
+/// An attribute the compiler gives a meaning to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownAttribute {
+    pub name: &'static str,
+    pub targets: &'static [AttributeTarget],
+}
+
+/// Every attribute the compiler knows.
+pub const KNOWN_ATTRIBUTES: &[KnownAttribute] = {
+    use AttributeTarget::*;
+
+    const fn known(name: &'static str, targets: &'static [AttributeTarget]) -> KnownAttribute {
+        KnownAttribute { name, targets }
+    }
+
+    &[
+        // stages, see `Stage::from_attribute`
+        known("vertex", &[Program]),
+        known("fragment", &[Program]),
+        known("compute", &[Program]),
+        known("ray_generation", &[Program]),
+        known("closest_hit", &[Program]),
+        known("any_hit", &[Program]),
+        known("miss", &[Program]),
+        known("task", &[Program]),
+        known("mesh", &[Program]),
+        known("tessellation_control", &[Program]),
+        known("tessellation_evaluation", &[Program]),
+        known("geometry", &[Program]),
+        // see `tessellation::program_domain` and the functions next to it
+        known("domain", &[Program]),
+        known("partitioning", &[Program]),
+        known("control_points", &[Program]),
+        // see `geometry::input_primitive` and the functions next to it
+        known("input_primitive", &[Program]),
+        known("output_primitive", &[Program]),
+        known("max_vertices", &[Program]),
+        // buffers, see `BufferClass::from_attribute`
+        known("Uniform", &[Constant]),
+        known("Storage", &[Constant]),
+        known("PushConstant", &[Constant]),
+        // explicit layout of buffer fields
+        known("offset", &[Field]),
+        known("align", &[Field]),
+        // see `MatrixLayout::from_attribute`
+        known("row_major", &[Constant, Field]),
+        known("column_major", &[Constant, Field]),
+        known(
+            "relaxed",
+            &[Constant, Local, Field, Input, Output, Workgroup],
+        ),
+        // interface of programs
+        known("Location", &[Input, Output]),
+        known("Position", &[Input, Output]),
+        known("FrontFacing", &[Input]),
+        known("VertexIndex", &[Input]),
+        known("InstanceIndex", &[Input]),
+        known("GlobalInvocationId", &[Input]),
+        known("LocalInvocationId", &[Input]),
+        known("LocalInvocationIndex", &[Input]),
+        known("WorkgroupId", &[Input]),
+        known("SubgroupInvocationId", &[Input]),
+        known("SubgroupSize", &[Input]),
+        known("InvocationId", &[Input]),
+        known("TessCoord", &[Input]),
+        known("TessLevelOuter", &[Output]),
+        known("TessLevelInner", &[Output]),
+        known("PrimitiveId", &[Input, Output]),
+        // see `tessellation::is_patch`
+        known("Patch", &[Input, Output]),
+        // see `ray_tracing::payload_location`
+        known("RayPayload", &[Output]),
+        // see `mesh::output_rate`
+        known("PerVertex", &[Output]),
+        known("PerPrimitive", &[Output]),
+        known("PrimitiveIndices", &[Output]),
+        // see `FloatMode::from_attributes`
+        known("no_nans", &[Function, Program]),
+        known("no_infs", &[Function, Program]),
+        known("reassociate", &[Function, Program]),
+        known("fast_math", &[Function, Program]),
+        // see `derives::derived_names`
+        known("derive", &[Type]),
+        // see `luts::lut_attribute`
+        known("lut", &[Function]),
+        // see `profiling::collect_profile_labels`
+        known("profile", &[Function, Program]),
+        // interpolation of varyings
+        known("perspective", &[Input, Output]),
+        known("linear", &[Input, Output]),
+        known("flat", &[Input, Output]),
+        known("centroid", &[Input, Output]),
+    ]
+};
+
+/// The known attribute with a name.
+pub fn known_attribute(name: &str) -> Option<&'static KnownAttribute> {
+    KNOWN_ATTRIBUTES.iter().find(|attr| attr.name == name)
+}
+
+/// All attributes of the declarations of a module, with the kind of
+/// declaration they are written on.
+pub fn module_attributes(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<(Id<Attribute>, AttributeTarget)> {
+    use AttributeTarget as T;
+
+    let mut attrs = vec![];
+    let mut program_vars = HashSet::new();
+    let mut add = |ids: &[Id<Attribute>], target| {
+        attrs.extend(ids.iter().map(|id| (*id, target)));
+    };
+
+    for id in &module.types {
+        let def = &hir_ctx.type_defs[*id];
+        add(&def.attrs, T::Type);
+        if let hir::TypeDefinitionRhs::Record { fields } = &hir_ctx.type_def_rhss[def.rhs] {
+            for field in fields {
+                add(&hir_ctx.variable_defs[*field].attrs, T::Field);
+            }
+        }
+    }
+    for id in &module.consts {
+        add(&hir_ctx.variable_defs[*id].attrs, T::Constant);
+    }
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        add(&func.attrs, T::Function);
+        for param in &func.arg_attrs {
+            add(param, T::Parameter);
+        }
+    }
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        add(&prog.attrs, T::Program);
+        let vars = [
+            (&prog.inputs, T::Input),
+            (&prog.outputs, T::Output),
+            (&prog.workgroup, T::Workgroup),
+        ];
+        for (vars, target) in vars.iter() {
+            for var in vars.iter() {
+                add(&hir_ctx.variable_defs[*var].attrs, *target);
+                program_vars.insert(*var);
+            }
+        }
+    }
+    // local variables are only reachable through the statements, the index
+    // has all of them, and the variables of programs as locals too
+    for (sym, _) in ty_ctx.references.symbols() {
+        match sym {
+            Symbol::Local(def) if !program_vars.contains(&def) => {
+                add(&hir_ctx.variable_defs[def].attrs, T::Local)
+            }
+            _ => {}
+        }
+    }
+
+    attrs.sort_by_key(|(id, _)| hir_ctx.attribute_fcs[id].start);
+    attrs.dedup();
+    attrs
+}
+
+/// Report known attributes on declarations they don't apply to, and warn
+/// about attributes that aren't known at all.
+pub(crate) fn check_attributes(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> (Vec<Error>, Vec<Warning>) {
+    let mut errs = vec![];
+    let mut warnings = vec![];
+
+    for (id, target) in module_attributes(module, ty_ctx, hir_ctx) {
+        let name = &hir_ctx.identifiers[hir_ctx.attributes[id].name];
+        let attribute = hir_ctx.attribute_fcs[&id];
+        match known_attribute(name) {
+            Some(known) if known.targets.contains(&target) => {}
+            Some(known) => errs.push(Error::MisplacedAttribute {
+                name: name.clone(),
+                attribute,
+                target,
+                allowed: known.targets,
+            }),
+            None => warnings.push(Warning::UnknownAttribute {
+                name: name.clone(),
+                attribute,
+                suggestions: suggestions::similar_names(
+                    name,
+                    KNOWN_ATTRIBUTES.iter().map(|attr| attr.name),
+                ),
+            }),
+        }
+    }
+
+    (errs, warnings)
+}
+
+/// Where an attribute is written, for messages like "fields and constants".
+pub(crate) fn target_list(targets: &[AttributeTarget]) -> String {
+    let names = targets
+        .iter()
+        .map(|target| format!("{}s", target))
+        .collect::<Vec<_>>();
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => names.concat(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_names_are_unique() {
+        for (i, attr) in KNOWN_ATTRIBUTES.iter().enumerate() {
+            assert_eq!(
+                known_attribute(attr.name),
+                Some(&KNOWN_ATTRIBUTES[i]),
+                "`{}` is registered twice",
+                attr.name
+            );
+        }
+    }
+
+    #[test]
+    fn target_lists() {
+        use AttributeTarget::*;
+
+        assert_eq!(target_list(&[Program]), "programs");
+        assert_eq!(
+            target_list(&[Constant, Local, Field]),
+            "constants, local variables and fields"
+        );
+    }
+}
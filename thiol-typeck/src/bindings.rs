@@ -0,0 +1,219 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Binding numbers of buffers.
+//!
+//! Buffers without a `binding` argument get the lowest binding of their set
+//! that is neither used by another buffer nor reserved, in the order the
+//! buffers are declared. Buffers without a `set` argument are in set 0. Push
+//! constants have no binding.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use thiol_hir as hir;
+
+use hir::VariableDef;
+use id_arena::Id;
+
+use crate::layout::buffer_class;
+use crate::{BufferClass, Context, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+}
+
+/// The binding of a buffer and whether it was assigned by the compiler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceBinding {
+    pub binding: Binding,
+    pub assigned: bool,
+}
+
+/// A range of bindings of a set that are never assigned to buffers without
+/// an explicit binding, written as `set:first-last` or `set:binding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingReservation {
+    pub set: u32,
+    pub first: u32,
+    pub last: u32,
+}
+
+impl BindingReservation {
+    pub fn contains(&self, binding: Binding) -> bool {
+        binding.set == self.set && self.first <= binding.binding && binding.binding <= self.last
+    }
+}
+
+impl fmt::Display for BindingReservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}:{}", self.set, self.first)
+        } else {
+            write!(f, "{}:{}-{}", self.set, self.first, self.last)
+        }
+    }
+}
+
+impl FromStr for BindingReservation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid binding reservation `{}`, expected `set:first-last` or `set:binding`",
+                s
+            )
+        };
+        let (set, range) = s.split_once(':').ok_or_else(invalid)?;
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let number = |n: &str| n.trim().parse::<u32>().map_err(|_| invalid());
+        let reservation = BindingReservation {
+            set: number(set)?,
+            first: number(first)?,
+            last: number(last)?,
+        };
+        if reservation.first > reservation.last {
+            return Err(invalid());
+        }
+        Ok(reservation)
+    }
+}
+
+impl Context {
+    /// Whether a binding is reserved and can't be assigned to buffers.
+    pub fn is_reserved(&self, binding: Binding) -> bool {
+        self.binding_reservations
+            .iter()
+            .any(|reservation| reservation.contains(binding))
+    }
+}
+
+/// Assign bindings to the buffers of the module that don't have one, and
+/// report buffers with the same explicit binding.
+pub(crate) fn assign_bindings(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+    let mut used = BTreeMap::new();
+    let mut missing = vec![];
+
+    for id in &module.consts {
+        match buffer_class(hir_ctx, *id) {
+            Some(BufferClass::Uniform) | Some(BufferClass::Storage) => {}
+            Some(BufferClass::PushConstant) | None => continue,
+        }
+        let set = attribute_argument(hir_ctx, *id, "set");
+        let binding = match attribute_argument(hir_ctx, *id, "binding") {
+            Some(binding) => Binding {
+                set: set.unwrap_or(0),
+                binding,
+            },
+            None => {
+                missing.push((*id, set.unwrap_or(0)));
+                continue;
+            }
+        };
+
+        let attribute = binding_attribute(hir_ctx, *id);
+        if let Some(previous) = used.insert(binding, attribute) {
+            errs.push(Error::BindingConflict {
+                binding,
+                previous,
+                attribute,
+            });
+            continue;
+        }
+        ty_ctx.bindings.insert(
+            *id,
+            ResourceBinding {
+                binding,
+                assigned: false,
+            },
+        );
+    }
+
+    for (id, set) in missing {
+        let binding = (0..)
+            .map(|binding| Binding { set, binding })
+            .find(|b| !used.contains_key(b) && !ty_ctx.is_reserved(*b))
+            .unwrap();
+        used.insert(binding, binding_attribute(hir_ctx, id));
+        ty_ctx.bindings.insert(
+            id,
+            ResourceBinding {
+                binding,
+                assigned: true,
+            },
+        );
+    }
+
+    errs
+}
+
+/// The location of the buffer attribute of a constant.
+fn binding_attribute(hir_ctx: &hir::Context, def: Id<VariableDef>) -> hir::FileLocation {
+    let attr = hir_ctx.variable_defs[def]
+        .attrs
+        .iter()
+        .find(|attr| {
+            BufferClass::from_attribute(&hir_ctx.identifiers[hir_ctx.attributes[**attr].name])
+                .is_some()
+        })
+        .unwrap();
+    hir_ctx.attribute_fcs[attr]
+}
+
+/// A named integer argument of the buffer attribute of a constant.
+fn attribute_argument(hir_ctx: &hir::Context, def: Id<VariableDef>, name: &str) -> Option<u32> {
+    hir_ctx.variable_defs[def].attrs.iter().find_map(|attr| {
+        let attr = &hir_ctx.attributes[*attr];
+        BufferClass::from_attribute(&hir_ctx.identifiers[attr.name])?;
+        let (_, value) = attr
+            .nam_args
+            .iter()
+            .find(|(arg, _)| hir_ctx.identifiers[*arg] == name)?;
+        match hir_ctx.expressions[*value] {
+            hir::Expression::Literal(hir::Literal::Integer(n, _)) => u32::try_from(n).ok(),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reservations() {
+        assert_eq!(
+            "1:0-3".parse(),
+            Ok(BindingReservation {
+                set: 1,
+                first: 0,
+                last: 3
+            })
+        );
+        assert_eq!(
+            "0:5".parse(),
+            Ok(BindingReservation {
+                set: 0,
+                first: 5,
+                last: 5
+            })
+        );
+        assert!("0:4-2".parse::<BindingReservation>().is_err());
+        assert!("3".parse::<BindingReservation>().is_err());
+        assert_eq!(
+            "2:1-4".parse::<BindingReservation>().unwrap().to_string(),
+            "2:1-4"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! What happens when an array is indexed outside of its bounds.
+//!
+//! Indices that are literals are checked against the size of arrays by the
+//! type checker. Other indices are checked at runtime as the policy of the
+//! [`Context`](crate::Context) says: `clamp` clamps them to the array,
+//! `undefined` leaves them unchecked and `trap` stops the invocation on
+//! targets that can. Open arrays have no size the backends know, so only
+//! the indices into their slices are checked, which are clamped unless the
+//! policy is `trap`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Context, Type, TypeId};
+
+/// How indices that may be out of bounds are checked at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum BoundsCheck {
+    /// clamp the index to the last element
+    Clamp,
+    /// leave the index unchecked, like GLSL and Metal do
+    #[default]
+    Undefined,
+    /// stop the invocation
+    Trap,
+}
+
+impl BoundsCheck {
+    pub const ALL: &'static [BoundsCheck] = &[
+        BoundsCheck::Clamp,
+        BoundsCheck::Undefined,
+        BoundsCheck::Trap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BoundsCheck::Clamp => "clamp",
+            BoundsCheck::Undefined => "undefined",
+            BoundsCheck::Trap => "trap",
+        }
+    }
+}
+
+impl FromStr for BoundsCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BoundsCheck::ALL
+            .iter()
+            .copied()
+            .find(|check| check.name() == s)
+            .ok_or_else(|| {
+                let names = BoundsCheck::ALL
+                    .iter()
+                    .map(|c| c.name())
+                    .collect::<Vec<_>>();
+                format!(
+                    "unknown bounds check `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for BoundsCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Context {
+    /// The number of elements of an array with a size, `None` for open
+    /// arrays and other types.
+    pub fn array_len(&self, ty: TypeId) -> Option<usize> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Array { size, .. } => Some(*size),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_policies() {
+        for check in BoundsCheck::ALL {
+            assert_eq!(check.name().parse(), Ok(*check));
+        }
+        assert_eq!(
+            "wrap".parse::<BoundsCheck>(),
+            Err("unknown bounds check `wrap`, expected one of clamp, undefined, trap".to_string())
+        );
+    }
+}
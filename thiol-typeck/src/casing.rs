@@ -0,0 +1,184 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Names that differ only in case.
+//!
+//! Some shader toolchains compare names without their case, and mangled
+//! names can lose it, so `albedo` and `Albedo` can end up as the same name
+//! on a target. Such names are reported in the `case-collisions` lint group
+//! when they are in the same namespace: the items of the module, following
+//! the [`Namespaces`] policy, the fields of a record, and the parameters and
+//! variables of a function or program, which the backends declare in one
+//! function.
+
+use std::collections::HashMap;
+
+use hir::{FileLocation, Identifier, Statement};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::{Context, ItemKind, Namespaces, Warning};
+
+pub(crate) fn check_case_collisions(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Warning> {
+    namespaces(module, ty_ctx, hir_ctx)
+        .into_iter()
+        .flat_map(|names| collisions(hir_ctx, names))
+        .collect()
+}
+
+/// The names declared in each namespace of a module, with what they
+/// declare, like `field` or `function`.
+pub(crate) fn namespaces(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Vec<(&'static str, Id<Identifier>)>> {
+    let mut namespaces = vec![];
+    let mut add = |names: Vec<(&'static str, Id<Identifier>)>| namespaces.push(names);
+
+    let items = module
+        .types
+        .iter()
+        .map(|id| (ItemKind::Type, hir_ctx.type_defs[*id].name))
+        .chain(
+            module
+                .functions
+                .iter()
+                .map(|id| (ItemKind::Function, hir_ctx.functions[*id].name)),
+        )
+        .chain(
+            module
+                .consts
+                .iter()
+                .map(|id| (ItemKind::Constant, hir_ctx.variable_defs[*id].name)),
+        );
+    match ty_ctx.namespaces {
+        Namespaces::Shared => add(items.map(|(kind, name)| (kind.name(), name)).collect()),
+        Namespaces::Separate => {
+            for kind in [ItemKind::Type, ItemKind::Function, ItemKind::Constant] {
+                add(items
+                    .clone()
+                    .filter(|(k, _)| *k == kind)
+                    .map(|(kind, name)| (kind.name(), name))
+                    .collect());
+            }
+        }
+    }
+
+    for id in &module.types {
+        let def = &hir_ctx.type_defs[*id];
+        if let hir::TypeDefinitionRhs::Record { fields } = &hir_ctx.type_def_rhss[def.rhs] {
+            add(fields
+                .iter()
+                .map(|field| ("field", hir_ctx.variable_defs[*field].name))
+                .collect());
+        }
+    }
+
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        let mut names = func
+            .args
+            .iter()
+            .map(|(name, _, _)| ("parameter", *name))
+            .collect();
+        locals(hir_ctx, &func.body, &mut names);
+        add(names);
+    }
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let mut names = prog
+            .inputs
+            .iter()
+            .chain(&prog.outputs)
+            .chain(&prog.workgroup)
+            .map(|var| ("variable", hir_ctx.variable_defs[*var].name))
+            .collect();
+        locals(hir_ctx, &prog.body, &mut names);
+        add(names);
+    }
+
+    namespaces
+}
+
+/// The variables declared anywhere in a body.
+fn locals(
+    hir_ctx: &hir::Context,
+    block: &[Id<Statement>],
+    names: &mut Vec<(&'static str, Id<Identifier>)>,
+) {
+    for stmt in block {
+        match &hir_ctx.statements[*stmt] {
+            Statement::Var(var) => names.push(("variable", hir_ctx.variable_defs[*var].name)),
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                locals(hir_ctx, then_body, names);
+                locals(hir_ctx, else_body, names);
+            }
+            Statement::For {
+                iter_name, body, ..
+            } => {
+                names.push(("variable", *iter_name));
+                locals(hir_ctx, body, names);
+            }
+            Statement::Match { arms, .. } => {
+                for arm in arms {
+                    if let hir::MatchPattern::Some(name) = arm.pattern {
+                        names.push(("variable", name));
+                    }
+                    locals(hir_ctx, &arm.body, names);
+                }
+            }
+            Statement::Becomes { .. }
+            | Statement::Destructure { .. }
+            | Statement::Return(_)
+            | Statement::Expr(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Discard => {}
+        }
+    }
+}
+
+/// Names of one namespace that differ only in case from a name declared
+/// before them. Names that are the same are redefinitions or shadow each
+/// other, which is reported elsewhere.
+fn collisions(hir_ctx: &hir::Context, names: Vec<(&'static str, Id<Identifier>)>) -> Vec<Warning> {
+    let mut names = names
+        .into_iter()
+        .map(|(kind, name)| {
+            let loc = hir_ctx.identifier_fcs[&name];
+            (loc, kind, hir_ctx.identifiers[name].as_str())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let mut warnings = vec![];
+    // the first declaration of every spelling, by the name in lower case
+    let mut seen: HashMap<String, Vec<(&str, FileLocation)>> = HashMap::new();
+    for (loc, kind, name) in names {
+        let spellings = seen.entry(name.to_lowercase()).or_default();
+        if spellings.iter().any(|(spelling, _)| *spelling == name) {
+            continue;
+        }
+        if let Some((previous_name, previous)) = spellings.first() {
+            warnings.push(Warning::CaseCollision {
+                kind,
+                name: name.to_string(),
+                collision: loc,
+                previous_name: previous_name.to_string(),
+                previous: *previous,
+            });
+        }
+        spellings.push((name, loc));
+    }
+    warnings
+}
@@ -0,0 +1,333 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Conversions with `as` and `bitcast`.
+//!
+//! `v as T` converts the value of `v` to the type `T`. These casts are
+//! valid:
+//!
+//! - between the same types, which retags vectors with a different space
+//! - between numbers and booleans, where an angle only converts to and from
+//!   floating point numbers and `to_radians` and `to_degrees` convert
+//!   between the units
+//! - between vectors, and between matrices, with the same number of
+//!   components, which converts each component
+//! - to `normalized<..>` of a vector the value converts to
+//!
+//! Casts that don't keep every value are reported in the `lossy-casts` lint
+//! group: floats to less precise floats, floats to integers, integers to
+//! narrower integers and between signed and unsigned integers.
+//!
+//! `bitcast(v)` reinterprets the bits of `v` as the type it is used as,
+//! which needs the same number of components of the same size.
+
+use hir::Expression;
+use thiol_hir as hir;
+
+use crate::layout::components;
+use crate::{lints, Context, Type, TypeId, VecSize, Warning};
+
+/// Why a cast or a bitcast is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CastProblem {
+    /// there is no conversion between the types
+    Unconvertible,
+    /// the types have a different number of components
+    ComponentCount { from: usize, to: usize },
+    /// angles in radians and degrees are converted, not cast
+    AngleUnits,
+    /// a bitcast between components of a different size
+    BitcastSize { from: usize, to: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Scalar,
+    Vector(VecSize),
+    Matrix(VecSize, VecSize),
+}
+
+impl Shape {
+    fn components(self) -> usize {
+        match self {
+            Shape::Scalar => 1,
+            Shape::Vector(size) => components(size),
+            Shape::Matrix(cols, rows) => components(cols) * components(rows),
+        }
+    }
+}
+
+impl Context {
+    /// The shape of a number, boolean, vector or matrix and the type of its
+    /// components.
+    fn cast_shape(&self, ty: TypeId) -> Option<(Shape, Type)> {
+        let ty = self.strip_normalized(self.strip_distinct(ty));
+        let shape = match self.types.get(ty)? {
+            Type::Bool => (Shape::Scalar, Type::Bool),
+            Type::BoolVec { components } => (Shape::Vector(*components), Type::Bool),
+            Type::FloatMat { cols, rows, .. } => (Shape::Matrix(*cols, *rows), Type::Float),
+            Type::DoubleMat { cols, rows, .. } => (Shape::Matrix(*cols, *rows), Type::Double),
+            Type::IntVec { components, .. }
+            | Type::UIntVec { components, .. }
+            | Type::LongVec { components, .. }
+            | Type::ULongVec { components, .. }
+            | Type::FloatVec { components, .. }
+            | Type::DoubleVec { components, .. }
+            | Type::HalfVec { components, .. } => {
+                (Shape::Vector(*components), self.types.get(ty)?.scalar()?)
+            }
+            Type::AtomicInt | Type::AtomicUInt => return None,
+            scalar => (Shape::Scalar, scalar.scalar()?),
+        };
+        Some(shape)
+    }
+
+    /// Check the cast of a value of type `from` to the type `to`.
+    pub fn check_cast(&self, from: TypeId, to: TypeId) -> Result<(), CastProblem> {
+        if self.is_error(from) || self.is_error(to) || from == to {
+            return Ok(());
+        }
+        let ((from_shape, from), (to_shape, to)) = self
+            .cast_shape(from)
+            .zip(self.cast_shape(to))
+            .ok_or(CastProblem::Unconvertible)?;
+        if from_shape != to_shape {
+            let (from, to) = (from_shape.components(), to_shape.components());
+            if from == to {
+                return Err(CastProblem::Unconvertible);
+            }
+            return Err(CastProblem::ComponentCount { from, to });
+        }
+        match (from, to) {
+            (Type::Radians, Type::Degrees) | (Type::Degrees, Type::Radians) => {
+                Err(CastProblem::AngleUnits)
+            }
+            (Type::Radians | Type::Degrees, other) | (other, Type::Radians | Type::Degrees)
+                if float_bits(&other).is_none() =>
+            {
+                Err(CastProblem::Unconvertible)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check `bitcast` of a value of type `from` used as a value of type
+    /// `to`.
+    pub fn check_bitcast(&self, from: TypeId, to: TypeId) -> Result<(), CastProblem> {
+        if self.is_error(from) || self.is_error(to) {
+            return Ok(());
+        }
+        let ((from_shape, from), (to_shape, to)) = self
+            .cast_shape(from)
+            .zip(self.cast_shape(to))
+            .ok_or(CastProblem::Unconvertible)?;
+        if !matches!(from_shape, Shape::Scalar | Shape::Vector(_)) || from_shape != to_shape {
+            return match (from_shape.components(), to_shape.components()) {
+                (from, to) if from != to => Err(CastProblem::ComponentCount { from, to }),
+                _ => Err(CastProblem::Unconvertible),
+            };
+        }
+        match (bits(&from), bits(&to)) {
+            (Some(from), Some(to)) if from == to => Ok(()),
+            (Some(from), Some(to)) => Err(CastProblem::BitcastSize { from, to }),
+            _ => Err(CastProblem::Unconvertible),
+        }
+    }
+
+    /// Whether a valid cast of a value of type `from` to the type `to` can
+    /// lose information.
+    pub fn is_lossy_cast(&self, from: TypeId, to: TypeId) -> bool {
+        let (from, to) = match self.cast_shape(from).zip(self.cast_shape(to)) {
+            Some(((_, from), (_, to))) => (from, to),
+            None => return false,
+        };
+        match (float_bits(&from), float_bits(&to)) {
+            (Some(from), Some(to)) => return to < from,
+            (Some(_), None) => return to != Type::Bool,
+            (None, _) => {}
+        }
+        match (integer(&from), integer(&to)) {
+            // negative values don't fit in unsigned integers, a signed
+            // integer needs a bit more for the unsigned values
+            (Some((from_bits, from_signed)), Some((to_bits, to_signed))) => {
+                match (from_signed, to_signed) {
+                    (true, false) => true,
+                    (false, true) => to_bits <= from_bits,
+                    _ => to_bits < from_bits,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn is_error(&self, ty: TypeId) -> bool {
+        matches!(self.types.get(ty), Some(Type::Error))
+    }
+}
+
+/// The size of floating point numbers and angles.
+fn float_bits(ty: &Type) -> Option<usize> {
+    match ty {
+        Type::Half => Some(16),
+        Type::Float | Type::Radians | Type::Degrees => Some(32),
+        Type::Double => Some(64),
+        _ => None,
+    }
+}
+
+/// The size of integers and whether they are signed.
+fn integer(ty: &Type) -> Option<(usize, bool)> {
+    match ty {
+        Type::Int => Some((32, true)),
+        Type::UInt => Some((32, false)),
+        Type::Long => Some((64, true)),
+        Type::ULong => Some((64, false)),
+        _ => None,
+    }
+}
+
+/// The size of numbers, `None` for booleans, whose size depends on the
+/// target.
+fn bits(ty: &Type) -> Option<usize> {
+    float_bits(ty).or_else(|| integer(ty).map(|(bits, _)| bits))
+}
+
+/// Report the casts in function and program bodies that can lose
+/// information.
+pub(crate) fn check_lossy_casts(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Warning> {
+    lints::expressions(module, hir_ctx)
+        .into_iter()
+        .filter_map(|id| {
+            let base = match hir_ctx.expressions[id] {
+                Expression::As { base, .. } => base,
+                _ => return None,
+            };
+            let from = *ty_ctx.expr_types.get(&base)?;
+            let to = *ty_ctx.expr_types.get(&id)?;
+            if ty_ctx.check_cast(from, to).is_err() || !ty_ctx.is_lossy_cast(from, to) {
+                return None;
+            }
+            Some(Warning::LossyCast {
+                cast: *hir_ctx.expression_fcs.get(&id)?,
+                from: ty_ctx.display_type(from).to_string(),
+                to: ty_ctx.display_type(to).to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecType;
+
+    fn vector(ctx: &mut Context, components: VecSize, scalar: Type) -> TypeId {
+        let (vtype, space) = (VecType::Unknown, None);
+        let ty = match scalar {
+            Type::Int => Type::IntVec {
+                components,
+                vtype,
+                space,
+            },
+            Type::UInt => Type::UIntVec {
+                components,
+                vtype,
+                space,
+            },
+            _ => Type::FloatVec {
+                components,
+                vtype,
+                space,
+            },
+        };
+        ctx.add_or_get_type(ty)
+    }
+
+    #[test]
+    fn cast_matrix() {
+        let mut ctx = Context::default();
+        let boolean = ctx.add_or_get_type(Type::Bool);
+        let int = ctx.add_or_get_type(Type::Int);
+        let float = ctx.add_or_get_type(Type::Float);
+        let double = ctx.add_or_get_type(Type::Double);
+        let radians = ctx.add_or_get_type(Type::Radians);
+        let degrees = ctx.add_or_get_type(Type::Degrees);
+        let float3 = vector(&mut ctx, VecSize::VS3, Type::Float);
+        let int3 = vector(&mut ctx, VecSize::VS3, Type::Int);
+        let float4 = vector(&mut ctx, VecSize::VS4, Type::Float);
+        let float2x2 = ctx.add_or_get_type(Type::FloatMat {
+            cols: VecSize::VS2,
+            rows: VecSize::VS2,
+            transform: None,
+        });
+
+        assert_eq!(ctx.check_cast(int, float), Ok(()));
+        assert_eq!(ctx.check_cast(boolean, double), Ok(()));
+        assert_eq!(ctx.check_cast(float, degrees), Ok(()));
+        assert_eq!(ctx.check_cast(int3, float3), Ok(()));
+        assert_eq!(
+            ctx.check_cast(float3, float4),
+            Err(CastProblem::ComponentCount { from: 3, to: 4 })
+        );
+        assert_eq!(
+            ctx.check_cast(float4, float2x2),
+            Err(CastProblem::Unconvertible)
+        );
+        assert_eq!(
+            ctx.check_cast(radians, degrees),
+            Err(CastProblem::AngleUnits)
+        );
+        assert_eq!(
+            ctx.check_cast(int, radians),
+            Err(CastProblem::Unconvertible)
+        );
+    }
+
+    #[test]
+    fn lossy_casts() {
+        let mut ctx = Context::default();
+        let boolean = ctx.add_or_get_type(Type::Bool);
+        let int = ctx.add_or_get_type(Type::Int);
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let long = ctx.add_or_get_type(Type::Long);
+        let float = ctx.add_or_get_type(Type::Float);
+        let double = ctx.add_or_get_type(Type::Double);
+
+        assert!(ctx.is_lossy_cast(double, float));
+        assert!(ctx.is_lossy_cast(float, int));
+        assert!(ctx.is_lossy_cast(int, uint));
+        assert!(ctx.is_lossy_cast(uint, int));
+        assert!(ctx.is_lossy_cast(long, int));
+        assert!(!ctx.is_lossy_cast(float, double));
+        assert!(!ctx.is_lossy_cast(int, long));
+        assert!(!ctx.is_lossy_cast(uint, long));
+        assert!(!ctx.is_lossy_cast(int, float));
+        assert!(!ctx.is_lossy_cast(float, boolean));
+    }
+
+    #[test]
+    fn bitcasts() {
+        let mut ctx = Context::default();
+        let int = ctx.add_or_get_type(Type::Int);
+        let float = ctx.add_or_get_type(Type::Float);
+        let double = ctx.add_or_get_type(Type::Double);
+        let uint3 = vector(&mut ctx, VecSize::VS3, Type::UInt);
+        let float3 = vector(&mut ctx, VecSize::VS3, Type::Float);
+
+        assert_eq!(ctx.check_bitcast(float, int), Ok(()));
+        assert_eq!(ctx.check_bitcast(float3, uint3), Ok(()));
+        assert_eq!(
+            ctx.check_bitcast(double, int),
+            Err(CastProblem::BitcastSize { from: 64, to: 32 })
+        );
+        assert_eq!(
+            ctx.check_bitcast(float3, int),
+            Err(CastProblem::ComponentCount { from: 3, to: 1 })
+        );
+    }
+}
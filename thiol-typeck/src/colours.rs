@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Linear and sRGB encoded colours.
+//!
+//! `float3 is Colour` is a linear colour and `float3 is SrgbColour` a colour
+//! in the sRGB encoding most textures and framebuffers store. Like vectors
+//! in different spaces, colours in different encodings can't be mixed: a
+//! value in one encoding can't be used where the other one is expected, and
+//! arithmetic on a linear and an sRGB encoded colour is an error. Values are
+//! converted with `srgb_to_linear(c)` and `linear_to_srgb(c)`. Vectors that
+//! aren't tagged as colours go with both encodings.
+
+use hir::{Expression, FileLocation};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::{Context, Error, Type, TypeId, VecType};
+
+impl Context {
+    /// `VecType::Colour` or `VecType::SrgbColour` for colours, `None` for
+    /// all other types.
+    pub fn colour_encoding(&self, ty: TypeId) -> Option<VecType> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::IntVec { vtype, .. }
+            | Type::UIntVec { vtype, .. }
+            | Type::LongVec { vtype, .. }
+            | Type::ULongVec { vtype, .. }
+            | Type::FloatVec { vtype, .. }
+            | Type::DoubleVec { vtype, .. }
+            | Type::HalfVec { vtype, .. } => match vtype {
+                VecType::Colour | VecType::SrgbColour => Some(*vtype),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A colour assigned, passed or returned where a colour of the other
+/// encoding is expected. Vectors that aren't colours are left to the other
+/// checks.
+pub(crate) fn check_value_encoding(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+    found: TypeId,
+    expected: TypeId,
+) -> Option<Error> {
+    match (
+        ty_ctx.colour_encoding(found)?,
+        ty_ctx.colour_encoding(expected)?,
+    ) {
+        (found, expected) if found != expected => Some(Error::ColourEncodingMismatch {
+            expr: hir_ctx.expression_fcs[&expr],
+            srgb: found == VecType::SrgbColour,
+        }),
+        _ => None,
+    }
+}
+
+/// The operands `a` and `b` of the arithmetic operation at `operation`.
+pub(crate) fn check_operands(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    operation: FileLocation,
+    (a, a_ty): (Id<Expression>, TypeId),
+    (b, b_ty): (Id<Expression>, TypeId),
+) -> Option<Error> {
+    let (srgb, linear) = match (ty_ctx.colour_encoding(a_ty)?, ty_ctx.colour_encoding(b_ty)?) {
+        (VecType::SrgbColour, VecType::Colour) => (a, b),
+        (VecType::Colour, VecType::SrgbColour) => (b, a),
+        _ => return None,
+    };
+    Some(Error::MixedColourEncodings {
+        operation,
+        srgb: hir_ctx.expression_fcs[&srgb],
+        linear: hir_ctx.expression_fcs[&linear],
+    })
+}
@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Records and arrays built from their values.
+//!
+//! `Light(position: p, range: r)` builds a record from the values of its
+//! fields, given in the order of the fields or by their names. A generic
+//! record gets its generic arguments from where it is used. `[a, b, c]`
+//! builds an array of the elements, which have the element type of the
+//! array it is used as, or else the type of the first element.
+//!
+//! Constants of records and arrays, and of the vectors, records and arrays
+//! nested in them, have values that are known when compiling. Their values
+//! are evaluated once the module type checks, see
+//! [`crate::consteval::Evaluator::eval_constant`], so that the backends can
+//! emit them as composite constants.
+
+use std::fmt;
+
+use thiol_hir::TypeDefinition;
+
+use id_arena::Id;
+
+use crate::{Context, Type, TypeId};
+
+/// Why the values of a record or an array don't build it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompositeProblem {
+    /// more values than the record has fields
+    TooManyValues { fields: usize, found: usize },
+    /// a value for a field the record doesn't have
+    UnknownField(String),
+    /// a field that was given a value before
+    DuplicateField(String),
+    /// fields that weren't given a value
+    MissingFields(Vec<String>),
+    /// a value that doesn't have the type of its field or of the elements
+    ValueType { expected: String, found: String },
+    /// a generic record that isn't used as one of its instances
+    UninferableRecord,
+    /// an array without elements
+    EmptyArray,
+}
+
+impl fmt::Display for CompositeProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompositeProblem::TooManyValues { fields, found } => {
+                write!(f, "{} values for {} fields", found, fields)
+            }
+            CompositeProblem::UnknownField(field) => write!(f, "no field `{}`", field),
+            CompositeProblem::DuplicateField(field) => {
+                write!(f, "`{}` was given a value before", field)
+            }
+            CompositeProblem::MissingFields(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| format!("`{}`", field))
+                    .collect::<Vec<_>>();
+                write!(f, "no value for {}", fields.join(", "))
+            }
+            CompositeProblem::ValueType { expected, found } => {
+                write!(f, "expected `{}`, found `{}`", expected, found)
+            }
+            CompositeProblem::UninferableRecord => write!(f, "generic arguments not known"),
+            CompositeProblem::EmptyArray => write!(f, "no elements"),
+        }
+    }
+}
+
+impl Context {
+    /// The type of the record a constructor of the type definition `def`
+    /// builds, the instance `expected` if the record is generic.
+    pub(crate) fn constructed_record(
+        &self,
+        name: &str,
+        def: Id<TypeDefinition>,
+        expected: Option<TypeId>,
+    ) -> Option<TypeId> {
+        if let Some(ty) = self.complete_types.get(name) {
+            return Some(*ty);
+        }
+        let expected = expected?;
+        match self.types.get(expected)? {
+            Type::Distinct { distinct_id, .. }
+                if self.distinct_defs.get(distinct_id) == Some(&def) =>
+            {
+                Some(expected)
+            }
+            _ => None,
+        }
+    }
+
+    /// The fields of a record with their types, in the order they are
+    /// declared.
+    pub fn record_fields(&self, ty: TypeId) -> Option<Vec<(String, TypeId)>> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Record { fields } => Some(
+                fields
+                    .iter()
+                    .map(|(name, ty)| (self.types.name(*name).to_string(), *ty))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The types of the fields of a record or of the elements of an array,
+    /// `None` for other types.
+    pub fn composite_types(&self, ty: TypeId) -> Option<Vec<TypeId>> {
+        if let Some(fields) = self.record_fields(ty) {
+            return Some(fields.into_iter().map(|(_, ty)| ty).collect());
+        }
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Array { base, size } => Some(vec![*base; *size]),
+            _ => None,
+        }
+    }
+
+    /// Whether the type is a record or an array, or a distinct type of one,
+    /// whose constants have to be evaluated when compiling.
+    pub fn is_composite(&self, ty: TypeId) -> bool {
+        matches!(
+            self.types.get(self.strip_distinct(ty)),
+            Some(Type::Record { .. } | Type::Array { .. })
+        )
+    }
+}
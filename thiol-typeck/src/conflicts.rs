@@ -0,0 +1,166 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Items of different kinds with the same name.
+//!
+//! Types, functions and constants are looked up in their own maps, so the
+//! checker doesn't need them to have distinct names. A type and a function
+//! with the same name are still confusing, the call `Light()` then calls the
+//! function instead of constructing the record, and the backends give all
+//! items names in one namespace. As the policy of the [`Context`] says,
+//! a name is either declared once across all kinds of items, or once per
+//! kind.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use hir::FileLocation;
+use thiol_hir as hir;
+
+use crate::{Context, Error};
+
+/// Whether items of different kinds can have the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Namespaces {
+    /// types, functions and constants share one namespace
+    #[default]
+    Shared,
+    /// every kind of item has its own namespace
+    Separate,
+}
+
+impl Namespaces {
+    pub const ALL: &'static [Namespaces] = &[Namespaces::Shared, Namespaces::Separate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Namespaces::Shared => "shared",
+            Namespaces::Separate => "separate",
+        }
+    }
+}
+
+impl FromStr for Namespaces {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Namespaces::ALL
+            .iter()
+            .copied()
+            .find(|namespaces| namespaces.name() == s)
+            .ok_or_else(|| {
+                let names = Namespaces::ALL.iter().map(|n| n.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown namespaces `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for Namespaces {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The kind of an item whose name conflicts with another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ItemKind {
+    Type,
+    Function,
+    Constant,
+}
+
+impl ItemKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemKind::Type => "type",
+            ItemKind::Function => "function",
+            ItemKind::Constant => "constant",
+        }
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Report items with the name of an item of another kind that is declared
+/// before them. Items with the name of an item of the same kind are
+/// redefinitions, which are reported when adding them.
+pub(crate) fn check_conflicts(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    if ty_ctx.namespaces == Namespaces::Separate {
+        return vec![];
+    }
+
+    let mut items = module
+        .types
+        .iter()
+        .map(|id| (ItemKind::Type, hir_ctx.type_defs[*id].name))
+        .chain(
+            module
+                .functions
+                .iter()
+                .map(|id| (ItemKind::Function, hir_ctx.functions[*id].name)),
+        )
+        .chain(
+            module
+                .consts
+                .iter()
+                .map(|id| (ItemKind::Constant, hir_ctx.variable_defs[*id].name)),
+        )
+        .map(|(kind, name)| {
+            (
+                hir_ctx.identifier_fcs[&name],
+                kind,
+                &hir_ctx.identifiers[name],
+            )
+        })
+        .collect::<Vec<_>>();
+    items.sort();
+
+    let mut errs = vec![];
+    // the first item of every kind with the name
+    let mut declared: BTreeMap<&str, Vec<(ItemKind, FileLocation)>> = BTreeMap::new();
+    for (loc, kind, name) in items {
+        let previous = declared.entry(name.as_str()).or_default();
+        if previous.iter().any(|(k, _)| *k == kind) {
+            continue;
+        }
+        if let Some((previous_kind, previous_loc)) = previous.first() {
+            errs.push(Error::NameConflict {
+                name: name.clone(),
+                previous_kind: *previous_kind,
+                previous: *previous_loc,
+                kind,
+                conflict: loc,
+            });
+        }
+        previous.push((kind, loc));
+    }
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("separate".parse(), Ok(Namespaces::Separate));
+        assert_eq!(
+            "merged".parse::<Namespaces>(),
+            Err("unknown namespaces `merged`, expected one of shared, separate".to_string())
+        );
+    }
+}
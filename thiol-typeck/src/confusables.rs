@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Names that look alike.
+//!
+//! Names can be written with the letters of any script, and letters of
+//! different scripts can look the same, like the Latin `a` and the Cyrillic
+//! `а`. A name that looks like another name declared in the module but is
+//! spelled with other characters is reported in the `confusable-names` lint
+//! group. Two names look alike when they have the same skeleton as defined
+//! by Unicode Technical Standard #39. Names made only of ASCII characters
+//! aren't compared with each other, telling `l` from `1` is left to the
+//! font.
+//!
+//! Names that only differ in how their characters are composed, like `é`
+//! and `e` with a combining accent, don't get here: the lexer normalizes
+//! both to the same name, so they are redefinitions or uses of each other.
+
+use std::collections::HashMap;
+
+use hir::FileLocation;
+use thiol_hir as hir;
+use unicode_security::skeleton;
+
+use crate::{casing, Context, Warning};
+
+pub(crate) fn check_confusables(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Warning> {
+    let mut names = casing::namespaces(module, ty_ctx, hir_ctx)
+        .into_iter()
+        .flatten()
+        .map(|(kind, name)| {
+            let loc = hir_ctx.identifier_fcs[&name];
+            (loc, kind, hir_ctx.identifiers[name].as_str())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let mut warnings = vec![];
+    // the first declaration of every spelling, by the skeleton of the name
+    let mut seen: HashMap<String, Vec<(&str, FileLocation)>> = HashMap::new();
+    for (loc, kind, name) in names {
+        let spellings = seen.entry(skeleton(name).collect()).or_default();
+        if spellings.iter().any(|(spelling, _)| *spelling == name) {
+            continue;
+        }
+        let previous = spellings
+            .iter()
+            .find(|(spelling, _)| !(spelling.is_ascii() && name.is_ascii()));
+        if let Some((previous_name, previous)) = previous {
+            warnings.push(Warning::ConfusableName {
+                kind,
+                name: name.to_string(),
+                confusable: loc,
+                previous_name: previous_name.to_string(),
+                previous: *previous,
+            });
+        }
+        spellings.push((name, loc));
+    }
+    warnings
+}
@@ -0,0 +1,1398 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Evaluation of expressions when compiling.
+//!
+//! Static assertions are evaluated once the module type checks, so the types
+//! of their expressions are known. Literals, constants with a value and the
+//! arithmetic and comparison operators on scalars are evaluated with the
+//! precision of their types on the target, integer arithmetic that overflows
+//! is an error instead of wrapping around. Buffers, constants without a
+//! value and calls of functions have no value when compiling.
+//!
+//! Vectors, records and arrays are composite constants, see
+//! [`Evaluator::eval_constant`]. Their fields and elements are evaluated
+//! like other values, so `LIGHTS[1].range` in a static assertion is known
+//! when compiling. The values of constants of records and arrays are
+//! evaluated once the module type checks, so the backends can emit them.
+//!
+//! Calls of pure functions are evaluated by running the statements of the
+//! functions, see [`Evaluator::call`], in static assertions, the values of
+//! constants, the sizes of arrays and the lookup tables of functions. The
+//! trigonometric intrinsics and `select` are evaluated as well. A call runs
+//! at most [`DEFAULT_EVAL_STEP_LIMIT`] statements, unless
+//! [`Context::eval_step_limit`] sets another limit, and nests at most
+//! [`MAX_CALL_DEPTH`] calls, so recursive functions fail to evaluate instead
+//! of compiling forever. Constants whose values call functions are emitted
+//! with their values, see [`is_evaluated_constant`].
+//!
+//! `sizeof(T)`, `alignof(T)` and `offsetof(T, field)` are answered by the
+//! layout engine, with the rules of storage buffers unless the rules are
+//! the last argument, like `sizeof(T, std140)`. They can be used in the
+//! sizes of arrays, which are evaluated while the type definitions are
+//! checked, before the types of expressions are known. There a literal
+//! without suffix gets the type of the other operand, and a constant the
+//! scalar type it is declared with, as do the parameters and variables of
+//! the functions they call.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use hir::{
+    Expression, FileLocation, ForLoopType, Function, Identifier, LayoutQuery, Literal,
+    LiteralSuffix, ParamMode, PrimitiveOp, PrimitiveType, Statement, TypeReference, VariableDef,
+};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::derives::Derive;
+use crate::layout::{BufferClass, LayoutRules};
+use crate::resolve::ResolutionTable;
+use crate::types::{Type, TypeId};
+use crate::{layout, uniformity, Context, Error, Intrinsic, Resolution, Symbol};
+
+/// The most statements the evaluation of a call runs, which bounds the time
+/// loops take when compiling
+pub const DEFAULT_EVAL_STEP_LIMIT: usize = 1_000_000;
+
+/// The most calls the evaluation of a call nests in each other
+pub const MAX_CALL_DEPTH: usize = 64;
+
+/// A scalar value known when compiling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i32),
+    UInt(u32),
+    Long(i64),
+    ULong(u64),
+    /// a `float` or a `half`
+    Float(f32),
+    Double(f64),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::UInt(u) => write!(f, "{}u", u),
+            Value::Long(i) => write!(f, "{}l", i),
+            Value::ULong(u) => write!(f, "{}ul", u),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Double(x) => write!(f, "{:?}lf", x),
+        }
+    }
+}
+
+/// A value known when compiling, the components of a vector and the fields
+/// and elements of records and arrays in their order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Scalar(Value),
+    Composite(Vec<Constant>),
+}
+
+/// Why an expression can't be evaluated when compiling
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalProblem {
+    /// a kind of expression that isn't evaluated, like calls
+    Unsupported(&'static str),
+    /// a buffer, whose value is only known when the program runs
+    Buffer(String),
+    /// a constant declared without a value
+    NoValue(String),
+    Overflow,
+    DivisionByZero,
+    /// a constant whose value uses the constant itself
+    Cycle(String),
+    /// a condition that isn't a `bool`
+    NotBool(Value),
+    /// the size of an array that isn't a positive integer
+    ArraySize(Value),
+    /// layout rules in a layout query that don't exist
+    UnknownRules(String),
+    /// a layout query of a type that can't be stored in buffers
+    NoLayout(String),
+    /// the size of a type that ends in an array without size
+    RuntimeSized(String),
+    /// the offset of a field the type doesn't have
+    UnknownField {
+        ty: String,
+        field: String,
+    },
+    /// an index past the last element
+    OutOfBounds {
+        index: Value,
+        len: usize,
+    },
+    /// a function that ends without returning a value
+    MissingReturn(String),
+    /// a call that runs more statements than the limit
+    StepLimit(usize),
+    /// calls nested more than [`MAX_CALL_DEPTH`] deep
+    CallDepth,
+}
+
+impl fmt::Display for EvalProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalProblem::Unsupported(what) => write!(f, "{} have no value when compiling", what),
+            EvalProblem::Buffer(name) => write!(f, "`{}` is a buffer", name),
+            EvalProblem::NoValue(name) => write!(f, "`{}` is declared without a value", name),
+            EvalProblem::Overflow => write!(f, "the result overflows its type"),
+            EvalProblem::DivisionByZero => write!(f, "division by zero"),
+            EvalProblem::Cycle(name) => write!(f, "the value of `{}` uses `{}`", name, name),
+            EvalProblem::NotBool(value) => write!(f, "expected a `bool`, found `{}`", value),
+            EvalProblem::ArraySize(value) => {
+                write!(f, "expected a positive integer, found `{}`", value)
+            }
+            EvalProblem::UnknownRules(name) => write!(f, "unknown layout rules `{}`", name),
+            EvalProblem::NoLayout(ty) => write!(f, "`{}` has no layout in buffers", ty),
+            EvalProblem::RuntimeSized(ty) => {
+                write!(
+                    f,
+                    "the size of `{}` is only known when the program runs",
+                    ty
+                )
+            }
+            EvalProblem::UnknownField { ty, field } => {
+                write!(f, "`{}` has no field `{}`", ty, field)
+            }
+            EvalProblem::OutOfBounds { index, len } => {
+                write!(f, "index `{}` is out of bounds of {} elements", index, len)
+            }
+            EvalProblem::MissingReturn(name) => {
+                write!(f, "`{}` ends without returning a value", name)
+            }
+            EvalProblem::StepLimit(limit) => {
+                write!(f, "the call runs more than {} statements", limit)
+            }
+            EvalProblem::CallDepth => {
+                write!(f, "calls nested more than {} deep", MAX_CALL_DEPTH)
+            }
+        }
+    }
+}
+
+/// The values of the parameters and variables of a function being evaluated
+#[derive(Default)]
+struct Frame {
+    args: Vec<Constant>,
+    locals: HashMap<Id<VariableDef>, Constant>,
+    loop_vars: HashMap<Id<Statement>, Constant>,
+}
+
+/// Where the evaluation of a function continues after a statement
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Constant),
+}
+
+/// Evaluates expressions, remembering the values of the constants they use
+pub struct Evaluator<'a> {
+    ty: &'a Context,
+    hir: &'a hir::Context,
+    consts: HashMap<Id<VariableDef>, Constant>,
+    /// the constants whose values are being evaluated
+    evaluating: Vec<Id<VariableDef>>,
+    /// the calls being evaluated, innermost last
+    frames: Vec<Frame>,
+    /// the statements the outermost call has run
+    steps: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(ty: &'a Context, hir: &'a hir::Context) -> Self {
+        Evaluator {
+            ty,
+            hir,
+            consts: HashMap::new(),
+            evaluating: vec![],
+            frames: vec![],
+            steps: 0,
+        }
+    }
+
+    /// The value a function returns for the values of its parameters, the
+    /// statements of the function run like they would in the program.
+    pub fn call(
+        &mut self,
+        func: Id<Function>,
+        args: Vec<Constant>,
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        if self.frames.is_empty() {
+            self.steps = 0;
+        }
+        let func = &self.hir.functions[func];
+        self.frames.push(Frame {
+            args,
+            ..Frame::default()
+        });
+        let flow = self.block(&func.body);
+        self.frames.pop();
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Err((
+                self.hir.identifier_fcs[&func.name],
+                EvalProblem::MissingReturn(self.hir.identifiers[func.name].clone()),
+            )),
+        }
+    }
+
+    fn block(&mut self, block: &[Id<Statement>]) -> Result<Flow, (FileLocation, EvalProblem)> {
+        for stmt in block {
+            match self.statement(*stmt)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn statement(&mut self, id: Id<Statement>) -> Result<Flow, (FileLocation, EvalProblem)> {
+        let loc = self.hir.statement_fcs[&id];
+        self.steps += 1;
+        let limit = self.ty.eval_step_limit.unwrap_or(DEFAULT_EVAL_STEP_LIMIT);
+        if self.steps > limit {
+            return Err((loc, EvalProblem::StepLimit(limit)));
+        }
+
+        match &self.hir.statements[id] {
+            Statement::Var(def) => {
+                let var = &self.hir.variable_defs[*def];
+                if let Some(rhs) = var.rhs {
+                    let value = self.eval_constant(rhs)?;
+                    let value = self.declared(value, rhs, var.type_, loc)?;
+                    self.frame().locals.insert(*def, value);
+                }
+            }
+            Statement::Becomes { lhs, rhs } => {
+                let value = self.eval_constant(*rhs)?;
+                self.assign(*lhs, value)?;
+            }
+            Statement::Destructure { lhs, rhs } => match self.eval_constant(*rhs)? {
+                Constant::Composite(fields) if fields.len() == lhs.len() => {
+                    for (lhs, value) in lhs.iter().zip(fields) {
+                        self.assign(*lhs, value)?;
+                    }
+                }
+                _ => return Err((loc, EvalProblem::Unsupported("assignments of this kind"))),
+            },
+            Statement::Return(Some(e)) => return Ok(Flow::Return(self.eval_constant(*e)?)),
+            Statement::Return(None) => {
+                return Err((loc, EvalProblem::Unsupported("returns without a value")))
+            }
+            Statement::Expr(e) => {
+                self.eval_constant(*e)?;
+            }
+            Statement::Break => return Ok(Flow::Break),
+            Statement::Continue => return Ok(Flow::Continue),
+            Statement::Discard => return Err((loc, EvalProblem::Unsupported("discards"))),
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                return match self.eval(*cond)? {
+                    Value::Bool(true) => self.block(then_body),
+                    Value::Bool(false) => self.block(else_body),
+                    value => Err((self.hir.expression_fcs[cond], EvalProblem::NotBool(value))),
+                };
+            }
+            Statement::For {
+                loop_type,
+                from,
+                to,
+                body,
+                ..
+            } => {
+                let first = self.eval(*from)?;
+                let (from, to) = match (integer(first), integer(self.eval(*to)?)) {
+                    (Some(from), Some(to)) => (from, to),
+                    _ => return Err((loc, EvalProblem::Unsupported("loops over these values"))),
+                };
+                // both bounds are included
+                let range: Box<dyn Iterator<Item = i128>> = match loop_type {
+                    ForLoopType::Up => Box::new(from..=to),
+                    ForLoopType::Down => Box::new((to..=from).rev()),
+                };
+                for i in range {
+                    let value = cast(Value::Long(i as i64), Some(value_type(first)))
+                        .ok_or((loc, EvalProblem::Overflow))?;
+                    self.frame().loop_vars.insert(id, Constant::Scalar(value));
+                    match self.block(body)? {
+                        Flow::Next | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::Match { .. } => return Err((loc, EvalProblem::Unsupported("optionals"))),
+        }
+        Ok(Flow::Next)
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("statements are evaluated in calls")
+    }
+
+    /// Assign a value to a variable, or to a field or an element of one.
+    fn assign(
+        &mut self,
+        lhs: Id<Expression>,
+        value: Constant,
+    ) -> Result<(), (FileLocation, EvalProblem)> {
+        let loc = self.hir.expression_fcs[&lhs];
+        let unsupported = (loc, EvalProblem::Unsupported("assignments of this kind"));
+
+        // the positions of the fields and elements from the variable to the
+        // assigned part, innermost first
+        let mut path = vec![];
+        let mut e = lhs;
+        let name = loop {
+            match &self.hir.expressions[e] {
+                Expression::Variable(name) => break *name,
+                Expression::Field { base, name } => {
+                    let name = &self.hir.identifiers[*name];
+                    let base_ty = *self.ty.expr_types.get(base).ok_or(unsupported.clone())?;
+                    let position = match self.ty.record_fields(base_ty) {
+                        Some(fields) => fields.iter().position(|(field, _)| field == name),
+                        None if name.len() == 1 => "xyzw".find(name).or_else(|| "rgba".find(name)),
+                        None => None,
+                    };
+                    path.push(position.ok_or(unsupported.clone())?);
+                    e = *base;
+                }
+                Expression::Index { base, index } => {
+                    let len = match self.eval_constant(*base)? {
+                        Constant::Composite(elements) => elements.len(),
+                        Constant::Scalar(_) => return Err(unsupported),
+                    };
+                    path.push(self.index(*index, len)?);
+                    e = *base;
+                }
+                _ => return Err(unsupported),
+            }
+        };
+
+        let no_value = (
+            loc,
+            EvalProblem::NoValue(self.hir.identifiers[name].clone()),
+        );
+        let frame = self.frames.last_mut().ok_or(unsupported.clone())?;
+        let mut slot = match self.ty.resolutions.symbol(name) {
+            Some(Symbol::Local(def)) if path.is_empty() => {
+                frame.locals.insert(def, value);
+                return Ok(());
+            }
+            Some(Symbol::Local(def)) => frame.locals.get_mut(&def).ok_or(no_value)?,
+            Some(Symbol::Parameter { index, .. }) => {
+                frame.args.get_mut(index).ok_or(unsupported.clone())?
+            }
+            _ => return Err(unsupported),
+        };
+        for position in path.into_iter().rev() {
+            slot = match slot {
+                Constant::Composite(elements) => {
+                    elements.get_mut(position).ok_or(unsupported.clone())?
+                }
+                Constant::Scalar(_) => return Err(unsupported),
+            };
+        }
+        *slot = value;
+        Ok(())
+    }
+
+    /// The position an index refers to in a vector or an array with `len`
+    /// elements.
+    fn index(
+        &mut self,
+        index: Id<Expression>,
+        len: usize,
+    ) -> Result<usize, (FileLocation, EvalProblem)> {
+        let loc = self.hir.expression_fcs[&index];
+        let value = self.eval(index)?;
+        let position = match integer(value) {
+            Some(i) => usize::try_from(i).ok(),
+            None => return Err((loc, EvalProblem::Unsupported("indices of this type"))),
+        };
+        position
+            .filter(|i| *i < len)
+            .ok_or((loc, EvalProblem::OutOfBounds { index: value, len }))
+    }
+
+    /// The value of a call of a function of the module while a call is
+    /// evaluated, with the arguments in the order of the parameters.
+    fn function_call(
+        &mut self,
+        call: Id<Expression>,
+        func: Id<Function>,
+        pos_args: &[Id<Expression>],
+        nam_args: &[(Id<Identifier>, Id<Expression>)],
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        let loc = self.hir.expression_fcs[&call];
+        let def = &self.hir.functions[func];
+        let params = &def.args;
+        if params.iter().any(|(_, _, mode)| *mode != ParamMode::In) {
+            return Err((loc, EvalProblem::Unsupported("calls with `out` parameters")));
+        }
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err((loc, EvalProblem::CallDepth));
+        }
+        let mut args = vec![None; params.len()];
+        for ((arg, e), (_, ty, _)) in args.iter_mut().zip(pos_args).zip(params) {
+            let value = self.eval_constant(*e)?;
+            *arg = Some(self.declared(value, *e, *ty, loc)?);
+        }
+        for (name, e) in nam_args {
+            let name = &self.hir.identifiers[*name];
+            let position = params
+                .iter()
+                .position(|(param, _, _)| self.hir.identifiers[*param] == *name);
+            if let Some(position) = position {
+                let value = self.eval_constant(*e)?;
+                args[position] = Some(self.declared(value, *e, params[position].1, loc)?);
+            }
+        }
+        let args = args
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or((loc, EvalProblem::Unsupported("calls without all arguments")))?;
+        let value = self.call(func, args)?;
+        self.declared(value, call, def.ret_type, loc)
+    }
+
+    /// A scalar value of an expression whose type isn't known yet, like in
+    /// the sizes of arrays, converted to the scalar type it is declared with.
+    fn declared(
+        &self,
+        value: Constant,
+        e: Id<Expression>,
+        ty: Id<TypeReference>,
+        loc: FileLocation,
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        match (self.ty.expr_types.contains_key(&e), value) {
+            (false, Constant::Scalar(scalar)) => match declared_scalar(&self.hir.type_refs[ty]) {
+                Some(ty) => cast(scalar, Some(ty))
+                    .map(Constant::Scalar)
+                    .ok_or((loc, EvalProblem::Overflow)),
+                None => Ok(Constant::Scalar(scalar)),
+            },
+            (_, value) => Ok(value),
+        }
+    }
+
+    /// The value of a call of a function derived from a record, the record
+    /// for `new` and whether the records are the same for `eq`.
+    fn derived_call(
+        &mut self,
+        call: Id<Expression>,
+        name: Id<Identifier>,
+        pos_args: &[Id<Expression>],
+        nam_args: &[(Id<Identifier>, Id<Expression>)],
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        match (
+            self.ty.derived_functions[&self.hir.identifiers[name]].derive,
+            pos_args,
+        ) {
+            (Derive::New, _) => self.record(call, pos_args, nam_args),
+            (Derive::Eq, [a, b]) => {
+                let same = self.eval_constant(*a)? == self.eval_constant(*b)?;
+                Ok(Constant::Scalar(Value::Bool(same)))
+            }
+            (Derive::Eq, _) => Err((
+                self.hir.expression_fcs[&call],
+                EvalProblem::Unsupported("calls without all arguments"),
+            )),
+        }
+    }
+
+    /// The value of a call of an intrinsic on scalars, the trigonometric
+    /// functions and `select`.
+    fn intrinsic(
+        &mut self,
+        call: Id<Expression>,
+        intrinsic: Intrinsic,
+        args: &[Id<Expression>],
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        let values = args
+            .iter()
+            .map(|e| self.eval(*e))
+            .collect::<Result<Vec<_>, _>>()?;
+        let float = |f: fn(f64) -> f64| match values[..] {
+            [Value::Float(x)] => Some(Value::Float(f(f64::from(x)) as f32)),
+            [Value::Double(x)] => Some(Value::Double(f(x))),
+            _ => None,
+        };
+        let value = match intrinsic {
+            Intrinsic::Sin => float(f64::sin),
+            Intrinsic::Cos => float(f64::cos),
+            Intrinsic::Tan => float(f64::tan),
+            Intrinsic::Asin => float(f64::asin),
+            Intrinsic::Acos => float(f64::acos),
+            Intrinsic::Atan => float(f64::atan),
+            Intrinsic::ToRadians => float(f64::to_radians),
+            Intrinsic::ToDegrees => float(f64::to_degrees),
+            Intrinsic::Atan2 => match values[..] {
+                [Value::Float(y), Value::Float(x)] => Some(Value::Float(y.atan2(x))),
+                [Value::Double(y), Value::Double(x)] => Some(Value::Double(y.atan2(x))),
+                _ => None,
+            },
+            Intrinsic::Select => match values[..] {
+                [Value::Bool(mask), a, b] => Some(if mask { a } else { b }),
+                _ => None,
+            },
+            _ => None,
+        };
+        let loc = self.hir.expression_fcs[&call];
+        value
+            .map(Constant::Scalar)
+            .ok_or((loc, EvalProblem::Unsupported("calls of this intrinsic")))
+    }
+
+    /// The value of an expression, or the problem with the innermost
+    /// expression that can't be evaluated.
+    pub fn eval(&mut self, id: Id<Expression>) -> Result<Value, (FileLocation, EvalProblem)> {
+        match self.eval_constant(id)? {
+            Constant::Scalar(value) => Ok(value),
+            Constant::Composite(_) => Err((
+                self.hir.expression_fcs[&id],
+                EvalProblem::Unsupported("vectors, records and arrays as scalars"),
+            )),
+        }
+    }
+
+    /// The value of an expression that can be a vector, a record or an
+    /// array, whose fields and elements are evaluated in turn.
+    pub fn eval_constant(
+        &mut self,
+        id: Id<Expression>,
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        let loc = self.hir.expression_fcs[&id];
+        let fail = |problem| Err((loc, problem));
+
+        match &self.hir.expressions[id] {
+            Expression::Literal(literal) => match self.literal(id, literal) {
+                Some(value) => Ok(Constant::Scalar(value)),
+                None => fail(EvalProblem::Overflow),
+            },
+            Expression::Variable(name) => {
+                let frame = self.frames.last();
+                let value = match (self.ty.resolutions.symbol(*name), frame) {
+                    (Some(Symbol::Constant(def)), _) => return self.constant(def, loc),
+                    (Some(Symbol::Parameter { index, .. }), Some(frame)) => frame.args.get(index),
+                    (Some(Symbol::Local(def)), Some(frame)) => frame.locals.get(&def),
+                    (Some(Symbol::LoopVariable(stmt)), Some(frame)) => frame.loop_vars.get(&stmt),
+                    _ => return fail(EvalProblem::Unsupported("variables")),
+                };
+                let name = self.hir.identifiers[*name].clone();
+                value.cloned().ok_or((loc, EvalProblem::NoValue(name)))
+            }
+            Expression::PrimitiveOp(op) => match &self.hir.prim_ops[*op] {
+                PrimitiveOp::Constructor {
+                    pos_args, nam_args, ..
+                } if nam_args.is_empty() => self.vector(id, pos_args),
+                PrimitiveOp::Constructor { .. } => {
+                    fail(EvalProblem::Unsupported("vectors with named components"))
+                }
+                _ => self.prim_op(*op, loc).map(Constant::Scalar),
+            },
+            Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => match self.ty.resolutions.symbol(*name) {
+                Some(Symbol::Type(_)) => self.record(id, pos_args, nam_args),
+                None if self
+                    .ty
+                    .derived_functions
+                    .contains_key(&self.hir.identifiers[*name]) =>
+                {
+                    self.derived_call(id, *name, pos_args, nam_args)
+                }
+                Some(Symbol::Function(func)) => self.function_call(id, func, pos_args, nam_args),
+                _ => match self.ty.call_intrinsics.get(&id) {
+                    Some(intrinsic) => self.intrinsic(id, *intrinsic, pos_args),
+                    None => fail(EvalProblem::Unsupported("calls of functions")),
+                },
+            },
+            Expression::Field { base, name } => {
+                let value = self.eval_constant(*base)?;
+                self.field(*base, value, &self.hir.identifiers[*name])
+                    .ok_or((loc, EvalProblem::Unsupported("fields of this kind")))
+            }
+            Expression::Index { base, index } => {
+                let mut elements = match self.eval_constant(*base)? {
+                    Constant::Composite(elements) => elements,
+                    Constant::Scalar(_) => return fail(EvalProblem::Unsupported("scalar indices")),
+                };
+                let position = self.index(*index, elements.len())?;
+                Ok(elements.swap_remove(position))
+            }
+            Expression::Slice { .. } => fail(EvalProblem::Unsupported("slices")),
+            Expression::As { base, ty } => {
+                let value = self.eval(*base)?;
+                let ty = self
+                    .scalar_type(id)
+                    .or_else(|| declared_scalar(&self.hir.type_refs[*ty]));
+                match cast(value, ty) {
+                    Some(value) => Ok(Constant::Scalar(value)),
+                    None => fail(EvalProblem::Overflow),
+                }
+            }
+            Expression::LayoutQuery {
+                query,
+                ty: _,
+                rules,
+            } => match self.ty.layout_types.get(&id) {
+                Some(ty) => self
+                    .layout_query(query, *ty, *rules)
+                    .map(Constant::Scalar)
+                    .map_err(|problem| (loc, problem)),
+                None => fail(EvalProblem::Unsupported("layout queries of generic types")),
+            },
+            Expression::Array(elements) => elements
+                .iter()
+                .map(|e| self.eval_constant(*e))
+                .collect::<Result<_, _>>()
+                .map(Constant::Composite),
+        }
+    }
+
+    /// The components of a vector, the components of the arguments in turn
+    /// converted to the components of the vector, or one scalar for all of
+    /// them. A scalar constructor converts its argument.
+    fn vector(
+        &mut self,
+        id: Id<Expression>,
+        args: &[Id<Expression>],
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        let loc = self.hir.expression_fcs[&id];
+        // conversions of scalars, like `float(n)`
+        if let (Some(to), [arg]) = (self.scalar_type(id), args) {
+            if to.scalar().as_ref() == Some(&to) {
+                return match cast(self.eval(*arg)?, Some(to)) {
+                    Some(value) => Ok(Constant::Scalar(value)),
+                    None => Err((loc, EvalProblem::Overflow)),
+                };
+            }
+        }
+        let ty = self.ty.expr_types.get(&id).and_then(|ty| {
+            let ty = self.ty.types.get(self.ty.strip_distinct(*ty))?;
+            let components = match ty {
+                Type::BoolVec { components }
+                | Type::IntVec { components, .. }
+                | Type::UIntVec { components, .. }
+                | Type::LongVec { components, .. }
+                | Type::ULongVec { components, .. }
+                | Type::FloatVec { components, .. }
+                | Type::DoubleVec { components, .. }
+                | Type::HalfVec { components, .. } => layout::components(*components),
+                _ => return None,
+            };
+            Some((components, ty.scalar()?))
+        });
+        let (size, scalar) = match ty {
+            Some(ty) => ty,
+            None => {
+                return Err((
+                    loc,
+                    EvalProblem::Unsupported("matrices and untyped vectors"),
+                ))
+            }
+        };
+
+        let mut components = vec![];
+        for arg in args {
+            match self.eval_constant(*arg)? {
+                Constant::Scalar(value) => components.push(value),
+                Constant::Composite(values) => {
+                    for value in values {
+                        match value {
+                            Constant::Scalar(value) => components.push(value),
+                            Constant::Composite(_) => {
+                                return Err((loc, EvalProblem::Unsupported("nested vectors")))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let [value] = components[..] {
+            components = vec![value; size];
+        }
+        if components.len() != size {
+            return Err((loc, EvalProblem::Unsupported("vectors of this shape")));
+        }
+        components
+            .into_iter()
+            .map(|value| match cast(value, Some(scalar.clone())) {
+                Some(value) => Ok(Constant::Scalar(value)),
+                None => Err((loc, EvalProblem::Overflow)),
+            })
+            .collect::<Result<_, _>>()
+            .map(Constant::Composite)
+    }
+
+    /// The fields of a record, in the order they are declared, from the
+    /// values given by position or by name.
+    fn record(
+        &mut self,
+        id: Id<Expression>,
+        pos_args: &[Id<Expression>],
+        nam_args: &[(Id<Identifier>, Id<Expression>)],
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        let loc = self.hir.expression_fcs[&id];
+        let fields = self
+            .ty
+            .expr_types
+            .get(&id)
+            .and_then(|ty| self.ty.record_fields(*ty))
+            .ok_or((loc, EvalProblem::Unsupported("records of unknown types")))?;
+        let mut values = vec![None; fields.len()];
+        for (value, e) in values.iter_mut().zip(pos_args) {
+            *value = Some(self.eval_constant(*e)?);
+        }
+        for (name, e) in nam_args {
+            let name = &self.hir.identifiers[*name];
+            if let Some(index) = fields.iter().position(|(field, _)| field == name) {
+                values[index] = Some(self.eval_constant(*e)?);
+            }
+        }
+        values
+            .into_iter()
+            .map(|value| value.ok_or((loc, EvalProblem::Unsupported("incomplete records"))))
+            .collect::<Result<_, _>>()
+            .map(Constant::Composite)
+    }
+
+    /// A field of a record or the components of a vector named by a
+    /// swizzle, `None` if the type of the base doesn't have the field.
+    fn field(&self, base: Id<Expression>, value: Constant, name: &str) -> Option<Constant> {
+        let elements = match value {
+            Constant::Composite(elements) => elements,
+            Constant::Scalar(_) => return None,
+        };
+        let base_ty = *self.ty.expr_types.get(&base)?;
+        if let Some(fields) = self.ty.record_fields(base_ty) {
+            let index = fields.iter().position(|(field, _)| field == name)?;
+            return elements.into_iter().nth(index);
+        }
+        let mut components = name
+            .chars()
+            .map(|c| {
+                let index = "xyzw".find(c).or_else(|| "rgba".find(c))?;
+                elements.get(index).cloned()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        match components.len() {
+            0 => None,
+            1 => components.pop(),
+            _ => Some(Constant::Composite(components)),
+        }
+    }
+
+    fn layout_query(
+        &self,
+        query: &LayoutQuery,
+        ty: TypeId,
+        rules: Option<Id<Identifier>>,
+    ) -> Result<Value, EvalProblem> {
+        let rules = match rules {
+            Some(name) => {
+                let name = &self.hir.identifiers[name];
+                LayoutRules::from_name(name)
+                    .ok_or_else(|| EvalProblem::UnknownRules(name.clone()))?
+            }
+            None => BufferClass::Storage.default_rules(),
+        };
+        let type_name = || self.ty.display_type(ty).to_string();
+        let layout = self
+            .ty
+            .layout(ty, rules)
+            .ok_or_else(|| EvalProblem::NoLayout(type_name()))?;
+
+        let bytes = match query {
+            LayoutQuery::Size if layout.runtime_sized => {
+                return Err(EvalProblem::RuntimeSized(type_name()));
+            }
+            LayoutQuery::Size => layout.size,
+            LayoutQuery::Align => layout.align,
+            LayoutQuery::Offset(field) => {
+                let name = &self.hir.identifiers[*field];
+                let fields = match self.ty.types.get(self.ty.strip_distinct(ty)) {
+                    Some(Type::Record { fields }) => fields.as_slice(),
+                    _ => &[],
+                };
+                let index = fields
+                    .iter()
+                    .position(|(field, _)| self.ty.types.name(*field) == name)
+                    .ok_or_else(|| EvalProblem::UnknownField {
+                        ty: type_name(),
+                        field: name.clone(),
+                    })?;
+                layout.offsets[index]
+            }
+        };
+        u32::try_from(bytes)
+            .map(Value::UInt)
+            .map_err(|_| EvalProblem::Overflow)
+    }
+
+    fn constant(
+        &mut self,
+        def: Id<VariableDef>,
+        loc: FileLocation,
+    ) -> Result<Constant, (FileLocation, EvalProblem)> {
+        if let Some(value) = self.consts.get(&def) {
+            return Ok(value.clone());
+        }
+        let var = &self.hir.variable_defs[def];
+        let name = self.hir.identifiers[var.name].clone();
+        if layout::buffer_class(self.hir, def).is_some() {
+            return Err((loc, EvalProblem::Buffer(name)));
+        }
+        let rhs = match var.rhs {
+            Some(rhs) => rhs,
+            None => return Err((loc, EvalProblem::NoValue(name))),
+        };
+        if self.evaluating.contains(&def) {
+            return Err((loc, EvalProblem::Cycle(name)));
+        }
+
+        self.evaluating.push(def);
+        let value = self.eval_constant(rhs);
+        self.evaluating.pop();
+        let value = self.declared(value?, rhs, var.type_, loc)?;
+        self.consts.insert(def, value.clone());
+        Ok(value)
+    }
+
+    fn prim_op(
+        &mut self,
+        id: Id<PrimitiveOp>,
+        loc: FileLocation,
+    ) -> Result<Value, (FileLocation, EvalProblem)> {
+        use PrimitiveOp as PO;
+
+        let (a, b) = match &self.hir.prim_ops[id] {
+            PO::Neg(a) | PO::Pos(a) => (*a, None),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => (*a, Some(*b)),
+            PO::Constructor { .. } => {
+                return Err((loc, EvalProblem::Unsupported("vectors as scalars")));
+            }
+        };
+        let mut a_value = self.eval(a)?;
+        let mut b_value = match b {
+            Some(b) => Some(self.eval(b)?),
+            None => None,
+        };
+        if let (Some(b), Some(b_value)) = (b, &mut b_value) {
+            let overflow = (loc, EvalProblem::Overflow);
+            if self.is_untyped_literal(a) {
+                a_value = cast(a_value, Some(value_type(*b_value))).ok_or(overflow)?;
+            } else if self.is_untyped_literal(b) {
+                *b_value = cast(*b_value, Some(value_type(a_value))).ok_or(overflow)?;
+            }
+        }
+        let (a, b) = (a_value, b_value);
+        operation(&self.hir.prim_ops[id], a, b).map_err(|problem| (loc, problem))
+    }
+
+    /// The value of a literal with the type the checker gave it, `None` if
+    /// it doesn't fit into the type.
+    fn literal(&self, id: Id<Expression>, literal: &Literal) -> Option<Value> {
+        let suffix = match literal {
+            Literal::Integer(_, suffix) | Literal::Float(_, suffix) => *suffix,
+            _ => None,
+        };
+        let ty = self.scalar_type(id).or_else(|| {
+            suffix.map(|suffix| match suffix {
+                LiteralSuffix::Int => Type::Int,
+                LiteralSuffix::UInt => Type::UInt,
+                LiteralSuffix::Long => Type::Long,
+                LiteralSuffix::ULong => Type::ULong,
+                LiteralSuffix::Float => Type::Float,
+                LiteralSuffix::Double => Type::Double,
+                LiteralSuffix::Radians => Type::Radians,
+                LiteralSuffix::Degrees => Type::Degrees,
+            })
+        });
+        match (literal, ty) {
+            (Literal::Bool(b), _) => Some(Value::Bool(*b)),
+            (Literal::Integer(i, _), Some(Type::UInt)) => u32::try_from(*i).ok().map(Value::UInt),
+            (Literal::Integer(i, _), Some(Type::Long)) => i64::try_from(*i).ok().map(Value::Long),
+            (Literal::Integer(i, _), Some(Type::ULong)) => u64::try_from(*i).ok().map(Value::ULong),
+            (
+                Literal::Integer(i, _),
+                Some(Type::Float | Type::Half | Type::Radians | Type::Degrees),
+            ) => Some(Value::Float(*i as f32)),
+            (Literal::Integer(i, _), Some(Type::Double)) => Some(Value::Double(*i as f64)),
+            (Literal::Integer(i, _), _) => i32::try_from(*i).ok().map(Value::Int),
+            (Literal::Float(x, _), Some(Type::Double)) => Some(Value::Double(*x)),
+            (Literal::Float(x, _), _) => Some(Value::Float(*x as f32)),
+            (Literal::String(_), _) => None,
+        }
+    }
+
+    /// The scalar type of an expression, looking through distinct types.
+    fn scalar_type(&self, id: Id<Expression>) -> Option<Type> {
+        let mut ty = self.ty.types.get(*self.ty.expr_types.get(&id)?)?;
+        while let Type::Distinct { inner, .. } = ty {
+            ty = self.ty.types.get(*inner)?;
+        }
+        Some(ty.clone())
+    }
+
+    /// Whether an expression is a literal without suffix whose type isn't
+    /// known yet.
+    fn is_untyped_literal(&self, id: Id<Expression>) -> bool {
+        let untyped = matches!(
+            self.hir.expressions[id],
+            Expression::Literal(Literal::Integer(_, None) | Literal::Float(_, None))
+        );
+        untyped && !self.ty.expr_types.contains_key(&id)
+    }
+}
+
+/// The value of an integer, `None` for other values.
+fn integer(value: Value) -> Option<i128> {
+    match value {
+        Value::Int(i) => Some(i128::from(i)),
+        Value::UInt(u) => Some(i128::from(u)),
+        Value::Long(i) => Some(i128::from(i)),
+        Value::ULong(u) => Some(i128::from(u)),
+        _ => None,
+    }
+}
+
+/// The type of a value, floats are `float`s.
+fn value_type(value: Value) -> Type {
+    match value {
+        Value::Bool(_) => Type::Bool,
+        Value::Int(_) => Type::Int,
+        Value::UInt(_) => Type::UInt,
+        Value::Long(_) => Type::Long,
+        Value::ULong(_) => Type::ULong,
+        Value::Float(_) => Type::Float,
+        Value::Double(_) => Type::Double,
+    }
+}
+
+/// The scalar type of a type reference, if it is a scalar primitive type.
+fn declared_scalar(ty: &TypeReference) -> Option<Type> {
+    match ty {
+        TypeReference::Primitive(PrimitiveType::Bool) => Some(Type::Bool),
+        TypeReference::Primitive(PrimitiveType::Int) => Some(Type::Int),
+        TypeReference::Primitive(PrimitiveType::UInt) => Some(Type::UInt),
+        TypeReference::Primitive(PrimitiveType::Long) => Some(Type::Long),
+        TypeReference::Primitive(PrimitiveType::ULong) => Some(Type::ULong),
+        TypeReference::Primitive(PrimitiveType::Float) => Some(Type::Float),
+        TypeReference::Primitive(PrimitiveType::Half) => Some(Type::Half),
+        TypeReference::Primitive(PrimitiveType::Double) => Some(Type::Double),
+        TypeReference::Primitive(PrimitiveType::Radians) => Some(Type::Radians),
+        TypeReference::Primitive(PrimitiveType::Degrees) => Some(Type::Degrees),
+        _ => None,
+    }
+}
+
+/// The layout queries in an expression, and in the values of the constants
+/// it uses if their resolutions are given, with whether they were found in
+/// the value of a constant.
+pub(crate) fn layout_queries(
+    hir: &hir::Context,
+    resolutions: Option<&ResolutionTable>,
+    id: Id<Expression>,
+    in_constant: bool,
+    seen: &mut HashSet<Id<VariableDef>>,
+    queries: &mut Vec<(Id<Expression>, bool)>,
+) {
+    use PrimitiveOp as PO;
+
+    let walk = |id, seen: &mut HashSet<_>, queries: &mut Vec<_>| {
+        layout_queries(hir, resolutions, id, in_constant, seen, queries)
+    };
+    match &hir.expressions[id] {
+        Expression::Literal(_) => {}
+        Expression::LayoutQuery { .. } => queries.push((id, in_constant)),
+        Expression::Variable(name) => {
+            if let Some(Symbol::Constant(def)) = resolutions.and_then(|r| r.symbol(*name)) {
+                if let (true, Some(rhs)) = (seen.insert(def), hir.variable_defs[def].rhs) {
+                    layout_queries(hir, resolutions, rhs, true, seen, queries);
+                }
+            }
+        }
+        Expression::PrimitiveOp(op) => match &hir.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => walk(*e, seen, queries),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                walk(*a, seen, queries);
+                walk(*b, seen, queries);
+            }
+            PO::Constructor {
+                pos_args, nam_args, ..
+            } => {
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    walk(*e, seen, queries);
+                }
+            }
+        },
+        Expression::Call {
+            pos_args, nam_args, ..
+        } => {
+            for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                walk(*e, seen, queries);
+            }
+        }
+        Expression::Field { base, .. } | Expression::As { base, .. } => walk(*base, seen, queries),
+        Expression::Index { base, index } => {
+            walk(*base, seen, queries);
+            walk(*index, seen, queries);
+        }
+        Expression::Slice { base, lo, hi } => {
+            walk(*base, seen, queries);
+            walk(*lo, seen, queries);
+            walk(*hi, seen, queries);
+        }
+        Expression::Array(elements) => {
+            for e in elements {
+                walk(*e, seen, queries);
+            }
+        }
+    }
+}
+
+impl Context {
+    /// The number of elements of an array whose size is an expression.
+    ///
+    /// The types of the layout queries in the expression are recorded first,
+    /// those in the values of constants aren't affected by the generic
+    /// arguments of the array.
+    pub(crate) fn array_size(
+        &mut self,
+        hir: &hir::Context,
+        size: Id<Expression>,
+        subst: &HashMap<&str, TypeId>,
+    ) -> Result<usize, Error> {
+        let mut queries = vec![];
+        let resolutions = Some(&self.resolutions);
+        layout_queries(
+            hir,
+            resolutions,
+            size,
+            false,
+            &mut HashSet::new(),
+            &mut queries,
+        );
+        for (query, in_constant) in queries {
+            let ty_ref = match &hir.expressions[query] {
+                Expression::LayoutQuery { ty, .. } => *ty,
+                _ => continue,
+            };
+            let ty = match in_constant {
+                true => self.ty_ref(hir, ty_ref, &HashMap::new())?,
+                false => self.ty_ref(hir, ty_ref, subst)?,
+            };
+            self.layout_types.insert(query, ty);
+        }
+
+        let value = Evaluator::new(self, hir)
+            .eval(size)
+            .map_err(|(expr, problem)| Error::ConstEvaluation { expr, problem })?;
+        match value {
+            Value::Int(n) if n > 0 => Ok(n as usize),
+            Value::UInt(n) if n > 0 => Ok(n as usize),
+            Value::Long(n) if n > 0 => Ok(n as usize),
+            Value::ULong(n) if n > 0 => Ok(n as usize),
+            _ => Err(Error::ConstEvaluation {
+                expr: hir.expression_fcs[&size],
+                problem: EvalProblem::ArraySize(value),
+            }),
+        }
+    }
+}
+
+/// The result of an operator on values of the same type.
+fn operation(op: &PrimitiveOp, a: Value, b: Option<Value>) -> Result<Value, EvalProblem> {
+    use PrimitiveOp as PO;
+    use Value::*;
+
+    let overflow = |value: Option<Value>| value.ok_or(EvalProblem::Overflow);
+    let b = match b {
+        Some(b) => b,
+        None => {
+            return match (op, a) {
+                (PO::Pos(_), a) => Ok(a),
+                (PO::Neg(_), Int(a)) => overflow(a.checked_neg().map(Int)),
+                (PO::Neg(_), Long(a)) => overflow(a.checked_neg().map(Long)),
+                (PO::Neg(_), Float(a)) => Ok(Float(-a)),
+                (PO::Neg(_), Double(a)) => Ok(Double(-a)),
+                _ => Err(EvalProblem::Unsupported("negated values of this type")),
+            };
+        }
+    };
+
+    let comparison = match (a, b) {
+        (Int(a), Int(b)) => a.partial_cmp(&b),
+        (UInt(a), UInt(b)) => a.partial_cmp(&b),
+        (Long(a), Long(b)) => a.partial_cmp(&b),
+        (ULong(a), ULong(b)) => a.partial_cmp(&b),
+        (Float(a), Float(b)) => a.partial_cmp(&b),
+        (Double(a), Double(b)) => a.partial_cmp(&b),
+        (Bool(a), Bool(b)) => a.partial_cmp(&b),
+        _ => {
+            return Err(EvalProblem::Unsupported(
+                "operations on values of different types",
+            ))
+        }
+    };
+    let compare =
+        |holds: fn(std::cmp::Ordering) -> bool| Ok(Bool(comparison.map(holds).unwrap_or(false)));
+    match op {
+        PO::Gt(..) => return compare(|o| o.is_gt()),
+        PO::Gte(..) => return compare(|o| o.is_ge()),
+        PO::Lt(..) => return compare(|o| o.is_lt()),
+        PO::Lte(..) => return compare(|o| o.is_le()),
+        PO::Eq(..) => return compare(|o| o.is_eq()),
+        PO::Neq(..) => return Ok(Bool(!comparison.map(|o| o.is_eq()).unwrap_or(false))),
+        _ => {}
+    }
+
+    let divisor_is_zero = matches!(b, Int(0) | UInt(0) | Long(0) | ULong(0));
+    if matches!(op, PO::Div(..) | PO::Mod(..)) && divisor_is_zero {
+        return Err(EvalProblem::DivisionByZero);
+    }
+    match (op, a, b) {
+        (PO::Add(..), Int(a), Int(b)) => overflow(a.checked_add(b).map(Int)),
+        (PO::Sub(..), Int(a), Int(b)) => overflow(a.checked_sub(b).map(Int)),
+        (PO::Mul(..), Int(a), Int(b)) => overflow(a.checked_mul(b).map(Int)),
+        (PO::Div(..), Int(a), Int(b)) => overflow(a.checked_div(b).map(Int)),
+        (PO::Mod(..), Int(a), Int(b)) => overflow(a.checked_rem(b).map(Int)),
+        (PO::Add(..), UInt(a), UInt(b)) => overflow(a.checked_add(b).map(UInt)),
+        (PO::Sub(..), UInt(a), UInt(b)) => overflow(a.checked_sub(b).map(UInt)),
+        (PO::Mul(..), UInt(a), UInt(b)) => overflow(a.checked_mul(b).map(UInt)),
+        (PO::Div(..), UInt(a), UInt(b)) => overflow(a.checked_div(b).map(UInt)),
+        (PO::Mod(..), UInt(a), UInt(b)) => overflow(a.checked_rem(b).map(UInt)),
+        (PO::Add(..), Long(a), Long(b)) => overflow(a.checked_add(b).map(Long)),
+        (PO::Sub(..), Long(a), Long(b)) => overflow(a.checked_sub(b).map(Long)),
+        (PO::Mul(..), Long(a), Long(b)) => overflow(a.checked_mul(b).map(Long)),
+        (PO::Div(..), Long(a), Long(b)) => overflow(a.checked_div(b).map(Long)),
+        (PO::Mod(..), Long(a), Long(b)) => overflow(a.checked_rem(b).map(Long)),
+        (PO::Add(..), ULong(a), ULong(b)) => overflow(a.checked_add(b).map(ULong)),
+        (PO::Sub(..), ULong(a), ULong(b)) => overflow(a.checked_sub(b).map(ULong)),
+        (PO::Mul(..), ULong(a), ULong(b)) => overflow(a.checked_mul(b).map(ULong)),
+        (PO::Div(..), ULong(a), ULong(b)) => overflow(a.checked_div(b).map(ULong)),
+        (PO::Mod(..), ULong(a), ULong(b)) => overflow(a.checked_rem(b).map(ULong)),
+        (PO::Add(..), Float(a), Float(b)) => Ok(Float(a + b)),
+        (PO::Sub(..), Float(a), Float(b)) => Ok(Float(a - b)),
+        (PO::Mul(..), Float(a), Float(b)) => Ok(Float(a * b)),
+        (PO::Div(..), Float(a), Float(b)) => Ok(Float(a / b)),
+        (PO::Add(..), Double(a), Double(b)) => Ok(Double(a + b)),
+        (PO::Sub(..), Double(a), Double(b)) => Ok(Double(a - b)),
+        (PO::Mul(..), Double(a), Double(b)) => Ok(Double(a * b)),
+        (PO::Div(..), Double(a), Double(b)) => Ok(Double(a / b)),
+        // `%` of floats rounds differently on the targets
+        _ => Err(EvalProblem::Unsupported("operations of this kind")),
+    }
+}
+
+/// A value converted with `as`, `None` if a float doesn't fit into an
+/// integer type.
+fn cast(value: Value, to: Option<Type>) -> Option<Value> {
+    let integer = match value {
+        Value::Bool(b) => Some(i128::from(b)),
+        Value::Int(i) => Some(i128::from(i)),
+        Value::UInt(u) => Some(i128::from(u)),
+        Value::Long(i) => Some(i128::from(i)),
+        Value::ULong(u) => Some(i128::from(u)),
+        Value::Float(_) | Value::Double(_) => None,
+    };
+    let float = match value {
+        Value::Float(x) => f64::from(x),
+        Value::Double(x) => x,
+        _ => integer? as f64,
+    };
+    // integers are converted like `static_cast` does, keeping their low bits,
+    // floats have to fit into the range of the type
+    let fits = |min: f64, max: f64| (min..=max).contains(&float);
+    match to? {
+        Type::Bool => Some(Value::Bool(float != 0.0)),
+        Type::Int => match integer {
+            Some(i) => Some(Value::Int(i as i32)),
+            None if fits(f64::from(i32::MIN), f64::from(i32::MAX)) => {
+                Some(Value::Int(float as i32))
+            }
+            None => None,
+        },
+        Type::UInt => match integer {
+            Some(i) => Some(Value::UInt(i as u32)),
+            None if fits(0.0, f64::from(u32::MAX)) => Some(Value::UInt(float as u32)),
+            None => None,
+        },
+        Type::Long => match integer {
+            Some(i) => Some(Value::Long(i as i64)),
+            None if fits(i64::MIN as f64, i64::MAX as f64) => Some(Value::Long(float as i64)),
+            None => None,
+        },
+        Type::ULong => match integer {
+            Some(i) => Some(Value::ULong(i as u64)),
+            None if fits(0.0, u64::MAX as f64) => Some(Value::ULong(float as u64)),
+            None => None,
+        },
+        Type::Float | Type::Half | Type::Radians | Type::Degrees => {
+            Some(Value::Float(float as f32))
+        }
+        Type::Double => Some(Value::Double(float)),
+        _ => None,
+    }
+}
+
+/// Evaluate the static assertions of a module that type checks.
+pub(crate) fn check_static_asserts(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut evaluator = Evaluator::new(ty_ctx, hir_ctx);
+    let mut errs = vec![];
+    for id in &module.static_asserts {
+        let assert = &hir_ctx.static_asserts[*id];
+        let condition = hir_ctx.expression_fcs[&assert.condition];
+        match evaluator.eval(assert.condition) {
+            Ok(Value::Bool(true)) => {}
+            Ok(Value::Bool(false)) => errs.push(Error::StaticAssertionFailed {
+                message: assert.message.clone(),
+                condition,
+            }),
+            Ok(value) => errs.push(Error::ConstEvaluation {
+                expr: condition,
+                problem: EvalProblem::NotBool(value),
+            }),
+            Err((expr, problem)) => errs.push(Error::ConstEvaluation { expr, problem }),
+        }
+    }
+    errs
+}
+
+/// Whether the value of a constant is evaluated once the module type checks,
+/// so that the backends emit it as its value: constants of records and
+/// arrays, and constants whose values call functions of the module or
+/// functions derived from records.
+pub fn is_evaluated_constant(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    def: Id<VariableDef>,
+) -> bool {
+    let var = &hir_ctx.variable_defs[def];
+    let rhs = match var.rhs {
+        Some(rhs) => rhs,
+        None => return false,
+    };
+    let is_composite = ty_ctx
+        .consts
+        .get(&hir_ctx.identifiers[var.name])
+        .is_some_and(|sig| ty_ctx.is_composite(sig.type_));
+    let mut calls = vec![];
+    uniformity::expression_calls(hir_ctx, rhs, &mut calls);
+    is_composite
+        || calls
+            .into_iter()
+            .any(|call| match &hir_ctx.expressions[call] {
+                Expression::Call { name, .. } => matches!(
+                    ty_ctx.resolutions.get(*name),
+                    Some(Resolution::Symbol(Symbol::Function(_)) | Resolution::Derived { .. })
+                ),
+                _ => false,
+            })
+}
+
+/// Evaluate the constants that are emitted as their values.
+pub(crate) fn check_evaluated_constants(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut evaluator = Evaluator::new(ty_ctx, hir_ctx);
+    let mut errs = vec![];
+    for def in &module.consts {
+        let rhs = match hir_ctx.variable_defs[*def].rhs {
+            Some(rhs) if is_evaluated_constant(ty_ctx, hir_ctx, *def) => rhs,
+            _ => continue,
+        };
+        if let Err((expr, problem)) = evaluator.eval_constant(rhs) {
+            errs.push(Error::ConstEvaluation { expr, problem });
+        }
+    }
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_overflow() {
+        let mut exprs = id_arena::Arena::<Expression>::new();
+        let operand = exprs.alloc(Expression::Literal(Literal::Bool(true)));
+        let add = PrimitiveOp::Add(operand, operand);
+        assert_eq!(
+            operation(&add, Value::Int(i32::MAX), Some(Value::Int(1))),
+            Err(EvalProblem::Overflow)
+        );
+        assert_eq!(
+            operation(&add, Value::UInt(1), Some(Value::UInt(2))),
+            Ok(Value::UInt(3))
+        );
+        assert_eq!(cast(Value::Float(-1.5), Some(Type::UInt)), None);
+        assert_eq!(
+            cast(Value::Int(-1), Some(Type::UInt)),
+            Some(Value::UInt(u32::MAX))
+        );
+        assert_eq!(
+            operation(&add, Value::Long(i64::MAX), Some(Value::Long(1))),
+            Err(EvalProblem::Overflow)
+        );
+        assert_eq!(
+            operation(
+                &add,
+                Value::ULong(u64::from(u32::MAX)),
+                Some(Value::ULong(1))
+            ),
+            Ok(Value::ULong(1 << 32))
+        );
+        assert_eq!(
+            cast(Value::Int(-1), Some(Type::ULong)),
+            Some(Value::ULong(u64::MAX))
+        );
+        assert_eq!(
+            cast(Value::ULong(1 << 32), Some(Type::UInt)),
+            Some(Value::UInt(0))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Assertions and debug printing.
+//!
+//! `assert(cond)` reports a failed assertion when `cond` is false, and
+//! `assert(cond, "message")` reports the message instead.
+//! `debug_print("hit {} at {}", id, t)` prints the format with the values of
+//! the arguments in place of the `{}`s, `{{` and `}}` print a brace.
+//! Booleans, numbers and vectors of them can be printed.
+//!
+//! Neither stops the invocation. The backends emit them with the debug
+//! output of the target, and leave them out when compiling for release, see
+//! [`Context::release`], or for targets without debug output.
+
+use std::fmt;
+
+use crate::{Context, Type, TypeId, VecSize};
+
+/// What is wrong with a call of `assert` or `debug_print`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugProblem {
+    /// the condition of an assertion of another type than `bool`
+    Condition(String),
+    /// `assert` or `debug_print` with another number of arguments
+    Arity {
+        expected: &'static str,
+        found: usize,
+    },
+    /// a message or format that isn't a string literal
+    NotString,
+    /// a `{` or `}` of the format that isn't part of a `{}`, `{{` or `}}`
+    UnmatchedBrace,
+    /// a format with another number of `{}` than values
+    ValueCount { placeholders: usize, values: usize },
+    /// a value of a type that can't be printed
+    Unprintable(String),
+}
+
+impl fmt::Display for DebugProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugProblem::Condition(type_name) => {
+                write!(f, "expected `bool`, found `{}`", type_name)
+            }
+            DebugProblem::Arity { expected, found } => {
+                write!(f, "expected {} arguments, found {}", expected, found)
+            }
+            DebugProblem::NotString => write!(f, "expected a string literal"),
+            DebugProblem::UnmatchedBrace => write!(f, "unmatched brace in the format"),
+            DebugProblem::ValueCount {
+                placeholders,
+                values,
+            } => write!(f, "{} placeholders for {} values", placeholders, values),
+            DebugProblem::Unprintable(type_name) => {
+                write!(f, "`{}` can't be printed", type_name)
+            }
+        }
+    }
+}
+
+/// A part of the format of `debug_print`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatPiece {
+    /// text printed as it is, with the escaped braces
+    Text(String),
+    /// a `{}` where the next value is printed
+    Value,
+}
+
+/// The parts of the format of `debug_print`, `None` if it has a brace that
+/// isn't part of a `{}`, `{{` or `}}`.
+pub fn format_pieces(format: &str) -> Option<Vec<FormatPiece>> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                text.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                if !text.is_empty() {
+                    pieces.push(FormatPiece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(FormatPiece::Value);
+            }
+            ('{' | '}', _) => return None,
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(FormatPiece::Text(text));
+    }
+    Some(pieces)
+}
+
+/// The scalar type of a value that can be printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintScalar {
+    Bool,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    Float,
+    Double,
+    Half,
+}
+
+impl Context {
+    /// How values of the type are printed: their scalar type, and the
+    /// number of components of vectors. `None` for types that can't be
+    /// printed.
+    pub fn printable(&self, ty: TypeId) -> Option<(PrintScalar, Option<VecSize>)> {
+        let ty = self.strip_normalized(self.strip_distinct(ty));
+        let scalar = match self.types.get(ty)? {
+            Type::Bool | Type::BoolVec { .. } => PrintScalar::Bool,
+            Type::Int | Type::IntVec { .. } => PrintScalar::Int,
+            Type::UInt | Type::UIntVec { .. } => PrintScalar::UInt,
+            Type::Long | Type::LongVec { .. } => PrintScalar::Long,
+            Type::ULong | Type::ULongVec { .. } => PrintScalar::ULong,
+            Type::Float | Type::FloatVec { .. } | Type::Radians | Type::Degrees => {
+                PrintScalar::Float
+            }
+            Type::Double | Type::DoubleVec { .. } => PrintScalar::Double,
+            Type::Half | Type::HalfVec { .. } => PrintScalar::Half,
+            _ => return None,
+        };
+        Some((scalar, self.vector_size(ty)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_of_formats() {
+        assert_eq!(
+            format_pieces("t = {}, {{}}").unwrap(),
+            vec![
+                FormatPiece::Text("t = ".to_string()),
+                FormatPiece::Value,
+                FormatPiece::Text(", {}".to_string()),
+            ]
+        );
+        assert_eq!(format_pieces("{}{}").unwrap().len(), 2);
+        assert!(format_pieces("{x}").is_none());
+        assert!(format_pieces("}").is_none());
+    }
+}
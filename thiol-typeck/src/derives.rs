@@ -0,0 +1,291 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Functions derived from records.
+//!
+//! `@derive(new, eq)` on the definition of a record adds functions named
+//! after the record in snake case: `light_new(position, range)` builds a
+//! `Light` from the values of its fields like its constructor does, and
+//! `light_eq(a, b)` compares two lights field by field, including the
+//! components of vectors and the elements of arrays. Calls of them are
+//! resolved like calls of the functions of the module, their signatures are
+//! added to [`Context::derived_functions`] once the types are defined.
+//!
+//! Only records without generic parameters derive functions, and `eq` only
+//! records whose fields can be compared, which resources and atomics can't.
+
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, TypeDefinition, TypeDefinitionRhs};
+use id_arena::Id;
+
+use crate::{Context, Error, Type, TypeId};
+
+/// A function `@derive` adds for a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Derive {
+    /// `light_new(position, range)`, the constructor of the record
+    New,
+    /// `light_eq(a, b)`, whether two records have the same fields
+    Eq,
+}
+
+/// The signature of a function derived from a record
+#[derive(Debug, Clone)]
+pub struct DerivedFunction {
+    pub derive: Derive,
+    pub def: Id<TypeDefinition>,
+    pub record: TypeId,
+    /// the parameters, the fields of the record for `new`
+    pub args: Vec<(String, TypeId)>,
+    pub ret: TypeId,
+}
+
+/// Why a record can't derive a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeriveProblem {
+    /// a name other than `new` and `eq`
+    Unknown(String),
+    /// the type definition isn't a record
+    NotRecord,
+    /// the record has generic parameters
+    Generic,
+    /// `eq` for a record with a field that can't be compared
+    Incomparable { field: String, type_name: String },
+    /// a function of the module has the name of the derived function
+    Conflict(String),
+}
+
+/// Why a call of a derived function is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerivedCallProblem {
+    /// `eq` with other than two values
+    Arity { expected: usize, found: usize },
+    /// a value of another type than the record
+    ValueType { expected: String, found: String },
+}
+
+impl Derive {
+    fn from_name(name: &str) -> Option<Derive> {
+        match name {
+            "new" => Some(Derive::New),
+            "eq" => Some(Derive::Eq),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Derive::New => "new",
+            Derive::Eq => "eq",
+        }
+    }
+}
+
+impl fmt::Display for DerivedCallProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerivedCallProblem::Arity { expected, found } => {
+                write!(f, "expected {} values, found {}", expected, found)
+            }
+            DerivedCallProblem::ValueType { expected, found } => {
+                write!(f, "expected `{}`, found `{}`", expected, found)
+            }
+        }
+    }
+}
+
+/// The name of a function derived from a record, `PointLight` derives
+/// `point_light_new`. The module of a qualified name is kept.
+pub fn derived_name(record: &str, derive: Derive) -> String {
+    let (module, name) = match record.rfind("::") {
+        Some(end) => record.split_at(end + 2),
+        None => ("", record),
+    };
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = module.to_string();
+    for (i, c) in chars.iter().enumerate() {
+        // a word starts at an upper case letter after a lower case one, or
+        // at the last upper case letter of an acronym like `HDRColour`
+        let prev_lower = i > 0 && chars[i - 1].is_lowercase();
+        let acronym_end = i > 0
+            && chars[i - 1].is_uppercase()
+            && chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+        if c.is_uppercase() && (prev_lower || acronym_end) {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    format!("{}_{}", snake, derive.name())
+}
+
+/// The `@derive` attributes of a type definition.
+fn derive_attributes(
+    hir_ctx: &hir::Context,
+    def: Id<TypeDefinition>,
+) -> impl Iterator<Item = Id<hir::Attribute>> + '_ {
+    hir_ctx.type_defs[def]
+        .attrs
+        .iter()
+        .copied()
+        .filter(move |attr| hir_ctx.identifiers[hir_ctx.attributes[*attr].name] == "derive")
+}
+
+/// The functions a type definition derives, with the names in the
+/// attributes, including the unknown ones.
+fn derives(
+    hir_ctx: &hir::Context,
+    def: Id<TypeDefinition>,
+) -> Vec<(Id<Expression>, Result<Derive, String>)> {
+    derive_attributes(hir_ctx, def)
+        .flat_map(|attr| &hir_ctx.attributes[attr].pos_args)
+        .map(|arg| {
+            let name = match &hir_ctx.expressions[*arg] {
+                Expression::Variable(name) => hir_ctx.identifiers[*name].clone(),
+                _ => String::new(),
+            };
+            (*arg, Derive::from_name(&name).ok_or(name))
+        })
+        .collect()
+}
+
+/// The names of the functions a type definition derives, known before the
+/// types are checked so that calls of them can be resolved.
+pub fn derived_names(hir_ctx: &hir::Context, def: Id<TypeDefinition>) -> Vec<(Derive, String)> {
+    let record = &hir_ctx.identifiers[hir_ctx.type_defs[def].name];
+    derives(hir_ctx, def)
+        .into_iter()
+        .filter_map(|(_, derive)| derive.ok())
+        .map(|derive| (derive, derived_name(record, derive)))
+        .collect()
+}
+
+impl Context {
+    /// Whether values of the type can be compared by their fields,
+    /// components and elements.
+    fn is_comparable(&self, ty: TypeId) -> bool {
+        match self.types.get(self.strip_distinct(ty)) {
+            Some(Type::Array { base, .. }) => self.is_comparable(*base),
+            Some(Type::Record { fields }) => fields.iter().all(|(_, ty)| self.is_comparable(*ty)),
+            Some(
+                Type::AtomicInt
+                | Type::AtomicUInt
+                | Type::OpenArray { .. }
+                | Type::Image { .. }
+                | Type::Texture { .. }
+                | Type::Sampler { .. }
+                | Type::AccelerationStructure
+                | Type::GenericParam { .. }
+                | Type::Var(..)
+                | Type::Error,
+            )
+            | None => false,
+            Some(_) => true,
+        }
+    }
+}
+
+/// Add the signatures of the functions derived from the records of the
+/// module.
+pub(crate) fn add_derived_functions(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+    for id in &module.types {
+        let def = &hir_ctx.type_defs[*id];
+        let name = &hir_ctx.identifiers[def.name];
+        let attribute = match derive_attributes(hir_ctx, *id).next() {
+            Some(attr) => hir_ctx.attribute_fcs[&attr],
+            None => continue,
+        };
+        let mut invalid = |loc: FileLocation, problem| {
+            errs.push(Error::InvalidDerive {
+                loc,
+                record: name.clone(),
+                problem,
+            })
+        };
+
+        let is_record = matches!(
+            hir_ctx.type_def_rhss[def.rhs],
+            TypeDefinitionRhs::Record { .. }
+        );
+        if !is_record {
+            invalid(attribute, DeriveProblem::NotRecord);
+            continue;
+        }
+        if !def.generics.is_empty() {
+            invalid(attribute, DeriveProblem::Generic);
+            continue;
+        }
+        let record = match ty_ctx.complete_types.get(name) {
+            Some(record) => *record,
+            // records with errors are reported where they are defined
+            None => continue,
+        };
+        let fields = ty_ctx.record_fields(record).unwrap_or_default();
+
+        for (arg, derive) in derives(hir_ctx, *id) {
+            let loc = hir_ctx.expression_fcs[&arg];
+            let derive = match derive {
+                Ok(derive) => derive,
+                Err(unknown) => {
+                    invalid(loc, DeriveProblem::Unknown(unknown));
+                    continue;
+                }
+            };
+            let function = derived_name(name, derive);
+            if ty_ctx.function_sigs.contains_key(&function) {
+                invalid(loc, DeriveProblem::Conflict(function));
+                continue;
+            }
+            let (args, ret) = match derive {
+                Derive::New => (fields.clone(), record),
+                Derive::Eq => {
+                    let incomparable = fields.iter().find(|(_, ty)| !ty_ctx.is_comparable(*ty));
+                    if let Some((field, ty)) = incomparable {
+                        let type_name = ty_ctx.display_type(*ty).to_string();
+                        let field = field.clone();
+                        invalid(loc, DeriveProblem::Incomparable { field, type_name });
+                        continue;
+                    }
+                    let args = vec![("a".to_string(), record), ("b".to_string(), record)];
+                    (args, ty_ctx.add_or_get_type(Type::Bool))
+                }
+            };
+            ty_ctx.derived_functions.insert(
+                function,
+                DerivedFunction {
+                    derive,
+                    def: *id,
+                    record,
+                    args,
+                    ret,
+                },
+            );
+        }
+    }
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snake_case_names() {
+        assert_eq!(derived_name("Light", Derive::New), "light_new");
+        assert_eq!(derived_name("PointLight", Derive::Eq), "point_light_eq");
+        assert_eq!(derived_name("HDRColour", Derive::Eq), "hdr_colour_eq");
+        assert_eq!(derived_name("Vertex2D", Derive::New), "vertex2d_new");
+        assert_eq!(
+            derived_name("lights::Spot", Derive::New),
+            "lights::spot_new"
+        );
+    }
+}
@@ -0,0 +1,323 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Assignments of the fields of a record to several targets.
+//!
+//! A function returns several values as a record, which may be a record
+//! without a name like `record { lo: float, hi: float }`. `(lo, hi) :=
+//! bounds(xs);` assigns its fields to the targets in the order of the
+//! fields, so the record has as many fields as there are targets. Every
+//! target is a variable or a field or element of one, with the type of its
+//! field, and no two targets name the same variable or overlapping parts of
+//! it, since it would be unclear which field it ends up with. Swizzles are
+//! the components they name, and an element at an index that isn't a
+//! literal may be any element.
+
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, Literal, Statement};
+use id_arena::Id;
+
+use crate::params::{assignable, NotAssignable};
+use crate::{Context, Error, SpaceCheck, Symbol};
+
+/// Why a destructuring assignment is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DestructureProblem {
+    /// the value isn't a record
+    NotRecord(String),
+    /// another number of targets than the record has fields
+    Count { fields: usize, targets: usize },
+    /// a target of another type than its field
+    TargetType {
+        field: String,
+        expected: String,
+        found: String,
+    },
+    /// a target that isn't a variable
+    NotAssignable(NotAssignable),
+    /// a target that overlaps an earlier target
+    Duplicate { target: String, first: FileLocation },
+}
+
+impl fmt::Display for DestructureProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DestructureProblem::NotRecord(type_name) => {
+                write!(f, "`{}` is not a record", type_name)
+            }
+            DestructureProblem::Count { fields, targets } => {
+                write!(f, "{} fields for {} targets", fields, targets)
+            }
+            DestructureProblem::TargetType {
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` is a `{}`, the target is a `{}`",
+                field, expected, found
+            ),
+            DestructureProblem::NotAssignable(NotAssignable::Constant) => {
+                write!(f, "constants cannot be written to")
+            }
+            DestructureProblem::NotAssignable(NotAssignable::LoopVariable) => {
+                write!(f, "loop variables cannot be written to")
+            }
+            DestructureProblem::NotAssignable(NotAssignable::Value) => {
+                write!(f, "this is a value, not a variable")
+            }
+            DestructureProblem::Duplicate { target, .. } => {
+                write!(f, "`{}` is assigned again here", target)
+            }
+        }
+    }
+}
+
+/// The destructuring assignments in a block and the blocks nested in it.
+fn destructurings(ctx: &hir::Context, block: &[Id<Statement>], found: &mut Vec<Id<Statement>>) {
+    for stmt in block {
+        match &ctx.statements[*stmt] {
+            Statement::Destructure { .. } => found.push(*stmt),
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                destructurings(ctx, then_body, found);
+                destructurings(ctx, else_body, found);
+            }
+            Statement::For { body, .. } => destructurings(ctx, body, found),
+            Statement::Match { arms, .. } => {
+                for arm in arms {
+                    destructurings(ctx, &arm.body, found);
+                }
+            }
+            Statement::Var(_)
+            | Statement::Becomes { .. }
+            | Statement::Return(_)
+            | Statement::Expr(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Discard => {}
+        }
+    }
+}
+
+/// A part of the variable a target assigns
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step<'a> {
+    Field(&'a str),
+    Element(i128),
+    /// an element at an index that isn't a literal
+    AnyElement,
+    /// components of a vector, as a mask of `xyzw`
+    Components(u8),
+}
+
+/// The variable a target assigns and the fields and elements leading to the
+/// assigned part of it.
+fn place<'a>(
+    ty_ctx: &Context,
+    hir_ctx: &'a hir::Context,
+    target: Id<Expression>,
+) -> Option<(Symbol, Vec<Step<'a>>)> {
+    let vector = |base: &Id<Expression>| {
+        ty_ctx
+            .expr_types
+            .get(base)
+            .and_then(|ty| ty_ctx.vector_size(*ty))
+            .is_some()
+    };
+    match &hir_ctx.expressions[target] {
+        Expression::Variable(name) => {
+            let sym = ty_ctx.references.symbol(hir_ctx.identifier_fcs[name])?;
+            Some((sym, vec![]))
+        }
+        Expression::Field { base, name } => {
+            let (sym, mut steps) = place(ty_ctx, hir_ctx, *base)?;
+            let name = &hir_ctx.identifiers[*name];
+            if vector(base) {
+                let components = name
+                    .chars()
+                    .filter_map(|c| "xyzw".find(c).or_else(|| "rgba".find(c)))
+                    .fold(0, |mask, i| mask | 1 << i);
+                steps.push(Step::Components(components));
+            } else {
+                steps.push(Step::Field(name));
+            }
+            Some((sym, steps))
+        }
+        Expression::Index { base, index } => {
+            let (sym, mut steps) = place(ty_ctx, hir_ctx, *base)?;
+            steps.push(match hir_ctx.expressions[*index] {
+                Expression::Literal(Literal::Integer(index, _)) if vector(base) => {
+                    Step::Components(1 << index.clamp(0, 7))
+                }
+                Expression::Literal(Literal::Integer(index, _)) => Step::Element(index),
+                _ if vector(base) => Step::Components(0b1111),
+                _ => Step::AnyElement,
+            });
+            Some((sym, steps))
+        }
+        _ => None,
+    }
+}
+
+/// Whether two parts of a variable overlap, one of them is a part of the
+/// other or they share components. An element at an index that isn't a
+/// literal may be any element.
+fn overlap(a: &[Step<'_>], b: &[Step<'_>]) -> bool {
+    a.iter().zip(b).all(|(x, y)| match (x, y) {
+        (Step::Components(x), Step::Components(y)) => x & y != 0,
+        (Step::AnyElement, Step::Element(_))
+        | (Step::Element(_), Step::AnyElement)
+        | (Step::AnyElement, Step::AnyElement) => true,
+        _ => x == y,
+    })
+}
+
+/// The name of a target in diagnostics.
+fn target_name(hir_ctx: &hir::Context, target: Id<Expression>) -> String {
+    match &hir_ctx.expressions[target] {
+        Expression::Variable(name) => hir_ctx.identifiers[*name].to_string(),
+        Expression::Field { base, name } => {
+            format!(
+                "{}.{}",
+                target_name(hir_ctx, *base),
+                hir_ctx.identifiers[*name]
+            )
+        }
+        Expression::Index { base, index } => match hir_ctx.expressions[*index] {
+            Expression::Literal(Literal::Integer(index, _)) => {
+                format!("{}[{}]", target_name(hir_ctx, *base), index)
+            }
+            _ => format!("{}[..]", target_name(hir_ctx, *base)),
+        },
+        _ => "this".to_string(),
+    }
+}
+
+/// Targets that assign a variable, or a part of one, that an earlier target
+/// assigns as well, with the earlier target.
+fn duplicates(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    lhs: &[Id<Expression>],
+) -> Vec<(Id<Expression>, Id<Expression>)> {
+    let places = lhs
+        .iter()
+        .filter_map(|target| Some((*target, place(ty_ctx, hir_ctx, *target)?)))
+        .collect::<Vec<_>>();
+    let mut found = vec![];
+    for (i, (target, (sym, steps))) in places.iter().enumerate() {
+        let first = places[..i]
+            .iter()
+            .find(|(_, (other, other_steps))| other == sym && overlap(steps, other_steps));
+        if let Some((first, _)) = first {
+            found.push((*target, *first));
+        }
+    }
+    found
+}
+
+/// The problems of a destructuring assignment, with the expression each is
+/// reported at.
+fn problems(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    lhs: &[Id<Expression>],
+    rhs: Id<Expression>,
+) -> Vec<(Id<Expression>, DestructureProblem)> {
+    let mut problems = vec![];
+    for target in lhs {
+        if let Err(reason) = assignable(ty_ctx, hir_ctx, *target) {
+            problems.push((*target, DestructureProblem::NotAssignable(reason)));
+        }
+    }
+    for (target, first) in duplicates(ty_ctx, hir_ctx, lhs) {
+        problems.push((
+            target,
+            DestructureProblem::Duplicate {
+                target: target_name(hir_ctx, target),
+                first: hir_ctx.expression_fcs[&first],
+            },
+        ));
+    }
+
+    // values without a type are reported where they are computed
+    let ty = match ty_ctx.expr_types.get(&rhs) {
+        Some(ty) => *ty,
+        None => return problems,
+    };
+    let fields = match ty_ctx.record_fields(ty) {
+        Some(fields) => fields,
+        None => {
+            let type_name = ty_ctx.display_type(ty).to_string();
+            problems.push((rhs, DestructureProblem::NotRecord(type_name)));
+            return problems;
+        }
+    };
+    if fields.len() != lhs.len() {
+        let (fields, targets) = (fields.len(), lhs.len());
+        problems.push((rhs, DestructureProblem::Count { fields, targets }));
+        return problems;
+    }
+
+    for (target, (field, expected)) in lhs.iter().zip(fields) {
+        let found = match ty_ctx.expr_types.get(target) {
+            Some(found) => *found,
+            None => continue,
+        };
+        let same_space = ty_ctx.space_check == SpaceCheck::Off
+            || ty_ctx.space_mismatch(found, expected).is_none();
+        if !ty_ctx.same_value_type(found, expected) || !same_space {
+            problems.push((
+                *target,
+                DestructureProblem::TargetType {
+                    field,
+                    expected: ty_ctx.display_type(expected).to_string(),
+                    found: ty_ctx.display_type(found).to_string(),
+                },
+            ));
+        }
+    }
+    problems
+}
+
+/// Report destructuring assignments of values that aren't records with a
+/// field for each target, targets that aren't variables of the type of
+/// their field, and targets assigning the same variable twice.
+pub(crate) fn check_destructuring(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut found = vec![];
+    let bodies = module
+        .functions
+        .iter()
+        .map(|id| &hir_ctx.functions[*id].body)
+        .chain(module.programs.iter().map(|id| &hir_ctx.programs[*id].body));
+    for body in bodies {
+        destructurings(hir_ctx, body, &mut found);
+    }
+
+    let mut errs = vec![];
+    for stmt in found {
+        let (lhs, rhs) = match &hir_ctx.statements[stmt] {
+            Statement::Destructure { lhs, rhs } => (lhs, *rhs),
+            _ => continue,
+        };
+        for (e, problem) in problems(ty_ctx, hir_ctx, lhs, rhs) {
+            errs.push(Error::InvalidDestructuring {
+                loc: hir_ctx.expression_fcs[&e],
+                problem,
+            });
+        }
+    }
+    errs
+}
@@ -0,0 +1,2906 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::fmt;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle};
+use thiol_hir::{keywords, FileId, FileLocation};
+
+use crate::angles::AngleUnit;
+use crate::attributes::target_list;
+use crate::casts::CastProblem;
+use crate::composites::CompositeProblem;
+use crate::consteval::EvalProblem;
+use crate::debug_output::DebugProblem;
+use crate::derives::{DeriveProblem, DerivedCallProblem};
+use crate::destructuring::DestructureProblem;
+use crate::geometry;
+use crate::images::{ImageAccess, ImageFormat, ImageTypeProblem};
+use crate::interpolation::InterpolationProblem;
+use crate::layout::{
+    BufferTypeProblem, LayoutAttributeProblem, LayoutRules, LayoutViolationKind,
+    MatrixLayoutProblem,
+};
+use crate::luts::{LutProblem, MAX_LUT_SIZE};
+use crate::matrices::MatrixConstructorProblem;
+use crate::mesh::MeshOutputProblem;
+use crate::optionals::OptionalProblem;
+use crate::params::NotAssignable;
+use crate::profile::Feature;
+use crate::profiling::ProfileProblem;
+use crate::ray_tracing::{self, PayloadProblem};
+use crate::shadowing::Shadowed;
+use crate::slices::SliceProblem;
+use crate::spaces::SpaceTransformProblem;
+use crate::stages::AttributeProblem;
+use crate::textures::{self, SamplingProblem};
+use crate::uniformity::NonUniformReason;
+use crate::{Error, Intrinsic, LintGroup, Warning};
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TypeRedefinition { .. } => write!(f, "type redefinition"),
+            Error::GenericParamaterRedefinition { .. } => {
+                write!(f, "generic parameter redefinition")
+            }
+            Error::RecursiveTypeDefinition { .. } => write!(f, "recursive type definition"),
+            Error::MutuallyRecursiveTypeDefinitions { .. } => {
+                write!(f, "mutually recursive type definitions")
+            }
+            Error::RecursiveFunction { .. } => write!(f, "recursive function"),
+            Error::InstantiationLimit { limit, .. } => {
+                write!(f, "more than {} instances of generic functions", limit)
+            }
+            Error::ConstantTypeMismatch { name, .. } => {
+                write!(f, "value of constant `{}` has the wrong type", name)
+            }
+            Error::StaticAssertionFailed { message, .. } => {
+                write!(f, "static assertion failed: {}", message)
+            }
+            Error::ConstEvaluation {
+                problem: EvalProblem::NotBool(_),
+                ..
+            } => write!(f, "condition of static assertion is not a `bool`"),
+            Error::ConstEvaluation {
+                problem: EvalProblem::ArraySize(_),
+                ..
+            } => write!(f, "invalid array size"),
+            Error::ConstEvaluation { .. } => write!(f, "cannot evaluate expression when compiling"),
+            Error::MutuallyRecursiveFunctions { .. } => write!(f, "mutually recursive functions"),
+            Error::UndefinedType { name, .. } => write!(f, "type `{}` not defined", name),
+            Error::NameConflict {
+                name,
+                previous_kind,
+                kind,
+                ..
+            } => write!(
+                f,
+                "{} `{}` has the same name as a {}",
+                kind, name, previous_kind
+            ),
+            Error::UndefinedVariable { name, .. } => {
+                write!(f, "variable `{}` not defined", name)
+            }
+            Error::UndefinedFunction { name, .. } => {
+                write!(f, "function `{}` not defined", name)
+            }
+            Error::HigherKindedGenericTypeUsed { .. } => {
+                write!(f, "higher kinded generics are not supported")
+            }
+            Error::MismatchedNumberGenericArgs {
+                given, expected, ..
+            } => write!(
+                f,
+                "mismatched number of generic arguments: expected {} but found {}",
+                expected, given
+            ),
+            Error::FieldRedefinition { .. } => write!(f, "field redefinition"),
+            Error::FunctionRedefinition { .. } => write!(f, "function redefinition"),
+            Error::ConstantRedefinition { .. } => write!(f, "constant redefinition"),
+            Error::SpaceRedefinition { .. } => write!(f, "space redefinition"),
+            Error::UndefinedSpace { name, .. } => write!(f, "space `{}` not declared", name),
+            Error::CyclicSpaceHierarchy { .. } => write!(f, "spaces are placed in each other"),
+            Error::InvalidSpaceTransform { space, .. } => {
+                write!(f, "invalid transform into space `{}`", space)
+            }
+            Error::SpaceMismatch { from, to, .. } => {
+                write!(f, "value in space `{}` used in space `{}`", from, to)
+            }
+            Error::ColourEncodingMismatch { srgb: true, .. } => {
+                write!(f, "sRGB encoded colour used as a linear colour")
+            }
+            Error::ColourEncodingMismatch { srgb: false, .. } => {
+                write!(f, "linear colour used as an sRGB encoded colour")
+            }
+            Error::MixedColourEncodings { .. } => {
+                write!(f, "arithmetic on a linear and an sRGB encoded colour")
+            }
+            Error::InvalidNormalizedType { type_name, .. } => {
+                write!(f, "`{}` can't be normalized", type_name)
+            }
+            Error::NotNormalized { .. } => write!(f, "vector not known to be normalized"),
+            Error::AngleUnitMismatch {
+                found, expected, ..
+            } => write!(
+                f,
+                "`{}` used as `{}`",
+                angle_type_name(*found),
+                angle_type_name(*expected)
+            ),
+            Error::MixedAngleUnits { .. } => write!(f, "arithmetic on radians and degrees"),
+            Error::InvalidImageType { problem, .. } => match problem {
+                ImageTypeProblem::UnknownFormat(format) => {
+                    write!(f, "unknown image format `{}`", format)
+                }
+                ImageTypeProblem::UnknownAccess(access) => {
+                    write!(f, "unknown image access `{}`", access)
+                }
+                ImageTypeProblem::ReadWriteFormat(format) => {
+                    write!(
+                        f,
+                        "images in the `{}` format cannot be read and written",
+                        format
+                    )
+                }
+            },
+            Error::MisplacedImage { .. } => write!(f, "image outside of a storage binding"),
+            Error::NotAnImage {
+                intrinsic,
+                type_name,
+                ..
+            } => write!(
+                f,
+                "`{}` of a value of type `{}`",
+                intrinsic.name(),
+                type_name
+            ),
+            Error::ImageAccessViolation {
+                access: ImageAccess::Write,
+                ..
+            } => write!(f, "image is only written"),
+            Error::ImageAccessViolation { .. } => write!(f, "image is only read"),
+            Error::InvalidImageCoordinates { found, .. } => {
+                write!(f, "`{}` cannot be coordinates into the image", found)
+            }
+            Error::TexelTypeMismatch {
+                found, expected, ..
+            } => write!(f, "`{}` stored as a texel of type `{}`", found, expected),
+            Error::ImageStoreInVertex { callee, .. } => {
+                write!(f, "`{}` is called in a vertex program", callee)
+            }
+            Error::InvalidTextureType { type_name, .. } => {
+                write!(f, "textures can't hold texels of type `{}`", type_name)
+            }
+            Error::MisplacedTexture { .. } => {
+                write!(f, "texture or sampler outside of a uniform binding")
+            }
+            Error::NotATexture {
+                intrinsic,
+                type_name: Some(type_name),
+                ..
+            } => write!(
+                f,
+                "`{}` of a value of type `{}`",
+                intrinsic.name(),
+                type_name
+            ),
+            Error::NotATexture { intrinsic, .. } => {
+                write!(f, "`{}` without a texture", intrinsic.name())
+            }
+            Error::UnsupportedSampling {
+                intrinsic,
+                type_name,
+                problem,
+                ..
+            } => match problem {
+                SamplingProblem::IntegerFiltering => write!(
+                    f,
+                    "`{}` filters the integer texels of a `{}`",
+                    intrinsic.name(),
+                    type_name
+                ),
+                SamplingProblem::NotDepth => write!(
+                    f,
+                    "`{}` of a `{}`, which holds no depths",
+                    intrinsic.name(),
+                    type_name
+                ),
+                SamplingProblem::Dimensions => {
+                    write!(f, "`{}` of a `{}`", intrinsic.name(), type_name)
+                }
+            },
+            Error::SamplingArity {
+                intrinsic,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "`{}` takes {} arguments but {} were given",
+                intrinsic.name(),
+                expected,
+                found
+            ),
+            Error::SamplingArgumentMismatch {
+                intrinsic,
+                param,
+                found,
+                ..
+            } => write!(
+                f,
+                "`{}` passed as `{}` of `{}`",
+                found,
+                param,
+                intrinsic.name()
+            ),
+            Error::LocalRedefinition { name, .. } => write!(f, "`{}` is declared twice", name),
+            Error::InvalidCast {
+                from, to, bitcast, ..
+            } => {
+                let verb = if *bitcast { "bitcast" } else { "cast" };
+                write!(f, "cannot {} `{}` to `{}`", verb, from, to)
+            }
+            Error::OperandTypeMismatch {
+                op, left, right, ..
+            } => write!(
+                f,
+                "`{}` cannot be applied to `{}` and `{}`",
+                op, left, right
+            ),
+            Error::InvalidOperand { op, ty, .. } => {
+                write!(f, "`{}` cannot be applied to `{}`", op, ty)
+            }
+            Error::InvalidComposite { type_name, .. } => {
+                write!(f, "invalid values for `{}`", type_name)
+            }
+            Error::InvalidDerive { record, .. } => {
+                write!(f, "`{}` cannot derive the function", record)
+            }
+            Error::InvalidDerivedCall { function, .. } => {
+                write!(f, "invalid values for `{}`", function)
+            }
+            Error::InvalidDestructuring { problem, .. } => match problem {
+                DestructureProblem::Duplicate { .. } => {
+                    write!(f, "variable assigned twice by a destructuring assignment")
+                }
+                _ => write!(f, "invalid destructuring assignment"),
+            },
+            Error::InvalidOptional { problem, .. } => match problem {
+                OptionalProblem::Unhandled(_) => write!(f, "unhandled optional value"),
+                OptionalProblem::NotWrapped(_) => write!(f, "value used as an optional"),
+                OptionalProblem::MissingArm(_) => write!(f, "`match` doesn't handle every case"),
+                OptionalProblem::DuplicateArm(_) => write!(f, "`match` handles a case twice"),
+                _ => write!(f, "invalid optional"),
+            },
+            Error::InvalidDebugOutput { problem, .. } => match problem {
+                DebugProblem::Condition(_) => write!(f, "assertion of a value that isn't `bool`"),
+                DebugProblem::Unprintable(_) => write!(f, "value can't be printed"),
+                _ => write!(f, "invalid debug output"),
+            },
+            Error::InvalidLookupTable { function, .. } => {
+                write!(f, "`{}` cannot have a lookup table", function)
+            }
+            Error::InvalidProfileLabel { problem, .. } => match problem {
+                ProfileProblem::Duplicate { .. } => write!(f, "profiling label used twice"),
+                _ => write!(f, "invalid profiling label"),
+            },
+            Error::InvalidSelectMask {
+                mask_type, value, ..
+            } => write!(
+                f,
+                "cannot select `{}` values with a `{}` mask",
+                value, mask_type
+            ),
+            Error::IntegerLiteralOutOfRange { value, ty, .. } => {
+                write!(f, "integer literal `{}` out of range for `{}`", value, ty)
+            }
+            Error::DeniedLint { warning, .. } => write!(f, "{}", warning),
+            Error::ConflictingGenericArgument { generic_name, .. } => write!(
+                f,
+                "conflicting types for generic parameter `{}`",
+                generic_name
+            ),
+            Error::UninferableGenericArgument { generic_name, .. } => {
+                write!(f, "cannot infer generic parameter `{}`", generic_name)
+            }
+            Error::InvalidBufferType { class, .. } => {
+                write!(f, "type cannot be stored in a {}", class)
+            }
+            Error::InvalidLayoutAttribute { name, .. } => {
+                write!(f, "invalid `{}` attribute", name)
+            }
+            Error::InvalidAtomicPlacement {
+                name, placement, ..
+            } => write!(f, "{} `{}` cannot contain an atomic", placement, name),
+            Error::WorkgroupOutsideCompute { .. } => {
+                write!(f, "workgroup variable outside of a compute program")
+            }
+            Error::BarrierInNonUniformControlFlow { callee, .. } => {
+                write!(f, "`{}` is called in non-uniform control flow", callee)
+            }
+            Error::DerivativeOutsideFragment { callee, .. } => {
+                write!(f, "`{}` is called outside of a fragment program", callee)
+            }
+            Error::DiscardOutsideFragment { callee: None, .. } => {
+                write!(f, "`discard` outside of a fragment program")
+            }
+            Error::DiscardOutsideFragment {
+                callee: Some(callee),
+                ..
+            } => write!(f, "`{}` is called outside of a fragment program", callee),
+            Error::TraceRayOutsideStage { callee, .. } => write!(
+                f,
+                "`{}` is called outside of a ray generation, closest hit or miss program",
+                callee
+            ),
+            Error::EmissionOutsideGeometry { callee, .. } => {
+                write!(f, "`{}` is called outside of a geometry program", callee)
+            }
+            Error::MisplacedAccelerationStructure { .. } => {
+                write!(f, "acceleration structure outside of a uniform binding")
+            }
+            Error::TraceRayArity { found, .. } => write!(
+                f,
+                "`trace_ray` takes {} arguments but {} were given",
+                ray_tracing::TRACE_RAY_PARAMS.len(),
+                found
+            ),
+            Error::TraceRayArgumentMismatch { param, found, .. } => {
+                write!(f, "`{}` passed as `{}` of `trace_ray`", found, param)
+            }
+            Error::InvalidRayPayload {
+                problem: PayloadProblem::OutsideRayTracing { .. },
+                ..
+            } => write!(f, "ray payload outside of a ray tracing program"),
+            Error::InvalidRayPayload { .. } => write!(f, "invalid `RayPayload` attribute"),
+            Error::PayloadTypeMismatch { location, .. } => {
+                write!(f, "payloads at location {} have different types", location)
+            }
+            Error::UnknownPayloadLocation {
+                location,
+                program_name,
+                ..
+            } => write!(
+                f,
+                "program `{}` has no payload at location {}",
+                program_name, location
+            ),
+            Error::InvalidMeshOutput {
+                problem: MeshOutputProblem::OutsideMesh { .. },
+                ..
+            } => write!(f, "mesh output outside of a mesh program"),
+            Error::InvalidMeshOutput { .. } => write!(f, "invalid output of a mesh program"),
+            Error::MeshOutputLimit {
+                per_vertex,
+                length,
+                limit,
+                ..
+            } => write!(
+                f,
+                "mesh program outputs {} {}, more than the limit of {}",
+                length,
+                if *per_vertex {
+                    "vertices"
+                } else {
+                    "primitives"
+                },
+                limit
+            ),
+            Error::MissingPrimitiveIndices { program_name, .. } => {
+                write!(
+                    f,
+                    "mesh program `{}` has no primitive indices",
+                    program_name
+                )
+            }
+            Error::InvalidStageAttribute {
+                stage,
+                problem: AttributeProblem::WrongStage { .. },
+                ..
+            } => write!(f, "attribute of {} {} program", stage.article(), stage),
+            Error::InvalidStageAttribute { stage, .. } => {
+                write!(
+                    f,
+                    "invalid attribute of {} {} program",
+                    stage.article(),
+                    stage
+                )
+            }
+            Error::MissingStageAttribute {
+                program_name,
+                attribute,
+                ..
+            } => write!(
+                f,
+                "program `{}` has no `{}` attribute",
+                program_name, attribute
+            ),
+            Error::InvalidPatch { .. } => write!(
+                f,
+                "varying per patch outside of a tessellation control or evaluation program"
+            ),
+            Error::ExpectedControlPointArray { .. } => {
+                write!(
+                    f,
+                    "varying of a tessellation program is not an array per control point"
+                )
+            }
+            Error::ExpectedPrimitiveArray { .. } => write!(
+                f,
+                "input of a geometry program is not an array per vertex of its primitive"
+            ),
+            Error::EmitWithoutPosition { program_name, .. } => write!(
+                f,
+                "geometry program `{}` emits vertices without a position",
+                program_name
+            ),
+            Error::TooManyEmittedVertices { max, .. } => {
+                write!(f, "geometry program emits more than {} vertices", max)
+            }
+            Error::InterfaceMismatch { name, .. } => {
+                write!(f, "`{}` has different types in consecutive stages", name)
+            }
+            Error::InvalidRelaxedPrecision { name, .. } => {
+                write!(f, "`{}` cannot have relaxed precision", name)
+            }
+            Error::UnsupportedFeature {
+                feature, profile, ..
+            } => write!(f, "the `{}` profile does not support {}", profile, feature),
+            Error::BindingConflict { binding, .. } => write!(
+                f,
+                "binding {} of set {} is used by two buffers",
+                binding.binding, binding.set
+            ),
+            Error::StringLiteralAsValue { .. } => write!(f, "string literal used as a value"),
+            Error::IndexOutOfBounds { .. } => write!(f, "index out of bounds"),
+            Error::MisplacedAttribute { name, target, .. } => {
+                write!(f, "`{}` cannot be used on a {}", name, target)
+            }
+            Error::InvalidInterpolation { name, problem, .. } => match problem {
+                InterpolationProblem::NotAVarying { .. } => {
+                    write!(f, "`{}` is not interpolated", name)
+                }
+                InterpolationProblem::Conflicting { .. } => {
+                    write!(f, "conflicting interpolations of `{}`", name)
+                }
+                InterpolationProblem::IntegerNotFlat { .. } => {
+                    write!(f, "`{}` cannot be interpolated", name)
+                }
+            },
+            Error::InvalidMatrixLayout { name, problem, .. } => match problem {
+                MatrixLayoutProblem::Conflicting { .. } => {
+                    write!(f, "conflicting matrix layouts of `{}`", name)
+                }
+                MatrixLayoutProblem::NoMatrix { .. } | MatrixLayoutProblem::NotABuffer => {
+                    write!(f, "`{}` cannot have a matrix layout", name)
+                }
+            },
+            Error::InvalidMatrixConstructor { type_name, .. } => {
+                write!(f, "invalid arguments for `{}`", type_name)
+            }
+            Error::InvalidSlice { problem, .. } => match problem {
+                SliceProblem::NotAnArray { type_name } => {
+                    write!(f, "cannot slice a value of type `{}`", type_name)
+                }
+                SliceProblem::NotIndexed => write!(f, "slice used as a value"),
+                SliceProblem::Reversed { .. } | SliceProblem::PastEnd { .. } => {
+                    write!(f, "slice out of bounds")
+                }
+                SliceProblem::IndexOutOfBounds { .. } => write!(f, "index out of bounds of slice"),
+            },
+            Error::InterpolationMismatch { name, .. } => write!(
+                f,
+                "`{}` is interpolated differently by the vertex and fragment programs",
+                name
+            ),
+            Error::ImpureCallInConstant { callee, .. } => {
+                write!(
+                    f,
+                    "`{}` has side effects and cannot be called in a constant",
+                    callee
+                )
+            }
+            Error::ArgumentNotAssignable {
+                param_name, mode, ..
+            } => write!(
+                f,
+                "argument for `{}` parameter `{}` is not a variable",
+                mode_keyword(*mode),
+                param_name
+            ),
+            Error::OutParameterNotAssigned { name, .. } => {
+                write!(f, "`out` parameter `{}` may not be assigned", name)
+            }
+            Error::OutParameterReadBeforeAssignment { name, .. } => {
+                write!(
+                    f,
+                    "`out` parameter `{}` is read before it is assigned",
+                    name
+                )
+            }
+            Error::FieldLayoutViolation {
+                field_name, kind, ..
+            } => match kind {
+                LayoutViolationKind::Overlap { previous, .. } => write!(
+                    f,
+                    "field `{}` overlaps the field `{}`",
+                    field_name, previous
+                ),
+                LayoutViolationKind::Misaligned { .. } => {
+                    write!(f, "field `{}` is misaligned", field_name)
+                }
+                LayoutViolationKind::AlignTooSmall { .. } => {
+                    write!(f, "alignment of field `{}` is too small", field_name)
+                }
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// The location the error is reported at, used to order errors
+    pub fn location(&self) -> FileLocation {
+        match self {
+            Error::TypeRedefinition {
+                redefinition_name, ..
+            }
+            | Error::FieldRedefinition {
+                redefinition_name, ..
+            }
+            | Error::FunctionRedefinition {
+                redefinition_name, ..
+            }
+            | Error::ConstantRedefinition {
+                redefinition_name, ..
+            }
+            | Error::SpaceRedefinition {
+                redefinition_name, ..
+            } => *redefinition_name,
+            Error::GenericParamaterRedefinition { redefinition, .. } => *redefinition,
+            Error::NameConflict { conflict, .. } => *conflict,
+            Error::RecursiveTypeDefinition { type_name, .. } => *type_name,
+            Error::MutuallyRecursiveTypeDefinitions {
+                type_def_idents, ..
+            } => type_def_idents[0],
+            Error::RecursiveFunction { function_name, .. } => *function_name,
+            Error::InstantiationLimit { call, .. } => *call,
+            Error::ConstantTypeMismatch { value, .. } => *value,
+            Error::StaticAssertionFailed { condition, .. } => *condition,
+            Error::ConstEvaluation { expr, .. } => *expr,
+            Error::MutuallyRecursiveFunctions {
+                function_idents, ..
+            } => function_idents[0],
+            Error::UndefinedType { uses, .. }
+            | Error::UndefinedVariable { uses, .. }
+            | Error::UndefinedFunction { uses, .. }
+            | Error::UndefinedSpace { uses, .. } => uses[0],
+            Error::CyclicSpaceHierarchy { space_names, .. } => space_names[0],
+            Error::InvalidSpaceTransform { via, .. } => *via,
+            Error::SpaceMismatch { expr, .. } | Error::ColourEncodingMismatch { expr, .. } => *expr,
+            Error::MixedColourEncodings { operation, .. } => *operation,
+            Error::InvalidNormalizedType { type_, .. } => *type_,
+            Error::NotNormalized { expr, .. } | Error::AngleUnitMismatch { expr, .. } => *expr,
+            Error::MixedAngleUnits { operation, .. } => *operation,
+            Error::InvalidImageType { type_, .. } | Error::MisplacedImage { type_ } => *type_,
+            Error::NotAnImage { expr, .. } => *expr,
+            Error::ImageAccessViolation { image, .. } => *image,
+            Error::InvalidImageCoordinates { coords, .. } => *coords,
+            Error::TexelTypeMismatch { texel, .. } => *texel,
+            Error::ImageStoreInVertex { call, .. } => *call,
+            Error::InvalidTextureType { type_, .. } | Error::MisplacedTexture { type_ } => *type_,
+            Error::NotATexture { expr, .. } => *expr,
+            Error::UnsupportedSampling { texture, .. } => *texture,
+            Error::SamplingArity { call, .. } => *call,
+            Error::SamplingArgumentMismatch { arg, .. } => *arg,
+            Error::LocalRedefinition { redefinition, .. } => *redefinition,
+            Error::InvalidCast { cast, .. } => *cast,
+            Error::OperandTypeMismatch {
+                left_loc,
+                right_loc,
+                ..
+            } => left_loc.merge(*right_loc),
+            Error::InvalidOperand { operation, .. } => *operation,
+            Error::InvalidComposite { value, .. } => *value,
+            Error::InvalidDerive { loc, .. }
+            | Error::InvalidDerivedCall { loc, .. }
+            | Error::InvalidDestructuring { loc, .. }
+            | Error::InvalidOptional { loc, .. }
+            | Error::InvalidDebugOutput { loc, .. } => *loc,
+            Error::InvalidLookupTable { attribute, .. }
+            | Error::InvalidProfileLabel { attribute, .. } => *attribute,
+            Error::InvalidSelectMask { mask, .. } => *mask,
+            Error::IntegerLiteralOutOfRange { literal, .. } => *literal,
+            Error::DeniedLint { warning, .. } => warning.location(),
+            Error::HigherKindedGenericTypeUsed { loc, .. }
+            | Error::MismatchedNumberGenericArgs { loc, .. } => *loc,
+            Error::ConflictingGenericArgument { arg, .. } => *arg,
+            Error::UninferableGenericArgument { call, .. } => *call,
+            Error::InvalidBufferType { type_, .. } => *type_,
+            Error::InvalidLayoutAttribute { attribute, .. }
+            | Error::FieldLayoutViolation { attribute, .. } => *attribute,
+            Error::InvalidAtomicPlacement { type_, .. }
+            | Error::InvalidRelaxedPrecision { type_, .. } => *type_,
+            Error::WorkgroupOutsideCompute { var, .. } => *var,
+            Error::BarrierInNonUniformControlFlow { call, .. }
+            | Error::DerivativeOutsideFragment { call, .. }
+            | Error::TraceRayOutsideStage { call, .. }
+            | Error::EmissionOutsideGeometry { call, .. } => *call,
+            Error::DiscardOutsideFragment { loc, .. } => *loc,
+            Error::MisplacedAccelerationStructure { type_ } => *type_,
+            Error::TraceRayArity { call, .. } => *call,
+            Error::TraceRayArgumentMismatch { arg, .. } => *arg,
+            Error::InvalidRayPayload { attribute, .. } => *attribute,
+            Error::PayloadTypeMismatch { payload, .. } => *payload,
+            Error::UnknownPayloadLocation { arg, .. } => *arg,
+            Error::InvalidMeshOutput { output, .. } | Error::MeshOutputLimit { output, .. } => {
+                *output
+            }
+            Error::MissingPrimitiveIndices { program, .. } => *program,
+            Error::InvalidStageAttribute { attribute, .. } => *attribute,
+            Error::MissingStageAttribute { program, .. } => *program,
+            Error::InvalidPatch { var, .. }
+            | Error::ExpectedControlPointArray { var, .. }
+            | Error::ExpectedPrimitiveArray { var, .. } => *var,
+            Error::EmitWithoutPosition { call, .. }
+            | Error::TooManyEmittedVertices { call, .. } => *call,
+            Error::InterfaceMismatch { input_loc, .. } => *input_loc,
+            Error::ArgumentNotAssignable { arg, .. } => *arg,
+            Error::ImpureCallInConstant { call, .. } => *call,
+            Error::StringLiteralAsValue { literal } => *literal,
+            Error::IndexOutOfBounds { index, .. } => *index,
+            Error::MisplacedAttribute { attribute, .. }
+            | Error::InvalidInterpolation { attribute, .. }
+            | Error::InvalidMatrixLayout { attribute, .. } => *attribute,
+            Error::InterpolationMismatch { input_loc, .. } => *input_loc,
+            Error::InvalidMatrixConstructor { constructor, .. } => *constructor,
+            Error::InvalidSlice { slice, .. } => *slice,
+            Error::BindingConflict { attribute, .. } => *attribute,
+            Error::UnsupportedFeature { loc, .. } => *loc,
+            Error::OutParameterNotAssigned { exit, .. } => *exit,
+            Error::OutParameterReadBeforeAssignment { use_, .. } => *use_,
+        }
+    }
+
+    /// Suggestion on how to resolve the error
+    pub fn help(&self) -> String {
+        match self {
+            Error::TypeRedefinition { .. } => {
+                "rename one of the types or remove the duplicate definition".to_string()
+            }
+            Error::GenericParamaterRedefinition { .. } => {
+                "the generic parameters of a type must have distinct names".to_string()
+            }
+            Error::RecursiveTypeDefinition { .. } => {
+                "values are stored inline, so a type containing itself would have infinite size"
+                    .to_string()
+            }
+            Error::MutuallyRecursiveTypeDefinitions { .. } => {
+                "break the cycle by removing one of the uses between the types".to_string()
+            }
+            Error::RecursiveFunction { .. } | Error::MutuallyRecursiveFunctions { .. } => {
+                "GPUs have no call stack, rewrite the recursion as a loop".to_string()
+            }
+            Error::InstantiationLimit { .. } => {
+                "every list of generic arguments a function is called with is an instance of its own, call the functions with fewer types or raise the limit with `--instantiation-limit`"
+                    .to_string()
+            }
+            Error::ConstantTypeMismatch { expected, .. } => {
+                format!("give the constant a value of type `{}`", expected)
+            }
+            Error::StaticAssertionFailed { .. } => {
+                "the module only compiles if the condition of the assertion is true".to_string()
+            }
+            Error::ConstEvaluation { problem, .. } => match problem {
+                EvalProblem::Unsupported(_) => {
+                    "only literals, constants with a value, operators, `as`, calls of pure functions and the vectors, records and arrays built from them are evaluated when compiling"
+                        .to_string()
+                }
+                EvalProblem::Buffer(_) => {
+                    "the values of buffers are only known when the program runs".to_string()
+                }
+                EvalProblem::NoValue(_) => {
+                    "give the constant a value, or give it one when compiling with `--override`"
+                        .to_string()
+                }
+                EvalProblem::Overflow => {
+                    "the values of integers are checked for overflows when compiling".to_string()
+                }
+                EvalProblem::DivisionByZero => "check the divisor".to_string(),
+                EvalProblem::Cycle(_) => {
+                    "constants can't use themselves in their values".to_string()
+                }
+                EvalProblem::NotBool(_) => {
+                    "the condition of a static assertion is a `bool`".to_string()
+                }
+                EvalProblem::ArraySize(_) => "arrays have at least one element".to_string(),
+                EvalProblem::UnknownRules(_) => format!(
+                    "the layout rules are {}",
+                    LayoutRules::ALL
+                        .iter()
+                        .map(|rules| format!("`{}`", rules))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                EvalProblem::NoLayout(_) => {
+                    "only types that can be stored in buffers have a layout".to_string()
+                }
+                EvalProblem::RuntimeSized(_) => {
+                    "ask for the size of the part before the array, or of its elements".to_string()
+                }
+                EvalProblem::UnknownField { .. } => "check the spelling of the field".to_string(),
+                EvalProblem::OutOfBounds { .. } => {
+                    "the index of the first element is 0".to_string()
+                }
+                EvalProblem::MissingReturn(_) => {
+                    "return a value at the end of the function".to_string()
+                }
+                EvalProblem::StepLimit(_) => {
+                    "evaluate fewer iterations of the loops when compiling, or raise the limit with `--eval-step-limit`".to_string()
+                }
+                EvalProblem::CallDepth => {
+                    "functions that call themselves can't be evaluated when compiling".to_string()
+                }
+            },
+            Error::UndefinedType { suggestions, .. } => match suggestions.as_slice() {
+                [] => "check the spelling or add a definition to a `type` section".to_string(),
+                [name] => format!("a type with a similar name exists: `{}`", name),
+                names => format!(
+                    "types with similar names exist: {}",
+                    names
+                        .iter()
+                        .map(|n| format!("`{}`", n))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Error::HigherKindedGenericTypeUsed { generic_name, .. } => format!(
+                "`{}` can only be used as a complete type, without generic arguments",
+                generic_name
+            ),
+            Error::MismatchedNumberGenericArgs { expected, .. } => format!(
+                "provide exactly {} generic argument{}",
+                expected,
+                if *expected == 1 { "" } else { "s" }
+            ),
+            Error::FieldRedefinition { .. } => {
+                "the fields of a record must have distinct names".to_string()
+            }
+            Error::FunctionRedefinition { .. } => {
+                "functions cannot be overloaded, give the functions distinct names".to_string()
+            }
+            Error::ConstantRedefinition { .. } => {
+                "rename one of the constants or remove the duplicate definition".to_string()
+            }
+            Error::NameConflict { .. } => {
+                "types, functions and constants share one namespace, rename one of the items"
+                    .to_string()
+            }
+            Error::SpaceRedefinition { .. } => {
+                "a space can only be placed in one parent, remove one of the declarations"
+                    .to_string()
+            }
+            Error::UndefinedVariable { suggestions, .. } => match suggestions.as_slice() {
+                [] => "check the spelling or declare the variable with `var` before its use"
+                    .to_string(),
+                [name] => format!("a variable with a similar name exists: `{}`", name),
+                names => format!(
+                    "variables with similar names exist: {}",
+                    names
+                        .iter()
+                        .map(|n| format!("`{}`", n))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Error::UndefinedFunction { suggestions, .. } => match suggestions.as_slice() {
+                [] => "check the spelling or add a definition with `function`".to_string(),
+                [name] => format!("a function with a similar name exists: `{}`", name),
+                names => format!(
+                    "functions with similar names exist: {}",
+                    names
+                        .iter()
+                        .map(|n| format!("`{}`", n))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Error::UndefinedSpace { suggestions, .. } => match suggestions.as_slice() {
+                [] => "declare the space with `space`, or remove all space declarations to use undeclared spaces"
+                    .to_string(),
+                [name] => format!("a space with a similar name exists: `{}`", name),
+                names => format!(
+                    "spaces with similar names exist: {}",
+                    names
+                        .iter()
+                        .map(|n| format!("`{}`", n))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Error::CyclicSpaceHierarchy { .. } => {
+                "one of the spaces has to be a root, remove its `parent`".to_string()
+            }
+            Error::InvalidSpaceTransform { space, problem, .. } => match problem {
+                SpaceTransformProblem::UndefinedConstant { suggestions } => match suggestions
+                    .as_slice()
+                {
+                    [name] => format!("a constant with a similar name exists: `{}`", name),
+                    _ => "the transform has to be a constant matrix".to_string(),
+                },
+                SpaceTransformProblem::NotAMatrix { .. } => {
+                    "the transform has to be a constant matrix".to_string()
+                }
+                SpaceTransformProblem::WrongSpaces { parent, .. } => format!(
+                    "the matrix has to transform `from {} to {}`",
+                    parent, space
+                ),
+            },
+            Error::LocalRedefinition { .. } => {
+                "rename one of them, or move the second one into a block of its own".to_string()
+            }
+            Error::InvalidCast {
+                bitcast, problem, ..
+            } => match problem {
+                _ if *bitcast => {
+                    "`bitcast` keeps the bits, both types need the same number of components of the same size"
+                        .to_string()
+                }
+                CastProblem::AngleUnits => {
+                    "convert the angle with `to_radians(..)` or `to_degrees(..)`".to_string()
+                }
+                _ => {
+                    "`as` converts numbers and booleans, and vectors or matrices with the same number of components, component by component"
+                        .to_string()
+                }
+            },
+            Error::OperandTypeMismatch { op, .. } => match *op {
+                "<" | "<=" | ">" | ">=" | "=" | "<>" => {
+                    "comparisons take two values of the same type, `<`, `<=`, `>` and `>=` only take numbers and vectors of numbers"
+                        .to_string()
+                }
+                _ => {
+                    "arithmetic takes two values of the same type, or a vector and a scalar of its component type, convert one of them with `as`"
+                        .to_string()
+                }
+            },
+            Error::InvalidOperand { op, .. } => {
+                format!("`{}` takes numbers, vectors and matrices", op)
+            }
+            Error::InvalidComposite {
+                type_name, problem, ..
+            } => match problem {
+                CompositeProblem::ValueType { .. } => {
+                    "the fields of a record get values of their types, the elements of an array all have the same type"
+                        .to_string()
+                }
+                CompositeProblem::UninferableRecord => {
+                    "build the record where its type is known, like in the value of a variable with a declared type"
+                        .to_string()
+                }
+                CompositeProblem::EmptyArray => "arrays have at least one element".to_string(),
+                _ => format!(
+                    "`{}` is built from one value for each of its fields, in their order or by their names",
+                    type_name
+                ),
+            },
+            Error::InvalidDerive { problem, .. } => match problem {
+                DeriveProblem::Unknown(_) => {
+                    "records derive the functions `new` and `eq`".to_string()
+                }
+                DeriveProblem::NotRecord | DeriveProblem::Generic => {
+                    "functions are derived for records without generic parameters".to_string()
+                }
+                DeriveProblem::Incomparable { .. } => {
+                    "`eq` compares records whose fields are numbers, vectors, matrices and the records and arrays of them"
+                        .to_string()
+                }
+                DeriveProblem::Conflict(_) => {
+                    "rename the function or don't derive it".to_string()
+                }
+            },
+            Error::InvalidDerivedCall { function, problem, .. } => match problem {
+                DerivedCallProblem::Arity { .. } => {
+                    format!("`{}` compares two values", function)
+                }
+                DerivedCallProblem::ValueType { expected, .. } => {
+                    format!("`{}` compares values of `{}`", function, expected)
+                }
+            },
+            Error::InvalidDestructuring { problem, .. } => match problem {
+                DestructureProblem::NotRecord(_) => {
+                    "the fields of a record are assigned to the targets, return a record like `record { lo: float, hi: float }` to return several values".to_string()
+                }
+                DestructureProblem::Count { .. } => {
+                    "give a target for each field of the record, in the order of the fields".to_string()
+                }
+                DestructureProblem::TargetType { .. } => {
+                    "the targets are assigned the fields in the order of the fields".to_string()
+                }
+                DestructureProblem::NotAssignable(_) => {
+                    "the targets are variables or fields and elements of variables".to_string()
+                }
+                DestructureProblem::Duplicate { .. } => {
+                    "assign the field to a variable of its own, or drop one of the targets".to_string()
+                }
+            },
+            Error::InvalidOptional { problem, .. } => match problem {
+                OptionalProblem::Inner(_) => {
+                    "resources, atomics and runtime sized arrays can't be optional".to_string()
+                }
+                OptionalProblem::NotOptional(_) => {
+                    "`unwrap_or` and `match` take the value of an optional".to_string()
+                }
+                OptionalProblem::UninferableNone => {
+                    "use `none()` where an optional is expected, like the value of a variable of an optional type".to_string()
+                }
+                OptionalProblem::Arity { .. } => {
+                    "`some(v)` takes one value, `none()` none and `unwrap_or(o, default)` two".to_string()
+                }
+                OptionalProblem::DefaultType { .. } => {
+                    "the default is used in place of the missing value".to_string()
+                }
+                OptionalProblem::Unhandled(_) => {
+                    "handle the missing value with `unwrap_or(o, default)` or a `match`".to_string()
+                }
+                OptionalProblem::NotWrapped(_) => "wrap the value with `some(..)`".to_string(),
+                OptionalProblem::MissingArm(case) => {
+                    format!("add a `{}` arm, a `match` handles both cases of an optional", case)
+                }
+                OptionalProblem::DuplicateArm(_) => {
+                    "a `match` has one arm for each case".to_string()
+                }
+            },
+            Error::InvalidDebugOutput { problem, .. } => match problem {
+                DebugProblem::Condition(_) => "compare the value, like `n > 0`".to_string(),
+                DebugProblem::Arity { .. } => {
+                    "`assert(cond)` takes a condition and an optional message, `debug_print(format, values...)` a format and its values".to_string()
+                }
+                DebugProblem::NotString => {
+                    "messages and formats are written in the source, like `\"n = {}\"`".to_string()
+                }
+                DebugProblem::UnmatchedBrace => {
+                    "write `{{` and `}}` to print a brace".to_string()
+                }
+                DebugProblem::ValueCount { .. } => {
+                    "each `{}` of the format prints the next value".to_string()
+                }
+                DebugProblem::Unprintable(_) => {
+                    "booleans, numbers and vectors of them can be printed, print the fields of records one by one".to_string()
+                }
+            },
+            Error::InvalidLookupTable { problem, .. } => match problem {
+                LutProblem::Size => format!(
+                    "give the number of entries of the table, like `@lut(256)`, up to {}",
+                    MAX_LUT_SIZE
+                ),
+                LutProblem::Generic => {
+                    "lookup tables are built for functions without generic parameters".to_string()
+                }
+                LutProblem::Parameters => {
+                    "the entries are the `int` or `uint` values from 0, or `float` values from 0.0 to 1.0"
+                        .to_string()
+                }
+                LutProblem::Effects(_) => {
+                    "the function is evaluated when compiling, which only pure functions are"
+                        .to_string()
+                }
+            },
+            Error::InvalidProfileLabel { problem, .. } => match problem {
+                ProfileProblem::Argument | ProfileProblem::Empty => {
+                    "give the label timings are reported with, like `@profile(\"shadows\")`"
+                        .to_string()
+                }
+                ProfileProblem::Duplicate { .. } => {
+                    "the timings of a label are mapped back to one function or program".to_string()
+                }
+            },
+            Error::InvalidSelectMask { .. } => {
+                "a `bool` mask selects one of the values, a `boolN` mask selects the components of vectors with N components"
+                    .to_string()
+            }
+            Error::IntegerLiteralOutOfRange { .. } => {
+                "write a value that fits, or give the literal a wider type with a suffix like `l` or `ul`"
+                    .to_string()
+            }
+            Error::DeniedLint { warning, .. } => warning.help(),
+            Error::SpaceMismatch {
+                from, to, chain, ..
+            } => match chain {
+                Some(chain) if !chain.is_empty() => format!(
+                    "transform the value with `{}`",
+                    chain
+                        .iter()
+                        .rev()
+                        .map(|step| step.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" * ")
+                ),
+                _ => format!(
+                    "declare `{}` and `{}` with `space` in one hierarchy to find the transforms between them",
+                    from, to
+                ),
+            },
+            Error::ColourEncodingMismatch { srgb: true, .. } => {
+                "decode the colour with `srgb_to_linear(..)`".to_string()
+            }
+            Error::ColourEncodingMismatch { srgb: false, .. } => {
+                "encode the colour with `linear_to_srgb(..)`".to_string()
+            }
+            Error::MixedColourEncodings { .. } => {
+                "decode the sRGB encoded colour with `srgb_to_linear(..)`, arithmetic on colours is only meaningful when they are linear".to_string()
+            }
+            Error::InvalidNormalizedType { .. } => {
+                "only vectors of `float`, `half` or `double` can be normalized".to_string()
+            }
+            Error::NotNormalized { .. } => {
+                "normalize it with `normalize(..)`, or cast it with `as normalized<..>` if its length is known to be one".to_string()
+            }
+            Error::AngleUnitMismatch {
+                found: Some(_),
+                expected: Some(unit),
+                ..
+            } => format!("convert it with `to_{}(..)`", unit.name()),
+            Error::AngleUnitMismatch {
+                found: None,
+                expected: Some(unit),
+                ..
+            } => format!(
+                "write angles with a `rad` or `deg` suffix, or cast the value with `as {}` if it is an angle in {}",
+                unit.name(),
+                unit.name()
+            ),
+            Error::AngleUnitMismatch { .. } => {
+                "cast it with `as float` to use the angle as a number".to_string()
+            }
+            Error::MixedAngleUnits { .. } => {
+                "convert one of the angles with `to_radians(..)` or `to_degrees(..)`".to_string()
+            }
+            Error::InvalidImageType { problem, .. } => match problem {
+                ImageTypeProblem::UnknownFormat(_) => format!(
+                    "the image formats are {}",
+                    ImageFormat::ALL
+                        .iter()
+                        .map(|format| format!("`{}`", format))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                ImageTypeProblem::UnknownAccess(_) => {
+                    "images are accessed `read`, `write` or `read_write`".to_string()
+                }
+                ImageTypeProblem::ReadWriteFormat(_) => {
+                    "only images in the `r32float`, `r32uint` and `r32sint` formats can be read and written by the same program"
+                        .to_string()
+                }
+            },
+            Error::MisplacedImage { .. } => {
+                "images are bound like buffers, declare a constant of the image type with a `@Storage` attribute"
+                    .to_string()
+            }
+            Error::NotAnImage { .. } => {
+                "the first argument is a constant bound as a storage image".to_string()
+            }
+            Error::ImageAccessViolation { intrinsic, .. } => format!(
+                "`{}` needs an image with `{}` or `read_write` access",
+                intrinsic.name(),
+                match intrinsic {
+                    Intrinsic::ImageLoad => "read",
+                    _ => "write",
+                }
+            ),
+            Error::InvalidImageCoordinates { expected, .. } => format!(
+                "texels are addressed with `{}` or the same vector of `uint`",
+                expected
+            ),
+            Error::TexelTypeMismatch { format, .. } => format!(
+                "texels of the `{}` format are read and written as four components, the format converts them when storing them",
+                format
+            ),
+            Error::ImageStoreInVertex { .. } => {
+                "vertex programs can't write images, write them in a fragment or compute program"
+                    .to_string()
+            }
+            Error::InvalidTextureType { .. } => {
+                "textures hold `float`, `half`, `int` or `uint` texels, depth textures like `texture_depth2d` have no texel type"
+                    .to_string()
+            }
+            Error::MisplacedTexture { .. } => {
+                "textures and samplers are bound like buffers, declare a constant of the type with a `@Uniform` attribute"
+                    .to_string()
+            }
+            Error::NotATexture { .. } => {
+                "the first argument is a constant bound as a texture".to_string()
+            }
+            Error::UnsupportedSampling {
+                intrinsic, problem, ..
+            } => match problem {
+                SamplingProblem::IntegerFiltering => {
+                    "integer texels can't be filtered, read them with `fetch` or `gather`"
+                        .to_string()
+                }
+                _ => format!(
+                    "`{}` supports {}",
+                    intrinsic.name(),
+                    textures::supported_texture_names(*intrinsic)
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Error::SamplingArity { signature, .. }
+            | Error::SamplingArgumentMismatch { signature, .. } => {
+                format!("the signature for this texture is `{}`", signature)
+            }
+            Error::ConflictingGenericArgument { generic_name, .. } => format!(
+                "all arguments using `{}` must have the same type",
+                generic_name
+            ),
+            Error::UninferableGenericArgument { generic_name, .. } => format!(
+                "use `{}` in the type of an argument so that it can be inferred",
+                generic_name
+            ),
+            Error::InvalidBufferType { problem, .. } => match problem {
+                BufferTypeProblem::Bool => {
+                    "booleans have no defined size in buffers, use `uint` instead".to_string()
+                }
+                BufferTypeProblem::OpenArray => {
+                    "only storage buffers can contain arrays without a size".to_string()
+                }
+                BufferTypeProblem::Atomic => {
+                    "only storage buffers can contain atomics".to_string()
+                }
+                BufferTypeProblem::Image => {
+                    "images are bound on their own with a `@Storage` attribute, not stored in buffers"
+                        .to_string()
+                }
+                BufferTypeProblem::Texture => {
+                    "textures and samplers are bound on their own with a `@Uniform` attribute, not stored in buffers"
+                        .to_string()
+                }
+                BufferTypeProblem::AccelerationStructure => {
+                    "acceleration structures are bound on their own with a `@Uniform` attribute, not stored in buffers"
+                        .to_string()
+                }
+                BufferTypeProblem::OpenArrayNotLast => {
+                    "an array without a size must be the last field of the buffer".to_string()
+                }
+            },
+            Error::InvalidLayoutAttribute { name, problem, .. } => match problem {
+                LayoutAttributeProblem::ExpectedInteger => {
+                    format!("write the {} in bytes, like `@{}(16)`", name, name)
+                }
+                LayoutAttributeProblem::NotPowerOfTwo(_) => {
+                    "alignments have to be powers of two".to_string()
+                }
+            },
+            Error::FieldLayoutViolation { class, rules, .. } => format!(
+                "fields of a {} are laid out with the {} rules",
+                class, rules
+            ),
+            Error::InvalidAtomicPlacement { .. } => {
+                "atomics can only be stored in storage buffers and workgroup variables".to_string()
+            }
+            Error::WorkgroupOutsideCompute { .. } => {
+                "mark the program with `@compute` or declare the variable with `var` in its body"
+                    .to_string()
+            }
+            Error::BarrierInNonUniformControlFlow { .. } => {
+                "all invocations of a workgroup have to reach a barrier, move the call out of the branch or loop"
+                    .to_string()
+            }
+            Error::DerivativeOutsideFragment { .. } => {
+                "derivatives are computed from neighbouring fragments, mark the program with `@fragment`"
+                    .to_string()
+            }
+            Error::DiscardOutsideFragment { .. } => {
+                "`discard` stops the invocation of a fragment without writing its outputs, mark the program with `@fragment`"
+                    .to_string()
+            }
+            Error::TraceRayOutsideStage { .. } => {
+                "rays are traced by ray tracing programs, mark the program with `@ray_generation`, `@closest_hit` or `@miss`"
+                    .to_string()
+            }
+            Error::EmissionOutsideGeometry { .. } => {
+                "other programs output a single vertex or fragment through their outputs, mark the program with `@geometry` to emit primitives"
+                    .to_string()
+            }
+            Error::MisplacedAccelerationStructure { .. } => {
+                "acceleration structures are bound like buffers, declare a constant of the type with a `@Uniform` attribute"
+                    .to_string()
+            }
+            Error::TraceRayArity { .. } | Error::TraceRayArgumentMismatch { .. } => format!(
+                "the signature of `trace_ray` is `{}`",
+                ray_tracing::trace_ray_signature()
+            ),
+            Error::InvalidRayPayload { problem, .. } => match problem {
+                PayloadProblem::ExpectedInteger => {
+                    "write the location of the payload, like `@RayPayload(0)`".to_string()
+                }
+                PayloadProblem::OutsideRayTracing { .. } => {
+                    "payloads are passed along with rays, only ray generation, hit and miss programs have them"
+                        .to_string()
+                }
+                PayloadProblem::Duplicate { .. } => {
+                    "every payload of a program needs a location of its own".to_string()
+                }
+            },
+            Error::PayloadTypeMismatch { .. } => {
+                "hit and miss programs see the payload of whichever program traced the ray, give payloads at the same location the same type"
+                    .to_string()
+            }
+            Error::UnknownPayloadLocation { location, .. } => format!(
+                "declare an output with a `@RayPayload({})` attribute in the program",
+                location
+            ),
+            Error::InvalidMeshOutput { problem, .. } => match problem {
+                MeshOutputProblem::OutsideMesh { .. } => {
+                    "only mesh programs output vertices and primitives, mark the program with `@mesh`"
+                        .to_string()
+                }
+                MeshOutputProblem::MissingRate => {
+                    "add a `@PerVertex` or `@PerPrimitive` attribute to the output".to_string()
+                }
+                MeshOutputProblem::ExpectedArray { .. } => {
+                    "declare the output as an array with an element for every vertex or primitive the program may output"
+                        .to_string()
+                }
+                MeshOutputProblem::ExpectedTriangles { .. } => {
+                    "primitives are triangles, declare the indices as an array of `uint3`".to_string()
+                }
+                MeshOutputProblem::LengthMismatch { .. } => {
+                    "the length of the outputs is the most vertices or primitives the program outputs, give the outputs per vertex the same length and the outputs per primitive the same length"
+                        .to_string()
+                }
+            },
+            Error::MeshOutputLimit { per_vertex, .. } => {
+                let what = if *per_vertex { "vertices" } else { "primitives" };
+                format!(
+                    "split the mesh into parts with fewer {}, or raise the limit with `--max-mesh-{}` if the target supports more",
+                    what, what
+                )
+            }
+            Error::MissingPrimitiveIndices { .. } => {
+                "declare an array of `uint3` with a `@PrimitiveIndices` attribute for the vertices of every triangle"
+                    .to_string()
+            }
+            Error::InvalidStageAttribute { stage, problem, .. } => match problem {
+                AttributeProblem::WrongStage { .. } => format!(
+                    "remove the attribute or mark the program with `@{}`",
+                    stage.attribute()
+                ),
+                AttributeProblem::ExpectedName { expected } => {
+                    let names = expected
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>();
+                    format!("the attribute takes one of {}", names.join(", "))
+                }
+                AttributeProblem::ExpectedCount { what, max } => {
+                    format!("write the number of {}, from 1 to {}", what, max)
+                }
+                AttributeProblem::Duplicate { .. } => "remove one of the attributes".to_string(),
+            },
+            Error::MissingStageAttribute { attribute, .. } => match *attribute {
+                "control_points" => {
+                    "give the number of control points of the patches, like `@control_points(3)`"
+                        .to_string()
+                }
+                "domain" => {
+                    "give the primitive the tessellator subdivides, like `@domain(triangles)`"
+                        .to_string()
+                }
+                "input_primitive" => {
+                    "give the primitive the program reads the vertices of, like `@input_primitive(triangles)`"
+                        .to_string()
+                }
+                "output_primitive" => {
+                    "give the strips the program emits, like `@output_primitive(triangle_strip)`"
+                        .to_string()
+                }
+                _ => "give the most vertices the program emits, like `@max_vertices(3)`"
+                    .to_string(),
+            },
+            Error::InvalidPatch { .. } => {
+                "tessellation control programs write varyings per patch, which tessellation evaluation programs read"
+                    .to_string()
+            }
+            Error::ExpectedControlPointArray { length, .. } => match length {
+                Some(length) => format!(
+                    "declare the varying as an array with an element for each of the {} control points, or pass it per patch with `@Patch`",
+                    length
+                ),
+                None => {
+                    "declare the varying as an array with an element per control point of the patch, or pass it per patch with `@Patch`"
+                        .to_string()
+                }
+            },
+            Error::ExpectedPrimitiveArray { length, .. } => match length {
+                Some(length) => format!(
+                    "declare the input as an array with an element for each of the {} vertices of the input primitive",
+                    length
+                ),
+                None => {
+                    "declare the input as an array with an element per vertex of the input primitive"
+                        .to_string()
+                }
+            },
+            Error::EmitWithoutPosition { .. } => {
+                "declare a `float4` output with a `@Position` attribute and write the clip space position of every vertex before emitting it"
+                    .to_string()
+            }
+            Error::TooManyEmittedVertices { .. } => format!(
+                "emit fewer vertices, or raise `@max_vertices` up to {}",
+                geometry::MAX_GEOMETRY_VERTICES
+            ),
+            Error::InterfaceMismatch { .. } => {
+                "inputs of tessellation control and geometry programs are arrays of the outputs of the stage before them with the same name, and inputs of tessellation evaluation programs have the type of the control outputs with the same name"
+                    .to_string()
+            }
+            Error::InvalidRelaxedPrecision { .. } => {
+                "only int, uint, float and half values, their vectors and float matrices can be relaxed"
+                    .to_string()
+            }
+            Error::UnsupportedFeature { feature, .. } => match feature {
+                Feature::DoublePrecision => "use `float` values instead".to_string(),
+                Feature::Atomics => {
+                    "combine the values of the invocations on the CPU or in a render pass"
+                        .to_string()
+                }
+                Feature::StorageBuffers | Feature::PushConstants => {
+                    "use a uniform buffer instead".to_string()
+                }
+                Feature::ComputePrograms => {
+                    "the profile only has vertex and fragment programs".to_string()
+                }
+                Feature::LinearInterpolation => {
+                    "divide the value by `w` in the vertex program and multiply it again in the fragment program"
+                        .to_string()
+                }
+                Feature::SeparateSamplers => {
+                    "the profile only has textures combined with their sampler, compile for a desktop profile to sample textures"
+                        .to_string()
+                }
+                Feature::RayTracing => {
+                    "the profile has no ray tracing pipelines, compile for a desktop profile to trace rays"
+                        .to_string()
+                }
+                Feature::MeshShading => {
+                    "the profile only has vertex programs to output vertices".to_string()
+                }
+                Feature::Tessellation => {
+                    "the profile has no tessellation, subdivide the mesh before drawing it"
+                        .to_string()
+                }
+                Feature::GeometryPrograms => {
+                    "the profile has no geometry programs, build the primitives on the CPU or in a compute pass"
+                        .to_string()
+                }
+                Feature::Int64 => {
+                    "use `int` or `uint` values instead, or a `uint2` with the low and high 32 bits"
+                        .to_string()
+                }
+                Feature::SubgroupOperations => {
+                    "the profile has no subgroups, share the values through workgroup variables instead"
+                        .to_string()
+                }
+            },
+            Error::BindingConflict { .. } => {
+                "remove the `binding` argument of one of the buffers to have a free binding assigned"
+                    .to_string()
+            }
+            Error::StringLiteralAsValue { .. } => {
+                "strings can only be used as arguments of attributes".to_string()
+            }
+            Error::IndexOutOfBounds { len, .. } => {
+                format!("arrays are indexed from 0 up to {}", len.saturating_sub(1))
+            }
+            Error::MisplacedAttribute { name, allowed, .. } => {
+                format!("`{}` can only be used on {}", name, target_list(allowed))
+            }
+            Error::InvalidInterpolation { problem, .. } => match problem {
+                InterpolationProblem::NotAVarying { .. } => {
+                    "only outputs of vertex, tessellation evaluation and geometry programs and inputs of fragment programs are interpolated"
+                        .to_string()
+                }
+                InterpolationProblem::Conflicting { .. } => {
+                    "remove one of the attributes".to_string()
+                }
+                InterpolationProblem::IntegerNotFlat { .. } => {
+                    "integers can only be passed `flat`, the value of the provoking vertex".to_string()
+                }
+            },
+            Error::InvalidMatrixLayout { problem, .. } => match problem {
+                MatrixLayoutProblem::NoMatrix { .. } => {
+                    "the attribute applies to matrices and arrays of matrices, the fields of records take attributes of their own"
+                        .to_string()
+                }
+                MatrixLayoutProblem::NotABuffer => {
+                    "only buffers are shared with the host, remove the attribute".to_string()
+                }
+                MatrixLayoutProblem::Conflicting { .. } => {
+                    "remove one of the attributes".to_string()
+                }
+            },
+            Error::InvalidMatrixConstructor { .. } => {
+                "a matrix is built from a scalar on its diagonal, another matrix, its columns or all its elements column by column"
+                    .to_string()
+            }
+            Error::InvalidSlice { problem, .. } => match problem {
+                SliceProblem::NotAnArray { .. } => {
+                    "only arrays and open arrays can be sliced".to_string()
+                }
+                SliceProblem::NotIndexed => {
+                    "a slice is a view into the array, index it or slice it again".to_string()
+                }
+                SliceProblem::Reversed { .. } | SliceProblem::PastEnd { .. } => {
+                    "`a[lo..hi]` needs `lo <= hi` and `hi` at most the length of `a`".to_string()
+                }
+                SliceProblem::IndexOutOfBounds { .. } => {
+                    "slices are indexed from 0 up to their length".to_string()
+                }
+            },
+            Error::InterpolationMismatch { .. } => {
+                "varyings are matched by name, give the output and the input the same attributes"
+                    .to_string()
+            }
+            Error::ImpureCallInConstant { .. } => {
+                "constants are computed once, move the call into a function or program body"
+                    .to_string()
+            }
+            Error::ArgumentNotAssignable { .. } => {
+                "the parameter is copied back into the argument, store the value in a local variable and pass that"
+                    .to_string()
+            }
+            Error::OutParameterNotAssigned { name, .. } => {
+                format!("assign a value to the whole of `{}` before returning", name)
+            }
+            Error::OutParameterReadBeforeAssignment { .. } => {
+                "`out` parameters start out undefined, declare it as `in out` to read the argument"
+                    .to_string()
+            }
+        }
+    }
+}
+
+impl From<Error> for Diagnostic<FileId> {
+    fn from(err: Error) -> Self {
+        let message = err.to_string();
+        let help = format!("help: {}", err.help());
+
+        let mut notes = vec![];
+
+        let labels = match err {
+            Error::MutuallyRecursiveTypeDefinitions {
+                type_def_idents,
+                cycle,
+            } => {
+                let mut labels = type_def_idents
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, loc)| {
+                        let style = if i == 0 {
+                            LabelStyle::Primary
+                        } else {
+                            LabelStyle::Secondary
+                        };
+
+                        let message = if i == 0 {
+                            "incomplete type due to a cyclic definition"
+                        } else {
+                            "type is part of a recursive cycle"
+                        };
+
+                        Label::new(style, loc.file, loc.range()).with_message(message)
+                    })
+                    .collect::<Vec<_>>();
+
+                labels.extend(cycle.iter().map(|edge| {
+                    Label::secondary(edge.use_loc.file, edge.use_loc.range())
+                        .with_message(format!("`{}` uses `{}` here", edge.user, edge.used))
+                }));
+
+                if let Some(first) = cycle.first() {
+                    let mut path = vec![first.user.as_str()];
+                    path.extend(cycle.iter().map(|edge| edge.used.as_str()));
+                    notes.push(format!("cycle: {}", path.join(" -> ")));
+                }
+
+                labels
+            }
+            Error::RecursiveTypeDefinition {
+                type_def,
+                type_name,
+                recurive_usages,
+            } => {
+                let mut labels = vec![
+                    Label::primary(type_name.file, type_name.range())
+                        .with_message("type has infinite size"),
+                    Label::secondary(type_def.file, type_def.range()),
+                ];
+                labels.extend(recurive_usages.into_iter().map(|loc| {
+                    Label::secondary(loc.file, loc.range()).with_message("recursive use here")
+                }));
+                labels
+            }
+            Error::MutuallyRecursiveFunctions {
+                function_idents,
+                cycle,
+            } => {
+                let mut labels = function_idents
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, loc)| {
+                        let style = if i == 0 {
+                            LabelStyle::Primary
+                        } else {
+                            LabelStyle::Secondary
+                        };
+                        Label::new(style, loc.file, loc.range())
+                            .with_message("function is part of a recursive cycle")
+                    })
+                    .collect::<Vec<_>>();
+
+                labels.extend(cycle.iter().map(|edge| {
+                    Label::secondary(edge.use_loc.file, edge.use_loc.range())
+                        .with_message(format!("`{}` calls `{}` here", edge.user, edge.used))
+                }));
+
+                if let Some(first) = cycle.first() {
+                    let mut path = vec![first.user.as_str()];
+                    path.extend(cycle.iter().map(|edge| edge.used.as_str()));
+                    notes.push(format!("cycle: {}", path.join(" -> ")));
+                }
+
+                labels
+            }
+            Error::RecursiveFunction {
+                function_name,
+                recursive_calls,
+            } => {
+                let mut labels = vec![Label::primary(function_name.file, function_name.range())
+                    .with_message("function calls itself")];
+                labels.extend(recursive_calls.into_iter().map(|loc| {
+                    Label::secondary(loc.file, loc.range()).with_message("recursive call here")
+                }));
+                labels
+            }
+            Error::InstantiationLimit { function, call, .. } => {
+                vec![Label::primary(call.file, call.range())
+                    .with_message(format!("instance of `{}` needed here", function))]
+            }
+            Error::ConstantTypeMismatch {
+                expected,
+                found,
+                value,
+                declared,
+                ..
+            } => vec![
+                Label::primary(value.file, value.range())
+                    .with_message(format!("expected `{}`, found `{}`", expected, found)),
+                Label::secondary(declared.file, declared.range()).with_message("declared type"),
+            ],
+            Error::StaticAssertionFailed { condition, .. } => {
+                vec![Label::primary(condition.file, condition.range()).with_message("this is false")]
+            }
+            Error::ConstEvaluation { expr, problem } => {
+                vec![Label::primary(expr.file, expr.range()).with_message(problem.to_string())]
+            }
+            Error::TypeRedefinition {
+                previous_name,
+                redefinition_name,
+                redefinition,
+            } => vec![
+                Label::primary(redefinition_name.file, redefinition_name.range())
+                    .with_message("redefinition of type"),
+                Label::secondary(redefinition.file, redefinition.range()),
+                Label::secondary(previous_name.file, previous_name.range())
+                    .with_message("first definition of type with the same name"),
+            ],
+            Error::UndefinedType { uses, .. } => uses
+                .into_iter()
+                .enumerate()
+                .map(|(i, loc)| {
+                    if i == 0 {
+                        Label::primary(loc.file, loc.range()).with_message("undefined type")
+                    } else {
+                        Label::secondary(loc.file, loc.range())
+                            .with_message("another usage of an undefined type")
+                    }
+                })
+                .collect(),
+            Error::HigherKindedGenericTypeUsed {
+                loc,
+                generic_name: _,
+            } => vec![Label::primary(loc.file, loc.range())],
+            Error::MismatchedNumberGenericArgs {
+                loc,
+                given: _,
+                expected: _,
+                def_loc,
+            } => vec![
+                Label::primary(loc.file, loc.range()),
+                Label::secondary(def_loc.file, def_loc.range()),
+            ],
+            Error::FieldRedefinition {
+                previous_name,
+                redefinition_name,
+                item,
+            } => vec![
+                Label::primary(redefinition_name.file, redefinition_name.range())
+                    .with_message("redefinition of field"),
+                Label::secondary(previous_name.file, previous_name.range())
+                    .with_message("previous definition of field with the same name"),
+                Label::secondary(item.file, item.range()),
+            ],
+            Error::GenericParamaterRedefinition {
+                previous_name,
+                redefinition,
+            } => vec![
+                Label::primary(redefinition.file, redefinition.range())
+                    .with_message("redefinition of generic parameter"),
+                Label::secondary(previous_name.file, previous_name.range())
+                    .with_message("previous definition of generic paramater with the same name"),
+            ],
+            Error::FunctionRedefinition {
+                previous_name,
+                previous_sig,
+                redefinition_name,
+                redefinition_sig,
+            } => vec![
+                Label::primary(redefinition_name.file, redefinition_name.range())
+                    .with_message("redefinition of function"),
+                Label::secondary(redefinition_sig.file, redefinition_sig.range()),
+                Label::secondary(previous_name.file, previous_name.range())
+                    .with_message("previous definition of function with the same name"),
+                Label::secondary(previous_sig.file, previous_sig.range()),
+            ],
+            Error::ConstantRedefinition {
+                previous_name,
+                previous_def,
+                redefinition_name,
+                redefinition_def,
+            } => vec![
+                Label::primary(redefinition_name.file, redefinition_name.range())
+                    .with_message("redefinition of constant"),
+                Label::secondary(redefinition_def.file, redefinition_def.range()),
+                Label::secondary(previous_name.file, previous_name.range())
+                    .with_message("previous definition of constant with the same name"),
+                Label::secondary(previous_def.file, previous_def.range()),
+            ],
+            Error::SpaceRedefinition {
+                previous_name,
+                redefinition_name,
+            } => vec![
+                Label::primary(redefinition_name.file, redefinition_name.range())
+                    .with_message("redefinition of space"),
+                Label::secondary(previous_name.file, previous_name.range())
+                    .with_message("first declaration of space with the same name"),
+            ],
+            Error::NameConflict {
+                previous_kind,
+                previous,
+                kind,
+                conflict,
+                ..
+            } => vec![
+                Label::primary(conflict.file, conflict.range())
+                    .with_message(format!("{} declared here", kind)),
+                Label::secondary(previous.file, previous.range()).with_message(format!(
+                    "{} with the same name declared here",
+                    previous_kind
+                )),
+            ],
+            Error::UndefinedVariable { uses, .. } => uses
+                .into_iter()
+                .enumerate()
+                .map(|(i, loc)| {
+                    if i == 0 {
+                        Label::primary(loc.file, loc.range()).with_message("undefined variable")
+                    } else {
+                        Label::secondary(loc.file, loc.range())
+                            .with_message("another usage of the undefined variable")
+                    }
+                })
+                .collect(),
+            Error::UndefinedFunction { uses, .. } => uses
+                .into_iter()
+                .enumerate()
+                .map(|(i, loc)| {
+                    if i == 0 {
+                        Label::primary(loc.file, loc.range()).with_message("undefined function")
+                    } else {
+                        Label::secondary(loc.file, loc.range())
+                            .with_message("another call of the undefined function")
+                    }
+                })
+                .collect(),
+            Error::UndefinedSpace { uses, .. } => uses
+                .into_iter()
+                .enumerate()
+                .map(|(i, loc)| {
+                    if i == 0 {
+                        Label::primary(loc.file, loc.range()).with_message("undeclared space")
+                    } else {
+                        Label::secondary(loc.file, loc.range())
+                            .with_message("another usage of an undeclared space")
+                    }
+                })
+                .collect(),
+            Error::CyclicSpaceHierarchy {
+                space_names,
+                parents,
+                cycle,
+            } => {
+                let mut labels = vec![Label::primary(space_names[0].file, space_names[0].range())];
+                labels.extend(parents.iter().zip(&cycle).map(|(loc, space)| {
+                    Label::secondary(loc.file, loc.range())
+                        .with_message(format!("parent of `{}`", space))
+                }));
+
+                let mut path = cycle.iter().map(String::as_str).collect::<Vec<_>>();
+                path.extend(cycle.first().map(String::as_str));
+                notes.push(format!("cycle: {}", path.join(" -> ")));
+
+                labels
+            }
+            Error::InvalidSpaceTransform {
+                space: _,
+                via,
+                problem,
+            } => {
+                let message = match problem {
+                    SpaceTransformProblem::UndefinedConstant { .. } => {
+                        "constant not defined".to_string()
+                    }
+                    SpaceTransformProblem::NotAMatrix { type_name }
+                    | SpaceTransformProblem::WrongSpaces { type_name, .. } => {
+                        format!("constant has type `{}`", type_name)
+                    }
+                };
+                vec![Label::primary(via.file, via.range()).with_message(message)]
+            }
+            Error::LocalRedefinition {
+                previous,
+                redefinition,
+                ..
+            } => vec![
+                Label::primary(redefinition.file, redefinition.range())
+                    .with_message("declared again in the same scope"),
+                Label::secondary(previous.file, previous.range())
+                    .with_message("previous declaration"),
+            ],
+            Error::InvalidCast { cast, problem, .. } => {
+                let message = match problem {
+                    CastProblem::Unconvertible => "no conversion between these types".to_string(),
+                    CastProblem::ComponentCount { from, to } => {
+                        format!("{} components cast to {}", from, to)
+                    }
+                    CastProblem::AngleUnits => "angles in different units".to_string(),
+                    CastProblem::BitcastSize { from, to } => {
+                        format!("{} bit components reinterpreted as {} bits", from, to)
+                    }
+                };
+                vec![Label::primary(cast.file, cast.range()).with_message(message)]
+            }
+            Error::OperandTypeMismatch {
+                left,
+                left_loc,
+                right,
+                right_loc,
+                ..
+            } => vec![
+                Label::primary(left_loc.file, left_loc.merge(right_loc).range()),
+                Label::secondary(left_loc.file, left_loc.range())
+                    .with_message(format!("`{}`", left)),
+                Label::secondary(right_loc.file, right_loc.range())
+                    .with_message(format!("`{}`", right)),
+            ],
+            Error::InvalidOperand { operation, ty, .. } => {
+                vec![Label::primary(operation.file, operation.range())
+                    .with_message(format!("`{}`", ty))]
+            }
+            Error::InvalidComposite { value, problem, .. } => {
+                vec![Label::primary(value.file, value.range()).with_message(problem.to_string())]
+            }
+            Error::InvalidDerive { loc, problem, .. } => {
+                let message = match problem {
+                    DeriveProblem::Unknown(name) => format!("unknown function `{}`", name),
+                    DeriveProblem::NotRecord => "not a record".to_string(),
+                    DeriveProblem::Generic => "the record is generic".to_string(),
+                    DeriveProblem::Incomparable { field, type_name } => {
+                        format!("`{}` has the type `{}`", field, type_name)
+                    }
+                    DeriveProblem::Conflict(name) => format!("`{}` is defined", name),
+                };
+                vec![Label::primary(loc.file, loc.range()).with_message(message)]
+            }
+            Error::InvalidDerivedCall { loc, problem, .. } => {
+                vec![Label::primary(loc.file, loc.range()).with_message(problem.to_string())]
+            }
+            Error::InvalidDestructuring { loc, problem } => {
+                let primary =
+                    Label::primary(loc.file, loc.range()).with_message(problem.to_string());
+                match problem {
+                    DestructureProblem::Duplicate { first, .. } => vec![
+                        primary,
+                        Label::secondary(first.file, first.range())
+                            .with_message("first assigned here"),
+                    ],
+                    _ => vec![primary],
+                }
+            }
+            Error::InvalidOptional { loc, problem } => {
+                vec![Label::primary(loc.file, loc.range()).with_message(problem.to_string())]
+            }
+            Error::InvalidDebugOutput { loc, problem } => {
+                vec![Label::primary(loc.file, loc.range()).with_message(problem.to_string())]
+            }
+            Error::InvalidLookupTable {
+                attribute, problem, ..
+            } => {
+                let message = match problem {
+                    LutProblem::Size => format!("expected a number from 1 to {}", MAX_LUT_SIZE),
+                    LutProblem::Generic => "the function is generic".to_string(),
+                    LutProblem::Parameters => {
+                        "expected one `int`, `uint` or `float` parameter".to_string()
+                    }
+                    LutProblem::Effects(effects) => {
+                        format!("the function has effects: {}", effects)
+                    }
+                };
+                vec![Label::primary(attribute.file, attribute.range()).with_message(message)]
+            }
+            Error::InvalidProfileLabel { attribute, problem } => {
+                let primary = Label::primary(attribute.file, attribute.range());
+                match problem {
+                    ProfileProblem::Argument => {
+                        vec![primary.with_message("expected a string literal")]
+                    }
+                    ProfileProblem::Empty => vec![primary.with_message("the label is empty")],
+                    ProfileProblem::Duplicate { first } => vec![
+                        primary.with_message("label used again here"),
+                        Label::secondary(first.file, first.range()).with_message("first used here"),
+                    ],
+                }
+            }
+            Error::InvalidSelectMask {
+                mask,
+                mask_components,
+                value,
+                value_loc,
+                ..
+            } => {
+                let message = match mask_components {
+                    Some(components) => format!("selects {} components", components),
+                    None => "not a boolean mask".to_string(),
+                };
+                vec![
+                    Label::primary(mask.file, mask.range()).with_message(message),
+                    Label::secondary(value_loc.file, value_loc.range())
+                        .with_message(format!("`{}`", value)),
+                ]
+            }
+            Error::IntegerLiteralOutOfRange {
+                literal, min, max, ..
+            } => vec![Label::primary(literal.file, literal.range())
+                .with_message(format!("the type holds values from {} to {}", min, max))],
+            Error::DeniedLint { group, warning } => {
+                notes.push(format!("the `{}` lints are denied", group));
+                warning_labels(*warning, &mut notes)
+            }
+            Error::SpaceMismatch {
+                expr,
+                from,
+                to,
+                chain,
+            } => {
+                if let Some(chain) = chain.filter(|chain| !chain.is_empty()) {
+                    let mut path = vec![chain[0].from.clone()];
+                    path.extend(chain.iter().map(|step| step.to.clone()));
+                    notes.push(format!("spaces: {}", path.join(" -> ")));
+                }
+                vec![Label::primary(expr.file, expr.range())
+                    .with_message(format!("in `{}`, expected a value in `{}`", from, to))]
+            }
+            Error::ColourEncodingMismatch { expr, srgb } => {
+                let message = match srgb {
+                    true => "sRGB encoded, expected a linear colour",
+                    false => "linear, expected an sRGB encoded colour",
+                };
+                vec![Label::primary(expr.file, expr.range()).with_message(message)]
+            }
+            Error::InvalidNormalizedType { type_, .. } => {
+                vec![Label::primary(type_.file, type_.range())]
+            }
+            Error::NotNormalized { expr, intrinsic } => {
+                let message = match intrinsic {
+                    Some(intrinsic) => {
+                        format!("`{}` expects a normalized vector here", intrinsic.name())
+                    }
+                    None => "expected a normalized vector".to_string(),
+                };
+                vec![Label::primary(expr.file, expr.range()).with_message(message)]
+            }
+            Error::AngleUnitMismatch {
+                expr,
+                expected,
+                intrinsic,
+                ..
+            } => {
+                let message = match intrinsic {
+                    Some(intrinsic) => format!(
+                        "`{}` expects `{}` here",
+                        intrinsic.name(),
+                        angle_type_name(expected)
+                    ),
+                    None => format!("expected `{}`", angle_type_name(expected)),
+                };
+                vec![Label::primary(expr.file, expr.range()).with_message(message)]
+            }
+            Error::InvalidImageType { type_, .. } => {
+                vec![Label::primary(type_.file, type_.range())]
+            }
+            Error::MisplacedImage { type_ } => {
+                vec![Label::primary(type_.file, type_.range())
+                    .with_message("type contains an image")]
+            }
+            Error::NotAnImage {
+                expr, intrinsic, ..
+            } => vec![Label::primary(expr.file, expr.range())
+                .with_message(format!("`{}` expects an image here", intrinsic.name()))],
+            Error::ImageAccessViolation {
+                image,
+                intrinsic,
+                access,
+            } => vec![
+                Label::primary(image.file, image.range()).with_message(format!(
+                    "`{}` of an image with `{}` access",
+                    intrinsic.name(),
+                    access.name()
+                )),
+            ],
+            Error::InvalidImageCoordinates {
+                coords, expected, ..
+            } => vec![Label::primary(coords.file, coords.range())
+                .with_message(format!("expected `{}`", expected))],
+            Error::TexelTypeMismatch {
+                texel, expected, ..
+            } => vec![Label::primary(texel.file, texel.range())
+                .with_message(format!("expected `{}`", expected))],
+            Error::InvalidTextureType { type_, .. } => {
+                vec![Label::primary(type_.file, type_.range())]
+            }
+            Error::MisplacedTexture { type_ } => {
+                vec![Label::primary(type_.file, type_.range())
+                    .with_message("type contains a texture or sampler")]
+            }
+            Error::NotATexture {
+                expr, intrinsic, ..
+            } => vec![Label::primary(expr.file, expr.range())
+                .with_message(format!("`{}` expects a texture here", intrinsic.name()))],
+            Error::UnsupportedSampling {
+                texture, type_name, ..
+            } => vec![Label::primary(texture.file, texture.range())
+                .with_message(format!("texture of type `{}`", type_name))],
+            Error::SamplingArity { call, expected, .. } => {
+                vec![Label::primary(call.file, call.range())
+                    .with_message(format!("expected {} arguments", expected))]
+            }
+            Error::SamplingArgumentMismatch { arg, expected, .. } => {
+                vec![Label::primary(arg.file, arg.range())
+                    .with_message(format!("expected `{}`", expected))]
+            }
+            Error::ImageStoreInVertex {
+                callee,
+                call,
+                intrinsic,
+                program,
+            } => {
+                if !intrinsic {
+                    notes.push(format!("`{}` stores to images", callee));
+                }
+                vec![
+                    Label::primary(call.file, call.range())
+                        .with_message("images can't be written by vertex programs"),
+                    Label::secondary(program.file, program.range())
+                        .with_message("this is a vertex program"),
+                ]
+            }
+            Error::MixedAngleUnits {
+                operation,
+                radians,
+                degrees,
+            } => vec![
+                Label::primary(operation.file, operation.range()),
+                Label::secondary(radians.file, radians.range()).with_message("radians"),
+                Label::secondary(degrees.file, degrees.range()).with_message("degrees"),
+            ],
+            Error::MixedColourEncodings {
+                operation,
+                srgb,
+                linear,
+            } => vec![
+                Label::primary(operation.file, operation.range()),
+                Label::secondary(srgb.file, srgb.range()).with_message("sRGB encoded"),
+                Label::secondary(linear.file, linear.range()).with_message("linear"),
+            ],
+            Error::ConflictingGenericArgument {
+                generic_name,
+                arg,
+                arg_type,
+                previous_arg,
+                previous_type,
+            } => vec![
+                Label::primary(arg.file, arg.range())
+                    .with_message(format!("argument has type `{}`", arg_type)),
+                Label::secondary(previous_arg.file, previous_arg.range()).with_message(format!(
+                    "`{}` inferred as `{}` from this argument",
+                    generic_name, previous_type
+                )),
+            ],
+            Error::UninferableGenericArgument {
+                generic_name: _,
+                call,
+                generic,
+            } => vec![
+                Label::primary(call.file, call.range()).with_message("in this call"),
+                Label::secondary(generic.file, generic.range())
+                    .with_message("generic parameter defined here"),
+            ],
+            Error::InvalidBufferType {
+                name: _,
+                type_,
+                attribute,
+                class,
+                problem,
+                field_path,
+            } => {
+                let part = match problem {
+                    BufferTypeProblem::Bool => "a boolean",
+                    BufferTypeProblem::Atomic => "an atomic",
+                    BufferTypeProblem::Image => "an image",
+                    BufferTypeProblem::Texture => "a texture or sampler",
+                    BufferTypeProblem::AccelerationStructure => "an acceleration structure",
+                    BufferTypeProblem::OpenArray | BufferTypeProblem::OpenArrayNotLast => {
+                        "an array without a size"
+                    }
+                };
+                let message = if field_path.is_empty() {
+                    format!("type is {}", part)
+                } else {
+                    format!("field `{}` is {}", field_path.join("."), part)
+                };
+                vec![
+                    Label::primary(type_.file, type_.range()).with_message(message),
+                    Label::secondary(attribute.file, attribute.range())
+                        .with_message(format!("bound to a {} here", class)),
+                ]
+            }
+            Error::InvalidLayoutAttribute {
+                name: _,
+                attribute,
+                problem,
+            } => {
+                let message = match problem {
+                    LayoutAttributeProblem::ExpectedInteger => {
+                        "expected a single integer argument".to_string()
+                    }
+                    LayoutAttributeProblem::NotPowerOfTwo(n) => {
+                        format!("{} is not a power of two", n)
+                    }
+                };
+                vec![Label::primary(attribute.file, attribute.range()).with_message(message)]
+            }
+            Error::FieldLayoutViolation {
+                field_name,
+                attribute,
+                buffer,
+                class,
+                rules: _,
+                kind,
+            } => {
+                let message = match kind {
+                    LayoutViolationKind::Overlap {
+                        offset,
+                        previous,
+                        previous_end,
+                    } => format!(
+                        "offset {} is before the end of `{}` at {}",
+                        offset, previous, previous_end
+                    ),
+                    LayoutViolationKind::Misaligned { offset, align } => format!(
+                        "offset {} is not a multiple of the alignment {}",
+                        offset, align
+                    ),
+                    LayoutViolationKind::AlignTooSmall { align, required } => format!(
+                        "`{}` needs an alignment of at least {}, not {}",
+                        field_name, required, align
+                    ),
+                };
+                vec![
+                    Label::primary(attribute.file, attribute.range()).with_message(message),
+                    Label::secondary(buffer.file, buffer.range())
+                        .with_message(format!("type used in a {} here", class)),
+                ]
+            }
+            Error::InvalidAtomicPlacement { type_, .. } => {
+                vec![Label::primary(type_.file, type_.range())
+                    .with_message("type contains an atomic")]
+            }
+            Error::WorkgroupOutsideCompute {
+                program_name: _,
+                program,
+                var,
+                stage,
+            } => {
+                let message = match stage {
+                    Some(stage) => format!("this is {} {} program", stage.article(), stage),
+                    None => "this program has no stage attribute".to_string(),
+                };
+                vec![
+                    Label::primary(var.file, var.range())
+                        .with_message("shared by the invocations of a workgroup"),
+                    Label::secondary(program.file, program.range()).with_message(message),
+                ]
+            }
+            Error::BarrierInNonUniformControlFlow {
+                callee,
+                call,
+                intrinsic,
+                reason,
+            } => {
+                if !intrinsic {
+                    notes.push(format!("`{}` calls a barrier", callee));
+                }
+                let loc = reason.location();
+                vec![
+                    Label::primary(call.file, call.range())
+                        .with_message("not all invocations may reach this call"),
+                    Label::secondary(loc.file, loc.range())
+                        .with_message(non_uniform_message(reason)),
+                ]
+            }
+            Error::DerivativeOutsideFragment {
+                callee,
+                call,
+                intrinsic,
+                program,
+                stage,
+            } => {
+                if !intrinsic {
+                    notes.push(format!("`{}` uses derivatives", callee));
+                }
+                let message = match stage {
+                    Some(stage) => format!("this is {} {} program", stage.article(), stage),
+                    None => "this program has no stage attribute".to_string(),
+                };
+                vec![
+                    Label::primary(call.file, call.range())
+                        .with_message("derivatives are only available to fragments"),
+                    Label::secondary(program.file, program.range()).with_message(message),
+                ]
+            }
+            Error::DiscardOutsideFragment {
+                callee,
+                loc,
+                program,
+                stage,
+            } => {
+                if let Some(callee) = callee {
+                    notes.push(format!("`{}` discards fragments", callee));
+                }
+                let message = match stage {
+                    Some(stage) => format!("this is {} {} program", stage.article(), stage),
+                    None => "this program has no stage attribute".to_string(),
+                };
+                vec![
+                    Label::primary(loc.file, loc.range())
+                        .with_message("only fragments can be discarded"),
+                    Label::secondary(program.file, program.range()).with_message(message),
+                ]
+            }
+            Error::TraceRayOutsideStage {
+                callee,
+                call,
+                intrinsic,
+                program,
+                stage,
+            } => {
+                if !intrinsic {
+                    notes.push(format!("`{}` traces rays", callee));
+                }
+                let message = match stage {
+                    Some(stage) => format!("this is {} {} program", stage.article(), stage),
+                    None => "this program has no stage attribute".to_string(),
+                };
+                vec![
+                    Label::primary(call.file, call.range())
+                        .with_message("rays can't be traced here"),
+                    Label::secondary(program.file, program.range()).with_message(message),
+                ]
+            }
+            Error::EmissionOutsideGeometry {
+                callee,
+                call,
+                intrinsic,
+                program,
+                stage,
+            } => {
+                if !intrinsic {
+                    notes.push(format!("`{}` emits primitives", callee));
+                }
+                let message = match stage {
+                    Some(stage) => format!("this is {} {} program", stage.article(), stage),
+                    None => "this program has no stage attribute".to_string(),
+                };
+                vec![
+                    Label::primary(call.file, call.range())
+                        .with_message("primitives can't be emitted here"),
+                    Label::secondary(program.file, program.range()).with_message(message),
+                ]
+            }
+            Error::MisplacedAccelerationStructure { type_ } => {
+                vec![Label::primary(type_.file, type_.range())
+                    .with_message("type contains an acceleration structure")]
+            }
+            Error::TraceRayArity { call, .. } => vec![Label::primary(call.file, call.range())
+                .with_message(format!(
+                    "expected {} arguments",
+                    ray_tracing::TRACE_RAY_PARAMS.len()
+                ))],
+            Error::TraceRayArgumentMismatch { arg, expected, .. } => {
+                vec![Label::primary(arg.file, arg.range())
+                    .with_message(format!("expected `{}`", expected))]
+            }
+            Error::InvalidRayPayload { attribute, problem } => {
+                let primary = Label::primary(attribute.file, attribute.range());
+                match problem {
+                    PayloadProblem::ExpectedInteger => {
+                        vec![primary.with_message("expected a single integer argument")]
+                    }
+                    PayloadProblem::OutsideRayTracing { stage } => {
+                        let message = match stage {
+                            Some(stage) => {
+                                format!("payload of {} {} program", stage.article(), stage)
+                            }
+                            None => "payload of a program without a stage".to_string(),
+                        };
+                        vec![primary.with_message(message)]
+                    }
+                    PayloadProblem::Duplicate { previous } => vec![
+                        primary.with_message("location used twice"),
+                        Label::secondary(previous.file, previous.range())
+                            .with_message("previous payload at this location"),
+                    ],
+                }
+            }
+            Error::PayloadTypeMismatch {
+                location: _,
+                found,
+                expected,
+                payload,
+                previous,
+            } => vec![
+                Label::primary(payload.file, payload.range())
+                    .with_message(format!("payload of type `{}`", found)),
+                Label::secondary(previous.file, previous.range()).with_message(format!(
+                    "payload of type `{}` at the same location",
+                    expected
+                )),
+            ],
+            Error::UnknownPayloadLocation {
+                location,
+                arg,
+                program_name: _,
+                program,
+            } => vec![
+                Label::primary(arg.file, arg.range())
+                    .with_message(format!("no payload at location {}", location)),
+                Label::secondary(program.file, program.range())
+                    .with_message("the call is reached from this program"),
+            ],
+            Error::InvalidMeshOutput { output, problem } => {
+                let primary = Label::primary(output.file, output.range());
+                match problem {
+                    MeshOutputProblem::OutsideMesh { stage } => {
+                        let message = match stage {
+                            Some(stage) => {
+                                format!("output of {} {} program", stage.article(), stage)
+                            }
+                            None => "output of a program without a stage".to_string(),
+                        };
+                        vec![primary.with_message(message)]
+                    }
+                    MeshOutputProblem::MissingRate => {
+                        vec![primary.with_message("neither per vertex nor per primitive")]
+                    }
+                    MeshOutputProblem::ExpectedArray { found } => {
+                        vec![primary.with_message(format!("output of type `{}`", found))]
+                    }
+                    MeshOutputProblem::ExpectedTriangles { found } => {
+                        vec![primary.with_message(format!("elements of type `{}`", found))]
+                    }
+                    MeshOutputProblem::LengthMismatch {
+                        length,
+                        expected,
+                        previous,
+                    } => vec![
+                        primary.with_message(format!("array of length {}", length)),
+                        Label::secondary(previous.file, previous.range())
+                            .with_message(format!("array of length {}", expected)),
+                    ],
+                }
+            }
+            Error::MeshOutputLimit { output, limit, .. } => {
+                vec![Label::primary(output.file, output.range())
+                    .with_message(format!("the target supports at most {}", limit))]
+            }
+            Error::MissingPrimitiveIndices {
+                program_name: _,
+                program,
+            } => vec![Label::primary(program.file, program.range())
+                .with_message("the primitives of this program have no vertices")],
+            Error::InvalidStageAttribute {
+                attribute,
+                stage: _,
+                problem,
+            } => {
+                let primary = Label::primary(attribute.file, attribute.range());
+                match problem {
+                    AttributeProblem::WrongStage { found } => {
+                        let message = match found {
+                            Some(found) => {
+                                format!("attribute of {} {} program", found.article(), found)
+                            }
+                            None => "attribute of a program without a stage".to_string(),
+                        };
+                        vec![primary.with_message(message)]
+                    }
+                    AttributeProblem::ExpectedName { .. } => {
+                        vec![primary.with_message("expected a single name argument")]
+                    }
+                    AttributeProblem::ExpectedCount { what, .. } => {
+                        vec![primary.with_message(format!("expected the number of {}", what))]
+                    }
+                    AttributeProblem::Duplicate { previous } => vec![
+                        primary.with_message("second attribute"),
+                        Label::secondary(previous.file, previous.range())
+                            .with_message("first attribute"),
+                    ],
+                }
+            }
+            Error::MissingStageAttribute {
+                program_name: _,
+                program,
+                attribute,
+            } => vec![Label::primary(program.file, program.range())
+                .with_message(format!("needs a `{}` attribute", attribute))],
+            Error::InvalidPatch { var, stage, output } => {
+                let side = if output { "output" } else { "input" };
+                let message = match stage {
+                    Some(stage) => format!("{} of {} {} program", side, stage.article(), stage),
+                    None => format!("{} of a program without a stage", side),
+                };
+                vec![Label::primary(var.file, var.range()).with_message(message)]
+            }
+            Error::ExpectedControlPointArray {
+                var,
+                found,
+                length: _,
+            } => vec![Label::primary(var.file, var.range())
+                .with_message(format!("varying of type `{}`", found))],
+            Error::ExpectedPrimitiveArray {
+                var,
+                found,
+                length: _,
+            } => vec![Label::primary(var.file, var.range())
+                .with_message(format!("input of type `{}`", found))],
+            Error::EmitWithoutPosition {
+                call,
+                program_name: _,
+                program,
+            } => vec![
+                Label::primary(call.file, call.range()).with_message("emits a vertex"),
+                Label::secondary(program.file, program.range())
+                    .with_message("this program has no `Position` output"),
+            ],
+            Error::TooManyEmittedVertices {
+                call,
+                emitted,
+                max: _,
+                attribute,
+            } => vec![
+                Label::primary(call.file, call.range())
+                    .with_message(format!("emits vertex {} whenever it is reached", emitted)),
+                Label::secondary(attribute.file, attribute.range())
+                    .with_message("the most vertices the program emits"),
+            ],
+            Error::InterfaceMismatch {
+                name: _,
+                output,
+                output_loc,
+                input,
+                input_loc,
+            } => vec![
+                Label::primary(input_loc.file, input_loc.range())
+                    .with_message(format!("input of type {}", input)),
+                Label::secondary(output_loc.file, output_loc.range())
+                    .with_message(format!("output of type {}", output)),
+            ],
+            Error::InvalidRelaxedPrecision {
+                name: _,
+                attribute,
+                type_,
+                type_name,
+            } => vec![
+                Label::primary(type_.file, type_.range())
+                    .with_message(format!("values of type `{}` cannot be relaxed", type_name)),
+                Label::secondary(attribute.file, attribute.range())
+                    .with_message("relaxed precision requested here"),
+            ],
+            Error::UnsupportedFeature {
+                feature: _,
+                profile,
+                loc,
+            } => vec![Label::primary(loc.file, loc.range())
+                .with_message(format!("not supported by the `{}` profile", profile))],
+            Error::BindingConflict {
+                binding: _,
+                previous,
+                attribute,
+            } => vec![
+                Label::primary(attribute.file, attribute.range())
+                    .with_message("binding used again here"),
+                Label::secondary(previous.file, previous.range()).with_message("first used here"),
+            ],
+            Error::IndexOutOfBounds { index, value, len } => {
+                vec![Label::primary(index.file, index.range())
+                    .with_message(format!("index {} of an array of {} elements", value, len))]
+            }
+            Error::StringLiteralAsValue { literal } => {
+                vec![Label::primary(literal.file, literal.range())
+                    .with_message("a string has no type")]
+            }
+            Error::MisplacedAttribute {
+                attribute, target, ..
+            } => vec![Label::primary(attribute.file, attribute.range())
+                .with_message(format!("not allowed on a {}", target))],
+            Error::InvalidInterpolation {
+                name: _,
+                attribute,
+                problem,
+            } => {
+                let primary = Label::primary(attribute.file, attribute.range());
+                match problem {
+                    InterpolationProblem::NotAVarying { stage, output } => {
+                        let side = if output { "output" } else { "input" };
+                        let message = match stage {
+                            Some(stage) => {
+                                format!("{} of {} {} program", side, stage.article(), stage)
+                            }
+                            None => format!("{} of a program without a stage", side),
+                        };
+                        vec![primary.with_message(message)]
+                    }
+                    InterpolationProblem::Conflicting { previous } => vec![
+                        primary.with_message("second interpolation"),
+                        Label::secondary(previous.file, previous.range())
+                            .with_message("first interpolation"),
+                    ],
+                    InterpolationProblem::IntegerNotFlat { type_name } => {
+                        vec![primary.with_message(format!(
+                            "values of type `{}` are not interpolated",
+                            type_name
+                        ))]
+                    }
+                }
+            }
+            Error::InvalidMatrixLayout {
+                name: _,
+                attribute,
+                problem,
+            } => {
+                let primary = Label::primary(attribute.file, attribute.range());
+                match problem {
+                    MatrixLayoutProblem::NoMatrix { type_name } => {
+                        vec![primary.with_message(format!(
+                            "values of type `{}` have no matrices of their own",
+                            type_name
+                        ))]
+                    }
+                    MatrixLayoutProblem::NotABuffer => {
+                        vec![primary.with_message("the constant is not bound to a buffer")]
+                    }
+                    MatrixLayoutProblem::Conflicting { previous } => vec![
+                        primary.with_message("second matrix layout"),
+                        Label::secondary(previous.file, previous.range())
+                            .with_message("first matrix layout"),
+                    ],
+                }
+            }
+            Error::InvalidMatrixConstructor {
+                constructor,
+                type_name: _,
+                problem,
+            } => {
+                let message = match problem {
+                    MatrixConstructorProblem::ArgumentCount { found } => {
+                        format!("{} arguments", found)
+                    }
+                    MatrixConstructorProblem::Column { index } => format!(
+                        "argument {} is not a vector with one component per row",
+                        index + 1
+                    ),
+                    MatrixConstructorProblem::Mixed => "scalars and vectors mixed".to_string(),
+                };
+                vec![Label::primary(constructor.file, constructor.range()).with_message(message)]
+            }
+            Error::InvalidSlice { slice, problem } => {
+                let message = match problem {
+                    SliceProblem::NotAnArray { .. } => "not an array".to_string(),
+                    SliceProblem::NotIndexed => "not indexed".to_string(),
+                    SliceProblem::Reversed { lo, hi } => {
+                        format!("starts at {} after its end at {}", lo, hi)
+                    }
+                    SliceProblem::PastEnd { hi, len } => {
+                        format!("ends at {} but the array has {} elements", hi, len)
+                    }
+                    SliceProblem::IndexOutOfBounds { index, len } => {
+                        format!("index {} of a slice of {} elements", index, len)
+                    }
+                };
+                vec![Label::primary(slice.file, slice.range()).with_message(message)]
+            }
+            Error::InterpolationMismatch {
+                name: _,
+                output,
+                output_loc,
+                input,
+                input_loc,
+            } => vec![
+                Label::primary(input_loc.file, input_loc.range())
+                    .with_message(format!("the input is {}", input)),
+                Label::secondary(output_loc.file, output_loc.range())
+                    .with_message(format!("the output is {}", output)),
+            ],
+            Error::ImpureCallInConstant {
+                constant,
+                callee: _,
+                call,
+                effects,
+            } => vec![
+                Label::primary(call.file, call.range())
+                    .with_message(format!("effects: {}", effects)),
+                Label::secondary(constant.file, constant.range())
+                    .with_message("in the value of this constant"),
+            ],
+            Error::ArgumentNotAssignable {
+                param_name: _,
+                mode,
+                arg,
+                param,
+                reason,
+            } => {
+                let message = match reason {
+                    NotAssignable::Constant => "constants cannot be written to",
+                    NotAssignable::LoopVariable => "loop variables cannot be written to",
+                    NotAssignable::Value => "this is a value, not a variable",
+                };
+                vec![
+                    Label::primary(arg.file, arg.range()).with_message(message),
+                    Label::secondary(param.file, param.range())
+                        .with_message(format!("declared `{}` here", mode_keyword(mode))),
+                ]
+            }
+            Error::OutParameterNotAssigned { name, param, exit } => vec![
+                Label::primary(exit.file, exit.range()).with_message(format!(
+                    "the function can return here without assigning `{}`",
+                    name
+                )),
+                Label::secondary(param.file, param.range()).with_message("declared `out` here"),
+            ],
+            Error::OutParameterReadBeforeAssignment {
+                name: _,
+                param,
+                use_,
+            } => vec![
+                Label::primary(use_.file, use_.range()).with_message("read before it is assigned"),
+                Label::secondary(param.file, param.range()).with_message("declared `out` here"),
+            ],
+        };
+
+        notes.push(help);
+
+        Diagnostic::error()
+            .with_message(message)
+            .with_labels(labels)
+            .with_notes(notes)
+    }
+}
+
+fn mode_keyword(mode: thiol_hir::ParamMode) -> &'static str {
+    match mode {
+        thiol_hir::ParamMode::In => "in",
+        thiol_hir::ParamMode::Out => "out",
+        thiol_hir::ParamMode::InOut => "in out",
+    }
+}
+
+/// The type of an angle in the unit, or `float`.
+fn angle_type_name(unit: Option<AngleUnit>) -> &'static str {
+    unit.map_or("float", AngleUnit::name)
+}
+
+fn non_uniform_message(reason: NonUniformReason) -> &'static str {
+    match reason {
+        NonUniformReason::Condition(_) => "this condition may differ between invocations",
+        NonUniformReason::LoopBounds(_) => "these loop bounds may differ between invocations",
+        NonUniformReason::EarlyExit(_) => "some invocations may leave here",
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::DerivativeInNonUniformControlFlow { callee, .. }
+            | Warning::SubgroupInNonUniformControlFlow { callee, .. } => {
+                write!(f, "`{}` is called in non-uniform control flow", callee)
+            }
+            Warning::UnknownAttribute { name, .. } => write!(f, "unknown attribute `{}`", name),
+            Warning::Shadowing { name, shadowed, .. } => match shadowed {
+                Shadowed::Local(_) => write!(f, "`{}` shadows a variable", name),
+                Shadowed::Constant(_) => write!(f, "`{}` shadows a constant", name),
+                Shadowed::Type(_) => write!(f, "`{}` shadows a type", name),
+                Shadowed::Intrinsic => write!(f, "`{}` shadows an intrinsic", name),
+            },
+            Warning::CaseCollision {
+                kind,
+                name,
+                previous_name,
+                ..
+            } => write!(
+                f,
+                "{} `{}` differs from `{}` only in case",
+                kind, name, previous_name
+            ),
+            Warning::ConfusableName {
+                kind,
+                name,
+                previous_name,
+                ..
+            } => write!(f, "{} `{}` looks like `{}`", kind, name, previous_name),
+            Warning::ImplicitOverflow { op, ty, .. } => {
+                write!(f, "`{}` on `{}` may overflow", op, ty)
+            }
+            Warning::FloatEquality { op, ty, .. } => {
+                write!(f, "`{}` compares `{}` values exactly", op, ty)
+            }
+            Warning::LossyCast { from, to, .. } => {
+                write!(f, "cast from `{}` to `{}` may lose information", from, to)
+            }
+            Warning::ReservedWord { word, .. } => write!(f, "`{}` is a reserved word", word),
+        }
+    }
+}
+
+impl Warning {
+    /// The location the warning is reported at, used to order warnings
+    pub fn location(&self) -> FileLocation {
+        match self {
+            Warning::DerivativeInNonUniformControlFlow { call, .. }
+            | Warning::SubgroupInNonUniformControlFlow { call, .. } => *call,
+            Warning::UnknownAttribute { attribute, .. } => *attribute,
+            Warning::Shadowing { declaration, .. } => *declaration,
+            Warning::CaseCollision { collision, .. } => *collision,
+            Warning::ConfusableName { confusable, .. } => *confusable,
+            Warning::ImplicitOverflow { operation, .. } => *operation,
+            Warning::FloatEquality { comparison, .. } => *comparison,
+            Warning::LossyCast { cast, .. } => *cast,
+            Warning::ReservedWord { name, .. } => *name,
+        }
+    }
+
+    /// The lint group that chooses how the warning is reported, if any
+    pub fn lint_group(&self) -> Option<LintGroup> {
+        match self {
+            Warning::DerivativeInNonUniformControlFlow { .. }
+            | Warning::UnknownAttribute { .. } => None,
+            Warning::Shadowing { .. } => Some(LintGroup::Shadowing),
+            Warning::CaseCollision { .. } => Some(LintGroup::CaseCollisions),
+            Warning::ConfusableName { .. } => Some(LintGroup::ConfusableNames),
+            Warning::SubgroupInNonUniformControlFlow { .. } => Some(LintGroup::SubgroupUniformity),
+            Warning::ImplicitOverflow { .. } => Some(LintGroup::IntegerOverflow),
+            Warning::FloatEquality { .. } => Some(LintGroup::FloatEquality),
+            Warning::LossyCast { .. } => Some(LintGroup::LossyCasts),
+            Warning::ReservedWord { .. } => Some(LintGroup::ReservedWords),
+        }
+    }
+
+    /// Suggestion on how to resolve the warning
+    pub fn help(&self) -> String {
+        match self {
+            Warning::DerivativeInNonUniformControlFlow { .. } => {
+                "derivatives are undefined when neighbouring fragments skip the call, compute them before the branch or loop"
+                    .to_string()
+            }
+            Warning::SubgroupInNonUniformControlFlow { .. } => {
+                "only the invocations reaching the call take part in it, call it before the branch or loop to include the whole subgroup"
+                    .to_string()
+            }
+            Warning::UnknownAttribute { suggestions, .. } => match suggestions.as_slice() {
+                [] => "check the spelling of the attribute".to_string(),
+                [name] => format!("an attribute with a similar name exists: `{}`", name),
+                names => format!(
+                    "attributes with similar names exist: {}",
+                    names
+                        .iter()
+                        .map(|n| format!("`{}`", n))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Warning::Shadowing { name, .. } => {
+                format!("rename `{}` to refer to both declarations", name)
+            }
+            Warning::CaseCollision { .. } => {
+                "some targets don't tell names apart by their case, rename one of them".to_string()
+            }
+            Warning::ConfusableName { .. } => {
+                "the names are spelled with different characters that look alike, rename one of them"
+                    .to_string()
+            }
+            Warning::ImplicitOverflow { op, .. } => {
+                let name = match *op {
+                    "+" => "add",
+                    "-" => "sub",
+                    _ => "mul",
+                };
+                if *op == "*" {
+                    format!(
+                        "use `wrapping_{}` or choose a policy with `--integer-overflow`",
+                        name
+                    )
+                } else {
+                    format!(
+                        "use `wrapping_{0}` or `saturating_{0}`, or choose a policy with `--integer-overflow`",
+                        name
+                    )
+                }
+            }
+            Warning::FloatEquality { op, .. } => {
+                let call = match *op {
+                    "=" => "approx_eq(a, b, tolerance)",
+                    _ => "approx_eq(a, b, tolerance) = false",
+                };
+                format!("compare with a tolerance instead: `{}`", call)
+            }
+            Warning::LossyCast { .. } => {
+                "make sure the values fit in the type, or allow the lints with `--allow lossy-casts` if they always do"
+                    .to_string()
+            }
+            Warning::ReservedWord { word, .. } => format!(
+                "write it as `{}` to keep using it as a name",
+                keywords::source_spelling(word)
+            ),
+        }
+    }
+}
+
+impl From<Warning> for Diagnostic<FileId> {
+    fn from(warning: Warning) -> Self {
+        let message = warning.to_string();
+        let help = format!("help: {}", warning.help());
+
+        let mut notes = vec![];
+        let labels = warning_labels(warning, &mut notes);
+        notes.push(help);
+
+        Diagnostic::warning()
+            .with_message(message)
+            .with_labels(labels)
+            .with_notes(notes)
+    }
+}
+
+/// The labels of a warning, also used when its lint group is denied.
+fn warning_labels(warning: Warning, notes: &mut Vec<String>) -> Vec<Label<FileId>> {
+    match warning {
+        Warning::DerivativeInNonUniformControlFlow {
+            callee,
+            call,
+            intrinsic,
+            reason,
+        } => {
+            if !intrinsic {
+                notes.push(format!("`{}` uses derivatives", callee));
+            }
+            let loc = reason.location();
+            vec![
+                Label::primary(call.file, call.range())
+                    .with_message("neighbouring fragments may not reach this call"),
+                Label::secondary(loc.file, loc.range()).with_message(non_uniform_message(reason)),
+            ]
+        }
+        Warning::SubgroupInNonUniformControlFlow {
+            callee,
+            call,
+            intrinsic,
+            reason,
+        } => {
+            if !intrinsic {
+                notes.push(format!("`{}` uses subgroup operations", callee));
+            }
+            let loc = reason.location();
+            vec![
+                Label::primary(call.file, call.range())
+                    .with_message("other invocations of the subgroup may not reach this call"),
+                Label::secondary(loc.file, loc.range()).with_message(non_uniform_message(reason)),
+            ]
+        }
+        Warning::UnknownAttribute { attribute, .. } => {
+            vec![Label::primary(attribute.file, attribute.range())
+                .with_message("the compiler ignores this attribute")]
+        }
+        Warning::Shadowing {
+            declaration,
+            shadowed,
+            ..
+        } => {
+            let primary = Label::primary(declaration.file, declaration.range());
+            let (message, previous) = match shadowed {
+                Shadowed::Local(loc) => ("hides the variable declared around it", Some(loc)),
+                Shadowed::Constant(loc) => ("hides the constant of the module", Some(loc)),
+                Shadowed::Type(loc) => ("hides the type of the module", Some(loc)),
+                Shadowed::Intrinsic => ("hides the intrinsic with the same name", None),
+            };
+            let mut labels = vec![primary.with_message(message)];
+            labels.extend(
+                previous.map(|loc| {
+                    Label::secondary(loc.file, loc.range()).with_message("declared here")
+                }),
+            );
+            labels
+        }
+        Warning::CaseCollision {
+            collision,
+            previous_name,
+            previous,
+            ..
+        } => vec![
+            Label::primary(collision.file, collision.range()).with_message("differs only in case"),
+            Label::secondary(previous.file, previous.range())
+                .with_message(format!("`{}` declared here", previous_name)),
+        ],
+        Warning::ConfusableName {
+            confusable,
+            previous_name,
+            previous,
+            ..
+        } => vec![
+            Label::primary(confusable.file, confusable.range())
+                .with_message("looks like another name"),
+            Label::secondary(previous.file, previous.range())
+                .with_message(format!("`{}` declared here", previous_name)),
+        ],
+        Warning::ImplicitOverflow { operation, .. } => {
+            vec![Label::primary(operation.file, operation.range())
+                .with_message("signed overflow is undefined on some targets")]
+        }
+        Warning::FloatEquality { comparison, .. } => {
+            vec![Label::primary(comparison.file, comparison.range())
+                .with_message("rounding can make the values differ")]
+        }
+        Warning::LossyCast { cast, to, .. } => {
+            vec![Label::primary(cast.file, cast.range())
+                .with_message(format!("not every value fits in `{}`", to))]
+        }
+        Warning::ReservedWord { name, .. } => {
+            vec![Label::primary(name.file, name.range())
+                .with_message("later versions of the language may make this a keyword")]
+        }
+    }
+}
@@ -0,0 +1,229 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::fmt;
+
+use crate::{Context, Name, Type, TypeId, VecSize, VecType};
+
+/// Renders a type the way it would be written in a source file.
+///
+/// Distinct types and records are shown by the name of the type definition
+/// that introduced them, instances of generic types only by the name of the
+/// generic type.
+pub struct TypeDisplay<'a> {
+    ctx: &'a Context,
+    ty: TypeId,
+}
+
+impl Context {
+    pub fn display_type(&self, ty: TypeId) -> TypeDisplay<'_> {
+        TypeDisplay { ctx: self, ty }
+    }
+
+    /// The name of a type with the generic arguments of instances of generic
+    /// records, like `Box<float>`, which tells the instances apart.
+    pub fn instance_name(&self, ty: TypeId) -> String {
+        if let Some(Type::Optional { inner }) = self.types.get(ty) {
+            return format!("optional<{}>", self.instance_name(*inner));
+        }
+        let name = self.display_type(ty).to_string();
+        match self.generic_args.get(&ty) {
+            Some(args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.instance_name(*arg))
+                    .collect::<Vec<_>>();
+                format!("{}<{}>", name, args.join(", "))
+            }
+            None => name,
+        }
+    }
+
+    /// The name of the type definition a distinct type originates from.
+    fn distinct_name(&self, ty: TypeId, distinct_id: usize) -> Option<&str> {
+        if let Some(def) = self.distinct_defs.get(&distinct_id) {
+            if let Some((name, _)) = self.defs.iter().find(|(_, id)| *id == def) {
+                return Some(name);
+            }
+        }
+
+        // the definition may have been removed due to errors, fall back to a
+        // name the type is known under
+        let generic = self
+            .generic_distinct_ids
+            .iter()
+            .find(|(_, id)| **id == distinct_id)
+            .map(|(name, _)| name);
+        let complete = || {
+            self.complete_types
+                .iter()
+                .find(|(_, id)| **id == ty)
+                .map(|(name, _)| name)
+        };
+        generic.or_else(complete).map(|name| name.as_str())
+    }
+}
+
+impl fmt::Display for TypeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ty = match self.ctx.types.get(self.ty) {
+            Some(ty) => ty,
+            None => return write!(f, "{{unknown}}"),
+        };
+
+        let sub = |ty: TypeId| self.ctx.display_type(ty);
+        let text = |name: Name| self.ctx.types.name(name);
+
+        match ty {
+            Type::Bool => write!(f, "bool"),
+            Type::Int => write!(f, "int"),
+            Type::UInt => write!(f, "uint"),
+            Type::Long => write!(f, "long"),
+            Type::ULong => write!(f, "ulong"),
+            Type::Float => write!(f, "float"),
+            Type::Double => write!(f, "double"),
+            Type::Half => write!(f, "half"),
+            Type::Radians => write!(f, "radians"),
+            Type::Degrees => write!(f, "degrees"),
+            Type::AtomicInt => write!(f, "atomic<int>"),
+            Type::AtomicUInt => write!(f, "atomic<uint>"),
+            Type::Image {
+                dim,
+                format,
+                access,
+            } => write!(f, "{}<{}, {}>", dim.type_name(), format, access.name()),
+            Type::Texture { dim, sampled } => {
+                write!(f, "{}", crate::textures::texture_type_name(*dim, *sampled))
+            }
+            Type::Sampler { comparison: false } => write!(f, "sampler"),
+            Type::Sampler { comparison: true } => write!(f, "sampler_comparison"),
+            Type::AccelerationStructure => write!(f, "acceleration_structure"),
+            Type::BoolVec { components } => write!(f, "bool{}", size(*components)),
+            Type::IntVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "int", *components, *vtype, space.map(text)),
+            Type::UIntVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "uint", *components, *vtype, space.map(text)),
+            Type::LongVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "long", *components, *vtype, space.map(text)),
+            Type::ULongVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "ulong", *components, *vtype, space.map(text)),
+            Type::FloatVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "float", *components, *vtype, space.map(text)),
+            Type::DoubleVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "double", *components, *vtype, space.map(text)),
+            Type::HalfVec {
+                components,
+                vtype,
+                space,
+            } => write_vec(f, "half", *components, *vtype, space.map(text)),
+            Type::FloatMat {
+                cols,
+                rows,
+                transform,
+            } => write_mat(
+                f,
+                "float",
+                *cols,
+                *rows,
+                transform.map(|(from, to)| (text(from), text(to))),
+            ),
+            Type::DoubleMat {
+                cols,
+                rows,
+                transform,
+            } => write_mat(
+                f,
+                "double",
+                *cols,
+                *rows,
+                transform.map(|(from, to)| (text(from), text(to))),
+            ),
+            Type::Packed { format } => write!(f, "{}", format.name()),
+            Type::Array { base, size } => write!(f, "array[{}] of {}", size, sub(*base)),
+            Type::OpenArray { base } => write!(f, "array of {}", sub(*base)),
+            Type::Record { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, ty)| format!("{}: {}", text(*name), sub(*ty)))
+                    .collect::<Vec<_>>();
+                match fields.is_empty() {
+                    true => write!(f, "record {{}}"),
+                    false => write!(f, "record {{ {} }}", fields.join(", ")),
+                }
+            }
+            Type::Distinct { distinct_id, inner } => {
+                match self.ctx.distinct_name(self.ty, *distinct_id) {
+                    Some(name) => write!(f, "{}", name),
+                    None => write!(f, "distinct {}", sub(*inner)),
+                }
+            }
+            Type::Normalized { inner } => write!(f, "normalized<{}>", sub(*inner)),
+            Type::Optional { inner } => write!(f, "optional<{}>", sub(*inner)),
+            Type::GenericParam { index: _, name } => write!(f, "{}", name),
+            Type::Var(_) => write!(f, "_"),
+            Type::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+fn size(s: VecSize) -> u8 {
+    match s {
+        VecSize::VS2 => 2,
+        VecSize::VS3 => 3,
+        VecSize::VS4 => 4,
+    }
+}
+
+fn write_vec(
+    f: &mut fmt::Formatter<'_>,
+    base: &str,
+    components: VecSize,
+    vtype: VecType,
+    space: Option<&str>,
+) -> fmt::Result {
+    write!(f, "{}{}", base, size(components))?;
+    match vtype {
+        VecType::Unknown => {}
+        VecType::Point => write!(f, " is Point")?,
+        VecType::Vector => write!(f, " is Vector")?,
+        VecType::Colour => write!(f, " is Colour")?,
+        VecType::SrgbColour => write!(f, " is SrgbColour")?,
+    }
+    if let Some(space) = space {
+        write!(f, " in {}", space)?;
+    }
+    Ok(())
+}
+
+fn write_mat(
+    f: &mut fmt::Formatter<'_>,
+    base: &str,
+    cols: VecSize,
+    rows: VecSize,
+    transform: Option<(&str, &str)>,
+) -> fmt::Result {
+    write!(f, "{}{}x{}", base, size(cols), size(rows))?;
+    if let Some((from, to)) = transform {
+        write!(f, " from {} to {}", from, to)?;
+    }
+    Ok(())
+}
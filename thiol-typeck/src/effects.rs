@@ -0,0 +1,347 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Side effects of functions.
+//!
+//! A call of a function without effects can be moved, merged with an equal
+//! call or removed if its value is unused, and it may be evaluated when
+//! compiling constants.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, ParamMode, Statement, VariableDef};
+use id_arena::Id;
+
+use crate::layout::buffer_class;
+use crate::params::call_arguments;
+use crate::uniformity::{expression_calls, statement_calls};
+use crate::{Callable, Context, Error, Intrinsic, Symbol};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Effects {
+    /// writes to buffers, with assignments or atomics
+    pub writes_resources: bool,
+    /// writes to the arguments of `out` and `in out` parameters
+    pub writes_parameters: bool,
+    /// uses derivatives, which depend on neighbouring fragments
+    pub derivatives: bool,
+    /// waits for the other invocations of the workgroup
+    pub barriers: bool,
+    /// emits the vertices and primitives of a geometry program
+    pub emits_primitives: bool,
+    /// exchanges values with the other active invocations of the subgroup
+    pub subgroup: bool,
+    /// stops the invocation of a fragment with `discard`
+    pub discards: bool,
+    /// prints debug output or checks assertions, which are left out in
+    /// release builds
+    pub debug_output: bool,
+}
+
+impl Effects {
+    pub fn is_pure(self) -> bool {
+        self == Effects::default()
+    }
+
+    /// The effects of a function calling one with these effects, writes to
+    /// parameters only affect the arguments of the call.
+    fn for_caller(self) -> Self {
+        Effects {
+            writes_parameters: false,
+            ..self
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Effects {
+            writes_resources: self.writes_resources || other.writes_resources,
+            writes_parameters: self.writes_parameters || other.writes_parameters,
+            derivatives: self.derivatives || other.derivatives,
+            barriers: self.barriers || other.barriers,
+            emits_primitives: self.emits_primitives || other.emits_primitives,
+            subgroup: self.subgroup || other.subgroup,
+            discards: self.discards || other.discards,
+            debug_output: self.debug_output || other.debug_output,
+        }
+    }
+}
+
+impl fmt::Display for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.writes_resources, "writes resources"),
+            (self.writes_parameters, "writes parameters"),
+            (self.derivatives, "derivatives"),
+            (self.barriers, "barriers"),
+            (self.emits_primitives, "emits primitives"),
+            (self.subgroup, "subgroup operations"),
+            (self.discards, "discards"),
+            (self.debug_output, "debug output"),
+        ];
+        let names = names
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            write!(f, "pure")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
+
+impl Context {
+    /// The effects of a call expression, `None` if the callee is unknown.
+    ///
+    /// Optimizations like common subexpression elimination may only merge or
+    /// remove calls without effects.
+    pub fn call_effects(&self, hir_ctx: &hir::Context, call: Id<Expression>) -> Option<Effects> {
+        if let Some(intrinsic) = self.call_intrinsics.get(&call) {
+            return Some(intrinsic.effects());
+        }
+        let name = match &hir_ctx.expressions[call] {
+            Expression::Call { name, .. } => *name,
+            _ => return None,
+        };
+        let sig = self.function_sigs.get(&hir_ctx.identifiers[name])?;
+        Some(sig.effects)
+    }
+}
+
+/// Store the effects of every function of the module in its signature.
+pub(crate) fn infer_effects(module: &hir::Module, ty_ctx: &mut Context, hir_ctx: &hir::Context) {
+    let mut effects = module
+        .functions
+        .iter()
+        .map(|id| (*id, direct_effects(ty_ctx, hir_ctx, *id)))
+        .collect::<Vec<_>>();
+
+    // add the effects of callees until nothing changes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..effects.len() {
+            let (func, mut current) = effects[i];
+            for (callee, _) in ty_ctx.call_graph.dependencies(Callable::Function(func)) {
+                let callee = match callee {
+                    Callable::Function(callee) => callee,
+                    Callable::Program(_) => continue,
+                };
+                if let Some((_, callee)) = effects.iter().find(|(f, _)| *f == callee) {
+                    current = current.union(callee.for_caller());
+                }
+            }
+            if current != effects[i].1 {
+                effects[i].1 = current;
+                changed = true;
+            }
+        }
+    }
+
+    for (func, effects) in effects {
+        let name = &hir_ctx.identifiers[hir_ctx.functions[func].name];
+        if let Some(sig) = ty_ctx.function_sigs.get_mut(name) {
+            if sig.func_id == func {
+                sig.effects = effects;
+            }
+        }
+    }
+}
+
+/// The effects of a function without those of the functions it calls.
+fn direct_effects(ty_ctx: &Context, hir_ctx: &hir::Context, id: Id<hir::Function>) -> Effects {
+    let func = &hir_ctx.functions[id];
+    let mut effects = Effects {
+        writes_parameters: func.args.iter().any(|(_, _, mode)| *mode != ParamMode::In),
+        ..Effects::default()
+    };
+
+    let mut calls = vec![];
+    for stmt in &func.body {
+        statement_calls(hir_ctx, *stmt, &mut calls);
+    }
+    for call in calls {
+        if let Some(intrinsic) = ty_ctx.call_intrinsics.get(&call) {
+            effects = effects.union(intrinsic.effects());
+        }
+    }
+
+    if !buffer_writes(ty_ctx, hir_ctx, &func.body).is_empty() {
+        effects.writes_resources = true;
+    }
+    let mut found = vec![];
+    discards(hir_ctx, &func.body, &mut found);
+    effects.discards = !found.is_empty();
+    effects
+}
+
+/// The `discard` statements in a block and the blocks nested in it.
+pub(crate) fn discards(
+    ctx: &hir::Context,
+    block: &[Id<Statement>],
+    found: &mut Vec<Id<Statement>>,
+) {
+    for stmt in block {
+        match &ctx.statements[*stmt] {
+            Statement::Discard => found.push(*stmt),
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                discards(ctx, then_body, found);
+                discards(ctx, else_body, found);
+            }
+            Statement::For { body, .. } => discards(ctx, body, found),
+            Statement::Match { arms, .. } => {
+                for arm in arms {
+                    discards(ctx, &arm.body, found);
+                }
+            }
+            Statement::Var(_)
+            | Statement::Becomes { .. }
+            | Statement::Destructure { .. }
+            | Statement::Return(_)
+            | Statement::Expr(_)
+            | Statement::Break
+            | Statement::Continue => {}
+        }
+    }
+}
+
+/// The constants bound to buffers that a body writes to, with assignments,
+/// as arguments for `out` and `in out` parameters or with atomics. Writes
+/// in the functions it calls are not included.
+pub(crate) fn buffer_writes(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    body: &[Id<Statement>],
+) -> BTreeSet<Id<VariableDef>> {
+    let mut calls = vec![];
+    let mut targets = vec![];
+    for stmt in body {
+        statement_calls(hir_ctx, *stmt, &mut calls);
+        assignment_targets(hir_ctx, *stmt, &mut targets);
+    }
+    for call in calls {
+        match ty_ctx.call_intrinsics.get(&call) {
+            // atomics and image stores operate on their first argument,
+            // `trace_ray` only writes what the programs it runs write
+            Some(Intrinsic::TraceRay) => {}
+            Some(intrinsic) if intrinsic.effects().writes_resources => {
+                if let Expression::Call { pos_args, .. } = &hir_ctx.expressions[call] {
+                    targets.extend(pos_args.first());
+                }
+            }
+            Some(_) => {}
+            None => {
+                for (arg, func, index) in call_arguments(ty_ctx, hir_ctx, call) {
+                    if hir_ctx.functions[func].args[index].2 != ParamMode::In {
+                        targets.push(arg);
+                    }
+                }
+            }
+        }
+    }
+
+    targets
+        .into_iter()
+        .filter_map(|e| buffer_constant(ty_ctx, hir_ctx, e))
+        .collect()
+}
+
+/// The left hand sides of the assignments in a statement and the statements
+/// nested in it.
+fn assignment_targets(ctx: &hir::Context, id: Id<Statement>, targets: &mut Vec<Id<Expression>>) {
+    match &ctx.statements[id] {
+        Statement::Becomes { lhs, .. } => targets.push(*lhs),
+        Statement::Destructure { lhs, .. } => targets.extend(lhs),
+        Statement::If {
+            then_body,
+            else_body,
+            ..
+        } => {
+            for stmt in then_body.iter().chain(else_body) {
+                assignment_targets(ctx, *stmt, targets);
+            }
+        }
+        Statement::For { body, .. } => {
+            for stmt in body {
+                assignment_targets(ctx, *stmt, targets);
+            }
+        }
+        Statement::Match { arms, .. } => {
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                assignment_targets(ctx, *stmt, targets);
+            }
+        }
+        Statement::Var(_)
+        | Statement::Expr(_)
+        | Statement::Return(_)
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Discard => {}
+    }
+}
+
+/// The constant bound to a buffer that the expression is a part of.
+fn buffer_constant(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+) -> Option<Id<VariableDef>> {
+    match &hir_ctx.expressions[expr] {
+        Expression::Variable(name) => {
+            match ty_ctx.references.symbol(hir_ctx.identifier_fcs[name])? {
+                Symbol::Constant(def) if buffer_class(hir_ctx, def).is_some() => Some(def),
+                _ => None,
+            }
+        }
+        Expression::Field { base, .. }
+        | Expression::Index { base, .. }
+        | Expression::Slice { base, .. } => buffer_constant(ty_ctx, hir_ctx, *base),
+        _ => None,
+    }
+}
+
+/// Report calls with effects in the values of constants.
+pub(crate) fn check_constants(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.consts {
+        let def = &hir_ctx.variable_defs[*id];
+        let rhs = match def.rhs {
+            Some(rhs) => rhs,
+            None => continue,
+        };
+        let mut calls = vec![];
+        expression_calls(hir_ctx, rhs, &mut calls);
+        for call in calls {
+            let effects = match ty_ctx.call_effects(hir_ctx, call) {
+                Some(effects) if !effects.is_pure() => effects,
+                _ => continue,
+            };
+            let name = match &hir_ctx.expressions[call] {
+                Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            errs.push(Error::ImpureCallInConstant {
+                constant: hir_ctx.identifier_fcs[&def.name],
+                callee: hir_ctx.identifiers[name].clone(),
+                call: hir_ctx.identifier_fcs[&name],
+                effects,
+            });
+        }
+    }
+
+    errs
+}
@@ -0,0 +1,168 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! How strictly floating point arithmetic follows IEEE 754.
+//!
+//! Functions and programs follow IEEE 754 unless they have one of these
+//! attributes, which let the backends optimize their arithmetic:
+//!
+//! - `@no_nans`: no operand or result is a NaN
+//! - `@no_infs`: no operand or result is infinite
+//! - `@reassociate`: arithmetic can be reassociated and contracted, which
+//!   changes the rounding of the results
+//! - `@fast_math`: all of the above
+//!
+//! The attributes only apply to the arithmetic in the body of the function,
+//! not to the functions it calls. Comparisons of floats with `=` and `<>`
+//! are reported in the `float-equality` lint group, as rounding makes them
+//! depend on the order of the operations, `approx_eq` compares floats with
+//! a tolerance instead.
+
+use thiol_hir as hir;
+
+use hir::{Attribute, PrimitiveOp};
+use id_arena::Id;
+
+use crate::{lints, Callable, Context, Type, TypeId, Warning};
+
+/// The IEEE 754 guarantees a function or program gives up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FloatMode {
+    /// operands and results are never NaN
+    pub no_nans: bool,
+    /// operands and results are never infinite
+    pub no_infs: bool,
+    /// arithmetic can be reassociated and contracted
+    pub reassociate: bool,
+}
+
+impl FloatMode {
+    /// The mode given by the attributes of a function or program.
+    pub fn from_attributes(hir_ctx: &hir::Context, attrs: &[Id<Attribute>]) -> FloatMode {
+        let mut mode = FloatMode::default();
+        for attr in attrs {
+            match hir_ctx.identifiers[hir_ctx.attributes[*attr].name].as_str() {
+                "no_nans" => mode.no_nans = true,
+                "no_infs" => mode.no_infs = true,
+                "reassociate" => mode.reassociate = true,
+                "fast_math" => {
+                    mode = FloatMode {
+                        no_nans: true,
+                        no_infs: true,
+                        reassociate: true,
+                    }
+                }
+                _ => {}
+            }
+        }
+        mode
+    }
+
+    /// Whether the mode keeps all guarantees of IEEE 754.
+    pub fn is_strict(self) -> bool {
+        self == FloatMode::default()
+    }
+}
+
+impl Context {
+    /// The float mode of a function or program.
+    pub fn float_mode(&self, callable: Callable) -> FloatMode {
+        self.float_modes.get(&callable).copied().unwrap_or_default()
+    }
+
+    /// Whether the type is a floating point scalar or vector, or an angle.
+    pub fn is_float(&self, ty: TypeId) -> bool {
+        let scalar = self
+            .types
+            .get(self.strip_distinct(ty))
+            .and_then(Type::scalar);
+        matches!(
+            scalar,
+            Some(Type::Float | Type::Half | Type::Double | Type::Radians | Type::Degrees)
+        )
+    }
+
+    /// Whether the type is a vector of floating point numbers.
+    pub fn is_float_vector(&self, ty: TypeId) -> bool {
+        matches!(
+            self.types.get(self.strip_distinct(ty)),
+            Some(Type::FloatVec { .. } | Type::HalfVec { .. } | Type::DoubleVec { .. })
+        )
+    }
+
+    /// The scalar type of floating point scalars and vectors and of angles,
+    /// the type of the tolerance of `approx_eq`.
+    pub(crate) fn float_scalar(&mut self, ty: TypeId) -> Option<TypeId> {
+        if !self.is_float(ty) {
+            return None;
+        }
+        let scalar = self.types.get(self.strip_distinct(ty))?.scalar()?;
+        Some(self.add_or_get_type(scalar))
+    }
+}
+
+/// Record the float modes of the functions and programs of a module that
+/// don't follow IEEE 754 strictly.
+pub(crate) fn collect_float_modes(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) {
+    let functions = module
+        .functions
+        .iter()
+        .map(|id| (Callable::Function(*id), &hir_ctx.functions[*id].attrs));
+    let programs = module
+        .programs
+        .iter()
+        .map(|id| (Callable::Program(*id), &hir_ctx.programs[*id].attrs));
+    for (callable, attrs) in functions.chain(programs) {
+        let mode = FloatMode::from_attributes(hir_ctx, attrs);
+        if !mode.is_strict() {
+            ty_ctx.float_modes.insert(callable, mode);
+        }
+    }
+}
+
+/// Report the comparisons of floats with `=` and `<>`.
+pub(crate) fn check_float_equality(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Warning> {
+    lints::primitive_ops(module, hir_ctx)
+        .into_iter()
+        .filter_map(|(id, op)| {
+            let (op, a, b) = match hir_ctx.prim_ops[op] {
+                PrimitiveOp::Eq(a, b) => ("=", a, b),
+                PrimitiveOp::Neq(a, b) => ("<>", a, b),
+                _ => return None,
+            };
+            let ty = [a, b]
+                .iter()
+                .filter_map(|e| ty_ctx.expr_types.get(e).copied())
+                .find(|ty| ty_ctx.is_float(*ty))?;
+            Some(Warning::FloatEquality {
+                op,
+                comparison: *hir_ctx.expression_fcs.get(&id)?,
+                ty: ty_ctx.display_type(ty).to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        assert!(FloatMode::default().is_strict());
+        let mode = FloatMode {
+            no_nans: true,
+            ..FloatMode::default()
+        };
+        assert!(!mode.is_strict());
+    }
+}
@@ -0,0 +1,368 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Geometry programs.
+//!
+//! A `@geometry` program runs once per primitive after the vertex stage, or
+//! the tessellation evaluation stage of a tessellation pipeline, and outputs
+//! primitives of its own. `@input_primitive` gives the primitive it reads,
+//! `points`, `lines`, `lines_adjacency`, `triangles` or
+//! `triangles_adjacency`, and its inputs other than builtins are arrays with
+//! an element per vertex of the primitive, which have the type of the
+//! outputs of the same name of the stage before them as their elements.
+//!
+//! The outputs are written once per vertex: `emit_vertex()` emits a vertex
+//! with the values the outputs have when it is called, and `end_primitive()`
+//! ends the strip of the `@output_primitive`, `points`, `line_strip` or
+//! `triangle_strip`, the vertices after it start a new one. A program emits
+//! at most `@max_vertices(n)` vertices, emitting more is undefined, so the
+//! vertices every run of the program emits are counted, and it needs a
+//! `Position` output for the vertices it emits.
+//!
+//! The stage is that of SPIR-V, GLSL ES 3.0 and Metal have no geometry
+//! programs.
+
+use std::collections::BTreeMap;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, Identifier, Program, Statement};
+use id_arena::Id;
+
+use crate::resources::reachable;
+use crate::stages::{count_arg, name_arg, program_attribute, program_stage, Stage};
+use crate::uniformity::statement_calls;
+use crate::varyings::{element_type, has_attribute, interface_mismatch, is_builtin, Varying};
+use crate::{Callable, Context, Error, Intrinsic, Symbol, Type};
+
+/// The most vertices a geometry program emits, the minimum every Vulkan
+/// implementation with geometry programs supports
+pub const MAX_GEOMETRY_VERTICES: u32 = 256;
+
+/// The primitive a geometry program reads the vertices of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InputPrimitive {
+    Points,
+    Lines,
+    /// lines with the vertices before and after them
+    LinesAdjacency,
+    Triangles,
+    /// triangles with the vertices of the neighbouring triangles
+    TrianglesAdjacency,
+}
+
+impl InputPrimitive {
+    pub const NAMES: &'static [&'static str] = &[
+        "points",
+        "lines",
+        "lines_adjacency",
+        "triangles",
+        "triangles_adjacency",
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "points" => Some(InputPrimitive::Points),
+            "lines" => Some(InputPrimitive::Lines),
+            "lines_adjacency" => Some(InputPrimitive::LinesAdjacency),
+            "triangles" => Some(InputPrimitive::Triangles),
+            "triangles_adjacency" => Some(InputPrimitive::TrianglesAdjacency),
+            _ => None,
+        }
+    }
+
+    /// The number of vertices of the primitive, the length of the inputs.
+    pub fn vertices(self) -> u32 {
+        match self {
+            InputPrimitive::Points => 1,
+            InputPrimitive::Lines => 2,
+            InputPrimitive::LinesAdjacency => 4,
+            InputPrimitive::Triangles => 3,
+            InputPrimitive::TrianglesAdjacency => 6,
+        }
+    }
+}
+
+/// The strips a geometry program emits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OutputPrimitive {
+    Points,
+    LineStrip,
+    TriangleStrip,
+}
+
+impl OutputPrimitive {
+    pub const NAMES: &'static [&'static str] = &["points", "line_strip", "triangle_strip"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "points" => Some(OutputPrimitive::Points),
+            "line_strip" => Some(OutputPrimitive::LineStrip),
+            "triangle_strip" => Some(OutputPrimitive::TriangleStrip),
+            _ => None,
+        }
+    }
+}
+
+/// The input primitive of a geometry program, `None` without a valid
+/// `input_primitive` attribute.
+pub fn input_primitive(hir_ctx: &hir::Context, program: Id<Program>) -> Option<InputPrimitive> {
+    let attr = program_attribute(hir_ctx, program, "input_primitive")?;
+    InputPrimitive::from_name(name_arg(hir_ctx, attr)?)
+}
+
+/// The output primitive of a geometry program, `None` without a valid
+/// `output_primitive` attribute.
+pub fn output_primitive(hir_ctx: &hir::Context, program: Id<Program>) -> Option<OutputPrimitive> {
+    let attr = program_attribute(hir_ctx, program, "output_primitive")?;
+    OutputPrimitive::from_name(name_arg(hir_ctx, attr)?)
+}
+
+/// The most vertices a geometry program emits, `None` without a valid
+/// `max_vertices` attribute.
+pub fn max_vertices(hir_ctx: &hir::Context, program: Id<Program>) -> Option<u32> {
+    let attr = program_attribute(hir_ctx, program, "max_vertices")?;
+    count_arg(hir_ctx, attr, MAX_GEOMETRY_VERTICES)
+}
+
+/// Check that the inputs of geometry programs are arrays per vertex of their
+/// input primitive matching the outputs of the stage before them, and the
+/// vertices the programs emit against their outputs and `max_vertices`.
+pub(crate) fn validate_geometry(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    // the stage before the geometry stage is the tessellation evaluation
+    // stage if the module has one
+    let tessellation = module
+        .programs
+        .iter()
+        .any(|id| program_stage(hir_ctx, *id) == Some(Stage::TessellationEvaluation));
+    let previous = if tessellation {
+        Stage::TessellationEvaluation
+    } else {
+        Stage::Vertex
+    };
+    // the first varying with each name on both sides of the interface
+    let mut outputs = BTreeMap::<&Identifier, Varying>::new();
+    let mut inputs = BTreeMap::<&Identifier, Varying>::new();
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        let (side, vars) = if stage == Some(previous) {
+            (&mut outputs, &prog.outputs)
+        } else if stage == Some(Stage::Geometry) {
+            (&mut inputs, &prog.inputs)
+        } else {
+            continue;
+        };
+        let length = input_primitive(hir_ctx, *id).map(InputPrimitive::vertices);
+
+        for var in vars {
+            if is_builtin(hir_ctx, *var) {
+                continue;
+            }
+            let def = &hir_ctx.variable_defs[*var];
+            let loc = hir_ctx.identifier_fcs[&def.name];
+            let ty = match ty_ctx.references.symbol_type(Symbol::Local(*var)) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            if stage == Some(Stage::Geometry) {
+                let valid = match ty_ctx.types.get(ty_ctx.strip_distinct(ty)) {
+                    Some(Type::Array { size, .. }) => {
+                        length.is_none_or(|length| *size == length as usize)
+                    }
+                    Some(Type::Error) => continue,
+                    _ => false,
+                };
+                if !valid {
+                    errs.push(Error::ExpectedPrimitiveArray {
+                        var: loc,
+                        found: ty_ctx.display_type(ty).to_string(),
+                        length,
+                    });
+                    continue;
+                }
+            }
+            let varying = Varying {
+                ty,
+                patch: false,
+                loc,
+            };
+            side.entry(&hir_ctx.identifiers[def.name])
+                .or_insert(varying);
+        }
+
+        if stage == Some(Stage::Geometry) {
+            errs.extend(check_emission(ty_ctx, hir_ctx, *id));
+        }
+    }
+
+    for (name, input) in &inputs {
+        let output = match outputs.get(name) {
+            Some(output) => output,
+            None => continue,
+        };
+        if element_type(ty_ctx, input).is_some_and(|element| element != output.ty) {
+            errs.push(interface_mismatch(ty_ctx, name, output, input));
+        }
+    }
+
+    errs
+}
+
+/// Check the vertices a geometry program emits, that it has a `Position`
+/// output for them and that every run emits at most `max_vertices`.
+fn check_emission(ty_ctx: &Context, hir_ctx: &hir::Context, program: Id<Program>) -> Vec<Error> {
+    let prog = &hir_ctx.programs[program];
+    let mut errs = vec![];
+
+    let positioned = prog
+        .outputs
+        .iter()
+        .any(|output| has_attribute(hir_ctx, *output, &["Position"]));
+    if !positioned {
+        let mut calls = vec![];
+        for callable in reachable(ty_ctx, Callable::Program(program)) {
+            let body = match callable {
+                Callable::Function(func) => &hir_ctx.functions[func].body,
+                Callable::Program(prog) => &hir_ctx.programs[prog].body,
+            };
+            for stmt in body {
+                statement_calls(hir_ctx, *stmt, &mut calls);
+            }
+        }
+        let emit = calls
+            .into_iter()
+            .find(|call| ty_ctx.call_intrinsics.get(call) == Some(&Intrinsic::EmitVertex));
+        if let Some(call) = emit {
+            errs.push(Error::EmitWithoutPosition {
+                call: hir_ctx.expression_fcs[&call],
+                program_name: hir_ctx.identifiers[prog.name].clone(),
+                program: hir_ctx.identifier_fcs[&prog.name],
+            });
+        }
+    }
+
+    if let Some(max) = max_vertices(hir_ctx, program) {
+        let mut counter = EmissionCounter {
+            ty_ctx,
+            hir_ctx,
+            max,
+            exceeding: None,
+        };
+        counter.block(&prog.body, 0);
+        if let Some((call, emitted)) = counter.exceeding {
+            let attribute = prog
+                .attrs
+                .iter()
+                .find(|id| hir_ctx.identifiers[hir_ctx.attributes[**id].name] == "max_vertices")
+                .map(|id| hir_ctx.attribute_fcs[id]);
+            errs.extend(attribute.map(|attribute| Error::TooManyEmittedVertices {
+                call,
+                emitted,
+                max,
+                attribute,
+            }));
+        }
+    }
+
+    errs
+}
+
+/// Counts the vertices a geometry program emits at least, from the calls of
+/// `emit_vertex` in its body outside of loops, and finds the first call
+/// emitting more than `max`.
+struct EmissionCounter<'a> {
+    ty_ctx: &'a Context,
+    hir_ctx: &'a hir::Context,
+    max: u32,
+    /// the call emitting more vertices than `max` with the number of vertices
+    exceeding: Option<(FileLocation, u32)>,
+}
+
+impl EmissionCounter<'_> {
+    /// The vertices emitted after the statements, `None` if they may leave
+    /// the block.
+    fn block(&mut self, body: &[Id<Statement>], mut emitted: u32) -> Option<u32> {
+        for stmt in body {
+            emitted = self.statement(*stmt, emitted)?;
+        }
+        Some(emitted)
+    }
+
+    fn statement(&mut self, stmt: Id<Statement>, emitted: u32) -> Option<u32> {
+        match &self.hir_ctx.statements[stmt] {
+            Statement::Expr(expr) => {
+                let emits = matches!(self.hir_ctx.expressions[*expr], Expression::Call { .. })
+                    && self.ty_ctx.call_intrinsics.get(expr) == Some(&Intrinsic::EmitVertex);
+                if !emits {
+                    return Some(emitted);
+                }
+                let emitted = emitted + 1;
+                if emitted > self.max && self.exceeding.is_none() {
+                    self.exceeding = Some((self.hir_ctx.expression_fcs[expr], emitted));
+                }
+                Some(emitted)
+            }
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                let then_emitted = self.block(then_body, emitted);
+                let else_emitted = self.block(else_body, emitted);
+                match (then_emitted, else_emitted) {
+                    (Some(then_emitted), Some(else_emitted)) => {
+                        Some(then_emitted.min(else_emitted))
+                    }
+                    // the statements after the `if` only run after the
+                    // branch that doesn't leave
+                    (Some(emitted), None) | (None, Some(emitted)) => Some(emitted),
+                    (None, None) => None,
+                }
+            }
+            // only one arm runs, like a branch of an `if`
+            Statement::Match { arms, .. } => arms
+                .iter()
+                .filter_map(|arm| self.block(&arm.body, emitted))
+                .min(),
+            // the body may run any number of times, a `break` or `continue`
+            // only leaves the loop
+            Statement::For { body, .. } => {
+                self.block(body, emitted);
+                Some(emitted)
+            }
+            Statement::Return(_) | Statement::Break | Statement::Continue | Statement::Discard => {
+                None
+            }
+            Statement::Var(_) | Statement::Becomes { .. } | Statement::Destructure { .. } => {
+                Some(emitted)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitive_names() {
+        for name in InputPrimitive::NAMES {
+            let primitive = InputPrimitive::from_name(name).unwrap();
+            assert!((1..=6).contains(&primitive.vertices()));
+        }
+        for name in OutputPrimitive::NAMES {
+            assert!(OutputPrimitive::from_name(name).is_some());
+        }
+        assert_eq!(OutputPrimitive::from_name("triangles"), None);
+        assert_eq!(InputPrimitive::TrianglesAdjacency.vertices(), 6);
+    }
+}
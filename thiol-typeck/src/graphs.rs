@@ -0,0 +1,516 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::hash::Hash;
+
+use hir::{Expression, FileLocation, Function, Program, Statement, TypeDefinition};
+use id_arena::Id;
+use thiol_hir as hir;
+
+/// A directed graph of items where an edge `a -> b` means that `a` uses `b`.
+///
+/// Every edge is annotated with the locations at which `b` is used by `a`.
+/// Nodes and edges are numbered in the order they were added, and the
+/// edges of a node are kept in that order.
+#[derive(Debug, Clone)]
+pub struct DependencyGraph<N> {
+    nodes: Vec<N>,
+    indices: HashMap<N, usize>,
+    edges: Vec<Edge>,
+    /// the edges from every node, by index of the node
+    outgoing: Vec<Vec<usize>>,
+    /// the edges to every node, by index of the node
+    incoming: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone)]
+struct Edge {
+    from: usize,
+    to: usize,
+    uses: Vec<FileLocation>,
+}
+
+/// Dependencies between type definitions
+pub type TypeGraph = DependencyGraph<Id<TypeDefinition>>;
+
+/// Calls between functions and programs
+pub type CallGraph = DependencyGraph<Callable>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Callable {
+    Function(Id<Function>),
+    Program(Id<Program>),
+}
+
+/// Items that can be displayed in an exported graph.
+pub trait GraphNode {
+    fn name<'a>(&self, ctx: &'a hir::Context) -> &'a str;
+
+    /// Graphviz node attributes other than the label
+    fn dot_attributes(&self) -> &'static str {
+        ""
+    }
+}
+
+impl GraphNode for Id<TypeDefinition> {
+    fn name<'a>(&self, ctx: &'a hir::Context) -> &'a str {
+        &ctx.identifiers[ctx.type_defs[*self].name]
+    }
+}
+
+impl GraphNode for Callable {
+    fn name<'a>(&self, ctx: &'a hir::Context) -> &'a str {
+        match self {
+            Callable::Function(id) => &ctx.identifiers[ctx.functions[*id].name],
+            Callable::Program(id) => &ctx.identifiers[ctx.programs[*id].name],
+        }
+    }
+
+    fn dot_attributes(&self) -> &'static str {
+        match self {
+            Callable::Function(_) => "",
+            Callable::Program(_) => ", shape=box",
+        }
+    }
+}
+
+impl<N> Default for DependencyGraph<N> {
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            indices: Default::default(),
+            edges: vec![],
+            outgoing: vec![],
+            incoming: vec![],
+        }
+    }
+}
+
+impl<N: Copy + Eq + Hash> DependencyGraph<N> {
+    pub fn add_node(&mut self, node: N) -> usize {
+        if let Some(idx) = self.indices.get(&node) {
+            return *idx;
+        }
+        let idx = self.nodes.len();
+        self.nodes.push(node);
+        self.outgoing.push(vec![]);
+        self.incoming.push(vec![]);
+        self.indices.insert(node, idx);
+        idx
+    }
+
+    /// Record that `from` uses `to` at the given locations.
+    pub fn add_uses(&mut self, from: N, to: N, uses: impl IntoIterator<Item = FileLocation>) {
+        let from = self.add_node(from);
+        let to = self.add_node(to);
+        let edges = &mut self.edges;
+        match self.outgoing[from]
+            .iter()
+            .find(|edge| edges[**edge].to == to)
+        {
+            Some(edge) => edges[*edge].uses.extend(uses),
+            None => {
+                let edge = edges.len();
+                edges.push(Edge {
+                    from,
+                    to,
+                    uses: uses.into_iter().collect(),
+                });
+                self.outgoing[from].push(edge);
+                self.incoming[to].push(edge);
+            }
+        }
+    }
+
+    pub fn index(&self, node: N) -> Option<usize> {
+        self.indices.get(&node).copied()
+    }
+
+    /// All nodes in the order they were added
+    pub fn nodes(&self) -> impl Iterator<Item = N> + '_ {
+        self.nodes.iter().copied()
+    }
+
+    /// The items used by `node`, together with the locations of the uses.
+    pub fn dependencies(&self, node: N) -> Vec<(N, &[FileLocation])> {
+        let edges = self.index(node).map_or(&[][..], |idx| &self.outgoing[idx]);
+        edges
+            .iter()
+            .map(|edge| {
+                let edge = &self.edges[*edge];
+                (self.nodes[edge.to], edge.uses.as_slice())
+            })
+            .collect()
+    }
+
+    /// The items using `node`, together with the locations of the uses.
+    pub fn dependents(&self, node: N) -> Vec<(N, &[FileLocation])> {
+        let edges = self.index(node).map_or(&[][..], |idx| &self.incoming[idx]);
+        edges
+            .iter()
+            .map(|edge| {
+                let edge = &self.edges[*edge];
+                (self.nodes[edge.from], edge.uses.as_slice())
+            })
+            .collect()
+    }
+
+    /// The shortest cycle that starts and ends in `start`, as a list of edges
+    /// with the locations of the uses.
+    pub fn find_cycle(&self, start: N) -> Option<Vec<(N, N, &[FileLocation])>> {
+        // breadth first search, remembering the edge each node was reached by
+        let mut reached_by: HashMap<N, (N, &[FileLocation])> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+
+        while let Some(node) = queue.pop_front() {
+            for (dep, uses) in self.dependencies(node) {
+                if dep == start {
+                    let mut cycle = vec![(node, dep, uses)];
+                    let mut cur = node;
+                    while cur != start {
+                        let (prev, uses) = reached_by[&cur];
+                        cycle.push((prev, cur, uses));
+                        cur = prev;
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                }
+                if let std::collections::hash_map::Entry::Vacant(entry) = reached_by.entry(dep) {
+                    entry.insert((node, uses));
+                    queue.push_back(dep);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Strongly connected components, dependencies before their users.
+    ///
+    /// Tarjan's algorithm, with an explicit stack instead of recursion so
+    /// that long chains of dependencies don't overflow the stack.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<N>> {
+        let mut scc = Tarjan::new(self.nodes.len());
+        for root in 0..self.nodes.len() {
+            if scc.index[root].is_some() {
+                continue;
+            }
+            scc.enter(root);
+            while let Some(&(node, next_edge)) = scc.visiting.last() {
+                if let Some(edge) = self.outgoing[node].get(next_edge) {
+                    scc.visiting.last_mut().unwrap().1 += 1;
+                    let dep = self.edges[*edge].to;
+                    match scc.index[dep] {
+                        None => scc.enter(dep),
+                        Some(index) if scc.on_stack[dep] => {
+                            scc.low[node] = scc.low[node].min(index);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                scc.visiting.pop();
+                if let Some(&(parent, _)) = scc.visiting.last() {
+                    scc.low[parent] = scc.low[parent].min(scc.low[node]);
+                }
+                if Some(scc.low[node]) == scc.index[node] {
+                    let mut group = vec![];
+                    loop {
+                        let member = scc.stack.pop().unwrap();
+                        scc.on_stack[member] = false;
+                        group.push(self.nodes[member]);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    scc.groups.push(group);
+                }
+            }
+        }
+        scc.groups
+    }
+
+    /// All nodes, dependencies before their users. The nodes of a cycle are
+    /// next to each other, in no particular order.
+    pub fn topological_order(&self) -> Vec<N> {
+        self.strongly_connected_components()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// The state of [`DependencyGraph::strongly_connected_components`], by
+/// index of the nodes
+struct Tarjan<N> {
+    /// the order in which the nodes were reached
+    index: Vec<Option<usize>>,
+    reached: usize,
+    /// the smallest index reachable from the node through the nodes on the
+    /// stack
+    low: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    /// the path to the node being visited, with the next edge to follow
+    /// from every node
+    visiting: Vec<(usize, usize)>,
+    groups: Vec<Vec<N>>,
+}
+
+impl<N> Tarjan<N> {
+    fn new(nodes: usize) -> Self {
+        Self {
+            index: vec![None; nodes],
+            reached: 0,
+            low: vec![0; nodes],
+            on_stack: vec![false; nodes],
+            stack: vec![],
+            visiting: vec![],
+            groups: vec![],
+        }
+    }
+
+    fn enter(&mut self, node: usize) {
+        self.index[node] = Some(self.reached);
+        self.low[node] = self.reached;
+        self.reached += 1;
+        self.on_stack[node] = true;
+        self.stack.push(node);
+        self.visiting.push((node, 0));
+    }
+}
+
+impl<N: Copy + Eq + Hash + GraphNode> DependencyGraph<N> {
+    /// Export the graph in the Graphviz DOT format.
+    pub fn to_dot(&self, ctx: &hir::Context, graph_name: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph {} {{", graph_name).unwrap();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            writeln!(
+                out,
+                "    n{} [label=\"{}\"{}];",
+                idx,
+                node.name(ctx).escape_default(),
+                node.dot_attributes()
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(out, "    n{} -> n{};", edge.from, edge.to).unwrap();
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// Build the call graph of all functions and programs in a module.
+///
+/// Calls are resolved by name, calls to unknown functions are not part of the
+/// graph.
+pub fn call_graph(ctx: &hir::Context, module: &hir::Module) -> CallGraph {
+    let mut graph = CallGraph::default();
+
+    let mut functions = HashMap::new();
+    for id in &module.functions {
+        graph.add_node(Callable::Function(*id));
+        functions
+            .entry(ctx.identifiers[ctx.functions[*id].name].as_str())
+            .or_insert(*id);
+    }
+    for id in &module.programs {
+        graph.add_node(Callable::Program(*id));
+    }
+
+    let bodies = module
+        .functions
+        .iter()
+        .map(|id| (Callable::Function(*id), &ctx.functions[*id].body))
+        .chain(
+            module
+                .programs
+                .iter()
+                .map(|id| (Callable::Program(*id), &ctx.programs[*id].body)),
+        );
+
+    for (caller, body) in bodies {
+        let mut calls = vec![];
+        for stmt in body {
+            statement_calls(ctx, *stmt, &mut calls);
+        }
+
+        for name in calls {
+            if let Some(callee) = functions.get(ctx.identifiers[name].as_str()) {
+                let loc = ctx.identifier_fcs[&name];
+                graph.add_uses(caller, Callable::Function(*callee), Some(loc));
+            }
+        }
+    }
+
+    graph
+}
+
+fn statement_calls(ctx: &hir::Context, id: Id<Statement>, calls: &mut Vec<Id<hir::Identifier>>) {
+    match &ctx.statements[id] {
+        Statement::Var(def) => {
+            if let Some(rhs) = ctx.variable_defs[*def].rhs {
+                expression_calls(ctx, rhs, calls);
+            }
+        }
+        Statement::Becomes { lhs, rhs } => {
+            expression_calls(ctx, *lhs, calls);
+            expression_calls(ctx, *rhs, calls);
+        }
+        Statement::Destructure { lhs, rhs } => {
+            for e in lhs {
+                expression_calls(ctx, *e, calls);
+            }
+            expression_calls(ctx, *rhs, calls);
+        }
+        Statement::Return(e) => {
+            if let Some(e) = e {
+                expression_calls(ctx, *e, calls);
+            }
+        }
+        Statement::Expr(e) => expression_calls(ctx, *e, calls),
+        Statement::Break | Statement::Continue | Statement::Discard => {}
+        Statement::If {
+            cond,
+            then_body,
+            else_body,
+        } => {
+            expression_calls(ctx, *cond, calls);
+            for stmt in then_body.iter().chain(else_body) {
+                statement_calls(ctx, *stmt, calls);
+            }
+        }
+        Statement::For { from, to, body, .. } => {
+            expression_calls(ctx, *from, calls);
+            expression_calls(ctx, *to, calls);
+            for stmt in body {
+                statement_calls(ctx, *stmt, calls);
+            }
+        }
+        Statement::Match { value, arms } => {
+            expression_calls(ctx, *value, calls);
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                statement_calls(ctx, *stmt, calls);
+            }
+        }
+    }
+}
+
+fn expression_calls(ctx: &hir::Context, id: Id<Expression>, calls: &mut Vec<Id<hir::Identifier>>) {
+    use hir::PrimitiveOp as PO;
+
+    match &ctx.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::LayoutQuery { .. } => {}
+        Expression::PrimitiveOp(op) => match &ctx.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => expression_calls(ctx, *e, calls),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                expression_calls(ctx, *a, calls);
+                expression_calls(ctx, *b, calls);
+            }
+            PO::Constructor {
+                ty: _,
+                pos_args,
+                nam_args,
+            } => {
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    expression_calls(ctx, *e, calls);
+                }
+            }
+        },
+        Expression::Call {
+            name,
+            pos_args,
+            nam_args,
+        } => {
+            calls.push(*name);
+            for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                expression_calls(ctx, *e, calls);
+            }
+        }
+        Expression::Field { base, name: _ } => expression_calls(ctx, *base, calls),
+        Expression::Index { base, index } => {
+            expression_calls(ctx, *base, calls);
+            expression_calls(ctx, *index, calls);
+        }
+        Expression::Slice { base, lo, hi } => {
+            expression_calls(ctx, *base, calls);
+            expression_calls(ctx, *lo, calls);
+            expression_calls(ctx, *hi, calls);
+        }
+        Expression::As { base, ty: _ } => expression_calls(ctx, *base, calls),
+        Expression::Array(elements) => {
+            for e in elements {
+                expression_calls(ctx, *e, calls);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(start: usize) -> FileLocation {
+        FileLocation {
+            file: 0,
+            start,
+            end: start + 1,
+        }
+    }
+
+    #[test]
+    fn components_before_their_users() {
+        // 0 -> 1 -> 2 -> 1, 0 -> 3
+        let mut g = DependencyGraph::default();
+        g.add_uses(0, 1, Some(at(0)));
+        g.add_uses(1, 2, Some(at(1)));
+        g.add_uses(2, 1, Some(at(2)));
+        g.add_uses(0, 3, Some(at(3)));
+        g.add_uses(0, 1, Some(at(4)));
+
+        let mut groups = g.strongly_connected_components();
+        groups.iter_mut().for_each(|group| group.sort_unstable());
+        assert_eq!(groups, vec![vec![1, 2], vec![3], vec![0]]);
+        assert_eq!(g.topological_order().last(), Some(&0));
+
+        assert_eq!(
+            g.dependencies(0),
+            vec![(1, &[at(0), at(4)][..]), (3, &[at(3)][..])]
+        );
+        assert_eq!(
+            g.dependents(1),
+            vec![(0, &[at(0), at(4)][..]), (2, &[at(2)][..])]
+        );
+        assert_eq!(g.find_cycle(1).map(|cycle| cycle.len()), Some(2));
+        assert_eq!(g.find_cycle(0), None);
+    }
+
+    #[test]
+    fn long_chains() {
+        let mut g = DependencyGraph::default();
+        for i in 0..100_000 {
+            g.add_uses(i, i + 1, None);
+        }
+        let order = g.topological_order();
+        assert_eq!(order.len(), 100_001);
+        assert_eq!(order[0], 100_000);
+    }
+}
@@ -0,0 +1,452 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Storage images, which programs read and write texel by texel.
+//!
+//! An image type like `image2d<rgba8unorm, write>` names the dimensions, the
+//! format the texels are stored in and whether programs read the image,
+//! write it or both. Images are bound like storage buffers, as constants
+//! with a `Storage` attribute, and are accessed with `imageLoad(image, c)`
+//! and `imageStore(image, c, texel)` at integer coordinates `c` with one
+//! component per dimension, and one more for the layer of an array.
+//!
+//! Texels are read and written as four component vectors of the type the
+//! format converts to: `float4` for normalized and floating point formats,
+//! `uint4` and `int4` for integer formats. Only images with one of the `r32`
+//! formats can be both read and written, as targets don't support the
+//! others. Vertex programs can't write images.
+
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation};
+use id_arena::Id;
+
+use crate::{BufferClass, Context, Error, Intrinsic, Symbol, Type, TypeId, VecSize, VecType};
+
+/// The dimensions of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImageDim {
+    D1,
+    D2,
+    D3,
+    /// an array of two dimensional layers
+    D2Array,
+}
+
+impl ImageDim {
+    /// The name of the image type with these dimensions.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            ImageDim::D1 => "image1d",
+            ImageDim::D2 => "image2d",
+            ImageDim::D3 => "image3d",
+            ImageDim::D2Array => "image2d_array",
+        }
+    }
+
+    /// The number of components of coordinates into the image, including
+    /// the layer of arrays.
+    pub fn coordinates(self) -> usize {
+        match self {
+            ImageDim::D1 => 1,
+            ImageDim::D2 => 2,
+            ImageDim::D3 | ImageDim::D2Array => 3,
+        }
+    }
+}
+
+impl From<hir::ImageDim> for ImageDim {
+    fn from(dim: hir::ImageDim) -> Self {
+        match dim {
+            hir::ImageDim::D1 => Self::D1,
+            hir::ImageDim::D2 => Self::D2,
+            hir::ImageDim::D3 => Self::D3,
+            hir::ImageDim::D2Array => Self::D2Array,
+        }
+    }
+}
+
+/// The format the texels of a storage image are stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImageFormat {
+    Rgba8Unorm,
+    Rgba8Snorm,
+    Rgba8Uint,
+    Rgba8Sint,
+    Rgba16Float,
+    Rgba16Uint,
+    Rgba16Sint,
+    Rgba32Float,
+    Rgba32Uint,
+    Rgba32Sint,
+    R32Float,
+    R32Uint,
+    R32Sint,
+}
+
+/// The scalar type texels of a format are read and written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexelScalar {
+    Float,
+    UInt,
+    Int,
+}
+
+impl ImageFormat {
+    pub const ALL: &'static [ImageFormat] = &[
+        ImageFormat::Rgba8Unorm,
+        ImageFormat::Rgba8Snorm,
+        ImageFormat::Rgba8Uint,
+        ImageFormat::Rgba8Sint,
+        ImageFormat::Rgba16Float,
+        ImageFormat::Rgba16Uint,
+        ImageFormat::Rgba16Sint,
+        ImageFormat::Rgba32Float,
+        ImageFormat::Rgba32Uint,
+        ImageFormat::Rgba32Sint,
+        ImageFormat::R32Float,
+        ImageFormat::R32Uint,
+        ImageFormat::R32Sint,
+    ];
+
+    /// The name of the format as it is written in source files.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Rgba8Unorm => "rgba8unorm",
+            ImageFormat::Rgba8Snorm => "rgba8snorm",
+            ImageFormat::Rgba8Uint => "rgba8uint",
+            ImageFormat::Rgba8Sint => "rgba8sint",
+            ImageFormat::Rgba16Float => "rgba16float",
+            ImageFormat::Rgba16Uint => "rgba16uint",
+            ImageFormat::Rgba16Sint => "rgba16sint",
+            ImageFormat::Rgba32Float => "rgba32float",
+            ImageFormat::Rgba32Uint => "rgba32uint",
+            ImageFormat::Rgba32Sint => "rgba32sint",
+            ImageFormat::R32Float => "r32float",
+            ImageFormat::R32Uint => "r32uint",
+            ImageFormat::R32Sint => "r32sint",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn texel_scalar(self) -> TexelScalar {
+        match self {
+            ImageFormat::Rgba8Unorm
+            | ImageFormat::Rgba8Snorm
+            | ImageFormat::Rgba16Float
+            | ImageFormat::Rgba32Float
+            | ImageFormat::R32Float => TexelScalar::Float,
+            ImageFormat::Rgba8Uint
+            | ImageFormat::Rgba16Uint
+            | ImageFormat::Rgba32Uint
+            | ImageFormat::R32Uint => TexelScalar::UInt,
+            ImageFormat::Rgba8Sint
+            | ImageFormat::Rgba16Sint
+            | ImageFormat::Rgba32Sint
+            | ImageFormat::R32Sint => TexelScalar::Int,
+        }
+    }
+
+    /// Whether images in the format can be read and written by the same
+    /// program.
+    pub fn allows_read_write(self) -> bool {
+        matches!(
+            self,
+            ImageFormat::R32Float | ImageFormat::R32Uint | ImageFormat::R32Sint
+        )
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Whether programs read a storage image, write it or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImageAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl ImageAccess {
+    pub const ALL: &'static [ImageAccess] = &[
+        ImageAccess::Read,
+        ImageAccess::Write,
+        ImageAccess::ReadWrite,
+    ];
+
+    /// The name of the access as it is written in source files.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageAccess::Read => "read",
+            ImageAccess::Write => "write",
+            ImageAccess::ReadWrite => "read_write",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    /// Whether the access allows the intrinsic, which is `imageLoad` or
+    /// `imageStore`.
+    fn allows(self, intrinsic: Intrinsic) -> bool {
+        match intrinsic {
+            Intrinsic::ImageLoad => self != ImageAccess::Write,
+            Intrinsic::ImageStore => self != ImageAccess::Read,
+            _ => true,
+        }
+    }
+}
+
+/// Why an image type is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageTypeProblem {
+    UnknownFormat(String),
+    UnknownAccess(String),
+    /// `read_write` access with a format other than the `r32` ones
+    ReadWriteFormat(ImageFormat),
+}
+
+impl Context {
+    /// The type of an image, or the problem with its format or access.
+    pub(crate) fn image_type(
+        &mut self,
+        dim: ImageDim,
+        format: &str,
+        access: &str,
+    ) -> Result<TypeId, ImageTypeProblem> {
+        let format = ImageFormat::from_name(format)
+            .ok_or_else(|| ImageTypeProblem::UnknownFormat(format.to_string()))?;
+        let access = ImageAccess::from_name(access)
+            .ok_or_else(|| ImageTypeProblem::UnknownAccess(access.to_string()))?;
+        if access == ImageAccess::ReadWrite && !format.allows_read_write() {
+            return Err(ImageTypeProblem::ReadWriteFormat(format));
+        }
+        Ok(self.add_or_get_type(Type::Image {
+            dim,
+            format,
+            access,
+        }))
+    }
+
+    /// The dimensions, format and access of an image type, `None` for all
+    /// other types.
+    pub fn image(&self, ty: TypeId) -> Option<(ImageDim, ImageFormat, ImageAccess)> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Image {
+                dim,
+                format,
+                access,
+            } => Some((*dim, *format, *access)),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of the type contains an image.
+    pub fn contains_image(&self, ty: TypeId) -> bool {
+        self.types
+            .contains(ty, |ty| matches!(ty, Type::Image { .. }))
+    }
+
+    /// The type texels of an image are read and written as.
+    pub(crate) fn texel_type(&mut self, format: ImageFormat) -> TypeId {
+        let (components, vtype, space) = (VecSize::VS4, VecType::Unknown, None);
+        self.add_or_get_type(match format.texel_scalar() {
+            TexelScalar::Float => Type::FloatVec {
+                components,
+                vtype,
+                space,
+            },
+            TexelScalar::UInt => Type::UIntVec {
+                components,
+                vtype,
+                space,
+            },
+            TexelScalar::Int => Type::IntVec {
+                components,
+                vtype,
+                space,
+            },
+        })
+    }
+
+    /// Whether values of the type are coordinates into an image with the
+    /// dimensions, which are signed or unsigned integers.
+    fn is_image_coordinate(&self, ty: TypeId, dim: ImageDim) -> bool {
+        let components = match self.types.get(self.strip_distinct(ty)) {
+            Some(Type::Int) | Some(Type::UInt) => 1,
+            Some(Type::IntVec { components, .. }) | Some(Type::UIntVec { components, .. }) => {
+                match components {
+                    VecSize::VS2 => 2,
+                    VecSize::VS3 => 3,
+                    VecSize::VS4 => 4,
+                }
+            }
+            Some(Type::Error) => return true,
+            _ => 0,
+        };
+        components == dim.coordinates()
+    }
+
+    /// The type of `imageLoad` and `imageStore` with positional arguments
+    /// of the given types.
+    pub(crate) fn image_intrinsic_type(
+        &mut self,
+        intrinsic: Intrinsic,
+        args: &[TypeId],
+    ) -> Option<TypeId> {
+        let (dim, format, _) = self.image(*args.first()?)?;
+        match (intrinsic, args) {
+            (Intrinsic::ImageLoad, [_, coords]) if self.is_image_coordinate(*coords, dim) => {
+                Some(self.texel_type(format))
+            }
+            // stores have no value
+            _ => None,
+        }
+    }
+}
+
+/// The name of the coordinate type of images with the dimensions.
+fn coordinate_type_name(dim: ImageDim) -> &'static str {
+    match dim.coordinates() {
+        1 => "int",
+        2 => "int2",
+        _ => "int3",
+    }
+}
+
+/// The arguments of a call of `imageLoad` or `imageStore`, the image, the
+/// coordinates and the texel for stores.
+pub(crate) fn check_call(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+    intrinsic: Intrinsic,
+    args: &[(Id<Expression>, Option<TypeId>)],
+) -> Vec<Error> {
+    let mut errs = vec![];
+    let (image, image_ty) = match args.first() {
+        Some((image, Some(ty))) => (*image, *ty),
+        _ => return errs,
+    };
+    let (dim, format, access) = match ty_ctx.image(image_ty) {
+        Some(image) => image,
+        None => {
+            if ty_ctx.types.get(image_ty) != Some(&Type::Error) {
+                errs.push(Error::NotAnImage {
+                    expr: hir_ctx.expression_fcs[&image],
+                    intrinsic,
+                    type_name: ty_ctx.display_type(image_ty).to_string(),
+                });
+            }
+            return errs;
+        }
+    };
+    if !access.allows(intrinsic) {
+        errs.push(Error::ImageAccessViolation {
+            image: hir_ctx.expression_fcs[&image],
+            intrinsic,
+            access,
+        });
+    }
+    if let Some((coords, Some(ty))) = args.get(1) {
+        if !ty_ctx.is_image_coordinate(*ty, dim) {
+            errs.push(Error::InvalidImageCoordinates {
+                coords: hir_ctx.expression_fcs[coords],
+                found: ty_ctx.display_type(*ty).to_string(),
+                expected: coordinate_type_name(dim),
+            });
+        }
+    }
+    if let (Intrinsic::ImageStore, Some((texel, Some(ty)))) = (intrinsic, args.get(2)) {
+        let expected = ty_ctx.texel_type(format);
+        if ty_ctx.strip_distinct(*ty) != expected && ty_ctx.types.get(*ty) != Some(&Type::Error) {
+            errs.push(Error::TexelTypeMismatch {
+                texel: hir_ctx.expression_fcs[texel],
+                found: ty_ctx.display_type(*ty).to_string(),
+                expected: ty_ctx.display_type(expected).to_string(),
+                format,
+            });
+        }
+    }
+    errs
+}
+
+/// Report images in parameters, return types, variables and constants that
+/// are not bound as storage images.
+///
+/// Images in the types of buffers are reported when validating the buffers.
+pub(crate) fn validate_image_placement(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    validate_opaque_placement(module, ty_ctx, hir_ctx, Context::contains_image, |type_| {
+        Error::MisplacedImage { type_ }
+    })
+}
+
+/// Report the types of parameters, return types, variables and constants
+/// that contain a type only bound constants can have, with `error`.
+pub(crate) fn validate_opaque_placement(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    contains: impl Fn(&Context, TypeId) -> bool,
+    error: impl Fn(FileLocation) -> Error,
+) -> Vec<Error> {
+    let mut errs = vec![];
+    let mut check = |type_ref, ty| {
+        if contains(ty_ctx, ty) {
+            errs.push(error(hir_ctx.type_ref_fcs[&type_ref]));
+        }
+    };
+
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        let sig = match ty_ctx.function_sigs.get(&hir_ctx.identifiers[func.name]) {
+            Some(sig) if sig.func_id == *id => sig,
+            _ => continue,
+        };
+        for ((_, type_ref, _), (_, ty)) in func.args.iter().zip(&sig.args) {
+            check(*type_ref, *ty);
+        }
+        check(func.ret_type, sig.ret);
+    }
+
+    for id in &module.consts {
+        let def = &hir_ctx.variable_defs[*id];
+        let sig = match ty_ctx.consts.get(&hir_ctx.identifiers[def.name]) {
+            Some(sig) if sig.const_id == *id => sig,
+            _ => continue,
+        };
+        let bound = def.attrs.iter().any(|attr| {
+            BufferClass::from_attribute(&hir_ctx.identifiers[hir_ctx.attributes[*attr].name])
+                .is_some()
+        });
+        if !bound {
+            check(def.type_, sig.type_);
+        }
+    }
+
+    for (sym, _) in ty_ctx.references.symbols() {
+        let id = match sym {
+            Symbol::Local(id) => id,
+            _ => continue,
+        };
+        if let Some(ty) = ty_ctx.references.symbol_type(sym) {
+            check(hir_ctx.variable_defs[id].type_, ty);
+        }
+    }
+
+    errs
+}
@@ -0,0 +1,179 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Storage for the types and names the type checker creates.
+//!
+//! Types refer to their components and to the names of fields and spaces by
+//! id, so a type is a few words and comparing two types never looks at more
+//! than one level. Every type and every name is stored exactly once.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::types::{Type, TypeId};
+
+/// An interned name of a field, a space or a transform, see
+/// [`TypeTable::name`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name(u32);
+
+/// All types of a type check, indexed by [`TypeId`]
+#[derive(Debug, Clone, Default)]
+pub struct TypeTable {
+    types: Vec<Type>,
+    /// ids of the types by the hash of the type, so that the types themselves
+    /// aren't stored a second time as keys
+    type_ids: HashMap<u64, Vec<TypeId>>,
+    names: Vec<Box<str>>,
+    name_ids: HashMap<u64, Vec<Name>>,
+}
+
+fn hash_of(value: &(impl Hash + ?Sized)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl TypeTable {
+    /// The number of distinct types.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    pub fn get(&self, id: TypeId) -> Option<&Type> {
+        self.types.get(id.0)
+    }
+
+    /// The id of a type, if it was interned before.
+    pub fn id_of(&self, ty: &Type) -> Option<TypeId> {
+        self.type_ids
+            .get(&hash_of(ty))?
+            .iter()
+            .copied()
+            .find(|id| self.types[id.0] == *ty)
+    }
+
+    /// The id of a type, adding the type if it is new.
+    pub fn intern(&mut self, ty: Type) -> TypeId {
+        let hash = hash_of(&ty);
+        let types = &self.types;
+        let ids = self.type_ids.entry(hash).or_default();
+        if let Some(id) = ids.iter().copied().find(|id| types[id.0] == ty) {
+            return id;
+        }
+        let id = TypeId(self.types.len());
+        self.types.push(ty);
+        ids.push(id);
+        id
+    }
+
+    /// Whether `ty` or a type it is built from through arrays, fields and
+    /// distinct types matches `pred`. Types shared by several fields are
+    /// looked at once, so records nesting each other stay linear.
+    pub fn contains(&self, ty: TypeId, pred: impl Fn(&Type) -> bool) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![ty];
+        while let Some(ty) = stack.pop() {
+            if !seen.insert(ty) {
+                continue;
+            }
+            let ty = match self.get(ty) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            if pred(ty) {
+                return true;
+            }
+            match ty {
+                Type::Array { base, .. } | Type::OpenArray { base } => stack.push(*base),
+                Type::Record { fields } => stack.extend(fields.iter().map(|(_, ty)| *ty)),
+                Type::Distinct { inner, .. } => stack.push(*inner),
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// All types with their ids, in the order they were interned.
+    pub fn iter(&self) -> impl Iterator<Item = (TypeId, &Type)> {
+        self.types.iter().enumerate().map(|(i, ty)| (TypeId(i), ty))
+    }
+
+    pub fn name(&self, name: Name) -> &str {
+        &self.names[name.0 as usize]
+    }
+
+    /// The interned name for a string, if it was interned before.
+    pub fn find_name(&self, name: &str) -> Option<Name> {
+        self.name_ids
+            .get(&hash_of(name))?
+            .iter()
+            .copied()
+            .find(|id| &*self.names[id.0 as usize] == name)
+    }
+
+    pub fn intern_name(&mut self, name: &str) -> Name {
+        let names = &self.names;
+        let ids = self.name_ids.entry(hash_of(name)).or_default();
+        if let Some(id) = ids
+            .iter()
+            .copied()
+            .find(|id| &*names[id.0 as usize] == name)
+        {
+            return id;
+        }
+        let id = Name(self.names.len() as u32);
+        self.names.push(name.into());
+        ids.push(id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn types_and_names_are_stored_once() {
+        let mut table = TypeTable::default();
+        let float = table.intern(Type::Float);
+        let x = table.intern_name("x");
+        let record = table.intern(Type::Record {
+            fields: vec![(x, float)],
+        });
+
+        assert_eq!(table.intern(Type::Float), float);
+        assert_eq!(table.intern_name("x"), x);
+        assert_eq!(table.find_name("x"), Some(x));
+        assert_eq!(table.find_name("y"), None);
+        assert_eq!(
+            table.id_of(&Type::Record {
+                fields: vec![(x, float)]
+            }),
+            Some(record)
+        );
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.name(x), "x");
+    }
+
+    #[test]
+    fn shared_components_are_visited_once() {
+        let mut table = TypeTable::default();
+        let (a, b) = (table.intern_name("a"), table.intern_name("b"));
+        let mut ty = table.intern(Type::Float);
+        for _ in 0..64 {
+            ty = table.intern(Type::Record {
+                fields: vec![(a, ty), (b, ty)],
+            });
+        }
+
+        assert!(table.contains(ty, |ty| *ty == Type::Float));
+        assert!(!table.contains(ty, |ty| *ty == Type::Int));
+    }
+}
@@ -0,0 +1,199 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! How the outputs of vertex, tessellation evaluation and geometry programs
+//! are interpolated across a primitive before fragment programs read them.
+//!
+//! Varyings are interpolated with perspective correction at the center of
+//! the fragment, integers aren't interpolated at all. The `flat`, `linear`
+//! and `perspective` attributes choose another interpolation, `centroid`
+//! samples inside the primitive instead of at the center. Varyings are
+//! matched by name between the stages, so an output and an input with the
+//! same name have to agree on their interpolation.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{FileLocation, Identifier};
+
+use crate::stages::program_stage;
+use crate::varyings::is_builtin;
+use crate::{Context, Error, Stage, Symbol};
+
+/// How a varying is interpolated between the vertices of a primitive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InterpolationMode {
+    /// linear in screen space after perspective correction
+    Perspective,
+    /// linear in screen space
+    Linear,
+    /// the value of the provoking vertex
+    Flat,
+}
+
+impl InterpolationMode {
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "perspective" => Some(InterpolationMode::Perspective),
+            "linear" => Some(InterpolationMode::Linear),
+            "flat" => Some(InterpolationMode::Flat),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interpolation {
+    pub mode: InterpolationMode,
+    /// sampled inside the primitive instead of at the center of the fragment
+    pub centroid: bool,
+}
+
+/// Why the interpolation attributes of a variable are not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpolationProblem {
+    /// the variable isn't passed to a fragment program by the stage before
+    /// it
+    NotAVarying { stage: Option<Stage>, output: bool },
+    /// another attribute already chose the interpolation
+    Conflicting { previous: FileLocation },
+    /// integers can only be passed `flat`
+    IntegerNotFlat { type_name: String },
+}
+
+/// The attribute sampling a varying inside the primitive
+const CENTROID: &str = "centroid";
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            InterpolationMode::Perspective => "perspective",
+            InterpolationMode::Linear => "linear",
+            InterpolationMode::Flat => "flat",
+        };
+        if self.centroid {
+            write!(f, "{} centroid", mode)
+        } else {
+            write!(f, "{}", mode)
+        }
+    }
+}
+
+/// Check the interpolation attributes of the inputs and outputs of programs
+/// and record the interpolation of every varying.
+pub(crate) fn check_interpolation(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+    // the first vertex or evaluation output and fragment input with each name
+    let mut outputs = BTreeMap::<&Identifier, (Interpolation, FileLocation)>::new();
+    let mut inputs = BTreeMap::<&Identifier, (Interpolation, FileLocation)>::new();
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        let vars = prog
+            .inputs
+            .iter()
+            .map(|var| (*var, false))
+            .chain(prog.outputs.iter().map(|var| (*var, true)));
+        for (var, output) in vars {
+            let def = &hir_ctx.variable_defs[var];
+            let name = &hir_ctx.identifiers[def.name];
+            let varying = match (stage, output) {
+                (Some(Stage::Vertex), true)
+                | (Some(Stage::TessellationEvaluation), true)
+                | (Some(Stage::Geometry), true)
+                | (Some(Stage::Fragment), false) => !is_builtin(hir_ctx, var),
+                _ => false,
+            };
+            let ty = ty_ctx.references.symbol_type(Symbol::Local(var));
+            let integer = ty.is_some_and(|ty| ty_ctx.is_integer(ty));
+
+            let mut mode = None;
+            let mut centroid = false;
+            for attr in &def.attrs {
+                let attr_name = &hir_ctx.identifiers[hir_ctx.attributes[*attr].name];
+                let attribute = hir_ctx.attribute_fcs[attr];
+                let new_mode = InterpolationMode::from_attribute(attr_name);
+                if new_mode.is_none() && attr_name != CENTROID {
+                    continue;
+                }
+
+                let mut error = |problem| {
+                    errs.push(Error::InvalidInterpolation {
+                        name: name.clone(),
+                        attribute,
+                        problem,
+                    })
+                };
+                if !varying {
+                    error(InterpolationProblem::NotAVarying { stage, output });
+                    continue;
+                }
+                let new_mode = match new_mode {
+                    Some(new_mode) => new_mode,
+                    None => {
+                        centroid = true;
+                        continue;
+                    }
+                };
+                if let Some((_, previous)) = mode {
+                    error(InterpolationProblem::Conflicting { previous });
+                } else if integer && new_mode != InterpolationMode::Flat {
+                    let type_name = ty
+                        .map(|ty| ty_ctx.display_type(ty).to_string())
+                        .unwrap_or_default();
+                    error(InterpolationProblem::IntegerNotFlat { type_name });
+                } else {
+                    mode = Some((new_mode, attribute));
+                }
+            }
+            if !varying {
+                continue;
+            }
+
+            let default = if integer {
+                InterpolationMode::Flat
+            } else {
+                InterpolationMode::Perspective
+            };
+            let interpolation = Interpolation {
+                mode: mode.map_or(default, |(mode, _)| mode),
+                centroid,
+            };
+            ty_ctx.interpolation.insert(var, interpolation);
+
+            let loc = hir_ctx.identifier_fcs[&def.name];
+            let (same_side, other_side) = if output {
+                (&mut outputs, &inputs)
+            } else {
+                (&mut inputs, &outputs)
+            };
+            if let Some((other, other_loc)) = other_side.get(name) {
+                if *other != interpolation {
+                    let ((output, output_loc), (input, input_loc)) = if output {
+                        ((interpolation, loc), (*other, *other_loc))
+                    } else {
+                        ((*other, *other_loc), (interpolation, loc))
+                    };
+                    errs.push(Error::InterpolationMismatch {
+                        name: name.clone(),
+                        output,
+                        output_loc,
+                        input,
+                        input_loc,
+                    });
+                }
+            }
+            same_side.entry(name).or_insert((interpolation, loc));
+        }
+    }
+
+    errs
+}
@@ -0,0 +1,1218 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Functions built into the language.
+//!
+//! Intrinsics are called like functions but have no definition in a module, a
+//! function of the module with the same name takes precedence over them.
+
+use crate::angles::AngleUnit;
+use crate::{Context, Effects, Type, TypeId, VecSize, VecType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Intrinsic {
+    /// `unpack(x)` converts a value of a packed type to its full precision
+    /// vector type
+    Unpack,
+    /// `atomic_add(a, v)` adds `v` to the atomic `a`, like all atomic
+    /// operations it gives the value `a` had before
+    AtomicAdd,
+    /// `atomic_min(a, v)` stores the minimum of `a` and `v` in `a`
+    AtomicMin,
+    /// `atomic_max(a, v)` stores the maximum of `a` and `v` in `a`
+    AtomicMax,
+    /// `atomic_exchange(a, v)` stores `v` in `a`
+    AtomicExchange,
+    /// `atomic_compare_exchange(a, cmp, v)` stores `v` in `a` if `a` equals
+    /// `cmp`
+    AtomicCompareExchange,
+    /// `workgroupBarrier()` waits for all invocations of the workgroup and
+    /// makes their writes to workgroup memory visible
+    WorkgroupBarrier,
+    /// `storageBarrier()` waits for all invocations of the workgroup and
+    /// makes their writes to storage buffers visible
+    StorageBarrier,
+    /// `dpdx(v)` is the partial derivative of `v` along the x axis of the
+    /// framebuffer
+    Dpdx,
+    /// `dpdy(v)` is the partial derivative of `v` along the y axis of the
+    /// framebuffer
+    Dpdy,
+    /// `fwidth(v)` is the sum of the absolute derivatives of `v` along both
+    /// axes
+    Fwidth,
+    /// `transpose(m)` swaps the rows and columns of a matrix
+    Transpose,
+    /// `inverse(m)` is the inverse of a square matrix
+    Inverse,
+    /// `determinant(m)` is the determinant of a square matrix
+    Determinant,
+    /// `identity()` is the identity of the square matrix type it is used as
+    Identity,
+    /// `srgb_to_linear(c)` decodes an sRGB encoded colour, the alpha of an
+    /// RGBA colour is kept as it is
+    SrgbToLinear,
+    /// `linear_to_srgb(c)` encodes a linear colour as sRGB
+    LinearToSrgb,
+    /// `normalize(v)` is the vector in the direction of `v` with a length of
+    /// one
+    Normalize,
+    /// `reflect(i, n)` reflects the incident vector `i` at the plane with the
+    /// normal `n`
+    Reflect,
+    /// `refract(i, n, eta)` is the direction of the incident vector `i` after
+    /// passing the surface with the normal `n` and the ratio of indices of
+    /// refraction `eta`, or zero for total internal reflection
+    Refract,
+    /// `sin(a)` is the sine of an angle in radians
+    Sin,
+    /// `cos(a)` is the cosine of an angle in radians
+    Cos,
+    /// `tan(a)` is the tangent of an angle in radians
+    Tan,
+    /// `asin(x)` is the angle in radians whose sine is `x`
+    Asin,
+    /// `acos(x)` is the angle in radians whose cosine is `x`
+    Acos,
+    /// `atan(x)` is the angle in radians whose tangent is `x`
+    Atan,
+    /// `atan2(y, x)` is the angle in radians between the x axis and the point
+    /// `(x, y)`
+    Atan2,
+    /// `to_radians(d)` converts an angle in degrees to radians
+    ToRadians,
+    /// `to_degrees(r)` converts an angle in radians to degrees
+    ToDegrees,
+    /// `imageLoad(image, c)` reads the texel of a storage image at the
+    /// integer coordinates `c`
+    ImageLoad,
+    /// `imageStore(image, c, texel)` writes the texel of a storage image at
+    /// the integer coordinates `c`
+    ImageStore,
+    /// `sample(t, s, c)` filters the texels of the texture `t` around the
+    /// coordinates `c` with the sampler `s`, at the level of detail given by
+    /// the derivatives of `c`
+    Sample,
+    /// `sample_lod(t, s, c, level)` samples the texture at an explicit
+    /// level of detail
+    SampleLod,
+    /// `sample_grad(t, s, c, ddx, ddy)` samples the texture at the level of
+    /// detail given by explicit gradients of the coordinates
+    SampleGrad,
+    /// `sample_compare(t, s, c, reference)` compares the depths of a depth
+    /// texture around `c` with `reference` and filters the results
+    SampleCompare,
+    /// `gather(t, s, c)` reads the four texels that `sample` would filter
+    Gather,
+    /// `fetch(t, c, level)` reads a single texel of the texture at the
+    /// integer coordinates `c` without a sampler
+    Fetch,
+    /// `trace_ray(scene, origin, direction, t_min, t_max, payload)` traces a
+    /// ray through the acceleration structure `scene` with the payload at
+    /// the location `payload`, see [`crate::ray_tracing`]
+    TraceRay,
+    /// `emit_vertex()` emits a vertex of a geometry program with the values
+    /// its outputs have, see [`crate::geometry`]
+    EmitVertex,
+    /// `end_primitive()` ends the strip of the vertices a geometry program
+    /// emitted before
+    EndPrimitive,
+    /// `subgroup_ballot(b)` has a bit set for every active invocation of the
+    /// subgroup where `b` is true, in the components of a `uint4`
+    SubgroupBallot,
+    /// `subgroup_shuffle(v, id)` is the `v` of the invocation of the
+    /// subgroup with the index `id`
+    SubgroupShuffle,
+    /// `subgroup_broadcast(v, id)` is the `v` of the invocation `id`, which
+    /// is the same for every invocation of the subgroup
+    SubgroupBroadcast,
+    /// `subgroup_add(v)` is the sum of the `v` of the active invocations of
+    /// the subgroup
+    SubgroupAdd,
+    /// `subgroup_min(v)` is the minimum of the `v` of the active invocations
+    SubgroupMin,
+    /// `subgroup_max(v)` is the maximum of the `v` of the active invocations
+    SubgroupMax,
+    /// `wrapping_add(a, b)` is `a + b` wrapped around on overflow, see
+    /// [`crate::overflow`]
+    WrappingAdd,
+    /// `wrapping_sub(a, b)` is `a - b` wrapped around on overflow
+    WrappingSub,
+    /// `wrapping_mul(a, b)` is the low bits of `a * b`
+    WrappingMul,
+    /// `saturating_add(a, b)` is `a + b` clamped to the range of the type
+    SaturatingAdd,
+    /// `saturating_sub(a, b)` is `a - b` clamped to the range of the type
+    SaturatingSub,
+    /// `approx_eq(a, b, tolerance)` is whether no component of `a` differs
+    /// from the one of `b` by more than `tolerance`, see [`crate::floats`]
+    ApproxEq,
+    /// `bitcast(v)` reinterprets the bits of `v` as the type it is used as,
+    /// see [`crate::casts`]
+    Bitcast,
+    /// `select(mask, a, b)` is `a` where `mask` is true and `b` elsewhere, a
+    /// `boolN` mask selects the components of vectors with `N` components
+    Select,
+    /// `any(v)` is whether a component of a boolean vector is true
+    Any,
+    /// `all(v)` is whether every component of a boolean vector is true
+    All,
+    /// `some(v)` is an optional with the value `v`, see
+    /// [`crate::optionals`]
+    Some,
+    /// `none()` is the optional without a value of the type it is used as
+    None,
+    /// `unwrap_or(o, default)` is the value of the optional `o`, or
+    /// `default` if it has none
+    UnwrapOr,
+    /// `assert(cond)` reports a failed assertion when `cond` is false, see
+    /// [`crate::debug_output`]
+    Assert,
+    /// `debug_print(format, values...)` prints the values with the format
+    DebugPrint,
+}
+
+impl Intrinsic {
+    pub const ALL: &'static [Intrinsic] = &[
+        Intrinsic::Unpack,
+        Intrinsic::AtomicAdd,
+        Intrinsic::AtomicMin,
+        Intrinsic::AtomicMax,
+        Intrinsic::AtomicExchange,
+        Intrinsic::AtomicCompareExchange,
+        Intrinsic::WorkgroupBarrier,
+        Intrinsic::StorageBarrier,
+        Intrinsic::Dpdx,
+        Intrinsic::Dpdy,
+        Intrinsic::Fwidth,
+        Intrinsic::Transpose,
+        Intrinsic::Inverse,
+        Intrinsic::Determinant,
+        Intrinsic::Identity,
+        Intrinsic::SrgbToLinear,
+        Intrinsic::LinearToSrgb,
+        Intrinsic::Normalize,
+        Intrinsic::Reflect,
+        Intrinsic::Refract,
+        Intrinsic::Sin,
+        Intrinsic::Cos,
+        Intrinsic::Tan,
+        Intrinsic::Asin,
+        Intrinsic::Acos,
+        Intrinsic::Atan,
+        Intrinsic::Atan2,
+        Intrinsic::ToRadians,
+        Intrinsic::ToDegrees,
+        Intrinsic::ImageLoad,
+        Intrinsic::ImageStore,
+        Intrinsic::Sample,
+        Intrinsic::SampleLod,
+        Intrinsic::SampleGrad,
+        Intrinsic::SampleCompare,
+        Intrinsic::Gather,
+        Intrinsic::Fetch,
+        Intrinsic::TraceRay,
+        Intrinsic::EmitVertex,
+        Intrinsic::EndPrimitive,
+        Intrinsic::SubgroupBallot,
+        Intrinsic::SubgroupShuffle,
+        Intrinsic::SubgroupBroadcast,
+        Intrinsic::SubgroupAdd,
+        Intrinsic::SubgroupMin,
+        Intrinsic::SubgroupMax,
+        Intrinsic::WrappingAdd,
+        Intrinsic::WrappingSub,
+        Intrinsic::WrappingMul,
+        Intrinsic::SaturatingAdd,
+        Intrinsic::SaturatingSub,
+        Intrinsic::ApproxEq,
+        Intrinsic::Bitcast,
+        Intrinsic::Select,
+        Intrinsic::Any,
+        Intrinsic::All,
+        Intrinsic::Some,
+        Intrinsic::None,
+        Intrinsic::UnwrapOr,
+        Intrinsic::Assert,
+        Intrinsic::DebugPrint,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|i| i.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Intrinsic::Unpack => "unpack",
+            Intrinsic::AtomicAdd => "atomic_add",
+            Intrinsic::AtomicMin => "atomic_min",
+            Intrinsic::AtomicMax => "atomic_max",
+            Intrinsic::AtomicExchange => "atomic_exchange",
+            Intrinsic::AtomicCompareExchange => "atomic_compare_exchange",
+            Intrinsic::WorkgroupBarrier => "workgroupBarrier",
+            Intrinsic::StorageBarrier => "storageBarrier",
+            Intrinsic::Dpdx => "dpdx",
+            Intrinsic::Dpdy => "dpdy",
+            Intrinsic::Fwidth => "fwidth",
+            Intrinsic::Transpose => "transpose",
+            Intrinsic::Inverse => "inverse",
+            Intrinsic::Determinant => "determinant",
+            Intrinsic::Identity => "identity",
+            Intrinsic::SrgbToLinear => "srgb_to_linear",
+            Intrinsic::LinearToSrgb => "linear_to_srgb",
+            Intrinsic::Normalize => "normalize",
+            Intrinsic::Reflect => "reflect",
+            Intrinsic::Refract => "refract",
+            Intrinsic::Sin => "sin",
+            Intrinsic::Cos => "cos",
+            Intrinsic::Tan => "tan",
+            Intrinsic::Asin => "asin",
+            Intrinsic::Acos => "acos",
+            Intrinsic::Atan => "atan",
+            Intrinsic::Atan2 => "atan2",
+            Intrinsic::ToRadians => "to_radians",
+            Intrinsic::ToDegrees => "to_degrees",
+            Intrinsic::ImageLoad => "imageLoad",
+            Intrinsic::ImageStore => "imageStore",
+            Intrinsic::Sample => "sample",
+            Intrinsic::SampleLod => "sample_lod",
+            Intrinsic::SampleGrad => "sample_grad",
+            Intrinsic::SampleCompare => "sample_compare",
+            Intrinsic::Gather => "gather",
+            Intrinsic::Fetch => "fetch",
+            Intrinsic::TraceRay => "trace_ray",
+            Intrinsic::EmitVertex => "emit_vertex",
+            Intrinsic::EndPrimitive => "end_primitive",
+            Intrinsic::SubgroupBallot => "subgroup_ballot",
+            Intrinsic::SubgroupShuffle => "subgroup_shuffle",
+            Intrinsic::SubgroupBroadcast => "subgroup_broadcast",
+            Intrinsic::SubgroupAdd => "subgroup_add",
+            Intrinsic::SubgroupMin => "subgroup_min",
+            Intrinsic::SubgroupMax => "subgroup_max",
+            Intrinsic::WrappingAdd => "wrapping_add",
+            Intrinsic::WrappingSub => "wrapping_sub",
+            Intrinsic::WrappingMul => "wrapping_mul",
+            Intrinsic::SaturatingAdd => "saturating_add",
+            Intrinsic::SaturatingSub => "saturating_sub",
+            Intrinsic::ApproxEq => "approx_eq",
+            Intrinsic::Bitcast => "bitcast",
+            Intrinsic::Select => "select",
+            Intrinsic::Any => "any",
+            Intrinsic::All => "all",
+            Intrinsic::Some => "some",
+            Intrinsic::None => "none",
+            Intrinsic::UnwrapOr => "unwrap_or",
+            Intrinsic::Assert => "assert",
+            Intrinsic::DebugPrint => "debug_print",
+        }
+    }
+
+    /// Whether all invocations of a workgroup have to call the intrinsic
+    /// together.
+    pub fn is_barrier(self) -> bool {
+        matches!(
+            self,
+            Intrinsic::WorkgroupBarrier | Intrinsic::StorageBarrier
+        )
+    }
+
+    /// Whether the intrinsic outputs the primitives of a geometry program.
+    pub fn is_emission(self) -> bool {
+        matches!(self, Intrinsic::EmitVertex | Intrinsic::EndPrimitive)
+    }
+
+    /// Whether the result is computed from the values of the other active
+    /// invocations of the subgroup, which depend on the control flow that
+    /// reaches the call.
+    pub fn is_subgroup(self) -> bool {
+        matches!(
+            self,
+            Intrinsic::SubgroupBallot
+                | Intrinsic::SubgroupShuffle
+                | Intrinsic::SubgroupBroadcast
+                | Intrinsic::SubgroupAdd
+                | Intrinsic::SubgroupMin
+                | Intrinsic::SubgroupMax
+        )
+    }
+
+    /// Whether the result is computed from the values of neighbouring
+    /// fragments, which only exist in fragment programs and are undefined
+    /// when the neighbours don't execute the call as well.
+    pub fn is_derivative(self) -> bool {
+        matches!(
+            self,
+            Intrinsic::Dpdx
+                | Intrinsic::Dpdy
+                | Intrinsic::Fwidth
+                | Intrinsic::Sample
+                | Intrinsic::SampleCompare
+        )
+    }
+
+    /// Whether the intrinsic reads a texture, with the parameters given by
+    /// [`crate::textures::sampling_signature`].
+    pub fn is_sampling(self) -> bool {
+        matches!(
+            self,
+            Intrinsic::Sample
+                | Intrinsic::SampleLod
+                | Intrinsic::SampleGrad
+                | Intrinsic::SampleCompare
+                | Intrinsic::Gather
+                | Intrinsic::Fetch
+        )
+    }
+
+    /// The positions of the arguments that have to be normalized vectors.
+    pub fn normalized_arguments(self) -> &'static [usize] {
+        match self {
+            Intrinsic::Reflect => &[1],
+            Intrinsic::Refract => &[0, 1],
+            _ => &[],
+        }
+    }
+
+    /// The positions of the arguments that are angles, with their unit, or
+    /// `float`s that aren't angles, with no unit.
+    pub fn angle_arguments(self) -> &'static [(usize, Option<AngleUnit>)] {
+        match self {
+            Intrinsic::Sin | Intrinsic::Cos | Intrinsic::Tan | Intrinsic::ToDegrees => {
+                &[(0, Some(AngleUnit::Radians))]
+            }
+            Intrinsic::ToRadians => &[(0, Some(AngleUnit::Degrees))],
+            Intrinsic::Asin | Intrinsic::Acos | Intrinsic::Atan => &[(0, None)],
+            Intrinsic::Atan2 => &[(0, None), (1, None)],
+            _ => &[],
+        }
+    }
+
+    /// The side effects of a call of the intrinsic.
+    pub fn effects(self) -> Effects {
+        match self {
+            Intrinsic::Unpack
+            | Intrinsic::Transpose
+            | Intrinsic::Inverse
+            | Intrinsic::Determinant
+            | Intrinsic::Identity
+            | Intrinsic::SrgbToLinear
+            | Intrinsic::LinearToSrgb
+            | Intrinsic::Normalize
+            | Intrinsic::Reflect
+            | Intrinsic::Refract
+            | Intrinsic::Sin
+            | Intrinsic::Cos
+            | Intrinsic::Tan
+            | Intrinsic::Asin
+            | Intrinsic::Acos
+            | Intrinsic::Atan
+            | Intrinsic::Atan2
+            | Intrinsic::ToRadians
+            | Intrinsic::ToDegrees
+            | Intrinsic::ImageLoad
+            | Intrinsic::SampleLod
+            | Intrinsic::SampleGrad
+            | Intrinsic::Gather
+            | Intrinsic::Fetch
+            | Intrinsic::WrappingAdd
+            | Intrinsic::WrappingSub
+            | Intrinsic::WrappingMul
+            | Intrinsic::SaturatingAdd
+            | Intrinsic::SaturatingSub
+            | Intrinsic::ApproxEq
+            | Intrinsic::Bitcast
+            | Intrinsic::Select
+            | Intrinsic::Any
+            | Intrinsic::All
+            | Intrinsic::Some
+            | Intrinsic::None
+            | Intrinsic::UnwrapOr => Effects::default(),
+            Intrinsic::AtomicAdd
+            | Intrinsic::AtomicMin
+            | Intrinsic::AtomicMax
+            | Intrinsic::AtomicExchange
+            | Intrinsic::AtomicCompareExchange
+            | Intrinsic::ImageStore
+            | Intrinsic::TraceRay => Effects {
+                writes_resources: true,
+                ..Effects::default()
+            },
+            Intrinsic::WorkgroupBarrier | Intrinsic::StorageBarrier => Effects {
+                barriers: true,
+                ..Effects::default()
+            },
+            Intrinsic::EmitVertex | Intrinsic::EndPrimitive => Effects {
+                emits_primitives: true,
+                ..Effects::default()
+            },
+            Intrinsic::Assert | Intrinsic::DebugPrint => Effects {
+                debug_output: true,
+                ..Effects::default()
+            },
+            Intrinsic::SubgroupBallot
+            | Intrinsic::SubgroupShuffle
+            | Intrinsic::SubgroupBroadcast
+            | Intrinsic::SubgroupAdd
+            | Intrinsic::SubgroupMin
+            | Intrinsic::SubgroupMax => Effects {
+                subgroup: true,
+                ..Effects::default()
+            },
+            Intrinsic::Dpdx
+            | Intrinsic::Dpdy
+            | Intrinsic::Fwidth
+            | Intrinsic::Sample
+            | Intrinsic::SampleCompare => Effects {
+                derivatives: true,
+                ..Effects::default()
+            },
+        }
+    }
+
+    /// Whether the result only depends on the arguments.
+    pub fn is_pure(self) -> bool {
+        match self {
+            // the derivatives of uniform values are zero everywhere
+            Intrinsic::Unpack | Intrinsic::Dpdx | Intrinsic::Dpdy | Intrinsic::Fwidth => true,
+            Intrinsic::Transpose
+            | Intrinsic::Inverse
+            | Intrinsic::Determinant
+            | Intrinsic::Identity
+            | Intrinsic::SrgbToLinear
+            | Intrinsic::LinearToSrgb
+            | Intrinsic::Normalize
+            | Intrinsic::Reflect
+            | Intrinsic::Refract
+            | Intrinsic::Sin
+            | Intrinsic::Cos
+            | Intrinsic::Tan
+            | Intrinsic::Asin
+            | Intrinsic::Acos
+            | Intrinsic::Atan
+            | Intrinsic::Atan2
+            | Intrinsic::ToRadians
+            | Intrinsic::ToDegrees
+            | Intrinsic::WrappingAdd
+            | Intrinsic::WrappingSub
+            | Intrinsic::WrappingMul
+            | Intrinsic::SaturatingAdd
+            | Intrinsic::SaturatingSub
+            | Intrinsic::ApproxEq
+            | Intrinsic::Bitcast
+            | Intrinsic::Select
+            | Intrinsic::Any
+            | Intrinsic::All
+            | Intrinsic::Some
+            | Intrinsic::None
+            | Intrinsic::UnwrapOr => true,
+            // textures are never written
+            Intrinsic::Sample
+            | Intrinsic::SampleLod
+            | Intrinsic::SampleGrad
+            | Intrinsic::SampleCompare
+            | Intrinsic::Gather
+            | Intrinsic::Fetch => true,
+            Intrinsic::AtomicAdd
+            | Intrinsic::AtomicMin
+            | Intrinsic::AtomicMax
+            | Intrinsic::AtomicExchange
+            | Intrinsic::AtomicCompareExchange
+            | Intrinsic::WorkgroupBarrier
+            | Intrinsic::StorageBarrier
+            | Intrinsic::ImageLoad
+            | Intrinsic::ImageStore
+            | Intrinsic::TraceRay
+            | Intrinsic::EmitVertex
+            | Intrinsic::EndPrimitive
+            | Intrinsic::Assert
+            | Intrinsic::DebugPrint => false,
+            // the values of the other invocations may differ
+            Intrinsic::SubgroupBallot
+            | Intrinsic::SubgroupShuffle
+            | Intrinsic::SubgroupBroadcast
+            | Intrinsic::SubgroupAdd
+            | Intrinsic::SubgroupMin
+            | Intrinsic::SubgroupMax => false,
+        }
+    }
+}
+
+impl Context {
+    /// The type of a call of the intrinsic with positional arguments of the
+    /// given types, `None` if the arguments don't fit the intrinsic.
+    pub fn intrinsic_type(&mut self, intrinsic: Intrinsic, args: &[TypeId]) -> Option<TypeId> {
+        match (intrinsic, args) {
+            (Intrinsic::Unpack, [arg]) => {
+                let arg = self.strip_distinct(*arg);
+                match self.types.get(arg)? {
+                    Type::Packed { format } => {
+                        let unpacked = format.unpacked();
+                        Some(self.add_or_get_type(unpacked))
+                    }
+                    _ => None,
+                }
+            }
+            (Intrinsic::Unpack, _) => None,
+            (
+                Intrinsic::AtomicAdd
+                | Intrinsic::AtomicMin
+                | Intrinsic::AtomicMax
+                | Intrinsic::AtomicExchange,
+                [atomic, value],
+            ) => self.atomic_operation(*atomic, &[*value]),
+            (Intrinsic::AtomicCompareExchange, [atomic, cmp, value]) => {
+                self.atomic_operation(*atomic, &[*cmp, *value])
+            }
+            (
+                Intrinsic::AtomicAdd
+                | Intrinsic::AtomicMin
+                | Intrinsic::AtomicMax
+                | Intrinsic::AtomicExchange
+                | Intrinsic::AtomicCompareExchange,
+                _,
+            ) => None,
+            // barriers have no value
+            (Intrinsic::WorkgroupBarrier | Intrinsic::StorageBarrier, _) => None,
+            (Intrinsic::EmitVertex | Intrinsic::EndPrimitive, _) => None,
+            (Intrinsic::Dpdx | Intrinsic::Dpdy | Intrinsic::Fwidth, [arg]) => {
+                let inner = self.strip_distinct(*arg);
+                match self.types.get(inner)? {
+                    Type::Float | Type::FloatVec { .. } | Type::Half | Type::HalfVec { .. } => {
+                        Some(*arg)
+                    }
+                    _ => None,
+                }
+            }
+            (Intrinsic::Dpdx | Intrinsic::Dpdy | Intrinsic::Fwidth, _) => None,
+            (Intrinsic::Transpose, [matrix]) => self.transpose_type(*matrix),
+            (Intrinsic::Inverse, [matrix]) => self.inverse_type(*matrix),
+            (Intrinsic::Determinant, [matrix]) => self.determinant_type(*matrix),
+            (Intrinsic::Transpose | Intrinsic::Inverse | Intrinsic::Determinant, _) => None,
+            // the type comes from where the value is used, see
+            // `Context::identity_type`
+            (Intrinsic::Identity, _) => None,
+            (Intrinsic::SrgbToLinear, [colour]) => {
+                self.colour_conversion(*colour, VecType::SrgbColour, VecType::Colour)
+            }
+            (Intrinsic::LinearToSrgb, [colour]) => {
+                self.colour_conversion(*colour, VecType::Colour, VecType::SrgbColour)
+            }
+            (Intrinsic::SrgbToLinear | Intrinsic::LinearToSrgb, _) => None,
+            (Intrinsic::Normalize, [v]) => {
+                let v = self.strip_normalized(*v);
+                self.normalized_type(v)
+            }
+            // a reflected normalized vector stays normalized
+            (Intrinsic::Reflect, [i, n]) => {
+                self.incident_vector(*i, *n)?;
+                Some(*i)
+            }
+            (Intrinsic::Refract, [i, n, eta]) => {
+                let vector = self.incident_vector(*i, *n)?;
+                let scalar = self.types.get(self.strip_distinct(vector))?.scalar()?;
+                match self.types.get(*eta) {
+                    Some(eta) if *eta == scalar => Some(vector),
+                    _ => None,
+                }
+            }
+            (Intrinsic::Normalize | Intrinsic::Reflect | Intrinsic::Refract, _) => None,
+            (
+                Intrinsic::Sin
+                | Intrinsic::Cos
+                | Intrinsic::Tan
+                | Intrinsic::Asin
+                | Intrinsic::Acos
+                | Intrinsic::Atan
+                | Intrinsic::Atan2
+                | Intrinsic::ToRadians
+                | Intrinsic::ToDegrees,
+                args,
+            ) => self.angle_function(intrinsic, args),
+            (Intrinsic::ImageLoad | Intrinsic::ImageStore, args) => {
+                self.image_intrinsic_type(intrinsic, args)
+            }
+            (
+                Intrinsic::Sample
+                | Intrinsic::SampleLod
+                | Intrinsic::SampleGrad
+                | Intrinsic::SampleCompare
+                | Intrinsic::Gather
+                | Intrinsic::Fetch,
+                args,
+            ) => self.sampling_type(intrinsic, args),
+            // tracing a ray has no value, the result is in the payload
+            (Intrinsic::TraceRay, _) => None,
+            (Intrinsic::SubgroupBallot, [arg]) => {
+                match self.types.get(self.strip_distinct(*arg))? {
+                    Type::Bool => Some(self.add_or_get_type(Type::UIntVec {
+                        components: VecSize::VS4,
+                        vtype: VecType::Unknown,
+                        space: None,
+                    })),
+                    _ => None,
+                }
+            }
+            (Intrinsic::SubgroupBallot, _) => None,
+            (Intrinsic::SubgroupShuffle | Intrinsic::SubgroupBroadcast, [value, id]) => {
+                let id = self.types.get(self.strip_distinct(*id))?;
+                if !matches!(id, Type::Int | Type::UInt) {
+                    return None;
+                }
+                self.subgroup_operation(*value, true)
+            }
+            (Intrinsic::SubgroupAdd | Intrinsic::SubgroupMin | Intrinsic::SubgroupMax, [value]) => {
+                self.subgroup_operation(*value, false)
+            }
+            (
+                Intrinsic::SubgroupShuffle
+                | Intrinsic::SubgroupBroadcast
+                | Intrinsic::SubgroupAdd
+                | Intrinsic::SubgroupMin
+                | Intrinsic::SubgroupMax,
+                _,
+            ) => None,
+            (
+                Intrinsic::WrappingAdd
+                | Intrinsic::WrappingSub
+                | Intrinsic::WrappingMul
+                | Intrinsic::SaturatingAdd
+                | Intrinsic::SaturatingSub,
+                [a, b],
+            ) => (a == b && self.is_integer(*a)).then_some(*a),
+            (
+                Intrinsic::WrappingAdd
+                | Intrinsic::WrappingSub
+                | Intrinsic::WrappingMul
+                | Intrinsic::SaturatingAdd
+                | Intrinsic::SaturatingSub,
+                _,
+            ) => None,
+            (Intrinsic::ApproxEq, [a, b, tolerance]) => {
+                if a != b || self.float_scalar(*a) != Some(*tolerance) {
+                    return None;
+                }
+                Some(self.add_or_get_type(Type::Bool))
+            }
+            (Intrinsic::ApproxEq, _) => None,
+            // the type comes from where the value is used, see
+            // `Context::check_bitcast`
+            (Intrinsic::Bitcast, _) => None,
+            (Intrinsic::Select, [mask, a, b]) => {
+                if a != b {
+                    return None;
+                }
+                match self.types.get(self.strip_distinct(*mask))? {
+                    Type::Bool => Some(*a),
+                    Type::BoolVec { components } => {
+                        (self.vector_size(*a) == Some(*components)).then_some(*a)
+                    }
+                    _ => None,
+                }
+            }
+            (Intrinsic::Any | Intrinsic::All, [v]) => {
+                match self.types.get(self.strip_distinct(*v))? {
+                    Type::BoolVec { .. } => Some(self.add_or_get_type(Type::Bool)),
+                    _ => None,
+                }
+            }
+            (Intrinsic::Select | Intrinsic::Any | Intrinsic::All, _) => None,
+            // the calls are typed and checked when indexing, see
+            // `crate::optionals`
+            (Intrinsic::Some | Intrinsic::None | Intrinsic::UnwrapOr, _) => None,
+            // debug output has no value, the calls are checked when
+            // indexing, see `crate::debug_output`
+            (Intrinsic::Assert | Intrinsic::DebugPrint, _) => None,
+        }
+    }
+
+    /// The number of components of a vector, `None` for other types.
+    pub fn vector_size(&self, ty: TypeId) -> Option<VecSize> {
+        match self
+            .types
+            .get(self.strip_normalized(self.strip_distinct(ty)))?
+        {
+            Type::BoolVec { components }
+            | Type::IntVec { components, .. }
+            | Type::UIntVec { components, .. }
+            | Type::LongVec { components, .. }
+            | Type::ULongVec { components, .. }
+            | Type::FloatVec { components, .. }
+            | Type::DoubleVec { components, .. }
+            | Type::HalfVec { components, .. } => Some(*components),
+            _ => None,
+        }
+    }
+
+    /// The vector type of the incident vector `i` and the normal `n` of a
+    /// reflection or refraction, both of which have to be vectors of floating
+    /// point numbers of the same type.
+    fn incident_vector(&mut self, i: TypeId, n: TypeId) -> Option<TypeId> {
+        let vector = self.strip_normalized(i);
+        if vector != self.strip_normalized(n) {
+            return None;
+        }
+        self.normalized_type(vector)?;
+        Some(vector)
+    }
+
+    /// The type of a trigonometric function or a conversion of angles, whose
+    /// arguments are all given by `Intrinsic::angle_arguments`.
+    fn angle_function(&mut self, intrinsic: Intrinsic, args: &[TypeId]) -> Option<TypeId> {
+        let params = intrinsic.angle_arguments();
+        if args.len() != params.len() {
+            return None;
+        }
+        for (arg, (_, unit)) in args.iter().zip(params) {
+            let fits = match unit {
+                Some(unit) => self.angle_unit(*arg) == Some(*unit),
+                None => matches!(self.types.get(self.strip_distinct(*arg)), Some(Type::Float)),
+            };
+            if !fits {
+                return None;
+            }
+        }
+        let ret = match intrinsic {
+            Intrinsic::Sin | Intrinsic::Cos | Intrinsic::Tan => Type::Float,
+            Intrinsic::ToDegrees => Type::Degrees,
+            _ => Type::Radians,
+        };
+        Some(self.add_or_get_type(ret))
+    }
+
+    /// The type of a conversion of an RGB or RGBA colour from one encoding
+    /// to the other. Vectors that aren't tagged as colours are taken to be
+    /// in the encoding that is converted from.
+    fn colour_conversion(&mut self, colour: TypeId, from: VecType, to: VecType) -> Option<TypeId> {
+        let colour = self.strip_distinct(colour);
+        let converted = match self.types.get(colour)? {
+            Type::FloatVec {
+                components: components @ (VecSize::VS3 | VecSize::VS4),
+                vtype,
+                space,
+            } if *vtype == from || *vtype == VecType::Unknown => Type::FloatVec {
+                components: *components,
+                vtype: to,
+                space: *space,
+            },
+            Type::HalfVec {
+                components: components @ (VecSize::VS3 | VecSize::VS4),
+                vtype,
+                space,
+            } if *vtype == from || *vtype == VecType::Unknown => Type::HalfVec {
+                components: *components,
+                vtype: to,
+                space: *space,
+            },
+            _ => return None,
+        };
+        Some(self.add_or_get_type(converted))
+    }
+
+    /// The type of a subgroup operation on a value, which has to be a numeric
+    /// scalar or vector, or a `bool` if the value is only passed between the
+    /// invocations.
+    fn subgroup_operation(&mut self, value: TypeId, bools: bool) -> Option<TypeId> {
+        match self.types.get(self.strip_distinct(value))? {
+            Type::Int
+            | Type::UInt
+            | Type::Float
+            | Type::Double
+            | Type::Half
+            | Type::IntVec { .. }
+            | Type::UIntVec { .. }
+            | Type::FloatVec { .. }
+            | Type::DoubleVec { .. }
+            | Type::HalfVec { .. } => Some(value),
+            Type::Bool | Type::BoolVec { .. } if bools => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The type of an atomic operation on `atomic` with operands that have to
+    /// be of the type the atomic holds.
+    fn atomic_operation(&mut self, atomic: TypeId, operands: &[TypeId]) -> Option<TypeId> {
+        let value = self.atomic_value_type(atomic)?;
+        if operands.iter().all(|op| *op == value) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PackedFormat;
+
+    #[test]
+    fn unpack() {
+        let mut ctx = Context::default();
+        let packed = ctx.add_or_get_type(Type::Packed {
+            format: PackedFormat::Unorm8x4,
+        });
+        let float4 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS4,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let float = ctx.add_or_get_type(Type::Float);
+
+        assert_eq!(Intrinsic::from_name("unpack"), Some(Intrinsic::Unpack));
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Unpack, &[packed]),
+            Some(float4)
+        );
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Unpack, &[float]), None);
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Unpack, &[]), None);
+    }
+
+    #[test]
+    fn atomic_operations() {
+        let mut ctx = Context::default();
+        let atomic = ctx.add_or_get_type(Type::AtomicUInt);
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let int = ctx.add_or_get_type(Type::Int);
+
+        let add = Intrinsic::from_name("atomic_add").unwrap();
+        assert_eq!(ctx.intrinsic_type(add, &[atomic, uint]), Some(uint));
+        assert_eq!(ctx.intrinsic_type(add, &[atomic, int]), None);
+        assert_eq!(ctx.intrinsic_type(add, &[uint, uint]), None);
+
+        let cmp_xchg = Intrinsic::AtomicCompareExchange;
+        assert_eq!(
+            ctx.intrinsic_type(cmp_xchg, &[atomic, uint, uint]),
+            Some(uint)
+        );
+        assert_eq!(ctx.intrinsic_type(cmp_xchg, &[atomic, uint]), None);
+    }
+
+    #[test]
+    fn subgroup_operations() {
+        let mut ctx = Context::default();
+        let boolean = ctx.add_or_get_type(Type::Bool);
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let float = ctx.add_or_get_type(Type::Float);
+
+        let ballot = ctx.intrinsic_type(Intrinsic::SubgroupBallot, &[boolean]);
+        assert!(matches!(
+            ballot.and_then(|ty| ctx.types.get(ty)),
+            Some(Type::UIntVec {
+                components: VecSize::VS4,
+                ..
+            })
+        ));
+        let shuffle = Intrinsic::SubgroupShuffle;
+        assert_eq!(ctx.intrinsic_type(shuffle, &[boolean, uint]), Some(boolean));
+        assert_eq!(ctx.intrinsic_type(shuffle, &[float, float]), None);
+        let add = Intrinsic::from_name("subgroup_add").unwrap();
+        assert_eq!(ctx.intrinsic_type(add, &[float]), Some(float));
+        assert_eq!(ctx.intrinsic_type(add, &[boolean]), None);
+    }
+
+    #[test]
+    fn overflowing_arithmetic() {
+        let mut ctx = Context::default();
+        let int = ctx.add_or_get_type(Type::Int);
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let float = ctx.add_or_get_type(Type::Float);
+        let ulong2 = ctx.add_or_get_type(Type::ULongVec {
+            components: VecSize::VS2,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+
+        let add = Intrinsic::from_name("saturating_add").unwrap();
+        assert_eq!(ctx.intrinsic_type(add, &[int, int]), Some(int));
+        assert_eq!(ctx.intrinsic_type(add, &[ulong2, ulong2]), Some(ulong2));
+        assert_eq!(ctx.intrinsic_type(add, &[int, uint]), None);
+        assert_eq!(ctx.intrinsic_type(add, &[float, float]), None);
+        let mul = Intrinsic::from_name("wrapping_mul").unwrap();
+        assert_eq!(ctx.intrinsic_type(mul, &[uint, uint]), Some(uint));
+        assert_eq!(ctx.intrinsic_type(mul, &[uint]), None);
+    }
+
+    #[test]
+    fn approx_eq() {
+        let mut ctx = Context::default();
+        let boolean = ctx.add_or_get_type(Type::Bool);
+        let float = ctx.add_or_get_type(Type::Float);
+        let half = ctx.add_or_get_type(Type::Half);
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+
+        let approx_eq = Intrinsic::from_name("approx_eq").unwrap();
+        assert_eq!(
+            ctx.intrinsic_type(approx_eq, &[float3, float3, float]),
+            Some(boolean)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(approx_eq, &[half, half, half]),
+            Some(boolean)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(approx_eq, &[float3, float3, float3]),
+            None
+        );
+        assert_eq!(ctx.intrinsic_type(approx_eq, &[float, half, float]), None);
+    }
+
+    #[test]
+    fn selection() {
+        let mut ctx = Context::default();
+        let boolean = ctx.add_or_get_type(Type::Bool);
+        let float = ctx.add_or_get_type(Type::Float);
+        let bool3 = ctx.add_or_get_type(Type::BoolVec {
+            components: VecSize::VS3,
+        });
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let uint2 = ctx.add_or_get_type(Type::UIntVec {
+            components: VecSize::VS2,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+
+        let select = Intrinsic::from_name("select").unwrap();
+        assert_eq!(
+            ctx.intrinsic_type(select, &[bool3, float3, float3]),
+            Some(float3)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(select, &[boolean, uint2, uint2]),
+            Some(uint2)
+        );
+        assert_eq!(ctx.intrinsic_type(select, &[bool3, uint2, uint2]), None);
+        assert_eq!(ctx.intrinsic_type(select, &[bool3, float, float]), None);
+        assert_eq!(ctx.intrinsic_type(select, &[boolean, float3, uint2]), None);
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Any, &[bool3]), Some(boolean));
+        assert_eq!(ctx.intrinsic_type(Intrinsic::All, &[boolean]), None);
+    }
+
+    #[test]
+    fn derivatives() {
+        let mut ctx = Context::default();
+        let world = ctx.types.intern_name("WorldSpace");
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Point,
+            space: Some(world),
+        });
+        let half = ctx.add_or_get_type(Type::Half);
+        let int = ctx.add_or_get_type(Type::Int);
+
+        let fwidth = Intrinsic::from_name("fwidth").unwrap();
+        assert!(fwidth.is_derivative());
+        assert_eq!(ctx.intrinsic_type(fwidth, &[float3]), Some(float3));
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Dpdx, &[half]), Some(half));
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Dpdy, &[int]), None);
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Dpdy, &[half, half]), None);
+    }
+
+    #[test]
+    fn colour_conversions() {
+        let mut ctx = Context::default();
+        let srgb = ctx.add_or_get_type(Type::HalfVec {
+            components: VecSize::VS4,
+            vtype: VecType::SrgbColour,
+            space: None,
+        });
+        let linear = ctx.add_or_get_type(Type::HalfVec {
+            components: VecSize::VS4,
+            vtype: VecType::Colour,
+            space: None,
+        });
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let float2 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS2,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+
+        let decode = Intrinsic::from_name("srgb_to_linear").unwrap();
+        assert_eq!(ctx.intrinsic_type(decode, &[srgb]), Some(linear));
+        assert_eq!(ctx.intrinsic_type(decode, &[linear]), None);
+        assert_eq!(ctx.intrinsic_type(decode, &[float2]), None);
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::LinearToSrgb, &[linear]),
+            Some(srgb)
+        );
+        let encoded = ctx.intrinsic_type(Intrinsic::LinearToSrgb, &[float3]);
+        assert_eq!(
+            encoded.map(|ty| ctx.types.get(ty).cloned()),
+            Some(Some(Type::FloatVec {
+                components: VecSize::VS3,
+                vtype: VecType::SrgbColour,
+                space: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn normalized_vectors() {
+        let mut ctx = Context::default();
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Vector,
+            space: None,
+        });
+        let normal = ctx.add_or_get_type(Type::Normalized { inner: float3 });
+        let float = ctx.add_or_get_type(Type::Float);
+        let half = ctx.add_or_get_type(Type::Half);
+        let int3 = ctx.add_or_get_type(Type::IntVec {
+            components: VecSize::VS3,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+
+        let normalize = Intrinsic::from_name("normalize").unwrap();
+        assert_eq!(ctx.intrinsic_type(normalize, &[float3]), Some(normal));
+        assert_eq!(ctx.intrinsic_type(normalize, &[normal]), Some(normal));
+        assert_eq!(ctx.intrinsic_type(normalize, &[int3]), None);
+        assert_eq!(ctx.intrinsic_type(normalize, &[float]), None);
+
+        assert_eq!(Intrinsic::Reflect.normalized_arguments(), &[1]);
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Reflect, &[normal, normal]),
+            Some(normal)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Reflect, &[float3, normal]),
+            Some(float3)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Refract, &[normal, normal, float]),
+            Some(float3)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Refract, &[normal, normal, half]),
+            None
+        );
+    }
+    #[test]
+    fn angles() {
+        let mut ctx = Context::default();
+        let radians = ctx.add_or_get_type(Type::Radians);
+        let degrees = ctx.add_or_get_type(Type::Degrees);
+        let float = ctx.add_or_get_type(Type::Float);
+
+        let sin = Intrinsic::from_name("sin").unwrap();
+        assert_eq!(ctx.intrinsic_type(sin, &[radians]), Some(float));
+        assert_eq!(ctx.intrinsic_type(sin, &[degrees]), None);
+        assert_eq!(ctx.intrinsic_type(sin, &[float]), None);
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Atan2, &[float, float]),
+            Some(radians)
+        );
+        assert_eq!(ctx.intrinsic_type(Intrinsic::Atan2, &[float]), None);
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::ToRadians, &[degrees]),
+            Some(radians)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::ToDegrees, &[radians]),
+            Some(degrees)
+        );
+        assert_eq!(ctx.intrinsic_type(Intrinsic::ToDegrees, &[degrees]), None);
+    }
+
+    #[test]
+    fn images() {
+        let mut ctx = Context::default();
+        let image = ctx
+            .image_type(crate::ImageDim::D2, "rgba8uint", "read")
+            .unwrap();
+        let int2 = ctx.add_or_get_type(Type::IntVec {
+            components: VecSize::VS2,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let uint4 = ctx.add_or_get_type(Type::UIntVec {
+            components: VecSize::VS4,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let int = ctx.add_or_get_type(Type::Int);
+
+        let load = Intrinsic::from_name("imageLoad").unwrap();
+        assert_eq!(ctx.intrinsic_type(load, &[image, int2]), Some(uint4));
+        assert_eq!(ctx.intrinsic_type(load, &[image, int]), None);
+        assert_eq!(ctx.intrinsic_type(load, &[int2, int2]), None);
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::ImageStore, &[image, int2, uint4]),
+            None
+        );
+        assert!(Intrinsic::ImageStore.effects().writes_resources);
+        assert!(ctx
+            .image_type(crate::ImageDim::D2, "rgba8unorm", "read_write")
+            .is_err());
+    }
+
+    #[test]
+    fn sampling() {
+        let mut ctx = Context::default();
+        let float = ctx.add_or_get_type(Type::Float);
+        let texture = ctx.texture_type(crate::TextureDim::Cube, float).unwrap();
+        let depth = ctx.add_or_get_type(Type::Texture {
+            dim: crate::TextureDim::D2,
+            sampled: crate::SampledType::Depth,
+        });
+        let sampler = ctx.add_or_get_type(Type::Sampler { comparison: false });
+        let comparison = ctx.add_or_get_type(Type::Sampler { comparison: true });
+        let float2 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS2,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Vector,
+            space: None,
+        });
+        let float4 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS4,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+
+        let sample = Intrinsic::from_name("sample").unwrap();
+        assert!(sample.is_sampling() && sample.is_derivative());
+        assert_eq!(
+            ctx.intrinsic_type(sample, &[texture, sampler, float3]),
+            Some(float4)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(sample, &[texture, sampler, float2]),
+            None
+        );
+        assert_eq!(ctx.intrinsic_type(sample, &[texture, float3]), None);
+        assert_eq!(
+            ctx.intrinsic_type(
+                Intrinsic::SampleCompare,
+                &[depth, comparison, float2, float]
+            ),
+            Some(float)
+        );
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::SampleCompare, &[depth, sampler, float2, float]),
+            None
+        );
+        assert_eq!(
+            ctx.intrinsic_type(Intrinsic::Gather, &[depth, sampler, float2]),
+            Some(float4)
+        );
+        assert!(!Intrinsic::SampleLod.is_derivative());
+        assert!(ctx.texture_type(crate::TextureDim::D2, float2).is_none());
+    }
+}
@@ -0,0 +1,1021 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Memory layout of types in buffers shared with the host.
+//!
+//! Matrices are stored column by column unless the module is compiled with
+//! another default or a buffer constant or field has a `row_major` or
+//! `column_major` attribute. The attribute applies to the matrices in the
+//! type of the declaration, and to arrays of them, but not to the fields of
+//! records in it, which have their own layout.
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use thiol_hir as hir;
+
+use hir::{FileLocation, Identifier, VariableDef};
+use id_arena::Id;
+
+use crate::{Context, Error, Name, Symbol, Type, TypeId, VecSize};
+
+/// Rules for the size and alignment of values in a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LayoutRules {
+    /// arrays and records are aligned to 16 bytes
+    Std140,
+    /// like std140, without the extra alignment of arrays and records
+    Std430,
+    /// every value is aligned to the size of its scalar components
+    Scalar,
+}
+
+impl LayoutRules {
+    pub const ALL: &'static [LayoutRules] = &[
+        LayoutRules::Std140,
+        LayoutRules::Std430,
+        LayoutRules::Scalar,
+    ];
+
+    /// The layout rules with this name, like `std140`
+    pub fn from_name(name: &str) -> Option<Self> {
+        LayoutRules::ALL
+            .iter()
+            .copied()
+            .find(|rules| rules.to_string() == name)
+    }
+}
+
+/// The kinds of buffers a constant can be bound to, selected with an
+/// attribute on the constant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BufferClass {
+    Uniform,
+    Storage,
+    PushConstant,
+}
+
+impl BufferClass {
+    /// The buffer class selected by an attribute with this name
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "Uniform" => Some(BufferClass::Uniform),
+            "Storage" => Some(BufferClass::Storage),
+            "PushConstant" => Some(BufferClass::PushConstant),
+            _ => None,
+        }
+    }
+
+    pub fn default_rules(self) -> LayoutRules {
+        match self {
+            BufferClass::Uniform => LayoutRules::Std140,
+            BufferClass::Storage | BufferClass::PushConstant => LayoutRules::Std430,
+        }
+    }
+}
+
+/// The order the elements of a matrix are stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum MatrixLayout {
+    /// one vector per column, like GLSL and Metal store matrices
+    #[default]
+    ColumnMajor,
+    /// one vector per row, like most CPU math libraries store matrices
+    RowMajor,
+}
+
+impl MatrixLayout {
+    pub const ALL: &'static [MatrixLayout] = &[MatrixLayout::ColumnMajor, MatrixLayout::RowMajor];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MatrixLayout::ColumnMajor => "column-major",
+            MatrixLayout::RowMajor => "row-major",
+        }
+    }
+
+    /// The matrix layout selected by an attribute with this name
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "column_major" => Some(MatrixLayout::ColumnMajor),
+            "row_major" => Some(MatrixLayout::RowMajor),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for MatrixLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MatrixLayout::ALL
+            .iter()
+            .copied()
+            .find(|layout| layout.name() == s)
+            .ok_or_else(|| {
+                let names = MatrixLayout::ALL
+                    .iter()
+                    .map(|l| l.name())
+                    .collect::<Vec<_>>();
+                format!(
+                    "unknown matrix layout `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for MatrixLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Display for LayoutRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutRules::Std140 => write!(f, "std140"),
+            LayoutRules::Std430 => write!(f, "std430"),
+            LayoutRules::Scalar => write!(f, "scalar"),
+        }
+    }
+}
+
+impl fmt::Display for BufferClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferClass::Uniform => write!(f, "uniform buffer"),
+            BufferClass::Storage => write!(f, "storage buffer"),
+            BufferClass::PushConstant => write!(f, "push constant"),
+        }
+    }
+}
+
+/// Why a type can't be used in a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferTypeProblem {
+    /// booleans have no defined representation in memory
+    Bool,
+    /// the buffer class needs a fixed size
+    OpenArray,
+    /// arrays without size can only be the last field of the buffer
+    OpenArrayNotLast,
+    /// atomics can only be written in storage buffers
+    Atomic,
+    /// images are bound on their own, not as part of a buffer
+    Image,
+    /// textures and samplers are bound on their own as well
+    Texture,
+    /// and so are acceleration structures
+    AccelerationStructure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+    /// offsets of the fields of a record
+    pub offsets: Vec<usize>,
+    /// distance between the elements of an array or the columns of a matrix
+    pub stride: Option<usize>,
+    /// whether the type ends in an array without size, `size` only covers the
+    /// part before it
+    pub runtime_sized: bool,
+}
+
+/// Layout of a record field given with `@offset(..)`, `@align(..)`,
+/// `@row_major` and `@column_major` attributes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub offset: Option<usize>,
+    pub align: Option<usize>,
+    pub matrix: Option<MatrixLayout>,
+}
+
+/// A field whose explicit layout doesn't agree with the layout rules
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LayoutViolation {
+    /// the distinct type of the record the field belongs to
+    pub distinct_id: usize,
+    pub field: usize,
+    pub kind: LayoutViolationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LayoutViolationKind {
+    /// the field starts before the end of the previous one
+    Overlap {
+        offset: usize,
+        previous: Identifier,
+        previous_end: usize,
+    },
+    /// the offset is not a multiple of the alignment of the field
+    Misaligned { offset: usize, align: usize },
+    /// the given alignment is smaller than the one the rules require
+    AlignTooSmall { align: usize, required: usize },
+}
+
+/// Why a layout attribute is not valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutAttributeProblem {
+    /// the attribute needs a single integer argument
+    ExpectedInteger,
+    /// alignments have to be powers of two
+    NotPowerOfTwo(usize),
+}
+
+/// Why a matrix layout attribute is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatrixLayoutProblem {
+    /// the type of the declaration has no matrices of its own
+    NoMatrix { type_name: String },
+    /// only buffers are stored in memory shared with the host
+    NotABuffer,
+    /// another attribute already chose the layout
+    Conflicting { previous: FileLocation },
+}
+
+impl Layout {
+    fn new(size: usize, align: usize) -> Self {
+        Layout {
+            size,
+            align,
+            offsets: vec![],
+            stride: None,
+            runtime_sized: false,
+        }
+    }
+}
+
+fn round_up(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
+}
+
+pub(crate) fn components(size: VecSize) -> usize {
+    match size {
+        VecSize::VS2 => 2,
+        VecSize::VS3 => 3,
+        VecSize::VS4 => 4,
+    }
+}
+
+impl Context {
+    /// The layout of `ty` under the given rules.
+    ///
+    /// Types that have no layout, like generic parameters, the error type or
+    /// records with an array without size before their last field, give `None`.
+    pub fn layout(&self, ty: TypeId, rules: LayoutRules) -> Option<Layout> {
+        self.layout_with(ty, rules, self.matrix_layout, &mut vec![])
+    }
+
+    /// The layout of `ty` with its matrices stored in the given order, like
+    /// the value of a constant with a matrix layout attribute.
+    pub fn layout_as(
+        &self,
+        ty: TypeId,
+        rules: LayoutRules,
+        matrices: MatrixLayout,
+    ) -> Option<Layout> {
+        self.layout_with(ty, rules, matrices, &mut vec![])
+    }
+
+    /// The fields of records in `ty` whose explicit layout doesn't agree with
+    /// the rules.
+    pub fn layout_violations(
+        &self,
+        ty: TypeId,
+        rules: LayoutRules,
+        matrices: MatrixLayout,
+    ) -> Vec<LayoutViolation> {
+        let mut violations = vec![];
+        self.layout_with(ty, rules, matrices, &mut violations);
+        violations.sort();
+        violations.dedup();
+        violations
+    }
+
+    /// The layout of the matrices of a constant or field.
+    pub fn matrix_layout_of(&self, def: Id<VariableDef>) -> MatrixLayout {
+        self.matrix_layouts
+            .get(&def)
+            .copied()
+            .unwrap_or(self.matrix_layout)
+    }
+
+    /// The layouts of all matrices stored in a value of `ty`, including the
+    /// ones in the fields of records.
+    pub fn matrix_layouts_in(&self, ty: TypeId, matrices: MatrixLayout) -> BTreeSet<MatrixLayout> {
+        let mut layouts = BTreeSet::new();
+        self.collect_matrix_layouts(ty, matrices, &mut layouts);
+        layouts
+    }
+
+    fn collect_matrix_layouts(
+        &self,
+        ty: TypeId,
+        matrices: MatrixLayout,
+        layouts: &mut BTreeSet<MatrixLayout>,
+    ) {
+        match self.types.get(ty) {
+            Some(Type::FloatMat { .. }) | Some(Type::DoubleMat { .. }) => {
+                layouts.insert(matrices);
+            }
+            Some(Type::Array { base, .. }) | Some(Type::OpenArray { base }) => {
+                self.collect_matrix_layouts(*base, matrices, layouts)
+            }
+            Some(Type::Record { fields }) => {
+                for (_, field) in fields {
+                    self.collect_matrix_layouts(*field, self.matrix_layout, layouts);
+                }
+            }
+            Some(Type::Distinct { distinct_id, inner }) => {
+                match (self.field_layouts.get(distinct_id), self.types.get(*inner)) {
+                    (Some(explicit), Some(Type::Record { fields })) => {
+                        for (i, (_, field)) in fields.iter().enumerate() {
+                            let matrices = self.field_matrix_layout(explicit, i);
+                            self.collect_matrix_layouts(*field, matrices, layouts);
+                        }
+                    }
+                    _ => self.collect_matrix_layouts(*inner, matrices, layouts),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn field_matrix_layout(&self, explicit: &[FieldLayout], field: usize) -> MatrixLayout {
+        explicit
+            .get(field)
+            .and_then(|layout| layout.matrix)
+            .unwrap_or(self.matrix_layout)
+    }
+
+    /// Whether `ty` has matrices of its own, directly or in arrays.
+    pub fn has_matrices(&self, ty: TypeId) -> bool {
+        match self.types.get(self.strip_distinct(ty)) {
+            Some(Type::FloatMat { .. }) | Some(Type::DoubleMat { .. }) => true,
+            Some(Type::Array { base, .. }) | Some(Type::OpenArray { base }) => {
+                self.has_matrices(*base)
+            }
+            _ => false,
+        }
+    }
+
+    fn layout_with(
+        &self,
+        ty: TypeId,
+        rules: LayoutRules,
+        matrices: MatrixLayout,
+        violations: &mut Vec<LayoutViolation>,
+    ) -> Option<Layout> {
+        let vector = |scalar: usize, n: VecSize| {
+            let n = components(n);
+            let align = match (rules, n) {
+                (LayoutRules::Scalar, _) => scalar,
+                (_, 2) => 2 * scalar,
+                _ => 4 * scalar,
+            };
+            Layout::new(n * scalar, align)
+        };
+        let matrix = |scalar: usize, cols: VecSize, rows: VecSize| {
+            // the matrix is an array of its columns or rows
+            let (count, length) = match matrices {
+                MatrixLayout::ColumnMajor => (cols, rows),
+                MatrixLayout::RowMajor => (rows, cols),
+            };
+            let vector = vector(scalar, length);
+            let align = match rules {
+                LayoutRules::Std140 => round_up(vector.align, 16),
+                LayoutRules::Std430 | LayoutRules::Scalar => vector.align,
+            };
+            let stride = round_up(vector.size, align);
+            Layout {
+                stride: Some(stride),
+                ..Layout::new(stride * components(count), align)
+            }
+        };
+
+        let layout = match self.types.get(ty)? {
+            Type::Bool
+            | Type::Int
+            | Type::UInt
+            | Type::Float
+            | Type::Radians
+            | Type::Degrees
+            | Type::AtomicInt
+            | Type::AtomicUInt => Layout::new(4, 4),
+            Type::Double | Type::Long | Type::ULong => Layout::new(8, 8),
+            Type::Half => Layout::new(2, 2),
+            Type::BoolVec { components } => vector(4, *components),
+            Type::IntVec { components, .. }
+            | Type::UIntVec { components, .. }
+            | Type::FloatVec { components, .. } => vector(4, *components),
+            Type::DoubleVec { components, .. }
+            | Type::LongVec { components, .. }
+            | Type::ULongVec { components, .. } => vector(8, *components),
+            Type::HalfVec { components, .. } => vector(2, *components),
+            Type::FloatMat { cols, rows, .. } => matrix(4, *cols, *rows),
+            Type::DoubleMat { cols, rows, .. } => matrix(8, *cols, *rows),
+            Type::Packed { format } => Layout::new(format.size(), 4),
+            Type::Array { base, size } => {
+                let stride = self.array_stride(*base, rules, matrices, violations)?;
+                Layout {
+                    stride: Some(stride.0),
+                    ..Layout::new(stride.0 * size, stride.1)
+                }
+            }
+            Type::OpenArray { base } => {
+                let stride = self.array_stride(*base, rules, matrices, violations)?;
+                Layout {
+                    stride: Some(stride.0),
+                    runtime_sized: true,
+                    ..Layout::new(0, stride.1)
+                }
+            }
+            Type::Record { fields } => self.record_layout(fields, None, rules, violations)?,
+            Type::Distinct { distinct_id, inner } => {
+                match (self.field_layouts.get(distinct_id), self.types.get(*inner)) {
+                    (Some(explicit), Some(Type::Record { fields })) => self.record_layout(
+                        fields,
+                        Some((*distinct_id, explicit)),
+                        rules,
+                        violations,
+                    )?,
+                    _ => self.layout_with(*inner, rules, matrices, violations)?,
+                }
+            }
+            Type::Normalized { inner } => self.layout_with(*inner, rules, matrices, violations)?,
+            // images, textures, samplers and acceleration structures are
+            // opaque to programs, optionals only exist in programs
+            Type::Optional { .. }
+            | Type::Image { .. }
+            | Type::Texture { .. }
+            | Type::Sampler { .. }
+            | Type::AccelerationStructure
+            | Type::GenericParam { .. }
+            | Type::Var(_)
+            | Type::Error => return None,
+        };
+
+        Some(layout)
+    }
+
+    fn record_layout(
+        &self,
+        fields: &[(Name, TypeId)],
+        explicit: Option<(usize, &[FieldLayout])>,
+        rules: LayoutRules,
+        violations: &mut Vec<LayoutViolation>,
+    ) -> Option<Layout> {
+        let mut offset = 0;
+        let mut align = 1;
+        let mut offsets = vec![];
+        let mut runtime_sized = false;
+
+        for (i, (_, field)) in fields.iter().enumerate() {
+            if runtime_sized {
+                return None;
+            }
+            let matrices = explicit.map_or(self.matrix_layout, |(_, explicit)| {
+                self.field_matrix_layout(explicit, i)
+            });
+            let layout = self.layout_with(*field, rules, matrices, violations)?;
+            let mut field_align = layout.align;
+
+            if let Some((distinct_id, explicit)) = explicit {
+                let mut violation = |kind| {
+                    violations.push(LayoutViolation {
+                        distinct_id,
+                        field: i,
+                        kind,
+                    })
+                };
+
+                let FieldLayout {
+                    offset: explicit_offset,
+                    align: explicit_align,
+                    ..
+                } = explicit.get(i).copied().unwrap_or_default();
+
+                if let Some(explicit_align) = explicit_align {
+                    if explicit_align < field_align {
+                        violation(LayoutViolationKind::AlignTooSmall {
+                            align: explicit_align,
+                            required: field_align,
+                        });
+                    }
+                    field_align = field_align.max(explicit_align);
+                }
+
+                if let Some(explicit_offset) = explicit_offset {
+                    if i > 0 && explicit_offset < offset {
+                        violation(LayoutViolationKind::Overlap {
+                            offset: explicit_offset,
+                            previous: self.types.name(fields[i - 1].0).to_string(),
+                            previous_end: offset,
+                        });
+                    } else if explicit_offset % field_align != 0 {
+                        violation(LayoutViolationKind::Misaligned {
+                            offset: explicit_offset,
+                            align: field_align,
+                        });
+                    }
+                    offset = explicit_offset;
+                }
+            }
+
+            offset = round_up(offset, field_align);
+            offsets.push(offset);
+            offset += layout.size;
+            align = align.max(field_align);
+            runtime_sized = layout.runtime_sized;
+        }
+
+        if rules == LayoutRules::Std140 {
+            align = round_up(align, 16);
+        }
+        let size = if runtime_sized {
+            offset
+        } else {
+            round_up(offset, align)
+        };
+        Some(Layout {
+            offsets,
+            runtime_sized,
+            ..Layout::new(size, align)
+        })
+    }
+
+    /// Stride and alignment of the elements of an array
+    fn array_stride(
+        &self,
+        base: TypeId,
+        rules: LayoutRules,
+        matrices: MatrixLayout,
+        violations: &mut Vec<LayoutViolation>,
+    ) -> Option<(usize, usize)> {
+        let elem = self.layout_with(base, rules, matrices, violations)?;
+        if elem.runtime_sized {
+            return None;
+        }
+        let align = match rules {
+            LayoutRules::Std140 => round_up(elem.align, 16),
+            LayoutRules::Std430 | LayoutRules::Scalar => elem.align,
+        };
+        Some((round_up(elem.size, align), align))
+    }
+
+    /// The first part of `ty` that can't be stored in the buffer class,
+    /// together with the path of fields leading to it.
+    pub fn buffer_type_problem(
+        &self,
+        ty: TypeId,
+        class: BufferClass,
+    ) -> Option<(BufferTypeProblem, Vec<String>)> {
+        // a storage binding of an image type is a storage image
+        if class == BufferClass::Storage && self.image(ty).is_some() {
+            return None;
+        }
+        // and a uniform binding of a texture, sampler or acceleration
+        // structure type binds it
+        if class == BufferClass::Uniform
+            && (self.texture(ty).is_some()
+                || self.sampler(ty).is_some()
+                || self.types.get(ty) == Some(&Type::AccelerationStructure))
+        {
+            return None;
+        }
+        let mut path = vec![];
+        let problem = self.buffer_problem(ty, class, true, &mut path)?;
+        Some((problem, path))
+    }
+
+    fn buffer_problem(
+        &self,
+        ty: TypeId,
+        class: BufferClass,
+        last: bool,
+        path: &mut Vec<String>,
+    ) -> Option<BufferTypeProblem> {
+        match self.types.get(ty)? {
+            Type::Bool | Type::BoolVec { .. } => Some(BufferTypeProblem::Bool),
+            Type::AtomicInt | Type::AtomicUInt if class != BufferClass::Storage => {
+                Some(BufferTypeProblem::Atomic)
+            }
+            Type::Image { .. } => Some(BufferTypeProblem::Image),
+            Type::Texture { .. } | Type::Sampler { .. } => Some(BufferTypeProblem::Texture),
+            Type::AccelerationStructure => Some(BufferTypeProblem::AccelerationStructure),
+            Type::Array { base, .. } => self.buffer_problem(*base, class, false, path),
+            Type::OpenArray { base } => {
+                if class != BufferClass::Storage {
+                    Some(BufferTypeProblem::OpenArray)
+                } else if !last {
+                    Some(BufferTypeProblem::OpenArrayNotLast)
+                } else {
+                    self.buffer_problem(*base, class, false, path)
+                }
+            }
+            Type::Record { fields } => fields.iter().enumerate().find_map(|(i, (name, ty))| {
+                path.push(self.types.name(*name).to_string());
+                let problem = self.buffer_problem(*ty, class, last && i + 1 == fields.len(), path);
+                if problem.is_none() {
+                    path.pop();
+                }
+                problem
+            }),
+            Type::Distinct { inner, .. } => self.buffer_problem(*inner, class, last, path),
+            _ => None,
+        }
+    }
+}
+
+/// The explicit layout of a record field given by its attributes.
+pub(crate) fn field_layout(
+    hir_ctx: &hir::Context,
+    field: Id<VariableDef>,
+    errs: &mut Vec<Error>,
+) -> FieldLayout {
+    let mut layout = FieldLayout::default();
+
+    for attr_id in &hir_ctx.variable_defs[field].attrs {
+        let attr = &hir_ctx.attributes[*attr_id];
+        let name = &hir_ctx.identifiers[attr.name];
+        if let Some(matrix) = MatrixLayout::from_attribute(name) {
+            // later conflicting attributes are reported by
+            // `collect_matrix_layouts`
+            layout.matrix = layout.matrix.or(Some(matrix));
+            continue;
+        }
+        if name != "offset" && name != "align" {
+            continue;
+        }
+
+        let value = match (attr.pos_args.as_slice(), attr.nam_args.is_empty()) {
+            ([arg], true) => match hir_ctx.expressions[*arg] {
+                hir::Expression::Literal(hir::Literal::Integer(n, _)) => usize::try_from(n).ok(),
+                _ => None,
+            },
+            _ => None,
+        };
+        let problem = match value {
+            None => Some(LayoutAttributeProblem::ExpectedInteger),
+            Some(n) if name == "align" && !n.is_power_of_two() => {
+                Some(LayoutAttributeProblem::NotPowerOfTwo(n))
+            }
+            Some(_) => None,
+        };
+        if let Some(problem) = problem {
+            errs.push(Error::InvalidLayoutAttribute {
+                name: name.clone(),
+                attribute: hir_ctx.attribute_fcs[attr_id],
+                problem,
+            });
+            continue;
+        }
+
+        if name == "offset" {
+            layout.offset = value;
+        } else {
+            layout.align = value;
+        }
+    }
+
+    layout
+}
+
+/// The class of the buffer a constant is bound to by its attributes.
+pub fn buffer_class(hir_ctx: &hir::Context, def: Id<VariableDef>) -> Option<BufferClass> {
+    hir_ctx.variable_defs[def].attrs.iter().find_map(|attr| {
+        BufferClass::from_attribute(&hir_ctx.identifiers[hir_ctx.attributes[*attr].name])
+    })
+}
+
+/// Check that constants bound to buffers have types that can be stored in
+/// them and that explicit field layouts agree with the layout rules of the
+/// buffer.
+pub(crate) fn validate_buffers(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.consts {
+        let def = &hir_ctx.variable_defs[*id];
+        let sig = match ty_ctx.consts.get(&hir_ctx.identifiers[def.name]) {
+            Some(sig) if sig.const_id == *id => sig,
+            _ => continue,
+        };
+
+        for attr_id in &def.attrs {
+            let attr = &hir_ctx.attributes[*attr_id];
+            let class = match BufferClass::from_attribute(&hir_ctx.identifiers[attr.name]) {
+                Some(class) => class,
+                None => continue,
+            };
+
+            if let Some((problem, path)) = ty_ctx.buffer_type_problem(sig.type_, class) {
+                errs.push(Error::InvalidBufferType {
+                    name: hir_ctx.identifier_fcs[&def.name],
+                    type_: hir_ctx.type_ref_fcs[&def.type_],
+                    attribute: hir_ctx.attribute_fcs[attr_id],
+                    class,
+                    problem,
+                    field_path: path,
+                });
+                continue;
+            }
+
+            let rules = class.default_rules();
+            let matrices = ty_ctx.matrix_layout_of(*id);
+            for violation in ty_ctx.layout_violations(sig.type_, rules, matrices) {
+                let record = &hir_ctx.type_defs[ty_ctx.distinct_defs[&violation.distinct_id]];
+                let field = match &hir_ctx.type_def_rhss[record.rhs] {
+                    hir::TypeDefinitionRhs::Record { fields } => fields[violation.field],
+                    _ => continue,
+                };
+                let field = &hir_ctx.variable_defs[field];
+
+                // point at the attribute that causes the violation
+                let attr_name = match violation.kind {
+                    LayoutViolationKind::AlignTooSmall { .. } => "align",
+                    LayoutViolationKind::Overlap { .. }
+                    | LayoutViolationKind::Misaligned { .. } => "offset",
+                };
+                let layout_attr = field
+                    .attrs
+                    .iter()
+                    .rev()
+                    .find(|a| hir_ctx.identifiers[hir_ctx.attributes[**a].name] == attr_name)
+                    .map(|a| hir_ctx.attribute_fcs[a])
+                    .unwrap_or(hir_ctx.identifier_fcs[&field.name]);
+
+                errs.push(Error::FieldLayoutViolation {
+                    field_name: hir_ctx.identifiers[field.name].clone(),
+                    attribute: layout_attr,
+                    buffer: hir_ctx.type_ref_fcs[&def.type_],
+                    class,
+                    rules,
+                    kind: violation.kind,
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+/// Record the buffer constants and fields with a matrix layout attribute,
+/// attributes on other constants or on values without matrices are reported.
+pub(crate) fn collect_matrix_layouts(ty_ctx: &mut Context, hir_ctx: &hir::Context) -> Vec<Error> {
+    let mut errs = vec![];
+
+    let defs = ty_ctx
+        .references
+        .symbols()
+        .filter_map(|(sym, _)| match sym {
+            Symbol::Constant(def) | Symbol::Field { field: def, .. } => Some((sym, def)),
+            _ => None,
+        })
+        .collect::<Vec<(Symbol, Id<VariableDef>)>>();
+
+    for (sym, id) in defs {
+        let def = &hir_ctx.variable_defs[id];
+        let mut chosen = None;
+        for attr in &def.attrs {
+            let matrices = match MatrixLayout::from_attribute(
+                &hir_ctx.identifiers[hir_ctx.attributes[*attr].name],
+            ) {
+                Some(matrices) => matrices,
+                None => continue,
+            };
+            let attribute = hir_ctx.attribute_fcs[attr];
+            let mut error = |problem| {
+                errs.push(Error::InvalidMatrixLayout {
+                    name: hir_ctx.identifiers[def.name].clone(),
+                    attribute,
+                    problem,
+                })
+            };
+
+            if let Some(previous) = chosen {
+                error(MatrixLayoutProblem::Conflicting { previous });
+                continue;
+            }
+            if matches!(sym, Symbol::Constant(_)) && buffer_class(hir_ctx, id).is_none() {
+                error(MatrixLayoutProblem::NotABuffer);
+                continue;
+            }
+            // values whose type is unknown had an error before
+            let ty = match ty_ctx.references.symbol_type(sym) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            if !ty_ctx.has_matrices(ty) {
+                error(MatrixLayoutProblem::NoMatrix {
+                    type_name: ty_ctx.display_type(ty).to_string(),
+                });
+                continue;
+            }
+            chosen = Some(attribute);
+            ty_ctx.matrix_layouts.insert(id, matrices);
+        }
+    }
+
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecType;
+
+    fn record(ctx: &mut Context) -> TypeId {
+        let float = ctx.add_or_get_type(Type::Float);
+        let float3 = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: VecType::Unknown,
+            space: None,
+        });
+        let floats = ctx.add_or_get_type(Type::Array {
+            base: float,
+            size: 2,
+        });
+        let float3x3 = ctx.add_or_get_type(Type::FloatMat {
+            cols: VecSize::VS3,
+            rows: VecSize::VS3,
+            transform: None,
+        });
+        let fields = vec![
+            (ctx.types.intern_name("a"), float),
+            (ctx.types.intern_name("b"), float3),
+            (ctx.types.intern_name("c"), float),
+            (ctx.types.intern_name("d"), floats),
+            (ctx.types.intern_name("m"), float3x3),
+        ];
+        ctx.add_or_get_type(Type::Record { fields })
+    }
+
+    #[test]
+    fn record_offsets() {
+        let mut ctx = Context::default();
+        let ty = record(&mut ctx);
+
+        let std140 = ctx.layout(ty, LayoutRules::Std140).unwrap();
+        assert_eq!(std140.offsets, vec![0, 16, 28, 32, 64]);
+        assert_eq!((std140.size, std140.align), (112, 16));
+
+        let std430 = ctx.layout(ty, LayoutRules::Std430).unwrap();
+        assert_eq!(std430.offsets, vec![0, 16, 28, 32, 48]);
+        assert_eq!((std430.size, std430.align), (96, 16));
+
+        let scalar = ctx.layout(ty, LayoutRules::Scalar).unwrap();
+        assert_eq!(scalar.offsets, vec![0, 4, 16, 20, 28]);
+        assert_eq!((scalar.size, scalar.align), (64, 4));
+    }
+
+    #[test]
+    fn explicit_field_layouts() {
+        let mut ctx = Context::default();
+        let float = ctx.add_or_get_type(Type::Float);
+        let (a, b) = (ctx.types.intern_name("a"), ctx.types.intern_name("b"));
+        let inner = ctx.add_or_get_type(Type::Record {
+            fields: vec![(a, float), (b, float)],
+        });
+        let ty = ctx.add_or_get_type(Type::Distinct {
+            distinct_id: 0,
+            inner,
+        });
+        ctx.field_layouts.insert(
+            0,
+            vec![
+                FieldLayout::default(),
+                FieldLayout {
+                    offset: Some(32),
+                    align: Some(16),
+                    matrix: None,
+                },
+            ],
+        );
+
+        let layout = ctx.layout(ty, LayoutRules::Std430).unwrap();
+        assert_eq!(layout.offsets, vec![0, 32]);
+        assert_eq!((layout.size, layout.align), (48, 16));
+        let violations = |ctx: &Context| {
+            ctx.layout_violations(ty, LayoutRules::Std430, MatrixLayout::ColumnMajor)
+        };
+        assert!(violations(&ctx).is_empty());
+
+        ctx.field_layouts.get_mut(&0).unwrap()[1].offset = Some(2);
+        assert_eq!(
+            violations(&ctx),
+            vec![LayoutViolation {
+                distinct_id: 0,
+                field: 1,
+                kind: LayoutViolationKind::Overlap {
+                    offset: 2,
+                    previous: "a".to_string(),
+                    previous_end: 4,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn row_major_matrices() {
+        let mut ctx = Context::default();
+        // two columns of three rows
+        let float2x3 = ctx.add_or_get_type(Type::FloatMat {
+            cols: VecSize::VS2,
+            rows: VecSize::VS3,
+            transform: None,
+        });
+        let size = |ctx: &Context, rules, matrices| {
+            let layout = ctx.layout_as(float2x3, rules, matrices).unwrap();
+            (layout.size, layout.stride.unwrap())
+        };
+        assert_eq!(
+            size(&ctx, LayoutRules::Std140, MatrixLayout::ColumnMajor),
+            (32, 16)
+        );
+        assert_eq!(
+            size(&ctx, LayoutRules::Std140, MatrixLayout::RowMajor),
+            (48, 16)
+        );
+        assert_eq!(
+            size(&ctx, LayoutRules::Std430, MatrixLayout::ColumnMajor),
+            (32, 16)
+        );
+        assert_eq!(
+            size(&ctx, LayoutRules::Std430, MatrixLayout::RowMajor),
+            (24, 8)
+        );
+
+        let (a, m) = (ctx.types.intern_name("a"), ctx.types.intern_name("m"));
+        let inner = ctx.add_or_get_type(Type::Record {
+            fields: vec![(a, float2x3), (m, float2x3)],
+        });
+        let ty = ctx.add_or_get_type(Type::Distinct {
+            distinct_id: 0,
+            inner,
+        });
+        ctx.field_layouts.insert(
+            0,
+            vec![
+                FieldLayout::default(),
+                FieldLayout {
+                    matrix: Some(MatrixLayout::RowMajor),
+                    ..FieldLayout::default()
+                },
+            ],
+        );
+        let layout = ctx.layout(ty, LayoutRules::Std430).unwrap();
+        assert_eq!(layout.offsets, vec![0, 32]);
+        assert_eq!(layout.size, 64);
+        assert_eq!(
+            ctx.matrix_layouts_in(ty, MatrixLayout::ColumnMajor),
+            MatrixLayout::ALL.iter().copied().collect()
+        );
+
+        // the default of the module applies to fields without an attribute
+        ctx.matrix_layout = MatrixLayout::RowMajor;
+        let layout = ctx.layout(ty, LayoutRules::Std430).unwrap();
+        assert_eq!(layout.offsets, vec![0, 24]);
+    }
+
+    #[test]
+    fn runtime_sized_arrays() {
+        let mut ctx = Context::default();
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let uints = ctx.add_or_get_type(Type::OpenArray { base: uint });
+        let (len, data) = (ctx.types.intern_name("len"), ctx.types.intern_name("data"));
+
+        let last = ctx.add_or_get_type(Type::Record {
+            fields: vec![(len, uint), (data, uints)],
+        });
+        let layout = ctx.layout(last, LayoutRules::Std430).unwrap();
+        assert!(layout.runtime_sized);
+        assert_eq!((layout.size, layout.stride), (4, None));
+        assert_eq!(ctx.buffer_type_problem(last, BufferClass::Storage), None);
+        assert_eq!(
+            ctx.buffer_type_problem(last, BufferClass::Uniform),
+            Some((BufferTypeProblem::OpenArray, vec!["data".to_string()]))
+        );
+
+        let first = ctx.add_or_get_type(Type::Record {
+            fields: vec![(data, uints), (len, uint)],
+        });
+        assert_eq!(ctx.layout(first, LayoutRules::Std430), None);
+        assert_eq!(
+            ctx.buffer_type_problem(first, BufferClass::Storage),
+            Some((
+                BufferTypeProblem::OpenArrayNotLast,
+                vec!["data".to_string()]
+            ))
+        );
+    }
+}
@@ -2,17 +2,100 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::{Duration, Instant};
 
 use hir::{Expression, FileLocation, Function, Identifier, TypeDefinition, VariableDef};
 use thiol_hir::{self as hir, TypeReference};
 
-use bimap::BiBTreeMap;
 use id_arena::Id;
 
+pub mod angles;
+pub mod atomics;
+pub mod attributes;
+pub mod bindings;
+pub mod bounds;
+pub mod casing;
+pub mod casts;
+pub mod colours;
+pub mod composites;
+pub mod conflicts;
+pub mod confusables;
+pub mod consteval;
+pub mod debug_output;
+pub mod derives;
+pub mod destructuring;
+pub mod diagnostics;
+pub mod display;
+pub mod effects;
+pub mod floats;
+pub mod geometry;
+pub mod graphs;
+pub mod images;
+pub mod interner;
+pub mod interpolation;
+pub mod intrinsics;
+pub mod layout;
+pub mod lints;
+pub mod luts;
+pub mod matrices;
+pub mod mesh;
+pub mod mono;
+pub mod normalized;
+pub mod operators;
+pub mod optionals;
+pub mod overflow;
+pub mod params;
+pub mod precision;
+pub mod profile;
+pub mod profiling;
+pub mod ray_tracing;
+pub mod recursion;
+pub mod references;
+pub mod resolve;
+pub mod resources;
+pub mod shadowing;
+pub mod slices;
+pub mod spaces;
+pub mod stages;
+pub mod suggestions;
+pub mod tessellation;
+pub mod textures;
 pub mod types;
+pub mod uniformity;
+pub mod unify;
+pub mod varyings;
+pub mod vertex;
+pub use attributes::{AttributeTarget, KnownAttribute};
+pub use bindings::{Binding, BindingReservation, ResourceBinding};
+pub use bounds::BoundsCheck;
+pub use conflicts::{ItemKind, Namespaces};
+pub use consteval::DEFAULT_EVAL_STEP_LIMIT;
+pub use display::TypeDisplay;
+pub use effects::Effects;
+pub use floats::FloatMode;
+pub use graphs::{CallGraph, Callable, DependencyGraph, TypeGraph};
+pub use images::{ImageAccess, ImageDim, ImageFormat};
+pub use interner::{Name, TypeTable};
+pub use interpolation::{Interpolation, InterpolationMode};
+pub use intrinsics::Intrinsic;
+pub use layout::{BufferClass, Layout, LayoutRules, MatrixLayout};
+pub use lints::{LintGroup, LintLevel};
+pub use mesh::{MeshOutput, DEFAULT_MAX_MESH_PRIMITIVES, DEFAULT_MAX_MESH_VERTICES};
+pub use mono::{Instance, DEFAULT_INSTANTIATION_LIMIT};
+pub use overflow::IntegerOverflow;
+pub use profile::{Conversion, Feature, Profile};
+pub use references::{ReferenceIndex, Symbol};
+pub use resolve::{Resolution, ResolutionTable};
+pub use resources::ResourceUsage;
+pub use spaces::{SpaceCheck, SpaceSig, SpaceTransform, TransformStep};
+pub use stages::Stage;
+pub use textures::{SampledType, TextureDim, TextureResource};
 pub use types::*;
+pub use unify::Comparison;
+pub use vertex::{VertexFormat, VertexInput};
 
+#[derive(Debug)]
 pub enum Error {
     TypeRedefinition {
         previous_name: FileLocation,
@@ -32,11 +115,62 @@ pub enum Error {
     },
     MutuallyRecursiveTypeDefinitions {
         type_def_idents: Vec<FileLocation>,
+        /// one of the cycles, starting and ending in the first type
+        cycle: Vec<CycleEdge>,
+    },
+
+    /// A function calling itself
+    RecursiveFunction {
+        function_name: FileLocation,
+        recursive_calls: Vec<FileLocation>,
+    },
+    MutuallyRecursiveFunctions {
+        function_idents: Vec<FileLocation>,
+        /// one of the cycles, starting and ending in the first function
+        cycle: Vec<CycleEdge>,
+    },
+    /// A call that needs more instances of generic functions than the limit
+    InstantiationLimit {
+        function: Identifier,
+        limit: usize,
+        call: FileLocation,
+    },
+    /// The value of a constant has another type than the constant
+    ConstantTypeMismatch {
+        name: Identifier,
+        expected: String,
+        found: String,
+        value: FileLocation,
+        declared: FileLocation,
+    },
+    /// A static assertion whose condition is false
+    StaticAssertionFailed {
+        message: String,
+        condition: FileLocation,
+    },
+    /// An expression that is evaluated when compiling but has no value
+    ConstEvaluation {
+        expr: FileLocation,
+        problem: consteval::EvalProblem,
     },
 
     UndefinedType {
         name: String,
         uses: Vec<FileLocation>,
+        /// defined types with a similar name
+        suggestions: Vec<String>,
+    },
+    UndefinedVariable {
+        name: String,
+        uses: Vec<FileLocation>,
+        /// variables and constants in scope with a similar name
+        suggestions: Vec<String>,
+    },
+    UndefinedFunction {
+        name: String,
+        uses: Vec<FileLocation>,
+        /// functions and intrinsics with a similar name
+        suggestions: Vec<String>,
     },
 
     HigherKindedGenericTypeUsed {
@@ -68,6 +202,691 @@ pub enum Error {
         redefinition_name: FileLocation,
         redefinition_def: FileLocation,
     },
+    /// An item with the name of an item of another kind
+    NameConflict {
+        name: Identifier,
+        previous_kind: ItemKind,
+        previous: FileLocation,
+        kind: ItemKind,
+        conflict: FileLocation,
+    },
+
+    /// Two arguments of a call lead to different types for the same generic
+    /// parameter
+    ConflictingGenericArgument {
+        generic_name: Identifier,
+        arg: FileLocation,
+        arg_type: String,
+        previous_arg: FileLocation,
+        previous_type: String,
+    },
+    /// A generic parameter of the called function doesn't occur in any of its
+    /// argument types
+    UninferableGenericArgument {
+        generic_name: Identifier,
+        call: FileLocation,
+        generic: FileLocation,
+    },
+
+    /// A constant bound to a buffer has a type that can't be stored in it
+    InvalidBufferType {
+        name: FileLocation,
+        type_: FileLocation,
+        attribute: FileLocation,
+        class: BufferClass,
+        problem: layout::BufferTypeProblem,
+        /// fields leading to the part of the type that is the problem
+        field_path: Vec<Identifier>,
+    },
+    /// An `@offset(..)` or `@align(..)` attribute without a valid value
+    InvalidLayoutAttribute {
+        name: Identifier,
+        attribute: FileLocation,
+        problem: layout::LayoutAttributeProblem,
+    },
+    /// The explicit layout of a field in a buffer doesn't agree with the
+    /// layout rules of the buffer
+    FieldLayoutViolation {
+        field_name: Identifier,
+        attribute: FileLocation,
+        buffer: FileLocation,
+        class: BufferClass,
+        rules: LayoutRules,
+        kind: layout::LayoutViolationKind,
+    },
+    /// An atomic type in a value that is not stored in memory shared between
+    /// invocations
+    InvalidAtomicPlacement {
+        name: Identifier,
+        type_: FileLocation,
+        placement: atomics::AtomicPlacement,
+    },
+    /// Workgroup variables in a program that doesn't run in workgroups
+    WorkgroupOutsideCompute {
+        program_name: Identifier,
+        program: FileLocation,
+        var: FileLocation,
+        stage: Option<Stage>,
+    },
+    /// A barrier, or a function calling one, where not all invocations of a
+    /// workgroup may reach it
+    BarrierInNonUniformControlFlow {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is the barrier itself
+        intrinsic: bool,
+        reason: uniformity::NonUniformReason,
+    },
+    /// A derivative, or a function using one, called by a program that isn't a
+    /// fragment program
+    DerivativeOutsideFragment {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is the derivative itself
+        intrinsic: bool,
+        program: FileLocation,
+        stage: Option<Stage>,
+    },
+    /// `discard`, or a call of a function discarding, in a program that isn't
+    /// a fragment program
+    DiscardOutsideFragment {
+        /// the function called, `None` for the `discard` statement itself
+        callee: Option<Identifier>,
+        loc: FileLocation,
+        program: FileLocation,
+        stage: Option<Stage>,
+    },
+    /// `trace_ray`, or a function using it, called by a program whose stage
+    /// can't trace rays
+    TraceRayOutsideStage {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is `trace_ray` itself
+        intrinsic: bool,
+        program: FileLocation,
+        stage: Option<Stage>,
+    },
+    /// `emit_vertex` or `end_primitive`, or a function using them, called
+    /// by a program that is not a geometry program
+    EmissionOutsideGeometry {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is one of the intrinsics itself
+        intrinsic: bool,
+        program: FileLocation,
+        stage: Option<Stage>,
+    },
+    /// An argument for an `out` or `in out` parameter that can't be written to
+    ArgumentNotAssignable {
+        param_name: Identifier,
+        mode: hir::ParamMode,
+        arg: FileLocation,
+        param: FileLocation,
+        reason: params::NotAssignable,
+    },
+    /// A path out of a function on which an `out` parameter isn't assigned
+    OutParameterNotAssigned {
+        name: Identifier,
+        param: FileLocation,
+        /// the `return` statement or the end of the function
+        exit: FileLocation,
+    },
+    /// A use of an `out` parameter before it is assigned
+    OutParameterReadBeforeAssignment {
+        name: Identifier,
+        param: FileLocation,
+        use_: FileLocation,
+    },
+    /// A call of a function or intrinsic with side effects in the value of a
+    /// constant
+    ImpureCallInConstant {
+        constant: FileLocation,
+        callee: Identifier,
+        call: FileLocation,
+        effects: Effects,
+    },
+    /// Two buffers with the same explicit set and binding
+    BindingConflict {
+        binding: Binding,
+        previous: FileLocation,
+        attribute: FileLocation,
+    },
+    /// A feature used by the module that the target profile doesn't support
+    UnsupportedFeature {
+        feature: profile::Feature,
+        profile: Profile,
+        loc: FileLocation,
+    },
+    /// A `@relaxed` attribute on a value whose type can't be computed with
+    /// less precision
+    InvalidRelaxedPrecision {
+        name: Identifier,
+        attribute: FileLocation,
+        type_: FileLocation,
+        type_name: String,
+    },
+    /// A string literal outside of an attribute argument
+    StringLiteralAsValue { literal: FileLocation },
+    /// A literal index past the end of an array with a size
+    IndexOutOfBounds {
+        index: FileLocation,
+        value: i128,
+        len: usize,
+    },
+    /// A known attribute on a declaration it has no meaning for
+    MisplacedAttribute {
+        name: Identifier,
+        attribute: FileLocation,
+        target: AttributeTarget,
+        /// where the attribute can be used instead
+        allowed: &'static [AttributeTarget],
+    },
+    InvalidInterpolation {
+        name: Identifier,
+        attribute: FileLocation,
+        problem: interpolation::InterpolationProblem,
+    },
+    InvalidMatrixLayout {
+        name: Identifier,
+        attribute: FileLocation,
+        problem: layout::MatrixLayoutProblem,
+    },
+    InvalidMatrixConstructor {
+        constructor: FileLocation,
+        type_name: String,
+        problem: matrices::MatrixConstructorProblem,
+    },
+    InvalidSlice {
+        slice: FileLocation,
+        problem: slices::SliceProblem,
+    },
+    /// A vertex output and a fragment input with the same name that are
+    /// interpolated differently
+    InterpolationMismatch {
+        name: Identifier,
+        output: Interpolation,
+        output_loc: FileLocation,
+        input: Interpolation,
+        input_loc: FileLocation,
+    },
+
+    SpaceRedefinition {
+        previous_name: FileLocation,
+        redefinition_name: FileLocation,
+    },
+    /// A space that isn't declared, in a module that declares spaces
+    UndefinedSpace {
+        name: String,
+        uses: Vec<FileLocation>,
+        /// declared spaces with a similar name
+        suggestions: Vec<String>,
+    },
+    /// Spaces that are placed in each other
+    CyclicSpaceHierarchy {
+        space_names: Vec<FileLocation>,
+        /// where each space names the next one as its parent
+        parents: Vec<FileLocation>,
+        cycle: Vec<Identifier>,
+    },
+    /// The constant after `via` doesn't transform from the parent space
+    InvalidSpaceTransform {
+        space: Identifier,
+        via: FileLocation,
+        problem: spaces::SpaceTransformProblem,
+    },
+    /// A vector in one space used where a vector in another space is
+    /// expected
+    SpaceMismatch {
+        expr: FileLocation,
+        from: Identifier,
+        to: Identifier,
+        /// the transforms from one space to the other, if both are declared
+        /// in the same hierarchy
+        chain: Option<Vec<TransformStep>>,
+    },
+    /// A linear colour used where an sRGB encoded colour is expected, or the
+    /// other way around
+    ColourEncodingMismatch {
+        expr: FileLocation,
+        /// whether the value is sRGB encoded
+        srgb: bool,
+    },
+    /// Arithmetic on a linear and an sRGB encoded colour
+    MixedColourEncodings {
+        operation: FileLocation,
+        srgb: FileLocation,
+        linear: FileLocation,
+    },
+    /// `normalized<T>` of a type that isn't a vector of floating point
+    /// numbers
+    InvalidNormalizedType {
+        type_: FileLocation,
+        type_name: String,
+    },
+    /// A vector that isn't known to be normalized used where a normalized
+    /// vector is expected
+    NotNormalized {
+        expr: FileLocation,
+        /// the intrinsic the vector is an argument of
+        intrinsic: Option<Intrinsic>,
+    },
+    /// An angle used where an angle in the other unit or a `float` is
+    /// expected, or a `float` used as an angle
+    AngleUnitMismatch {
+        expr: FileLocation,
+        /// the unit of the value, `None` for a `float`
+        found: Option<angles::AngleUnit>,
+        /// the expected unit, `None` for a `float`
+        expected: Option<angles::AngleUnit>,
+        /// the intrinsic the value is an argument of
+        intrinsic: Option<Intrinsic>,
+    },
+    /// Arithmetic on an angle in radians and an angle in degrees
+    MixedAngleUnits {
+        operation: FileLocation,
+        radians: FileLocation,
+        degrees: FileLocation,
+    },
+    /// An image type with an unknown format or access, or an access the
+    /// format doesn't allow
+    InvalidImageType {
+        type_: FileLocation,
+        problem: images::ImageTypeProblem,
+    },
+    /// An image type that isn't the type of a constant bound as a storage
+    /// image
+    MisplacedImage { type_: FileLocation },
+    /// The first argument of `imageLoad` or `imageStore` isn't an image
+    NotAnImage {
+        expr: FileLocation,
+        intrinsic: Intrinsic,
+        type_name: String,
+    },
+    /// `imageLoad` of an image that is only written, or `imageStore` of an
+    /// image that is only read
+    ImageAccessViolation {
+        image: FileLocation,
+        intrinsic: Intrinsic,
+        access: images::ImageAccess,
+    },
+    /// Coordinates into an image that aren't integers with a component for
+    /// every dimension of the image
+    InvalidImageCoordinates {
+        coords: FileLocation,
+        found: String,
+        expected: &'static str,
+    },
+    /// A texel stored in an image that isn't of the type of the texels of the
+    /// image's format
+    TexelTypeMismatch {
+        texel: FileLocation,
+        found: String,
+        expected: String,
+        format: images::ImageFormat,
+    },
+    /// `imageStore`, or a function using it, called by a vertex program
+    ImageStoreInVertex {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is `imageStore` itself
+        intrinsic: bool,
+        program: FileLocation,
+    },
+    /// A texture of a type that isn't a scalar texels can have
+    InvalidTextureType {
+        type_: FileLocation,
+        type_name: String,
+    },
+    /// A texture or sampler type that isn't the type of a constant bound as
+    /// a uniform
+    MisplacedTexture { type_: FileLocation },
+    /// The first argument of a sampling intrinsic isn't a texture, or there
+    /// are no arguments
+    NotATexture {
+        expr: FileLocation,
+        intrinsic: Intrinsic,
+        /// the type of the first argument
+        type_name: Option<String>,
+    },
+    /// A sampling intrinsic that can't sample the texture
+    UnsupportedSampling {
+        texture: FileLocation,
+        intrinsic: Intrinsic,
+        type_name: String,
+        problem: textures::SamplingProblem,
+    },
+    /// A sampling intrinsic called with the wrong number of arguments for the
+    /// texture
+    SamplingArity {
+        call: FileLocation,
+        intrinsic: Intrinsic,
+        expected: usize,
+        found: usize,
+        signature: String,
+    },
+    /// An argument of a sampling intrinsic that doesn't fit its parameter
+    SamplingArgumentMismatch {
+        arg: FileLocation,
+        intrinsic: Intrinsic,
+        param: &'static str,
+        found: String,
+        expected: String,
+        signature: String,
+    },
+    /// An acceleration structure type that isn't the type of a constant
+    /// bound as a uniform
+    MisplacedAccelerationStructure { type_: FileLocation },
+    /// A call of `trace_ray` with the wrong number of arguments
+    TraceRayArity { call: FileLocation, found: usize },
+    /// An argument of `trace_ray` that doesn't fit its parameter
+    TraceRayArgumentMismatch {
+        arg: FileLocation,
+        param: &'static str,
+        found: String,
+        expected: &'static str,
+    },
+    /// A `RayPayload` attribute that isn't valid
+    InvalidRayPayload {
+        attribute: FileLocation,
+        problem: ray_tracing::PayloadProblem,
+    },
+    /// Payloads of two programs at the same location with different types
+    PayloadTypeMismatch {
+        location: u32,
+        found: String,
+        expected: String,
+        payload: FileLocation,
+        previous: FileLocation,
+    },
+    /// A payload location `trace_ray` is called with that a program
+    /// reaching the call has no payload at
+    UnknownPayloadLocation {
+        location: u32,
+        arg: FileLocation,
+        program_name: Identifier,
+        program: FileLocation,
+    },
+    /// An output of a program that isn't a valid mesh output
+    InvalidMeshOutput {
+        output: FileLocation,
+        problem: mesh::MeshOutputProblem,
+    },
+    /// Outputs of a mesh program with more vertices or primitives than the
+    /// target supports
+    MeshOutputLimit {
+        output: FileLocation,
+        per_vertex: bool,
+        length: usize,
+        limit: u32,
+    },
+    /// A mesh program without an output with the vertices of its primitives
+    MissingPrimitiveIndices {
+        program_name: Identifier,
+        program: FileLocation,
+    },
+    /// An attribute that only programs of one stage have, like `domain`,
+    /// that isn't valid
+    InvalidStageAttribute {
+        attribute: FileLocation,
+        /// the stage of the programs with the attribute
+        stage: Stage,
+        problem: stages::AttributeProblem,
+    },
+    /// A program without an attribute its stage needs
+    MissingStageAttribute {
+        program_name: Identifier,
+        program: FileLocation,
+        attribute: &'static str,
+    },
+    /// A `Patch` attribute on a variable that isn't passed from a
+    /// tessellation control to a tessellation evaluation program
+    InvalidPatch {
+        var: FileLocation,
+        stage: Option<Stage>,
+        output: bool,
+    },
+    /// A varying of a tessellation program that isn't an array with an
+    /// element per control point
+    ExpectedControlPointArray {
+        var: FileLocation,
+        found: String,
+        /// the number of control points, if the array needs that length
+        length: Option<u32>,
+    },
+    /// An input of a geometry program that isn't an array with an element
+    /// per vertex of its input primitive
+    ExpectedPrimitiveArray {
+        var: FileLocation,
+        found: String,
+        /// the number of vertices of the input primitive, if it is valid
+        length: Option<u32>,
+    },
+    /// `emit_vertex` reached from a geometry program without a `Position`
+    /// output
+    EmitWithoutPosition {
+        call: FileLocation,
+        program_name: Identifier,
+        program: FileLocation,
+    },
+    /// A call of `emit_vertex` emitting more vertices than the `max_vertices`
+    /// of the geometry program whenever it is reached
+    TooManyEmittedVertices {
+        call: FileLocation,
+        /// the vertices emitted with the call, at least
+        emitted: u32,
+        max: u32,
+        attribute: FileLocation,
+    },
+    /// An input and the output of the stage before it with the same name
+    /// whose types don't match
+    InterfaceMismatch {
+        name: Identifier,
+        output: String,
+        output_loc: FileLocation,
+        input: String,
+        input_loc: FileLocation,
+    },
+    /// A local variable, parameter or loop variable declared twice in the
+    /// same scope
+    LocalRedefinition {
+        name: Identifier,
+        previous: FileLocation,
+        redefinition: FileLocation,
+    },
+    /// A cast with `as`, or a `bitcast`, between types it can't convert
+    InvalidCast {
+        cast: FileLocation,
+        from: String,
+        to: String,
+        /// whether the conversion is a `bitcast`
+        bitcast: bool,
+        problem: casts::CastProblem,
+    },
+    /// A binary operator applied to operands of types it doesn't take, see
+    /// [`operators`]
+    OperandTypeMismatch {
+        /// the operator, like `+` or `<`
+        op: &'static str,
+        left: String,
+        left_loc: FileLocation,
+        right: String,
+        right_loc: FileLocation,
+    },
+    /// `-` or `+` of a value that isn't a number, vector or matrix
+    InvalidOperand {
+        op: &'static str,
+        operation: FileLocation,
+        ty: String,
+    },
+    /// A record constructor or an array whose values don't build it, see
+    /// [`composites`]
+    InvalidComposite {
+        value: FileLocation,
+        type_name: String,
+        problem: composites::CompositeProblem,
+    },
+    /// A `@derive` attribute on a type definition that can't derive the
+    /// function, see [`derives`]
+    InvalidDerive {
+        /// the attribute, or the name of the function in it
+        loc: FileLocation,
+        record: String,
+        problem: derives::DeriveProblem,
+    },
+    /// A call of a function derived from a record with invalid values
+    InvalidDerivedCall {
+        loc: FileLocation,
+        function: String,
+        problem: derives::DerivedCallProblem,
+    },
+    /// A destructuring assignment whose value isn't a record with a field for
+    /// each target, or with a target that can't be assigned its field
+    InvalidDestructuring {
+        loc: FileLocation,
+        problem: destructuring::DestructureProblem,
+    },
+    /// An optional type or value, or the handling of one, that is invalid,
+    /// see [`optionals`]
+    InvalidOptional {
+        loc: FileLocation,
+        problem: optionals::OptionalProblem,
+    },
+    /// A call of `assert` or `debug_print` that is invalid, see
+    /// [`debug_output`]
+    InvalidDebugOutput {
+        loc: FileLocation,
+        problem: debug_output::DebugProblem,
+    },
+    /// A `@lut` attribute on a function that can't have a lookup table, see
+    /// [`luts`]
+    InvalidLookupTable {
+        attribute: FileLocation,
+        function: String,
+        problem: luts::LutProblem,
+    },
+    /// A `@profile` attribute without a valid label, see [`profiling`]
+    InvalidProfileLabel {
+        attribute: FileLocation,
+        problem: profiling::ProfileProblem,
+    },
+    /// `select` with a mask that isn't a `bool` or a boolean vector with the
+    /// components of the values
+    InvalidSelectMask {
+        mask: FileLocation,
+        mask_type: String,
+        /// the components of a boolean vector mask
+        mask_components: Option<usize>,
+        value: String,
+        value_loc: FileLocation,
+    },
+    /// An integer literal whose value doesn't fit in the type it has, from
+    /// its suffix or from where it is used
+    IntegerLiteralOutOfRange {
+        /// the literal, with its minus sign if it is negated
+        literal: FileLocation,
+        value: i128,
+        ty: String,
+        min: i128,
+        max: i128,
+    },
+    /// A warning in a lint group that is denied
+    DeniedLint {
+        group: LintGroup,
+        warning: Box<Warning>,
+    },
+}
+
+/// Problems that don't prevent compilation but likely lead to wrong results
+#[derive(Debug, Clone)]
+pub enum Warning {
+    /// A derivative, or a function using one, where neighbouring fragments
+    /// may not execute it, which makes its result undefined
+    DerivativeInNonUniformControlFlow {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is the derivative itself
+        intrinsic: bool,
+        reason: uniformity::NonUniformReason,
+    },
+    /// A subgroup operation, or a function using one, that some invocations
+    /// of the subgroup may not reach, which leaves them out of its result
+    SubgroupInNonUniformControlFlow {
+        callee: Identifier,
+        call: FileLocation,
+        /// whether the callee is the subgroup operation itself
+        intrinsic: bool,
+        reason: uniformity::NonUniformReason,
+    },
+    /// An attribute the compiler doesn't know, which it ignores
+    UnknownAttribute {
+        name: Identifier,
+        attribute: FileLocation,
+        /// known attributes with a similar name
+        suggestions: Vec<String>,
+    },
+    /// A declaration that hides another declaration with the same name
+    Shadowing {
+        name: Identifier,
+        declaration: FileLocation,
+        shadowed: shadowing::Shadowed,
+    },
+    /// A name that differs only in case from a name in the same namespace
+    CaseCollision {
+        /// what the name declares, like `field` or `function`
+        kind: &'static str,
+        name: Identifier,
+        collision: FileLocation,
+        previous_name: Identifier,
+        previous: FileLocation,
+    },
+    /// A name that looks like a name spelled with other characters in the
+    /// module
+    ConfusableName {
+        /// what the name declares, like `field` or `function`
+        kind: &'static str,
+        name: Identifier,
+        confusable: FileLocation,
+        previous_name: Identifier,
+        previous: FileLocation,
+    },
+    /// Integer arithmetic whose overflow is left to the target
+    ImplicitOverflow {
+        /// the operator, `+`, `-` or `*`
+        op: &'static str,
+        operation: FileLocation,
+        ty: String,
+    },
+    /// A comparison of floats with `=` or `<>`, whose result depends on the
+    /// rounding of the operands
+    FloatEquality {
+        /// the operator, `=` or `<>`
+        op: &'static str,
+        comparison: FileLocation,
+        ty: String,
+    },
+    /// A cast with `as` to a type that can't represent every value of the
+    /// cast type
+    LossyCast {
+        cast: FileLocation,
+        from: String,
+        to: String,
+    },
+    /// A name that is reserved for a future keyword, written without `r#`
+    ReservedWord {
+        word: Identifier,
+        name: FileLocation,
+    },
+}
+
+/// A use of one type by another, or a call of one function by another, in a
+/// cycle of type definitions
+#[derive(Debug, Clone)]
+pub struct CycleEdge {
+    pub user: Identifier,
+    pub user_name: FileLocation,
+    pub used: Identifier,
+    /// location of the use in the definition of `user`
+    pub use_loc: FileLocation,
 }
 
 pub fn type_check(
@@ -75,20 +894,158 @@ pub fn type_check(
     hir_ctx: &hir::Context,
     module: &hir::Module,
 ) -> Result<(), Vec<Error>> {
-    process_type_definitions(module, ty_ctx, hir_ctx)?;
+    ty_ctx.phase_timings.clear();
+    let mut timer = PhaseTimer(Instant::now());
+
+    // undefined names are reported by the resolver, before and apart from the
+    // errors of the other phases
+    let (resolutions, mut undefined) = resolve::resolve_names(module, hir_ctx);
+    ty_ctx.resolutions = resolutions;
+    timer.lap(ty_ctx, "name resolution");
+
+    // every phase runs even if a previous one failed, items with errors are
+    // poisoned so that their uses don't lead to follow-up errors
+    let mut errs = process_type_definitions(module, ty_ctx, hir_ctx);
+    timer.lap(ty_ctx, "type definitions");
+    errs.extend(add_function_signatures(module, ty_ctx, hir_ctx));
+    errs.extend(derives::add_derived_functions(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "function signatures");
+    errs.extend(add_constants(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "constants");
+    errs.extend(conflicts::check_conflicts(module, ty_ctx, hir_ctx));
+    errs.extend(spaces::check_spaces(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "spaces");
+    errs.extend(layout::validate_buffers(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "buffer layouts");
+
+    ty_ctx.call_graph = graphs::call_graph(hir_ctx, module);
+    errs.extend(recursion::check_recursion(ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "call graph");
+
+    // the index is useful for tooling even if the module has errors, indexing
+    // also infers the generic arguments of calls
+    let (references, call_errs) = references::index_references(ty_ctx, hir_ctx, module);
+    ty_ctx.references = references;
+    errs.extend(call_errs);
+    errs.extend(overflow::check_literal_ranges(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "references");
+    effects::infer_effects(module, ty_ctx, hir_ctx);
+    errs.extend(effects::check_constants(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "effects");
+    errs.extend(bindings::assign_bindings(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "bindings");
+    errs.extend(precision::collect_relaxed_precision(ty_ctx, hir_ctx));
+    errs.extend(layout::collect_matrix_layouts(ty_ctx, hir_ctx));
+    floats::collect_float_modes(module, ty_ctx, hir_ctx);
+    errs.extend(profiling::collect_profile_labels(module, ty_ctx, hir_ctx));
+    errs.extend(atomics::validate_atomic_placement(module, ty_ctx, hir_ctx));
+    errs.extend(images::validate_image_placement(module, ty_ctx, hir_ctx));
+    errs.extend(textures::validate_texture_placement(
+        module, ty_ctx, hir_ctx,
+    ));
+    errs.extend(ray_tracing::validate_acceleration_structure_placement(
+        module, ty_ctx, hir_ctx,
+    ));
+    errs.extend(stages::validate_stages(module, hir_ctx));
+    errs.extend(interpolation::check_interpolation(module, ty_ctx, hir_ctx));
+    errs.extend(profile::check_profile(module, ty_ctx, hir_ctx));
+    errs.extend(stages::validate_derivatives(module, ty_ctx, hir_ctx));
+    errs.extend(stages::validate_discards(module, ty_ctx, hir_ctx));
+    errs.extend(stages::validate_image_stores(module, ty_ctx, hir_ctx));
+    errs.extend(stages::validate_trace_rays(module, ty_ctx, hir_ctx));
+    errs.extend(stages::validate_primitive_emission(module, ty_ctx, hir_ctx));
+    errs.extend(ray_tracing::validate_payloads(module, ty_ctx, hir_ctx));
+    errs.extend(mesh::validate_mesh_outputs(module, ty_ctx, hir_ctx));
+    errs.extend(stages::validate_stage_attributes(module, hir_ctx));
+    errs.extend(tessellation::validate_tessellation(module, ty_ctx, hir_ctx));
+    errs.extend(geometry::validate_geometry(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "targets");
+    errs.extend(params::check_arguments(module, ty_ctx, hir_ctx));
+    errs.extend(params::check_out_parameters(module, ty_ctx, hir_ctx));
+    errs.extend(destructuring::check_destructuring(module, ty_ctx, hir_ctx));
+    errs.extend(optionals::check_matches(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "arguments");
+    // instances are only collected from modules without errors, where every
+    // call of a generic function has its generic arguments
+    if undefined.is_empty() && errs.is_empty() {
+        let (instances, instance_errs) = mono::collect_instances(module, ty_ctx, hir_ctx);
+        ty_ctx.instances = instances;
+        errs.extend(instance_errs);
+        timer.lap(ty_ctx, "instances");
+    }
+    // static assertions and the constants emitted as their values are
+    // evaluated in modules without errors, where the types of all
+    // expressions are known
+    if undefined.is_empty() && errs.is_empty() {
+        errs.extend(consteval::check_static_asserts(module, ty_ctx, hir_ctx));
+        errs.extend(consteval::check_evaluated_constants(
+            module, ty_ctx, hir_ctx,
+        ));
+        let (tables, lut_errs) = luts::build_lookup_tables(module, ty_ctx, hir_ctx);
+        ty_ctx.lookup_tables = tables;
+        errs.extend(lut_errs);
+        timer.lap(ty_ctx, "static assertions");
+    }
+    let (uniformity_errs, mut warnings) = uniformity::check_uniformity(module, ty_ctx, hir_ctx);
+    errs.extend(uniformity_errs);
+    timer.lap(ty_ctx, "uniformity");
+    let (attribute_errs, attribute_warnings) =
+        attributes::check_attributes(module, ty_ctx, hir_ctx);
+    errs.extend(attribute_errs);
+    warnings.extend(attribute_warnings);
+    timer.lap(ty_ctx, "attributes");
+    let (shadowing_errs, shadowing_warnings) = shadowing::check_shadowing(module, hir_ctx);
+    errs.extend(shadowing_errs);
+    warnings.extend(shadowing_warnings);
+    warnings.extend(casing::check_case_collisions(module, ty_ctx, hir_ctx));
+    warnings.extend(confusables::check_confusables(module, ty_ctx, hir_ctx));
+    warnings.extend(
+        hir_ctx
+            .reserved_words
+            .iter()
+            .map(|(word, name)| Warning::ReservedWord {
+                word: word.clone(),
+                name: *name,
+            }),
+    );
+    timer.lap(ty_ctx, "shadowing");
+    warnings.extend(overflow::check_overflow(module, ty_ctx, hir_ctx));
+    warnings.extend(floats::check_float_equality(module, ty_ctx, hir_ctx));
+    warnings.extend(casts::check_lossy_casts(module, ty_ctx, hir_ctx));
+    timer.lap(ty_ctx, "arithmetic");
+
+    warnings.sort_by_key(Warning::location);
+    let (warnings, denied) = lints::apply_levels(ty_ctx, warnings);
+    ty_ctx.warnings = warnings;
+    errs.extend(denied);
+
+    // the phases still run into undefined types to poison the items using
+    // them, those were reported by the resolver already
+    errs.retain(|err| !matches!(err, Error::UndefinedType { .. }));
+    if undefined.is_empty() && errs.is_empty() {
+        return Ok(());
+    }
 
-    add_function_signatures(module, ty_ctx, hir_ctx)?;
+    // errors are found in the order of the phases and of hash map or graph
+    // traversals, report them in source order instead
+    undefined.sort_by_key(Error::location);
+    errs.sort_by_key(Error::location);
+    undefined.extend(errs);
+    Err(undefined)
+}
 
-    add_constants(module, ty_ctx, hir_ctx)?;
+/// Measures the time between the ends of the phases of a type check.
+struct PhaseTimer(Instant);
 
-    Ok(())
+impl PhaseTimer {
+    fn lap(&mut self, ty_ctx: &mut Context, phase: &'static str) {
+        let now = Instant::now();
+        ty_ctx.phase_timings.push((phase, now - self.0));
+        self.0 = now;
+    }
 }
 
-fn add_constants(
-    module: &hir::Module,
-    ty_ctx: &mut Context,
-    hir_ctx: &hir::Context,
-) -> Result<(), Vec<Error>> {
+fn add_constants(module: &hir::Module, ty_ctx: &mut Context, hir_ctx: &hir::Context) -> Vec<Error> {
     let mut errs = vec![];
 
     for c in &module.consts {
@@ -97,148 +1054,322 @@ fn add_constants(
         }
     }
 
-    if errs.is_empty() {
-        Ok(())
-    } else {
-        Err(errs)
-    }
+    errs
 }
 
 fn add_function_signatures(
     module: &hir::Module,
     ty_ctx: &mut Context,
     hir_ctx: &hir::Context,
-) -> Result<(), Vec<Error>> {
+) -> Vec<Error> {
     let mut errors = vec![];
     for func in &module.functions {
-        if let Err(err) = ty_ctx.add_function_signature(hir_ctx, *func) {
-            errors.push(err);
+        if let Err(errs) = ty_ctx.add_function_signature(hir_ctx, *func) {
+            errors.extend(errs);
         }
     }
 
-    if !errors.is_empty() {
-        Err(errors)
-    } else {
-        Ok(())
-    }
+    errors
 }
 
 fn process_type_definitions(
     module: &hir::Module,
     ty_ctx: &mut Context,
     hir_ctx: &hir::Context,
-) -> Result<(), Vec<Error>> {
+) -> Vec<Error> {
     let mut errs = vec![];
+    ty_ctx.type_order.clear();
 
-    // sort type definitions by dependency
-    let mut tyname_to_node = HashMap::new();
-    let mut g =
-        petgraph::graph::Graph::<Option<Id<TypeDefinition>>, petgraph::graph::NodeIndex>::new();
+    // sort type definitions by dependency, the names are interned so that
+    // looking up the uses doesn't copy them
+    let mut tyname_to_def = HashMap::new();
+    let mut g = TypeGraph::default();
 
     let mut deps = HashMap::new();
 
+    // type definitions that can't be added, either because they are
+    // redefinitions or because they have errors themselves
+    let mut redefinitions = HashSet::new();
+    let mut poisoned = HashSet::new();
+
     for ty in &module.types {
         let ty_def = &hir_ctx.type_defs[*ty];
         let ty_name = &hir_ctx.identifiers[ty_def.name];
 
-        let node = g.add_node(Some(*ty));
+        g.add_node(*ty);
 
-        // definition with the same name
-        if let Some(prev_idx) = tyname_to_node.insert(ty_name.clone(), node) {
-            let prev_id = g[prev_idx].unwrap();
-            let prev_def = &hir_ctx.type_defs[prev_id];
+        // definition with the same name, the first definition is kept
+        let symbol = ty_ctx.types.intern_name(ty_name);
+        if let Some(prev_id) = tyname_to_def.get(&symbol) {
+            let prev_def = &hir_ctx.type_defs[*prev_id];
 
             errs.push(Error::TypeRedefinition {
                 previous_name: hir_ctx.identifier_fcs[&prev_def.name],
                 redefinition_name: hir_ctx.identifier_fcs[&ty_def.name],
                 redefinition: hir_ctx.type_def_fcs[ty],
             });
+            redefinitions.insert(*ty);
             continue;
         }
+        tyname_to_def.insert(symbol, *ty);
+    }
+    let type_name =
+        |id: Id<TypeDefinition>| hir_ctx.identifiers[hir_ctx.type_defs[id].name].as_str();
+
+    for id in tyname_to_def.values() {
+        let params = phantom_params(hir_ctx, &hir_ctx.type_defs[*id]);
+        ty_ctx
+            .phantom_params
+            .insert(type_name(*id).to_string(), params);
     }
 
     for ty in &module.types {
+        if redefinitions.contains(ty) {
+            continue;
+        }
+
         let def = &hir_ctx.type_defs[*ty];
         let ty_name = &hir_ctx.identifiers[def.name];
-        if let Err(err) = type_def_deps(hir_ctx, def, &mut deps) {
+        if let Err(err) = type_def_deps(hir_ctx, def, &ty_ctx.phantom_params, &mut deps) {
             errs.push(err);
+            poisoned.insert(*ty);
+            deps.clear();
             continue;
         }
 
-        if let Some(usages) = deps.get(ty_name.as_str()) {
+        let self_uses = deps
+            .remove(ty_name.as_str())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, kind)| *kind == UseKind::Size)
+            .map(|(loc, _)| loc)
+            .collect::<Vec<_>>();
+        if !self_uses.is_empty() {
             errs.push(Error::RecursiveTypeDefinition {
                 type_def: hir_ctx.type_def_fcs[ty],
                 type_name: hir_ctx.identifier_fcs[&def.name],
-                recurive_usages: usages.clone(),
+                recurive_usages: self_uses,
             });
-            continue;
+            poisoned.insert(*ty);
         }
 
-        let self_node = tyname_to_node[ty_name];
-
-        for (name, uses) in deps.drain() {
-            if let Some(id) = tyname_to_node.get(name) {
-                g.add_edge(self_node, *id, Default::default());
+        // in order of first use, so that the graph doesn't depend on hashing
+        let mut ty_deps = deps.drain().collect::<Vec<_>>();
+        ty_deps.sort_by_key(|(_, uses)| uses[0].0);
+
+        for (name, uses) in ty_deps {
+            let used = ty_ctx
+                .types
+                .find_name(name)
+                .and_then(|symbol| tyname_to_def.get(&symbol));
+            if let Some(id) = used {
+                // only uses that affect the size can lead to infinite types
+                let size_uses = uses
+                    .into_iter()
+                    .filter(|(_, kind)| *kind == UseKind::Size)
+                    .map(|(loc, _)| loc)
+                    .collect::<Vec<_>>();
+                if !size_uses.is_empty() {
+                    g.add_uses(*ty, *id, size_uses);
+                }
             } else {
+                let uses = uses.into_iter().map(|(loc, _)| loc).collect();
+                let candidates = tyname_to_def.values().map(|id| type_name(*id)).chain(
+                    def.generics
+                        .iter()
+                        .map(|id| hir_ctx.identifiers[*id].as_str()),
+                );
                 errs.push(Error::UndefinedType {
                     name: name.to_string(),
                     uses,
+                    suggestions: suggestions::similar_names(name, candidates),
                 });
-                continue;
+                poisoned.insert(*ty);
             };
         }
     }
 
-    if !errs.is_empty() {
-        return Err(errs);
-    }
-
-    let groups = petgraph::algo::tarjan_scc(&g);
+    let groups = g.strongly_connected_components();
 
     for group in groups {
         if group.len() > 1 {
+            let name_loc =
+                |id: Id<TypeDefinition>| hir_ctx.identifier_fcs[&hir_ctx.type_defs[id].name];
+
+            let mut type_def_idents = group.iter().map(|id| name_loc(*id)).collect::<Vec<_>>();
+            type_def_idents.sort();
+
+            // the cycle starts at the type that comes first in the source
+            let first = *group.iter().min_by_key(|id| name_loc(**id)).unwrap();
+            let cycle = g
+                .find_cycle(first)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(user, used, uses)| CycleEdge {
+                    user: type_name(user).to_string(),
+                    user_name: name_loc(user),
+                    used: type_name(used).to_string(),
+                    use_loc: uses[0],
+                })
+                .collect();
+
             errs.push(Error::MutuallyRecursiveTypeDefinitions {
-                type_def_idents: group
-                    .into_iter()
-                    .map(|id| {
-                        let id = g[id].unwrap();
-                        hir_ctx.identifier_fcs[&hir_ctx.type_defs[id].name]
-                    })
-                    .collect(),
+                type_def_idents,
+                cycle,
             });
+            for id in group {
+                poisoned.insert(id);
+                ty_ctx.poison_type(hir_ctx, id);
+            }
             continue;
         }
 
         debug_assert_eq!(group.len(), 1);
 
-        let id = g[group[0]].unwrap();
+        let id = group[0];
 
-        if let Err(errors) = ty_ctx.add_type_definition(hir_ctx, id) {
-            errs.extend(errors);
+        if redefinitions.contains(&id) {
+            continue;
         }
-    }
 
-    if errs.is_empty() {
-        Ok(())
-    } else {
-        Err(errs)
+        // dependencies come before their users, so they are already poisoned
+        let poisoned_dep = g
+            .dependencies(id)
+            .iter()
+            .any(|(dep, _)| poisoned.contains(dep));
+
+        if poisoned.contains(&id) || poisoned_dep {
+            poisoned.insert(id);
+            ty_ctx.poison_type(hir_ctx, id);
+            continue;
+        }
+
+        match ty_ctx.add_type_definition(hir_ctx, id) {
+            Ok(_) => ty_ctx.type_order.push(id),
+            Err(errors) => {
+                errs.extend(errors);
+                poisoned.insert(id);
+                ty_ctx.poison_type(hir_ctx, id);
+            }
+        }
     }
+
+    ty_ctx.type_graph = g;
+    errs
 }
 
 #[derive(Default, Clone)]
 pub struct Context {
     pub defs: BTreeMap<Identifier, Id<TypeDefinition>>,
     pub generic_distinct_ids: BTreeMap<Identifier, usize>,
+    /// the generic arguments of every instance of a generic record
+    pub generic_args: BTreeMap<TypeId, Vec<TypeId>>,
+    /// for every declared type, which of its generic parameters are phantom,
+    /// meaning that they don't occur in the definition. Arguments for them
+    /// don't affect the size of the type, so they can refer to types that
+    /// depend on the type itself
+    pub phantom_params: BTreeMap<Identifier, Vec<bool>>,
 
     // generic types are *incomplete* before they are applied, but
     // non generic types will be able to be mapped directly to a type
     pub complete_types: BTreeMap<Identifier, TypeId>,
 
-    pub types: BiBTreeMap<Type, TypeId>,
+    pub types: TypeTable,
     pub distinct_counter: usize,
+    pub var_counter: usize,
+    /// the type definition that introduced each distinct id
+    pub distinct_defs: BTreeMap<usize, Id<TypeDefinition>>,
+    /// explicit layouts of the fields of record types, by distinct id
+    pub field_layouts: BTreeMap<usize, Vec<layout::FieldLayout>>,
 
     pub function_sigs: BTreeMap<Identifier, FunctionSig>,
+    /// the signatures of the functions derived from records, see [`derives`]
+    pub derived_functions: BTreeMap<Identifier, derives::DerivedFunction>,
     pub consts: BTreeMap<Identifier, ConstantSig>,
+    /// the declared coordinate spaces
+    pub spaces: BTreeMap<Identifier, SpaceSig>,
+
+    /// items that have errors or depend on items with errors, uses of them are
+    /// not reported again
+    pub poisoned_types: BTreeMap<Identifier, Id<TypeDefinition>>,
+    pub poisoned_functions: BTreeMap<Identifier, Id<Function>>,
+    pub poisoned_consts: BTreeMap<Identifier, Id<VariableDef>>,
+
+    /// dependencies between the type definitions of the module
+    pub type_graph: TypeGraph,
+    /// the type definitions without errors, dependencies before their users
+    pub type_order: Vec<Id<TypeDefinition>>,
+    /// calls between the functions and programs of the module
+    pub call_graph: CallGraph,
+
+    /// what the uses of names in the module refer to
+    pub resolutions: ResolutionTable,
+    pub references: ReferenceIndex,
+    /// types of expressions in function and program bodies, as far as they are known
+    pub expr_types: HashMap<Id<Expression>, TypeId>,
+    /// the types asked about by `sizeof`, `alignof` and `offsetof`
+    pub layout_types: HashMap<Id<Expression>, TypeId>,
+    /// inferred generic arguments of calls to generic functions, arguments
+    /// that couldn't be inferred are the error type
+    pub call_generics: HashMap<Id<Expression>, Vec<TypeId>>,
+    /// calls that resolved to an intrinsic instead of a function of the module
+    pub call_intrinsics: HashMap<Id<Expression>, Intrinsic>,
+
+    /// the target the module is compiled for
+    pub profile: Profile,
+    /// constants, fields and variables that may be computed with less precision
+    pub relaxed_precision: BTreeSet<Id<VariableDef>>,
+    /// the layout of matrices in buffers without a matrix layout attribute
+    pub matrix_layout: MatrixLayout,
+    /// buffer constants and fields with a matrix layout attribute
+    pub matrix_layouts: BTreeMap<Id<VariableDef>, MatrixLayout>,
+    /// functions and programs whose floating point arithmetic doesn't follow
+    /// IEEE 754 strictly
+    pub float_modes: BTreeMap<Callable, FloatMode>,
+    /// the functions with a `@lut` attribute and the values of their entries
+    pub lookup_tables: BTreeMap<Id<Function>, luts::LookupTable>,
+    /// the labels of the functions and programs with a `@profile` attribute
+    pub profile_labels: BTreeMap<Callable, String>,
+    /// how the backends check indices that may be out of bounds
+    pub bounds_check: BoundsCheck,
+    /// what plain `+`, `-` and `*` on integers do when they overflow
+    pub integer_overflow: IntegerOverflow,
+    /// whether assertions and debug prints are left out of the generated
+    /// code
+    pub release: bool,
+    /// what the checker does with vectors used in the wrong space
+    pub space_check: SpaceCheck,
+    /// whether items of different kinds can have the same name
+    pub namespaces: Namespaces,
+    /// the most instances of generic functions, [`DEFAULT_INSTANTIATION_LIMIT`]
+    /// if not set
+    pub instantiation_limit: Option<usize>,
+    /// the most statements evaluating a call when compiling runs,
+    /// [`DEFAULT_EVAL_STEP_LIMIT`] if not set
+    pub eval_step_limit: Option<usize>,
+    /// the most vertices a mesh program can output,
+    /// [`DEFAULT_MAX_MESH_VERTICES`] if not set
+    pub max_mesh_vertices: Option<u32>,
+    /// the most primitives a mesh program can output,
+    /// [`DEFAULT_MAX_MESH_PRIMITIVES`] if not set
+    pub max_mesh_primitives: Option<u32>,
+    /// the instances of generic functions the backends emit
+    pub instances: Vec<Instance>,
+    /// transforms applied to vectors used in another space
+    pub space_transforms: HashMap<Id<Expression>, SpaceTransform>,
+    /// the interpolation of the varyings of vertex and fragment programs
+    pub interpolation: BTreeMap<Id<VariableDef>, Interpolation>,
+    /// bindings that are not assigned to buffers without an explicit binding
+    pub binding_reservations: Vec<BindingReservation>,
+    /// the bindings of the uniform and storage buffers
+    pub bindings: BTreeMap<Id<VariableDef>, ResourceBinding>,
+    /// the level of lint groups, groups that aren't in the map have their
+    /// default level
+    pub lint_levels: BTreeMap<LintGroup, LintLevel>,
+    /// warnings found by the last type check, in source order
+    pub warnings: Vec<Warning>,
+    /// how long each phase of the last type check took, in the order they ran
+    pub phase_timings: Vec<(&'static str, Duration)>,
 }
 
 impl Context {
@@ -248,101 +1379,124 @@ impl Context {
         id: Id<TypeReference>,
         subst: &HashMap<&str, TypeId>,
     ) -> Result<TypeId, Error> {
-        use hir::PrimitiveType as PT;
         use TypeReference as TR;
 
         let ty_ref = &ctx.type_refs[id];
 
         let ty = match ty_ref {
-            TR::Primitive(prim) => match prim {
-                PT::Bool => Type::Bool,
-                PT::Int => Type::Int,
-                PT::UInt => Type::UInt,
-                PT::Float => Type::Float,
-                PT::Double => Type::Double,
-                PT::BoolVec { components } => Type::BoolVec {
-                    components: (*components).into(),
-                },
-                PT::IntVec {
-                    components,
-                    vtype,
-                    space,
-                } => Type::IntVec {
-                    components: (*components).into(),
-                    vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
-                    space: space.map(|id| ctx.identifiers[id].clone()),
-                },
-                PT::UIntVec {
-                    components,
-                    vtype,
-                    space,
-                } => Type::UIntVec {
-                    components: (*components).into(),
-                    vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
-                    space: space.map(|id| ctx.identifiers[id].clone()),
-                },
-                PT::FloatVec {
-                    components,
-                    vtype,
-                    space,
-                } => Type::FloatVec {
-                    components: (*components).into(),
-                    vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
-                    space: space.map(|id| ctx.identifiers[id].clone()),
-                },
-                PT::DoubleVec {
-                    components,
-                    vtype,
-                    space,
-                } => Type::DoubleVec {
-                    components: (*components).into(),
-                    vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
-                    space: space.map(|id| ctx.identifiers[id].clone()),
-                },
-                PT::FloatMat {
-                    cols,
-                    rows,
-                    transform,
-                } => Type::FloatMat {
-                    cols: (*cols).into(),
-                    rows: (*rows).into(),
-                    transform: transform.map(|(from, to)| {
-                        (ctx.identifiers[from].clone(), ctx.identifiers[to].clone())
-                    }),
-                },
-                PT::DoubleMat {
-                    cols,
-                    rows,
-                    transform,
-                } => Type::DoubleMat {
-                    cols: (*cols).into(),
-                    rows: (*rows).into(),
-                    transform: transform.map(|(from, to)| {
-                        (ctx.identifiers[from].clone(), ctx.identifiers[to].clone())
-                    }),
-                },
-            },
+            TR::Primitive(prim) => self.primitive_type(ctx, prim),
             TR::OpenArray(inner) => {
                 let inner_id = self.ty_ref(ctx, *inner, subst)?;
                 Type::OpenArray { base: inner_id }
             }
             TR::Array { base, size } => {
                 let inner_id = self.ty_ref(ctx, *base, subst)?;
+                let size = match size {
+                    hir::ArraySize::Literal(size) => *size,
+                    hir::ArraySize::Expression(expr) => self.array_size(ctx, *expr, subst)?,
+                };
                 Type::Array {
                     base: inner_id,
-                    size: *size,
+                    size,
+                }
+            }
+            TR::Image {
+                dim,
+                format,
+                access,
+            } => {
+                return self
+                    .image_type(
+                        (*dim).into(),
+                        &ctx.identifiers[*format],
+                        &ctx.identifiers[*access],
+                    )
+                    .map_err(|problem| Error::InvalidImageType {
+                        type_: ctx.type_ref_fcs[&id],
+                        problem,
+                    });
+            }
+            TR::Texture { dim, sampled } => {
+                let sampled = match sampled {
+                    Some(sampled) => *sampled,
+                    None => {
+                        return Ok(self.add_or_get_type(Type::Texture {
+                            dim: (*dim).into(),
+                            sampled: SampledType::Depth,
+                        }))
+                    }
+                };
+                let inner = self.ty_ref(ctx, sampled, subst)?;
+                return self.texture_type((*dim).into(), inner).ok_or_else(|| {
+                    Error::InvalidTextureType {
+                        type_: ctx.type_ref_fcs[&sampled],
+                        type_name: self.display_type(inner).to_string(),
+                    }
+                });
+            }
+            TR::Sampler { comparison } => Type::Sampler {
+                comparison: *comparison,
+            },
+            TR::AccelerationStructure => Type::AccelerationStructure,
+            TR::Record { fields } => {
+                let mut names = HashMap::new();
+                let mut record_fields = Vec::with_capacity(fields.len());
+                for (name, ty) in fields {
+                    let name_s = &ctx.identifiers[*name];
+                    let loc = ctx.identifier_fcs[name];
+                    if let Some(prev) = names.insert(name_s.as_str(), loc) {
+                        return Err(Error::FieldRedefinition {
+                            item: ctx.type_ref_fcs[&id],
+                            previous_name: prev,
+                            redefinition_name: loc,
+                        });
+                    }
+                    let field_ty = self.ty_ref(ctx, *ty, subst)?;
+                    record_fields.push((self.types.intern_name(name_s), field_ty));
+                }
+                // records without a name are the same type wherever they
+                // have the same fields
+                Type::Record {
+                    fields: record_fields,
                 }
             }
+            TR::Normalized(base) => {
+                let inner = self.ty_ref(ctx, *base, subst)?;
+                return self
+                    .normalized_type(inner)
+                    .ok_or_else(|| Error::InvalidNormalizedType {
+                        type_: ctx.type_ref_fcs[&id],
+                        type_name: self.display_type(inner).to_string(),
+                    });
+            }
+            TR::Optional(base) => {
+                let inner = self.ty_ref(ctx, *base, subst)?;
+                return self
+                    .optional_type(inner)
+                    .ok_or_else(|| Error::InvalidOptional {
+                        loc: ctx.type_ref_fcs[&id],
+                        problem: optionals::OptionalProblem::Inner(
+                            self.display_type(inner).to_string(),
+                        ),
+                    });
+            }
             TR::Named { name, generics } => {
                 let loc = ctx.type_ref_fcs[&id];
+                let name = &ctx.identifiers[*name];
+                let is_generic = subst.contains_key(name.as_str());
+
                 let mut gens = Vec::with_capacity(generics.len());
-                for id in generics {
-                    let id: TypeId = self.ty_ref(ctx, *id, subst)?;
-                    gens.push(id);
+                for (i, id) in generics.iter().enumerate() {
+                    if !is_generic && self.is_phantom_param(name, i) {
+                        // the argument doesn't show up in the type, it only has to exist
+                        self.ty_check_declared(ctx, *id, &|n| subst.contains_key(n))?;
+                        gens.push(self.error_type());
+                    } else {
+                        let id: TypeId = self.ty_ref(ctx, *id, subst)?;
+                        gens.push(id);
+                    }
                 }
 
-                let name = &ctx.identifiers[*name];
-
                 if let Some(subst_id) = subst.get(name.as_str()) {
                     if !gens.is_empty() {
                         return Err(Error::HigherKindedGenericTypeUsed {
@@ -360,16 +1514,129 @@ impl Context {
         Ok(self.add_or_get_type(ty))
     }
 
+    /// The type of a primitive type in a type reference or constructor.
+    pub(crate) fn primitive_type(&mut self, ctx: &hir::Context, prim: &hir::PrimitiveType) -> Type {
+        use hir::PrimitiveType as PT;
+
+        match prim {
+            PT::Bool => Type::Bool,
+            PT::Int => Type::Int,
+            PT::UInt => Type::UInt,
+            PT::Long => Type::Long,
+            PT::ULong => Type::ULong,
+            PT::Float => Type::Float,
+            PT::Double => Type::Double,
+            PT::Half => Type::Half,
+            PT::Radians => Type::Radians,
+            PT::Degrees => Type::Degrees,
+            PT::AtomicInt => Type::AtomicInt,
+            PT::AtomicUInt => Type::AtomicUInt,
+            PT::BoolVec { components } => Type::BoolVec {
+                components: (*components).into(),
+            },
+            PT::IntVec {
+                components,
+                vtype,
+                space,
+            } => Type::IntVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::UIntVec {
+                components,
+                vtype,
+                space,
+            } => Type::UIntVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::LongVec {
+                components,
+                vtype,
+                space,
+            } => Type::LongVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::ULongVec {
+                components,
+                vtype,
+                space,
+            } => Type::ULongVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::FloatVec {
+                components,
+                vtype,
+                space,
+            } => Type::FloatVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::DoubleVec {
+                components,
+                vtype,
+                space,
+            } => Type::DoubleVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::HalfVec {
+                components,
+                vtype,
+                space,
+            } => Type::HalfVec {
+                components: (*components).into(),
+                vtype: vtype.map(|ty| ctx.vec_types[ty]).into(),
+                space: space.map(|id| self.types.intern_name(&ctx.identifiers[id])),
+            },
+            PT::FloatMat {
+                cols,
+                rows,
+                transform,
+            } => Type::FloatMat {
+                cols: (*cols).into(),
+                rows: (*rows).into(),
+                transform: transform.map(|(from, to)| {
+                    let from = self.types.intern_name(&ctx.identifiers[from]);
+                    (from, self.types.intern_name(&ctx.identifiers[to]))
+                }),
+            },
+            PT::DoubleMat {
+                cols,
+                rows,
+                transform,
+            } => Type::DoubleMat {
+                cols: (*cols).into(),
+                rows: (*rows).into(),
+                transform: transform.map(|(from, to)| {
+                    let from = self.types.intern_name(&ctx.identifiers[from]);
+                    (from, self.types.intern_name(&ctx.identifiers[to]))
+                }),
+            },
+            PT::Packed { format } => Type::Packed {
+                format: (*format).into(),
+            },
+        }
+    }
+
     fn add_type_definition(
         &mut self,
         ctx: &hir::Context,
-        id: Id<TypeDefinition>,
+        def_id: Id<TypeDefinition>,
     ) -> Result<(), Vec<Error>> {
-        let def = &ctx.type_defs[id];
-        let def_loc = ctx.type_def_fcs[&id];
+        let def = &ctx.type_defs[def_id];
+        let def_loc = ctx.type_def_fcs[&def_id];
         let name = &ctx.identifiers[def.name];
 
-        let old = self.defs.insert(name.clone(), id);
+        let old = self.defs.insert(name.clone(), def_id);
         debug_assert!(old.is_none());
 
         // "complete" types (types without generics) can be stored separately and
@@ -381,7 +1648,7 @@ impl Context {
                         .ty_ref(ctx, *id, &Default::default())
                         .map_err(|err| vec![err])?;
 
-                    let distinct_id = self.next_distinct_id();
+                    let distinct_id = self.next_distinct_id(def_id);
                     self.add_type(Type::Distinct {
                         distinct_id,
                         inner: alias_id,
@@ -395,6 +1662,7 @@ impl Context {
                     let mut fields_so_far = HashMap::new();
 
                     let mut fields = vec![];
+                    let mut layouts = vec![];
 
                     for field in field_ids {
                         let var_def = &ctx.variable_defs[*field];
@@ -411,12 +1679,14 @@ impl Context {
 
                         match self.ty_ref(ctx, var_def.type_, &Default::default()) {
                             Ok(id) => {
-                                fields.push((var_name.clone(), id));
+                                fields.push((self.types.intern_name(var_name), id));
                             }
                             Err(err) => {
                                 errs.push(err);
                             }
                         }
+
+                        layouts.push(layout::field_layout(ctx, *field, &mut errs));
                     }
 
                     if !errs.is_empty() {
@@ -424,7 +1694,8 @@ impl Context {
                     }
 
                     let inner = self.add_or_get_type(Type::Record { fields });
-                    let distinct_id = self.next_distinct_id();
+                    let distinct_id = self.next_distinct_id(def_id);
+                    self.add_field_layouts(distinct_id, layouts);
                     self.add_type(Type::Distinct { distinct_id, inner })
                 }
             };
@@ -445,7 +1716,7 @@ impl Context {
                         errs.push(err);
                     }
 
-                    let distinct_id = self.next_distinct_id();
+                    let distinct_id = self.next_distinct_id(def_id);
                     self.generic_distinct_ids.insert(name.clone(), distinct_id);
                 }
                 hir::TypeDefinitionRhs::Alias(id) => {
@@ -455,6 +1726,7 @@ impl Context {
                 }
                 hir::TypeDefinitionRhs::Record { fields } => {
                     let mut fields_so_far = HashMap::new();
+                    let mut layouts = vec![];
 
                     for field in fields {
                         let var_def = &ctx.variable_defs[*field];
@@ -472,9 +1744,12 @@ impl Context {
                         if let Err(err) = self.ty_validate_ref(ctx, var_def.type_, &generics) {
                             errs.push(err);
                         }
+
+                        layouts.push(layout::field_layout(ctx, *field, &mut errs));
                     }
 
-                    let distinct_id = self.next_distinct_id();
+                    let distinct_id = self.next_distinct_id(def_id);
+                    self.add_field_layouts(distinct_id, layouts);
                     self.generic_distinct_ids.insert(name.clone(), distinct_id);
                 }
             }
@@ -490,50 +1765,74 @@ impl Context {
         &mut self,
         ctx: &hir::Context,
         func: Id<Function>,
-    ) -> Result<(), Error> {
+    ) -> Result<(), Vec<Error>> {
         let fun = &ctx.functions[func];
+        let name = &ctx.identifiers[fun.name];
+
+        if let Some(prev_id) = self.function_sigs.get(name).map(|sig| sig.func_id) {
+            let prev_func = &ctx.functions[prev_id];
+            let prev_item_fc = ctx.function_fcs[&prev_id];
+            let prev_name_fc = ctx.identifier_fcs[&prev_func.name];
+
+            let redef_item_fc = ctx.function_fcs[&func];
+            let redef_name_fc = ctx.identifier_fcs[&fun.name];
+
+            return Err(vec![Error::FunctionRedefinition {
+                previous_name: prev_name_fc,
+                previous_sig: prev_item_fc,
+                redefinition_name: redef_name_fc,
+                redefinition_sig: redef_item_fc,
+            }]);
+        }
 
-        let ret = self.ty_ref(ctx, fun.ret_type, &Default::default())?;
+        let mut errs = vec![];
+
+        let mut generics = vec![];
+        let mut subst = HashMap::new();
+        let mut generic_locs = HashMap::new();
+        for (index, gen) in fun.generics.iter().enumerate() {
+            let gen_name = &ctx.identifiers[*gen];
+            let loc = ctx.identifier_fcs[gen];
+            if let Some(prev) = generic_locs.insert(gen_name.as_str(), loc) {
+                errs.push(Error::GenericParamaterRedefinition {
+                    previous_name: prev,
+                    redefinition: loc,
+                });
+                continue;
+            }
+            let param = self.add_or_get_type(Type::GenericParam {
+                index,
+                name: gen_name.clone(),
+            });
+            generics.push(gen_name.clone());
+            subst.insert(gen_name.as_str(), param);
+        }
 
+        let ret = self.ty_ref_or_error(ctx, fun.ret_type, &subst, &mut errs);
         let args = fun
             .args
             .iter()
-            .map(|(nam, ty)| {
+            .map(|(nam, ty, _)| {
                 let ident = ctx.identifiers[*nam].clone();
-                let ty = self.ty_ref(ctx, *ty, &Default::default())?;
-                Ok((ident, ty))
+                (ident, self.ty_ref_or_error(ctx, *ty, &subst, &mut errs))
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect();
 
         let sig = FunctionSig {
             func_id: func,
+            generics,
             args,
             ret,
+            // known once the bodies are indexed
+            effects: Effects::default(),
         };
-        let name = ctx.identifiers[fun.name].clone();
+        self.function_sigs.insert(name.clone(), sig);
 
-        use std::collections::btree_map::Entry;
-        match self.function_sigs.entry(name) {
-            Entry::Vacant(entry) => {
-                entry.insert(sig);
-                Ok(())
-            }
-            Entry::Occupied(entry) => {
-                let prev_id = entry.get().func_id;
-                let prev_func = &ctx.functions[prev_id];
-                let prev_item_fc = ctx.function_fcs[&prev_id];
-                let prev_name_fc = ctx.identifier_fcs[&prev_func.name];
-
-                let redef_item_fc = ctx.function_fcs[&func];
-                let redef_name_fc = ctx.identifier_fcs[&fun.name];
-
-                Err(Error::FunctionRedefinition {
-                    previous_name: prev_name_fc,
-                    previous_sig: prev_item_fc,
-                    redefinition_name: redef_name_fc,
-                    redefinition_sig: redef_item_fc,
-                })
-            }
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            self.poisoned_functions.insert(name.clone(), func);
+            Err(errs)
         }
     }
 
@@ -541,33 +1840,38 @@ impl Context {
         let def = &hir_ctx.variable_defs[id];
         let name = &hir_ctx.identifiers[def.name];
 
-        let ty = self.ty_ref(hir_ctx, def.type_, &Default::default())?;
+        if let Some(prev_id) = self.consts.get(name).map(|sig| sig.const_id) {
+            let prev = &hir_ctx.variable_defs[prev_id];
 
-        match self.consts.entry(name.clone()) {
-            std::collections::btree_map::Entry::Vacant(entry) => {
-                entry.insert(ConstantSig {
-                    const_id: id,
-                    type_: ty,
-                });
-                Ok(())
-            }
-            std::collections::btree_map::Entry::Occupied(entry) => {
-                let prev_sig = entry.get();
-                let prev = &hir_ctx.variable_defs[prev_sig.const_id];
+            let redef_name = hir_ctx.identifier_fcs[&def.name];
+            let redef_def = hir_ctx.variable_def_fcs[&id];
 
-                let redef_name = hir_ctx.identifier_fcs[&def.name];
-                let redef_def = hir_ctx.variable_def_fcs[&id];
+            let prev_name = hir_ctx.identifier_fcs[&prev.name];
+            let prev_def = hir_ctx.variable_def_fcs[&prev_id];
 
-                let prev_name = hir_ctx.identifier_fcs[&prev.name];
-                let prev_def = hir_ctx.variable_def_fcs[&prev_sig.const_id];
+            return Err(Error::ConstantRedefinition {
+                previous_def: prev_def,
+                previous_name: prev_name,
 
-                Err(Error::ConstantRedefinition {
-                    previous_def: prev_def,
-                    previous_name: prev_name,
+                redefinition_def: redef_def,
+                redefinition_name: redef_name,
+            });
+        }
 
-                    redefinition_def: redef_def,
-                    redefinition_name: redef_name,
-                })
+        let mut errs = vec![];
+        let ty = self.ty_ref_or_error(hir_ctx, def.type_, &Default::default(), &mut errs);
+
+        let sig = ConstantSig {
+            const_id: id,
+            type_: ty,
+        };
+        self.consts.insert(name.clone(), sig);
+
+        match errs.pop() {
+            None => Ok(()),
+            Some(err) => {
+                self.poisoned_consts.insert(name.clone(), id);
+                Err(err)
             }
         }
     }
@@ -590,25 +1894,52 @@ impl Context {
             } => {}
             Expression::Field { base, name } => {}
             Expression::Index { base, index } => {}
+            Expression::Slice { base, lo, hi } => {}
             Expression::As { base, ty } => {}
+            Expression::LayoutQuery { query, ty, rules } => {}
+            Expression::Array(elements) => {}
         }
         todo!()
     }
 
+    /// Translate a type reference of a function or constant, errors are
+    /// recorded and the error type is used instead.
+    fn ty_ref_or_error(
+        &mut self,
+        ctx: &hir::Context,
+        id: Id<TypeReference>,
+        subst: &HashMap<&str, TypeId>,
+        errs: &mut Vec<Error>,
+    ) -> TypeId {
+        match self.ty_ref(ctx, id, subst) {
+            Ok(ty) => ty,
+            Err(err) => {
+                errs.push(err);
+                self.error_type()
+            }
+        }
+    }
+
+    pub fn error_type(&mut self) -> TypeId {
+        self.add_or_get_type(Type::Error)
+    }
+
+    /// A new type variable, not bound to any type yet.
+    pub fn fresh_var(&mut self) -> TypeId {
+        let id = self.var_counter;
+        self.var_counter += 1;
+        self.add_type(Type::Var(id))
+    }
+
     fn add_type(&mut self, ty: Type) -> TypeId {
         let next_id = TypeId(self.types.len());
-
-        let res = self.types.insert(ty, next_id);
-        debug_assert_eq!(res.did_overwrite(), false);
-        next_id
+        let id = self.types.intern(ty);
+        debug_assert_eq!(id, next_id);
+        id
     }
 
     fn add_or_get_type(&mut self, ty: Type) -> TypeId {
-        if let Some(id) = self.types.get_by_left(&ty) {
-            *id
-        } else {
-            self.add_type(ty)
-        }
+        self.types.intern(ty)
     }
 
     fn ty_named(
@@ -629,6 +1960,10 @@ impl Context {
             }
         }
 
+        if self.poisoned_types.contains_key(name) {
+            return Ok(self.error_type());
+        }
+
         if let Some(id) = self.defs.get(name) {
             let def_loc = ctx.type_def_fcs[id];
             let def = &ctx.type_defs[*id];
@@ -660,7 +1995,7 @@ impl Context {
                         let mut record_fields = Vec::with_capacity(fields.len());
                         for field in fields {
                             let def = &ctx.variable_defs[*field];
-                            let name = ctx.identifiers[def.name].clone();
+                            let name = self.types.intern_name(&ctx.identifiers[def.name]);
                             let field_ty = self.ty_ref(ctx, def.type_, &subst)?;
                             record_fields.push((name, field_ty));
                         }
@@ -669,7 +2004,11 @@ impl Context {
                             fields: record_fields,
                         });
 
-                        Ok(self.add_or_get_type(Type::Distinct { distinct_id, inner }))
+                        let ty = self.add_or_get_type(Type::Distinct { distinct_id, inner });
+                        if !generics.is_empty() {
+                            self.generic_args.insert(ty, generics.to_vec());
+                        }
+                        Ok(ty)
                     }
                 }
             }
@@ -677,6 +2016,7 @@ impl Context {
             Err(Error::UndefinedType {
                 name: name.to_string(),
                 uses: vec![loc],
+                suggestions: self.similar_type_names(name, std::iter::empty()),
             })
         }
     }
@@ -694,8 +2034,21 @@ impl Context {
         let ty_ref = &ctx.type_refs[id];
 
         match ty_ref {
-            TypeReference::Primitive(_) => Ok(()),
-            TypeReference::OpenArray(inner) => self.ty_validate_ref(ctx, *inner, generics),
+            TypeReference::Primitive(_)
+            | TypeReference::Image { .. }
+            | TypeReference::Texture { sampled: None, .. }
+            | TypeReference::Sampler { .. }
+            | TypeReference::AccelerationStructure => Ok(()),
+            TypeReference::OpenArray(inner)
+            | TypeReference::Normalized(inner)
+            | TypeReference::Optional(inner)
+            | TypeReference::Texture {
+                sampled: Some(inner),
+                ..
+            } => self.ty_validate_ref(ctx, *inner, generics),
+            TypeReference::Record { fields } => fields
+                .iter()
+                .try_for_each(|(_, ty)| self.ty_validate_ref(ctx, *ty, generics)),
             TypeReference::Array { base, size: _ } => self.ty_validate_ref(ctx, *base, generics),
             TypeReference::Named {
                 name,
@@ -712,6 +2065,8 @@ impl Context {
                     } else {
                         Ok(())
                     }
+                } else if self.poisoned_types.contains_key(name_s.as_str()) {
+                    Ok(())
                 } else if let Some(def_id) = self.defs.get(name_s.as_str()) {
                     let def = &ctx.type_defs[*def_id];
                     let def_loc = ctx.type_def_fcs[def_id];
@@ -723,8 +2078,12 @@ impl Context {
                             def_loc,
                         })
                     } else {
-                        for gen in applied_gens {
-                            self.ty_validate_ref(ctx, *gen, generics)?;
+                        for (i, gen) in applied_gens.iter().enumerate() {
+                            if self.is_phantom_param(name_s, i) {
+                                self.ty_check_declared(ctx, *gen, &|n| generics.contains(n))?;
+                            } else {
+                                self.ty_validate_ref(ctx, *gen, generics)?;
+                            }
                         }
                         Ok(())
                     }
@@ -732,23 +2091,117 @@ impl Context {
                     Err(Error::UndefinedType {
                         name: name_s.to_string(),
                         uses: vec![loc],
+                        suggestions: self.similar_type_names(name_s, generics.iter().copied()),
                     })
                 }
             }
         }
     }
 
-    fn next_distinct_id(&mut self) -> usize {
+    fn is_phantom_param(&self, type_name: &str, index: usize) -> bool {
+        self.phantom_params
+            .get(type_name)
+            .and_then(|params| params.get(index))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Check that all types named in a type reference are declared, without
+    /// requiring them to be processed already.
+    fn ty_check_declared(
+        &self,
+        ctx: &hir::Context,
+        id: Id<TypeReference>,
+        is_generic: &dyn Fn(&str) -> bool,
+    ) -> Result<(), Error> {
+        match &ctx.type_refs[id] {
+            TypeReference::Primitive(_)
+            | TypeReference::Image { .. }
+            | TypeReference::Texture { sampled: None, .. }
+            | TypeReference::Sampler { .. }
+            | TypeReference::AccelerationStructure => Ok(()),
+            TypeReference::OpenArray(base)
+            | TypeReference::Array { base, size: _ }
+            | TypeReference::Normalized(base)
+            | TypeReference::Optional(base)
+            | TypeReference::Texture {
+                sampled: Some(base),
+                ..
+            } => self.ty_check_declared(ctx, *base, is_generic),
+            TypeReference::Record { fields } => fields
+                .iter()
+                .try_for_each(|(_, ty)| self.ty_check_declared(ctx, *ty, is_generic)),
+            TypeReference::Named { name, generics } => {
+                let name_s = ctx.identifiers[*name].as_str();
+                if !is_generic(name_s)
+                    && !self.phantom_params.contains_key(name_s)
+                    && !self.poisoned_types.contains_key(name_s)
+                {
+                    return Err(Error::UndefinedType {
+                        name: name_s.to_string(),
+                        uses: vec![ctx.identifier_fcs[name]],
+                        suggestions: self.similar_type_names(name_s, std::iter::empty()),
+                    });
+                }
+                for gen in generics {
+                    self.ty_check_declared(ctx, *gen, is_generic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn similar_type_names<'a>(
+        &'a self,
+        name: &str,
+        generics: impl Iterator<Item = &'a str>,
+    ) -> Vec<String> {
+        let candidates = self
+            .defs
+            .keys()
+            .chain(self.poisoned_types.keys())
+            .map(String::as_str)
+            .chain(generics);
+        suggestions::similar_names(name, candidates)
+    }
+
+    fn poison_type(&mut self, ctx: &hir::Context, def_id: Id<TypeDefinition>) {
+        let name = &ctx.identifiers[ctx.type_defs[def_id].name];
+        self.defs.remove(name);
+        self.complete_types.remove(name);
+        self.poisoned_types.insert(name.clone(), def_id);
+    }
+
+    fn add_field_layouts(&mut self, distinct_id: usize, layouts: Vec<layout::FieldLayout>) {
+        if layouts.iter().any(|l| *l != layout::FieldLayout::default()) {
+            self.field_layouts.insert(distinct_id, layouts);
+        }
+    }
+
+    fn next_distinct_id(&mut self, def: Id<TypeDefinition>) -> usize {
         let id = self.distinct_counter;
         self.distinct_counter += 1;
+        self.distinct_defs.insert(id, def);
         id
     }
 }
 
+/// How a type is used in the definition of another type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UseKind {
+    /// the size of the type depends on the used type
+    Size,
+    /// the type is only an argument for a phantom generic parameter
+    Phantom,
+}
+
+type TypeDeps<'a> = HashMap<&'a str, Vec<(FileLocation, UseKind)>>;
+
 fn type_def_deps<'a>(
     ctx: &'a hir::Context,
     ty: &hir::TypeDefinition,
-    deps: &mut HashMap<&'a str, Vec<FileLocation>>,
+    phantoms: &BTreeMap<Identifier, Vec<bool>>,
+    deps: &mut TypeDeps<'a>,
 ) -> Result<(), Error> {
     let mut generics = HashMap::new();
 
@@ -764,20 +2217,8 @@ fn type_def_deps<'a>(
         }
     }
 
-    let rhs = &ctx.type_def_rhss[ty.rhs];
-    match rhs {
-        hir::TypeDefinitionRhs::Distinct(ty) => {
-            type_ref_deps(ctx, *ty, deps);
-        }
-        hir::TypeDefinitionRhs::Alias(ty) => {
-            type_ref_deps(ctx, *ty, deps);
-        }
-        hir::TypeDefinitionRhs::Record { fields } => {
-            for field in fields {
-                let def = &ctx.variable_defs[*field];
-                type_ref_deps(ctx, def.type_, deps);
-            }
-        }
+    for ty in type_def_refs(ctx, ty) {
+        type_ref_deps(ctx, ty, phantoms, UseKind::Size, deps);
     }
 
     for (gen, _) in generics {
@@ -787,25 +2228,89 @@ fn type_def_deps<'a>(
     Ok(())
 }
 
+/// The type references on the right hand side of a type definition
+fn type_def_refs(ctx: &hir::Context, ty: &hir::TypeDefinition) -> Vec<Id<TypeReference>> {
+    match &ctx.type_def_rhss[ty.rhs] {
+        hir::TypeDefinitionRhs::Distinct(ty) | hir::TypeDefinitionRhs::Alias(ty) => vec![*ty],
+        hir::TypeDefinitionRhs::Record { fields } => fields
+            .iter()
+            .map(|field| ctx.variable_defs[*field].type_)
+            .collect(),
+    }
+}
+
+/// Which generic parameters of a type definition don't occur in its right
+/// hand side.
+fn phantom_params(ctx: &hir::Context, ty: &hir::TypeDefinition) -> Vec<bool> {
+    let mut used = HashMap::new();
+    for ty in type_def_refs(ctx, ty) {
+        type_ref_deps(ctx, ty, &BTreeMap::new(), UseKind::Size, &mut used);
+    }
+
+    ty.generics
+        .iter()
+        .map(|gen| !used.contains_key(ctx.identifiers[*gen].as_str()))
+        .collect()
+}
+
 fn type_ref_deps<'a>(
     ctx: &'a hir::Context,
     ty: Id<hir::TypeReference>,
-    deps: &mut HashMap<&'a str, Vec<FileLocation>>,
+    phantoms: &BTreeMap<Identifier, Vec<bool>>,
+    kind: UseKind,
+    deps: &mut TypeDeps<'a>,
 ) {
     let ty_ref = &ctx.type_refs[ty];
     match ty_ref {
-        TypeReference::Primitive(_) => {}
-        TypeReference::OpenArray(base) => type_ref_deps(ctx, *base, deps),
-        TypeReference::Array { base, size: _ } => type_ref_deps(ctx, *base, deps),
+        TypeReference::Primitive(_)
+        | TypeReference::Image { .. }
+        | TypeReference::Texture { sampled: None, .. }
+        | TypeReference::Sampler { .. }
+        | TypeReference::AccelerationStructure => {}
+        TypeReference::OpenArray(base)
+        | TypeReference::Normalized(base)
+        | TypeReference::Optional(base)
+        | TypeReference::Texture {
+            sampled: Some(base),
+            ..
+        } => type_ref_deps(ctx, *base, phantoms, kind, deps),
+        TypeReference::Record { fields } => {
+            for (_, ty) in fields {
+                type_ref_deps(ctx, *ty, phantoms, kind, deps);
+            }
+        }
+        TypeReference::Array { base, size } => {
+            // the types asked about in the size affect the size as well
+            if let hir::ArraySize::Expression(size) = size {
+                let mut queries = vec![];
+                consteval::layout_queries(
+                    ctx,
+                    None,
+                    *size,
+                    false,
+                    &mut HashSet::new(),
+                    &mut queries,
+                );
+                for (query, _) in queries {
+                    if let Expression::LayoutQuery { ty, .. } = &ctx.expressions[query] {
+                        type_ref_deps(ctx, *ty, phantoms, UseKind::Size, deps);
+                    }
+                }
+            }
+            type_ref_deps(ctx, *base, phantoms, kind, deps)
+        }
         TypeReference::Named { name, generics } => {
             let usage_loc = ctx.identifier_fcs[name];
             let name = &ctx.identifiers[*name];
 
             let locs = deps.entry(name.as_str()).or_default();
-            locs.push(usage_loc);
+            locs.push((usage_loc, kind));
 
-            for gen in generics {
-                type_ref_deps(ctx, *gen, deps);
+            let params = phantoms.get(name);
+            for (i, gen) in generics.iter().enumerate() {
+                let phantom = params.and_then(|p| p.get(i)).copied().unwrap_or(false);
+                let gen_kind = if phantom { UseKind::Phantom } else { kind };
+                type_ref_deps(ctx, *gen, phantoms, gen_kind, deps);
             }
         }
     }
@@ -0,0 +1,309 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Groups of warnings whose level can be chosen.
+//!
+//! Warnings that belong to a group are reported as the level of the group in
+//! the [`Context`] says: `allow` drops them, `warn` reports them as warnings
+//! and `deny` as errors. Warnings without a group are always reported.
+
+use std::fmt;
+use std::str::FromStr;
+
+use hir::{Expression, PrimitiveOp, Statement};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::{Context, Error, Warning};
+
+/// A group of related warnings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintGroup {
+    /// declarations that hide another declaration with the same name
+    Shadowing,
+    /// names of the same namespace that differ only in case
+    CaseCollisions,
+    /// names that look like other names spelled with other characters
+    ConfusableNames,
+    /// subgroup operations that some invocations of the subgroup may not
+    /// reach
+    SubgroupUniformity,
+    /// integer arithmetic whose overflow is left to the target
+    IntegerOverflow,
+    /// comparisons of floats without a tolerance
+    FloatEquality,
+    /// casts that can lose information
+    LossyCasts,
+    /// names that are reserved for future keywords
+    ReservedWords,
+}
+
+impl LintGroup {
+    pub const ALL: &'static [LintGroup] = &[
+        LintGroup::Shadowing,
+        LintGroup::CaseCollisions,
+        LintGroup::ConfusableNames,
+        LintGroup::SubgroupUniformity,
+        LintGroup::IntegerOverflow,
+        LintGroup::FloatEquality,
+        LintGroup::LossyCasts,
+        LintGroup::ReservedWords,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LintGroup::Shadowing => "shadowing",
+            LintGroup::CaseCollisions => "case-collisions",
+            LintGroup::ConfusableNames => "confusable-names",
+            LintGroup::SubgroupUniformity => "subgroup-uniformity",
+            LintGroup::IntegerOverflow => "integer-overflow",
+            LintGroup::FloatEquality => "float-equality",
+            LintGroup::LossyCasts => "lossy-casts",
+            LintGroup::ReservedWords => "reserved-words",
+        }
+    }
+
+    /// The level of the group when the context doesn't set one
+    pub fn default_level(self) -> LintLevel {
+        match self {
+            LintGroup::Shadowing => LintLevel::Allow,
+            LintGroup::CaseCollisions => LintLevel::Allow,
+            LintGroup::ConfusableNames => LintLevel::Warn,
+            LintGroup::SubgroupUniformity => LintLevel::Warn,
+            LintGroup::IntegerOverflow => LintLevel::Allow,
+            LintGroup::FloatEquality => LintLevel::Warn,
+            LintGroup::LossyCasts => LintLevel::Warn,
+            LintGroup::ReservedWords => LintLevel::Warn,
+        }
+    }
+}
+
+impl FromStr for LintGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LintGroup::ALL
+            .iter()
+            .copied()
+            .find(|group| group.name() == s)
+            .ok_or_else(|| {
+                let names = LintGroup::ALL.iter().map(|g| g.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown lint group `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for LintGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// How the warnings of a group are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Context {
+    /// The level the warnings of a group are reported at.
+    pub fn lint_level(&self, group: LintGroup) -> LintLevel {
+        self.lint_levels
+            .get(&group)
+            .copied()
+            .unwrap_or_else(|| group.default_level())
+    }
+}
+
+/// Split the warnings into the ones reported as warnings and the denied
+/// ones, which are reported as errors. Allowed warnings are dropped.
+pub(crate) fn apply_levels(ctx: &Context, warnings: Vec<Warning>) -> (Vec<Warning>, Vec<Error>) {
+    let mut reported = vec![];
+    let mut denied = vec![];
+    for warning in warnings {
+        let group = match warning.lint_group() {
+            Some(group) => group,
+            None => {
+                reported.push(warning);
+                continue;
+            }
+        };
+        match ctx.lint_level(group) {
+            LintLevel::Allow => {}
+            LintLevel::Warn => reported.push(warning),
+            LintLevel::Deny => denied.push(Error::DeniedLint {
+                group,
+                warning: Box::new(warning),
+            }),
+        }
+    }
+    (reported, denied)
+}
+
+/// The expressions in the bodies of the functions and programs of a module,
+/// every expression before the expressions it contains.
+pub(crate) fn expressions(module: &hir::Module, hir_ctx: &hir::Context) -> Vec<Id<Expression>> {
+    let mut exprs = vec![];
+    let bodies = module
+        .functions
+        .iter()
+        .map(|id| &hir_ctx.functions[*id].body)
+        .chain(module.programs.iter().map(|id| &hir_ctx.programs[*id].body));
+    for body in bodies {
+        for stmt in body {
+            statement_exprs(hir_ctx, *stmt, &mut exprs);
+        }
+    }
+    exprs
+}
+
+/// The primitive operations in the bodies of the functions and programs of
+/// a module, with the expressions they are.
+pub(crate) fn primitive_ops(
+    module: &hir::Module,
+    hir_ctx: &hir::Context,
+) -> Vec<(Id<Expression>, Id<PrimitiveOp>)> {
+    expressions(module, hir_ctx)
+        .into_iter()
+        .filter_map(|id| match hir_ctx.expressions[id] {
+            Expression::PrimitiveOp(op) => Some((id, op)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn statement_exprs(ctx: &hir::Context, id: Id<Statement>, exprs: &mut Vec<Id<Expression>>) {
+    match &ctx.statements[id] {
+        Statement::Var(def) => {
+            if let Some(rhs) = ctx.variable_defs[*def].rhs {
+                expression_exprs(ctx, rhs, exprs);
+            }
+        }
+        Statement::Becomes { lhs, rhs } => {
+            expression_exprs(ctx, *lhs, exprs);
+            expression_exprs(ctx, *rhs, exprs);
+        }
+        Statement::Destructure { lhs, rhs } => {
+            for e in lhs {
+                expression_exprs(ctx, *e, exprs);
+            }
+            expression_exprs(ctx, *rhs, exprs);
+        }
+        Statement::Return(Some(e)) | Statement::Expr(e) => expression_exprs(ctx, *e, exprs),
+        Statement::Return(None) | Statement::Break | Statement::Continue | Statement::Discard => {}
+        Statement::If {
+            cond,
+            then_body,
+            else_body,
+        } => {
+            expression_exprs(ctx, *cond, exprs);
+            for stmt in then_body.iter().chain(else_body) {
+                statement_exprs(ctx, *stmt, exprs);
+            }
+        }
+        Statement::For { from, to, body, .. } => {
+            expression_exprs(ctx, *from, exprs);
+            expression_exprs(ctx, *to, exprs);
+            for stmt in body {
+                statement_exprs(ctx, *stmt, exprs);
+            }
+        }
+        Statement::Match { value, arms } => {
+            expression_exprs(ctx, *value, exprs);
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                statement_exprs(ctx, *stmt, exprs);
+            }
+        }
+    }
+}
+
+/// An expression and the expressions it contains, every expression before
+/// the expressions it contains.
+pub(crate) fn expression_exprs(
+    ctx: &hir::Context,
+    id: Id<Expression>,
+    exprs: &mut Vec<Id<Expression>>,
+) {
+    use PrimitiveOp as PO;
+
+    exprs.push(id);
+    match &ctx.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::LayoutQuery { .. } => {}
+        Expression::PrimitiveOp(op) => match &ctx.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => expression_exprs(ctx, *e, exprs),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                expression_exprs(ctx, *a, exprs);
+                expression_exprs(ctx, *b, exprs);
+            }
+            PO::Constructor {
+                pos_args, nam_args, ..
+            } => {
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    expression_exprs(ctx, *e, exprs);
+                }
+            }
+        },
+        Expression::Call {
+            pos_args, nam_args, ..
+        } => {
+            for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                expression_exprs(ctx, *e, exprs);
+            }
+        }
+        Expression::Field { base, .. } | Expression::As { base, .. } => {
+            expression_exprs(ctx, *base, exprs)
+        }
+        Expression::Index { base, index } => {
+            expression_exprs(ctx, *base, exprs);
+            expression_exprs(ctx, *index, exprs);
+        }
+        Expression::Slice { base, lo, hi } => {
+            expression_exprs(ctx, *base, exprs);
+            expression_exprs(ctx, *lo, exprs);
+            expression_exprs(ctx, *hi, exprs);
+        }
+        Expression::Array(elements) => {
+            for e in elements {
+                expression_exprs(ctx, *e, exprs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let mut ctx = Context::default();
+        assert_eq!(ctx.lint_level(LintGroup::Shadowing), LintLevel::Allow);
+        ctx.lint_levels
+            .insert(LintGroup::Shadowing, LintLevel::Deny);
+        assert_eq!(ctx.lint_level(LintGroup::Shadowing), LintLevel::Deny);
+        assert_eq!(
+            "shadows".parse::<LintGroup>(),
+            Err(
+                "unknown lint group `shadows`, expected one of shadowing, case-collisions, confusable-names, subgroup-uniformity, integer-overflow, float-equality, lossy-casts, reserved-words"
+                    .to_string()
+            )
+        );
+    }
+}
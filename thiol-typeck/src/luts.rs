@@ -0,0 +1,177 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Lookup tables of functions.
+//!
+//! A function with a `@lut(size)` attribute is evaluated when compiling for
+//! `size` arguments, and the backends emit the results as a constant array
+//! and the function as a lookup in it. This bakes curves that are expensive
+//! to compute, at the cost of their precision between the entries.
+//!
+//! The function has one parameter, which is an `int` or a `uint` for the
+//! entries from 0 up to `size - 1`, or a `float` for `size` entries evenly
+//! spaced from 0.0 to 1.0. Integer arguments are clamped to the entries,
+//! float arguments are clamped to 0.0 to 1.0 and rounded to the nearest
+//! entry. The function is pure and not generic, it is evaluated with
+//! [`Evaluator::call`].
+
+use std::collections::BTreeMap;
+
+use thiol_hir as hir;
+
+use hir::{FileLocation, Function, ParamMode};
+use id_arena::Id;
+
+use crate::consteval::{Constant, Evaluator, Value};
+use crate::stages::count_arg;
+use crate::{Context, Effects, Error, Type};
+
+/// The most entries of a lookup table
+pub const MAX_LUT_SIZE: u32 = 4096;
+
+/// The type of the parameter of a function with a lookup table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LutDomain {
+    Int,
+    UInt,
+    Float,
+}
+
+/// The values of a function with a `@lut` attribute for each entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupTable {
+    pub domain: LutDomain,
+    pub values: Vec<Constant>,
+}
+
+/// Why a function can't have a lookup table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LutProblem {
+    /// the size is not a number from 1 to [`MAX_LUT_SIZE`]
+    Size,
+    /// the function has generic parameters
+    Generic,
+    /// the function doesn't have one `int`, `uint` or `float` parameter
+    Parameters,
+    /// the function has effects
+    Effects(Effects),
+}
+
+impl LutDomain {
+    /// The argument of the entry `index` of a table with `size` entries.
+    fn argument(self, index: usize, size: usize) -> Value {
+        match self {
+            LutDomain::Int => Value::Int(index as i32),
+            LutDomain::UInt => Value::UInt(index as u32),
+            LutDomain::Float if size == 1 => Value::Float(0.0),
+            LutDomain::Float => Value::Float(index as f32 / (size - 1) as f32),
+        }
+    }
+}
+
+/// The `@lut` attribute of a function and its location.
+pub fn lut_attribute(
+    hir_ctx: &hir::Context,
+    func: Id<Function>,
+) -> Option<(&hir::Attribute, FileLocation)> {
+    hir_ctx.functions[func]
+        .attrs
+        .iter()
+        .find(|attr| hir_ctx.identifiers[hir_ctx.attributes[**attr].name] == "lut")
+        .map(|attr| (&hir_ctx.attributes[*attr], hir_ctx.attribute_fcs[attr]))
+}
+
+/// The domain of a function that can have a lookup table.
+fn lut_domain(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    func: Id<Function>,
+) -> Result<LutDomain, LutProblem> {
+    let def = &hir_ctx.functions[func];
+    if !def.generics.is_empty() {
+        return Err(LutProblem::Generic);
+    }
+    let sig = &ty_ctx.function_sigs[&hir_ctx.identifiers[def.name]];
+    let domain = match (def.args.as_slice(), sig.args.as_slice()) {
+        ([(_, _, ParamMode::In)], [(_, ty)]) => match ty_ctx.types.get(*ty) {
+            Some(Type::Int) => LutDomain::Int,
+            Some(Type::UInt) => LutDomain::UInt,
+            Some(Type::Float) => LutDomain::Float,
+            _ => return Err(LutProblem::Parameters),
+        },
+        _ => return Err(LutProblem::Parameters),
+    };
+    if !sig.effects.is_pure() {
+        return Err(LutProblem::Effects(sig.effects));
+    }
+    Ok(domain)
+}
+
+/// The lookup table of a function with a `@lut` attribute.
+fn lookup_table(
+    evaluator: &mut Evaluator,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    func: Id<Function>,
+    (attr, attribute): (&hir::Attribute, FileLocation),
+) -> Result<LookupTable, Error> {
+    let invalid = |problem| Error::InvalidLookupTable {
+        attribute,
+        function: hir_ctx.identifiers[hir_ctx.functions[func].name].clone(),
+        problem,
+    };
+    let size = count_arg(hir_ctx, attr, MAX_LUT_SIZE).ok_or_else(|| invalid(LutProblem::Size))?;
+    let domain = lut_domain(ty_ctx, hir_ctx, func).map_err(invalid)?;
+
+    let size = size as usize;
+    let values = (0..size)
+        .map(|index| {
+            let arg = Constant::Scalar(domain.argument(index, size));
+            evaluator.call(func, vec![arg])
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|(expr, problem)| Error::ConstEvaluation { expr, problem })?;
+    Ok(LookupTable { domain, values })
+}
+
+/// Evaluate the functions with a `@lut` attribute for the entries of their
+/// lookup tables.
+pub(crate) fn build_lookup_tables(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> (BTreeMap<Id<Function>, LookupTable>, Vec<Error>) {
+    let mut evaluator = Evaluator::new(ty_ctx, hir_ctx);
+    let mut tables = BTreeMap::new();
+    let mut errs = vec![];
+
+    for id in &module.functions {
+        let attr = match lut_attribute(hir_ctx, *id) {
+            Some(attr) => attr,
+            None => continue,
+        };
+        match lookup_table(&mut evaluator, ty_ctx, hir_ctx, *id, attr) {
+            Ok(table) => {
+                tables.insert(*id, table);
+            }
+            Err(err) => errs.push(err),
+        }
+    }
+
+    (tables, errs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_arguments() {
+        assert_eq!(LutDomain::UInt.argument(3, 8), Value::UInt(3));
+        assert_eq!(LutDomain::Float.argument(0, 5), Value::Float(0.0));
+        assert_eq!(LutDomain::Float.argument(2, 5), Value::Float(0.5));
+        assert_eq!(LutDomain::Float.argument(4, 5), Value::Float(1.0));
+        assert_eq!(LutDomain::Float.argument(0, 1), Value::Float(0.0));
+    }
+}
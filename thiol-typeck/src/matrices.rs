@@ -0,0 +1,281 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Constructing matrices and accessing their components.
+//!
+//! `float4x4(c0, c1, c2, c3)` builds a matrix from its columns, and a
+//! matrix can also be built from all its elements column by column, from a
+//! single scalar on its diagonal or from another matrix. Indexing a matrix
+//! gives a column, indexing a vector gives a component.
+//!
+//! The inverse of a transform from one space to another transforms back, so
+//! `inverse` swaps the spaces of the matrix. The transpose is only the
+//! inverse of rotations, which the type of a matrix doesn't tell apart, so
+//! `transpose` drops the spaces instead of swapping them.
+
+use crate::{Context, Type, TypeId, VecSize, VecType};
+
+/// Why the arguments of a matrix constructor don't build the matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixConstructorProblem {
+    /// neither one value, one per column nor one per element
+    ArgumentCount { found: usize },
+    /// the columns are not vectors with one component per row
+    Column { index: usize },
+    /// scalars and vectors mixed
+    Mixed,
+}
+
+impl Context {
+    /// The type of `m[i]`: a column of a matrix or a component of a vector.
+    pub(crate) fn index_type(&mut self, base: TypeId) -> Option<TypeId> {
+        let ty = match self.types.get(self.strip_distinct(base))? {
+            Type::Array { base, .. } | Type::OpenArray { base } => return Some(*base),
+            Type::FloatMat { rows, .. } => float_vec(*rows),
+            Type::DoubleMat { rows, .. } => Type::DoubleVec {
+                components: *rows,
+                vtype: VecType::Unknown,
+                space: None,
+            },
+            Type::BoolVec { .. } => Type::Bool,
+            ty @ (Type::IntVec { .. }
+            | Type::UIntVec { .. }
+            | Type::LongVec { .. }
+            | Type::ULongVec { .. }
+            | Type::FloatVec { .. }
+            | Type::DoubleVec { .. }
+            | Type::HalfVec { .. }) => ty.scalar()?,
+            _ => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// The type of `transpose(m)`, with the spaces of the matrix dropped.
+    pub(crate) fn transpose_type(&mut self, matrix: TypeId) -> Option<TypeId> {
+        let ty = match self.types.get(self.strip_distinct(matrix))? {
+            Type::FloatMat { cols, rows, .. } => Type::FloatMat {
+                cols: *rows,
+                rows: *cols,
+                transform: None,
+            },
+            Type::DoubleMat { cols, rows, .. } => Type::DoubleMat {
+                cols: *rows,
+                rows: *cols,
+                transform: None,
+            },
+            _ => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// The type of `inverse(m)` for a square matrix, which transforms from
+    /// the space `m` transforms to back into the one it transforms from.
+    pub(crate) fn inverse_type(&mut self, matrix: TypeId) -> Option<TypeId> {
+        let ty = match self.types.get(self.strip_distinct(matrix))? {
+            Type::FloatMat {
+                cols,
+                rows,
+                transform,
+            } if cols == rows => Type::FloatMat {
+                cols: *cols,
+                rows: *rows,
+                transform: transform.map(|(from, to)| (to, from)),
+            },
+            Type::DoubleMat {
+                cols,
+                rows,
+                transform,
+            } if cols == rows => Type::DoubleMat {
+                cols: *cols,
+                rows: *rows,
+                transform: transform.map(|(from, to)| (to, from)),
+            },
+            _ => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// The type of `determinant(m)` for a square matrix.
+    pub(crate) fn determinant_type(&mut self, matrix: TypeId) -> Option<TypeId> {
+        let ty = match self.types.get(self.strip_distinct(matrix))? {
+            Type::FloatMat { cols, rows, .. } if cols == rows => Type::Float,
+            Type::DoubleMat { cols, rows, .. } if cols == rows => Type::Double,
+            _ => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// The type of `identity()` used as a value of type `expected`, which
+    /// has to be a square matrix. The identity stays in its space, so a
+    /// transform between two spaces is dropped.
+    pub fn identity_type(&mut self, expected: TypeId) -> Option<TypeId> {
+        let ty = match self.types.get(expected)? {
+            Type::FloatMat {
+                cols,
+                rows,
+                transform,
+            } if cols == rows => Type::FloatMat {
+                cols: *cols,
+                rows: *rows,
+                transform: transform.filter(|(from, to)| from == to),
+            },
+            Type::DoubleMat {
+                cols,
+                rows,
+                transform,
+            } if cols == rows => Type::DoubleMat {
+                cols: *cols,
+                rows: *rows,
+                transform: transform.filter(|(from, to)| from == to),
+            },
+            _ => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// Check the arguments of a matrix constructor, `None` if they build the
+    /// matrix or if the type of one of them is unknown.
+    pub(crate) fn matrix_constructor_problem(
+        &self,
+        cols: VecSize,
+        rows: VecSize,
+        args: &[Option<TypeId>],
+    ) -> Option<MatrixConstructorProblem> {
+        let args = args
+            .iter()
+            .map(|ty| self.types.get(self.strip_distinct((*ty)?)))
+            .collect::<Option<Vec<_>>>()?;
+        if args.iter().any(|ty| matches!(ty, Type::Error)) {
+            return None;
+        }
+        let is_scalar = |ty: &Type| {
+            matches!(
+                ty,
+                Type::Int | Type::UInt | Type::Float | Type::Double | Type::Half
+            )
+        };
+        let (cols, rows) = (count(cols), count(rows));
+
+        match args.as_slice() {
+            // the diagonal, or another matrix
+            [Type::FloatMat { .. } | Type::DoubleMat { .. }] => None,
+            [ty] if is_scalar(ty) => None,
+            _ if args.iter().all(|ty| is_scalar(ty)) && args.len() == cols * rows => None,
+            _ if args.len() == cols && !args.iter().all(|ty| is_scalar(ty)) => {
+                if args.iter().any(|ty| is_scalar(ty)) {
+                    return Some(MatrixConstructorProblem::Mixed);
+                }
+                let index = args.iter().position(|ty| match ty {
+                    Type::IntVec { components, .. }
+                    | Type::UIntVec { components, .. }
+                    | Type::LongVec { components, .. }
+                    | Type::ULongVec { components, .. }
+                    | Type::FloatVec { components, .. }
+                    | Type::DoubleVec { components, .. }
+                    | Type::HalfVec { components, .. } => count(*components) != rows,
+                    _ => true,
+                })?;
+                Some(MatrixConstructorProblem::Column { index })
+            }
+            _ => Some(MatrixConstructorProblem::ArgumentCount { found: args.len() }),
+        }
+    }
+}
+
+fn float_vec(components: VecSize) -> Type {
+    Type::FloatVec {
+        components,
+        vtype: VecType::Unknown,
+        space: None,
+    }
+}
+
+fn count(size: VecSize) -> usize {
+    crate::layout::components(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mat(
+        ctx: &mut Context,
+        cols: VecSize,
+        rows: VecSize,
+        spaces: Option<(&str, &str)>,
+    ) -> TypeId {
+        let transform =
+            spaces.map(|(from, to)| (ctx.types.intern_name(from), ctx.types.intern_name(to)));
+        ctx.add_or_get_type(Type::FloatMat {
+            cols,
+            rows,
+            transform,
+        })
+    }
+
+    #[test]
+    fn result_types() {
+        use VecSize::*;
+
+        let mut ctx = Context::default();
+        let to_view = mat(&mut ctx, VS4, VS4, Some(("World", "View")));
+        let to_world = mat(&mut ctx, VS4, VS4, Some(("View", "World")));
+        let plain = mat(&mut ctx, VS4, VS4, None);
+        let float2x3 = mat(&mut ctx, VS2, VS3, None);
+        let float3x2 = mat(&mut ctx, VS3, VS2, None);
+        let float3 = ctx.add_or_get_type(float_vec(VS3));
+        let float = ctx.add_or_get_type(Type::Float);
+
+        assert_eq!(ctx.inverse_type(to_view), Some(to_world));
+        assert_eq!(ctx.inverse_type(float2x3), None);
+        assert_eq!(ctx.transpose_type(to_view), Some(plain));
+        assert_eq!(ctx.transpose_type(float2x3), Some(float3x2));
+        assert_eq!(ctx.determinant_type(to_view), Some(float));
+        assert_eq!(ctx.determinant_type(float3x2), None);
+        assert_eq!(ctx.identity_type(to_view), Some(plain));
+        assert_eq!(ctx.identity_type(float3), None);
+        assert_eq!(ctx.index_type(float2x3), Some(float3));
+        assert_eq!(ctx.index_type(float3), Some(float));
+    }
+
+    #[test]
+    fn constructor_arguments() {
+        use VecSize::*;
+
+        let mut ctx = Context::default();
+        let float = ctx.add_or_get_type(Type::Float);
+        let float3 = ctx.add_or_get_type(float_vec(VS3));
+        let float4 = ctx.add_or_get_type(float_vec(VS4));
+        let float4x4 = mat(&mut ctx, VS4, VS4, None);
+        let check = |args: &[TypeId]| {
+            let args = args.iter().copied().map(Some).collect::<Vec<_>>();
+            ctx.matrix_constructor_problem(VS3, VS3, &args)
+        };
+
+        assert_eq!(check(&[float]), None);
+        assert_eq!(check(&[float4x4]), None);
+        assert_eq!(check(&[float3, float3, float3]), None);
+        assert_eq!(check(&[float; 9]), None);
+        assert_eq!(
+            check(&[float3, float4, float3]),
+            Some(MatrixConstructorProblem::Column { index: 1 })
+        );
+        assert_eq!(
+            check(&[float3, float, float3]),
+            Some(MatrixConstructorProblem::Mixed)
+        );
+        assert_eq!(
+            check(&[float, float, float]),
+            Some(MatrixConstructorProblem::ArgumentCount { found: 3 })
+        );
+        assert_eq!(
+            check(&[float3, float3]),
+            Some(MatrixConstructorProblem::ArgumentCount { found: 2 })
+        );
+        assert_eq!(
+            ctx.matrix_constructor_problem(VS3, VS3, &[None, Some(float3)]),
+            None
+        );
+    }
+}
@@ -0,0 +1,241 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Mesh shading pipelines.
+//!
+//! A mesh shading pipeline replaces the vertex stage with `@mesh` programs,
+//! workgroups that write the vertices and primitives of a small part of a
+//! mesh to their outputs. Optional `@task` programs run before them and
+//! decide how many mesh workgroups are launched. Both run in workgroups like
+//! compute programs, with the same builtin inputs and workgroup variables.
+//!
+//! Every output of a mesh program is an array with an element per vertex or
+//! per primitive, selected with an attribute:
+//!
+//! - `PerVertex`: an element per vertex, the output that also has a
+//!   `Position` attribute holds the clip space positions
+//! - `PerPrimitive`: an element per primitive, passed to the fragments of
+//!   the primitive
+//! - `PrimitiveIndices`: the indices of the three vertices of every
+//!   triangle, as `uint3` elements
+//!
+//! The lengths of the arrays are the most vertices and primitives the
+//! program outputs, so all outputs per vertex have the same length, as do
+//! all outputs per primitive, and the lengths are limited by what the target
+//! supports, [`Context::max_mesh_vertices`] and
+//! [`Context::max_mesh_primitives`].
+//!
+//! The stages are those of the SPIR-V extension `SPV_EXT_mesh_shader`. The
+//! Metal and GLSL ES backends reject mesh shading programs.
+
+use thiol_hir as hir;
+
+use hir::{FileLocation, VariableDef};
+use id_arena::Id;
+
+use crate::stages::{program_stage, Stage};
+use crate::{Context, Error, Symbol, Type, VecSize};
+
+/// The most vertices a mesh program outputs if the target doesn't say, the
+/// minimum the Vulkan mesh shader extension guarantees
+pub const DEFAULT_MAX_MESH_VERTICES: u32 = 256;
+/// The most primitives a mesh program outputs if the target doesn't say
+pub const DEFAULT_MAX_MESH_PRIMITIVES: u32 = 256;
+
+/// What an output of a mesh program has an element for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshOutput {
+    PerVertex,
+    PerPrimitive,
+    /// the vertices of every triangle
+    PrimitiveIndices,
+}
+
+impl MeshOutput {
+    /// The kind of output selected by an attribute with this name
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "PerVertex" => Some(MeshOutput::PerVertex),
+            "PerPrimitive" => Some(MeshOutput::PerPrimitive),
+            "PrimitiveIndices" => Some(MeshOutput::PrimitiveIndices),
+            _ => None,
+        }
+    }
+
+    /// Whether the output has an element per vertex, and not per primitive.
+    pub fn per_vertex(self) -> bool {
+        self == MeshOutput::PerVertex
+    }
+}
+
+/// Why an output of a program is not a valid mesh output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MeshOutputProblem {
+    /// only mesh programs have outputs per vertex and per primitive
+    OutsideMesh { stage: Option<Stage> },
+    /// an output of a mesh program that is neither per vertex nor per
+    /// primitive
+    MissingRate,
+    /// the output is not an array with a length
+    ExpectedArray { found: String },
+    /// the elements of the primitive indices are not triangles
+    ExpectedTriangles { found: String },
+    /// another output with the same rate has a different length
+    LengthMismatch {
+        length: usize,
+        expected: usize,
+        previous: FileLocation,
+    },
+}
+
+/// The kind of mesh output an output of a program is, from its attribute.
+pub fn output_rate(hir_ctx: &hir::Context, def: Id<VariableDef>) -> Option<MeshOutput> {
+    hir_ctx.variable_defs[def].attrs.iter().find_map(|attr| {
+        MeshOutput::from_attribute(&hir_ctx.identifiers[hir_ctx.attributes[*attr].name])
+    })
+}
+
+/// Check the outputs of mesh programs, their lengths against the limits of
+/// the target, and report mesh outputs of other programs.
+pub(crate) fn validate_mesh_outputs(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let max_vertices = ty_ctx
+        .max_mesh_vertices
+        .unwrap_or(DEFAULT_MAX_MESH_VERTICES);
+    let max_primitives = ty_ctx
+        .max_mesh_primitives
+        .unwrap_or(DEFAULT_MAX_MESH_PRIMITIVES);
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        // the first output per vertex and per primitive with its length,
+        // which the others are compared to
+        let mut vertices: Option<(usize, FileLocation)> = None;
+        let mut primitives: Option<(usize, FileLocation)> = None;
+        let mut indices = false;
+
+        for output in &prog.outputs {
+            let rate = output_rate(hir_ctx, *output);
+            let loc = hir_ctx.variable_def_fcs[output];
+            if stage != Some(Stage::Mesh) {
+                if rate.is_some() {
+                    errs.push(Error::InvalidMeshOutput {
+                        output: loc,
+                        problem: MeshOutputProblem::OutsideMesh { stage },
+                    });
+                }
+                continue;
+            }
+            let rate = match rate {
+                Some(rate) => rate,
+                None => {
+                    errs.push(Error::InvalidMeshOutput {
+                        output: loc,
+                        problem: MeshOutputProblem::MissingRate,
+                    });
+                    continue;
+                }
+            };
+            indices |= rate == MeshOutput::PrimitiveIndices;
+
+            let ty = match ty_ctx.references.symbol_type(Symbol::Local(*output)) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            let (base, length) = match ty_ctx.types.get(ty_ctx.strip_distinct(ty)) {
+                Some(Type::Array { base, size }) => (*base, *size),
+                Some(Type::Error) => continue,
+                _ => {
+                    errs.push(Error::InvalidMeshOutput {
+                        output: loc,
+                        problem: MeshOutputProblem::ExpectedArray {
+                            found: ty_ctx.display_type(ty).to_string(),
+                        },
+                    });
+                    continue;
+                }
+            };
+            if rate == MeshOutput::PrimitiveIndices {
+                let triangles = matches!(
+                    ty_ctx.types.get(ty_ctx.strip_distinct(base)),
+                    Some(Type::UIntVec {
+                        components: VecSize::VS3,
+                        ..
+                    }) | Some(Type::Error)
+                );
+                if !triangles {
+                    errs.push(Error::InvalidMeshOutput {
+                        output: loc,
+                        problem: MeshOutputProblem::ExpectedTriangles {
+                            found: ty_ctx.display_type(base).to_string(),
+                        },
+                    });
+                }
+            }
+
+            let (first, limit) = if rate.per_vertex() {
+                (&mut vertices, max_vertices)
+            } else {
+                (&mut primitives, max_primitives)
+            };
+            match first {
+                Some((expected, previous)) if *expected != length => {
+                    errs.push(Error::InvalidMeshOutput {
+                        output: loc,
+                        problem: MeshOutputProblem::LengthMismatch {
+                            length,
+                            expected: *expected,
+                            previous: *previous,
+                        },
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    *first = Some((length, loc));
+                    // the other outputs have the same length or are
+                    // reported, so the limit is checked once
+                    if length > limit as usize {
+                        errs.push(Error::MeshOutputLimit {
+                            output: loc,
+                            per_vertex: rate.per_vertex(),
+                            length,
+                            limit,
+                        });
+                    }
+                }
+            }
+        }
+
+        if stage == Some(Stage::Mesh) && !indices {
+            errs.push(Error::MissingPrimitiveIndices {
+                program_name: hir_ctx.identifiers[prog.name].clone(),
+                program: hir_ctx.identifier_fcs[&prog.name],
+            });
+        }
+    }
+
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::{known_attribute, AttributeTarget};
+
+    #[test]
+    fn output_attributes() {
+        for name in ["PerVertex", "PerPrimitive", "PrimitiveIndices"] {
+            let output = MeshOutput::from_attribute(name).unwrap();
+            assert_eq!(output.per_vertex(), name == "PerVertex");
+            let known = known_attribute(name).unwrap();
+            assert_eq!(known.targets, &[AttributeTarget::Output]);
+        }
+        assert_eq!(MeshOutput::from_attribute("Position"), None);
+    }
+}
@@ -0,0 +1,332 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Instances of generic functions.
+//!
+//! The targets have no generic functions, so a generic function is emitted
+//! once for every list of generic arguments it is called with. Starting from
+//! the code the backends emit, the programs and the functions that aren't
+//! generic, the calls of generic functions are followed with their generic
+//! arguments made concrete, which gives a flat list of the instances without
+//! duplicates. As every instance can call further generic functions with
+//! bigger types, the number of instances is limited.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use hir::{Expression, Function, Statement};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::types::TypeId;
+use crate::{unify, Context, Error, Symbol};
+
+/// The most instances of generic functions a module has by default
+pub const DEFAULT_INSTANTIATION_LIMIT: usize = 256;
+
+/// A generic function with concrete generic arguments
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub func: Id<Function>,
+    pub generics: Vec<TypeId>,
+    /// the concrete types of the types in the signature and body of the
+    /// function that contain generic parameters
+    types: HashMap<TypeId, TypeId>,
+}
+
+impl Instance {
+    /// The type a type of the function has in this instance.
+    pub fn concrete(&self, ty: TypeId) -> TypeId {
+        self.types.get(&ty).copied().unwrap_or(ty)
+    }
+}
+
+impl Context {
+    /// The name of an instance of a generic function, like `pick<float>`.
+    pub fn function_instance_name(&self, name: &str, generics: &[TypeId]) -> String {
+        let args = generics
+            .iter()
+            .map(|ty| self.instance_name(*ty))
+            .collect::<Vec<_>>();
+        format!("{}<{}>", name, args.join(", "))
+    }
+}
+
+pub(crate) fn collect_instances(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> (Vec<Instance>, Vec<Error>) {
+    let limit = ty_ctx
+        .instantiation_limit
+        .unwrap_or(DEFAULT_INSTANTIATION_LIMIT);
+
+    // bodies to look for calls in, with the instance they belong to
+    let mut queue = VecDeque::new();
+    for id in &module.programs {
+        queue.push_back((&hir_ctx.programs[*id].body, None));
+    }
+    for id in &module.functions {
+        if hir_ctx.functions[*id].generics.is_empty() {
+            queue.push_back((&hir_ctx.functions[*id].body, None));
+        }
+    }
+
+    let mut instances: Vec<Instance> = vec![];
+    let mut seen = HashSet::new();
+    while let Some((body, caller)) = queue.pop_front() {
+        let mut exprs = vec![];
+        for stmt in body {
+            statement_expressions(hir_ctx, *stmt, &mut exprs);
+        }
+
+        for expr in exprs {
+            let name = match &hir_ctx.expressions[expr] {
+                Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            let (func, generics) = match (
+                ty_ctx.resolutions.symbol(name),
+                ty_ctx.call_generics.get(&expr),
+            ) {
+                (Some(Symbol::Function(func)), Some(generics)) => (func, generics.clone()),
+                _ => continue,
+            };
+            let generics = match caller {
+                Some(index) => {
+                    let caller: &Instance = &instances[index];
+                    generics.iter().map(|ty| caller.concrete(*ty)).collect()
+                }
+                None => generics,
+            };
+            if !seen.insert((func, generics.clone())) {
+                continue;
+            }
+
+            if instances.len() == limit {
+                let err = Error::InstantiationLimit {
+                    function: hir_ctx.identifiers[name].clone(),
+                    limit,
+                    call: hir_ctx.identifier_fcs[&name],
+                };
+                return (instances, vec![err]);
+            }
+            let types = concrete_types(ty_ctx, hir_ctx, func, &generics);
+            instances.push(Instance {
+                func,
+                generics,
+                types,
+            });
+            queue.push_back((&hir_ctx.functions[func].body, Some(instances.len() - 1)));
+        }
+    }
+
+    (instances, vec![])
+}
+
+/// The concrete types of the generic types of a function.
+fn concrete_types(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+    func: Id<Function>,
+    generics: &[TypeId],
+) -> HashMap<TypeId, TypeId> {
+    let def = &hir_ctx.functions[func];
+    let mut types = (0..def.args.len())
+        .filter_map(|index| {
+            let sym = Symbol::Parameter { func, index };
+            ty_ctx.references.symbol_type(sym)
+        })
+        .collect::<Vec<_>>();
+    if let Some(sig) = ty_ctx.function_sigs.get(&hir_ctx.identifiers[def.name]) {
+        types.extend(sig.args.iter().map(|(_, ty)| *ty));
+        types.push(sig.ret);
+    }
+
+    let mut exprs = vec![];
+    let mut locals = vec![];
+    for stmt in &def.body {
+        statement_expressions(hir_ctx, *stmt, &mut exprs);
+        statement_locals(hir_ctx, *stmt, &mut locals);
+    }
+    for expr in exprs {
+        types.extend(ty_ctx.expr_types.get(&expr));
+        types.extend(ty_ctx.call_generics.get(&expr).into_iter().flatten());
+    }
+    for sym in locals {
+        types.extend(ty_ctx.references.symbol_type(sym));
+    }
+
+    let mut concrete = HashMap::new();
+    for ty in types {
+        let instance = unify::instantiate(ty_ctx, ty, generics);
+        if instance != ty {
+            concrete.insert(ty, instance);
+        }
+    }
+    concrete
+}
+
+/// The local variables, loop variables and values of `match` arms declared
+/// in a statement.
+fn statement_locals(ctx: &hir::Context, id: Id<Statement>, locals: &mut Vec<Symbol>) {
+    match &ctx.statements[id] {
+        Statement::Var(def) => locals.push(Symbol::Local(*def)),
+        Statement::If {
+            then_body,
+            else_body,
+            ..
+        } => {
+            for stmt in then_body.iter().chain(else_body) {
+                statement_locals(ctx, *stmt, locals);
+            }
+        }
+        Statement::For { body, .. } => {
+            locals.push(Symbol::LoopVariable(id));
+            for stmt in body {
+                statement_locals(ctx, *stmt, locals);
+            }
+        }
+        Statement::Match { arms, .. } => {
+            locals.push(Symbol::MatchBinding(id));
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                statement_locals(ctx, *stmt, locals);
+            }
+        }
+        Statement::Becomes { .. }
+        | Statement::Destructure { .. }
+        | Statement::Return(_)
+        | Statement::Expr(_)
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Discard => {}
+    }
+}
+
+/// All expressions in a statement, outer expressions before inner ones.
+fn statement_expressions(ctx: &hir::Context, id: Id<Statement>, exprs: &mut Vec<Id<Expression>>) {
+    match &ctx.statements[id] {
+        Statement::Var(def) => {
+            if let Some(rhs) = ctx.variable_defs[*def].rhs {
+                expressions(ctx, rhs, exprs);
+            }
+        }
+        Statement::Becomes { lhs, rhs } => {
+            expressions(ctx, *lhs, exprs);
+            expressions(ctx, *rhs, exprs);
+        }
+        Statement::Destructure { lhs, rhs } => {
+            for e in lhs {
+                expressions(ctx, *e, exprs);
+            }
+            expressions(ctx, *rhs, exprs);
+        }
+        Statement::Return(e) => {
+            if let Some(e) = e {
+                expressions(ctx, *e, exprs);
+            }
+        }
+        Statement::Expr(e) => expressions(ctx, *e, exprs),
+        Statement::Break | Statement::Continue | Statement::Discard => {}
+        Statement::If {
+            cond,
+            then_body,
+            else_body,
+        } => {
+            expressions(ctx, *cond, exprs);
+            for stmt in then_body.iter().chain(else_body) {
+                statement_expressions(ctx, *stmt, exprs);
+            }
+        }
+        Statement::For { from, to, body, .. } => {
+            expressions(ctx, *from, exprs);
+            expressions(ctx, *to, exprs);
+            for stmt in body {
+                statement_expressions(ctx, *stmt, exprs);
+            }
+        }
+        Statement::Match { value, arms } => {
+            expressions(ctx, *value, exprs);
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                statement_expressions(ctx, *stmt, exprs);
+            }
+        }
+    }
+}
+
+fn expressions(ctx: &hir::Context, id: Id<Expression>, exprs: &mut Vec<Id<Expression>>) {
+    use hir::PrimitiveOp as PO;
+
+    exprs.push(id);
+    match &ctx.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::LayoutQuery { .. } => {}
+        Expression::PrimitiveOp(op) => match &ctx.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => expressions(ctx, *e, exprs),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                expressions(ctx, *a, exprs);
+                expressions(ctx, *b, exprs);
+            }
+            PO::Constructor {
+                ty: _,
+                pos_args,
+                nam_args,
+            } => {
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    expressions(ctx, *e, exprs);
+                }
+            }
+        },
+        Expression::Call {
+            name: _,
+            pos_args,
+            nam_args,
+        } => {
+            for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                expressions(ctx, *e, exprs);
+            }
+        }
+        Expression::Field { base, name: _ } => expressions(ctx, *base, exprs),
+        Expression::Index { base, index } => {
+            expressions(ctx, *base, exprs);
+            expressions(ctx, *index, exprs);
+        }
+        Expression::Slice { base, lo, hi } => {
+            expressions(ctx, *base, exprs);
+            expressions(ctx, *lo, exprs);
+            expressions(ctx, *hi, exprs);
+        }
+        Expression::As { base, ty: _ } => expressions(ctx, *base, exprs),
+        Expression::Array(elements) => {
+            for e in elements {
+                expressions(ctx, *e, exprs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Type;
+
+    #[test]
+    fn instance_names() {
+        let mut ctx = Context::default();
+        let float = ctx.add_or_get_type(Type::Float);
+        let floats = ctx.add_or_get_type(Type::OpenArray { base: float });
+        assert_eq!(
+            ctx.function_instance_name("pick", &[float, floats]),
+            "pick<float, array of float>"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Vectors known to have a length of one.
+//!
+//! `normalized<float3>` is a `float3` whose length is one, like the normals
+//! and directions of lighting code. A normalized vector goes wherever its
+//! vector type goes, but only these values go where a normalized vector is
+//! expected:
+//!
+//! - values of a normalized type, like variables, parameters and calls
+//! - `normalize(v)`
+//! - `-n` and `+n` of a normalized `n`
+//! - `reflect(i, n)` of a normalized `i`
+//! - constructors of unit axes from literals, like `float3(0.0, -1.0, 0.0)`
+//!
+//! Everything else, including arithmetic on normalized vectors, gives a
+//! vector that isn't known to be normalized. A value known to be normalized
+//! by other means is cast with `as normalized<float3>`. The normals of
+//! `reflect` and `refract` and the incident vector of `refract` have to be
+//! normalized.
+
+use hir::{Expression, Literal, PrimitiveOp};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::{Context, Error, Intrinsic, Type, TypeId};
+
+impl Context {
+    /// Whether values of the type are normalized vectors.
+    pub fn is_normalized(&self, ty: TypeId) -> bool {
+        matches!(self.types.get(ty), Some(Type::Normalized { .. }))
+    }
+
+    /// The vector type of a normalized type, other types stay as they are.
+    pub(crate) fn strip_normalized(&self, ty: TypeId) -> TypeId {
+        match self.types.get(ty) {
+            Some(Type::Normalized { inner }) => *inner,
+            _ => ty,
+        }
+    }
+
+    /// The normalized type of a vector of floating point numbers, `None` for
+    /// other types.
+    pub(crate) fn normalized_type(&mut self, ty: TypeId) -> Option<TypeId> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Normalized { .. } => Some(ty),
+            Type::FloatVec { .. } | Type::DoubleVec { .. } | Type::HalfVec { .. } => {
+                Some(self.add_or_get_type(Type::Normalized { inner: ty }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A vector assigned, passed or returned where a normalized vector is
+/// expected, which has to be normalized already or an axis with literal
+/// components like `float3(0.0, 1.0, 0.0)`. `found` is `None` for arithmetic,
+/// which is told apart by its operands.
+pub(crate) fn check_value(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+    found: Option<TypeId>,
+    expected: TypeId,
+) -> Option<Error> {
+    if !ty_ctx.is_normalized(expected) || normalized(ty_ctx, hir_ctx, expr, found)? {
+        return None;
+    }
+    Some(Error::NotNormalized {
+        expr: hir_ctx.expression_fcs[&expr],
+        intrinsic: None,
+    })
+}
+
+/// An argument of an intrinsic that has to be normalized.
+pub(crate) fn check_argument(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    intrinsic: Intrinsic,
+    arg: Id<Expression>,
+    ty: Option<TypeId>,
+) -> Option<Error> {
+    if normalized(ty_ctx, hir_ctx, arg, ty)? {
+        return None;
+    }
+    Some(Error::NotNormalized {
+        expr: hir_ctx.expression_fcs[&arg],
+        intrinsic: Some(intrinsic),
+    })
+}
+
+/// Whether the value of an expression of type `ty` is known to be
+/// normalized, `None` if it can't be told because of errors.
+fn normalized(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+    ty: Option<TypeId>,
+) -> Option<bool> {
+    if let Some(ty) = ty {
+        return match ty_ctx.types.get(ty)? {
+            Type::Error => None,
+            Type::Normalized { .. } => Some(true),
+            _ => Some(is_unit_axis(hir_ctx, expr)),
+        };
+    }
+
+    // arithmetic has no type, its operands are enough to tell
+    match &hir_ctx.expressions[expr] {
+        Expression::PrimitiveOp(op) => match &hir_ctx.prim_ops[*op] {
+            PrimitiveOp::Neg(e) | PrimitiveOp::Pos(e) => {
+                normalized(ty_ctx, hir_ctx, *e, ty_ctx.expr_types.get(e).copied())
+            }
+            PrimitiveOp::Add(..)
+            | PrimitiveOp::Sub(..)
+            | PrimitiveOp::Mul(..)
+            | PrimitiveOp::Div(..)
+            | PrimitiveOp::Mod(..) => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether an expression is a vector constructor with literal components, one
+/// of them 1 or -1 and the others 0.
+fn is_unit_axis(hir_ctx: &hir::Context, expr: Id<Expression>) -> bool {
+    let args = match &hir_ctx.expressions[expr] {
+        Expression::PrimitiveOp(op) => match &hir_ctx.prim_ops[*op] {
+            PrimitiveOp::Constructor {
+                pos_args, nam_args, ..
+            } if nam_args.is_empty() && pos_args.len() > 1 => pos_args,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    let mut ones = 0;
+    for arg in args {
+        match literal_value(hir_ctx, *arg) {
+            Some(value) if value.abs() == 1.0 => ones += 1,
+            Some(0.0) => {}
+            _ => return false,
+        }
+    }
+    ones == 1
+}
+
+fn literal_value(hir_ctx: &hir::Context, expr: Id<Expression>) -> Option<f64> {
+    match &hir_ctx.expressions[expr] {
+        Expression::Literal(Literal::Float(value, _)) => Some(*value),
+        Expression::Literal(Literal::Integer(value, _)) => Some(*value as f64),
+        Expression::PrimitiveOp(op) => match &hir_ctx.prim_ops[*op] {
+            PrimitiveOp::Neg(e) => literal_value(hir_ctx, *e).map(|value| -value),
+            PrimitiveOp::Pos(e) => literal_value(hir_ctx, *e),
+            _ => None,
+        },
+        _ => None,
+    }
+}
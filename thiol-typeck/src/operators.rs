@@ -0,0 +1,470 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The types of the primitive operators.
+//!
+//! `+`, `-`, `*`, `/` and `mod` take two numbers of the same type, two
+//! vectors of the same type, which they apply to each component, or a
+//! vector and a scalar of its component type, which goes with every
+//! component. Matrices are added to and subtracted from matrices of the same
+//! type and multiplied with and divided by their scalar. `*` of a matrix
+//! and a vector or another matrix is their product:
+//!
+//! - `floatCxR * floatC` is a `floatR`
+//! - `floatR * floatCxR` is a `floatC`
+//! - `floatKxR * floatCxK` is a `floatCxR`
+//!
+//! The product of a transform between two spaces and a vector is in the
+//! space the transform leads to. `-` and `+` of a single operand take
+//! numbers, vectors and matrices.
+//!
+//! `<`, `<=`, `>` and `>=` compare numbers of the same type, `=` and `<>`
+//! also booleans. Vectors are compared component by component, which gives
+//! a vector of booleans.
+//!
+//! Vectors in different spaces and colours in different encodings go
+//! together here, spaces are checked where values are stored and encodings
+//! in [`crate::colours`]. The units of arithmetic on angles come from
+//! [`crate::angles`], other arithmetic on angles treats them as `float`s.
+
+use hir::PrimitiveOp;
+use thiol_hir as hir;
+
+use crate::{Context, Conversion, Type, TypeId, VecSize, VecType};
+
+/// The symbol of an operator, like `+` or `mod`.
+pub fn symbol(op: &PrimitiveOp) -> &'static str {
+    use PrimitiveOp as PO;
+
+    match op {
+        PO::Neg(_) | PO::Sub(..) => "-",
+        PO::Pos(_) | PO::Add(..) => "+",
+        PO::Mul(..) => "*",
+        PO::Div(..) => "/",
+        PO::Mod(..) => "mod",
+        PO::Gt(..) => ">",
+        PO::Gte(..) => ">=",
+        PO::Lt(..) => "<",
+        PO::Lte(..) => "<=",
+        PO::Eq(..) => "=",
+        PO::Neq(..) => "<>",
+        PO::Constructor { .. } => "",
+    }
+}
+
+/// A value a primitive operator can take
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    Scalar(Type),
+    Vector(Type, VecSize),
+    Matrix(Type, VecSize, VecSize),
+}
+
+impl Context {
+    fn operand(&self, ty: TypeId) -> Option<Operand> {
+        let ty = self
+            .types
+            .get(self.strip_normalized(self.strip_distinct(ty)))?;
+        let operand = match ty {
+            Type::Bool => Operand::Scalar(Type::Bool),
+            Type::BoolVec { components } => Operand::Vector(Type::Bool, *components),
+            Type::FloatMat { cols, rows, .. } => Operand::Matrix(Type::Float, *cols, *rows),
+            Type::DoubleMat { cols, rows, .. } => Operand::Matrix(Type::Double, *cols, *rows),
+            Type::IntVec { components, .. }
+            | Type::UIntVec { components, .. }
+            | Type::LongVec { components, .. }
+            | Type::ULongVec { components, .. }
+            | Type::FloatVec { components, .. }
+            | Type::DoubleVec { components, .. }
+            | Type::HalfVec { components, .. } => Operand::Vector(ty.scalar()?, *components),
+            Type::AtomicInt | Type::AtomicUInt => return None,
+            _ => Operand::Scalar(ty.scalar()?),
+        };
+        Some(operand)
+    }
+
+    /// Whether the type of an operand isn't known, because it has an error
+    /// or is generic.
+    pub(crate) fn is_unknown_operand(&self, ty: TypeId) -> bool {
+        matches!(
+            self.types.get(self.strip_distinct(ty)),
+            Some(Type::Error | Type::Var(_) | Type::GenericParam { .. }) | None
+        )
+    }
+
+    /// The type of `-a` or `+a` of an operand of type `a`, `None` if the
+    /// operator doesn't take the operand. The negation of a normalized
+    /// vector stays normalized.
+    pub fn unary_type(&self, a: TypeId) -> Option<TypeId> {
+        match self.operand(a)? {
+            Operand::Scalar(Type::Bool) | Operand::Vector(Type::Bool, _) => None,
+            _ => Some(a),
+        }
+    }
+
+    /// The type of the binary operation `op` on operands of the types `a`
+    /// and `b`, `None` if the operator doesn't take the operands.
+    pub fn binary_type(&mut self, op: &PrimitiveOp, a: TypeId, b: TypeId) -> Option<TypeId> {
+        use PrimitiveOp as PO;
+
+        let (a_op, b_op) = (self.operand(a)?, self.operand(b)?);
+        let eq = match op {
+            PO::Add(..) | PO::Sub(..) | PO::Mul(..) | PO::Div(..) | PO::Mod(..) => None,
+            PO::Eq(..) | PO::Neq(..) => Some(true),
+            PO::Gt(..) | PO::Gte(..) | PO::Lt(..) | PO::Lte(..) => Some(false),
+            PO::Neg(_) | PO::Pos(_) | PO::Constructor { .. } => return None,
+        };
+        let (a_op, b_op) = match eq {
+            Some(_) => (a_op, b_op),
+            None => (unitless(a_op), unitless(b_op)),
+        };
+        let (a, b) = (self.strip_normalized(a), self.strip_normalized(b));
+        let ((a, a_op), (b, b_op)) = self.promote((a, a_op), (b, b_op))?;
+        if let Some(eq) = eq {
+            return self.comparison_type(a_op, b_op, eq);
+        }
+        if is_bool(&a_op) || is_bool(&b_op) {
+            return None;
+        }
+        let sum = matches!(op, PO::Add(..) | PO::Sub(..));
+        let product = matches!(op, PO::Mul(..));
+        let scales = matches!(op, PO::Mul(..) | PO::Div(..));
+        match (a_op, b_op) {
+            (Operand::Scalar(x), Operand::Scalar(y)) if x == y => Some(self.either(a, b)),
+            (Operand::Vector(x, n), Operand::Vector(y, m)) if x == y && n == m => {
+                Some(self.either(a, b))
+            }
+            (Operand::Vector(x, _), Operand::Scalar(y)) if x == y => Some(a),
+            (Operand::Scalar(x), Operand::Vector(y, _)) if x == y => Some(b),
+            (Operand::Matrix(x, c, r), Operand::Matrix(y, d, s))
+                if x == y && c == d && r == s && sum =>
+            {
+                Some(self.either(a, b))
+            }
+            (Operand::Matrix(x, ..), Operand::Scalar(y)) if x == y && scales => Some(a),
+            (Operand::Scalar(x), Operand::Matrix(y, ..)) if x == y && product => Some(b),
+            (Operand::Matrix(x, cols, rows), Operand::Vector(y, n)) if x == y && cols == n => {
+                let space = self.transform(a).map(|(_, to)| to);
+                Some(self.vector_like(b, rows, space))
+            }
+            (Operand::Vector(x, n), Operand::Matrix(y, cols, rows)) if x == y && rows == n => {
+                Some(self.vector_like(a, cols, None))
+            }
+            (Operand::Matrix(x, k, rows), Operand::Matrix(y, cols, l))
+                if x == y && k == l && product =>
+            {
+                let transform = match (self.transform(a), self.transform(b)) {
+                    (Some((from, to)), Some((first, second))) if from == second => {
+                        Some((first, to))
+                    }
+                    _ => None,
+                };
+                let ty = match x {
+                    Type::Double => Type::DoubleMat {
+                        cols,
+                        rows,
+                        transform,
+                    },
+                    _ => Type::FloatMat {
+                        cols,
+                        rows,
+                        transform,
+                    },
+                };
+                Some(self.add_or_get_type(ty))
+            }
+            _ => None,
+        }
+    }
+
+    /// The type of a comparison, `eq` for `=` and `<>`.
+    fn comparison_type(&mut self, a: Operand, b: Operand, eq: bool) -> Option<TypeId> {
+        if a != b || (!eq && is_bool(&a)) {
+            return None;
+        }
+        let ty = match a {
+            Operand::Scalar(_) => Type::Bool,
+            Operand::Vector(_, components) => Type::BoolVec { components },
+            Operand::Matrix(..) => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// Convert operands with different component types to the component
+    /// type both convert to implicitly, like `float` for `half` and `float`.
+    fn promote(
+        &mut self,
+        (a, a_op): (TypeId, Operand),
+        (b, b_op): (TypeId, Operand),
+    ) -> Option<((TypeId, Operand), (TypeId, Operand))> {
+        let (x, y) = (a_op.scalar().clone(), b_op.scalar().clone());
+        if x == y {
+            return Some(((a, a_op), (b, b_op)));
+        }
+        let (x_id, y_id) = (
+            self.add_or_get_type(x.clone()),
+            self.add_or_get_type(y.clone()),
+        );
+        let implicit = |from, to| self.conversion(from, to) == Some(Conversion::Implicit);
+        let common = match (implicit(x_id, y_id), implicit(y_id, x_id)) {
+            // only `half` and `float` convert to each other
+            (true, true) => Type::Float,
+            (true, false) => y,
+            (false, true) => x,
+            (false, false) => return None,
+        };
+        let mut convert = |ty: TypeId, op: Operand| -> Option<(TypeId, Operand)> {
+            if *op.scalar() == common {
+                return Some((ty, op));
+            }
+            Some((
+                self.with_scalar(ty, &common)?,
+                op.with_scalar(common.clone()),
+            ))
+        };
+        Some((convert(a, a_op)?, convert(b, b_op)?))
+    }
+
+    /// The type with the shape of `ty` and the component type `scalar`.
+    fn with_scalar(&mut self, ty: TypeId, scalar: &Type) -> Option<TypeId> {
+        let ty = self.types.get(self.strip_distinct(ty))?.clone();
+        let converted = match ty {
+            Type::IntVec {
+                components,
+                vtype,
+                space,
+            }
+            | Type::UIntVec {
+                components,
+                vtype,
+                space,
+            }
+            | Type::LongVec {
+                components,
+                vtype,
+                space,
+            }
+            | Type::ULongVec {
+                components,
+                vtype,
+                space,
+            }
+            | Type::FloatVec {
+                components,
+                vtype,
+                space,
+            }
+            | Type::DoubleVec {
+                components,
+                vtype,
+                space,
+            }
+            | Type::HalfVec {
+                components,
+                vtype,
+                space,
+            } => match scalar {
+                Type::Int => Type::IntVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                Type::UInt => Type::UIntVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                Type::Long => Type::LongVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                Type::ULong => Type::ULongVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                Type::Float => Type::FloatVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                Type::Double => Type::DoubleVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                Type::Half => Type::HalfVec {
+                    components,
+                    vtype,
+                    space,
+                },
+                _ => return None,
+            },
+            Type::FloatMat { .. } | Type::DoubleMat { .. } => return None,
+            _ => scalar.clone(),
+        };
+        Some(self.add_or_get_type(converted))
+    }
+
+    /// The type of an operation whose operands have the same type apart from
+    /// their space, encoding or distinct type, which is the distinct type if
+    /// one of them has one.
+    fn either(&self, a: TypeId, b: TypeId) -> TypeId {
+        let distinct = |ty| matches!(self.types.get(ty), Some(Type::Distinct { .. }));
+        if !distinct(a) && distinct(b) {
+            b
+        } else {
+            a
+        }
+    }
+
+    fn transform(&self, ty: TypeId) -> Option<(crate::Name, crate::Name)> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::FloatMat { transform, .. } | Type::DoubleMat { transform, .. } => *transform,
+            _ => None,
+        }
+    }
+
+    /// A vector with the component type and kind of the vector `ty`, but
+    /// `components` components in `space`.
+    fn vector_like(
+        &mut self,
+        ty: TypeId,
+        components: VecSize,
+        space: Option<crate::Name>,
+    ) -> TypeId {
+        let ty = self.strip_normalized(self.strip_distinct(ty));
+        let vector = match self.types.get(ty) {
+            Some(Type::FloatVec { vtype, .. }) => Type::FloatVec {
+                components,
+                vtype: *vtype,
+                space,
+            },
+            Some(Type::DoubleVec { vtype, .. }) => Type::DoubleVec {
+                components,
+                vtype: *vtype,
+                space,
+            },
+            _ => Type::FloatVec {
+                components,
+                vtype: VecType::Unknown,
+                space,
+            },
+        };
+        self.add_or_get_type(vector)
+    }
+}
+
+impl Operand {
+    fn scalar(&self) -> &Type {
+        match self {
+            Operand::Scalar(ty) | Operand::Vector(ty, _) | Operand::Matrix(ty, ..) => ty,
+        }
+    }
+
+    fn with_scalar(self, scalar: Type) -> Operand {
+        match self {
+            Operand::Scalar(_) => Operand::Scalar(scalar),
+            Operand::Vector(_, n) => Operand::Vector(scalar, n),
+            Operand::Matrix(_, c, r) => Operand::Matrix(scalar, c, r),
+        }
+    }
+}
+
+/// Angles as the `float`s they are.
+fn unitless(operand: Operand) -> Operand {
+    let float = |ty: Type| match ty {
+        Type::Radians | Type::Degrees => Type::Float,
+        ty => ty,
+    };
+    match operand {
+        Operand::Scalar(ty) => Operand::Scalar(float(ty)),
+        operand => operand,
+    }
+}
+
+fn is_bool(operand: &Operand) -> bool {
+    matches!(
+        operand,
+        Operand::Scalar(Type::Bool) | Operand::Vector(Type::Bool, _)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id_arena::Arena;
+
+    fn float_vec(ctx: &mut Context, components: VecSize) -> TypeId {
+        ctx.add_or_get_type(Type::FloatVec {
+            components,
+            vtype: VecType::Unknown,
+            space: None,
+        })
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut exprs: Arena<hir::Expression> = Arena::new();
+        let (a, b) = (
+            exprs.alloc(hir::Expression::Literal(hir::Literal::Bool(true))),
+            exprs.alloc(hir::Expression::Literal(hir::Literal::Bool(false))),
+        );
+        let (add, mul, div) = (
+            PrimitiveOp::Add(a, b),
+            PrimitiveOp::Mul(a, b),
+            PrimitiveOp::Div(a, b),
+        );
+
+        let mut ctx = Context::default();
+        let int = ctx.add_or_get_type(Type::Int);
+        let float = ctx.add_or_get_type(Type::Float);
+        let radians = ctx.add_or_get_type(Type::Radians);
+        let float2 = float_vec(&mut ctx, VecSize::VS2);
+        let float3 = float_vec(&mut ctx, VecSize::VS3);
+        let float3x2 = ctx.add_or_get_type(Type::FloatMat {
+            cols: VecSize::VS3,
+            rows: VecSize::VS2,
+            transform: None,
+        });
+
+        assert_eq!(ctx.binary_type(&add, int, int), Some(int));
+        assert_eq!(ctx.binary_type(&add, int, float), None);
+        assert_eq!(ctx.binary_type(&mul, float, float3), Some(float3));
+        assert_eq!(ctx.binary_type(&div, float3, float), Some(float3));
+        assert_eq!(ctx.binary_type(&add, float3, float2), None);
+        assert_eq!(ctx.binary_type(&mul, float3x2, float3), Some(float2));
+        assert_eq!(ctx.binary_type(&mul, float2, float3x2), Some(float3));
+        assert_eq!(ctx.binary_type(&mul, float3, float3x2), None);
+        assert_eq!(ctx.binary_type(&add, float3x2, float), None);
+        assert_eq!(ctx.binary_type(&mul, radians, float3), Some(float3));
+    }
+
+    #[test]
+    fn comparisons() {
+        let mut exprs: Arena<hir::Expression> = Arena::new();
+        let (a, b) = (
+            exprs.alloc(hir::Expression::Literal(hir::Literal::Bool(true))),
+            exprs.alloc(hir::Expression::Literal(hir::Literal::Bool(false))),
+        );
+        let (lt, eq) = (PrimitiveOp::Lt(a, b), PrimitiveOp::Eq(a, b));
+
+        let mut ctx = Context::default();
+        let boolean = ctx.add_or_get_type(Type::Bool);
+        let bool3 = ctx.add_or_get_type(Type::BoolVec {
+            components: VecSize::VS3,
+        });
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let float = ctx.add_or_get_type(Type::Float);
+        let float3 = float_vec(&mut ctx, VecSize::VS3);
+
+        assert_eq!(ctx.binary_type(&lt, uint, uint), Some(boolean));
+        assert_eq!(ctx.binary_type(&lt, uint, float), None);
+        assert_eq!(ctx.binary_type(&lt, float3, float3), Some(bool3));
+        assert_eq!(ctx.binary_type(&eq, boolean, boolean), Some(boolean));
+        assert_eq!(ctx.binary_type(&lt, boolean, boolean), None);
+        assert_eq!(ctx.binary_type(&eq, float3, float), None);
+    }
+}
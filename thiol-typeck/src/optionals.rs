@@ -0,0 +1,257 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Values that may be missing.
+//!
+//! `optional<Hit>` is either a `Hit` or no value, like the result of a ray
+//! intersection that may not hit anything. `some(hit)` is an optional with
+//! a value and `none()` one without, whose type comes from where it is used
+//! like the type of `identity()`. The value of an optional is only reached
+//! by handling the missing case as well:
+//!
+//! - `unwrap_or(o, default)` is the value of `o`, or `default` without one
+//! - `match o some(hit) then ... none then ... end` runs the arm for the
+//!   case of `o`, the `some` arm has the value as `hit`
+//!
+//! A `match` has exactly one arm for each case. An optional can't be used
+//! where its value is expected, and a value has to be wrapped with `some`
+//! where an optional is expected. Resources, atomics and runtime sized
+//! arrays can't be optional.
+
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, MatchPattern, Statement};
+use id_arena::Id;
+
+use crate::{Context, Error, Type, TypeId};
+
+/// What is wrong with an optional or its handling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionalProblem {
+    /// `optional<T>` or `some(v)` of a type that can't be optional
+    Inner(String),
+    /// `unwrap_or` or `match` of a value that isn't an optional
+    NotOptional(String),
+    /// `none()` where no optional type is expected
+    UninferableNone,
+    /// `some`, `none` or `unwrap_or` with another number of values
+    Arity { expected: usize, found: usize },
+    /// the default of `unwrap_or` of another type than the value
+    DefaultType { expected: String, found: String },
+    /// an optional used where its value is expected, or whose value is
+    /// accessed without handling the missing case
+    Unhandled(String),
+    /// a value used where an optional is expected
+    NotWrapped(String),
+    /// a `match` without an arm for the case
+    MissingArm(&'static str),
+    /// a `match` with a second arm for the case
+    DuplicateArm(&'static str),
+}
+
+impl fmt::Display for OptionalProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionalProblem::Inner(type_name) => {
+                write!(f, "`{}` can't be optional", type_name)
+            }
+            OptionalProblem::NotOptional(type_name) => {
+                write!(f, "`{}` is not an optional", type_name)
+            }
+            OptionalProblem::UninferableNone => {
+                write!(f, "the optional type of `none()` isn't known here")
+            }
+            OptionalProblem::Arity { expected, found } => {
+                write!(f, "expected {} values, found {}", expected, found)
+            }
+            OptionalProblem::DefaultType { expected, found } => {
+                write!(f, "expected `{}`, found `{}`", expected, found)
+            }
+            OptionalProblem::Unhandled(type_name) => {
+                write!(f, "this `{}` may have no value", type_name)
+            }
+            OptionalProblem::NotWrapped(type_name) => {
+                write!(f, "`{}` is not an optional", type_name)
+            }
+            OptionalProblem::MissingArm(case) => write!(f, "no `{}` arm", case),
+            OptionalProblem::DuplicateArm(case) => write!(f, "second `{}` arm", case),
+        }
+    }
+}
+
+impl Context {
+    /// The type of the value of an optional type, `None` for other types.
+    pub fn optional_inner(&self, ty: TypeId) -> Option<TypeId> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Optional { inner } => Some(*inner),
+            _ => None,
+        }
+    }
+
+    /// The optional type of values of the type, `None` for types that can't
+    /// be optional.
+    pub(crate) fn optional_type(&mut self, inner: TypeId) -> Option<TypeId> {
+        match self.types.get(self.strip_distinct(inner))? {
+            Type::AtomicInt
+            | Type::AtomicUInt
+            | Type::OpenArray { .. }
+            | Type::Image { .. }
+            | Type::Texture { .. }
+            | Type::Sampler { .. }
+            | Type::AccelerationStructure => None,
+            _ => Some(self.add_or_get_type(Type::Optional { inner })),
+        }
+    }
+
+    /// Whether it can't be told if values of the type are optionals, because
+    /// of errors or generic parameters.
+    fn is_unknown_optional(&self, ty: TypeId) -> bool {
+        matches!(
+            self.types.get(self.strip_distinct(ty)),
+            Some(Type::Error | Type::Var(_) | Type::GenericParam { .. }) | None
+        )
+    }
+}
+
+/// An optional used where a plain value is expected, which has to be
+/// unwrapped first, or a plain value where an optional is expected, which
+/// has to be wrapped in `some`.
+pub(crate) fn check_value(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+    found: TypeId,
+    expected: TypeId,
+) -> Option<Error> {
+    if ty_ctx.is_unknown_optional(found) || ty_ctx.is_unknown_optional(expected) {
+        return None;
+    }
+    let problem = match (
+        ty_ctx.optional_inner(found),
+        ty_ctx.optional_inner(expected),
+    ) {
+        (Some(_), None) => OptionalProblem::Unhandled(ty_ctx.display_type(found).to_string()),
+        (None, Some(_)) => OptionalProblem::NotWrapped(ty_ctx.display_type(found).to_string()),
+        _ => return None,
+    };
+    Some(Error::InvalidOptional {
+        loc: hir_ctx.expression_fcs[&expr],
+        problem,
+    })
+}
+
+/// The base of a field access or an index of type `ty`, which can't be an
+/// optional.
+pub(crate) fn check_access(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    base: Id<Expression>,
+    ty: TypeId,
+) -> Option<Error> {
+    ty_ctx.optional_inner(ty)?;
+    Some(Error::InvalidOptional {
+        loc: hir_ctx.expression_fcs[&base],
+        problem: OptionalProblem::Unhandled(ty_ctx.display_type(ty).to_string()),
+    })
+}
+
+/// The `match` statements in a block and the blocks nested in it.
+fn matches(ctx: &hir::Context, block: &[Id<Statement>], found: &mut Vec<Id<Statement>>) {
+    for stmt in block {
+        match &ctx.statements[*stmt] {
+            Statement::Match { arms, .. } => {
+                found.push(*stmt);
+                for arm in arms {
+                    matches(ctx, &arm.body, found);
+                }
+            }
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                matches(ctx, then_body, found);
+                matches(ctx, else_body, found);
+            }
+            Statement::For { body, .. } => matches(ctx, body, found),
+            Statement::Var(_)
+            | Statement::Becomes { .. }
+            | Statement::Destructure { .. }
+            | Statement::Return(_)
+            | Statement::Expr(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Discard => {}
+        }
+    }
+}
+
+/// The problems of a `match` of the optional `value` with the arms, with
+/// the location each is reported at.
+fn problems(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    stmt: Id<Statement>,
+    value: Id<Expression>,
+    arms: &[hir::MatchArm],
+) -> Vec<(hir::FileLocation, OptionalProblem)> {
+    let mut problems = vec![];
+    // values without a type are reported where they are computed
+    if let Some(ty) = ty_ctx.expr_types.get(&value) {
+        if ty_ctx.optional_inner(*ty).is_none() && !ty_ctx.is_unknown_optional(*ty) {
+            let type_name = ty_ctx.display_type(*ty).to_string();
+            problems.push((
+                hir_ctx.expression_fcs[&value],
+                OptionalProblem::NotOptional(type_name),
+            ));
+        }
+    }
+
+    for case in ["some", "none"] {
+        let mut arms = arms.iter().filter(|arm| match arm.pattern {
+            MatchPattern::Some(_) => case == "some",
+            MatchPattern::None => case == "none",
+        });
+        if arms.next().is_none() {
+            let loc = hir_ctx.statement_fcs[&stmt];
+            problems.push((loc, OptionalProblem::MissingArm(case)));
+        }
+        for arm in arms {
+            problems.push((arm.loc, OptionalProblem::DuplicateArm(case)));
+        }
+    }
+    problems
+}
+
+/// Report `match` statements of values that aren't optionals, and those
+/// that don't have exactly one arm for each case.
+pub(crate) fn check_matches(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut found = vec![];
+    let bodies = module
+        .functions
+        .iter()
+        .map(|id| &hir_ctx.functions[*id].body)
+        .chain(module.programs.iter().map(|id| &hir_ctx.programs[*id].body));
+    for body in bodies {
+        matches(hir_ctx, body, &mut found);
+    }
+
+    let mut errs = vec![];
+    for stmt in found {
+        let (value, arms) = match &hir_ctx.statements[stmt] {
+            Statement::Match { value, arms } => (*value, arms),
+            _ => continue,
+        };
+        for (loc, problem) in problems(ty_ctx, hir_ctx, stmt, value, arms) {
+            errs.push(Error::InvalidOptional { loc, problem });
+        }
+    }
+    errs
+}
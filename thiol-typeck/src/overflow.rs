@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! What happens when integer arithmetic overflows.
+//!
+//! The intrinsics `wrapping_add`, `wrapping_sub` and `wrapping_mul` wrap
+//! around on every target, `saturating_add` and `saturating_sub` clamp the
+//! result to the range of the type. Plain `+`, `-` and `*` on integers do
+//! what the policy of the [`Context`] says: `wrap` wraps them around,
+//! `saturate` clamps sums and differences and wraps products, and
+//! `undefined` leaves them to the target, where signed overflow is
+//! undefined on Metal. The plain operations on signed integers are reported
+//! in the `integer-overflow` lint group while the policy is `undefined`.
+//!
+//! Integer literals have to fit in the type they have, from their suffix or
+//! from where they are used, a negated literal may be the smallest value of
+//! a signed type.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use hir::{Expression, Literal, PrimitiveOp};
+use thiol_hir as hir;
+
+use crate::{lints, Context, Error, Type, TypeId, Warning};
+
+/// What plain integer arithmetic does when it overflows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum IntegerOverflow {
+    /// wrap around, like GLSL does
+    Wrap,
+    /// clamp sums and differences to the range of the type
+    Saturate,
+    /// leave it to the target
+    #[default]
+    Undefined,
+}
+
+impl IntegerOverflow {
+    pub const ALL: &'static [IntegerOverflow] = &[
+        IntegerOverflow::Wrap,
+        IntegerOverflow::Saturate,
+        IntegerOverflow::Undefined,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntegerOverflow::Wrap => "wrap",
+            IntegerOverflow::Saturate => "saturate",
+            IntegerOverflow::Undefined => "undefined",
+        }
+    }
+}
+
+impl FromStr for IntegerOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IntegerOverflow::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| {
+                let names = IntegerOverflow::ALL
+                    .iter()
+                    .map(|p| p.name())
+                    .collect::<Vec<_>>();
+                format!(
+                    "unknown integer overflow `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for IntegerOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Context {
+    /// The scalar type of integer scalars and vectors, `None` for other
+    /// types.
+    pub fn integer_scalar(&self, ty: TypeId) -> Option<Type> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Int | Type::IntVec { .. } => Some(Type::Int),
+            Type::UInt | Type::UIntVec { .. } => Some(Type::UInt),
+            Type::Long | Type::LongVec { .. } => Some(Type::Long),
+            Type::ULong | Type::ULongVec { .. } => Some(Type::ULong),
+            _ => None,
+        }
+    }
+
+    /// Whether the type is an integer scalar or vector.
+    pub fn is_integer(&self, ty: TypeId) -> bool {
+        self.integer_scalar(ty).is_some()
+    }
+
+    /// The type of a sum, difference or product of integers with operands of
+    /// the given types, which is the vector when a vector is combined with a
+    /// scalar. `None` if the operands aren't integers.
+    pub fn integer_arithmetic_type(&self, a: Option<TypeId>, b: Option<TypeId>) -> Option<TypeId> {
+        let (a, b) = (a?, b?);
+        if !self.is_integer(a) || !self.is_integer(b) {
+            return None;
+        }
+        let vector = |ty| {
+            matches!(
+                self.types.get(self.strip_distinct(ty)),
+                Some(
+                    Type::IntVec { .. }
+                        | Type::UIntVec { .. }
+                        | Type::LongVec { .. }
+                        | Type::ULongVec { .. }
+                )
+            )
+        };
+        Some(if !vector(a) && vector(b) { b } else { a })
+    }
+}
+
+/// The smallest and the largest value of an integer type.
+pub fn integer_range(ty: Type) -> Option<(i128, i128)> {
+    match ty {
+        Type::Int => Some((i32::MIN.into(), i32::MAX.into())),
+        Type::UInt => Some((0, u32::MAX.into())),
+        Type::Long => Some((i64::MIN.into(), i64::MAX.into())),
+        Type::ULong => Some((0, u64::MAX.into())),
+        _ => None,
+    }
+}
+
+/// Report the integer literals of a module whose value doesn't fit in their
+/// type, which needs the types of the literals.
+pub(crate) fn check_literal_ranges(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut exprs = lints::expressions(module, hir_ctx);
+    for id in &module.consts {
+        if let Some(rhs) = hir_ctx.variable_defs[*id].rhs {
+            lints::expression_exprs(hir_ctx, rhs, &mut exprs);
+        }
+    }
+    for id in &module.static_asserts {
+        lints::expression_exprs(hir_ctx, hir_ctx.static_asserts[*id].condition, &mut exprs);
+    }
+
+    // negated literals are reported with their minus sign
+    let negated = exprs
+        .iter()
+        .filter_map(|id| match hir_ctx.expressions[*id] {
+            Expression::PrimitiveOp(op) => match hir_ctx.prim_ops[op] {
+                PrimitiveOp::Neg(e) => Some((e, *id)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    exprs
+        .into_iter()
+        .filter_map(|id| {
+            let value = match hir_ctx.expressions[id] {
+                Expression::Literal(Literal::Integer(value, _)) => value,
+                _ => return None,
+            };
+            let ty = *ty_ctx.expr_types.get(&id)?;
+            let (min, max) = integer_range(ty_ctx.integer_scalar(ty)?)?;
+            let (value, literal) = match negated.get(&id) {
+                Some(neg) => (-value, *neg),
+                None => (value, id),
+            };
+            if (min..=max).contains(&value) {
+                return None;
+            }
+            Some(Error::IntegerLiteralOutOfRange {
+                literal: *hir_ctx.expression_fcs.get(&literal)?,
+                value,
+                ty: ty_ctx.display_type(ty).to_string(),
+                min,
+                max,
+            })
+        })
+        .collect()
+}
+
+/// Report the plain `+`, `-` and `*` on signed integers in function and
+/// program bodies when the policy leaves their overflow to the target.
+pub(crate) fn check_overflow(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Warning> {
+    if ty_ctx.integer_overflow != IntegerOverflow::Undefined {
+        return vec![];
+    }
+    lints::primitive_ops(module, hir_ctx)
+        .into_iter()
+        .filter_map(|(id, op)| {
+            let (op, a, b) = match hir_ctx.prim_ops[op] {
+                PrimitiveOp::Add(a, b) => ("+", a, b),
+                PrimitiveOp::Sub(a, b) => ("-", a, b),
+                PrimitiveOp::Mul(a, b) => ("*", a, b),
+                _ => return None,
+            };
+            let operand = |e| ty_ctx.expr_types.get(&e).copied();
+            let ty = ty_ctx.integer_arithmetic_type(operand(a), operand(b))?;
+            // unsigned integers wrap around on every target
+            if !matches!(ty_ctx.integer_scalar(ty), Some(Type::Int | Type::Long)) {
+                return None;
+            }
+            Some(Warning::ImplicitOverflow {
+                op,
+                operation: *hir_ctx.expression_fcs.get(&id)?,
+                ty: ty_ctx.display_type(ty).to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_ranges() {
+        assert_eq!(integer_range(Type::Int), Some((-2147483648, 2147483647)));
+        assert_eq!(integer_range(Type::UInt), Some((0, 4294967295)));
+        assert_eq!(integer_range(Type::ULong), Some((0, 18446744073709551615)));
+        assert_eq!(integer_range(Type::Float), None);
+    }
+
+    #[test]
+    fn parse_policies() {
+        for policy in IntegerOverflow::ALL {
+            assert_eq!(policy.name().parse(), Ok(*policy));
+        }
+        assert_eq!(
+            "clamp".parse::<IntegerOverflow>(),
+            Err(
+                "unknown integer overflow `clamp`, expected one of wrap, saturate, undefined"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,437 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Checks for `out` and `in out` parameters.
+//!
+//! Arguments for them are written back to when the function returns, so they
+//! have to be variables. Inside of the function an `out` parameter starts out
+//! undefined, it has to be assigned before it is read and on every path out
+//! of the function.
+
+use std::collections::HashSet;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, Function, Identifier, ParamMode, Statement};
+use id_arena::Id;
+
+use crate::uniformity::statement_calls;
+use crate::{Context, Error, Symbol};
+
+/// Why an expression can't be passed to an `out` or `in out` parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotAssignable {
+    Constant,
+    LoopVariable,
+    /// the expression computes a value instead of naming a variable
+    Value,
+}
+
+/// Report arguments for `out` and `in out` parameters that are not variables.
+pub(crate) fn check_arguments(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut calls = vec![];
+    let bodies = module
+        .functions
+        .iter()
+        .map(|id| &hir_ctx.functions[*id].body)
+        .chain(module.programs.iter().map(|id| &hir_ctx.programs[*id].body));
+    for body in bodies {
+        for stmt in body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+    }
+
+    let mut errs = vec![];
+    for call in calls {
+        for (arg, func, index) in call_arguments(ty_ctx, hir_ctx, call) {
+            let (param_name, _, mode) = hir_ctx.functions[func].args[index];
+            if mode == ParamMode::In {
+                continue;
+            }
+            if let Err(reason) = assignable(ty_ctx, hir_ctx, arg) {
+                errs.push(Error::ArgumentNotAssignable {
+                    param_name: hir_ctx.identifiers[param_name].clone(),
+                    mode,
+                    arg: hir_ctx.expression_fcs[&arg],
+                    param: hir_ctx.identifier_fcs[&param_name],
+                    reason,
+                });
+            }
+        }
+    }
+    errs
+}
+
+/// The arguments of a call of a function of the module, with the function
+/// and the index of the parameter they are passed to.
+pub(crate) fn call_arguments(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    call: Id<Expression>,
+) -> Vec<(Id<Expression>, Id<Function>, usize)> {
+    let (name, pos_args, nam_args) = match &hir_ctx.expressions[call] {
+        Expression::Call {
+            name,
+            pos_args,
+            nam_args,
+        } => (*name, pos_args, nam_args),
+        _ => return vec![],
+    };
+    let func = match ty_ctx.references.symbol(hir_ctx.identifier_fcs[&name]) {
+        Some(Symbol::Function(func)) => func,
+        _ => return vec![],
+    };
+    let params = &hir_ctx.functions[func].args;
+
+    let mut args = vec![];
+    for (index, arg) in pos_args.iter().enumerate() {
+        if index < params.len() {
+            args.push((*arg, func, index));
+        }
+    }
+    for (arg_name, arg) in nam_args {
+        let index = params
+            .iter()
+            .position(|(n, _, _)| hir_ctx.identifiers[*n] == hir_ctx.identifiers[*arg_name]);
+        if let Some(index) = index {
+            args.push((*arg, func, index));
+        }
+    }
+    args
+}
+
+/// Whether the expression names a variable, or a part of one, that can be
+/// written to.
+pub(crate) fn assignable(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+) -> Result<(), NotAssignable> {
+    match &hir_ctx.expressions[expr] {
+        Expression::Variable(name) => {
+            match ty_ctx.references.symbol(hir_ctx.identifier_fcs[name]) {
+                Some(Symbol::Constant(_)) => Err(NotAssignable::Constant),
+                Some(Symbol::LoopVariable(_)) => Err(NotAssignable::LoopVariable),
+                // unresolved names are reported elsewhere
+                _ => Ok(()),
+            }
+        }
+        Expression::Field { base, .. }
+        | Expression::Index { base, .. }
+        | Expression::Slice { base, .. } => assignable(ty_ctx, hir_ctx, *base),
+        Expression::Literal(_)
+        | Expression::PrimitiveOp(_)
+        | Expression::Call { .. }
+        | Expression::As { .. }
+        | Expression::LayoutQuery { .. }
+        | Expression::Array(_) => Err(NotAssignable::Value),
+    }
+}
+
+/// Report `out` parameters that are read before they are assigned or not
+/// assigned on every path out of their function.
+pub(crate) fn check_out_parameters(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        if func.args.iter().all(|(_, _, mode)| *mode != ParamMode::Out) {
+            continue;
+        }
+
+        let mut walker = Walker {
+            ty: ty_ctx,
+            hir: hir_ctx,
+            func: *id,
+            assigned: HashSet::new(),
+            diverged: false,
+            errors: vec![],
+        };
+        walker.block(&func.body);
+        if !walker.diverged {
+            // the body ends with the `end` keyword of the function
+            let loc = hir_ctx.function_fcs[id];
+            let end = FileLocation {
+                start: loc.end - "end".len(),
+                ..loc
+            };
+            walker.check_assigned(end);
+        }
+        errs.extend(walker.errors);
+    }
+
+    errs
+}
+
+struct Walker<'a> {
+    ty: &'a Context,
+    hir: &'a hir::Context,
+    func: Id<Function>,
+    /// indices of the `out` parameters that are assigned on every path to
+    /// the current statement
+    assigned: HashSet<usize>,
+    /// whether all paths to the current statement left the function or loop
+    diverged: bool,
+    errors: Vec<Error>,
+}
+
+impl Walker<'_> {
+    fn block(&mut self, block: &[Id<Statement>]) {
+        for stmt in block {
+            self.statement(*stmt);
+        }
+    }
+
+    fn statement(&mut self, id: Id<Statement>) {
+        match &self.hir.statements[id] {
+            Statement::Var(def) => {
+                if let Some(rhs) = self.hir.variable_defs[*def].rhs {
+                    self.read(rhs);
+                }
+            }
+            Statement::Becomes { lhs, rhs } => {
+                self.read(*rhs);
+                self.write(*lhs);
+            }
+            Statement::Destructure { lhs, rhs } => {
+                self.read(*rhs);
+                for lhs in lhs {
+                    self.write(*lhs);
+                }
+            }
+            Statement::Expr(e) => self.read(*e),
+            Statement::Return(e) => {
+                if let Some(e) = e {
+                    self.read(*e);
+                }
+                self.check_assigned(self.hir.statement_fcs[&id]);
+                self.diverged = true;
+            }
+            Statement::Break | Statement::Continue | Statement::Discard => self.diverged = true,
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                self.read(*cond);
+                let before = (self.assigned.clone(), self.diverged);
+
+                self.block(then_body);
+                let after_then = std::mem::replace(&mut self.assigned, before.0);
+                let then_diverged = std::mem::replace(&mut self.diverged, before.1);
+                self.block(else_body);
+
+                match (then_diverged, self.diverged) {
+                    (true, _) => {}
+                    (false, true) => {
+                        self.assigned = after_then;
+                        self.diverged = false;
+                    }
+                    (false, false) => self.assigned.retain(|i| after_then.contains(i)),
+                }
+            }
+            Statement::For { from, to, body, .. } => {
+                self.read(*from);
+                self.read(*to);
+                // the body may not run at all, so nothing it assigns is
+                // assigned after the loop
+                let before = (self.assigned.clone(), self.diverged);
+                self.block(body);
+                self.assigned = before.0;
+                self.diverged = before.1;
+            }
+            Statement::Match { value, arms } => {
+                self.read(*value);
+                // one of the arms runs, what is assigned after the `match` is
+                // what every arm that doesn't leave assigns
+                let before = (self.assigned.clone(), self.diverged);
+                let mut after: Option<HashSet<usize>> = None;
+                for arm in arms {
+                    self.assigned = before.0.clone();
+                    self.diverged = before.1;
+                    self.block(&arm.body);
+                    if !self.diverged {
+                        after = Some(match after {
+                            Some(after) => after.intersection(&self.assigned).copied().collect(),
+                            None => self.assigned.clone(),
+                        });
+                    }
+                }
+                match after {
+                    Some(after) => {
+                        self.assigned = after;
+                        self.diverged = false;
+                    }
+                    None if arms.is_empty() => {
+                        self.assigned = before.0;
+                        self.diverged = before.1;
+                    }
+                    None => self.diverged = true,
+                }
+            }
+        }
+    }
+
+    /// Report the `out` parameters that are not assigned when the function
+    /// returns at `exit`.
+    fn check_assigned(&mut self, exit: FileLocation) {
+        if self.diverged {
+            return;
+        }
+        let func = &self.hir.functions[self.func];
+        for (index, (name, _, mode)) in func.args.iter().enumerate() {
+            if *mode == ParamMode::Out && !self.assigned.contains(&index) {
+                self.errors.push(Error::OutParameterNotAssigned {
+                    name: self.hir.identifiers[*name].clone(),
+                    param: self.hir.identifier_fcs[name],
+                    exit,
+                });
+            }
+        }
+    }
+
+    /// The `out` parameter of the function a variable refers to.
+    fn out_param(&self, name: Id<Identifier>) -> Option<usize> {
+        match self.ty.references.symbol(self.hir.identifier_fcs[&name])? {
+            Symbol::Parameter { func, index }
+                if func == self.func
+                    && self.hir.functions[func].args[index].2 == ParamMode::Out =>
+            {
+                Some(index)
+            }
+            _ => None,
+        }
+    }
+
+    /// The target of an assignment, a whole `out` parameter becomes assigned.
+    fn write(&mut self, id: Id<Expression>) {
+        match &self.hir.expressions[id] {
+            Expression::Variable(name) => {
+                if let Some(index) = self.out_param(*name) {
+                    self.assigned.insert(index);
+                }
+            }
+            // writing to a part of a parameter neither reads nor assigns it
+            Expression::Field { base, .. } => self.place(*base),
+            Expression::Index { base, index } => {
+                self.place(*base);
+                self.read(*index);
+            }
+            Expression::Slice { base, lo, hi } => {
+                self.place(*base);
+                self.read(*lo);
+                self.read(*hi);
+            }
+            _ => self.read(id),
+        }
+    }
+
+    /// A variable, or a part of it, that is written to partially.
+    fn place(&mut self, id: Id<Expression>) {
+        match &self.hir.expressions[id] {
+            Expression::Variable(_) => {}
+            Expression::Field { base, .. } => self.place(*base),
+            Expression::Index { base, index } => {
+                self.place(*base);
+                self.read(*index);
+            }
+            Expression::Slice { base, lo, hi } => {
+                self.place(*base);
+                self.read(*lo);
+                self.read(*hi);
+            }
+            _ => self.read(id),
+        }
+    }
+
+    fn read(&mut self, id: Id<Expression>) {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            Expression::Literal(_) | Expression::LayoutQuery { .. } => {}
+            Expression::Variable(name) => {
+                let index = match self.out_param(*name) {
+                    Some(index) => index,
+                    None => return,
+                };
+                if !self.diverged && !self.assigned.contains(&index) {
+                    let param = self.hir.functions[self.func].args[index].0;
+                    self.errors.push(Error::OutParameterReadBeforeAssignment {
+                        name: self.hir.identifiers[*name].clone(),
+                        param: self.hir.identifier_fcs[&param],
+                        use_: self.hir.identifier_fcs[name],
+                    });
+                    // only report the first use
+                    self.assigned.insert(index);
+                }
+            }
+            Expression::PrimitiveOp(op) => match &self.hir.prim_ops[*op] {
+                PO::Neg(e) | PO::Pos(e) => self.read(*e),
+                PO::Add(a, b)
+                | PO::Sub(a, b)
+                | PO::Mul(a, b)
+                | PO::Div(a, b)
+                | PO::Mod(a, b)
+                | PO::Gt(a, b)
+                | PO::Gte(a, b)
+                | PO::Lt(a, b)
+                | PO::Lte(a, b)
+                | PO::Eq(a, b)
+                | PO::Neq(a, b) => {
+                    self.read(*a);
+                    self.read(*b);
+                }
+                PO::Constructor {
+                    pos_args, nam_args, ..
+                } => {
+                    for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                        self.read(*e);
+                    }
+                }
+            },
+            Expression::Call {
+                pos_args, nam_args, ..
+            } => {
+                let mut outs = vec![];
+                for (arg, func, index) in call_arguments(self.ty, self.hir, id) {
+                    if self.hir.functions[func].args[index].2 == ParamMode::Out {
+                        outs.push(arg);
+                    }
+                }
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    if !outs.contains(e) {
+                        self.read(*e);
+                    }
+                }
+                // out arguments are written once the call returns
+                for e in outs {
+                    self.write(e);
+                }
+            }
+            Expression::Field { base, .. } | Expression::As { base, .. } => self.read(*base),
+            Expression::Index { base, index } => {
+                self.read(*base);
+                self.read(*index);
+            }
+            Expression::Slice { base, lo, hi } => {
+                self.read(*base);
+                self.read(*lo);
+                self.read(*hi);
+            }
+            Expression::Array(elements) => {
+                for e in elements {
+                    self.read(*e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Values marked with the `@relaxed` attribute, which backends may compute
+//! with less precision (like `mediump` in GLSL).
+
+use thiol_hir as hir;
+
+use hir::VariableDef;
+use id_arena::Id;
+
+use crate::{Context, Error, Symbol, Type, TypeId};
+
+impl Context {
+    /// Whether values of the type can be computed with relaxed precision.
+    pub fn allows_relaxed_precision(&self, ty: TypeId) -> bool {
+        match self.types.get(self.strip_distinct(ty)) {
+            Some(Type::Int)
+            | Some(Type::UInt)
+            | Some(Type::Float)
+            | Some(Type::Half)
+            | Some(Type::Radians)
+            | Some(Type::Degrees)
+            | Some(Type::IntVec { .. })
+            | Some(Type::UIntVec { .. })
+            | Some(Type::FloatVec { .. })
+            | Some(Type::HalfVec { .. })
+            | Some(Type::FloatMat { .. })
+            | Some(Type::Error) => true,
+            Some(Type::Array { base, .. }) | Some(Type::OpenArray { base }) => {
+                self.allows_relaxed_precision(*base)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Record the constants, fields and variables with a `@relaxed` attribute,
+/// values whose type can't be relaxed are reported.
+pub(crate) fn collect_relaxed_precision(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    let defs = ty_ctx
+        .references
+        .symbols()
+        .filter_map(|(sym, _)| match sym {
+            Symbol::Constant(def) | Symbol::Local(def) | Symbol::Field { field: def, .. } => {
+                Some((sym, def))
+            }
+            _ => None,
+        })
+        .collect::<Vec<(Symbol, Id<VariableDef>)>>();
+
+    for (sym, id) in defs {
+        let def = &hir_ctx.variable_defs[id];
+        let attr = def
+            .attrs
+            .iter()
+            .find(|attr| hir_ctx.identifiers[hir_ctx.attributes[**attr].name] == "relaxed");
+        let attr = match attr {
+            Some(attr) => attr,
+            None => continue,
+        };
+
+        // values whose type is unknown had an error before
+        let ty = match ty_ctx.references.symbol_type(sym) {
+            Some(ty) => ty,
+            None => continue,
+        };
+        if ty_ctx.allows_relaxed_precision(ty) {
+            ty_ctx.relaxed_precision.insert(id);
+        } else {
+            errs.push(Error::InvalidRelaxedPrecision {
+                name: hir_ctx.identifiers[def.name].clone(),
+                attribute: hir_ctx.attribute_fcs[attr],
+                type_: hir_ctx.type_ref_fcs[&def.type_],
+                type_name: ty_ctx.display_type(ty).to_string(),
+            });
+        }
+    }
+
+    errs
+}
@@ -0,0 +1,421 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Target profiles, the features they support and the conversions between
+//! types they allow.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiol_hir as hir;
+
+use hir::FileLocation;
+
+use crate::layout::components;
+use crate::{BufferClass, Context, Error, InterpolationMode, Stage, Symbol, Type, TypeId};
+
+/// The kind of target a module is compiled for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Profile {
+    /// half precision values are computed with at least float precision
+    #[default]
+    Desktop,
+    /// half precision values are computed with 16 bit floats
+    DesktopFloat16,
+    /// GLSL ES 3.0 for WebGL 2 and mobile devices, half precision values are
+    /// computed with `mediump`
+    GlslEs3,
+}
+
+impl Profile {
+    pub const ALL: &'static [Profile] =
+        &[Profile::Desktop, Profile::DesktopFloat16, Profile::GlslEs3];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Desktop => "desktop",
+            Profile::DesktopFloat16 => "desktop-f16",
+            Profile::GlslEs3 => "gles3",
+        }
+    }
+
+    /// Whether `half` is a type of its own instead of a relaxed `float`
+    pub fn native_half(self) -> bool {
+        match self {
+            Profile::Desktop | Profile::GlslEs3 => false,
+            Profile::DesktopFloat16 => true,
+        }
+    }
+
+    pub fn supports(self, feature: Feature) -> bool {
+        match (self, feature) {
+            (Profile::Desktop, _) | (Profile::DesktopFloat16, _) => true,
+            // storage buffers, atomics and compute shaders came with GLSL ES 3.1
+            (Profile::GlslEs3, _) => false,
+        }
+    }
+}
+
+/// A feature of the language that not every profile supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    DoublePrecision,
+    /// `long` and `ulong` scalars and vectors
+    Int64,
+    Atomics,
+    StorageBuffers,
+    PushConstants,
+    ComputePrograms,
+    LinearInterpolation,
+    /// textures and samplers bound on their own instead of combined
+    SeparateSamplers,
+    /// ray tracing programs and the acceleration structures they trace
+    /// rays through
+    RayTracing,
+    /// task and mesh programs
+    MeshShading,
+    /// tessellation control and evaluation programs
+    Tessellation,
+    GeometryPrograms,
+    /// subgroup intrinsics and the builtin inputs of subgroups
+    SubgroupOperations,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::DoublePrecision => write!(f, "double precision"),
+            Feature::Int64 => write!(f, "64 bit integers"),
+            Feature::Atomics => write!(f, "atomics"),
+            Feature::StorageBuffers => write!(f, "storage buffers"),
+            Feature::PushConstants => write!(f, "push constants"),
+            Feature::ComputePrograms => write!(f, "compute programs"),
+            Feature::LinearInterpolation => write!(f, "linear interpolation"),
+            Feature::SeparateSamplers => write!(f, "separate textures and samplers"),
+            Feature::RayTracing => write!(f, "ray tracing"),
+            Feature::MeshShading => write!(f, "mesh shading"),
+            Feature::Tessellation => write!(f, "tessellation"),
+            Feature::GeometryPrograms => write!(f, "geometry programs"),
+            Feature::SubgroupOperations => write!(f, "subgroup operations"),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Profile::ALL
+            .iter()
+            .copied()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| {
+                let names = Profile::ALL.iter().map(|p| p.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown profile `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// How a value of one type can be converted to another type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Conversion {
+    /// the types are the same
+    Identity,
+    /// the value is converted where the other type is expected
+    Implicit,
+    /// the value has to be converted with `as`
+    Explicit,
+}
+
+/// The scalar component type of a numeric scalar or vector type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    Float,
+    Double,
+    Half,
+}
+
+impl Context {
+    /// How a value of type `from` can be converted to type `to` under the
+    /// profile of the context, `None` if it can't be converted at all.
+    ///
+    /// Numeric scalars and vectors with the same number of components can be
+    /// converted explicitly. Without native half precision `half` is only a
+    /// hint to compute with less precision, so `half` and `float` convert to
+    /// each other implicitly. With native half precision only the widening
+    /// from `half` to `float` is implicit. 32 bit integers widen implicitly
+    /// to the 64 bit integers that hold all their values, `uint` to both.
+    pub fn conversion(&self, from: TypeId, to: TypeId) -> Option<Conversion> {
+        if from == to {
+            return Some(Conversion::Identity);
+        }
+
+        let (from, to) = (self.numeric_shape(from)?, self.numeric_shape(to)?);
+        if from.1 != to.1 {
+            return None;
+        }
+
+        let conversion = match (from.0, to.0) {
+            (Scalar::Half, Scalar::Float) => Conversion::Implicit,
+            (Scalar::Int | Scalar::UInt, Scalar::Long) => Conversion::Implicit,
+            (Scalar::UInt, Scalar::ULong) => Conversion::Implicit,
+            (Scalar::Float, Scalar::Half) if !self.profile.native_half() => Conversion::Implicit,
+            _ => Conversion::Explicit,
+        };
+        Some(conversion)
+    }
+
+    /// The scalar type and number of components of a numeric type,
+    /// annotations of vectors are ignored.
+    fn numeric_shape(&self, ty: TypeId) -> Option<(Scalar, usize)> {
+        let shape = match self.types.get(ty)? {
+            Type::Int => (Scalar::Int, 1),
+            Type::UInt => (Scalar::UInt, 1),
+            Type::Long => (Scalar::Long, 1),
+            Type::ULong => (Scalar::ULong, 1),
+            Type::Float | Type::Radians | Type::Degrees => (Scalar::Float, 1),
+            Type::Double => (Scalar::Double, 1),
+            Type::Half => (Scalar::Half, 1),
+            Type::IntVec { components: n, .. } => (Scalar::Int, components(*n)),
+            Type::UIntVec { components: n, .. } => (Scalar::UInt, components(*n)),
+            Type::LongVec { components: n, .. } => (Scalar::Long, components(*n)),
+            Type::ULongVec { components: n, .. } => (Scalar::ULong, components(*n)),
+            Type::FloatVec { components: n, .. } => (Scalar::Float, components(*n)),
+            Type::DoubleVec { components: n, .. } => (Scalar::Double, components(*n)),
+            Type::HalfVec { components: n, .. } => (Scalar::Half, components(*n)),
+            _ => return None,
+        };
+        Some(shape)
+    }
+}
+
+impl Context {
+    /// The feature a value of the type needs, records are not included
+    /// because their fields are checked on their own.
+    fn type_feature(&self, ty: TypeId) -> Option<Feature> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Double | Type::DoubleVec { .. } | Type::DoubleMat { .. } => {
+                Some(Feature::DoublePrecision)
+            }
+            Type::Long | Type::ULong | Type::LongVec { .. } | Type::ULongVec { .. } => {
+                Some(Feature::Int64)
+            }
+            Type::AtomicInt | Type::AtomicUInt => Some(Feature::Atomics),
+            Type::Texture { .. } | Type::Sampler { .. } => Some(Feature::SeparateSamplers),
+            Type::AccelerationStructure => Some(Feature::RayTracing),
+            Type::Array { base, .. } | Type::OpenArray { base } => self.type_feature(*base),
+            _ => None,
+        }
+    }
+}
+
+/// Builtin inputs that only exist with subgroup operations
+const SUBGROUP_BUILTINS: &[&str] = &["SubgroupInvocationId", "SubgroupSize"];
+
+/// Report uses of features that the profile of the context doesn't support.
+pub(crate) fn check_profile(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let profile = ty_ctx.profile;
+    let mut errs = vec![];
+    let mut report = |feature: Feature, loc: FileLocation| {
+        if !profile.supports(feature) {
+            errs.push(Error::UnsupportedFeature {
+                feature,
+                profile,
+                loc,
+            });
+        }
+    };
+
+    for (sym, _) in ty_ctx.references.symbols() {
+        let type_ref = match sym {
+            Symbol::Constant(def) | Symbol::Local(def) | Symbol::Field { field: def, .. } => {
+                hir_ctx.variable_defs[def].type_
+            }
+            Symbol::Parameter { func, index } => hir_ctx.functions[func].args[index].1,
+            _ => continue,
+        };
+        let feature = ty_ctx
+            .references
+            .symbol_type(sym)
+            .and_then(|ty| ty_ctx.type_feature(ty));
+        if let Some(feature) = feature {
+            report(feature, hir_ctx.type_ref_fcs[&type_ref]);
+        }
+    }
+
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        let sig = match ty_ctx.function_sigs.get(&hir_ctx.identifiers[func.name]) {
+            Some(sig) if sig.func_id == *id => sig,
+            _ => continue,
+        };
+        if let Some(feature) = ty_ctx.type_feature(sig.ret) {
+            report(feature, hir_ctx.type_ref_fcs[&func.ret_type]);
+        }
+    }
+
+    for id in &module.consts {
+        for attr in &hir_ctx.variable_defs[*id].attrs {
+            let name = &hir_ctx.identifiers[hir_ctx.attributes[*attr].name];
+            let feature = match BufferClass::from_attribute(name) {
+                Some(BufferClass::Storage) => Feature::StorageBuffers,
+                Some(BufferClass::PushConstant) => Feature::PushConstants,
+                _ => continue,
+            };
+            report(feature, hir_ctx.attribute_fcs[attr]);
+        }
+    }
+
+    for id in &module.programs {
+        for attr in &hir_ctx.programs[*id].attrs {
+            let name = &hir_ctx.identifiers[hir_ctx.attributes[*attr].name];
+            match Stage::from_attribute(name) {
+                Some(Stage::Compute) => {
+                    report(Feature::ComputePrograms, hir_ctx.attribute_fcs[attr])
+                }
+                Some(stage) if stage.is_ray_tracing() => {
+                    report(Feature::RayTracing, hir_ctx.attribute_fcs[attr])
+                }
+                Some(Stage::Task) | Some(Stage::Mesh) => {
+                    report(Feature::MeshShading, hir_ctx.attribute_fcs[attr])
+                }
+                Some(stage) if stage.is_tessellation() => {
+                    report(Feature::Tessellation, hir_ctx.attribute_fcs[attr])
+                }
+                Some(Stage::Geometry) => {
+                    report(Feature::GeometryPrograms, hir_ctx.attribute_fcs[attr])
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (call, intrinsic) in &ty_ctx.call_intrinsics {
+        if intrinsic.is_subgroup() {
+            report(Feature::SubgroupOperations, hir_ctx.expression_fcs[call]);
+        }
+    }
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        for input in &prog.inputs {
+            for attr in &hir_ctx.variable_defs[*input].attrs {
+                let name = hir_ctx.identifiers[hir_ctx.attributes[*attr].name].as_str();
+                if SUBGROUP_BUILTINS.contains(&name) {
+                    report(Feature::SubgroupOperations, hir_ctx.attribute_fcs[attr]);
+                }
+            }
+        }
+    }
+
+    for (var, interpolation) in &ty_ctx.interpolation {
+        if interpolation.mode != InterpolationMode::Linear {
+            continue;
+        }
+        for attr in &hir_ctx.variable_defs[*var].attrs {
+            let name = &hir_ctx.identifiers[hir_ctx.attributes[*attr].name];
+            if InterpolationMode::from_attribute(name) == Some(InterpolationMode::Linear) {
+                report(Feature::LinearInterpolation, hir_ctx.attribute_fcs[attr]);
+            }
+        }
+    }
+
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VecSize, VecType};
+
+    fn vec(ctx: &mut Context, half: bool, components: VecSize) -> TypeId {
+        let (vtype, space) = (VecType::Unknown, None);
+        ctx.add_or_get_type(if half {
+            Type::HalfVec {
+                components,
+                vtype,
+                space,
+            }
+        } else {
+            Type::FloatVec {
+                components,
+                vtype,
+                space,
+            }
+        })
+    }
+
+    #[test]
+    fn half_conversions() {
+        let mut ctx = Context::default();
+        let half3 = vec(&mut ctx, true, VecSize::VS3);
+        let float3 = vec(&mut ctx, false, VecSize::VS3);
+        let float4 = vec(&mut ctx, false, VecSize::VS4);
+        let int = ctx.add_or_get_type(Type::Int);
+        let half = ctx.add_or_get_type(Type::Half);
+
+        assert_eq!(ctx.conversion(half3, half3), Some(Conversion::Identity));
+        assert_eq!(ctx.conversion(half3, float3), Some(Conversion::Implicit));
+        assert_eq!(ctx.conversion(float3, half3), Some(Conversion::Implicit));
+        assert_eq!(ctx.conversion(half3, float4), None);
+        assert_eq!(ctx.conversion(int, half), Some(Conversion::Explicit));
+
+        ctx.profile = Profile::DesktopFloat16;
+        assert_eq!(ctx.conversion(half3, float3), Some(Conversion::Implicit));
+        assert_eq!(ctx.conversion(float3, half3), Some(Conversion::Explicit));
+    }
+
+    #[test]
+    fn integer_widening() {
+        let mut ctx = Context::default();
+        let int = ctx.add_or_get_type(Type::Int);
+        let uint = ctx.add_or_get_type(Type::UInt);
+        let long = ctx.add_or_get_type(Type::Long);
+        let ulong = ctx.add_or_get_type(Type::ULong);
+
+        assert_eq!(ctx.conversion(int, long), Some(Conversion::Implicit));
+        assert_eq!(ctx.conversion(uint, long), Some(Conversion::Implicit));
+        assert_eq!(ctx.conversion(uint, ulong), Some(Conversion::Implicit));
+        assert_eq!(ctx.conversion(int, ulong), Some(Conversion::Explicit));
+        assert_eq!(ctx.conversion(long, int), Some(Conversion::Explicit));
+        assert_eq!(ctx.type_feature(ulong), Some(Feature::Int64));
+        assert!(!Profile::GlslEs3.supports(Feature::Int64));
+    }
+
+    #[test]
+    fn array_features() {
+        let mut ctx = Context::default();
+        let double = ctx.add_or_get_type(Type::Double);
+        let doubles = ctx.add_or_get_type(Type::Array {
+            base: double,
+            size: 4,
+        });
+        let atomic = ctx.add_or_get_type(Type::AtomicUInt);
+        let atomics = ctx.add_or_get_type(Type::OpenArray { base: atomic });
+        let float = ctx.add_or_get_type(Type::Float);
+
+        assert_eq!(ctx.type_feature(doubles), Some(Feature::DoublePrecision));
+        assert_eq!(ctx.type_feature(atomics), Some(Feature::Atomics));
+        assert_eq!(ctx.type_feature(float), None);
+        assert!(Profile::Desktop.supports(Feature::Atomics));
+        assert!(!Profile::GlslEs3.supports(Feature::DoublePrecision));
+    }
+}
@@ -0,0 +1,96 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Labels for GPU profiling.
+//!
+//! A function or program with a `@profile("label")` attribute is marked
+//! with the label in the generated code, with timestamps or markers where
+//! the target has them. The labels of the functions a program calls are
+//! listed in the reflection of its entry point, so that engines can map the
+//! timings of a capture back to the source. Labels are not empty and every
+//! label of a module is used once.
+
+use std::collections::BTreeMap;
+
+use thiol_hir as hir;
+
+use hir::{Attribute, FileLocation, Program};
+use id_arena::Id;
+
+use crate::resources::reachable;
+use crate::stages::string_arg;
+use crate::{Callable, Context, Error};
+
+/// Why the `@profile` attribute of a function or program is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileProblem {
+    /// the attribute doesn't have one string argument
+    Argument,
+    /// the label is empty
+    Empty,
+    /// another function or program has the label
+    Duplicate { first: FileLocation },
+}
+
+impl Context {
+    /// The labels of a program and the functions it calls, directly or
+    /// indirectly, with the function or program that has the label.
+    pub fn program_profile_labels(&self, program: Id<Program>) -> Vec<(&str, Callable)> {
+        reachable(self, Callable::Program(program))
+            .into_iter()
+            .filter_map(|callable| Some((self.profile_labels.get(&callable)?.as_str(), callable)))
+            .collect()
+    }
+}
+
+/// The `@profile` attribute of a function or program.
+fn profile_attribute(hir_ctx: &hir::Context, attrs: &[Id<Attribute>]) -> Option<Id<Attribute>> {
+    attrs
+        .iter()
+        .copied()
+        .find(|attr| hir_ctx.identifiers[hir_ctx.attributes[*attr].name] == "profile")
+}
+
+/// Record the profiling labels of the functions and programs of a module.
+pub(crate) fn collect_profile_labels(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let functions = module
+        .functions
+        .iter()
+        .map(|id| (Callable::Function(*id), &hir_ctx.functions[*id].attrs));
+    let programs = module
+        .programs
+        .iter()
+        .map(|id| (Callable::Program(*id), &hir_ctx.programs[*id].attrs));
+
+    let mut first = BTreeMap::new();
+    let mut errs = vec![];
+    for (callable, attrs) in functions.chain(programs) {
+        let attr = match profile_attribute(hir_ctx, attrs) {
+            Some(attr) => attr,
+            None => continue,
+        };
+        let loc = hir_ctx.attribute_fcs[&attr];
+        let problem = match string_arg(hir_ctx, &hir_ctx.attributes[attr]) {
+            None => ProfileProblem::Argument,
+            Some("") => ProfileProblem::Empty,
+            Some(label) => match first.get(label) {
+                Some(first) => ProfileProblem::Duplicate { first: *first },
+                None => {
+                    first.insert(label, loc);
+                    ty_ctx.profile_labels.insert(callable, label.to_string());
+                    continue;
+                }
+            },
+        };
+        errs.push(Error::InvalidProfileLabel {
+            attribute: loc,
+            problem,
+        });
+    }
+    errs
+}
@@ -0,0 +1,335 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Ray tracing pipelines.
+//!
+//! A ray tracing pipeline starts with a `@ray_generation` program, which
+//! traces rays through an acceleration structure, a constant of type
+//! `acceleration_structure` bound with a `Uniform` attribute like textures.
+//! Every intersection of a ray runs the `@any_hit` program, the closest one
+//! the `@closest_hit` program and a ray that hits nothing the `@miss`
+//! program.
+//!
+//! The programs pass data along with a ray in its payload, an output with a
+//! `RayPayload` attribute giving its location. The ray generation program
+//! fills it in, `trace_ray(scene, origin, direction, t_min, t_max, payload)`
+//! traces a ray with the payload at the location `payload`, and the hit and
+//! miss programs write their results to it. So the location has to be a
+//! constant naming a payload of every program the call is reached from, and
+//! payloads at the same location have the same type in every program of the
+//! module.
+//!
+//! The stages are those of the SPIR-V ray tracing extension
+//! `SPV_KHR_ray_tracing`, with payloads at the locations of its
+//! `RayPayloadKHR` and `IncomingRayPayloadKHR` variables. The Metal and
+//! GLSL ES backends have no such stages and reject ray tracing programs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+
+use thiol_hir as hir;
+
+use hir::{Attribute, Expression, FileLocation, Program, VariableDef};
+use id_arena::Id;
+
+use crate::consteval::{Evaluator, Value};
+use crate::resources::reachable;
+use crate::stages::{program_stage, Stage};
+use crate::uniformity::statement_calls;
+use crate::{Callable, Context, Error, Intrinsic, Symbol, Type, TypeId, VecSize};
+
+/// The names and types of the parameters of `trace_ray`, in order
+pub const TRACE_RAY_PARAMS: [(&str, &str); 6] = [
+    ("scene", "acceleration_structure"),
+    ("origin", "float3"),
+    ("direction", "float3"),
+    ("t_min", "float"),
+    ("t_max", "float"),
+    ("payload", "uint"),
+];
+
+/// Why a `RayPayload` attribute is not valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadProblem {
+    /// the attribute needs a single integer argument, the location
+    ExpectedInteger,
+    /// only ray tracing programs pass payloads along with rays
+    OutsideRayTracing { stage: Option<Stage> },
+    /// another payload of the program has the same location
+    Duplicate { previous: FileLocation },
+}
+
+/// The `RayPayload` attribute of an output with the location it gives, `None`
+/// if its argument is not an integer.
+pub fn payload_location(
+    hir_ctx: &hir::Context,
+    def: Id<VariableDef>,
+) -> Option<(Id<Attribute>, Option<u32>)> {
+    hir_ctx.variable_defs[def].attrs.iter().find_map(|id| {
+        let attr = &hir_ctx.attributes[*id];
+        if hir_ctx.identifiers[attr.name] != "RayPayload" {
+            return None;
+        }
+        let location = match (attr.pos_args.as_slice(), attr.nam_args.is_empty()) {
+            ([arg], true) => match hir_ctx.expressions[*arg] {
+                Expression::Literal(hir::Literal::Integer(n, _)) => u32::try_from(n).ok(),
+                _ => None,
+            },
+            _ => None,
+        };
+        Some((*id, location))
+    })
+}
+
+impl Context {
+    /// Whether the type is an acceleration structure or contains one.
+    pub fn contains_acceleration_structure(&self, ty: TypeId) -> bool {
+        self.types
+            .contains(ty, |ty| *ty == Type::AccelerationStructure)
+    }
+
+    /// The type of the parameter of `trace_ray` at `index`, which literal
+    /// arguments get.
+    pub(crate) fn trace_ray_param_type(&mut self, index: usize) -> Option<TypeId> {
+        let ty = match index {
+            0 => Type::AccelerationStructure,
+            1 | 2 => Type::FloatVec {
+                components: VecSize::VS3,
+                vtype: crate::VecType::Unknown,
+                space: None,
+            },
+            3 | 4 => Type::Float,
+            5 => Type::UInt,
+            _ => return None,
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// Whether a value of the type can be passed as the parameter of
+    /// `trace_ray` at `index`, origins and directions can be in any space.
+    fn fits_trace_ray_param(&self, ty: TypeId, index: usize) -> bool {
+        matches!(
+            (self.types.get(self.strip_distinct(ty)), index),
+            (Some(Type::Error), _)
+                | (Some(Type::AccelerationStructure), 0)
+                | (
+                    Some(Type::FloatVec {
+                        components: VecSize::VS3,
+                        ..
+                    }),
+                    1 | 2,
+                )
+                | (Some(Type::Float), 3 | 4)
+                | (Some(Type::UInt), 5)
+        )
+    }
+}
+
+/// The signature of `trace_ray`, shown in the help of errors in its calls.
+pub fn trace_ray_signature() -> String {
+    let params = TRACE_RAY_PARAMS
+        .iter()
+        .map(|(name, ty)| format!("{}: {}", name, ty))
+        .collect::<Vec<_>>();
+    format!("trace_ray({})", params.join(", "))
+}
+
+/// Check the arguments of a call of `trace_ray`.
+pub(crate) fn check_call(
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    call: FileLocation,
+    args: &[(Id<Expression>, Option<TypeId>)],
+) -> Vec<Error> {
+    if args.len() != TRACE_RAY_PARAMS.len() {
+        return vec![Error::TraceRayArity {
+            call,
+            found: args.len(),
+        }];
+    }
+    let mut errs = vec![];
+    for (index, (arg, ty)) in args.iter().enumerate() {
+        let ty = match ty {
+            Some(ty) => *ty,
+            None => continue,
+        };
+        if !ty_ctx.fits_trace_ray_param(ty, index) {
+            let (param, expected) = TRACE_RAY_PARAMS[index];
+            errs.push(Error::TraceRayArgumentMismatch {
+                arg: hir_ctx.expression_fcs[arg],
+                param,
+                found: ty_ctx.display_type(ty).to_string(),
+                expected,
+            });
+        }
+    }
+    errs
+}
+
+/// Report acceleration structures in parameters, return types, variables
+/// and constants that are not bound.
+pub(crate) fn validate_acceleration_structure_placement(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    crate::images::validate_opaque_placement(
+        module,
+        ty_ctx,
+        hir_ctx,
+        Context::contains_acceleration_structure,
+        |type_| Error::MisplacedAccelerationStructure { type_ },
+    )
+}
+
+/// Check the `RayPayload` attributes of the programs, the types of payloads
+/// sharing a location and the payload locations `trace_ray` is called with.
+pub(crate) fn validate_payloads(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+    // the first payload at every location, which the others are compared to
+    let mut module_payloads: BTreeMap<u32, (TypeId, FileLocation)> = BTreeMap::new();
+    let mut program_payloads: HashMap<Id<Program>, BTreeMap<u32, FileLocation>> = HashMap::new();
+
+    for id in &module.programs {
+        let stage = program_stage(hir_ctx, *id);
+        let payloads = program_payloads.entry(*id).or_default();
+        for output in &hir_ctx.programs[*id].outputs {
+            let (attr, location) = match payload_location(hir_ctx, *output) {
+                Some(payload) => payload,
+                None => continue,
+            };
+            let attribute = hir_ctx.attribute_fcs[&attr];
+            let problem = match (location, stage) {
+                (_, stage) if !stage.is_some_and(Stage::is_ray_tracing) => {
+                    Some(PayloadProblem::OutsideRayTracing { stage })
+                }
+                (None, _) => Some(PayloadProblem::ExpectedInteger),
+                (Some(location), _) => {
+                    payloads
+                        .get(&location)
+                        .map(|previous| PayloadProblem::Duplicate {
+                            previous: *previous,
+                        })
+                }
+            };
+            if let Some(problem) = problem {
+                errs.push(Error::InvalidRayPayload { attribute, problem });
+                continue;
+            }
+            let location = location.unwrap_or_default();
+            let payload = hir_ctx.variable_def_fcs[output];
+            payloads.insert(location, payload);
+
+            let ty = match ty_ctx.references.symbol_type(Symbol::Local(*output)) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            match module_payloads.get(&location) {
+                Some((expected, previous)) if *expected != ty => {
+                    errs.push(Error::PayloadTypeMismatch {
+                        location,
+                        found: ty_ctx.display_type(ty).to_string(),
+                        expected: ty_ctx.display_type(*expected).to_string(),
+                        payload,
+                        previous: *previous,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    module_payloads.insert(location, (ty, payload));
+                }
+            }
+        }
+    }
+
+    // the locations `trace_ray` is called with, evaluated once per call
+    let mut evaluator = Evaluator::new(ty_ctx, hir_ctx);
+    let mut locations = HashMap::new();
+    for id in &module.programs {
+        // calls in other stages are reported by `validate_trace_rays`
+        if !program_stage(hir_ctx, *id).is_some_and(Stage::traces_rays) {
+            continue;
+        }
+        let prog = &hir_ctx.programs[*id];
+        let mut calls = vec![];
+        for callable in reachable(ty_ctx, Callable::Program(*id)) {
+            let body = match callable {
+                Callable::Function(func) => &hir_ctx.functions[func].body,
+                Callable::Program(prog) => &hir_ctx.programs[prog].body,
+            };
+            for stmt in body {
+                statement_calls(hir_ctx, *stmt, &mut calls);
+            }
+        }
+
+        for call in calls {
+            if ty_ctx.call_intrinsics.get(&call) != Some(&Intrinsic::TraceRay) {
+                continue;
+            }
+            let arg = match &hir_ctx.expressions[call] {
+                Expression::Call { pos_args, .. } if pos_args.len() == TRACE_RAY_PARAMS.len() => {
+                    pos_args[TRACE_RAY_PARAMS.len() - 1]
+                }
+                _ => continue,
+            };
+            let location = *locations.entry(call).or_insert_with(|| {
+                match evaluator.eval(arg) {
+                    Ok(Value::UInt(location)) => Some(location),
+                    // other types are reported when checking the arguments
+                    Ok(_) => None,
+                    Err((expr, problem)) => {
+                        errs.push(Error::ConstEvaluation { expr, problem });
+                        None
+                    }
+                }
+            });
+            let location = match location {
+                Some(location) => location,
+                None => continue,
+            };
+            if !program_payloads[id].contains_key(&location) {
+                errs.push(Error::UnknownPayloadLocation {
+                    location,
+                    arg: hir_ctx.expression_fcs[&arg],
+                    program_name: hir_ctx.identifiers[prog.name].clone(),
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_ray_params() {
+        let mut ctx = Context::default();
+        let float3 = ctx.trace_ray_param_type(1).unwrap();
+        let point = ctx.add_or_get_type(Type::FloatVec {
+            components: VecSize::VS3,
+            vtype: crate::VecType::Point,
+            space: None,
+        });
+        let uint = ctx.trace_ray_param_type(5).unwrap();
+        assert!(ctx.fits_trace_ray_param(float3, 2));
+        assert!(ctx.fits_trace_ray_param(point, 1));
+        assert!(!ctx.fits_trace_ray_param(float3, 3));
+        assert!(ctx.fits_trace_ray_param(uint, 5));
+        assert!(!ctx.fits_trace_ray_param(uint, 0));
+        assert_eq!(ctx.trace_ray_param_type(6), None);
+        assert_eq!(
+            trace_ray_signature(),
+            "trace_ray(scene: acceleration_structure, origin: float3, direction: float3, \
+             t_min: float, t_max: float, payload: uint)"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Recursion between functions.
+//!
+//! GPUs have no call stack, every call is inlined, so functions can't call
+//! themselves, directly or through other functions.
+
+use thiol_hir as hir;
+
+use hir::Function;
+use id_arena::Id;
+
+use crate::{Callable, CycleEdge, Error};
+
+/// Report the cycles in the call graph.
+pub(crate) fn check_recursion(ty_ctx: &crate::Context, hir_ctx: &hir::Context) -> Vec<Error> {
+    let g = &ty_ctx.call_graph;
+    let name_loc = |id: Id<Function>| hir_ctx.identifier_fcs[&hir_ctx.functions[id].name];
+    let name = |id: Id<Function>| hir_ctx.identifiers[hir_ctx.functions[id].name].clone();
+
+    let mut errs = vec![];
+    for group in g.strongly_connected_components() {
+        let group = group
+            .into_iter()
+            .filter_map(|c| match c {
+                Callable::Function(id) => Some(id),
+                Callable::Program(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if group.len() > 1 {
+            let mut function_idents = group.iter().map(|id| name_loc(*id)).collect::<Vec<_>>();
+            function_idents.sort();
+
+            // the cycle starts at the function that comes first in the source
+            let first = *group.iter().min_by_key(|id| name_loc(**id)).unwrap();
+            let cycle = g
+                .find_cycle(Callable::Function(first))
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(caller, callee, calls)| match (caller, callee) {
+                    (Callable::Function(caller), Callable::Function(callee)) => Some(CycleEdge {
+                        user: name(caller),
+                        user_name: name_loc(caller),
+                        used: name(callee),
+                        use_loc: calls[0],
+                    }),
+                    _ => None,
+                })
+                .collect();
+
+            errs.push(Error::MutuallyRecursiveFunctions {
+                function_idents,
+                cycle,
+            });
+            continue;
+        }
+
+        for id in group {
+            let recursive_calls = g
+                .dependencies(Callable::Function(id))
+                .into_iter()
+                .filter(|(callee, _)| *callee == Callable::Function(id))
+                .flat_map(|(_, calls)| calls.iter().copied())
+                .collect::<Vec<_>>();
+            if !recursive_calls.is_empty() {
+                errs.push(Error::RecursiveFunction {
+                    function_name: name_loc(id),
+                    recursive_calls,
+                });
+            }
+        }
+    }
+    errs
+}
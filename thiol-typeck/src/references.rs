@@ -0,0 +1,1853 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::collections::{BTreeMap, HashMap};
+
+use hir::{
+    FileId, FileLocation, Function, Identifier, Program, SpaceDefinition, Statement,
+    TypeDefinition, VariableDef,
+};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::angles;
+use crate::colours;
+use crate::composites::CompositeProblem;
+use crate::consteval::Evaluator;
+use crate::debug_output::{self, DebugProblem, FormatPiece};
+use crate::derives::{Derive, DerivedCallProblem};
+use crate::images;
+use crate::layout;
+use crate::normalized;
+use crate::operators;
+use crate::optionals::{self, OptionalProblem};
+use crate::ray_tracing;
+use crate::slices::{self, SliceProblem};
+use crate::spaces;
+use crate::textures;
+use crate::unify::{self, Substitution, UnifyError};
+use crate::{Context, Error, FunctionSig, Intrinsic, Resolution, Type, TypeId};
+
+/// Something an identifier can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symbol {
+    Type(Id<TypeDefinition>),
+    GenericParam {
+        def: Id<TypeDefinition>,
+        index: usize,
+    },
+    Field {
+        def: Id<TypeDefinition>,
+        field: Id<VariableDef>,
+    },
+    Function(Id<Function>),
+    FunctionGenericParam {
+        func: Id<Function>,
+        index: usize,
+    },
+    Parameter {
+        func: Id<Function>,
+        index: usize,
+    },
+    Constant(Id<VariableDef>),
+    Program(Id<Program>),
+    /// Local variables, program inputs/outputs and workgroup variables
+    Local(Id<VariableDef>),
+    /// The iteration variable of a `for` loop
+    LoopVariable(Id<Statement>),
+    /// The value of the `some` arm of a `match`
+    MatchBinding(Id<Statement>),
+    Space(Id<SpaceDefinition>),
+}
+
+/// Maps identifier uses to the symbols they refer to and back.
+///
+/// Every occurrence of an identifier that could be resolved is recorded,
+/// including the identifier in the declaration of the symbol itself.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceIndex {
+    definitions: BTreeMap<Symbol, FileLocation>,
+    occurrences: BTreeMap<FileLocation, Symbol>,
+    references: BTreeMap<Symbol, Vec<FileLocation>>,
+    types: BTreeMap<Symbol, TypeId>,
+}
+
+impl ReferenceIndex {
+    /// The location of the identifier declaring the symbol.
+    pub fn definition(&self, sym: Symbol) -> Option<FileLocation> {
+        self.definitions.get(&sym).copied()
+    }
+
+    /// All uses of the symbol, not including the declaration.
+    pub fn references(&self, sym: Symbol) -> &[FileLocation] {
+        self.references
+            .get(&sym)
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The symbol an identifier at exactly `loc` refers to.
+    pub fn symbol(&self, loc: FileLocation) -> Option<Symbol> {
+        self.occurrences.get(&loc).copied()
+    }
+
+    /// The identifier occurrence containing `offset` and the symbol it refers to.
+    pub fn symbol_at(&self, file: FileId, offset: usize) -> Option<(FileLocation, Symbol)> {
+        let key = FileLocation {
+            file,
+            start: offset,
+            end: usize::MAX,
+        };
+        self.occurrences
+            .range(..=key)
+            .next_back()
+            .filter(|(loc, _)| loc.file == file && loc.start <= offset && offset <= loc.end)
+            .map(|(loc, sym)| (*loc, *sym))
+    }
+
+    /// Go-to-definition for the identifier at `offset`.
+    pub fn definition_at(&self, file: FileId, offset: usize) -> Option<FileLocation> {
+        let (_, sym) = self.symbol_at(file, offset)?;
+        self.definition(sym)
+    }
+
+    /// Find-references for the identifier at `offset`.
+    pub fn references_at(&self, file: FileId, offset: usize) -> &[FileLocation] {
+        match self.symbol_at(file, offset) {
+            Some((_, sym)) => self.references(sym),
+            None => &[],
+        }
+    }
+
+    /// The type of a constant, parameter, field or local variable, if it is
+    /// known.
+    pub fn symbol_type(&self, sym: Symbol) -> Option<TypeId> {
+        self.types.get(&sym).copied()
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = (Symbol, FileLocation)> + '_ {
+        self.definitions.iter().map(|(sym, loc)| (*sym, *loc))
+    }
+
+    fn define(&mut self, sym: Symbol, loc: FileLocation) {
+        self.definitions.insert(sym, loc);
+        self.occurrences.insert(loc, sym);
+    }
+
+    fn reference(&mut self, sym: Symbol, loc: FileLocation) {
+        self.occurrences.insert(loc, sym);
+        self.references.entry(sym).or_default().push(loc);
+    }
+}
+
+/// Index all references in a module, calls to generic functions whose
+/// generic arguments can't be inferred are reported.
+pub(crate) fn index_references(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+    module: &hir::Module,
+) -> (ReferenceIndex, Vec<Error>) {
+    let mut indexer = Indexer {
+        ty: ty_ctx,
+        hir: hir_ctx,
+        index: ReferenceIndex::default(),
+        spaces: HashMap::new(),
+        undefined_spaces: BTreeMap::new(),
+        subst: Some(HashMap::new()),
+        ret: None,
+        in_array_size: false,
+        errors: vec![],
+    };
+    indexer.module(module);
+    let mut errors = indexer.errors;
+    errors.extend(spaces::undefined_spaces(
+        indexer.undefined_spaces,
+        hir_ctx,
+        module,
+    ));
+    (indexer.index, errors)
+}
+
+struct Indexer<'a> {
+    ty: &'a mut Context,
+    hir: &'a hir::Context,
+    index: ReferenceIndex,
+
+    // Names are looked up in the resolution table instead of the type
+    // context, so that items which failed to type check can still be found.
+    spaces: HashMap<&'a str, Id<SpaceDefinition>>,
+    /// uses of spaces that aren't declared, in modules that declare spaces
+    undefined_spaces: BTreeMap<&'a str, Vec<FileLocation>>,
+
+    /// types of the generic parameters in scope, `None` inside of generic
+    /// type definitions whose references can't be translated on their own
+    subst: Option<HashMap<&'a str, TypeId>>,
+    /// return type of the function being indexed
+    ret: Option<TypeId>,
+    /// whether the expressions being indexed are the size of an array, whose
+    /// problems are reported when the type of the array is translated
+    in_array_size: bool,
+
+    errors: Vec<Error>,
+}
+
+impl<'a> Indexer<'a> {
+    fn module(&mut self, module: &hir::Module) {
+        for id in &module.spaces {
+            let name = self.name(self.hir.spaces[*id].name);
+            self.spaces.entry(name).or_insert(*id);
+        }
+
+        for id in &module.spaces {
+            let def = &self.hir.spaces[*id];
+            self.define(Symbol::Space(*id), def.name);
+            if let Some((parent, via)) = def.parent {
+                self.space(parent);
+                if let Some(sym) = self.ty.resolutions.symbol(via) {
+                    self.reference(sym, via);
+                }
+            }
+        }
+
+        for id in &module.uses {
+            let space = self
+                .spaces
+                .get(self.name(*id))
+                .map(|def| Symbol::Space(*def));
+            if let Some(sym) = self.ty.resolutions.symbol(*id).or(space) {
+                self.reference(sym, *id);
+            }
+        }
+
+        for id in &module.types {
+            self.type_definition(*id);
+        }
+
+        for id in &module.consts {
+            let def = &self.hir.variable_defs[*id];
+            self.define(Symbol::Constant(*id), def.name);
+            if let Some(sig) = self.ty.consts.get(self.name(def.name)) {
+                if sig.const_id == *id {
+                    self.index.types.insert(Symbol::Constant(*id), sig.type_);
+                }
+            }
+            let ty = self.type_ref(def.type_);
+            if let Some(rhs) = def.rhs {
+                let found = self.value(rhs, ty);
+                if let (Some(found), Some(expected)) = (found, ty) {
+                    if !self.ty.same_value_type(found, expected) {
+                        self.errors.push(Error::ConstantTypeMismatch {
+                            name: self.name(def.name).to_string(),
+                            expected: self.ty.display_type(expected).to_string(),
+                            found: self.ty.display_type(found).to_string(),
+                            value: self.hir.expression_fcs[&rhs],
+                            declared: self.hir.type_ref_fcs[&def.type_],
+                        });
+                    }
+                }
+            }
+        }
+
+        for id in &module.static_asserts {
+            let bool_ty = self.ty.add_or_get_type(Type::Bool);
+            self.value(self.hir.static_asserts[*id].condition, Some(bool_ty));
+        }
+
+        for id in &module.functions {
+            let func = &self.hir.functions[*id];
+            self.define(Symbol::Function(*id), func.name);
+
+            self.function_generics(*id);
+            for (index, (name, ty, _)) in func.args.iter().enumerate() {
+                let sym = Symbol::Parameter { func: *id, index };
+                self.define(sym, *name);
+                let ty = self.type_ref(*ty);
+                self.declare(sym, ty);
+            }
+            self.ret = self.type_ref(func.ret_type);
+            self.block(&func.body);
+            self.ret = None;
+            self.subst = Some(HashMap::new());
+        }
+
+        for id in &module.programs {
+            let prog = &self.hir.programs[*id];
+            self.define(Symbol::Program(*id), prog.name);
+
+            for var in prog
+                .inputs
+                .iter()
+                .chain(&prog.outputs)
+                .chain(&prog.workgroup)
+            {
+                self.local(*var);
+            }
+            self.block(&prog.body);
+        }
+    }
+
+    fn type_definition(&mut self, id: Id<TypeDefinition>) {
+        let def = &self.hir.type_defs[id];
+        self.define(Symbol::Type(id), def.name);
+
+        for (index, gen) in def.generics.iter().enumerate() {
+            self.define(Symbol::GenericParam { def: id, index }, *gen);
+        }
+        if !def.generics.is_empty() {
+            self.subst = None;
+        }
+
+        match &self.hir.type_def_rhss[def.rhs] {
+            hir::TypeDefinitionRhs::Distinct(ty) | hir::TypeDefinitionRhs::Alias(ty) => {
+                self.type_ref(*ty);
+            }
+            hir::TypeDefinitionRhs::Record { fields } => {
+                for field in fields {
+                    let var = &self.hir.variable_defs[*field];
+                    let sym = Symbol::Field {
+                        def: id,
+                        field: *field,
+                    };
+                    self.define(sym, var.name);
+                    if let Some(ty) = self.type_ref(var.type_) {
+                        self.index.types.insert(sym, ty);
+                    }
+                }
+            }
+        }
+        self.subst = Some(HashMap::new());
+    }
+
+    /// Bring the generic parameters of a function into scope.
+    fn function_generics(&mut self, id: Id<Function>) {
+        let func = &self.hir.functions[id];
+        let sig = self
+            .ty
+            .function_sigs
+            .get(self.name(func.name))
+            .filter(|sig| sig.func_id == id)
+            .cloned();
+
+        let mut subst = HashMap::new();
+        for (index, gen) in func.generics.iter().enumerate() {
+            let sym = Symbol::FunctionGenericParam { func: id, index };
+            self.define(sym, *gen);
+            let name = self.name(*gen);
+
+            // redefined parameters are not part of the signature
+            if let Some(sig) = &sig {
+                if let Some(index) = sig.generics.iter().position(|g| g == name) {
+                    let param = self.ty.add_or_get_type(Type::GenericParam {
+                        index,
+                        name: name.to_string(),
+                    });
+                    subst.insert(name, param);
+                }
+            }
+        }
+        self.subst = Some(subst);
+    }
+
+    /// Record all names used in a type reference and translate it to a type if possible.
+    fn type_ref(&mut self, id: Id<hir::TypeReference>) -> Option<TypeId> {
+        self.type_ref_names(id);
+
+        let subst = self.subst.as_ref()?;
+        self.ty.ty_ref(self.hir, id, subst).ok()
+    }
+
+    fn type_ref_names(&mut self, id: Id<hir::TypeReference>) {
+        match &self.hir.type_refs[id] {
+            hir::TypeReference::Primitive(ty) => {
+                for space in spaces::primitive_spaces(ty) {
+                    self.space(space);
+                }
+            }
+            hir::TypeReference::Image { .. }
+            | hir::TypeReference::Texture { sampled: None, .. }
+            | hir::TypeReference::Sampler { .. }
+            | hir::TypeReference::AccelerationStructure => {}
+            hir::TypeReference::OpenArray(base)
+            | hir::TypeReference::Normalized(base)
+            | hir::TypeReference::Optional(base)
+            | hir::TypeReference::Texture {
+                sampled: Some(base),
+                ..
+            } => self.type_ref_names(*base),
+            hir::TypeReference::Record { fields } => {
+                for (_, ty) in fields {
+                    self.type_ref_names(*ty);
+                }
+            }
+            hir::TypeReference::Array { base, size } => {
+                if let hir::ArraySize::Expression(size) = size {
+                    let in_array_size = std::mem::replace(&mut self.in_array_size, true);
+                    self.value(*size, None);
+                    self.in_array_size = in_array_size;
+                }
+                self.type_ref_names(*base);
+            }
+            hir::TypeReference::Named { name, generics } => {
+                if let Some(sym) = self.ty.resolutions.symbol(*name) {
+                    self.reference(sym, *name);
+                }
+                for gen in generics {
+                    self.type_ref_names(*gen);
+                }
+            }
+        }
+    }
+
+    /// Record a use of a space, spaces are free-form names as long as the
+    /// module doesn't declare any.
+    fn space(&mut self, name: Id<Identifier>) {
+        if let Some(def) = self.spaces.get(self.name(name)) {
+            self.reference(Symbol::Space(*def), name);
+        } else if !self.spaces.is_empty() {
+            let loc = self.hir.identifier_fcs[&name];
+            let uses = self.undefined_spaces.entry(self.name(name)).or_default();
+            uses.push(loc);
+        }
+    }
+
+    fn local(&mut self, id: Id<VariableDef>) {
+        let def = &self.hir.variable_defs[id];
+        let ty = self.type_ref(def.type_);
+        if let Some(rhs) = def.rhs {
+            self.value(rhs, ty);
+        }
+        self.define(Symbol::Local(id), def.name);
+        self.declare(Symbol::Local(id), ty);
+    }
+
+    fn block(&mut self, block: &[Id<Statement>]) {
+        for stmt in block {
+            self.statement(*stmt);
+        }
+    }
+
+    fn statement(&mut self, id: Id<Statement>) {
+        match &self.hir.statements[id] {
+            Statement::Var(var) => self.local(*var),
+            Statement::Becomes { lhs, rhs } => {
+                let ty = self.expr(*lhs);
+                self.value(*rhs, ty);
+            }
+            // the targets are checked against the fields in `destructuring`
+            Statement::Destructure { lhs, rhs } => {
+                for e in lhs {
+                    self.expr(*e);
+                }
+                self.expr(*rhs);
+            }
+            Statement::Return(e) => {
+                if let Some(e) = e {
+                    self.value(*e, self.ret);
+                }
+            }
+            Statement::Expr(e) => {
+                self.expr(*e);
+            }
+            Statement::Break | Statement::Continue | Statement::Discard => {}
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                self.expr(*cond);
+                self.block(then_body);
+                self.block(else_body);
+            }
+            Statement::For {
+                iter_name,
+                loop_type: _,
+                from,
+                to,
+                body,
+            } => {
+                let (ty, _) = self.operands(*from, *to, None);
+
+                let sym = Symbol::LoopVariable(id);
+                self.define(sym, *iter_name);
+                self.declare(sym, ty);
+                self.block(body);
+            }
+            // the arms are checked against the cases in `optionals`
+            Statement::Match { value, arms } => {
+                let inner = self.expr(*value).and_then(|ty| self.ty.optional_inner(ty));
+                for arm in arms {
+                    if let hir::MatchPattern::Some(name) = arm.pattern {
+                        let sym = Symbol::MatchBinding(id);
+                        self.define(sym, name);
+                        self.declare(sym, inner);
+                    }
+                    self.block(&arm.body);
+                }
+            }
+        }
+    }
+
+    /// Record all names used in an expression and compute its type where that
+    /// is needed to resolve field accesses.
+    fn expr(&mut self, id: Id<hir::Expression>) -> Option<TypeId> {
+        self.expr_expecting(id, None)
+    }
+
+    /// Like [`expr`](Self::expr), for an expression whose value is used as
+    /// a value of type `expected`. Literals without a suffix take their type
+    /// from the expected type.
+    fn expr_expecting(
+        &mut self,
+        id: Id<hir::Expression>,
+        expected: Option<TypeId>,
+    ) -> Option<TypeId> {
+        let ty = self.expr_type(id, expected);
+        if let Some(ty) = ty {
+            self.ty.expr_types.insert(id, ty);
+        }
+        ty
+    }
+
+    /// The base of an index or a slice, which can be a slice itself.
+    fn view(&mut self, id: Id<hir::Expression>) -> Option<TypeId> {
+        if !matches!(self.hir.expressions[id], hir::Expression::Slice { .. }) {
+            return self.expr(id);
+        }
+        let ty = self.slice(id);
+        if let Some(ty) = ty {
+            self.ty.expr_types.insert(id, ty);
+        }
+        ty
+    }
+
+    /// The type of a slice, checking the bounds that are literals.
+    fn slice(&mut self, id: Id<hir::Expression>) -> Option<TypeId> {
+        let (base, lo, hi) = match self.hir.expressions[id] {
+            hir::Expression::Slice { base, lo, hi } => (base, lo, hi),
+            _ => return None,
+        };
+        let base_ty = self.view(base);
+        self.expr(lo);
+        self.expr(hi);
+
+        let ty = base_ty.and_then(|ty| self.ty.slice_type(ty));
+        let problem = match (base_ty, ty) {
+            (Some(base_ty), None) if self.ty.types.get(base_ty) != Some(&Type::Error) => {
+                Some(SliceProblem::NotAnArray {
+                    type_name: self.ty.display_type(base_ty).to_string(),
+                })
+            }
+            _ => slices::bounds_problem(
+                self.constant_integer(lo),
+                self.constant_integer(hi),
+                self.view_len(base),
+            ),
+        };
+        if let Some(problem) = problem {
+            self.errors.push(Error::InvalidSlice {
+                slice: self.hir.expression_fcs[&id],
+                problem,
+            });
+        }
+        ty
+    }
+
+    /// The number of elements of an array or a slice, if it is known.
+    fn view_len(&self, id: Id<hir::Expression>) -> Option<i128> {
+        if let hir::Expression::Slice { lo, hi, .. } = self.hir.expressions[id] {
+            let len = self.constant_integer(hi)? - self.constant_integer(lo)?;
+            // reversed bounds are reported by the slice
+            return (len >= 0).then_some(len);
+        }
+        let len = self.ty.array_len(*self.ty.expr_types.get(&id)?)?;
+        Some(len as i128)
+    }
+
+    fn constant_integer(&self, id: Id<hir::Expression>) -> Option<i128> {
+        match self.hir.expressions[id] {
+            hir::Expression::Literal(hir::Literal::Integer(value, _)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// An expression whose value is stored in or passed as a value of type
+    /// `expected`, which checks the space of vectors, the encoding of colours,
+    /// normalized vectors, the unit of angles and optionals.
+    fn value(&mut self, id: Id<hir::Expression>, expected: Option<TypeId>) -> Option<TypeId> {
+        let ty = self.expr_expecting(id, expected);
+        if let Some(expected) = expected {
+            let err = normalized::check_value(self.ty, self.hir, id, ty, expected);
+            self.errors.extend(err);
+        }
+        if let (Some(ty), Some(expected)) = (ty, expected) {
+            let err = spaces::check_value_space(self.ty, self.hir, id, ty, expected);
+            self.errors.extend(err);
+            let err = colours::check_value_encoding(self.ty, self.hir, id, ty, expected);
+            self.errors.extend(err);
+            let err = angles::check_value(self.ty, self.hir, id, ty, expected);
+            self.errors.extend(err);
+            let err = optionals::check_value(self.ty, self.hir, id, ty, expected);
+            self.errors.extend(err);
+        }
+        ty
+    }
+
+    /// The operands of a binary operation. A literal operand expects the
+    /// type of the other operand, so the other one is indexed first.
+    fn operands(
+        &mut self,
+        a: Id<hir::Expression>,
+        b: Id<hir::Expression>,
+        expected: Option<TypeId>,
+    ) -> (Option<TypeId>, Option<TypeId>) {
+        if self.is_literal(a) && !self.is_literal(b) {
+            let b_ty = self.expr_expecting(b, expected);
+            (self.expr_expecting(a, b_ty.or(expected)), b_ty)
+        } else {
+            let a_ty = self.expr_expecting(a, expected);
+            (a_ty, self.expr_expecting(b, a_ty.or(expected)))
+        }
+    }
+
+    /// The type of a binary operation, reporting operands of types the
+    /// operator doesn't take.
+    fn binary(
+        &mut self,
+        op: Id<hir::PrimitiveOp>,
+        (a, a_ty): (Id<hir::Expression>, TypeId),
+        (b, b_ty): (Id<hir::Expression>, TypeId),
+    ) -> Option<TypeId> {
+        if self.ty.is_unknown_operand(a_ty) || self.ty.is_unknown_operand(b_ty) {
+            return None;
+        }
+        let prim_op = &self.hir.prim_ops[op];
+        let ty = self.ty.binary_type(prim_op, a_ty, b_ty);
+        if ty.is_none() {
+            self.errors.push(Error::OperandTypeMismatch {
+                op: operators::symbol(prim_op),
+                left: self.ty.display_type(a_ty).to_string(),
+                left_loc: self.hir.expression_fcs[&a],
+                right: self.ty.display_type(b_ty).to_string(),
+                right_loc: self.hir.expression_fcs[&b],
+            });
+        }
+        ty
+    }
+
+    /// The type of the constructor of a record, checking that every field
+    /// gets one value of its type.
+    fn record_constructor(
+        &mut self,
+        id: Id<hir::Expression>,
+        name: Id<Identifier>,
+        def: Id<hir::TypeDefinition>,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<Identifier>, Id<hir::Expression>)],
+        expected: Option<TypeId>,
+    ) -> Option<TypeId> {
+        self.reference(Symbol::Type(def), name);
+        let record = match self.ty.constructed_record(self.name(name), def, expected) {
+            Some(record) => record,
+            None => {
+                self.args(pos_args, nam_args);
+                self.errors.push(Error::InvalidComposite {
+                    value: self.hir.expression_fcs[&id],
+                    type_name: self.name(name).to_string(),
+                    problem: CompositeProblem::UninferableRecord,
+                });
+                return None;
+            }
+        };
+        self.record_values(id, record, pos_args, nam_args)
+    }
+
+    /// The type of a record built from the values of its fields, given in
+    /// the order of the fields or by their names.
+    fn record_values(
+        &mut self,
+        id: Id<hir::Expression>,
+        record: TypeId,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<Identifier>, Id<hir::Expression>)],
+    ) -> Option<TypeId> {
+        let call = self.hir.expression_fcs[&id];
+        let fields = match self.ty.record_fields(record) {
+            Some(fields) => fields,
+            None => {
+                self.args(pos_args, nam_args);
+                return None;
+            }
+        };
+        let type_name = self.ty.display_type(record).to_string();
+        let mut problems = vec![];
+        if pos_args.len() > fields.len() {
+            let (fields, found) = (fields.len(), pos_args.len());
+            problems.push((call, CompositeProblem::TooManyValues { fields, found }));
+        }
+        let mut given = vec![false; fields.len()];
+        for (index, e) in pos_args.iter().enumerate() {
+            if let Some(given) = given.get_mut(index) {
+                *given = true;
+            }
+            let field_ty = fields.get(index).map(|(_, ty)| *ty);
+            self.element(*e, field_ty, &type_name);
+        }
+        for (field_name, e) in nam_args {
+            let field = self.name(*field_name);
+            if let Some((def, field, _)) = self.record_field(record, field) {
+                self.reference(Symbol::Field { def, field }, *field_name);
+            }
+            let loc = self.hir.identifier_fcs[field_name];
+            let index = fields.iter().position(|(name, _)| name == field);
+            match index {
+                None => problems.push((loc, CompositeProblem::UnknownField(field.to_string()))),
+                Some(index) if given[index] => {
+                    problems.push((loc, CompositeProblem::DuplicateField(field.to_string())))
+                }
+                Some(index) => given[index] = true,
+            }
+            let field_ty = index.map(|index| fields[index].1);
+            self.element(*e, field_ty, &type_name);
+        }
+        let missing = fields
+            .iter()
+            .zip(&given)
+            .filter(|(_, given)| !**given)
+            .map(|((name, _), _)| name.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() && pos_args.len() <= fields.len() {
+            problems.push((call, CompositeProblem::MissingFields(missing)));
+        }
+        for (value, problem) in problems {
+            self.errors.push(Error::InvalidComposite {
+                value,
+                type_name: type_name.clone(),
+                problem,
+            });
+        }
+        Some(record)
+    }
+
+    /// The type of a call of a function derived from a record.
+    fn derived_call(
+        &mut self,
+        id: Id<hir::Expression>,
+        name: Id<Identifier>,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<Identifier>, Id<hir::Expression>)],
+    ) -> Option<TypeId> {
+        let function = match self.ty.derived_functions.get(self.name(name)) {
+            Some(function) => function.clone(),
+            // records that can't derive the function are reported at the
+            // `@derive` attribute
+            None => {
+                self.args(pos_args, nam_args);
+                return None;
+            }
+        };
+        if function.derive == Derive::New {
+            return self.record_values(id, function.record, pos_args, nam_args);
+        }
+
+        let expected = function.record;
+        let found = pos_args.len() + nam_args.len();
+        if found != function.args.len() || !nam_args.is_empty() {
+            self.errors.push(Error::InvalidDerivedCall {
+                loc: self.hir.expression_fcs[&id],
+                function: self.name(name).to_string(),
+                problem: DerivedCallProblem::Arity {
+                    expected: function.args.len(),
+                    found,
+                },
+            });
+        }
+        for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+            let found = match self.value(*e, Some(expected)) {
+                Some(found) if !self.ty.same_value_type(found, expected) => found,
+                _ => continue,
+            };
+            self.errors.push(Error::InvalidDerivedCall {
+                loc: self.hir.expression_fcs[e],
+                function: self.name(name).to_string(),
+                problem: DerivedCallProblem::ValueType {
+                    expected: self.ty.display_type(expected).to_string(),
+                    found: self.ty.display_type(found).to_string(),
+                },
+            });
+        }
+        Some(function.ret)
+    }
+
+    /// The type of `some(v)`, `none()` or `unwrap_or(o, default)`, see
+    /// [`optionals`].
+    fn optional_call(
+        &mut self,
+        id: Id<hir::Expression>,
+        intrinsic: Intrinsic,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<Identifier>, Id<hir::Expression>)],
+        expected: Option<TypeId>,
+    ) -> Option<TypeId> {
+        self.ty.call_intrinsics.insert(id, intrinsic);
+        let invalid = |loc: FileLocation, problem| Error::InvalidOptional { loc, problem };
+        let arity = match intrinsic {
+            Intrinsic::Some => 1,
+            Intrinsic::None => 0,
+            _ => 2,
+        };
+        let found = pos_args.len() + nam_args.len();
+        if found != arity || !nam_args.is_empty() {
+            self.args(pos_args, nam_args);
+            let problem = OptionalProblem::Arity {
+                expected: arity,
+                found,
+            };
+            self.errors
+                .push(invalid(self.hir.expression_fcs[&id], problem));
+            return None;
+        }
+
+        match (intrinsic, pos_args) {
+            (Intrinsic::Some, [value]) => {
+                let inner = expected.and_then(|ty| self.ty.optional_inner(ty));
+                // values without a known type, like swizzles, take the
+                // expected optional type
+                let ty = match self.value(*value, inner) {
+                    Some(ty) => ty,
+                    None => return expected.filter(|_| inner.is_some()),
+                };
+                let optional = self.ty.optional_type(ty);
+                if optional.is_none() {
+                    let type_name = self.ty.display_type(ty).to_string();
+                    let problem = OptionalProblem::Inner(type_name);
+                    self.errors
+                        .push(invalid(self.hir.expression_fcs[value], problem));
+                }
+                optional
+            }
+            (Intrinsic::None, []) => {
+                let optional = expected.filter(|ty| self.ty.optional_inner(*ty).is_some());
+                if optional.is_none() {
+                    let problem = OptionalProblem::UninferableNone;
+                    self.errors
+                        .push(invalid(self.hir.expression_fcs[&id], problem));
+                }
+                optional
+            }
+            (_, [optional, default]) => {
+                let optional_ty = self.expr(*optional);
+                let inner = match optional_ty.map(|ty| (ty, self.ty.optional_inner(ty))) {
+                    Some((_, Some(inner))) => inner,
+                    Some((ty, None)) => {
+                        self.expr(*default);
+                        if self.ty.types.get(ty) != Some(&Type::Error) {
+                            let type_name = self.ty.display_type(ty).to_string();
+                            let problem = OptionalProblem::NotOptional(type_name);
+                            self.errors
+                                .push(invalid(self.hir.expression_fcs[optional], problem));
+                        }
+                        return None;
+                    }
+                    None => {
+                        self.expr(*default);
+                        return None;
+                    }
+                };
+                let found = self.value(*default, Some(inner));
+                if let Some(found) = found.filter(|found| !self.ty.same_value_type(*found, inner)) {
+                    let problem = OptionalProblem::DefaultType {
+                        expected: self.ty.display_type(inner).to_string(),
+                        found: self.ty.display_type(found).to_string(),
+                    };
+                    self.errors
+                        .push(invalid(self.hir.expression_fcs[default], problem));
+                }
+                Some(inner)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check a call of `assert(cond, message)` or `debug_print(format,
+    /// values...)`, which have no value, see [`debug_output`].
+    fn debug_call(
+        &mut self,
+        id: Id<hir::Expression>,
+        intrinsic: Intrinsic,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<Identifier>, Id<hir::Expression>)],
+    ) -> Option<TypeId> {
+        self.ty.call_intrinsics.insert(id, intrinsic);
+        let invalid = |loc: FileLocation, problem| Error::InvalidDebugOutput { loc, problem };
+        let found = pos_args.len() + nam_args.len();
+        let (expected, arity_fits) = match intrinsic {
+            Intrinsic::Assert => ("1 or 2", found == 1 || found == 2),
+            _ => ("at least 1", found >= 1),
+        };
+        if !arity_fits || !nam_args.is_empty() {
+            self.args(pos_args, nam_args);
+            let problem = DebugProblem::Arity { expected, found };
+            self.errors
+                .push(invalid(self.hir.expression_fcs[&id], problem));
+            return None;
+        }
+
+        let (string, values) = match intrinsic {
+            Intrinsic::Assert => {
+                let cond = pos_args[0];
+                let bool_ty = self.ty.add_or_get_type(Type::Bool);
+                if let Some(ty) = self.value(cond, Some(bool_ty)) {
+                    let stripped = self.ty.strip_distinct(ty);
+                    if !matches!(self.ty.types.get(stripped), Some(Type::Bool | Type::Error)) {
+                        let problem = DebugProblem::Condition(self.ty.display_type(ty).to_string());
+                        self.errors
+                            .push(invalid(self.hir.expression_fcs[&cond], problem));
+                    }
+                }
+                (pos_args.get(1), &[][..])
+            }
+            _ => (pos_args.first(), &pos_args[1..]),
+        };
+
+        // messages and formats are emitted as they are, they aren't values
+        let format = match string.map(|arg| (*arg, &self.hir.expressions[*arg])) {
+            Some((_, hir::Expression::Literal(hir::Literal::String(string)))) => Some(*string),
+            Some((arg, _)) => {
+                self.expr(arg);
+                let problem = DebugProblem::NotString;
+                self.errors
+                    .push(invalid(self.hir.expression_fcs[&arg], problem));
+                None
+            }
+            None => None,
+        };
+        if let (Intrinsic::DebugPrint, Some(format)) = (intrinsic, format) {
+            let loc = self.hir.expression_fcs[&pos_args[0]];
+            match debug_output::format_pieces(&self.hir.strings[format]) {
+                Some(pieces) => {
+                    let placeholders = pieces
+                        .iter()
+                        .filter(|piece| **piece == FormatPiece::Value)
+                        .count();
+                    if placeholders != values.len() {
+                        let problem = DebugProblem::ValueCount {
+                            placeholders,
+                            values: values.len(),
+                        };
+                        self.errors.push(invalid(loc, problem));
+                    }
+                }
+                None => self.errors.push(invalid(loc, DebugProblem::UnmatchedBrace)),
+            }
+        }
+
+        for value in values {
+            let ty = match self.expr(*value) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            if self.ty.printable(ty).is_none() && self.ty.types.get(ty) != Some(&Type::Error) {
+                let problem = DebugProblem::Unprintable(self.ty.display_type(ty).to_string());
+                self.errors
+                    .push(invalid(self.hir.expression_fcs[value], problem));
+            }
+        }
+        None
+    }
+
+    /// Type the arguments of a call that can't be checked further.
+    fn args(
+        &mut self,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<Identifier>, Id<hir::Expression>)],
+    ) {
+        for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+            self.expr(*e);
+        }
+    }
+
+    /// The type of an array built from its elements, which have the element
+    /// type of the expected array or else the type of the first element.
+    fn array(
+        &mut self,
+        id: Id<hir::Expression>,
+        elements: &[Id<hir::Expression>],
+        expected: Option<TypeId>,
+    ) -> Option<TypeId> {
+        let expected = expected.map(|ty| self.ty.strip_distinct(ty));
+        let expected_element = match expected.and_then(|ty| self.ty.types.get(ty)) {
+            Some(Type::Array { base, .. } | Type::OpenArray { base }) => Some(*base),
+            _ => None,
+        };
+        let (element, rest) = match (expected_element, elements.split_first()) {
+            (_, None) => {
+                self.errors.push(Error::InvalidComposite {
+                    value: self.hir.expression_fcs[&id],
+                    type_name: "array".to_string(),
+                    problem: CompositeProblem::EmptyArray,
+                });
+                return None;
+            }
+            (Some(element), Some(_)) => (element, elements),
+            (None, Some((first, rest))) => (self.value(*first, None)?, rest),
+        };
+        let array = self.ty.add_or_get_type(Type::Array {
+            base: element,
+            size: elements.len(),
+        });
+        let type_name = self.ty.display_type(array).to_string();
+        for e in rest {
+            self.element(*e, Some(element), &type_name);
+        }
+        Some(array)
+    }
+
+    /// A field of a record or an element of an array, reporting values of
+    /// another type than the one expected.
+    fn element(
+        &mut self,
+        e: Id<hir::Expression>,
+        expected: Option<TypeId>,
+        type_name: &str,
+    ) -> Option<TypeId> {
+        let found = self.value(e, expected);
+        if let (Some(found), Some(expected)) = (found, expected) {
+            if !self.ty.same_value_type(found, expected) {
+                self.errors.push(Error::InvalidComposite {
+                    value: self.hir.expression_fcs[&e],
+                    type_name: type_name.to_string(),
+                    problem: CompositeProblem::ValueType {
+                        expected: self.ty.display_type(expected).to_string(),
+                        found: self.ty.display_type(found).to_string(),
+                    },
+                });
+            }
+        }
+        found
+    }
+
+    /// Check that the mask of `select` is a `bool`, or a boolean vector with
+    /// the components of the values.
+    fn select_mask(
+        &mut self,
+        (mask, mask_ty): (Id<hir::Expression>, TypeId),
+        (value, value_ty): (Id<hir::Expression>, TypeId),
+    ) -> Option<Error> {
+        if self.ty.is_unknown_operand(mask_ty) || self.ty.is_unknown_operand(value_ty) {
+            return None;
+        }
+        let mask_components = match self.ty.types.get(self.ty.strip_distinct(mask_ty))? {
+            Type::Bool => return None,
+            Type::BoolVec { components } => {
+                if self.ty.vector_size(value_ty) == Some(*components) {
+                    return None;
+                }
+                Some(layout::components(*components))
+            }
+            _ => None,
+        };
+        Some(Error::InvalidSelectMask {
+            mask: self.hir.expression_fcs[&mask],
+            mask_type: self.ty.display_type(mask_ty).to_string(),
+            mask_components,
+            value: self.ty.display_type(value_ty).to_string(),
+            value_loc: self.hir.expression_fcs[&value],
+        })
+    }
+
+    fn is_literal(&self, id: Id<hir::Expression>) -> bool {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            hir::Expression::Literal(_) => true,
+            hir::Expression::PrimitiveOp(op) => match &self.hir.prim_ops[*op] {
+                PO::Neg(e) | PO::Pos(e) => self.is_literal(*e),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// The type of a literal: the type of its suffix, or the scalar type of
+    /// the expected type if the literal can be one, or `int` and `float`.
+    fn literal_type(&mut self, literal: hir::Literal, expected: Option<TypeId>) -> TypeId {
+        use hir::LiteralSuffix as LS;
+
+        let scalar = expected
+            .and_then(|ty| self.ty.types.get(ty))
+            .and_then(Type::scalar);
+        let ty = match literal {
+            hir::Literal::Integer(_, Some(LS::Int)) => Type::Int,
+            hir::Literal::Integer(_, Some(LS::UInt)) => Type::UInt,
+            hir::Literal::Integer(_, Some(LS::Long)) => Type::Long,
+            hir::Literal::Integer(_, Some(LS::ULong)) => Type::ULong,
+            hir::Literal::Float(_, Some(LS::Float)) => Type::Float,
+            hir::Literal::Float(_, Some(LS::Double)) => Type::Double,
+            hir::Literal::Float(_, Some(LS::Radians)) => Type::Radians,
+            hir::Literal::Float(_, Some(LS::Degrees)) => Type::Degrees,
+            hir::Literal::Integer(_, _) => scalar.unwrap_or(Type::Int),
+            hir::Literal::Float(_, _) => match scalar {
+                Some(
+                    ty @ (Type::Float | Type::Half | Type::Double | Type::Radians | Type::Degrees),
+                ) => ty,
+                _ => Type::Float,
+            },
+            hir::Literal::Bool(_) => Type::Bool,
+            hir::Literal::String(_) => Type::Error,
+        };
+        self.ty.add_or_get_type(ty)
+    }
+
+    fn expr_type(&mut self, id: Id<hir::Expression>, expected: Option<TypeId>) -> Option<TypeId> {
+        match &self.hir.expressions[id] {
+            hir::Expression::Literal(hir::Literal::String(_)) => {
+                self.errors.push(Error::StringLiteralAsValue {
+                    literal: self.hir.expression_fcs[&id],
+                });
+                Some(self.ty.error_type())
+            }
+            hir::Expression::Literal(lit) => Some(self.literal_type(*lit, expected)),
+            hir::Expression::Variable(name) => {
+                let sym = self.ty.resolutions.symbol(*name)?;
+                self.reference(sym, *name);
+                match sym {
+                    Symbol::Constant(_) => {
+                        self.ty.consts.get(self.name(*name)).map(|sig| sig.type_)
+                    }
+                    _ => self.index.symbol_type(sym),
+                }
+            }
+            hir::Expression::PrimitiveOp(op) => {
+                use hir::PrimitiveOp as PO;
+                match &self.hir.prim_ops[*op] {
+                    PO::Neg(e) | PO::Pos(e) => {
+                        let ty = self.expr_expecting(*e, expected)?;
+                        if self.ty.is_unknown_operand(ty) {
+                            return None;
+                        }
+                        let result = self.ty.unary_type(ty);
+                        if result.is_none() {
+                            self.errors.push(Error::InvalidOperand {
+                                op: operators::symbol(&self.hir.prim_ops[*op]),
+                                operation: self.hir.prim_op_fcs[op],
+                                ty: self.ty.display_type(ty).to_string(),
+                            });
+                        }
+                        result
+                    }
+                    PO::Add(a, b)
+                    | PO::Sub(a, b)
+                    | PO::Mul(a, b)
+                    | PO::Div(a, b)
+                    | PO::Mod(a, b) => {
+                        let (a_ty, b_ty) = match self.operands(*a, *b, expected) {
+                            (Some(a_ty), Some(b_ty)) => (a_ty, b_ty),
+                            _ => return None,
+                        };
+                        let operation = self.hir.prim_op_fcs[op];
+                        let err = colours::check_operands(
+                            self.ty,
+                            self.hir,
+                            operation,
+                            (*a, a_ty),
+                            (*b, b_ty),
+                        );
+                        // the encoding of the result isn't known
+                        if let Some(err) = err {
+                            self.errors.push(err);
+                            return Some(self.ty.error_type());
+                        }
+                        match angles::arithmetic_type(
+                            self.ty,
+                            self.hir,
+                            &self.hir.prim_ops[*op],
+                            operation,
+                            (*a, a_ty),
+                            (*b, b_ty),
+                        ) {
+                            Ok(Some(ty)) => Some(ty),
+                            Ok(None) => self.binary(*op, (*a, a_ty), (*b, b_ty)),
+                            Err(err) => {
+                                self.errors.push(err);
+                                None
+                            }
+                        }
+                    }
+                    PO::Gt(a, b)
+                    | PO::Gte(a, b)
+                    | PO::Lt(a, b)
+                    | PO::Lte(a, b)
+                    | PO::Eq(a, b)
+                    | PO::Neq(a, b) => match self.operands(*a, *b, None) {
+                        (Some(a_ty), Some(b_ty)) => self.binary(*op, (*a, a_ty), (*b, b_ty)),
+                        _ => None,
+                    },
+                    PO::Constructor {
+                        ty,
+                        pos_args,
+                        nam_args,
+                    } => {
+                        let component =
+                            constructor_scalar(ty).map(|ty| self.ty.add_or_get_type(ty));
+                        let args = pos_args
+                            .iter()
+                            .map(|e| self.expr_expecting(*e, component))
+                            .collect::<Vec<_>>();
+                        for (_, e) in nam_args {
+                            self.expr_expecting(*e, component);
+                        }
+
+                        let prim = self.ty.primitive_type(self.hir, ty);
+                        let constructed = self.ty.add_or_get_type(prim);
+                        for (e, arg_ty) in pos_args.iter().zip(&args) {
+                            if let Some(arg_ty) = arg_ty {
+                                let err = angles::check_constructor_argument(
+                                    self.ty,
+                                    self.hir,
+                                    *e,
+                                    *arg_ty,
+                                    constructed,
+                                );
+                                self.errors.extend(err);
+                            }
+                        }
+                        if let hir::PrimitiveType::FloatMat { cols, rows, .. }
+                        | hir::PrimitiveType::DoubleMat { cols, rows, .. } = ty
+                        {
+                            let problem = match nam_args.is_empty() {
+                                true => self.ty.matrix_constructor_problem(
+                                    (*cols).into(),
+                                    (*rows).into(),
+                                    &args,
+                                ),
+                                false => None,
+                            };
+                            if let Some(problem) = problem {
+                                self.errors.push(Error::InvalidMatrixConstructor {
+                                    constructor: self.hir.prim_op_fcs[op],
+                                    type_name: self.ty.display_type(constructed).to_string(),
+                                    problem,
+                                });
+                            }
+                        }
+                        Some(constructed)
+                    }
+                }
+            }
+            hir::Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => {
+                if let Some(Resolution::Symbol(Symbol::Type(def))) = self.ty.resolutions.get(*name)
+                {
+                    return self.record_constructor(id, *name, def, pos_args, nam_args, expected);
+                }
+                if let Some(Resolution::Derived { .. }) = self.ty.resolutions.get(*name) {
+                    return self.derived_call(id, *name, pos_args, nam_args);
+                }
+                if let Some(Resolution::Intrinsic(
+                    intrinsic @ (Intrinsic::Some | Intrinsic::None | Intrinsic::UnwrapOr),
+                )) = self.ty.resolutions.get(*name)
+                {
+                    return self.optional_call(id, intrinsic, pos_args, nam_args, expected);
+                }
+                if let Some(Resolution::Intrinsic(
+                    intrinsic @ (Intrinsic::Assert | Intrinsic::DebugPrint),
+                )) = self.ty.resolutions.get(*name)
+                {
+                    return self.debug_call(id, intrinsic, pos_args, nam_args);
+                }
+                let (func, intrinsic) = match self.ty.resolutions.get(*name) {
+                    Some(Resolution::Symbol(Symbol::Function(func))) => (Some(func), None),
+                    Some(Resolution::Intrinsic(intrinsic)) => (None, Some(intrinsic)),
+                    _ => (None, None),
+                };
+
+                // the arguments by the index of the parameter
+                let mut params = vec![];
+                for (index, e) in pos_args.iter().enumerate() {
+                    params.push((Some(index), *e));
+                }
+                let mut named = vec![];
+                for (arg_name, e) in nam_args {
+                    let mut index = None;
+                    if let Some(func) = func {
+                        let arg_name_s = self.name(*arg_name);
+                        index = self.hir.functions[func]
+                            .args
+                            .iter()
+                            .position(|(n, _, _)| self.hir.identifiers[*n] == *arg_name_s);
+                        if let Some(index) = index {
+                            named.push((Symbol::Parameter { func, index }, *arg_name));
+                        }
+                    }
+                    params.push((index, *e));
+                }
+
+                // literal arguments take the type of their parameter, or the
+                // type of the other arguments of intrinsics, the angle they
+                // expect, the parameter of the texture they sample or of
+                // `trace_ray`, the invocation index of subgroup operations, or
+                // the scalar of the values `approx_eq` compares, the values
+                // `select` selects from take the type of each other
+                let param_types = self
+                    .ty
+                    .function_sigs
+                    .get(self.name(*name))
+                    .filter(|sig| sig.generics.is_empty() && Some(sig.func_id) == func)
+                    .map(|sig| sig.args.iter().map(|(_, ty)| *ty).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let param_type =
+                    |index: Option<usize>| index.and_then(|i| param_types.get(i).copied());
+                let mut types = vec![None; params.len()];
+                for (i, (index, e)) in params.iter().enumerate() {
+                    if !self.is_literal(*e) {
+                        types[i] = self.value(*e, param_type(*index));
+                    }
+                }
+                let sibling = types
+                    .iter()
+                    .zip(&params)
+                    .filter(|(_, (index, _))| {
+                        intrinsic != Some(Intrinsic::Select) || *index != Some(0)
+                    })
+                    .find_map(|(ty, _)| *ty)
+                    .filter(|_| intrinsic.is_some());
+                for (i, (index, e)) in params.iter().enumerate() {
+                    if self.is_literal(*e) {
+                        let angle = intrinsic
+                            .into_iter()
+                            .flat_map(|intrinsic| intrinsic.angle_arguments())
+                            .find(|(position, _)| Some(*position) == *index)
+                            .and_then(|(_, unit)| *unit)
+                            .map(|unit| self.ty.angle_type(unit));
+                        let sampling = intrinsic
+                            .filter(|intrinsic| intrinsic.is_sampling())
+                            .zip(types.first().copied().flatten())
+                            .zip(*index)
+                            .and_then(|((intrinsic, texture), index)| {
+                                self.ty.sampling_argument_type(intrinsic, texture, index)
+                            });
+                        let trace_ray = intrinsic
+                            .filter(|intrinsic| *intrinsic == Intrinsic::TraceRay)
+                            .zip(*index)
+                            .and_then(|(_, index)| self.ty.trace_ray_param_type(index));
+                        let lane = intrinsic
+                            .filter(|intrinsic| {
+                                matches!(
+                                    intrinsic,
+                                    Intrinsic::SubgroupShuffle | Intrinsic::SubgroupBroadcast
+                                )
+                            })
+                            .filter(|_| *index == Some(1))
+                            .map(|_| self.ty.add_or_get_type(Type::UInt));
+                        let tolerance = intrinsic
+                            .filter(|intrinsic| *intrinsic == Intrinsic::ApproxEq)
+                            .filter(|_| *index == Some(2))
+                            .and_then(|_| self.ty.float_scalar(sibling?));
+                        let expected = param_type(*index)
+                            .or(angle)
+                            .or(sampling)
+                            .or(trace_ray)
+                            .or(lane)
+                            .or(tolerance)
+                            .or(sibling);
+                        types[i] = self.expr_expecting(*e, expected);
+                    }
+                }
+                let args = params
+                    .iter()
+                    .zip(types)
+                    .map(|((index, e), ty)| (*index, *e, ty))
+                    .collect::<Vec<_>>();
+
+                if let Some(func) = func {
+                    self.reference(Symbol::Function(func), *name);
+                }
+                for (sym, arg_name) in named {
+                    self.reference(sym, arg_name);
+                }
+
+                if let Some(intrinsic) = intrinsic {
+                    self.ty.call_intrinsics.insert(id, intrinsic);
+                    if intrinsic == Intrinsic::Identity && args.is_empty() {
+                        return self.ty.identity_type(expected?);
+                    }
+                    if intrinsic == Intrinsic::Bitcast {
+                        let from = match args.as_slice() {
+                            [(Some(0), _, from)] => (*from)?,
+                            _ => return None,
+                        };
+                        let to = expected?;
+                        if let Err(problem) = self.ty.check_bitcast(from, to) {
+                            self.errors.push(Error::InvalidCast {
+                                cast: self.hir.expression_fcs[&id],
+                                from: self.ty.display_type(from).to_string(),
+                                to: self.ty.display_type(to).to_string(),
+                                bitcast: true,
+                                problem,
+                            });
+                        }
+                        return Some(to);
+                    }
+                    for position in intrinsic.normalized_arguments() {
+                        let arg = args.iter().find(|(index, ..)| *index == Some(*position));
+                        if let Some((_, e, ty)) = arg {
+                            let err =
+                                normalized::check_argument(self.ty, self.hir, intrinsic, *e, *ty);
+                            self.errors.extend(err);
+                        }
+                    }
+                    for (position, unit) in intrinsic.angle_arguments() {
+                        let arg = args.iter().find(|(index, ..)| *index == Some(*position));
+                        if let Some((_, e, Some(ty))) = arg {
+                            let err = angles::check_argument(
+                                self.ty, self.hir, intrinsic, *e, *ty, *unit,
+                            );
+                            self.errors.extend(err);
+                        }
+                    }
+                    if matches!(intrinsic, Intrinsic::ImageLoad | Intrinsic::ImageStore) {
+                        let positional = args
+                            .iter()
+                            .filter(|(index, ..)| index.is_some())
+                            .map(|(_, e, ty)| (*e, *ty))
+                            .collect::<Vec<_>>();
+                        let errs = images::check_call(self.ty, self.hir, intrinsic, &positional);
+                        self.errors.extend(errs);
+                    }
+                    if intrinsic.is_sampling() {
+                        let positional = args
+                            .iter()
+                            .filter(|(index, ..)| index.is_some())
+                            .map(|(_, e, ty)| (*e, *ty))
+                            .collect::<Vec<_>>();
+                        let call = self.hir.expression_fcs[&id];
+                        let errs =
+                            textures::check_call(self.ty, self.hir, intrinsic, call, &positional);
+                        self.errors.extend(errs);
+                    }
+                    if intrinsic == Intrinsic::TraceRay {
+                        let positional = args
+                            .iter()
+                            .filter(|(index, ..)| index.is_some())
+                            .map(|(_, e, ty)| (*e, *ty))
+                            .collect::<Vec<_>>();
+                        let call = self.hir.expression_fcs[&id];
+                        let errs = ray_tracing::check_call(self.ty, self.hir, call, &positional);
+                        self.errors.extend(errs);
+                    }
+                    if intrinsic == Intrinsic::Select {
+                        if let [(Some(0), mask, Some(mask_ty)), (Some(1), value, Some(value_ty)), ..] =
+                            args.as_slice()
+                        {
+                            let err = self.select_mask((*mask, *mask_ty), (*value, *value_ty));
+                            self.errors.extend(err);
+                        }
+                    }
+                    let arg_types = args
+                        .iter()
+                        .map(|(index, _, ty)| index.and(*ty))
+                        .collect::<Option<Vec<_>>>()?;
+                    return self.ty.intrinsic_type(intrinsic, &arg_types);
+                }
+
+                let sig = self.ty.function_sigs.get(self.name(*name))?.clone();
+                if sig.generics.is_empty() {
+                    return Some(sig.ret);
+                }
+
+                let generic_args = self.infer_generic_args(&sig, *name, &args);
+                let ret = unify::instantiate(self.ty, sig.ret, &generic_args);
+                self.ty.call_generics.insert(id, generic_args);
+                Some(ret)
+            }
+            hir::Expression::Field { base, name } => {
+                let base_ty = self.expr(*base)?;
+                let err = optionals::check_access(self.ty, self.hir, *base, base_ty);
+                self.errors.extend(err);
+                let (def, field, field_ty) = self.record_field(base_ty, self.name(*name))?;
+                self.reference(Symbol::Field { def, field }, *name);
+                Some(field_ty)
+            }
+            hir::Expression::Index { base, index } => {
+                let base_ty = self.view(*base);
+                self.expr(*index);
+                if let Some(base_ty) = base_ty {
+                    let err = optionals::check_access(self.ty, self.hir, *base, base_ty);
+                    self.errors.extend(err);
+                }
+                if let hir::Expression::Slice { .. } = self.hir.expressions[*base] {
+                    let problem = self
+                        .constant_integer(*index)
+                        .zip(self.view_len(*base))
+                        .and_then(|(index, len)| slices::index_problem(index, len));
+                    if let Some(problem) = problem {
+                        self.errors.push(Error::InvalidSlice {
+                            slice: self.hir.expression_fcs[&id],
+                            problem,
+                        });
+                    }
+                } else if let Some(len) = base_ty.and_then(|ty| self.ty.array_len(ty)) {
+                    let value = self.constant_integer(*index);
+                    if let Some(value) = value.filter(|value| *value >= len as i128) {
+                        self.errors.push(Error::IndexOutOfBounds {
+                            index: self.hir.expression_fcs[index],
+                            value,
+                            len,
+                        });
+                    }
+                }
+                self.ty.index_type(base_ty?)
+            }
+            hir::Expression::Slice { .. } => {
+                self.errors.push(Error::InvalidSlice {
+                    slice: self.hir.expression_fcs[&id],
+                    problem: SliceProblem::NotIndexed,
+                });
+                self.slice(id)
+            }
+            hir::Expression::As { base, ty } => {
+                let from = self.expr(*base);
+                let to = self.type_ref(*ty)?;
+                if let Some(from) = from {
+                    if let Err(problem) = self.ty.check_cast(from, to) {
+                        self.errors.push(Error::InvalidCast {
+                            cast: self.hir.expression_fcs[&id],
+                            from: self.ty.display_type(from).to_string(),
+                            to: self.ty.display_type(to).to_string(),
+                            bitcast: false,
+                            problem,
+                        });
+                    }
+                }
+                Some(to)
+            }
+            hir::Expression::LayoutQuery { ty, .. } => {
+                if let Some(queried) = self.type_ref(*ty) {
+                    self.ty.layout_types.insert(id, queried);
+                    if !self.in_array_size {
+                        if let Err((expr, problem)) = Evaluator::new(self.ty, self.hir).eval(id) {
+                            self.errors.push(Error::ConstEvaluation { expr, problem });
+                        }
+                    }
+                }
+                Some(self.ty.add_or_get_type(Type::UInt))
+            }
+            hir::Expression::Array(elements) => self.array(id, elements, expected),
+        }
+    }
+
+    /// Infer the generic arguments of a call from the types of the arguments.
+    fn infer_generic_args(
+        &mut self,
+        sig: &FunctionSig,
+        call_name: Id<Identifier>,
+        args: &[(Option<usize>, Id<hir::Expression>, Option<TypeId>)],
+    ) -> Vec<TypeId> {
+        let vars = sig
+            .generics
+            .iter()
+            .map(|_| self.ty.fresh_var())
+            .collect::<Vec<_>>();
+        let mut subst = Substitution::default();
+        // the argument each generic parameter was first inferred from
+        let mut bound_by = vec![None; sig.generics.len()];
+
+        for (index, expr, ty) in args {
+            let (param, arg) = match (index.and_then(|i| sig.args.get(i)), ty) {
+                (Some((_, param)), Some(arg)) => (*param, *arg),
+                _ => continue,
+            };
+            let arg_loc = self.hir.expression_fcs[expr];
+            let param = unify::instantiate(self.ty, param, &vars);
+
+            match unify::unify(self.ty, param, arg, &mut subst) {
+                Ok(()) => {}
+                Err(UnifyError::Conflict(var)) => {
+                    let gen = vars.iter().position(|v| *v == var);
+                    if let Some((gen, Some(previous_arg))) = gen.map(|gen| (gen, bound_by[gen])) {
+                        let previous_type = subst.apply(self.ty, var);
+                        self.errors.push(Error::ConflictingGenericArgument {
+                            generic_name: sig.generics[gen].clone(),
+                            arg: arg_loc,
+                            arg_type: self.ty.display_type(arg).to_string(),
+                            previous_arg,
+                            previous_type: self.ty.display_type(previous_type).to_string(),
+                        });
+                    }
+                }
+                // mismatched argument types are reported when checking the body
+                Err(_) => {}
+            }
+
+            for (gen, loc) in bound_by.iter_mut().enumerate() {
+                if loc.is_none() && subst.is_bound(vars[gen]) {
+                    *loc = Some(arg_loc);
+                }
+            }
+        }
+
+        let func = &self.hir.functions[sig.func_id];
+        for (gen, name) in sig.generics.iter().enumerate() {
+            let used = sig
+                .args
+                .iter()
+                .any(|(_, ty)| unify::contains_generic(self.ty, *ty, gen));
+            if !used {
+                let generic = func
+                    .generics
+                    .iter()
+                    .find(|g| self.hir.identifiers[**g] == *name)
+                    .map(|g| self.hir.identifier_fcs[g]);
+                if let Some(generic) = generic {
+                    self.errors.push(Error::UninferableGenericArgument {
+                        generic_name: name.clone(),
+                        call: self.hir.identifier_fcs[&call_name],
+                        generic,
+                    });
+                }
+            }
+        }
+
+        let error = self.ty.error_type();
+        vars.into_iter()
+            .map(|var| match subst.get(var) {
+                Some(_) => subst.apply(self.ty, var),
+                None => error,
+            })
+            .collect()
+    }
+
+    fn record_field(
+        &self,
+        ty: TypeId,
+        name: &str,
+    ) -> Option<(Id<TypeDefinition>, Id<VariableDef>, TypeId)> {
+        let (distinct_id, inner) = match self.ty.types.get(ty)? {
+            Type::Distinct { distinct_id, inner } => (*distinct_id, *inner),
+            _ => return None,
+        };
+        let field_ty = match self.ty.types.get(inner)? {
+            Type::Record { fields } => {
+                let name = self.ty.types.find_name(name)?;
+                fields.iter().find(|(n, _)| *n == name)?.1
+            }
+            _ => return None,
+        };
+        let def = *self.ty.distinct_defs.get(&distinct_id)?;
+        let field = match &self.hir.type_def_rhss[self.hir.type_defs[def].rhs] {
+            hir::TypeDefinitionRhs::Record { fields } => *fields
+                .iter()
+                .find(|f| self.hir.identifiers[self.hir.variable_defs[**f].name] == name)?,
+            _ => return None,
+        };
+        Some((def, field, field_ty))
+    }
+
+    /// Record the type of a parameter or variable for its uses.
+    fn declare(&mut self, sym: Symbol, ty: Option<TypeId>) {
+        if let Some(ty) = ty {
+            self.index.types.insert(sym, ty);
+        }
+    }
+
+    fn define(&mut self, sym: Symbol, name: Id<Identifier>) {
+        self.index.define(sym, self.hir.identifier_fcs[&name]);
+    }
+
+    fn reference(&mut self, sym: Symbol, name: Id<Identifier>) {
+        self.index.reference(sym, self.hir.identifier_fcs[&name]);
+    }
+
+    fn name(&self, id: Id<Identifier>) -> &'a str {
+        self.hir.identifiers[id].as_str()
+    }
+}
+
+/// The scalar type of the components of a value built by a constructor.
+fn constructor_scalar(ty: &hir::PrimitiveType) -> Option<Type> {
+    use hir::PrimitiveType as PT;
+
+    match ty {
+        PT::Int | PT::IntVec { .. } => Some(Type::Int),
+        PT::UInt | PT::UIntVec { .. } => Some(Type::UInt),
+        PT::Long | PT::LongVec { .. } => Some(Type::Long),
+        PT::ULong | PT::ULongVec { .. } => Some(Type::ULong),
+        PT::Float | PT::FloatVec { .. } | PT::FloatMat { .. } => Some(Type::Float),
+        PT::Radians | PT::Degrees => Some(Type::Float),
+        PT::Double | PT::DoubleVec { .. } | PT::DoubleMat { .. } => Some(Type::Double),
+        PT::Half | PT::HalfVec { .. } => Some(Type::Half),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check the files as one module, the file ids are their indices.
+    fn index(files: &[&str]) -> ReferenceIndex {
+        let mut hir_ctx = hir::Context::default();
+        let mut module = hir::Module::default();
+        for (file, src) in files.iter().enumerate() {
+            let ast = thiol_syntax::parser::parse_file(file, src).unwrap();
+            let lowered = thiol_ast_lowering::lower(&mut hir_ctx, &ast).unwrap();
+            module.types.extend(lowered.types);
+            module.consts.extend(lowered.consts);
+            module.functions.extend(lowered.functions);
+            module.programs.extend(lowered.programs);
+            module.spaces.extend(lowered.spaces);
+            module.static_asserts.extend(lowered.static_asserts);
+            module.uses.extend(lowered.uses);
+        }
+        let mut ty_ctx = Context::default();
+        crate::type_check(&mut ty_ctx, &hir_ctx, &module).unwrap();
+        ty_ctx.references
+    }
+
+    /// The location of the `nth` occurrence of `name` in a file.
+    fn loc(file: FileId, src: &str, name: &str, nth: usize) -> FileLocation {
+        let (start, _) = src.match_indices(name).nth(nth).unwrap();
+        FileLocation {
+            file,
+            start,
+            end: start + name.len(),
+        }
+    }
+
+    const LIGHTS: &str = "
+type
+    Light = record
+        colour: float3;
+        range: float;
+    end
+
+const
+    SCALE: float := 2.0;
+
+function attenuate(light: Light, d: float) returns float3
+begin
+    return light.colour * (light.range - d) * SCALE;
+end
+";
+
+    const SHADE: &str = "
+function shade(light: Light, d: float) returns float3
+begin
+    var lit: float3 := attenuate(light, d * SCALE);
+    return lit;
+end
+";
+
+    #[test]
+    fn definitions() {
+        let index = index(&[LIGHTS]);
+        for (name, nth) in [
+            ("Light", 0),
+            ("colour", 0),
+            ("range", 0),
+            ("SCALE", 0),
+            ("attenuate", 0),
+            ("light", 0),
+        ] {
+            let def = loc(0, LIGHTS, name, nth);
+            let sym = index.symbol(def).unwrap();
+            assert_eq!(index.definition(sym), Some(def), "{}", name);
+            assert!(!index.references(sym).contains(&def), "{}", name);
+        }
+
+        let light = index.symbol(loc(0, LIGHTS, "Light", 0)).unwrap();
+        assert!(matches!(light, Symbol::Type(_)));
+        let scale = index.symbol(loc(0, LIGHTS, "SCALE", 0)).unwrap();
+        assert!(matches!(scale, Symbol::Constant(_)));
+        let param = index.symbol(loc(0, LIGHTS, "light", 0)).unwrap();
+        assert!(matches!(param, Symbol::Parameter { index: 0, .. }));
+    }
+
+    #[test]
+    fn uses_across_modules() {
+        let index = index(&[LIGHTS, SHADE]);
+
+        let scale = index.symbol(loc(0, LIGHTS, "SCALE", 0)).unwrap();
+        assert_eq!(
+            index.references(scale),
+            &[loc(0, LIGHTS, "SCALE", 1), loc(1, SHADE, "SCALE", 0)]
+        );
+        let attenuate = index.symbol(loc(0, LIGHTS, "attenuate", 0)).unwrap();
+        assert_eq!(
+            index.references(attenuate),
+            &[loc(1, SHADE, "attenuate", 0)]
+        );
+
+        // the same offset in different files refers to different symbols
+        let call = loc(1, SHADE, "attenuate", 0);
+        assert_eq!(
+            index.definition_at(1, call.start + 2),
+            Some(loc(0, LIGHTS, "attenuate", 0))
+        );
+        assert_ne!(
+            index.symbol_at(0, call.start + 2).map(|(_, sym)| sym),
+            Some(attenuate)
+        );
+
+        // parameters of the same name in different functions are different
+        let outer = index.symbol(loc(0, LIGHTS, "light", 0)).unwrap();
+        let inner = index.symbol(loc(1, SHADE, "light", 0)).unwrap();
+        assert_ne!(outer, inner);
+        assert_eq!(index.references(inner), &[loc(1, SHADE, "light", 1)]);
+    }
+
+    #[test]
+    fn shadowed_locals() {
+        let src = "
+function sum(count: int) returns int
+begin
+    var total: int := 0;
+    for step in 0 to count do
+        var total: int := step;
+        var copy: int := total;
+    end
+    return total;
+end
+";
+        let index = index(&[src]);
+
+        let outer = index.symbol(loc(0, src, "total", 0)).unwrap();
+        let inner = index.symbol(loc(0, src, "total", 1)).unwrap();
+        assert!(matches!(outer, Symbol::Local(_)));
+        assert!(matches!(inner, Symbol::Local(_)));
+        assert_ne!(outer, inner);
+        assert_eq!(index.references(inner), &[loc(0, src, "total", 2)]);
+        assert_eq!(index.references(outer), &[loc(0, src, "total", 3)]);
+
+        let step = index.symbol(loc(0, src, "step", 0)).unwrap();
+        assert!(matches!(step, Symbol::LoopVariable(_)));
+        assert_eq!(index.references(step), &[loc(0, src, "step", 1)]);
+    }
+
+    #[test]
+    fn fields() {
+        let index = index(&[LIGHTS]);
+
+        let colour = index.symbol(loc(0, LIGHTS, "colour", 0)).unwrap();
+        let range = index.symbol(loc(0, LIGHTS, "range", 0)).unwrap();
+        assert!(matches!(colour, Symbol::Field { .. }));
+        assert_eq!(index.references(colour), &[loc(0, LIGHTS, "colour", 1)]);
+        assert_eq!(index.references(range), &[loc(0, LIGHTS, "range", 1)]);
+
+        // the base of a field access refers to the parameter
+        let light = index.symbol(loc(0, LIGHTS, "light", 0)).unwrap();
+        assert_eq!(
+            index.references(light),
+            &[loc(0, LIGHTS, "light", 1), loc(0, LIGHTS, "light", 2)]
+        );
+        let range_use = loc(0, LIGHTS, "range", 1);
+        assert_eq!(
+            index.definition_at(0, range_use.end),
+            Some(loc(0, LIGHTS, "range", 0))
+        );
+    }
+}
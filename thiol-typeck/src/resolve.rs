@@ -0,0 +1,584 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Resolution of the identifiers of a module.
+//!
+//! Before anything is type checked, every use of a type, generic parameter,
+//! function, constant or local variable is looked up and recorded in a
+//! [`ResolutionTable`]. Names that refer to nothing are reported here and
+//! nowhere else, so the undefined names of a module are all reported before
+//! its type errors, and tooling can ask what an identifier refers to
+//! without depending on the types.
+//!
+//! Items are looked up by their qualified name, the lowering already
+//! qualifies the names that refer to items of other modules. A local
+//! variable is in scope after its declaration until the end of its block,
+//! and hides the constants and intrinsics with the same name.
+
+use std::collections::HashMap;
+
+use hir::{Function, Identifier, Statement, TypeDefinition, VariableDef};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::derives::{self, Derive};
+use crate::{suggestions, Error, Intrinsic, Symbol};
+
+/// What a use of an identifier refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    Symbol(Symbol),
+    /// a call of an intrinsic, which has no declaration
+    Intrinsic(Intrinsic),
+    /// a call of a function derived from a record, see [`crate::derives`]
+    Derived {
+        def: Id<TypeDefinition>,
+        derive: Derive,
+    },
+}
+
+/// The resolutions of the identifiers of a module.
+///
+/// Only uses of names are recorded, not the identifiers declaring them, and
+/// neither fields nor named arguments, which can only be resolved once the
+/// type of the record or the called function is known.
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionTable {
+    names: HashMap<Id<Identifier>, Resolution>,
+}
+
+impl ResolutionTable {
+    /// What the identifier refers to, `None` if it is undefined or not a use
+    /// of a name.
+    pub fn get(&self, ident: Id<Identifier>) -> Option<Resolution> {
+        self.names.get(&ident).copied()
+    }
+
+    /// The symbol the identifier refers to, if it refers to one.
+    pub fn symbol(&self, ident: Id<Identifier>) -> Option<Symbol> {
+        match self.get(ident)? {
+            Resolution::Symbol(sym) => Some(sym),
+            Resolution::Intrinsic(_) | Resolution::Derived { .. } => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id<Identifier>, Resolution)> + '_ {
+        self.names.iter().map(|(ident, res)| (*ident, *res))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Type,
+    Variable,
+    Function,
+}
+
+/// Resolve the identifiers of a module, reporting the undefined ones.
+pub(crate) fn resolve_names(
+    module: &hir::Module,
+    hir_ctx: &hir::Context,
+) -> (ResolutionTable, Vec<Error>) {
+    let mut resolver = Resolver {
+        hir: hir_ctx,
+        table: ResolutionTable::default(),
+        types: HashMap::new(),
+        functions: HashMap::new(),
+        derived: HashMap::new(),
+        consts: HashMap::new(),
+        generics: HashMap::new(),
+        scopes: vec![],
+        undefined: vec![],
+        errors: vec![],
+    };
+    for id in &module.types {
+        let name = resolver.name(hir_ctx.type_defs[*id].name);
+        resolver.types.entry(name).or_insert(*id);
+    }
+    for id in &module.functions {
+        let name = resolver.name(hir_ctx.functions[*id].name);
+        resolver.functions.entry(name).or_insert(*id);
+    }
+    for id in &module.types {
+        for (derive, name) in derives::derived_names(hir_ctx, *id) {
+            resolver.derived.entry(name).or_insert((*id, derive));
+        }
+    }
+    for id in &module.consts {
+        let name = resolver.name(hir_ctx.variable_defs[*id].name);
+        resolver.consts.entry(name).or_insert(*id);
+    }
+
+    // the paths of `use` declarations that refer to nothing are reported
+    // when lowering
+    for id in &module.uses {
+        let name = resolver.name(*id);
+        let sym = resolver
+            .types
+            .get(name)
+            .map(|def| Symbol::Type(*def))
+            .or_else(|| resolver.functions.get(name).map(|f| Symbol::Function(*f)))
+            .or_else(|| resolver.consts.get(name).map(|c| Symbol::Constant(*c)));
+        if let Some(sym) = sym {
+            resolver.resolve(*id, Resolution::Symbol(sym));
+        }
+    }
+
+    // undefined spaces and constants of space transforms are reported when
+    // checking the spaces
+    for id in &module.spaces {
+        if let Some((_, via)) = hir_ctx.spaces[*id].parent {
+            if let Some(c) = resolver.consts.get(resolver.name(via)) {
+                resolver.resolve(via, Resolution::Symbol(Symbol::Constant(*c)));
+            }
+        }
+    }
+
+    for id in &module.types {
+        let def = &hir_ctx.type_defs[*id];
+        for (index, gen) in def.generics.iter().enumerate() {
+            let sym = Symbol::GenericParam { def: *id, index };
+            resolver.generics.entry(resolver.name(*gen)).or_insert(sym);
+        }
+        match &hir_ctx.type_def_rhss[def.rhs] {
+            hir::TypeDefinitionRhs::Distinct(ty) | hir::TypeDefinitionRhs::Alias(ty) => {
+                resolver.type_ref(*ty);
+            }
+            hir::TypeDefinitionRhs::Record { fields } => {
+                for field in fields {
+                    resolver.type_ref(hir_ctx.variable_defs[*field].type_);
+                }
+            }
+        }
+        resolver.end_item();
+    }
+
+    for id in &module.consts {
+        let def = &hir_ctx.variable_defs[*id];
+        resolver.type_ref(def.type_);
+        if let Some(rhs) = def.rhs {
+            resolver.expr(rhs);
+        }
+        resolver.end_item();
+    }
+
+    for id in &module.static_asserts {
+        resolver.expr(hir_ctx.static_asserts[*id].condition);
+        resolver.end_item();
+    }
+
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        for (index, gen) in func.generics.iter().enumerate() {
+            let sym = Symbol::FunctionGenericParam { func: *id, index };
+            resolver.generics.entry(resolver.name(*gen)).or_insert(sym);
+        }
+        resolver.scopes.push(HashMap::new());
+        for (index, (name, ty, _)) in func.args.iter().enumerate() {
+            resolver.type_ref(*ty);
+            resolver.declare(*name, Symbol::Parameter { func: *id, index });
+        }
+        resolver.type_ref(func.ret_type);
+        resolver.block(&func.body);
+        resolver.scopes.pop();
+        resolver.end_item();
+    }
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        resolver.scopes.push(HashMap::new());
+        for var in prog
+            .inputs
+            .iter()
+            .chain(&prog.outputs)
+            .chain(&prog.workgroup)
+        {
+            resolver.local(*var);
+        }
+        resolver.block(&prog.body);
+        resolver.scopes.pop();
+        resolver.end_item();
+    }
+
+    (resolver.table, resolver.errors)
+}
+
+struct Resolver<'a> {
+    hir: &'a hir::Context,
+    table: ResolutionTable,
+
+    types: HashMap<&'a str, Id<TypeDefinition>>,
+    functions: HashMap<&'a str, Id<Function>>,
+    derived: HashMap<String, (Id<TypeDefinition>, Derive)>,
+    consts: HashMap<&'a str, Id<VariableDef>>,
+    /// the generic parameters of the item being resolved
+    generics: HashMap<&'a str, Symbol>,
+    scopes: Vec<HashMap<&'a str, Symbol>>,
+
+    /// the undefined names of the item being resolved, all uses of a name in
+    /// an item are reported together
+    undefined: Vec<(Kind, &'a str, Vec<hir::FileLocation>, Vec<String>)>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Report the undefined names of the item that was resolved.
+    fn end_item(&mut self) {
+        self.generics.clear();
+        for (kind, name, uses, suggestions) in self.undefined.drain(..) {
+            let name = name.to_string();
+            self.errors.push(match kind {
+                Kind::Type => Error::UndefinedType {
+                    name,
+                    uses,
+                    suggestions,
+                },
+                Kind::Variable => Error::UndefinedVariable {
+                    name,
+                    uses,
+                    suggestions,
+                },
+                Kind::Function => Error::UndefinedFunction {
+                    name,
+                    uses,
+                    suggestions,
+                },
+            });
+        }
+    }
+
+    fn type_ref(&mut self, id: Id<hir::TypeReference>) {
+        match &self.hir.type_refs[id] {
+            hir::TypeReference::Primitive(_)
+            | hir::TypeReference::Image { .. }
+            | hir::TypeReference::Texture { sampled: None, .. }
+            | hir::TypeReference::Sampler { .. }
+            | hir::TypeReference::AccelerationStructure => {}
+            hir::TypeReference::OpenArray(base)
+            | hir::TypeReference::Normalized(base)
+            | hir::TypeReference::Optional(base)
+            | hir::TypeReference::Texture {
+                sampled: Some(base),
+                ..
+            } => self.type_ref(*base),
+            hir::TypeReference::Record { fields } => {
+                for (_, ty) in fields {
+                    self.type_ref(*ty);
+                }
+            }
+            hir::TypeReference::Array { base, size } => {
+                if let hir::ArraySize::Expression(size) = size {
+                    self.expr(*size);
+                }
+                self.type_ref(*base);
+            }
+            hir::TypeReference::Named { name, generics } => {
+                let name_s = self.name(*name);
+                if let Some(sym) = self.generics.get(name_s) {
+                    self.resolve(*name, Resolution::Symbol(*sym));
+                } else if let Some(def) = self.types.get(name_s) {
+                    self.resolve(*name, Resolution::Symbol(Symbol::Type(*def)));
+                } else {
+                    self.undefined(Kind::Type, *name);
+                }
+                for gen in generics {
+                    self.type_ref(*gen);
+                }
+            }
+        }
+    }
+
+    fn local(&mut self, id: Id<VariableDef>) {
+        let def = &self.hir.variable_defs[id];
+        self.type_ref(def.type_);
+        // the variable isn't in scope in its own initializer
+        if let Some(rhs) = def.rhs {
+            self.expr(rhs);
+        }
+        self.declare(def.name, Symbol::Local(id));
+    }
+
+    fn block(&mut self, block: &[Id<Statement>]) {
+        self.scopes.push(HashMap::new());
+        for stmt in block {
+            self.statement(*stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, id: Id<Statement>) {
+        match &self.hir.statements[id] {
+            Statement::Var(var) => self.local(*var),
+            Statement::Becomes { lhs, rhs } => {
+                self.expr(*lhs);
+                self.expr(*rhs);
+            }
+            Statement::Destructure { lhs, rhs } => {
+                for e in lhs {
+                    self.expr(*e);
+                }
+                self.expr(*rhs);
+            }
+            Statement::Return(e) => {
+                if let Some(e) = e {
+                    self.expr(*e);
+                }
+            }
+            Statement::Expr(e) => self.expr(*e),
+            Statement::Break | Statement::Continue | Statement::Discard => {}
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                self.expr(*cond);
+                self.block(then_body);
+                self.block(else_body);
+            }
+            Statement::For {
+                iter_name,
+                from,
+                to,
+                body,
+                ..
+            } => {
+                self.expr(*from);
+                self.expr(*to);
+                self.scopes.push(HashMap::new());
+                self.declare(*iter_name, Symbol::LoopVariable(id));
+                self.block(body);
+                self.scopes.pop();
+            }
+            Statement::Match { value, arms } => {
+                self.expr(*value);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    if let hir::MatchPattern::Some(name) = arm.pattern {
+                        self.declare(name, Symbol::MatchBinding(id));
+                    }
+                    self.block(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, id: Id<hir::Expression>) {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            hir::Expression::Literal(_) => {}
+            hir::Expression::Variable(name) => {
+                let name_s = self.name(*name);
+                let local = self.scopes.iter().rev().find_map(|s| s.get(name_s));
+                if let Some(sym) = local {
+                    self.resolve(*name, Resolution::Symbol(*sym));
+                } else if let Some(c) = self.consts.get(name_s) {
+                    self.resolve(*name, Resolution::Symbol(Symbol::Constant(*c)));
+                } else {
+                    self.undefined(Kind::Variable, *name);
+                }
+            }
+            hir::Expression::PrimitiveOp(op) => match &self.hir.prim_ops[*op] {
+                PO::Neg(e) | PO::Pos(e) => self.expr(*e),
+                PO::Add(a, b)
+                | PO::Sub(a, b)
+                | PO::Mul(a, b)
+                | PO::Div(a, b)
+                | PO::Mod(a, b)
+                | PO::Gt(a, b)
+                | PO::Gte(a, b)
+                | PO::Lt(a, b)
+                | PO::Lte(a, b)
+                | PO::Eq(a, b)
+                | PO::Neq(a, b) => {
+                    self.expr(*a);
+                    self.expr(*b);
+                }
+                PO::Constructor {
+                    pos_args, nam_args, ..
+                } => {
+                    for e in pos_args {
+                        self.expr(*e);
+                    }
+                    for (_, e) in nam_args {
+                        self.expr(*e);
+                    }
+                }
+            },
+            hir::Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => {
+                let name_s = self.name(*name);
+                if let Some(func) = self.functions.get(name_s) {
+                    self.resolve(*name, Resolution::Symbol(Symbol::Function(*func)));
+                } else if let Some(def) = self.types.get(name_s) {
+                    // the constructor of a record
+                    self.resolve(*name, Resolution::Symbol(Symbol::Type(*def)));
+                } else if let Some((def, derive)) = self.derived.get(name_s) {
+                    let (def, derive) = (*def, *derive);
+                    self.resolve(*name, Resolution::Derived { def, derive });
+                } else if let Some(intrinsic) = Intrinsic::from_name(name_s) {
+                    self.resolve(*name, Resolution::Intrinsic(intrinsic));
+                } else {
+                    self.undefined(Kind::Function, *name);
+                }
+                for e in pos_args {
+                    self.expr(*e);
+                }
+                for (_, e) in nam_args {
+                    self.expr(*e);
+                }
+            }
+            hir::Expression::Field { base, .. } => self.expr(*base),
+            hir::Expression::Index { base, index } => {
+                self.expr(*base);
+                self.expr(*index);
+            }
+            hir::Expression::Slice { base, lo, hi } => {
+                self.expr(*base);
+                self.expr(*lo);
+                self.expr(*hi);
+            }
+            hir::Expression::As { base, ty } => {
+                self.expr(*base);
+                self.type_ref(*ty);
+            }
+            hir::Expression::LayoutQuery { ty, .. } => self.type_ref(*ty),
+            hir::Expression::Array(elements) => {
+                for e in elements {
+                    self.expr(*e);
+                }
+            }
+        }
+    }
+
+    fn declare(&mut self, name: Id<Identifier>, sym: Symbol) {
+        let name = self.name(name);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, sym);
+        }
+    }
+
+    fn resolve(&mut self, ident: Id<Identifier>, res: Resolution) {
+        self.table.names.insert(ident, res);
+    }
+
+    fn undefined(&mut self, kind: Kind, ident: Id<Identifier>) {
+        let name = self.name(ident);
+        let loc = match self.hir.path_fcs.get(&ident) {
+            Some(&path) => path,
+            None => self.hir.identifier_fcs[&ident],
+        };
+        if let Some((_, _, uses, _)) = self
+            .undefined
+            .iter_mut()
+            .find(|(k, n, _, _)| *k == kind && *n == name)
+        {
+            uses.push(loc);
+            return;
+        }
+
+        let suggestions = match kind {
+            Kind::Type => {
+                let generics = self.generics.keys().copied();
+                suggestions::similar_names(name, self.types.keys().copied().chain(generics))
+            }
+            Kind::Variable => {
+                let locals = self.scopes.iter().flat_map(|s| s.keys().copied());
+                suggestions::similar_names(name, locals.chain(self.consts.keys().copied()))
+            }
+            Kind::Function => {
+                let intrinsics = Intrinsic::ALL.iter().map(|i| i.name());
+                let derived = self.derived.keys().map(|name| name.as_str());
+                let candidates = self.functions.keys().chain(self.types.keys()).copied();
+                suggestions::similar_names(name, candidates.chain(derived).chain(intrinsics))
+            }
+        };
+        self.undefined.push((kind, name, vec![loc], suggestions));
+    }
+
+    fn name(&self, id: Id<Identifier>) -> &'a str {
+        self.hir.identifiers[id].as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An identifier at a distinct location
+    fn ident(ctx: &mut hir::Context, name: &str) -> Id<Identifier> {
+        let start = ctx.identifiers.len() * 10;
+        let id = ctx.identifiers.alloc(name.to_string());
+        let loc = hir::FileLocation {
+            file: 0,
+            start,
+            end: start + name.len(),
+        };
+        ctx.identifier_fcs.insert(id, loc);
+        id
+    }
+
+    fn statement(ctx: &mut hir::Context, var: Id<Identifier>) -> Id<Statement> {
+        let e = ctx.expressions.alloc(hir::Expression::Variable(var));
+        ctx.statements.alloc(Statement::Return(Some(e)))
+    }
+
+    #[test]
+    fn locals_and_undefined_names() {
+        let mut ctx = hir::Context::default();
+        let float = ctx
+            .type_refs
+            .alloc(hir::TypeReference::Primitive(hir::PrimitiveType::Float));
+        let name = ident(&mut ctx, "scale");
+        let param = ident(&mut ctx, "length");
+        let used = ident(&mut ctx, "length");
+        let typo = ident(&mut ctx, "lenght");
+        let typo_again = ident(&mut ctx, "lenght");
+        let body = vec![
+            statement(&mut ctx, used),
+            statement(&mut ctx, typo),
+            statement(&mut ctx, typo_again),
+        ];
+        let func = ctx.functions.alloc(Function {
+            attrs: vec![],
+            visibility: hir::Visibility::Private,
+            name,
+            generics: vec![],
+            args: vec![(param, float, hir::ParamMode::In)],
+            arg_attrs: vec![vec![]],
+            ret_type: float,
+            body,
+        });
+        let module = hir::Module {
+            functions: vec![func],
+            ..Default::default()
+        };
+
+        let (table, errors) = resolve_names(&module, &ctx);
+        assert_eq!(
+            table.symbol(used),
+            Some(Symbol::Parameter { func, index: 0 })
+        );
+        assert_eq!(table.get(typo), None);
+        match errors.as_slice() {
+            [Error::UndefinedVariable {
+                name,
+                uses,
+                suggestions,
+            }] => {
+                assert_eq!(name, "lenght");
+                assert_eq!(
+                    uses,
+                    &[ctx.identifier_fcs[&typo], ctx.identifier_fcs[&typo_again]]
+                );
+                assert_eq!(suggestions, &["length".to_string()]);
+            }
+            errors => panic!("unexpected errors {:?}", errors),
+        }
+    }
+}
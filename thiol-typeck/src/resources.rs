@@ -0,0 +1,118 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The resources each program accesses, so that pipelines only need to bind
+//! those.
+
+use std::collections::HashSet;
+
+use thiol_hir as hir;
+
+use hir::{Identifier, Program, VariableDef};
+use id_arena::Id;
+
+use crate::effects::buffer_writes;
+use crate::layout::buffer_class;
+use crate::{
+    BufferClass, Callable, Context, ImageAccess, ImageDim, ImageFormat, ResourceBinding, Symbol,
+    TextureResource,
+};
+
+/// A constant bound to a buffer that a program accesses, directly or through
+/// the functions it calls
+#[derive(Debug, Clone)]
+pub struct ResourceUsage {
+    pub name: Identifier,
+    pub constant: Id<VariableDef>,
+    pub class: BufferClass,
+    /// `None` for push constants
+    pub binding: Option<ResourceBinding>,
+    /// whether the program writes to the buffer, otherwise it only reads it
+    pub written: bool,
+    /// the dimensions, format and access of a storage image
+    pub image: Option<(ImageDim, ImageFormat, ImageAccess)>,
+    /// the texture or sampler of a constant bound like a uniform buffer
+    pub texture: Option<TextureResource>,
+}
+
+impl Context {
+    /// The resources used by a program, in the order the constants are
+    /// declared.
+    pub fn program_resources(
+        &self,
+        hir_ctx: &hir::Context,
+        module: &hir::Module,
+        program: Id<Program>,
+    ) -> Vec<ResourceUsage> {
+        let callables = reachable(self, Callable::Program(program));
+
+        let mut written = HashSet::new();
+        for callable in &callables {
+            let body = match callable {
+                Callable::Function(id) => &hir_ctx.functions[*id].body,
+                Callable::Program(id) => &hir_ctx.programs[*id].body,
+            };
+            written.extend(buffer_writes(self, hir_ctx, body));
+        }
+
+        let spans = callables
+            .iter()
+            .map(|callable| match callable {
+                Callable::Function(id) => hir_ctx.function_fcs[id],
+                Callable::Program(id) => hir_ctx.program_fcs[id],
+            })
+            .collect::<Vec<_>>();
+
+        let mut resources = vec![];
+        for id in &module.consts {
+            let class = match buffer_class(hir_ctx, *id) {
+                Some(class) => class,
+                None => continue,
+            };
+            let used = self
+                .references
+                .references(Symbol::Constant(*id))
+                .iter()
+                .any(|loc| {
+                    spans.iter().any(|span| {
+                        span.file == loc.file && span.start <= loc.start && loc.end <= span.end
+                    })
+                });
+            if !used {
+                continue;
+            }
+
+            let ty = self
+                .consts
+                .get(&hir_ctx.identifiers[hir_ctx.variable_defs[*id].name])
+                .map(|sig| sig.type_);
+            resources.push(ResourceUsage {
+                name: hir_ctx.identifiers[hir_ctx.variable_defs[*id].name].clone(),
+                constant: *id,
+                class,
+                binding: self.bindings.get(id).copied(),
+                written: written.contains(id),
+                image: ty.and_then(|ty| self.image(ty)),
+                texture: ty.and_then(|ty| self.texture_resource(ty)),
+            });
+        }
+        resources
+    }
+}
+
+/// The program or function and all functions it calls, directly or
+/// indirectly.
+pub(crate) fn reachable(ty_ctx: &Context, start: Callable) -> Vec<Callable> {
+    let mut found = vec![start];
+    let mut i = 0;
+    while i < found.len() {
+        for (callee, _) in ty_ctx.call_graph.dependencies(found[i]) {
+            if !found.contains(&callee) {
+                found.push(callee);
+            }
+        }
+        i += 1;
+    }
+    found
+}
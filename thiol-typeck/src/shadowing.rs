@@ -0,0 +1,228 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Declarations that hide another declaration with the same name.
+//!
+//! A local variable, parameter or loop variable hides the locals of the
+//! blocks around it, the constants of its module and the intrinsics, and a
+//! generic parameter hides the types of its module. Hiding is allowed, the
+//! hidden declarations are reported in the `shadowing` lint group so that a
+//! project can forbid it.
+//!
+//! Declaring a name twice in the same scope is an error. The parameters of
+//! a function, the inputs, outputs and workgroup variables of a program and
+//! the variable of a `for` loop are in the scope of the top level of the
+//! body, as that is where the backends declare them.
+
+use std::collections::HashMap;
+
+use hir::{FileLocation, Identifier, Statement};
+use id_arena::Id;
+use thiol_hir as hir;
+
+use crate::{Error, Intrinsic, Warning};
+
+/// The declaration a name hides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shadowed {
+    /// a local variable, parameter or loop variable of a block around it
+    Local(FileLocation),
+    /// a constant of the module
+    Constant(FileLocation),
+    /// a type of the module
+    Type(FileLocation),
+    Intrinsic,
+}
+
+pub(crate) fn check_shadowing(
+    module: &hir::Module,
+    hir_ctx: &hir::Context,
+) -> (Vec<Error>, Vec<Warning>) {
+    let name_loc = |name: Id<Identifier>| {
+        (
+            hir_ctx.identifiers[name].as_str(),
+            hir_ctx.identifier_fcs[&name],
+        )
+    };
+    let mut checker = Checker {
+        hir: hir_ctx,
+        consts: HashMap::new(),
+        types: HashMap::new(),
+        module: vec![],
+        scopes: vec![],
+        errors: vec![],
+        warnings: vec![],
+    };
+    for id in &module.consts {
+        let (name, loc) = name_loc(hir_ctx.variable_defs[*id].name);
+        checker.consts.entry(name).or_insert(loc);
+    }
+    for id in &module.types {
+        let (name, loc) = name_loc(hir_ctx.type_defs[*id].name);
+        checker.types.entry(name).or_insert(loc);
+    }
+
+    for id in &module.types {
+        let def = &hir_ctx.type_defs[*id];
+        checker.enter(def.name);
+        for gen in &def.generics {
+            checker.generic(*gen);
+        }
+    }
+    for id in &module.functions {
+        let func = &hir_ctx.functions[*id];
+        checker.enter(func.name);
+        let (name, loc) = name_loc(func.name);
+        let unqualified = name.rsplit("::").next().unwrap_or(name);
+        if Intrinsic::from_name(unqualified).is_some() {
+            checker.shadows(func.name, loc, Shadowed::Intrinsic);
+        }
+        for gen in &func.generics {
+            checker.generic(*gen);
+        }
+        checker.scopes.push(HashMap::new());
+        for (arg, _, _) in &func.args {
+            checker.declare(*arg);
+        }
+        checker.statements(&func.body);
+        checker.scopes.pop();
+    }
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        checker.enter(prog.name);
+        checker.scopes.push(HashMap::new());
+        for var in prog
+            .inputs
+            .iter()
+            .chain(&prog.outputs)
+            .chain(&prog.workgroup)
+        {
+            checker.declare(hir_ctx.variable_defs[*var].name);
+        }
+        checker.statements(&prog.body);
+        checker.scopes.pop();
+    }
+
+    (checker.errors, checker.warnings)
+}
+
+struct Checker<'a> {
+    hir: &'a hir::Context,
+    consts: HashMap<&'a str, FileLocation>,
+    types: HashMap<&'a str, FileLocation>,
+    /// the path of the module of the item being checked
+    module: Vec<&'a str>,
+    scopes: Vec<HashMap<&'a str, FileLocation>>,
+    errors: Vec<Error>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Checker<'a> {
+    /// Start checking the item with the qualified name.
+    fn enter(&mut self, item: Id<Identifier>) {
+        let mut path = self.hir.identifiers[item].split("::").collect::<Vec<_>>();
+        path.pop();
+        self.module = path;
+    }
+
+    /// The item of the module, or a module around it, with the name.
+    fn item(&self, items: &HashMap<&str, FileLocation>, name: &str) -> Option<FileLocation> {
+        (0..=self.module.len()).rev().find_map(|depth| {
+            let mut path = self.module[..depth].to_vec();
+            path.push(name);
+            items.get(path.join("::").as_str()).copied()
+        })
+    }
+
+    fn generic(&mut self, gen: Id<Identifier>) {
+        let name = self.hir.identifiers[gen].as_str();
+        if let Some(ty) = self.item(&self.types, name) {
+            self.shadows(gen, self.hir.identifier_fcs[&gen], Shadowed::Type(ty));
+        }
+    }
+
+    fn declare(&mut self, ident: Id<Identifier>) {
+        let name = self.hir.identifiers[ident].as_str();
+        let loc = self.hir.identifier_fcs[&ident];
+
+        if let Some(previous) = self.scopes.last().and_then(|scope| scope.get(name)) {
+            self.errors.push(Error::LocalRedefinition {
+                name: name.to_string(),
+                previous: *previous,
+                redefinition: loc,
+            });
+            return;
+        }
+
+        let outer = self.scopes.iter().rev().find_map(|scope| scope.get(name));
+        let shadowed = match outer {
+            Some(local) => Some(Shadowed::Local(*local)),
+            None => self
+                .item(&self.consts, name)
+                .map(Shadowed::Constant)
+                .or_else(|| Intrinsic::from_name(name).map(|_| Shadowed::Intrinsic)),
+        };
+        if let Some(shadowed) = shadowed {
+            self.shadows(ident, loc, shadowed);
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, loc);
+        }
+    }
+
+    fn shadows(&mut self, ident: Id<Identifier>, declaration: FileLocation, shadowed: Shadowed) {
+        self.warnings.push(Warning::Shadowing {
+            name: self.hir.identifiers[ident].clone(),
+            declaration,
+            shadowed,
+        });
+    }
+
+    fn block(&mut self, block: &[Id<Statement>]) {
+        self.scopes.push(HashMap::new());
+        self.statements(block);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, block: &[Id<Statement>]) {
+        for stmt in block {
+            match &self.hir.statements[*stmt] {
+                Statement::Var(def) => self.declare(self.hir.variable_defs[*def].name),
+                Statement::If {
+                    then_body,
+                    else_body,
+                    ..
+                } => {
+                    self.block(then_body);
+                    self.block(else_body);
+                }
+                Statement::For {
+                    iter_name, body, ..
+                } => {
+                    self.scopes.push(HashMap::new());
+                    self.declare(*iter_name);
+                    self.statements(body);
+                    self.scopes.pop();
+                }
+                Statement::Match { arms, .. } => {
+                    for arm in arms {
+                        self.scopes.push(HashMap::new());
+                        if let hir::MatchPattern::Some(name) = arm.pattern {
+                            self.declare(name);
+                        }
+                        self.statements(&arm.body);
+                        self.scopes.pop();
+                    }
+                }
+                Statement::Becomes { .. }
+                | Statement::Destructure { .. }
+                | Statement::Return(_)
+                | Statement::Expr(_)
+                | Statement::Break
+                | Statement::Continue
+                | Statement::Discard => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Views of a range of the elements of an array.
+//!
+//! `a[lo..hi]` is an open array of the elements of `a` from `lo` up to, but
+//! not including, `hi`. The view isn't stored anywhere: it is indexed or
+//! sliced again, which the backends turn into an index into `a`. Indices
+//! into a view are clamped to it at runtime, bounds and indices that are
+//! literals are checked here.
+
+use crate::{Context, Type, TypeId};
+
+/// Why a slice is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SliceProblem {
+    /// only arrays can be sliced
+    NotAnArray { type_name: String },
+    /// a slice is a view that isn't stored, it can only be indexed
+    NotIndexed,
+    /// `lo` is greater than `hi`
+    Reversed { lo: i128, hi: i128 },
+    /// the view ends after the end of the array
+    PastEnd { hi: i128, len: i128 },
+    /// an index outside of the view
+    IndexOutOfBounds { index: i128, len: i128 },
+}
+
+impl Context {
+    /// The type of `a[lo..hi]`, an open array of the elements of `a`.
+    pub(crate) fn slice_type(&mut self, base: TypeId) -> Option<TypeId> {
+        let elem = match self.types.get(self.strip_distinct(base))? {
+            Type::Array { base, .. } | Type::OpenArray { base } => *base,
+            _ => return None,
+        };
+        Some(self.add_or_get_type(Type::OpenArray { base: elem }))
+    }
+}
+
+/// Check the bounds of a slice against each other and the length of the
+/// sliced array, as far as they are known.
+pub(crate) fn bounds_problem(
+    lo: Option<i128>,
+    hi: Option<i128>,
+    len: Option<i128>,
+) -> Option<SliceProblem> {
+    match (lo, hi, len) {
+        (Some(lo), Some(hi), _) if lo > hi => Some(SliceProblem::Reversed { lo, hi }),
+        (_, Some(hi), Some(len)) if hi > len => Some(SliceProblem::PastEnd { hi, len }),
+        _ => None,
+    }
+}
+
+/// Check an index into a view of `len` elements.
+pub(crate) fn index_problem(index: i128, len: i128) -> Option<SliceProblem> {
+    (index < 0 || index >= len).then_some(SliceProblem::IndexOutOfBounds { index, len })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_bounds() {
+        assert_eq!(bounds_problem(Some(0), Some(4), Some(4)), None);
+        assert_eq!(bounds_problem(None, Some(4), None), None);
+        assert_eq!(
+            bounds_problem(Some(3), Some(2), None),
+            Some(SliceProblem::Reversed { lo: 3, hi: 2 })
+        );
+        assert_eq!(
+            bounds_problem(None, Some(5), Some(4)),
+            Some(SliceProblem::PastEnd { hi: 5, len: 4 })
+        );
+        assert_eq!(index_problem(1, 2), None);
+        assert_eq!(
+            index_problem(2, 2),
+            Some(SliceProblem::IndexOutOfBounds { index: 2, len: 2 })
+        );
+    }
+}
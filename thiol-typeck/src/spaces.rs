@@ -0,0 +1,592 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Coordinate spaces and the transforms between them.
+//!
+//! `space World: parent Model via model_to_world;` places a space in its
+//! parent, the constant after `via` transforms from the parent space into
+//! the new one. The spaces of a module form a forest, so every two spaces in
+//! the same tree are connected by exactly one chain of transforms.
+//!
+//! Modules without space declarations can name any space in their types.
+//! As soon as a module declares one space, all spaces it refers to have to
+//! be declared, which the reference index checks.
+//!
+//! With [`SpaceCheck::Report`], a vector in one space stored in or passed to
+//! a vector in another space is an error that names the transforms leading
+//! from one space to the other. [`SpaceCheck::Insert`] applies the
+//! transforms instead, where the constants are square matrices the vector
+//! can be multiplied with.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, Identifier, SpaceDefinition};
+use id_arena::Id;
+
+use crate::layout::components;
+use crate::{suggestions, Context, Error, Name, Type, TypeId, VecType};
+
+/// What the checker does with a vector in one space used where a vector in
+/// another space is expected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum SpaceCheck {
+    /// spaces are only documentation
+    #[default]
+    Off,
+    /// report the mismatch with the transforms between the spaces
+    Report,
+    /// apply the transforms between the spaces, report the mismatch if
+    /// they can't be applied
+    Insert,
+}
+
+impl SpaceCheck {
+    pub const ALL: &'static [SpaceCheck] =
+        &[SpaceCheck::Off, SpaceCheck::Report, SpaceCheck::Insert];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpaceCheck::Off => "off",
+            SpaceCheck::Report => "report",
+            SpaceCheck::Insert => "insert",
+        }
+    }
+}
+
+impl FromStr for SpaceCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SpaceCheck::ALL
+            .iter()
+            .copied()
+            .find(|check| check.name() == s)
+            .ok_or_else(|| {
+                let names = SpaceCheck::ALL.iter().map(|c| c.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown space check `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// A declared space with the space it is placed in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceSig {
+    pub space_id: Id<SpaceDefinition>,
+    /// the parent space and the constant transforming from it into this space
+    pub parent: Option<(Identifier, Identifier)>,
+}
+
+/// One transform of a chain between two spaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformStep {
+    /// the constant transforming from the parent into the child space
+    pub via: Identifier,
+    pub from: Identifier,
+    pub to: Identifier,
+    /// whether the step goes from the child to the parent, which uses the
+    /// inverse of the constant
+    pub inverse: bool,
+}
+
+impl fmt::Display for TransformStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.inverse {
+            write!(f, "inverse({})", self.via)
+        } else {
+            write!(f, "{}", self.via)
+        }
+    }
+}
+
+/// A transform chain the checker applies to a vector
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceTransform {
+    pub steps: Vec<TransformStep>,
+    /// the vector with one more component the matrices are applied to, and
+    /// the value of that component: 1 for points, 0 for directions
+    pub extended: Option<(TypeId, f64)>,
+    /// the number of components of the vector
+    pub components: usize,
+}
+
+/// Why the transform of a space declaration is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpaceTransformProblem {
+    UndefinedConstant {
+        suggestions: Vec<String>,
+    },
+    NotAMatrix {
+        type_name: String,
+    },
+    /// the matrix transforms between other spaces
+    WrongSpaces {
+        type_name: String,
+        parent: Identifier,
+    },
+}
+
+/// The spaces named in a primitive type.
+pub(crate) fn primitive_spaces(ty: &hir::PrimitiveType) -> Vec<Id<Identifier>> {
+    use hir::PrimitiveType as PT;
+
+    match ty {
+        PT::IntVec { space, .. }
+        | PT::UIntVec { space, .. }
+        | PT::LongVec { space, .. }
+        | PT::ULongVec { space, .. }
+        | PT::FloatVec { space, .. }
+        | PT::DoubleVec { space, .. }
+        | PT::HalfVec { space, .. } => space.iter().copied().collect(),
+        PT::FloatMat { transform, .. } | PT::DoubleMat { transform, .. } => transform
+            .iter()
+            .flat_map(|(from, to)| vec![*from, *to])
+            .collect(),
+        _ => vec![],
+    }
+}
+
+impl Context {
+    /// The transforms leading from one space to another, `None` if one of
+    /// them isn't declared or they are in different hierarchies.
+    pub fn transform_chain(&self, from: &str, to: &str) -> Option<Vec<TransformStep>> {
+        let from_path = self.space_ancestors(from)?;
+        let to_path = self.space_ancestors(to)?;
+        let common = from_path.iter().find(|space| to_path.contains(space))?;
+
+        let mut chain = vec![];
+        for space in from_path.iter().take_while(|space| *space != common) {
+            let (parent, via) = self.spaces[space].parent.clone()?;
+            chain.push(TransformStep {
+                via,
+                from: space.clone(),
+                to: parent,
+                inverse: true,
+            });
+        }
+        let down = to_path.iter().take_while(|space| *space != common);
+        let mut down = down
+            .map(|space| {
+                let (parent, via) = self.spaces[space].parent.clone()?;
+                Some(TransformStep {
+                    via,
+                    from: parent,
+                    to: space.clone(),
+                    inverse: false,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        down.reverse();
+        chain.extend(down);
+        Some(chain)
+    }
+
+    /// Whether a value of type `found` fits where a value of type `expected`
+    /// is expected, apart from the spaces of vectors and matrices, the
+    /// encodings of colours and normalized vectors, which are checked on their
+    /// own. Types with errors
+    /// fit everything, the errors are reported where the types are declared.
+    pub(crate) fn same_value_type(&self, found: TypeId, expected: TypeId) -> bool {
+        let without_space = |ty: TypeId| -> Option<Type> {
+            let mut ty = self.types.get(self.strip_normalized(ty))?.clone();
+            if let Type::IntVec { space, vtype, .. }
+            | Type::UIntVec { space, vtype, .. }
+            | Type::LongVec { space, vtype, .. }
+            | Type::ULongVec { space, vtype, .. }
+            | Type::FloatVec { space, vtype, .. }
+            | Type::DoubleVec { space, vtype, .. }
+            | Type::HalfVec { space, vtype, .. } = &mut ty
+            {
+                *space = None;
+                if *vtype == VecType::SrgbColour {
+                    *vtype = VecType::Colour;
+                }
+            }
+            if let Type::FloatMat { transform, .. } | Type::DoubleMat { transform, .. } = &mut ty {
+                *transform = None;
+            }
+            Some(ty)
+        };
+        match (without_space(found), without_space(expected)) {
+            (Some(Type::Error), _) | (_, Some(Type::Error)) => true,
+            (found, expected) => found == expected,
+        }
+    }
+
+    /// The spaces of two vector types that only differ in their space.
+    pub(crate) fn space_mismatch(&self, found: TypeId, expected: TypeId) -> Option<(Name, Name)> {
+        let without_space = |ty: TypeId| -> Option<(Type, Name)> {
+            let mut ty = self.types.get(self.strip_normalized(ty))?.clone();
+            let space = match &mut ty {
+                Type::IntVec { space, .. }
+                | Type::UIntVec { space, .. }
+                | Type::LongVec { space, .. }
+                | Type::ULongVec { space, .. }
+                | Type::FloatVec { space, .. }
+                | Type::DoubleVec { space, .. }
+                | Type::HalfVec { space, .. } => space.take()?,
+                _ => return None,
+            };
+            Some((ty, space))
+        };
+        let (found, from) = without_space(found)?;
+        let (expected, to) = without_space(expected)?;
+        if found == expected && from != to {
+            Some((from, to))
+        } else {
+            None
+        }
+    }
+
+    /// How to apply a transform chain to a vector, if all transforms are
+    /// square matrices of the same size with the scalar type of the vector.
+    fn space_transform(&mut self, ty: TypeId, steps: Vec<TransformStep>) -> Option<SpaceTransform> {
+        let (double, size, vtype) = match self.types.get(ty)? {
+            Type::FloatVec {
+                components, vtype, ..
+            } => (false, components, vtype),
+            Type::DoubleVec {
+                components, vtype, ..
+            } => (true, components, vtype),
+            _ => return None,
+        };
+        let (size, vtype) = (components(*size), *vtype);
+
+        let mut matrix_size = None;
+        for step in &steps {
+            let sig = self.consts.get(&step.via)?;
+            let (cols, rows) = match self.types.get(self.strip_distinct(sig.type_))? {
+                Type::FloatMat { cols, rows, .. } if !double => (cols, rows),
+                Type::DoubleMat { cols, rows, .. } if double => (cols, rows),
+                _ => return None,
+            };
+            if cols != rows || matrix_size.is_some_and(|size| size != *cols) {
+                return None;
+            }
+            matrix_size = Some(*cols);
+        }
+
+        let matrix_size = matrix_size.map_or(size, components);
+        let extended = if matrix_size == size {
+            None
+        } else if matrix_size == size + 1 {
+            let w = match vtype {
+                VecType::Point => 1.0,
+                VecType::Vector => 0.0,
+                // only points and directions have a defined `w`
+                VecType::Unknown | VecType::Colour | VecType::SrgbColour => return None,
+            };
+            let components = crate::VecSize::VS4;
+            let ty = match double {
+                false => Type::FloatVec {
+                    components,
+                    vtype: VecType::Unknown,
+                    space: None,
+                },
+                true => Type::DoubleVec {
+                    components,
+                    vtype: VecType::Unknown,
+                    space: None,
+                },
+            };
+            Some((self.types.intern(ty), w))
+        } else {
+            return None;
+        };
+
+        Some(SpaceTransform {
+            steps,
+            extended,
+            components: size,
+        })
+    }
+
+    /// A space followed by its parent, the parent's parent and so on.
+    fn space_ancestors(&self, space: &str) -> Option<Vec<Identifier>> {
+        let mut path = vec![];
+        let mut current = Some(space.to_string());
+        while let Some(space) = current {
+            if path.contains(&space) {
+                // cycles are reported by `check_spaces`
+                break;
+            }
+            let sig = self.spaces.get(&space)?;
+            current = sig.parent.as_ref().map(|(parent, _)| parent.clone());
+            path.push(space);
+        }
+        Some(path)
+    }
+}
+
+/// Register the spaces of a module, and check that they are declared once,
+/// that their hierarchy has no cycles and that their transforms are
+/// matrices between the right spaces.
+pub(crate) fn check_spaces(
+    module: &hir::Module,
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.spaces {
+        let def = &hir_ctx.spaces[*id];
+        let name = &hir_ctx.identifiers[def.name];
+        if let Some(prev) = ty_ctx.spaces.get(name) {
+            let prev_name = hir_ctx.spaces[prev.space_id].name;
+            errs.push(Error::SpaceRedefinition {
+                previous_name: hir_ctx.identifier_fcs[&prev_name],
+                redefinition_name: hir_ctx.identifier_fcs[&def.name],
+            });
+            continue;
+        }
+        let parent = def.parent.map(|(parent, via)| {
+            let parent = hir_ctx.identifiers[parent].clone();
+            (parent, hir_ctx.identifiers[via].clone())
+        });
+        ty_ctx.spaces.insert(
+            name.clone(),
+            SpaceSig {
+                space_id: *id,
+                parent,
+            },
+        );
+    }
+
+    for (name, sig) in &ty_ctx.spaces {
+        let def = &hir_ctx.spaces[sig.space_id];
+        let (parent, via) = match (&sig.parent, def.parent) {
+            (Some((parent, _)), Some((_, via))) => (parent, via),
+            _ => continue,
+        };
+        let via_name = &hir_ctx.identifiers[via];
+        // undefined parents are reported by the reference index
+        if !ty_ctx.spaces.contains_key(parent) || ty_ctx.poisoned_consts.contains_key(via_name) {
+            continue;
+        }
+        let problem = match ty_ctx.consts.get(via_name) {
+            None => SpaceTransformProblem::UndefinedConstant {
+                suggestions: suggestions::similar_names(
+                    via_name,
+                    ty_ctx.consts.keys().map(String::as_str),
+                ),
+            },
+            Some(sig) => match ty_ctx.types.get(ty_ctx.strip_distinct(sig.type_)) {
+                Some(Type::FloatMat { transform, .. })
+                | Some(Type::DoubleMat { transform, .. }) => {
+                    let expected = transform.is_none_or(|(from, to)| {
+                        ty_ctx.types.name(from) == parent && ty_ctx.types.name(to) == name
+                    });
+                    if expected {
+                        continue;
+                    }
+                    SpaceTransformProblem::WrongSpaces {
+                        type_name: ty_ctx.display_type(sig.type_).to_string(),
+                        parent: parent.clone(),
+                    }
+                }
+                _ => SpaceTransformProblem::NotAMatrix {
+                    type_name: ty_ctx.display_type(sig.type_).to_string(),
+                },
+            },
+        };
+        errs.push(Error::InvalidSpaceTransform {
+            space: name.clone(),
+            via: hir_ctx.identifier_fcs[&via],
+            problem,
+        });
+    }
+
+    errs.extend(check_hierarchy(ty_ctx, hir_ctx));
+    errs
+}
+
+/// Report cycles of spaces placed in each other, once per cycle.
+fn check_hierarchy(ty_ctx: &Context, hir_ctx: &hir::Context) -> Vec<Error> {
+    let mut errs = vec![];
+    let mut done = BTreeSet::new();
+
+    for name in ty_ctx.spaces.keys() {
+        let mut path = vec![];
+        let mut current = Some(name);
+        while let Some(space) = current {
+            if done.contains(space) {
+                break;
+            }
+            if let Some(start) = path.iter().position(|s| *s == space) {
+                let cycle = path[start..].to_vec();
+                errs.push(cycle_error(ty_ctx, hir_ctx, &cycle));
+                break;
+            }
+            path.push(space);
+            current = ty_ctx.spaces[space]
+                .parent
+                .as_ref()
+                .map(|(parent, _)| parent)
+                .filter(|parent| ty_ctx.spaces.contains_key(*parent));
+        }
+        done.extend(path);
+    }
+
+    errs
+}
+
+fn cycle_error(ty_ctx: &Context, hir_ctx: &hir::Context, cycle: &[&Identifier]) -> Error {
+    // start at the space declared first, so the error doesn't depend on
+    // the order of the names
+    let loc = |space: &Identifier| -> FileLocation {
+        let def = &hir_ctx.spaces[ty_ctx.spaces[space].space_id];
+        hir_ctx.identifier_fcs[&def.name]
+    };
+    let first = (0..cycle.len())
+        .min_by_key(|i| loc(cycle[*i]).start)
+        .unwrap_or(0);
+    let cycle = cycle[first..]
+        .iter()
+        .chain(&cycle[..first])
+        .map(|space| (*space).clone())
+        .collect::<Vec<_>>();
+
+    Error::CyclicSpaceHierarchy {
+        space_names: cycle.iter().map(loc).collect(),
+        parents: cycle
+            .iter()
+            .map(|space| {
+                let def = &hir_ctx.spaces[ty_ctx.spaces[space].space_id];
+                let (parent, _) = def.parent.expect("spaces in a cycle have parents");
+                hir_ctx.identifier_fcs[&parent]
+            })
+            .collect(),
+        cycle,
+    }
+}
+
+/// Check a value of type `found` used where a value of type `expected` is
+/// needed, applying or reporting the transforms between their spaces.
+pub(crate) fn check_value_space(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+    expr: Id<Expression>,
+    found: TypeId,
+    expected: TypeId,
+) -> Option<Error> {
+    if ty_ctx.space_check == SpaceCheck::Off {
+        return None;
+    }
+    let (from, to) = ty_ctx.space_mismatch(found, expected)?;
+    let from = ty_ctx.types.name(from).to_string();
+    let to = ty_ctx.types.name(to).to_string();
+    let chain = ty_ctx.transform_chain(&from, &to);
+
+    if let (SpaceCheck::Insert, Some(steps)) = (ty_ctx.space_check, chain.clone()) {
+        if let Some(transform) = ty_ctx.space_transform(found, steps) {
+            ty_ctx.space_transforms.insert(expr, transform);
+            return None;
+        }
+    }
+    Some(Error::SpaceMismatch {
+        expr: hir_ctx.expression_fcs[&expr],
+        from,
+        to,
+        chain,
+    })
+}
+
+/// Group the uses of undefined spaces by name.
+pub(crate) fn undefined_spaces(
+    uses: BTreeMap<&str, Vec<FileLocation>>,
+    hir_ctx: &hir::Context,
+    module: &hir::Module,
+) -> Vec<Error> {
+    let declared = module
+        .spaces
+        .iter()
+        .map(|id| hir_ctx.identifiers[hir_ctx.spaces[*id].name].as_str())
+        .collect::<Vec<_>>();
+    uses.into_iter()
+        .map(|(name, uses)| Error::UndefinedSpace {
+            name: name.to_string(),
+            uses,
+            suggestions: suggestions::similar_names(name, declared.iter().copied()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn space(
+        ctx: &mut Context,
+        arena: &mut id_arena::Arena<SpaceDefinition>,
+        name: &str,
+        parent: Option<(&str, &str)>,
+    ) {
+        let mut names = id_arena::Arena::<Identifier>::new();
+        let space_id = arena.alloc(SpaceDefinition {
+            visibility: hir::Visibility::Public,
+            name: names.alloc(name.to_string()),
+            parent: None,
+        });
+        ctx.spaces.insert(
+            name.to_string(),
+            SpaceSig {
+                space_id,
+                parent: parent.map(|(p, via)| (p.to_string(), via.to_string())),
+            },
+        );
+    }
+
+    fn step(via: &str, from: &str, to: &str, inverse: bool) -> TransformStep {
+        TransformStep {
+            via: via.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            inverse,
+        }
+    }
+
+    #[test]
+    fn chains_through_the_common_ancestor() {
+        let mut ctx = Context::default();
+        let mut arena = id_arena::Arena::new();
+        space(&mut ctx, &mut arena, "World", None);
+        space(
+            &mut ctx,
+            &mut arena,
+            "Model",
+            Some(("World", "world_to_model")),
+        );
+        space(
+            &mut ctx,
+            &mut arena,
+            "Camera",
+            Some(("World", "world_to_camera")),
+        );
+        space(&mut ctx, &mut arena, "Clip", Some(("Camera", "projection")));
+        space(&mut ctx, &mut arena, "Other", None);
+
+        assert_eq!(
+            ctx.transform_chain("Model", "Clip"),
+            Some(vec![
+                step("world_to_model", "Model", "World", true),
+                step("world_to_camera", "World", "Camera", false),
+                step("projection", "Camera", "Clip", false),
+            ])
+        );
+        assert_eq!(
+            ctx.transform_chain("Clip", "Camera"),
+            Some(vec![step("projection", "Clip", "Camera", true)])
+        );
+        assert_eq!(ctx.transform_chain("World", "World"), Some(vec![]));
+        assert_eq!(ctx.transform_chain("Model", "Other"), None);
+        assert_eq!(ctx.transform_chain("Model", "Tangent"), None);
+    }
+}
@@ -0,0 +1,581 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Pipeline stages of programs, selected with an attribute on the program,
+//! and the parts of the language only available in some of them.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, Program};
+use id_arena::Id;
+
+use crate::effects::discards;
+use crate::geometry::{InputPrimitive, OutputPrimitive, MAX_GEOMETRY_VERTICES};
+use crate::tessellation::{Domain, Partitioning, MAX_CONTROL_POINTS};
+use crate::uniformity::{functions_calling, statement_calls};
+use crate::{Context, Error, Intrinsic, Symbol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+    Compute,
+    /// the program rays are traced from
+    RayGeneration,
+    /// the closest intersection of a ray, after the any hit programs
+    ClosestHit,
+    /// every intersection of a ray that may be ignored
+    AnyHit,
+    /// rays that intersect nothing
+    Miss,
+    /// workgroups that decide how many mesh workgroups to launch
+    Task,
+    /// workgroups that output the vertices and primitives of a meshlet
+    Mesh,
+    /// the control points of a patch and how finely it is subdivided
+    TessellationControl,
+    /// the vertices of a subdivided patch
+    TessellationEvaluation,
+    /// the primitives made from the vertices of every input primitive
+    Geometry,
+}
+
+impl Stage {
+    /// The stage selected by an attribute with this name
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "vertex" => Some(Stage::Vertex),
+            "fragment" => Some(Stage::Fragment),
+            "compute" => Some(Stage::Compute),
+            "ray_generation" => Some(Stage::RayGeneration),
+            "closest_hit" => Some(Stage::ClosestHit),
+            "any_hit" => Some(Stage::AnyHit),
+            "miss" => Some(Stage::Miss),
+            "task" => Some(Stage::Task),
+            "mesh" => Some(Stage::Mesh),
+            "tessellation_control" => Some(Stage::TessellationControl),
+            "tessellation_evaluation" => Some(Stage::TessellationEvaluation),
+            "geometry" => Some(Stage::Geometry),
+            _ => None,
+        }
+    }
+
+    /// The name of the attribute selecting the stage.
+    pub fn attribute(self) -> &'static str {
+        match self {
+            Stage::Vertex => "vertex",
+            Stage::Fragment => "fragment",
+            Stage::Compute => "compute",
+            Stage::RayGeneration => "ray_generation",
+            Stage::ClosestHit => "closest_hit",
+            Stage::AnyHit => "any_hit",
+            Stage::Miss => "miss",
+            Stage::Task => "task",
+            Stage::Mesh => "mesh",
+            Stage::TessellationControl => "tessellation_control",
+            Stage::TessellationEvaluation => "tessellation_evaluation",
+            Stage::Geometry => "geometry",
+        }
+    }
+
+    /// Whether the stage is one of the stages of a ray tracing pipeline.
+    pub fn is_ray_tracing(self) -> bool {
+        matches!(
+            self,
+            Stage::RayGeneration | Stage::ClosestHit | Stage::AnyHit | Stage::Miss
+        )
+    }
+
+    /// The indefinite article in front of the name of the stage.
+    pub fn article(self) -> &'static str {
+        match self {
+            Stage::AnyHit => "an",
+            _ => "a",
+        }
+    }
+
+    /// Whether the stage is one of the stages of a tessellation pipeline.
+    pub fn is_tessellation(self) -> bool {
+        matches!(
+            self,
+            Stage::TessellationControl | Stage::TessellationEvaluation
+        )
+    }
+
+    /// Whether programs of the stage run in workgroups, which can have
+    /// workgroup variables.
+    pub fn has_workgroups(self) -> bool {
+        matches!(self, Stage::Compute | Stage::Task | Stage::Mesh)
+    }
+
+    /// Whether programs of the stage can trace rays of their own.
+    pub fn traces_rays(self) -> bool {
+        matches!(self, Stage::RayGeneration | Stage::ClosestHit | Stage::Miss)
+    }
+
+    /// The program attributes programs of the stage can't do without.
+    pub fn required_attributes(self) -> &'static [&'static str] {
+        match self {
+            Stage::TessellationControl => &["control_points"],
+            Stage::TessellationEvaluation => &["domain"],
+            Stage::Geometry => &["input_primitive", "output_primitive", "max_vertices"],
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Vertex => write!(f, "vertex"),
+            Stage::Fragment => write!(f, "fragment"),
+            Stage::Compute => write!(f, "compute"),
+            Stage::RayGeneration => write!(f, "ray generation"),
+            Stage::ClosestHit => write!(f, "closest hit"),
+            Stage::AnyHit => write!(f, "any hit"),
+            Stage::Miss => write!(f, "miss"),
+            Stage::Task => write!(f, "task"),
+            Stage::Mesh => write!(f, "mesh"),
+            Stage::TessellationControl => write!(f, "tessellation control"),
+            Stage::TessellationEvaluation => write!(f, "tessellation evaluation"),
+            Stage::Geometry => write!(f, "geometry"),
+        }
+    }
+}
+
+/// The stage of a program, `None` if it has no stage attribute.
+pub fn program_stage(hir_ctx: &hir::Context, program: Id<Program>) -> Option<Stage> {
+    hir_ctx.programs[program].attrs.iter().find_map(|attr| {
+        Stage::from_attribute(&hir_ctx.identifiers[hir_ctx.attributes[*attr].name])
+    })
+}
+
+/// Program attributes that only programs of one stage have, with the
+/// stage
+const STAGE_ATTRIBUTES: &[(&str, Stage)] = &[
+    ("domain", Stage::TessellationEvaluation),
+    ("partitioning", Stage::TessellationEvaluation),
+    ("control_points", Stage::TessellationControl),
+    ("input_primitive", Stage::Geometry),
+    ("output_primitive", Stage::Geometry),
+    ("max_vertices", Stage::Geometry),
+];
+
+/// Why an attribute of a program that only programs of one stage have is not
+/// valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeProblem {
+    /// the attribute is on a program of another stage
+    WrongStage { found: Option<Stage> },
+    /// the argument is not one of the names the attribute takes
+    ExpectedName { expected: &'static [&'static str] },
+    /// the argument is not a number from 1 to `max`
+    ExpectedCount { what: &'static str, max: u32 },
+    /// the program has the attribute already
+    Duplicate { previous: FileLocation },
+}
+
+/// The single positional argument of an attribute, if it has one
+fn single_arg(attr: &hir::Attribute) -> Option<Id<Expression>> {
+    match (attr.pos_args.as_slice(), attr.nam_args.is_empty()) {
+        ([arg], true) => Some(*arg),
+        _ => None,
+    }
+}
+
+/// The argument of an attribute that takes a name, like `@domain(quads)`.
+pub(crate) fn name_arg<'a>(hir_ctx: &'a hir::Context, attr: &hir::Attribute) -> Option<&'a str> {
+    match hir_ctx.expressions[single_arg(attr)?] {
+        Expression::Variable(name) => Some(&hir_ctx.identifiers[name]),
+        _ => None,
+    }
+}
+
+/// The argument of an attribute that takes a number from 1 to `max`, like
+/// `@control_points(3)`.
+pub(crate) fn count_arg(hir_ctx: &hir::Context, attr: &hir::Attribute, max: u32) -> Option<u32> {
+    match hir_ctx.expressions[single_arg(attr)?] {
+        Expression::Literal(hir::Literal::Integer(n, _)) => {
+            u32::try_from(n).ok().filter(|n| (1..=max).contains(n))
+        }
+        _ => None,
+    }
+}
+
+/// The argument of an attribute that takes a string, like
+/// `@profile("shadows")`.
+pub(crate) fn string_arg<'a>(hir_ctx: &'a hir::Context, attr: &hir::Attribute) -> Option<&'a str> {
+    match hir_ctx.expressions[single_arg(attr)?] {
+        Expression::Literal(hir::Literal::String(s)) => Some(&hir_ctx.strings[s]),
+        _ => None,
+    }
+}
+
+/// The attribute of a program with a name.
+pub(crate) fn program_attribute<'a>(
+    hir_ctx: &'a hir::Context,
+    program: Id<Program>,
+    name: &str,
+) -> Option<&'a hir::Attribute> {
+    hir_ctx.programs[program]
+        .attrs
+        .iter()
+        .map(|attr| &hir_ctx.attributes[*attr])
+        .find(|attr| hir_ctx.identifiers[attr.name] == name)
+}
+
+/// What is wrong with the argument of an attribute in [`STAGE_ATTRIBUTES`].
+fn argument_problem(
+    hir_ctx: &hir::Context,
+    attr: &hir::Attribute,
+    name: &str,
+) -> Option<AttributeProblem> {
+    let names = match name {
+        "domain" => Domain::NAMES,
+        "partitioning" => Partitioning::NAMES,
+        "input_primitive" => InputPrimitive::NAMES,
+        "output_primitive" => OutputPrimitive::NAMES,
+        _ => {
+            let (what, max) = match name {
+                "control_points" => ("control points of the patches", MAX_CONTROL_POINTS),
+                _ => ("vertices the program emits", MAX_GEOMETRY_VERTICES),
+            };
+            return count_arg(hir_ctx, attr, max)
+                .is_none()
+                .then_some(AttributeProblem::ExpectedCount { what, max });
+        }
+    };
+    let valid = name_arg(hir_ctx, attr).is_some_and(|arg| names.contains(&arg));
+    (!valid).then_some(AttributeProblem::ExpectedName { expected: names })
+}
+
+/// Check the attributes only programs of one stage have, and that programs
+/// have the ones their stage needs.
+pub(crate) fn validate_stage_attributes(
+    module: &hir::Module,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        let mut seen = HashMap::new();
+
+        for attr_id in &prog.attrs {
+            let attr = &hir_ctx.attributes[*attr_id];
+            let name = hir_ctx.identifiers[attr.name].as_str();
+            let expected = match STAGE_ATTRIBUTES.iter().find(|(known, _)| *known == name) {
+                Some((_, expected)) => *expected,
+                None => continue,
+            };
+            let attribute = hir_ctx.attribute_fcs[attr_id];
+            let problem = if stage != Some(expected) {
+                Some(AttributeProblem::WrongStage { found: stage })
+            } else if let Some(previous) = seen.get(name) {
+                Some(AttributeProblem::Duplicate {
+                    previous: *previous,
+                })
+            } else {
+                argument_problem(hir_ctx, attr, name)
+            };
+            seen.entry(name).or_insert(attribute);
+            if let Some(problem) = problem {
+                errs.push(Error::InvalidStageAttribute {
+                    attribute,
+                    stage: expected,
+                    problem,
+                });
+            }
+        }
+
+        let required = stage.map_or(&[][..], Stage::required_attributes);
+        for attribute in required {
+            if !seen.contains_key(attribute) {
+                errs.push(Error::MissingStageAttribute {
+                    program_name: hir_ctx.identifiers[prog.name].clone(),
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                    attribute,
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+/// Report workgroup variables of programs that don't run in workgroups.
+pub(crate) fn validate_stages(module: &hir::Module, hir_ctx: &hir::Context) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        if stage.is_some_and(Stage::has_workgroups) {
+            continue;
+        }
+        for var in &prog.workgroup {
+            errs.push(Error::WorkgroupOutsideCompute {
+                program_name: hir_ctx.identifiers[prog.name].clone(),
+                program: hir_ctx.identifier_fcs[&prog.name],
+                var: hir_ctx.identifier_fcs[&hir_ctx.variable_defs[*var].name],
+                stage,
+            });
+        }
+    }
+
+    errs
+}
+
+/// Report derivatives, and calls of functions using them, in programs that
+/// are not fragment programs.
+pub(crate) fn validate_derivatives(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let derivative_functions = functions_calling(module, ty_ctx, hir_ctx, Intrinsic::is_derivative);
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        if stage == Some(Stage::Fragment) {
+            continue;
+        }
+
+        let mut calls = vec![];
+        for stmt in &prog.body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+        for call in calls {
+            let name = match &hir_ctx.expressions[call] {
+                hir::Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            let intrinsic = ty_ctx
+                .call_intrinsics
+                .get(&call)
+                .is_some_and(|i| i.is_derivative());
+            let calls_derivative = match ty_ctx.references.symbol(hir_ctx.identifier_fcs[&name]) {
+                Some(Symbol::Function(func)) => derivative_functions.contains(&func),
+                _ => false,
+            };
+            if intrinsic || calls_derivative {
+                errs.push(Error::DerivativeOutsideFragment {
+                    callee: hir_ctx.identifiers[name].clone(),
+                    call: hir_ctx.identifier_fcs[&name],
+                    intrinsic,
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                    stage,
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+/// Report `discard`, and calls of functions discarding, in programs that are
+/// not fragment programs.
+pub(crate) fn validate_discards(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        if stage == Some(Stage::Fragment) {
+            continue;
+        }
+
+        let mut found = vec![];
+        discards(hir_ctx, &prog.body, &mut found);
+        for stmt in found {
+            errs.push(Error::DiscardOutsideFragment {
+                callee: None,
+                loc: hir_ctx.statement_fcs[&stmt],
+                program: hir_ctx.identifier_fcs[&prog.name],
+                stage,
+            });
+        }
+
+        let mut calls = vec![];
+        for stmt in &prog.body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+        for call in calls {
+            let name = match &hir_ctx.expressions[call] {
+                hir::Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            if ty_ctx
+                .call_effects(hir_ctx, call)
+                .is_some_and(|effects| effects.discards)
+            {
+                errs.push(Error::DiscardOutsideFragment {
+                    callee: Some(hir_ctx.identifiers[name].clone()),
+                    loc: hir_ctx.identifier_fcs[&name],
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                    stage,
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+/// Report `imageStore`, and calls of functions using it, in vertex programs.
+pub(crate) fn validate_image_stores(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let is_store = |i: Intrinsic| i == Intrinsic::ImageStore;
+    let storing_functions = functions_calling(module, ty_ctx, hir_ctx, is_store);
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        if program_stage(hir_ctx, *id) != Some(Stage::Vertex) {
+            continue;
+        }
+
+        let mut calls = vec![];
+        for stmt in &prog.body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+        for call in calls {
+            let name = match &hir_ctx.expressions[call] {
+                hir::Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            let intrinsic = ty_ctx
+                .call_intrinsics
+                .get(&call)
+                .is_some_and(|i| is_store(*i));
+            let calls_store = match ty_ctx.references.symbol(hir_ctx.identifier_fcs[&name]) {
+                Some(Symbol::Function(func)) => storing_functions.contains(&func),
+                _ => false,
+            };
+            if intrinsic || calls_store {
+                errs.push(Error::ImageStoreInVertex {
+                    callee: hir_ctx.identifiers[name].clone(),
+                    call: hir_ctx.identifier_fcs[&name],
+                    intrinsic,
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+/// Report `trace_ray`, and calls of functions using it, in programs whose
+/// stage can't trace rays.
+pub(crate) fn validate_trace_rays(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let is_trace = |i: Intrinsic| i == Intrinsic::TraceRay;
+    let tracing_functions = functions_calling(module, ty_ctx, hir_ctx, is_trace);
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        if stage.is_some_and(Stage::traces_rays) {
+            continue;
+        }
+
+        let mut calls = vec![];
+        for stmt in &prog.body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+        for call in calls {
+            let name = match &hir_ctx.expressions[call] {
+                hir::Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            let intrinsic = ty_ctx
+                .call_intrinsics
+                .get(&call)
+                .is_some_and(|i| is_trace(*i));
+            let calls_trace = match ty_ctx.references.symbol(hir_ctx.identifier_fcs[&name]) {
+                Some(Symbol::Function(func)) => tracing_functions.contains(&func),
+                _ => false,
+            };
+            if intrinsic || calls_trace {
+                errs.push(Error::TraceRayOutsideStage {
+                    callee: hir_ctx.identifiers[name].clone(),
+                    call: hir_ctx.identifier_fcs[&name],
+                    intrinsic,
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                    stage,
+                });
+            }
+        }
+    }
+
+    errs
+}
+
+/// Report `emit_vertex` and `end_primitive`, and calls of functions using
+/// them, in programs that are not geometry programs.
+pub(crate) fn validate_primitive_emission(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let emitting_functions = functions_calling(module, ty_ctx, hir_ctx, Intrinsic::is_emission);
+    let mut errs = vec![];
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        if stage == Some(Stage::Geometry) {
+            continue;
+        }
+
+        let mut calls = vec![];
+        for stmt in &prog.body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+        for call in calls {
+            let name = match &hir_ctx.expressions[call] {
+                hir::Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            let intrinsic = ty_ctx
+                .call_intrinsics
+                .get(&call)
+                .is_some_and(|i| i.is_emission());
+            let calls_emission = match ty_ctx.references.symbol(hir_ctx.identifier_fcs[&name]) {
+                Some(Symbol::Function(func)) => emitting_functions.contains(&func),
+                _ => false,
+            };
+            if intrinsic || calls_emission {
+                errs.push(Error::EmissionOutsideGeometry {
+                    callee: hir_ctx.identifiers[name].clone(),
+                    call: hir_ctx.identifier_fcs[&name],
+                    intrinsic,
+                    program: hir_ctx.identifier_fcs[&prog.name],
+                    stage,
+                });
+            }
+        }
+    }
+
+    errs
+}
@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+/// Maximum number of names suggested for a single error
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The names from `candidates` that are closest to `name`, if they are close
+/// enough to be a likely typo. Items of modules are also suggested for their
+/// name without the path of the module, so `Rya` suggests `geometry::Ray`.
+pub fn similar_names<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    // allow roughly one edit per three characters
+    let max_distance = ((name.chars().count() + 1) / 3).max(1);
+
+    let mut res = candidates
+        .into_iter()
+        .filter(|cand| *cand != name)
+        .map(|cand| {
+            let unqualified = cand.rsplit("::").next().unwrap_or(cand);
+            let dist = edit_distance(name, cand).min(edit_distance(name, unqualified));
+            (dist, cand)
+        })
+        .filter(|(dist, _)| *dist <= max_distance)
+        .collect::<Vec<_>>();
+
+    res.sort();
+    res.dedup();
+
+    // only the closest names are of interest
+    let best = res.first().map(|(dist, _)| *dist);
+    res.into_iter()
+        .take_while(|(dist, _)| Some(*dist) == best)
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, cand)| cand.to_string())
+        .collect()
+}
+
+/// Edit distance between two strings (optimal string alignment, so swapping
+/// two neighbouring characters is a single edit), where a change in case only
+/// counts as half an edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+
+    let cost = |x: char, y: char| {
+        if x == y {
+            0
+        } else if x.to_lowercase().eq(y.to_lowercase()) {
+            1
+        } else {
+            2
+        }
+    };
+
+    // distances are doubled to be able to count half edits
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = 2 * i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = 2 * j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let mut dist = (d[i - 1][j - 1] + cost(a[i - 1], b[j - 1]))
+                .min(d[i - 1][j] + 2)
+                .min(d[i][j - 1] + 2);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist = dist.min(d[i - 2][j - 2] + 2);
+            }
+            d[i][j] = dist;
+        }
+    }
+
+    d[a.len()][b.len()].div_ceil(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("Light", "Light"), 0);
+        assert_eq!(edit_distance("Light", "light"), 1);
+        assert_eq!(edit_distance("Lihgt", "Light"), 1);
+        assert_eq!(edit_distance("Vertex", "Vertices"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_names() {
+        let candidates = ["Lights", "Light", "Sight", "Material", "Lite"];
+        assert_eq!(
+            similar_names("Ligth", candidates.iter().copied()),
+            vec!["Light"]
+        );
+        assert_eq!(
+            similar_names("Might", candidates.iter().copied()),
+            vec!["Light", "Sight"]
+        );
+        assert!(similar_names("Texture", candidates.iter().copied()).is_empty());
+
+        let candidates = ["geometry::Ray", "geometry::Hit", "Ray"];
+        assert_eq!(
+            similar_names("Rya", candidates.iter().copied()),
+            vec!["Ray", "geometry::Ray"]
+        );
+        assert_eq!(
+            similar_names("geomtry::Hit", candidates.iter().copied()),
+            vec!["geometry::Hit"]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Tessellation pipelines.
+//!
+//! Between the vertex and the fragment stage, a tessellation pipeline
+//! subdivides patches of control points. A `@tessellation_control` program
+//! runs once per control point of the output patch, `@control_points(n)`
+//! gives their number, and writes how finely the patch is subdivided to the
+//! `TessLevelOuter` and `TessLevelInner` outputs. The fixed function
+//! tessellator then subdivides the `@domain` of the
+//! `@tessellation_evaluation` program, `triangles`, `quads` or `isolines`,
+//! with the spacing of its `@partitioning`, `equal` by default or
+//! `fractional_even` or `fractional_odd`, and the evaluation program
+//! computes a vertex at every `TessCoord` of the subdivided domain.
+//!
+//! Varyings of the tessellation stages other than builtins are arrays with
+//! an element per control point: the inputs of control programs, which have
+//! the type of the vertex outputs of the same name as their elements, the
+//! outputs of control programs, with the length given by `control_points`,
+//! and the inputs of evaluation programs. Outputs of control programs with a
+//! `Patch` attribute are written once per patch instead, and read by the
+//! inputs of evaluation programs with a `Patch` attribute. Like varyings of
+//! fragment programs, the inputs are matched by name to the outputs of the
+//! stage before them, see [`crate::interpolation`].
+//!
+//! The stages are those of SPIR-V, GLSL ES 3.0 and Metal have no
+//! tessellation programs.
+
+use std::collections::BTreeMap;
+
+use thiol_hir as hir;
+
+use hir::{Identifier, Program, VariableDef};
+use id_arena::Id;
+
+use crate::stages::{count_arg, name_arg, program_attribute, program_stage, Stage};
+use crate::varyings::{element_type, has_attribute, interface_mismatch, is_builtin, Varying};
+use crate::{Context, Error, Symbol, Type};
+
+/// The most control points of a patch, the minimum every Vulkan
+/// implementation with tessellation supports
+pub const MAX_CONTROL_POINTS: u32 = 32;
+
+/// The primitive the tessellator subdivides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Domain {
+    Triangles,
+    Quads,
+    Isolines,
+}
+
+impl Domain {
+    pub const NAMES: &'static [&'static str] = &["triangles", "quads", "isolines"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "triangles" => Some(Domain::Triangles),
+            "quads" => Some(Domain::Quads),
+            "isolines" => Some(Domain::Isolines),
+            _ => None,
+        }
+    }
+}
+
+/// How the tessellator spaces the subdivisions of the edges of the domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Partitioning {
+    /// the levels are rounded up to an integer, subdivisions have the same
+    /// length
+    #[default]
+    Equal,
+    /// the levels are rounded up to an even integer, subdivisions grow and
+    /// shrink smoothly with the level
+    FractionalEven,
+    /// the levels are rounded up to an odd integer, subdivisions grow and
+    /// shrink smoothly with the level
+    FractionalOdd,
+}
+
+impl Partitioning {
+    pub const NAMES: &'static [&'static str] = &["equal", "fractional_even", "fractional_odd"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "equal" => Some(Partitioning::Equal),
+            "fractional_even" => Some(Partitioning::FractionalEven),
+            "fractional_odd" => Some(Partitioning::FractionalOdd),
+            _ => None,
+        }
+    }
+}
+
+/// The domain of a tessellation evaluation program, `None` without a valid
+/// `domain` attribute.
+pub fn program_domain(hir_ctx: &hir::Context, program: Id<Program>) -> Option<Domain> {
+    let attr = program_attribute(hir_ctx, program, "domain")?;
+    Domain::from_name(name_arg(hir_ctx, attr)?)
+}
+
+/// The partitioning of a tessellation evaluation program.
+pub fn program_partitioning(hir_ctx: &hir::Context, program: Id<Program>) -> Partitioning {
+    program_attribute(hir_ctx, program, "partitioning")
+        .and_then(|attr| Partitioning::from_name(name_arg(hir_ctx, attr)?))
+        .unwrap_or_default()
+}
+
+/// The number of control points of the patches a tessellation control
+/// program outputs, `None` without a valid `control_points` attribute.
+pub fn control_points(hir_ctx: &hir::Context, program: Id<Program>) -> Option<u32> {
+    let attr = program_attribute(hir_ctx, program, "control_points")?;
+    count_arg(hir_ctx, attr, MAX_CONTROL_POINTS)
+}
+
+/// Whether an input or output is passed once per patch.
+pub fn is_patch(hir_ctx: &hir::Context, def: Id<VariableDef>) -> bool {
+    has_attribute(hir_ctx, def, &["Patch"])
+}
+
+/// Check that the varyings of the tessellation stages are arrays per control
+/// point or passed per patch, and that they match the varyings of the same
+/// name of the stage before them.
+pub(crate) fn validate_tessellation(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    let mut errs = vec![];
+
+    // the first varying with each name on both sides of the interfaces
+    // between the vertex and control, and the control and evaluation stages
+    let mut vertex_outputs = BTreeMap::<&Identifier, Varying>::new();
+    let mut control_inputs = BTreeMap::<&Identifier, Varying>::new();
+    let mut control_outputs = BTreeMap::<&Identifier, Varying>::new();
+    let mut evaluation_inputs = BTreeMap::<&Identifier, Varying>::new();
+
+    for id in &module.programs {
+        let prog = &hir_ctx.programs[*id];
+        let stage = program_stage(hir_ctx, *id);
+        let points = control_points(hir_ctx, *id);
+        let vars = prog
+            .inputs
+            .iter()
+            .map(|var| (*var, false))
+            .chain(prog.outputs.iter().map(|var| (*var, true)));
+
+        for (var, output) in vars {
+            let def = &hir_ctx.variable_defs[var];
+            let name = &hir_ctx.identifiers[def.name];
+            let loc = hir_ctx.identifier_fcs[&def.name];
+            let patch = is_patch(hir_ctx, var);
+            // only control programs write varyings per patch, and only
+            // evaluation programs read them
+            let patch_allowed = matches!(
+                (stage, output),
+                (Some(Stage::TessellationControl), true)
+                    | (Some(Stage::TessellationEvaluation), false)
+            );
+            if patch && !patch_allowed {
+                errs.push(Error::InvalidPatch {
+                    var: loc,
+                    stage,
+                    output,
+                });
+                continue;
+            }
+            let side = match (stage, output) {
+                (Some(Stage::Vertex), true) => &mut vertex_outputs,
+                (Some(Stage::TessellationControl), false) => &mut control_inputs,
+                (Some(Stage::TessellationControl), true) => &mut control_outputs,
+                (Some(Stage::TessellationEvaluation), false) => &mut evaluation_inputs,
+                _ => continue,
+            };
+            if is_builtin(hir_ctx, var) {
+                continue;
+            }
+            let ty = match ty_ctx.references.symbol_type(Symbol::Local(var)) {
+                Some(ty) => ty,
+                None => continue,
+            };
+
+            let per_control_point = !patch && stage != Some(Stage::Vertex);
+            if per_control_point {
+                // outputs of control programs have an element per control
+                // point of the output patch, inputs one per control point of
+                // the input patch, whatever its size
+                let length = if output { points } else { None };
+                let valid = match ty_ctx.types.get(ty_ctx.strip_distinct(ty)) {
+                    Some(Type::Array { size, .. }) => {
+                        length.is_none_or(|length| *size == length as usize)
+                    }
+                    Some(Type::Error) => continue,
+                    _ => false,
+                };
+                if !valid {
+                    errs.push(Error::ExpectedControlPointArray {
+                        var: loc,
+                        found: ty_ctx.display_type(ty).to_string(),
+                        length,
+                    });
+                    continue;
+                }
+            }
+            side.entry(name).or_insert(Varying { ty, patch, loc });
+        }
+    }
+
+    for (name, input) in &control_inputs {
+        let output = match vertex_outputs.get(name) {
+            Some(output) => output,
+            None => continue,
+        };
+        if element_type(ty_ctx, input).is_some_and(|element| element != output.ty) {
+            errs.push(interface_mismatch(ty_ctx, name, output, input));
+        }
+    }
+    for (name, input) in &evaluation_inputs {
+        let output = match control_outputs.get(name) {
+            Some(output) => output,
+            None => continue,
+        };
+        if input.ty != output.ty || input.patch != output.patch {
+            errs.push(interface_mismatch(ty_ctx, name, output, input));
+        }
+    }
+
+    errs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_names() {
+        for name in Domain::NAMES {
+            assert!(Domain::from_name(name).is_some());
+        }
+        for name in Partitioning::NAMES {
+            assert!(Partitioning::from_name(name).is_some());
+        }
+        assert_eq!(Domain::from_name("quad"), None);
+        assert_eq!(Partitioning::default(), Partitioning::Equal);
+    }
+}
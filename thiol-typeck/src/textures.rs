@@ -0,0 +1,757 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Sampled textures and the samplers they are read through.
+//!
+//! A texture type like `texture2d<float>` names the dimensions of the
+//! texture and the scalar type of its texels, which are `float`, `half`,
+//! `int` or `uint`. Depth textures like `texture_depth2d` hold a single
+//! depth per texel and have no sampled type. Textures and samplers are bound
+//! like uniform buffers, as constants with a `Uniform` attribute.
+//!
+//! The sampling intrinsics `sample`, `sample_lod`, `sample_grad`,
+//! `sample_compare`, `gather` and `fetch` aren't declared one signature at a
+//! time. Their parameters follow from the dimensions and the sampled type of
+//! the texture, see [`sampling_signature`], which also tells why a
+//! combination isn't supported:
+//!
+//! - coordinates have a component per dimension, cubes are sampled with a
+//!   direction and the layer of an array is the last component,
+//! - only `gather` and `fetch` read integer texels, the others filter them,
+//! - `sample_compare` compares with the depths of depth textures,
+//! - one dimensional textures have no mipmaps to pick a level of, `gather`
+//!   reads the four texels around two dimensional coordinates and texels of
+//!   cubes can't be fetched.
+
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation};
+use id_arena::Id;
+
+use crate::{Context, Error, Intrinsic, Type, TypeId, VecSize, VecType};
+
+/// The dimensions of a texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TextureDim {
+    D1,
+    D2,
+    D3,
+    Cube,
+    /// an array of two dimensional layers
+    D2Array,
+}
+
+impl TextureDim {
+    pub const ALL: &'static [TextureDim] = &[
+        TextureDim::D1,
+        TextureDim::D2,
+        TextureDim::D3,
+        TextureDim::Cube,
+        TextureDim::D2Array,
+    ];
+
+    /// The name of the texture type with these dimensions.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            TextureDim::D1 => "texture1d",
+            TextureDim::D2 => "texture2d",
+            TextureDim::D3 => "texture3d",
+            TextureDim::Cube => "texture_cube",
+            TextureDim::D2Array => "texture2d_array",
+        }
+    }
+
+    /// The name of the depth texture type with these dimensions, `None` for
+    /// the dimensions that have no depth textures.
+    pub fn depth_type_name(self) -> Option<&'static str> {
+        match self {
+            TextureDim::D2 => Some("texture_depth2d"),
+            TextureDim::Cube => Some("texture_depth_cube"),
+            TextureDim::D2Array => Some("texture_depth2d_array"),
+            TextureDim::D1 | TextureDim::D3 => None,
+        }
+    }
+
+    /// The number of components of the coordinates a texture is sampled
+    /// at, including the layer of arrays.
+    pub fn coordinates(self) -> usize {
+        match self {
+            TextureDim::D1 => 1,
+            TextureDim::D2 => 2,
+            TextureDim::D3 | TextureDim::Cube | TextureDim::D2Array => 3,
+        }
+    }
+
+    /// The number of components of the gradients of coordinates, layers
+    /// have none.
+    pub fn gradients(self) -> usize {
+        match self {
+            TextureDim::D1 => 1,
+            TextureDim::D2 | TextureDim::D2Array => 2,
+            TextureDim::D3 | TextureDim::Cube => 3,
+        }
+    }
+}
+
+impl From<hir::TextureDim> for TextureDim {
+    fn from(dim: hir::TextureDim) -> Self {
+        match dim {
+            hir::TextureDim::D1 => Self::D1,
+            hir::TextureDim::D2 => Self::D2,
+            hir::TextureDim::D3 => Self::D3,
+            hir::TextureDim::Cube => Self::Cube,
+            hir::TextureDim::D2Array => Self::D2Array,
+        }
+    }
+}
+
+/// The type of the texels of a texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SampledType {
+    Float,
+    Half,
+    Int,
+    UInt,
+    /// a single depth, which is a `float`
+    Depth,
+}
+
+impl SampledType {
+    pub const ALL: &'static [SampledType] = &[
+        SampledType::Float,
+        SampledType::Half,
+        SampledType::Int,
+        SampledType::UInt,
+        SampledType::Depth,
+    ];
+
+    /// The name of the scalar type of the texels, `None` for depths.
+    pub fn scalar_name(self) -> Option<&'static str> {
+        match self {
+            SampledType::Float => Some("float"),
+            SampledType::Half => Some("half"),
+            SampledType::Int => Some("int"),
+            SampledType::UInt => Some("uint"),
+            SampledType::Depth => None,
+        }
+    }
+
+    /// Whether texels of the type can be filtered.
+    fn is_filterable(self) -> bool {
+        !matches!(self, SampledType::Int | SampledType::UInt)
+    }
+}
+
+/// The name of a texture type, like `texture2d<float>` or
+/// `texture_depth_cube`.
+pub fn texture_type_name(dim: TextureDim, sampled: SampledType) -> String {
+    match sampled.scalar_name() {
+        Some(scalar) => format!("{}<{}>", dim.type_name(), scalar),
+        None => dim.depth_type_name().unwrap_or("texture_depth").to_string(),
+    }
+}
+
+/// A texture or a sampler bound as a resource of a program, see
+/// [`Context::program_resources`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureResource {
+    Texture {
+        dim: TextureDim,
+        sampled: SampledType,
+    },
+    Sampler {
+        comparison: bool,
+    },
+}
+
+impl TextureResource {
+    /// The name of the type of the resource, like `texture2d<float>` or
+    /// `sampler_comparison`.
+    pub fn type_name(self) -> String {
+        match self {
+            TextureResource::Texture { dim, sampled } => texture_type_name(dim, sampled),
+            TextureResource::Sampler { comparison: false } => "sampler".to_string(),
+            TextureResource::Sampler { comparison: true } => "sampler_comparison".to_string(),
+        }
+    }
+
+    /// The resource with the type name `name`, see [`type_name`](Self::type_name).
+    pub fn from_type_name(name: &str) -> Option<Self> {
+        let textures = TextureDim::ALL.iter().flat_map(|dim| {
+            SampledType::ALL
+                .iter()
+                .map(move |sampled| TextureResource::Texture {
+                    dim: *dim,
+                    sampled: *sampled,
+                })
+        });
+        let samplers = [false, true]
+            .iter()
+            .map(|comparison| TextureResource::Sampler {
+                comparison: *comparison,
+            });
+        textures
+            .chain(samplers)
+            .find(|resource| resource.type_name() == name)
+    }
+}
+
+impl fmt::Display for TextureResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.type_name())
+    }
+}
+
+/// The type of a parameter of a sampling intrinsic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    /// the texture that is sampled
+    Texture,
+    Sampler {
+        comparison: bool,
+    },
+    /// a `float` or a vector of floats with this many components
+    Float(usize),
+    /// an `int`, `uint` or a vector of them with this many components
+    Int(usize),
+}
+
+/// A named parameter of a sampling intrinsic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplingParam {
+    pub name: &'static str,
+    pub ty: ParamType,
+}
+
+impl SamplingParam {
+    fn new(name: &'static str, ty: ParamType) -> Self {
+        SamplingParam { name, ty }
+    }
+}
+
+/// The parameters and the result of a sampling intrinsic called with a
+/// texture of some dimensions and sampled type
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingSignature {
+    pub params: Vec<SamplingParam>,
+    pub ret: Type,
+}
+
+/// Why a sampling intrinsic can't be called with a texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingProblem {
+    /// filtering integer texels, which only `gather` and `fetch` read
+    IntegerFiltering,
+    /// `sample_compare` of a texture that doesn't hold depths
+    NotDepth,
+    /// the intrinsic doesn't support textures with the dimensions
+    Dimensions,
+}
+
+/// The signature of a sampling intrinsic for a texture with the dimensions
+/// and sampled type, or why the intrinsic can't sample the texture.
+pub fn sampling_signature(
+    intrinsic: Intrinsic,
+    dim: TextureDim,
+    sampled: SampledType,
+) -> Result<SamplingSignature, SamplingProblem> {
+    use ParamType::*;
+
+    let texture = SamplingParam::new("texture", Texture);
+    let sampler = SamplingParam::new("sampler", Sampler { comparison: false });
+    let coords = SamplingParam::new("coords", Float(dim.coordinates()));
+    let params = match intrinsic {
+        Intrinsic::Sample => vec![texture, sampler, coords],
+        Intrinsic::SampleLod if dim != TextureDim::D1 => {
+            vec![
+                texture,
+                sampler,
+                coords,
+                SamplingParam::new("level", Float(1)),
+            ]
+        }
+        Intrinsic::SampleGrad if dim != TextureDim::D1 => vec![
+            texture,
+            sampler,
+            coords,
+            SamplingParam::new("ddx", Float(dim.gradients())),
+            SamplingParam::new("ddy", Float(dim.gradients())),
+        ],
+        Intrinsic::SampleCompare if sampled == SampledType::Depth => vec![
+            texture,
+            SamplingParam::new("sampler", Sampler { comparison: true }),
+            coords,
+            SamplingParam::new("reference", Float(1)),
+        ],
+        Intrinsic::SampleCompare => return Err(SamplingProblem::NotDepth),
+        Intrinsic::Gather
+            if matches!(dim, TextureDim::D2 | TextureDim::Cube | TextureDim::D2Array) =>
+        {
+            vec![texture, sampler, coords]
+        }
+        Intrinsic::Fetch if dim != TextureDim::Cube => vec![
+            texture,
+            SamplingParam::new("coords", Int(dim.coordinates())),
+            SamplingParam::new("level", Int(1)),
+        ],
+        _ => return Err(SamplingProblem::Dimensions),
+    };
+
+    let filtered = matches!(
+        intrinsic,
+        Intrinsic::Sample | Intrinsic::SampleLod | Intrinsic::SampleGrad
+    );
+    if filtered && !sampled.is_filterable() {
+        return Err(SamplingProblem::IntegerFiltering);
+    }
+
+    // depths are single values, gathering them gives the four around the
+    // coordinates
+    let (components, vtype, space) = (VecSize::VS4, VecType::Unknown, None);
+    let ret = match sampled {
+        SampledType::Depth if intrinsic != Intrinsic::Gather => Type::Float,
+        SampledType::Float | SampledType::Depth => Type::FloatVec {
+            components,
+            vtype,
+            space,
+        },
+        SampledType::Half => Type::HalfVec {
+            components,
+            vtype,
+            space,
+        },
+        SampledType::Int => Type::IntVec {
+            components,
+            vtype,
+            space,
+        },
+        SampledType::UInt => Type::UIntVec {
+            components,
+            vtype,
+            space,
+        },
+    };
+    Ok(SamplingSignature { params, ret })
+}
+
+/// All textures a sampling intrinsic supports, with their signatures, in the
+/// order of their dimensions and sampled types.
+pub fn supported_textures(
+    intrinsic: Intrinsic,
+) -> impl Iterator<Item = (TextureDim, SampledType, SamplingSignature)> {
+    TextureDim::ALL.iter().flat_map(move |dim| {
+        SampledType::ALL
+            .iter()
+            .filter(move |sampled| {
+                **sampled != SampledType::Depth || dim.depth_type_name().is_some()
+            })
+            .filter_map(move |sampled| {
+                let sig = sampling_signature(intrinsic, *dim, *sampled).ok()?;
+                Some((*dim, *sampled, sig))
+            })
+    })
+}
+
+/// The names of the texture types a sampling intrinsic supports, without
+/// their sampled types, like `texture2d` and `texture_depth2d`.
+pub fn supported_texture_names(intrinsic: Intrinsic) -> Vec<&'static str> {
+    let mut names = vec![];
+    for (dim, sampled, _) in supported_textures(intrinsic) {
+        let name = match sampled {
+            SampledType::Depth => dim.depth_type_name().unwrap_or_default(),
+            _ => dim.type_name(),
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let vector = |f: &mut fmt::Formatter<'_>, scalar, n| match n {
+            1 => write!(f, "{}", scalar),
+            n => write!(f, "{}{}", scalar, n),
+        };
+        match self {
+            ParamType::Texture => write!(f, "texture"),
+            ParamType::Sampler { comparison: false } => write!(f, "sampler"),
+            ParamType::Sampler { comparison: true } => write!(f, "sampler_comparison"),
+            ParamType::Float(n) => vector(f, "float", *n),
+            ParamType::Int(n) => vector(f, "int", *n),
+        }
+    }
+}
+
+impl Context {
+    /// The type of a texture with the sampled type `sampled`, `None` if
+    /// textures can't hold texels of the type.
+    pub(crate) fn texture_type(&mut self, dim: TextureDim, sampled: TypeId) -> Option<TypeId> {
+        let sampled = match self.types.get(sampled)? {
+            Type::Float => SampledType::Float,
+            Type::Half => SampledType::Half,
+            Type::Int => SampledType::Int,
+            Type::UInt => SampledType::UInt,
+            _ => return None,
+        };
+        Some(self.add_or_get_type(Type::Texture { dim, sampled }))
+    }
+
+    /// The dimensions and sampled type of a texture type, `None` for all
+    /// other types.
+    pub fn texture(&self, ty: TypeId) -> Option<(TextureDim, SampledType)> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Texture { dim, sampled } => Some((*dim, *sampled)),
+            _ => None,
+        }
+    }
+
+    /// Whether a sampler type is a comparison sampler, `None` for all other
+    /// types.
+    pub fn sampler(&self, ty: TypeId) -> Option<bool> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Sampler { comparison } => Some(*comparison),
+            _ => None,
+        }
+    }
+
+    /// The texture or sampler of a type, `None` for all other types.
+    pub fn texture_resource(&self, ty: TypeId) -> Option<TextureResource> {
+        match self.types.get(self.strip_distinct(ty))? {
+            Type::Texture { dim, sampled } => Some(TextureResource::Texture {
+                dim: *dim,
+                sampled: *sampled,
+            }),
+            Type::Sampler { comparison } => Some(TextureResource::Sampler {
+                comparison: *comparison,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of the type contains a texture or a sampler.
+    pub fn contains_texture(&self, ty: TypeId) -> bool {
+        self.types.contains(ty, |ty| {
+            matches!(ty, Type::Texture { .. } | Type::Sampler { .. })
+        })
+    }
+
+    /// Whether a value of the type can be passed as the parameter of a
+    /// sampling intrinsic.
+    fn fits_sampling_param(&self, ty: TypeId, param: ParamType) -> bool {
+        let components = |size: &VecSize| match size {
+            VecSize::VS2 => 2,
+            VecSize::VS3 => 3,
+            VecSize::VS4 => 4,
+        };
+        match (self.types.get(self.strip_distinct(ty)), param) {
+            (Some(Type::Error), _) => true,
+            (Some(Type::Texture { .. }), ParamType::Texture) => true,
+            (
+                Some(Type::Sampler { comparison }),
+                ParamType::Sampler {
+                    comparison: expected,
+                },
+            ) => *comparison == expected,
+            (Some(Type::Float), ParamType::Float(1)) => true,
+            (
+                Some(Type::FloatVec {
+                    components: size, ..
+                }),
+                ParamType::Float(n),
+            ) => components(size) == n,
+            (Some(Type::Int) | Some(Type::UInt), ParamType::Int(1)) => true,
+            (
+                Some(Type::IntVec {
+                    components: size, ..
+                })
+                | Some(Type::UIntVec {
+                    components: size, ..
+                }),
+                ParamType::Int(n),
+            ) => components(size) == n,
+            _ => false,
+        }
+    }
+
+    /// The type values of a sampling parameter take when nothing else gives
+    /// them a type, which is what literal arguments are.
+    pub(crate) fn sampling_param_type(&mut self, param: ParamType) -> Option<TypeId> {
+        let (components, vtype, space) = (VecSize::VS2, VecType::Unknown, None);
+        let ty = match param {
+            ParamType::Texture => return None,
+            ParamType::Sampler { comparison } => Type::Sampler { comparison },
+            ParamType::Float(1) => Type::Float,
+            ParamType::Int(1) => Type::Int,
+            ParamType::Float(n) | ParamType::Int(n) => {
+                let components = match n {
+                    2 => components,
+                    3 => VecSize::VS3,
+                    _ => VecSize::VS4,
+                };
+                match param {
+                    ParamType::Float(_) => Type::FloatVec {
+                        components,
+                        vtype,
+                        space,
+                    },
+                    _ => Type::IntVec {
+                        components,
+                        vtype,
+                        space,
+                    },
+                }
+            }
+        };
+        Some(self.add_or_get_type(ty))
+    }
+
+    /// The type of the parameter at `position` of a sampling intrinsic
+    /// called with a texture of type `texture`.
+    pub(crate) fn sampling_argument_type(
+        &mut self,
+        intrinsic: Intrinsic,
+        texture: TypeId,
+        position: usize,
+    ) -> Option<TypeId> {
+        let (dim, sampled) = self.texture(texture)?;
+        let sig = sampling_signature(intrinsic, dim, sampled).ok()?;
+        self.sampling_param_type(sig.params.get(position)?.ty)
+    }
+
+    /// The type of a sampling intrinsic with positional arguments of the
+    /// given types.
+    pub(crate) fn sampling_type(
+        &mut self,
+        intrinsic: Intrinsic,
+        args: &[TypeId],
+    ) -> Option<TypeId> {
+        let (dim, sampled) = self.texture(*args.first()?)?;
+        let sig = sampling_signature(intrinsic, dim, sampled).ok()?;
+        let fits = args.len() == sig.params.len()
+            && args
+                .iter()
+                .zip(&sig.params)
+                .all(|(arg, param)| self.fits_sampling_param(*arg, param.ty));
+        if fits {
+            Some(self.add_or_get_type(sig.ret))
+        } else {
+            None
+        }
+    }
+
+    /// A sampling signature as it is shown in diagnostics, like
+    /// `sample(texture: texture2d<float>, sampler: sampler, coords: float2) -> float4`.
+    fn display_sampling_signature(
+        &self,
+        intrinsic: Intrinsic,
+        texture: TypeId,
+        sig: &SamplingSignature,
+    ) -> String {
+        let params = sig
+            .params
+            .iter()
+            .map(|param| match param.ty {
+                ParamType::Texture => format!("{}: {}", param.name, self.display_type(texture)),
+                ty => format!("{}: {}", param.name, ty),
+            })
+            .collect::<Vec<_>>();
+        let ret = match &sig.ret {
+            Type::Float => "float".to_string(),
+            Type::FloatVec { .. } => "float4".to_string(),
+            Type::HalfVec { .. } => "half4".to_string(),
+            Type::IntVec { .. } => "int4".to_string(),
+            _ => "uint4".to_string(),
+        };
+        format!("{}({}) -> {}", intrinsic.name(), params.join(", "), ret)
+    }
+}
+
+/// Check the arguments of a call of a sampling intrinsic against the
+/// signature for the texture it samples.
+pub(crate) fn check_call(
+    ty_ctx: &mut Context,
+    hir_ctx: &hir::Context,
+    intrinsic: Intrinsic,
+    call: FileLocation,
+    args: &[(Id<Expression>, Option<TypeId>)],
+) -> Vec<Error> {
+    let mut errs = vec![];
+    let (texture, texture_ty) = match args.first() {
+        Some((texture, Some(ty))) => (*texture, *ty),
+        Some((_, None)) => return errs,
+        None => {
+            errs.push(Error::NotATexture {
+                expr: call,
+                intrinsic,
+                type_name: None,
+            });
+            return errs;
+        }
+    };
+    let (dim, sampled) = match ty_ctx.texture(texture_ty) {
+        Some(texture) => texture,
+        None => {
+            if ty_ctx.types.get(texture_ty) != Some(&Type::Error) {
+                errs.push(Error::NotATexture {
+                    expr: hir_ctx.expression_fcs[&texture],
+                    intrinsic,
+                    type_name: Some(ty_ctx.display_type(texture_ty).to_string()),
+                });
+            }
+            return errs;
+        }
+    };
+    let sig = match sampling_signature(intrinsic, dim, sampled) {
+        Ok(sig) => sig,
+        Err(problem) => {
+            errs.push(Error::UnsupportedSampling {
+                texture: hir_ctx.expression_fcs[&texture],
+                intrinsic,
+                type_name: ty_ctx.display_type(texture_ty).to_string(),
+                problem,
+            });
+            return errs;
+        }
+    };
+    let signature = ty_ctx.display_sampling_signature(intrinsic, texture_ty, &sig);
+
+    if args.len() != sig.params.len() {
+        errs.push(Error::SamplingArity {
+            call,
+            intrinsic,
+            expected: sig.params.len(),
+            found: args.len(),
+            signature,
+        });
+        return errs;
+    }
+    for ((arg, ty), param) in args.iter().zip(&sig.params).skip(1) {
+        let ty = match ty {
+            Some(ty) => *ty,
+            None => continue,
+        };
+        if !ty_ctx.fits_sampling_param(ty, param.ty) {
+            errs.push(Error::SamplingArgumentMismatch {
+                arg: hir_ctx.expression_fcs[arg],
+                intrinsic,
+                param: param.name,
+                found: ty_ctx.display_type(ty).to_string(),
+                expected: param.ty.to_string(),
+                signature: signature.clone(),
+            });
+        }
+    }
+    errs
+}
+
+/// Report textures and samplers in parameters, return types, variables and
+/// constants that are not bound.
+///
+/// Textures and samplers in the types of buffers are reported when
+/// validating the buffers.
+pub(crate) fn validate_texture_placement(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> Vec<Error> {
+    crate::images::validate_opaque_placement(
+        module,
+        ty_ctx,
+        hir_ctx,
+        Context::contains_texture,
+        |type_| Error::MisplacedTexture { type_ },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures() {
+        let sig = sampling_signature(
+            Intrinsic::SampleGrad,
+            TextureDim::D2Array,
+            SampledType::Half,
+        )
+        .unwrap();
+        let params = sig
+            .params
+            .iter()
+            .map(|param| (param.name, param.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            vec![
+                ("texture", ParamType::Texture),
+                ("sampler", ParamType::Sampler { comparison: false }),
+                ("coords", ParamType::Float(3)),
+                ("ddx", ParamType::Float(2)),
+                ("ddy", ParamType::Float(2)),
+            ]
+        );
+
+        let fetch = sampling_signature(Intrinsic::Fetch, TextureDim::D2, SampledType::Depth);
+        assert_eq!(fetch.map(|sig| sig.ret), Ok(Type::Float));
+        let gather = sampling_signature(Intrinsic::Gather, TextureDim::Cube, SampledType::UInt);
+        assert!(matches!(
+            gather.map(|sig| sig.ret),
+            Ok(Type::UIntVec { .. })
+        ));
+    }
+
+    #[test]
+    fn problems() {
+        use SamplingProblem::*;
+
+        let problem = |intrinsic, dim, sampled| sampling_signature(intrinsic, dim, sampled).err();
+        assert_eq!(
+            problem(Intrinsic::Sample, TextureDim::D2, SampledType::Int),
+            Some(IntegerFiltering)
+        );
+        assert_eq!(
+            problem(Intrinsic::SampleCompare, TextureDim::D2, SampledType::Float),
+            Some(NotDepth)
+        );
+        assert_eq!(
+            problem(Intrinsic::SampleLod, TextureDim::D1, SampledType::Float),
+            Some(Dimensions)
+        );
+        assert_eq!(
+            problem(Intrinsic::Fetch, TextureDim::Cube, SampledType::Float),
+            Some(Dimensions)
+        );
+        assert_eq!(
+            problem(Intrinsic::Fetch, TextureDim::D3, SampledType::Int),
+            None
+        );
+    }
+
+    #[test]
+    fn supported_names() {
+        assert_eq!(
+            supported_texture_names(Intrinsic::Gather),
+            vec![
+                "texture2d",
+                "texture_depth2d",
+                "texture_cube",
+                "texture_depth_cube",
+                "texture2d_array",
+                "texture_depth2d_array",
+            ]
+        );
+        assert_eq!(
+            supported_texture_names(Intrinsic::SampleCompare),
+            vec![
+                "texture_depth2d",
+                "texture_depth_cube",
+                "texture_depth2d_array"
+            ]
+        );
+    }
+}
@@ -5,7 +5,9 @@
 use id_arena::Id;
 use thiol_hir::{self as hir, Identifier};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+use crate::interner::Name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TypeId(pub(crate) usize);
 
 impl TypeId {
@@ -18,8 +20,14 @@ impl TypeId {
 #[derive(Debug, Clone)]
 pub struct FunctionSig {
     pub func_id: Id<hir::Function>,
+    /// names of the generic parameters, they appear as [`Type::GenericParam`]
+    /// in the argument and return types
+    pub generics: Vec<Identifier>,
     pub args: Vec<(Identifier, TypeId)>,
     pub ret: TypeId,
+    /// side effects of the function, including those of the functions it
+    /// calls
+    pub effects: crate::Effects,
 }
 
 #[derive(Debug, Clone)]
@@ -28,13 +36,24 @@ pub struct ConstantSig {
     pub type_: TypeId,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Type {
     Bool,
     Int,
     UInt,
+    /// a 64 bit `int`, see [`crate::profile::Feature::Int64`]
+    Long,
+    /// a 64 bit `uint`
+    ULong,
     Float,
     Double,
+    Half,
+    /// a `float` angle in radians, see [`crate::angles`]
+    Radians,
+    /// a `float` angle in degrees
+    Degrees,
+    AtomicInt,
+    AtomicUInt,
 
     BoolVec {
         components: VecSize,
@@ -42,34 +61,55 @@ pub enum Type {
     IntVec {
         components: VecSize,
         vtype: VecType,
-        space: Option<Identifier>,
+        space: Option<Name>,
     },
     UIntVec {
         components: VecSize,
         vtype: VecType,
-        space: Option<Identifier>,
+        space: Option<Name>,
+    },
+    LongVec {
+        components: VecSize,
+        vtype: VecType,
+        space: Option<Name>,
+    },
+    ULongVec {
+        components: VecSize,
+        vtype: VecType,
+        space: Option<Name>,
     },
 
     FloatVec {
         components: VecSize,
         vtype: VecType,
-        space: Option<Identifier>,
+        space: Option<Name>,
     },
     DoubleVec {
         components: VecSize,
         vtype: VecType,
-        space: Option<Identifier>,
+        space: Option<Name>,
+    },
+    HalfVec {
+        components: VecSize,
+        vtype: VecType,
+        space: Option<Name>,
     },
 
     FloatMat {
         cols: VecSize,
         rows: VecSize,
-        transform: Option<(Identifier, Identifier)>,
+        transform: Option<(Name, Name)>,
     },
     DoubleMat {
         cols: VecSize,
         rows: VecSize,
-        transform: Option<(Identifier, Identifier)>,
+        transform: Option<(Name, Name)>,
+    },
+
+    /// A vector stored in fewer bits than its full precision type, see
+    /// [`PackedFormat::unpacked`]
+    Packed {
+        format: PackedFormat,
     },
 
     Array {
@@ -81,21 +121,91 @@ pub enum Type {
     },
 
     Record {
-        fields: Vec<(Identifier, TypeId)>,
+        fields: Vec<(Name, TypeId)>,
     },
 
     Distinct {
         distinct_id: usize,
         inner: TypeId,
     },
+
+    /// A vector of floating point numbers known to have a length of one, see
+    /// [`crate::normalized`]
+    Normalized {
+        inner: TypeId,
+    },
+
+    /// A value that may be missing, see [`crate::optionals`]
+    Optional {
+        inner: TypeId,
+    },
+
+    /// A storage image, see [`crate::images`]
+    Image {
+        dim: crate::ImageDim,
+        format: crate::ImageFormat,
+        access: crate::ImageAccess,
+    },
+
+    /// A sampled texture, see [`crate::textures`]
+    Texture {
+        dim: crate::TextureDim,
+        sampled: crate::SampledType,
+    },
+
+    /// A sampler textures are sampled with, comparison samplers compare
+    /// depths
+    Sampler {
+        comparison: bool,
+    },
+
+    /// The geometry rays are traced against, see [`crate::ray_tracing`]
+    AccelerationStructure,
+
+    /// A generic parameter of a function signature, `index` is the position in
+    /// [`FunctionSig::generics`]
+    GenericParam {
+        index: usize,
+        name: Identifier,
+    },
+
+    /// A type variable of the unification engine, it stands for the type it
+    /// is bound to in a [`Substitution`](crate::unify::Substitution)
+    Var(usize),
+
+    /// The type of anything that had an error before, it unifies with every
+    /// other type so that a single error doesn't lead to follow-up errors.
+    Error,
+}
+
+impl Type {
+    /// The numeric scalar type of scalars, vectors, matrices and atomics,
+    /// the type a literal used together with a value of the type gets.
+    pub fn scalar(&self) -> Option<Type> {
+        match self {
+            Type::Int | Type::IntVec { .. } | Type::AtomicInt => Some(Type::Int),
+            Type::UInt | Type::UIntVec { .. } | Type::AtomicUInt => Some(Type::UInt),
+            Type::Long | Type::LongVec { .. } => Some(Type::Long),
+            Type::ULong | Type::ULongVec { .. } => Some(Type::ULong),
+            Type::Float | Type::FloatVec { .. } | Type::FloatMat { .. } => Some(Type::Float),
+            Type::Double | Type::DoubleVec { .. } | Type::DoubleMat { .. } => Some(Type::Double),
+            Type::Half | Type::HalfVec { .. } => Some(Type::Half),
+            Type::Radians => Some(Type::Radians),
+            Type::Degrees => Some(Type::Degrees),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum VecType {
     Unknown,
     Point,
     Vector,
+    /// a linear colour
     Colour,
+    /// an sRGB encoded colour
+    SrgbColour,
 }
 
 impl From<Option<thiol_hir::VecType>> for VecType {
@@ -104,12 +214,13 @@ impl From<Option<thiol_hir::VecType>> for VecType {
             Some(thiol_hir::VecType::Point) => Self::Point,
             Some(thiol_hir::VecType::Vector) => Self::Vector,
             Some(thiol_hir::VecType::Colour) => Self::Colour,
+            Some(thiol_hir::VecType::SrgbColour) => Self::SrgbColour,
             None => Self::Unknown,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum VecSize {
     VS2,
     VS3,
@@ -125,3 +236,88 @@ impl From<thiol_hir::VecSize> for VecSize {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PackedFormat {
+    Unorm8x4,
+    Snorm8x4,
+    Uint8x4,
+    Sint8x4,
+    Unorm16x2,
+    Snorm16x2,
+    Float16x2,
+    Float16x4,
+    Rgb10a2,
+}
+
+impl PackedFormat {
+    /// The name of the format as it is written in source files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unorm8x4 => "unorm8x4",
+            Self::Snorm8x4 => "snorm8x4",
+            Self::Uint8x4 => "uint8x4",
+            Self::Sint8x4 => "sint8x4",
+            Self::Unorm16x2 => "unorm16x2",
+            Self::Snorm16x2 => "snorm16x2",
+            Self::Float16x2 => "float16x2",
+            Self::Float16x4 => "float16x4",
+            Self::Rgb10a2 => "rgb10a2",
+        }
+    }
+
+    /// The size of a packed value in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::Float16x4 => 8,
+            _ => 4,
+        }
+    }
+
+    /// The full precision type a packed value is converted to when it is
+    /// unpacked.
+    ///
+    /// Normalised and half precision formats unpack to float vectors, the
+    /// integer formats keep their signedness.
+    pub fn unpacked(self) -> Type {
+        let vtype = VecType::Unknown;
+        match self {
+            Self::Uint8x4 => Type::UIntVec {
+                components: VecSize::VS4,
+                vtype,
+                space: None,
+            },
+            Self::Sint8x4 => Type::IntVec {
+                components: VecSize::VS4,
+                vtype,
+                space: None,
+            },
+            Self::Unorm16x2 | Self::Snorm16x2 | Self::Float16x2 => Type::FloatVec {
+                components: VecSize::VS2,
+                vtype,
+                space: None,
+            },
+            Self::Unorm8x4 | Self::Snorm8x4 | Self::Float16x4 | Self::Rgb10a2 => Type::FloatVec {
+                components: VecSize::VS4,
+                vtype,
+                space: None,
+            },
+        }
+    }
+}
+
+impl From<thiol_hir::PackedFormat> for PackedFormat {
+    fn from(f: thiol_hir::PackedFormat) -> Self {
+        match f {
+            thiol_hir::PackedFormat::Unorm8x4 => Self::Unorm8x4,
+            thiol_hir::PackedFormat::Snorm8x4 => Self::Snorm8x4,
+            thiol_hir::PackedFormat::Uint8x4 => Self::Uint8x4,
+            thiol_hir::PackedFormat::Sint8x4 => Self::Sint8x4,
+            thiol_hir::PackedFormat::Unorm16x2 => Self::Unorm16x2,
+            thiol_hir::PackedFormat::Snorm16x2 => Self::Snorm16x2,
+            thiol_hir::PackedFormat::Float16x2 => Self::Float16x2,
+            thiol_hir::PackedFormat::Float16x4 => Self::Float16x4,
+            thiol_hir::PackedFormat::Rgb10a2 => Self::Rgb10a2,
+        }
+    }
+}
@@ -0,0 +1,470 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A conservative analysis of which parts of a body are executed by all
+//! invocations together, all invocations of a workgroup for compute programs
+//! and all fragments of a primitive for fragment programs.
+//!
+//! Control flow is uniform as long as it only depends on literals, constants
+//! and loop variables of uniform loops. Everything else, like inputs,
+//! parameters and local variables, may differ between invocations.
+
+use std::collections::HashSet;
+
+use thiol_hir as hir;
+
+use hir::{Expression, FileLocation, Function, Identifier, Statement};
+use id_arena::Id;
+
+use crate::{Callable, Context, Error, Intrinsic, Symbol, Warning};
+
+/// Why control flow may not be uniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonUniformReason {
+    /// a branch on a condition that may differ between invocations
+    Condition(FileLocation),
+    /// a loop whose bounds may differ between invocations
+    LoopBounds(FileLocation),
+    /// a `return`, `break`, `continue` or `discard` in non-uniform control
+    /// flow before
+    EarlyExit(FileLocation),
+}
+
+impl NonUniformReason {
+    pub fn location(&self) -> FileLocation {
+        match self {
+            NonUniformReason::Condition(loc)
+            | NonUniformReason::LoopBounds(loc)
+            | NonUniformReason::EarlyExit(loc) => *loc,
+        }
+    }
+}
+
+/// Report barriers in non-uniform control flow as errors and derivatives and
+/// subgroup operations in non-uniform control flow as warnings, including
+/// calls of functions that contain them.
+pub(crate) fn check_uniformity(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+) -> (Vec<Error>, Vec<Warning>) {
+    let barrier_functions = functions_calling(module, ty_ctx, hir_ctx, Intrinsic::is_barrier);
+    let derivative_functions = functions_calling(module, ty_ctx, hir_ctx, Intrinsic::is_derivative);
+    let subgroup_functions = functions_calling(module, ty_ctx, hir_ctx, Intrinsic::is_subgroup);
+
+    let mut walker = Walker {
+        ty: ty_ctx,
+        hir: hir_ctx,
+        barrier_functions: &barrier_functions,
+        derivative_functions: &derivative_functions,
+        subgroup_functions: &subgroup_functions,
+        uniform_loops: HashSet::new(),
+        uniform_matches: HashSet::new(),
+        reason: None,
+        errors: vec![],
+        warnings: vec![],
+    };
+    for id in &module.functions {
+        walker.body(&hir_ctx.functions[*id].body);
+    }
+    for id in &module.programs {
+        walker.body(&hir_ctx.programs[*id].body);
+    }
+    (walker.errors, walker.warnings)
+}
+
+/// Functions that call an intrinsic matching `pred`, directly or through
+/// other functions.
+pub(crate) fn functions_calling(
+    module: &hir::Module,
+    ty_ctx: &Context,
+    hir_ctx: &hir::Context,
+    pred: impl Fn(Intrinsic) -> bool,
+) -> HashSet<Id<Function>> {
+    let mut found = HashSet::new();
+    let mut queue = vec![];
+    for id in &module.functions {
+        let mut calls = vec![];
+        for stmt in &hir_ctx.functions[*id].body {
+            statement_calls(hir_ctx, *stmt, &mut calls);
+        }
+        let direct = calls
+            .iter()
+            .any(|call| ty_ctx.call_intrinsics.get(call).is_some_and(|i| pred(*i)));
+        if direct && found.insert(*id) {
+            queue.push(*id);
+        }
+    }
+
+    while let Some(func) = queue.pop() {
+        for (caller, _) in ty_ctx.call_graph.dependents(Callable::Function(func)) {
+            if let Callable::Function(caller) = caller {
+                if found.insert(caller) {
+                    queue.push(caller);
+                }
+            }
+        }
+    }
+    found
+}
+
+struct Walker<'a> {
+    ty: &'a Context,
+    hir: &'a hir::Context,
+    barrier_functions: &'a HashSet<Id<Function>>,
+    derivative_functions: &'a HashSet<Id<Function>>,
+    subgroup_functions: &'a HashSet<Id<Function>>,
+    /// `for` loops whose iteration variable is uniform
+    uniform_loops: HashSet<Id<Statement>>,
+    /// `match` statements of a uniform optional
+    uniform_matches: HashSet<Id<Statement>>,
+    /// why the code currently walked is not uniform, `None` if it is
+    reason: Option<NonUniformReason>,
+    errors: Vec<Error>,
+    warnings: Vec<Warning>,
+}
+
+impl Walker<'_> {
+    fn body(&mut self, body: &[Id<Statement>]) {
+        self.reason = None;
+        self.block(body);
+    }
+
+    fn block(&mut self, block: &[Id<Statement>]) {
+        for stmt in block {
+            self.statement(*stmt);
+        }
+    }
+
+    fn statement(&mut self, id: Id<Statement>) {
+        match &self.hir.statements[id] {
+            Statement::Var(def) => {
+                if let Some(rhs) = self.hir.variable_defs[*def].rhs {
+                    self.expr(rhs);
+                }
+            }
+            Statement::Becomes { lhs, rhs } => {
+                self.expr(*lhs);
+                self.expr(*rhs);
+            }
+            Statement::Destructure { lhs, rhs } => {
+                for e in lhs {
+                    self.expr(*e);
+                }
+                self.expr(*rhs);
+            }
+            Statement::Expr(e) => self.expr(*e),
+            Statement::Return(e) => {
+                if let Some(e) = e {
+                    self.expr(*e);
+                }
+                self.exit(id);
+            }
+            Statement::Break | Statement::Continue | Statement::Discard => self.exit(id),
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                self.expr(*cond);
+                let outer = self.reason;
+                let inner = match outer {
+                    None if !self.is_uniform(*cond) => {
+                        Some(NonUniformReason::Condition(self.hir.expression_fcs[cond]))
+                    }
+                    _ => outer,
+                };
+
+                self.reason = inner;
+                self.block(then_body);
+                let after_then = self.reason;
+                self.reason = inner;
+                self.block(else_body);
+                let after_else = self.reason;
+
+                // invocations that left early make the rest of the body
+                // non-uniform
+                self.reason = outer
+                    .or_else(|| after_then.filter(is_exit))
+                    .or_else(|| after_else.filter(is_exit));
+            }
+            Statement::Match { value, arms } => {
+                self.expr(*value);
+                let outer = self.reason;
+                let inner = if self.is_uniform(*value) {
+                    self.uniform_matches.insert(id);
+                    outer
+                } else {
+                    outer.or(Some(NonUniformReason::Condition(
+                        self.hir.expression_fcs[value],
+                    )))
+                };
+
+                let mut exit = None;
+                for arm in arms {
+                    self.reason = inner;
+                    self.block(&arm.body);
+                    exit = exit.or_else(|| self.reason.filter(is_exit));
+                }
+                self.reason = outer.or(exit);
+            }
+            Statement::For { from, to, body, .. } => {
+                self.expr(*from);
+                self.expr(*to);
+                let outer = self.reason;
+                if self.is_uniform(*from) && self.is_uniform(*to) {
+                    self.uniform_loops.insert(id);
+                } else if outer.is_none() {
+                    let bounds = self.hir.expression_fcs[from].merge(self.hir.expression_fcs[to]);
+                    self.reason = Some(NonUniformReason::LoopBounds(bounds));
+                }
+
+                self.block(body);
+                self.reason = outer.or_else(|| self.reason.filter(is_exit));
+            }
+        }
+    }
+
+    /// A `return`, `break`, `continue` or `discard` statement.
+    fn exit(&mut self, id: Id<Statement>) {
+        if self.reason.is_some() {
+            self.reason = Some(NonUniformReason::EarlyExit(self.hir.statement_fcs[&id]));
+        }
+    }
+
+    fn expr(&mut self, id: Id<Expression>) {
+        let mut calls = vec![];
+        expression_calls(self.hir, id, &mut calls);
+        let reason = match self.reason {
+            Some(reason) => reason,
+            None => return,
+        };
+
+        for call in calls {
+            let name = match &self.hir.expressions[call] {
+                Expression::Call { name, .. } => *name,
+                _ => continue,
+            };
+            let intrinsic = self.ty.call_intrinsics.get(&call).copied();
+            let function = match self.symbol(name) {
+                Some(Symbol::Function(func)) => Some(func),
+                _ => None,
+            };
+            let callee = self.hir.identifiers[name].clone();
+            let loc = self.hir.identifier_fcs[&name];
+
+            let is_barrier = intrinsic.is_some_and(Intrinsic::is_barrier);
+            let calls_barrier = function.is_some_and(|f| self.barrier_functions.contains(&f));
+            if is_barrier || calls_barrier {
+                self.errors.push(Error::BarrierInNonUniformControlFlow {
+                    callee: callee.clone(),
+                    call: loc,
+                    intrinsic: is_barrier,
+                    reason,
+                });
+            }
+
+            let is_derivative = intrinsic.is_some_and(Intrinsic::is_derivative);
+            let calls_derivative = function.is_some_and(|f| self.derivative_functions.contains(&f));
+            if is_derivative || calls_derivative {
+                self.warnings
+                    .push(Warning::DerivativeInNonUniformControlFlow {
+                        callee: callee.clone(),
+                        call: loc,
+                        intrinsic: is_derivative,
+                        reason,
+                    });
+            }
+
+            let is_subgroup = intrinsic.is_some_and(Intrinsic::is_subgroup);
+            let calls_subgroup = function.is_some_and(|f| self.subgroup_functions.contains(&f));
+            if is_subgroup || calls_subgroup {
+                self.warnings
+                    .push(Warning::SubgroupInNonUniformControlFlow {
+                        callee,
+                        call: loc,
+                        intrinsic: is_subgroup,
+                        reason,
+                    });
+            }
+        }
+    }
+
+    fn symbol(&self, name: Id<Identifier>) -> Option<Symbol> {
+        self.ty.references.symbol(self.hir.identifier_fcs[&name])
+    }
+
+    /// Whether the expression has the same value in all invocations.
+    fn is_uniform(&self, id: Id<Expression>) -> bool {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            Expression::Literal(_) | Expression::LayoutQuery { .. } => true,
+            Expression::Variable(name) => match self.symbol(*name) {
+                Some(Symbol::Constant(_)) => true,
+                Some(Symbol::LoopVariable(stmt)) => self.uniform_loops.contains(&stmt),
+                Some(Symbol::MatchBinding(stmt)) => self.uniform_matches.contains(&stmt),
+                _ => false,
+            },
+            Expression::PrimitiveOp(op) => match &self.hir.prim_ops[*op] {
+                PO::Neg(e) | PO::Pos(e) => self.is_uniform(*e),
+                PO::Add(a, b)
+                | PO::Sub(a, b)
+                | PO::Mul(a, b)
+                | PO::Div(a, b)
+                | PO::Mod(a, b)
+                | PO::Gt(a, b)
+                | PO::Gte(a, b)
+                | PO::Lt(a, b)
+                | PO::Lte(a, b)
+                | PO::Eq(a, b)
+                | PO::Neq(a, b) => self.is_uniform(*a) && self.is_uniform(*b),
+                PO::Constructor {
+                    pos_args, nam_args, ..
+                } => pos_args
+                    .iter()
+                    .chain(nam_args.iter().map(|(_, e)| e))
+                    .all(|e| self.is_uniform(*e)),
+            },
+            Expression::Call {
+                pos_args, nam_args, ..
+            } => {
+                // intrinsics like atomics give different values to each invocation
+                let pure = match self.ty.call_intrinsics.get(&id) {
+                    Some(intrinsic) => intrinsic.is_pure(),
+                    None => true,
+                };
+                pure && pos_args
+                    .iter()
+                    .chain(nam_args.iter().map(|(_, e)| e))
+                    .all(|e| self.is_uniform(*e))
+            }
+            Expression::Field { base, .. } => self.is_uniform(*base),
+            Expression::Index { base, index } => self.is_uniform(*base) && self.is_uniform(*index),
+            Expression::Slice { base, lo, hi } => {
+                self.is_uniform(*base) && self.is_uniform(*lo) && self.is_uniform(*hi)
+            }
+            Expression::As { base, .. } => self.is_uniform(*base),
+            Expression::Array(elements) => elements.iter().all(|e| self.is_uniform(*e)),
+        }
+    }
+}
+
+fn is_exit(reason: &NonUniformReason) -> bool {
+    matches!(reason, NonUniformReason::EarlyExit(_))
+}
+
+/// The call expressions in a statement and the statements nested in it.
+pub(crate) fn statement_calls(
+    ctx: &hir::Context,
+    id: Id<Statement>,
+    calls: &mut Vec<Id<Expression>>,
+) {
+    match &ctx.statements[id] {
+        Statement::Var(def) => {
+            if let Some(rhs) = ctx.variable_defs[*def].rhs {
+                expression_calls(ctx, rhs, calls);
+            }
+        }
+        Statement::Becomes { lhs, rhs } => {
+            expression_calls(ctx, *lhs, calls);
+            expression_calls(ctx, *rhs, calls);
+        }
+        Statement::Destructure { lhs, rhs } => {
+            for e in lhs {
+                expression_calls(ctx, *e, calls);
+            }
+            expression_calls(ctx, *rhs, calls);
+        }
+        Statement::Return(Some(e)) | Statement::Expr(e) => expression_calls(ctx, *e, calls),
+        Statement::Return(None) | Statement::Break | Statement::Continue | Statement::Discard => {}
+        Statement::If {
+            cond,
+            then_body,
+            else_body,
+        } => {
+            expression_calls(ctx, *cond, calls);
+            for stmt in then_body.iter().chain(else_body) {
+                statement_calls(ctx, *stmt, calls);
+            }
+        }
+        Statement::Match { value, arms } => {
+            expression_calls(ctx, *value, calls);
+            for arm in arms {
+                for stmt in &arm.body {
+                    statement_calls(ctx, *stmt, calls);
+                }
+            }
+        }
+        Statement::For { from, to, body, .. } => {
+            expression_calls(ctx, *from, calls);
+            expression_calls(ctx, *to, calls);
+            for stmt in body {
+                statement_calls(ctx, *stmt, calls);
+            }
+        }
+    }
+}
+
+/// The call expressions in an expression.
+pub(crate) fn expression_calls(
+    ctx: &hir::Context,
+    id: Id<Expression>,
+    calls: &mut Vec<Id<Expression>>,
+) {
+    use hir::PrimitiveOp as PO;
+
+    match &ctx.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::LayoutQuery { .. } => {}
+        Expression::PrimitiveOp(op) => match &ctx.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => expression_calls(ctx, *e, calls),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                expression_calls(ctx, *a, calls);
+                expression_calls(ctx, *b, calls);
+            }
+            PO::Constructor {
+                pos_args, nam_args, ..
+            } => {
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    expression_calls(ctx, *e, calls);
+                }
+            }
+        },
+        Expression::Call {
+            pos_args, nam_args, ..
+        } => {
+            calls.push(id);
+            for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                expression_calls(ctx, *e, calls);
+            }
+        }
+        Expression::Field { base, .. } | Expression::As { base, .. } => {
+            expression_calls(ctx, *base, calls)
+        }
+        Expression::Index { base, index } => {
+            expression_calls(ctx, *base, calls);
+            expression_calls(ctx, *index, calls);
+        }
+        Expression::Slice { base, lo, hi } => {
+            expression_calls(ctx, *base, calls);
+            expression_calls(ctx, *lo, calls);
+            expression_calls(ctx, *hi, calls);
+        }
+        Expression::Array(elements) => {
+            for e in elements {
+                expression_calls(ctx, *e, calls);
+            }
+        }
+    }
+}
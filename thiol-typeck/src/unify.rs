@@ -0,0 +1,387 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Unification of types with type variables.
+//!
+//! Type variables ([`Type::Var`]) are created with [`Context::fresh_var`] and
+//! bound to types in a [`Substitution`] while unifying. The error type
+//! unifies with every type without binding anything, so a single error
+//! doesn't lead to follow-up errors.
+
+use std::collections::BTreeMap;
+
+use crate::{Context, Type, TypeId};
+
+/// Why two types could not be unified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnifyError {
+    /// the types have different shapes
+    Mismatch,
+    /// the type variable is already bound to a type that doesn't unify
+    Conflict(TypeId),
+    /// the type variable would have to be bound to a type containing itself
+    Occurs(TypeId),
+}
+
+/// Bindings of type variables to types
+#[derive(Debug, Clone, Default)]
+pub struct Substitution {
+    bindings: BTreeMap<TypeId, TypeId>,
+}
+
+impl Substitution {
+    /// The type a variable is bound to directly.
+    pub fn get(&self, var: TypeId) -> Option<TypeId> {
+        self.bindings.get(&var).copied()
+    }
+
+    pub fn is_bound(&self, var: TypeId) -> bool {
+        self.bindings.contains_key(&var)
+    }
+
+    /// Replace all bound type variables in `ty`, unbound variables are kept.
+    pub fn apply(&self, ctx: &mut Context, ty: TypeId) -> TypeId {
+        map_type(ctx, ty, &mut |ctx, ty| match ctx.types.get(ty) {
+            Some(Type::Var(_)) => Some(match self.get(ty) {
+                Some(bound) => self.apply(ctx, bound),
+                None => ty,
+            }),
+            _ => None,
+        })
+    }
+
+    /// Follow the bindings of `ty` until it is not a bound variable.
+    fn resolve(&self, mut ty: TypeId) -> TypeId {
+        while let Some(bound) = self.get(ty) {
+            ty = bound;
+        }
+        ty
+    }
+}
+
+/// Unify `a` and `b`, binding type variables in `subst` as needed.
+///
+/// On failure `subst` may contain some of the bindings made before the
+/// mismatch was found.
+pub fn unify(
+    ctx: &Context,
+    a: TypeId,
+    b: TypeId,
+    subst: &mut Substitution,
+) -> Result<(), UnifyError> {
+    // report conflicts with earlier bindings as such
+    for (var, other) in [(a, b), (b, a)] {
+        if let Some(bound) = subst.get(var) {
+            return unify(ctx, bound, other, subst).map_err(|err| match err {
+                UnifyError::Mismatch => UnifyError::Conflict(var),
+                err => err,
+            });
+        }
+    }
+
+    if a == b {
+        return Ok(());
+    }
+
+    let (ty_a, ty_b) = match (ctx.types.get(a), ctx.types.get(b)) {
+        (Some(ty_a), Some(ty_b)) => (ty_a, ty_b),
+        _ => return Err(UnifyError::Mismatch),
+    };
+
+    match (ty_a, ty_b) {
+        (Type::Error, _) | (_, Type::Error) => Ok(()),
+        (Type::Var(_), _) => bind(ctx, a, b, subst),
+        (_, Type::Var(_)) => bind(ctx, b, a, subst),
+        (
+            Type::Array { base: a, size },
+            Type::Array {
+                base: b,
+                size: size_b,
+            },
+        ) if size == size_b => unify(ctx, *a, *b, subst),
+        (Type::OpenArray { base: a }, Type::OpenArray { base: b }) => unify(ctx, *a, *b, subst),
+        (Type::Record { fields: a }, Type::Record { fields: b }) if a.len() == b.len() => {
+            for ((name_a, a), (name_b, b)) in a.iter().zip(b) {
+                if name_a != name_b {
+                    return Err(UnifyError::Mismatch);
+                }
+                unify(ctx, *a, *b, subst)?;
+            }
+            Ok(())
+        }
+        (
+            Type::Distinct {
+                distinct_id,
+                inner: a,
+            },
+            Type::Distinct {
+                distinct_id: id_b,
+                inner: b,
+            },
+        ) if distinct_id == id_b => unify(ctx, *a, *b, subst),
+        (Type::Normalized { inner: a }, Type::Normalized { inner: b }) => unify(ctx, *a, *b, subst),
+        (Type::Optional { inner: a }, Type::Optional { inner: b }) => unify(ctx, *a, *b, subst),
+        _ => Err(UnifyError::Mismatch),
+    }
+}
+
+/// Whether `a` and `b` unify without any prior bindings.
+pub fn unifiable(ctx: &Context, a: TypeId, b: TypeId) -> bool {
+    unify(ctx, a, b, &mut Substitution::default()).is_ok()
+}
+
+/// How distinct types are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// distinct types are only compatible with themselves, like in the checker
+    Nominal,
+    /// distinct types are compatible with everything that has the same
+    /// representation, for consumers that care about the layout
+    Structural,
+}
+
+impl Context {
+    /// Whether values of type `a` can be used as values of type `b`.
+    pub fn types_compatible(&self, a: TypeId, b: TypeId, mode: Comparison) -> bool {
+        match mode {
+            Comparison::Nominal => unifiable(self, a, b),
+            Comparison::Structural => self.structurally_equal(a, b),
+        }
+    }
+
+    fn structurally_equal(&self, a: TypeId, b: TypeId) -> bool {
+        let a = self.strip_distinct(a);
+        let b = self.strip_distinct(b);
+        if a == b {
+            return true;
+        }
+
+        let (ty_a, ty_b) = match (self.types.get(a), self.types.get(b)) {
+            (Some(ty_a), Some(ty_b)) => (ty_a, ty_b),
+            _ => return false,
+        };
+
+        match (ty_a, ty_b) {
+            (Type::Error, _) | (_, Type::Error) => true,
+            (
+                Type::Array { base: a, size },
+                Type::Array {
+                    base: b,
+                    size: size_b,
+                },
+            ) => size == size_b && self.structurally_equal(*a, *b),
+            (Type::OpenArray { base: a }, Type::OpenArray { base: b }) => {
+                self.structurally_equal(*a, *b)
+            }
+            (Type::Record { fields: a }, Type::Record { fields: b }) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|((name_a, a), (name_b, b))| {
+                        name_a == name_b && self.structurally_equal(*a, *b)
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn strip_distinct(&self, mut ty: TypeId) -> TypeId {
+        while let Some(Type::Distinct { inner, .. }) = self.types.get(ty) {
+            ty = *inner;
+        }
+        ty
+    }
+}
+
+fn bind(
+    ctx: &Context,
+    var: TypeId,
+    ty: TypeId,
+    subst: &mut Substitution,
+) -> Result<(), UnifyError> {
+    if occurs(ctx, var, ty, subst) {
+        return Err(UnifyError::Occurs(var));
+    }
+    subst.bindings.insert(var, ty);
+    Ok(())
+}
+
+/// Whether the type variable `var` occurs in `ty` under the substitution.
+fn occurs(ctx: &Context, var: TypeId, ty: TypeId, subst: &Substitution) -> bool {
+    let ty = subst.resolve(ty);
+    if ty == var {
+        return true;
+    }
+    match ctx.types.get(ty) {
+        Some(Type::Array { base, .. }) | Some(Type::OpenArray { base }) => {
+            occurs(ctx, var, *base, subst)
+        }
+        Some(Type::Record { fields }) => fields.iter().any(|(_, ty)| occurs(ctx, var, *ty, subst)),
+        Some(Type::Distinct { inner, .. })
+        | Some(Type::Normalized { inner })
+        | Some(Type::Optional { inner }) => occurs(ctx, var, *inner, subst),
+        _ => false,
+    }
+}
+
+/// Replace the generic parameters in `ty` with the given arguments.
+pub fn instantiate(ctx: &mut Context, ty: TypeId, args: &[TypeId]) -> TypeId {
+    map_type(ctx, ty, &mut |ctx, ty| match ctx.types.get(ty) {
+        Some(Type::GenericParam { index, .. }) => Some(args[*index]),
+        _ => None,
+    })
+}
+
+/// Whether the generic parameter `index` occurs in `ty`.
+pub fn contains_generic(ctx: &Context, ty: TypeId, index: usize) -> bool {
+    match ctx.types.get(ty) {
+        Some(Type::GenericParam { index: i, .. }) => *i == index,
+        Some(Type::Array { base, .. }) | Some(Type::OpenArray { base }) => {
+            contains_generic(ctx, *base, index)
+        }
+        Some(Type::Record { fields }) => fields
+            .iter()
+            .any(|(_, ty)| contains_generic(ctx, *ty, index)),
+        Some(Type::Distinct { inner, .. })
+        | Some(Type::Normalized { inner })
+        | Some(Type::Optional { inner }) => contains_generic(ctx, *inner, index),
+        _ => false,
+    }
+}
+
+/// Rebuild `ty` bottom up, replacing every part for which `f` returns a type.
+fn map_type(
+    ctx: &mut Context,
+    ty: TypeId,
+    f: &mut impl FnMut(&mut Context, TypeId) -> Option<TypeId>,
+) -> TypeId {
+    if let Some(replaced) = f(ctx, ty) {
+        return replaced;
+    }
+
+    let type_ = match ctx.types.get(ty) {
+        Some(type_) => type_.clone(),
+        None => return ty,
+    };
+
+    let mapped = match type_ {
+        Type::Array { base, size } => Type::Array {
+            base: map_type(ctx, base, f),
+            size,
+        },
+        Type::OpenArray { base } => Type::OpenArray {
+            base: map_type(ctx, base, f),
+        },
+        Type::Record { fields } => Type::Record {
+            fields: fields
+                .into_iter()
+                .map(|(name, ty)| (name, map_type(ctx, ty, f)))
+                .collect(),
+        },
+        Type::Distinct { distinct_id, inner } => Type::Distinct {
+            distinct_id,
+            inner: map_type(ctx, inner, f),
+        },
+        Type::Normalized { inner } => Type::Normalized {
+            inner: map_type(ctx, inner, f),
+        },
+        Type::Optional { inner } => Type::Optional {
+            inner: map_type(ctx, inner, f),
+        },
+        _ => return ty,
+    };
+
+    let mapped = ctx.add_or_get_type(mapped);
+    // instances of generic records keep their generic arguments
+    if let Some(args) = ctx.generic_args.get(&ty).cloned() {
+        let args = args.into_iter().map(|arg| map_type(ctx, arg, f)).collect();
+        ctx.generic_args.entry(mapped).or_insert(args);
+    }
+    mapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array_of(ctx: &mut Context, base: TypeId) -> TypeId {
+        ctx.add_or_get_type(Type::OpenArray { base })
+    }
+
+    #[test]
+    fn binds_variables() {
+        let mut ctx = Context::default();
+        let int = ctx.add_or_get_type(Type::Int);
+        let var = ctx.fresh_var();
+        let ints = array_of(&mut ctx, int);
+        let vars = array_of(&mut ctx, var);
+
+        let mut subst = Substitution::default();
+        assert_eq!(unify(&ctx, vars, ints, &mut subst), Ok(()));
+        assert_eq!(subst.get(var), Some(int));
+        assert_eq!(subst.apply(&mut ctx, vars), ints);
+    }
+
+    #[test]
+    fn conflicting_binding() {
+        let mut ctx = Context::default();
+        let int = ctx.add_or_get_type(Type::Int);
+        let float = ctx.add_or_get_type(Type::Float);
+        let var = ctx.fresh_var();
+
+        let mut subst = Substitution::default();
+        assert_eq!(unify(&ctx, var, int, &mut subst), Ok(()));
+        assert_eq!(
+            unify(&ctx, float, var, &mut subst),
+            Err(UnifyError::Conflict(var))
+        );
+        assert_eq!(
+            unify(&ctx, int, float, &mut subst),
+            Err(UnifyError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn occurs_check() {
+        let mut ctx = Context::default();
+        let var = ctx.fresh_var();
+        let vars = array_of(&mut ctx, var);
+
+        let mut subst = Substitution::default();
+        assert_eq!(
+            unify(&ctx, var, vars, &mut subst),
+            Err(UnifyError::Occurs(var))
+        );
+    }
+
+    #[test]
+    fn distinct_comparison() {
+        let mut ctx = Context::default();
+        let float = ctx.add_or_get_type(Type::Float);
+        let meters = ctx.add_or_get_type(Type::Distinct {
+            distinct_id: 0,
+            inner: float,
+        });
+        let floats = array_of(&mut ctx, float);
+        let all_meters = array_of(&mut ctx, meters);
+
+        assert!(!ctx.types_compatible(meters, float, Comparison::Nominal));
+        assert!(!ctx.types_compatible(all_meters, floats, Comparison::Nominal));
+        assert!(ctx.types_compatible(meters, float, Comparison::Structural));
+        assert!(ctx.types_compatible(all_meters, floats, Comparison::Structural));
+        assert!(ctx.types_compatible(meters, meters, Comparison::Nominal));
+    }
+
+    #[test]
+    fn error_unifies_without_binding() {
+        let mut ctx = Context::default();
+        let error = ctx.error_type();
+        let int = ctx.add_or_get_type(Type::Int);
+        let var = ctx.fresh_var();
+
+        let mut subst = Substitution::default();
+        assert_eq!(unify(&ctx, var, error, &mut subst), Ok(()));
+        assert!(!subst.is_bound(var));
+        assert_eq!(unify(&ctx, var, int, &mut subst), Ok(()));
+        assert_eq!(subst.get(var), Some(int));
+    }
+}
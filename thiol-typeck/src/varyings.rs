@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Varyings passed between the stages of a pipeline before the fragment
+//! stage, matched by name between the outputs of a stage and the inputs of
+//! the stage after it.
+
+use thiol_hir as hir;
+
+use hir::{FileLocation, Identifier, VariableDef};
+use id_arena::Id;
+
+use crate::{Context, Error, Type, TypeId};
+
+/// Attributes of inputs and outputs that make them builtins instead of
+/// varyings, in every stage
+const BUILTINS: &[&str] = &[
+    "Position",
+    "FrontFacing",
+    "VertexIndex",
+    "InstanceIndex",
+    "GlobalInvocationId",
+    "LocalInvocationId",
+    "LocalInvocationIndex",
+    "WorkgroupId",
+    "SubgroupInvocationId",
+    "SubgroupSize",
+    "InvocationId",
+    "TessCoord",
+    "TessLevelOuter",
+    "TessLevelInner",
+    "PrimitiveId",
+];
+
+/// A varying of a program, matched by name to the varyings of the stage
+/// before or after it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Varying {
+    pub ty: TypeId,
+    /// passed once per patch of a tessellation pipeline
+    pub patch: bool,
+    pub loc: FileLocation,
+}
+
+impl Varying {
+    fn describe(&self, ty_ctx: &Context) -> String {
+        let ty = ty_ctx.display_type(self.ty);
+        if self.patch {
+            format!("`{}` per patch", ty)
+        } else {
+            format!("`{}`", ty)
+        }
+    }
+}
+
+/// Whether an input or output has one of the attributes.
+pub(crate) fn has_attribute(hir_ctx: &hir::Context, def: Id<VariableDef>, names: &[&str]) -> bool {
+    hir_ctx.variable_defs[def]
+        .attrs
+        .iter()
+        .any(|attr| names.contains(&hir_ctx.identifiers[hir_ctx.attributes[*attr].name].as_str()))
+}
+
+/// Whether an input or output is a builtin, which isn't matched to the
+/// varyings of other stages.
+pub(crate) fn is_builtin(hir_ctx: &hir::Context, def: Id<VariableDef>) -> bool {
+    has_attribute(hir_ctx, def, BUILTINS)
+}
+
+/// The type of the elements of an input array with an element per vertex of
+/// the stage before it.
+pub(crate) fn element_type(ty_ctx: &Context, input: &Varying) -> Option<TypeId> {
+    match ty_ctx.types.get(ty_ctx.strip_distinct(input.ty)) {
+        Some(Type::Array { base, .. }) => Some(*base),
+        _ => None,
+    }
+}
+
+pub(crate) fn interface_mismatch(
+    ty_ctx: &Context,
+    name: &Identifier,
+    output: &Varying,
+    input: &Varying,
+) -> Error {
+    Error::InterfaceMismatch {
+        name: name.clone(),
+        output: output.describe(ty_ctx),
+        output_loc: output.loc,
+        input: input.describe(ty_ctx),
+        input_loc: input.loc,
+    }
+}
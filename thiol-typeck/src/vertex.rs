@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Reflection of the vertex inputs of programs.
+
+use std::fmt;
+
+use thiol_hir as hir;
+
+use hir::{Identifier, Program};
+use id_arena::Id;
+
+use crate::{Context, PackedFormat, Symbol, Type, TypeId, VecSize};
+
+/// The format of a vertex attribute in a vertex buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VertexFormat {
+    Float32(Option<VecSize>),
+    Float64(Option<VecSize>),
+    Uint32(Option<VecSize>),
+    Sint32(Option<VecSize>),
+    Packed(PackedFormat),
+}
+
+/// An input of a program, in the order the inputs are declared
+#[derive(Debug, Clone)]
+pub struct VertexInput {
+    pub name: Identifier,
+    pub location: usize,
+    /// `None` if the type of the input can't be read from a vertex buffer
+    pub format: Option<VertexFormat>,
+}
+
+impl Context {
+    /// The format a value of the type is stored in when it is a vertex
+    /// attribute.
+    pub fn vertex_format(&self, ty: TypeId) -> Option<VertexFormat> {
+        let format = match self.types.get(self.strip_distinct(ty))? {
+            Type::Float | Type::Radians | Type::Degrees => VertexFormat::Float32(None),
+            Type::Double => VertexFormat::Float64(None),
+            Type::UInt => VertexFormat::Uint32(None),
+            Type::Int => VertexFormat::Sint32(None),
+            Type::FloatVec { components, .. } => VertexFormat::Float32(Some(*components)),
+            Type::DoubleVec { components, .. } => VertexFormat::Float64(Some(*components)),
+            Type::UIntVec { components, .. } => VertexFormat::Uint32(Some(*components)),
+            Type::IntVec { components, .. } => VertexFormat::Sint32(Some(*components)),
+            Type::HalfVec {
+                components: VecSize::VS2,
+                ..
+            } => VertexFormat::Packed(PackedFormat::Float16x2),
+            Type::HalfVec {
+                components: VecSize::VS4,
+                ..
+            } => VertexFormat::Packed(PackedFormat::Float16x4),
+            Type::Packed { format } => VertexFormat::Packed(*format),
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// The inputs of a program with their locations and vertex formats.
+    pub fn vertex_inputs(&self, hir_ctx: &hir::Context, program: Id<Program>) -> Vec<VertexInput> {
+        hir_ctx.programs[program]
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(location, input)| VertexInput {
+                name: hir_ctx.identifiers[hir_ctx.variable_defs[*input].name].clone(),
+                location,
+                format: self
+                    .references
+                    .symbol_type(Symbol::Local(*input))
+                    .and_then(|ty| self.vertex_format(ty)),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for VertexFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (base, components) = match self {
+            VertexFormat::Float32(c) => ("float32", c),
+            VertexFormat::Float64(c) => ("float64", c),
+            VertexFormat::Uint32(c) => ("uint32", c),
+            VertexFormat::Sint32(c) => ("sint32", c),
+            VertexFormat::Packed(format) => return write!(f, "{}", format.name()),
+        };
+        write!(f, "{}", base)?;
+        match components {
+            Some(VecSize::VS2) => write!(f, "x2"),
+            Some(VecSize::VS3) => write!(f, "x3"),
+            Some(VecSize::VS4) => write!(f, "x4"),
+            None => Ok(()),
+        }
+    }
+}
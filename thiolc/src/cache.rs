@@ -0,0 +1,283 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! A cache of backend artifacts on disk.
+//!
+//! Entries are keyed by a hash of the compiler version, the source of a file
+//! and every option that changes the artifacts, so an entry is never used
+//! for a different input and old entries simply stop being looked up. Only
+//! modules that compiled without errors are cached, together with their
+//! warnings, so that a cached build reports the same as a full one.
+
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle, Severity};
+use thiol_backend::mangle::MangledName;
+use thiol_backend::Artifact;
+use thiol_hir::{FileId, FileLocation};
+
+const MAGIC: &[u8] = b"thiol-cache 4\n";
+
+const SEVERITIES: &[Severity] = &[
+    Severity::Bug,
+    Severity::Error,
+    Severity::Warning,
+    Severity::Note,
+    Severity::Help,
+];
+
+/// The hash of everything an entry depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Key(u64);
+
+impl Key {
+    /// A key for the given parts. The parts are separated by their lengths,
+    /// so moving bytes from one part to the next gives another key.
+    pub fn new<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Key {
+        // FNV-1a, which unlike the hasher of std is the same for every build
+        // of the compiler
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+        write(env!("CARGO_PKG_VERSION").as_bytes());
+        for part in parts {
+            write(&(part.len() as u64).to_le_bytes());
+            write(part);
+        }
+        Key(hash)
+    }
+}
+
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Cache { dir })
+    }
+
+    fn path(&self, key: Key) -> PathBuf {
+        self.dir.join(format!("{:016x}", key.0))
+    }
+
+    /// The diagnostics and artifacts stored for a key, with the source
+    /// locations pointing into `file`. Missing and damaged entries are both a
+    /// miss.
+    pub fn load(&self, key: Key, file: FileId) -> Option<(Vec<Diagnostic<FileId>>, Vec<Artifact>)> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        let mut reader = Reader(bytes.strip_prefix(MAGIC)?);
+        let diagnostics = (0..reader.number()?)
+            .map(|_| {
+                let severity = *SEVERITIES.get(reader.number()?)?;
+                let code = match reader.number()? {
+                    0 => None,
+                    _ => Some(String::from_utf8(reader.bytes()?.to_vec()).ok()?),
+                };
+                let message = String::from_utf8(reader.bytes()?.to_vec()).ok()?;
+                let labels = (0..reader.number()?)
+                    .map(|_| {
+                        let style = match reader.number()? {
+                            0 => LabelStyle::Primary,
+                            _ => LabelStyle::Secondary,
+                        };
+                        let range = reader.number()?..reader.number()?;
+                        let message = String::from_utf8(reader.bytes()?.to_vec()).ok()?;
+                        Some(Label::new(style, file, range).with_message(message))
+                    })
+                    .collect::<Option<_>>()?;
+                let notes = (0..reader.number()?)
+                    .map(|_| String::from_utf8(reader.bytes()?.to_vec()).ok())
+                    .collect::<Option<_>>()?;
+                Some(Diagnostic {
+                    severity,
+                    code,
+                    message,
+                    labels,
+                    notes,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let artifacts = (0..reader.number()?)
+            .map(|_| {
+                let name = String::from_utf8(reader.bytes()?.to_vec()).ok()?;
+                let contents = reader.bytes()?.to_vec();
+                let entry_points = (0..reader.number()?)
+                    .map(|_| String::from_utf8(reader.bytes()?.to_vec()).ok())
+                    .collect::<Option<_>>()?;
+                let source_map = (0..reader.number()?)
+                    .map(|_| {
+                        let offset = reader.number()?;
+                        let start = reader.number()?;
+                        let end = reader.number()?;
+                        Some((offset, FileLocation { file, start, end }))
+                    })
+                    .collect::<Option<_>>()?;
+                let names = (0..reader.number()?)
+                    .map(|_| {
+                        Some(MangledName {
+                            mangled: String::from_utf8(reader.bytes()?.to_vec()).ok()?,
+                            source: String::from_utf8(reader.bytes()?.to_vec()).ok()?,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                Some(Artifact {
+                    name,
+                    contents,
+                    entry_points,
+                    source_map,
+                    names,
+                })
+            })
+            .collect::<Option<_>>()?;
+        if !reader.0.is_empty() {
+            return None;
+        }
+        Some((diagnostics, artifacts))
+    }
+
+    /// Store the diagnostics and artifacts of a module, whose labels and
+    /// source maps may only refer to the file of the module.
+    pub fn store(
+        &self,
+        key: Key,
+        diagnostics: &[Diagnostic<FileId>],
+        artifacts: &[Artifact],
+    ) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        let number = |bytes: &mut Vec<u8>, n: usize| {
+            bytes.extend_from_slice(&(n as u64).to_le_bytes());
+        };
+        number(&mut bytes, diagnostics.len());
+        for diag in diagnostics {
+            let severity = SEVERITIES.iter().position(|s| *s == diag.severity);
+            number(&mut bytes, severity.unwrap());
+            number(&mut bytes, diag.code.is_some() as usize);
+            if let Some(code) = &diag.code {
+                number(&mut bytes, code.len());
+                bytes.extend_from_slice(code.as_bytes());
+            }
+            number(&mut bytes, diag.message.len());
+            bytes.extend_from_slice(diag.message.as_bytes());
+            number(&mut bytes, diag.labels.len());
+            for label in &diag.labels {
+                number(&mut bytes, (label.style == LabelStyle::Secondary) as usize);
+                number(&mut bytes, label.range.start);
+                number(&mut bytes, label.range.end);
+                number(&mut bytes, label.message.len());
+                bytes.extend_from_slice(label.message.as_bytes());
+            }
+            number(&mut bytes, diag.notes.len());
+            for note in &diag.notes {
+                number(&mut bytes, note.len());
+                bytes.extend_from_slice(note.as_bytes());
+            }
+        }
+        number(&mut bytes, artifacts.len());
+        for artifact in artifacts {
+            number(&mut bytes, artifact.name.len());
+            bytes.extend_from_slice(artifact.name.as_bytes());
+            number(&mut bytes, artifact.contents.len());
+            bytes.extend_from_slice(&artifact.contents);
+            number(&mut bytes, artifact.entry_points.len());
+            for entry_point in &artifact.entry_points {
+                number(&mut bytes, entry_point.len());
+                bytes.extend_from_slice(entry_point.as_bytes());
+            }
+            number(&mut bytes, artifact.source_map.len());
+            for (offset, loc) in &artifact.source_map {
+                number(&mut bytes, *offset);
+                number(&mut bytes, loc.start);
+                number(&mut bytes, loc.end);
+            }
+            number(&mut bytes, artifact.names.len());
+            for name in &artifact.names {
+                for part in [&name.mangled, &name.source] {
+                    number(&mut bytes, part.len());
+                    bytes.extend_from_slice(part.as_bytes());
+                }
+            }
+        }
+
+        // write the entry under another name first, so that a compiler
+        // running at the same time never reads half of it
+        let path = self.path(key);
+        let partial = path.with_extension(format!("partial-{}", std::process::id()));
+        fs::write(&partial, bytes)?;
+        fs::rename(partial, path)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn number(&mut self) -> Option<usize> {
+        let (number, rest) = self.0.split_at_checked(8)?;
+        self.0 = rest;
+        u64::from_le_bytes(number.try_into().ok()?).try_into().ok()
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.number()?;
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_load() {
+        let dir = std::env::temp_dir().join(format!("thiol-cache-test-{}", std::process::id()));
+        let cache = Cache::open(&dir).unwrap();
+        let key = Key::new([&b"program"[..], b"msl"]);
+        assert_ne!(key, Key::new([&b"progra"[..], b"mmsl"]));
+
+        let mut artifact = Artifact::text("main.metal", "kernel void main() {}".to_string())
+            .with_entry_points(vec!["main".to_string()])
+            .with_names(vec![MangledName {
+                mangled: "main_".to_string(),
+                source: "main".to_string(),
+            }]);
+        let loc = |file| FileLocation {
+            file,
+            start: 4,
+            end: 9,
+        };
+        artifact.source_map = vec![(0, loc(3))];
+        let warning = |file| {
+            Diagnostic::warning()
+                .with_code("W0001")
+                .with_message("unused variable")
+                .with_labels(vec![
+                    Label::primary(file, 4..9).with_message("never read"),
+                    Label::secondary(file, 0..3),
+                ])
+                .with_notes(vec!["help: remove it".to_string()])
+        };
+        assert_eq!(cache.load(key, 0), None);
+        cache
+            .store(key, &[warning(3)], &[artifact.clone()])
+            .unwrap();
+
+        artifact.source_map = vec![(0, loc(1))];
+        assert_eq!(cache.load(key, 1), Some((vec![warning(1)], vec![artifact])));
+
+        fs::write(cache.path(key), b"thiol-cache 4\n\x05").unwrap();
+        assert_eq!(cache.load(key, 1), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,764 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! The thiol compiler driver.
+//!
+//! Crates with backends of their own can run the driver with
+//! [`main_with`], the backends they register are available to `--emit`
+//! next to the ones of thiol.
+
+use codespan_reporting::{
+    diagnostic::{Diagnostic, Label},
+    files::Files,
+    term::{
+        termcolor::{ColorChoice, StandardStream},
+        Config,
+    },
+};
+use thiol_backend::{Artifact, Backend, Registry};
+use thiol_syntax::parser;
+use thiol_syntax::FileId;
+
+use anyhow::bail;
+use clap::Clap;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+mod cache;
+mod link;
+mod overrides;
+mod passes;
+mod pretty_printing;
+mod publish;
+mod stats;
+mod watch;
+
+pub use publish::Publisher;
+pub use stats::CompilationStats;
+pub use watch::Watcher;
+
+#[derive(Debug, Clap)]
+#[clap(version = "0.1", author = "Tia")]
+struct Arguments {
+    #[clap(long)]
+    parse_only: bool,
+
+    #[clap(long)]
+    dump_type_context: bool,
+
+    /// The target profile, `desktop`, `desktop-f16` or `gles3`
+    #[clap(long, default_value = "desktop")]
+    profile: thiol_typeck::Profile,
+
+    /// What to do with vectors used in another space: `off`, `report` the
+    /// transforms between the spaces, or `insert` them
+    #[clap(long, default_value = "off")]
+    space_check: thiol_typeck::SpaceCheck,
+
+    /// How matrices in buffers without a `row_major` or `column_major`
+    /// attribute are stored: `column-major` or `row-major`
+    #[clap(long, default_value = "column-major")]
+    matrix_layout: thiol_typeck::MatrixLayout,
+
+    /// How indices that may be out of bounds are checked at runtime:
+    /// `clamp`, `undefined` or `trap`
+    #[clap(long, default_value = "undefined")]
+    bounds_check: thiol_typeck::BoundsCheck,
+
+    /// What plain `+`, `-` and `*` on integers do when they overflow:
+    /// `wrap`, `saturate` or `undefined`
+    #[clap(long, default_value = "undefined")]
+    integer_overflow: thiol_typeck::IntegerOverflow,
+
+    /// Leave assertions and debug prints out of the generated code
+    #[clap(long)]
+    release: bool,
+
+    /// Whether types, functions and constants with the same name are
+    /// allowed: `shared` reports them, `separate` allows them
+    #[clap(long, default_value = "shared")]
+    namespaces: thiol_typeck::Namespaces,
+
+    /// The most instances of generic functions a module can have
+    #[clap(long, default_value = "256")]
+    instantiation_limit: usize,
+
+    /// The most statements evaluating a call when compiling can run
+    #[clap(long, default_value = "1000000")]
+    eval_step_limit: usize,
+
+    /// The most vertices a mesh program can output on the target
+    #[clap(long, default_value = "256")]
+    max_mesh_vertices: u32,
+
+    /// The most primitives a mesh program can output on the target
+    #[clap(long, default_value = "256")]
+    max_mesh_primitives: u32,
+
+    /// Give a constant declared without a value a value, as `NAME=VALUE`
+    #[clap(long = "override", number_of_values = 1)]
+    overrides: Vec<overrides::ConstantOverride>,
+
+    /// Report the warnings of a lint group, like `shadowing`
+    #[clap(long = "warn", number_of_values = 1)]
+    warn: Vec<thiol_typeck::LintGroup>,
+
+    /// Report the warnings of a lint group as errors
+    #[clap(long = "deny", number_of_values = 1)]
+    deny: Vec<thiol_typeck::LintGroup>,
+
+    /// Don't report the warnings of a lint group
+    #[clap(long = "allow", number_of_values = 1)]
+    allow: Vec<thiol_typeck::LintGroup>,
+
+    /// Bindings that are not assigned to buffers automatically, as
+    /// `set:first-last` or `set:binding`
+    #[clap(long = "reserve-bindings")]
+    reserve_bindings: Vec<thiol_typeck::BindingReservation>,
+
+    /// Print the type dependency graph in the Graphviz DOT format
+    #[clap(long)]
+    dump_type_graph: bool,
+
+    /// Print the call graph in the Graphviz DOT format
+    #[clap(long)]
+    dump_call_graph: bool,
+
+    /// Print the location and vertex format of the inputs of every program
+    #[clap(long)]
+    dump_vertex_formats: bool,
+
+    /// Print the resources every program accesses
+    #[clap(long)]
+    dump_resources: bool,
+
+    /// Print the side effects of every function
+    #[clap(long)]
+    dump_effects: bool,
+
+    /// Print the profiling labels of every program and the functions it
+    /// calls
+    #[clap(long)]
+    dump_profile_labels: bool,
+
+    /// Print the instances of generic functions
+    #[clap(long)]
+    dump_instances: bool,
+
+    /// The optimization passes to run before the backend, separated by
+    /// commas, out of `const-fold`, `inline`, `cse`, `unroll` and `dce`. By
+    /// default `const-fold,dce`, or `none`
+    #[clap(long)]
+    passes: Option<String>,
+
+    /// Print the module after an optimization pass, or after every pass with
+    /// `all`
+    #[clap(long)]
+    print_ir_after: Option<String>,
+
+    /// Print how long every phase of the compilation took, and how many
+    /// types and functions were checked
+    #[clap(long)]
+    timings: bool,
+
+    /// Print the artifacts of a backend, see `--list-backends`
+    #[clap(long)]
+    emit: Option<String>,
+
+    /// Emit every program on its own, with only the functions, types and
+    /// constants it uses
+    #[clap(long)]
+    split_entry_points: bool,
+
+    /// The environment SPIR-V artifacts are validated for, like `vulkan1.1`
+    #[cfg(feature = "spirv-val")]
+    #[clap(long, default_value = "vulkan1.0")]
+    spirv_target_env: thiol_backend::spirv::TargetEnv,
+
+    /// Print the names of the available backends
+    #[clap(long)]
+    list_backends: bool,
+
+    /// Bind the buffers of each set through a Metal argument buffer
+    #[clap(long)]
+    msl_argument_buffers: bool,
+
+    /// Reuse the artifacts of earlier runs stored in this directory, and
+    /// store the new ones there
+    #[clap(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Keep running and compile the files again when they change, directories
+    /// are searched for `.rsh` files
+    #[clap(long)]
+    watch: bool,
+
+    /// With `--watch`, send the artifacts of every entry point to the engines
+    /// connected to this address, like `127.0.0.1:7171`
+    #[clap(long)]
+    publish: Option<String>,
+
+    /// Do not display colours in the terminal output
+    #[clap(long)]
+    no_colour: bool,
+
+    file_paths: Vec<PathBuf>,
+}
+
+/// Run the compiler with the command line arguments of the process, with
+/// additional backends. They replace the backends of thiol with the same
+/// name.
+pub fn main_with(backends: Registry) {
+    match run(backends) {
+        Ok(()) => {}
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn run(extra_backends: Registry) -> anyhow::Result<()> {
+    let args: Arguments = Arguments::parse();
+
+    let mut backends = Registry::new();
+    backends.register(thiol_glsl::GlslBackend);
+    backends.register(thiol_msl::MslBackend {
+        options: thiol_msl::Options {
+            argument_buffers: args.msl_argument_buffers,
+        },
+    });
+    backends.extend(extra_backends);
+
+    if args.list_backends {
+        for backend in backends.backends() {
+            println!("{}: {}", backend.name(), backend.description());
+        }
+    }
+
+    let pass_manager = match &args.passes {
+        Some(passes) => passes::PassManager::new(
+            passes
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty() && *name != "none"),
+        ),
+        None => passes::PassManager::new(passes::DEFAULT_PIPELINE.iter().copied()),
+    }
+    .map_err(anyhow::Error::msg)?;
+    if let Some(pass) = &args.print_ir_after {
+        if pass != "all" && !pass_manager.contains(pass) {
+            bail!("`--print-ir-after {}` names a pass that doesn't run", pass);
+        }
+    }
+
+    let backend = match &args.emit {
+        Some(name) => match backends.get(name) {
+            Some(backend) => Some(backend),
+            None => bail!("unknown backend `{}`, see `--list-backends`", name),
+        },
+        None => None,
+    };
+
+    // dumps need the whole pipeline to run, so they are never served from
+    // the cache
+    let dumps = args.dump_type_context
+        || args.dump_type_graph
+        || args.dump_call_graph
+        || args.dump_vertex_formats
+        || args.dump_resources
+        || args.dump_effects
+        || args.dump_profile_labels
+        || args.dump_instances
+        || args.print_ir_after.is_some();
+    let publisher = match &args.publish {
+        Some(_) if !args.watch => bail!("`--publish` only works with `--watch`"),
+        Some(_) if backend.is_none() => bail!("`--publish` needs a backend, see `--emit`"),
+        Some(addr) => Some(Publisher::bind(addr.as_str())?),
+        None => None,
+    };
+    // the events of the publisher need the checked module too
+    let cache = match (&args.cache_dir, backend) {
+        (Some(dir), Some(_)) if !dumps && publisher.is_none() => Some(cache::Cache::open(dir)?),
+        _ => None,
+    };
+
+    let mut driver = Driver {
+        args: &args,
+        backend,
+        pass_manager,
+        cache,
+        publisher,
+        stats: CompilationStats::default(),
+    };
+
+    if args.watch {
+        return watch(&mut driver);
+    }
+
+    for path in &args.file_paths {
+        let artifacts = driver.compile(path)?;
+        print_artifacts(&artifacts)?;
+    }
+
+    if args.timings {
+        eprintln!("{}", driver.stats);
+    }
+
+    Ok(())
+}
+
+/// Compile the files and then every file that changes, until the process is
+/// stopped. Errors are reported and don't stop the watching.
+fn watch(driver: &mut Driver<'_>) -> anyhow::Result<()> {
+    let mut watcher = Watcher::new(driver.args.file_paths.iter().cloned());
+    let mut changed = watcher.poll();
+    loop {
+        for path in changed {
+            let start = Instant::now();
+            match driver.compile(&path) {
+                Ok(artifacts) => {
+                    print_artifacts(&artifacts)?;
+                    eprintln!(
+                        "compiled {} in {:.3}ms",
+                        path.display(),
+                        start.elapsed().as_secs_f64() * 1000.0
+                    );
+                }
+                Err(err) => eprintln!("{}: {}", path.display(), err),
+            }
+        }
+        if driver.args.timings {
+            eprintln!("{}", driver.stats);
+        }
+        std::io::stdout().flush()?;
+        changed = watcher.wait();
+    }
+}
+
+/// The options and state shared by the compilations of a run
+struct Driver<'a> {
+    args: &'a Arguments,
+    backend: Option<&'a dyn Backend>,
+    pass_manager: passes::PassManager,
+    cache: Option<cache::Cache>,
+    publisher: Option<Publisher>,
+    stats: CompilationStats,
+}
+
+impl Driver<'_> {
+    /// Compile a file and return the artifacts of the backend, if there is
+    /// one. The diagnostics are printed.
+    fn compile(&mut self, path: &Path) -> anyhow::Result<Vec<Artifact>> {
+        let args = self.args;
+        let stats = &mut self.stats;
+        let mut hir_ctx = thiol_hir::Context::default();
+        let mut files = codespan_reporting::files::SimpleFiles::new();
+
+        let content = std::fs::read_to_string(path)?;
+        let id = files.add(path.display().to_string(), content);
+        let src = files.source(id).unwrap();
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("module");
+
+        stats.files += 1;
+        let key = match (&self.cache, self.backend) {
+            (Some(cache), Some(backend)) => {
+                let key = cache_key(args, backend.name(), name, src);
+                if let Some((diagnostics, artifacts)) = cache.load(key, id) {
+                    stats.cached += 1;
+                    for diag in diagnostics {
+                        emit(!args.no_colour, &files, diag);
+                    }
+                    return Ok(artifacts);
+                }
+                Some(key)
+            }
+            _ => None,
+        };
+
+        let start = Instant::now();
+        let ast = parser::parse_file(id, src);
+        stats.record("parse", start.elapsed());
+        let ast = match ast {
+            Ok(val) => val,
+            Err(err) => {
+                let diag = parse_error_to_diag(err);
+                emit(!args.no_colour, &files, diag);
+
+                bail!("Aborting due to previous error")
+            }
+        };
+        if args.parse_only {
+            return Ok(vec![]);
+        }
+
+        let start = Instant::now();
+        let module = thiol_ast_lowering::lower(&mut hir_ctx, &ast);
+        stats.record("lower", start.elapsed());
+        let mut module = match module {
+            Ok(module) => module,
+            Err(errs) => {
+                for err in errs {
+                    let diag = ast_lowering_error_to_diag(err);
+                    emit(!args.no_colour, &files, diag);
+                }
+                bail!("aborting due to previous error");
+            }
+        };
+        if let Err(diags) =
+            overrides::apply(&args.overrides, &mut hir_ctx, &module, &ast, &mut files)
+        {
+            for diag in diags {
+                emit(!args.no_colour, &files, diag);
+            }
+            bail!("aborting due to previous error");
+        }
+
+        let mut ty_ctx = thiol_typeck::Context {
+            profile: args.profile,
+            space_check: args.space_check,
+            matrix_layout: args.matrix_layout,
+            bounds_check: args.bounds_check,
+            integer_overflow: args.integer_overflow,
+            release: args.release,
+            namespaces: args.namespaces,
+            instantiation_limit: Some(args.instantiation_limit),
+            eval_step_limit: Some(args.eval_step_limit),
+            max_mesh_vertices: Some(args.max_mesh_vertices),
+            max_mesh_primitives: Some(args.max_mesh_primitives),
+            binding_reservations: args.reserve_bindings.clone(),
+            lint_levels: lint_levels(args),
+            ..Default::default()
+        };
+        let result = thiol_typeck::type_check(&mut ty_ctx, &hir_ctx, &module);
+        for (phase, time) in &ty_ctx.phase_timings {
+            stats.record(format!("typeck: {}", phase), *time);
+        }
+        stats.types += ty_ctx.types.len();
+        stats.functions += ty_ctx.function_sigs.len();
+        stats.programs += module.programs.len();
+        for warning in &ty_ctx.warnings {
+            let diag = Diagnostic::from(warning.clone());
+            emit(!args.no_colour, &files, diag);
+        }
+        match result {
+            Ok(_) => {}
+            Err(errs) => {
+                for err in errs {
+                    let diag = Diagnostic::from(err);
+                    emit(!args.no_colour, &files, diag);
+                }
+                bail!("aboring due to previous error")
+            }
+        }
+
+        if args.dump_type_context {
+            println!("{}", pretty_printing::dump_type_context(&hir_ctx, &ty_ctx));
+        }
+
+        if args.dump_type_graph {
+            println!("{}", ty_ctx.type_graph.to_dot(&hir_ctx, "types"));
+        }
+
+        if args.dump_call_graph {
+            println!("{}", ty_ctx.call_graph.to_dot(&hir_ctx, "calls"));
+        }
+
+        if args.dump_vertex_formats {
+            println!(
+                "{}",
+                pretty_printing::dump_vertex_formats(&hir_ctx, &ty_ctx, &module)
+            );
+        }
+
+        if args.dump_resources {
+            println!(
+                "{}",
+                pretty_printing::dump_resources(&hir_ctx, &ty_ctx, &module)
+            );
+        }
+
+        if args.dump_effects {
+            println!(
+                "{}",
+                pretty_printing::dump_effects(&hir_ctx, &ty_ctx, &module)
+            );
+        }
+
+        if args.dump_profile_labels {
+            println!(
+                "{}",
+                pretty_printing::dump_profile_labels(&hir_ctx, &ty_ctx, &module)
+            );
+        }
+
+        if args.dump_instances {
+            println!("{}", pretty_printing::dump_instances(&hir_ctx, &ty_ctx));
+        }
+
+        let mut cx = passes::PassContext {
+            hir: &mut hir_ctx,
+            ty: &mut ty_ctx,
+            module: &mut module,
+        };
+        let ran = self.pass_manager.timings().len();
+        self.pass_manager.run(&mut cx, |pass, cx| {
+            if args
+                .print_ir_after
+                .as_deref()
+                .is_some_and(|p| p == "all" || p == pass)
+            {
+                println!("// after {}", pass);
+                println!("{}", pretty_printing::dump_module(cx.hir, cx.module));
+            }
+        });
+
+        for (pass, time) in &self.pass_manager.timings()[ran..] {
+            stats.record(format!("pass: {}", pass), *time);
+        }
+
+        let backend = match self.backend {
+            Some(backend) => backend,
+            None => return Ok(vec![]),
+        };
+        let input = thiol_backend::Input {
+            name,
+            hir: &hir_ctx,
+            ty: &ty_ctx,
+            module: &module,
+        };
+        let start = Instant::now();
+        let output = if args.split_entry_points {
+            link::emit_entry_points(backend, &input)
+        } else {
+            backend.emit(input)
+        };
+        stats.record(format!("backend: {}", backend.name()), start.elapsed());
+        #[cfg(feature = "spirv-val")]
+        let output = validate_spirv(output, args.spirv_target_env);
+        let failed = output.has_errors();
+        // a cache hit reports the warnings again, the ones with labels in the
+        // files of `--override` aren't stored
+        let diagnostics = ty_ctx
+            .warnings
+            .iter()
+            .map(|warning| Diagnostic::from(warning.clone()))
+            .chain(output.diagnostics.iter().cloned())
+            .collect::<Vec<_>>();
+        let cacheable = diagnostics
+            .iter()
+            .all(|diag| diag.labels.iter().all(|label| label.file_id == id));
+        for diag in output.diagnostics {
+            emit(!args.no_colour, &files, diag);
+        }
+        if failed {
+            bail!("aborting due to previous error");
+        }
+
+        if let Some(publisher) = &mut self.publisher {
+            publisher.publish(&thiol_backend::hot_reload::events(
+                &input,
+                &output.artifacts,
+            ));
+        }
+
+        if let (Some(cache), Some(key), true) = (&self.cache, key, cacheable) {
+            if let Err(err) = cache.store(key, &diagnostics, &output.artifacts) {
+                eprintln!(
+                    "warning: couldn't store the artifacts in the cache: {}",
+                    err
+                );
+            }
+        }
+        Ok(output.artifacts)
+    }
+}
+
+/// The levels of the lint groups given as arguments, `deny` wins over `warn`
+/// and `warn` over `allow`.
+fn lint_levels(args: &Arguments) -> BTreeMap<thiol_typeck::LintGroup, thiol_typeck::LintLevel> {
+    use thiol_typeck::LintLevel;
+
+    let mut levels = BTreeMap::new();
+    for (groups, level) in [
+        (&args.allow, LintLevel::Allow),
+        (&args.warn, LintLevel::Warn),
+        (&args.deny, LintLevel::Deny),
+    ] {
+        for group in groups {
+            levels.insert(*group, level);
+        }
+    }
+    levels
+}
+
+/// The cache key of a file, from its source and the options that affect its
+/// artifacts.
+fn cache_key(args: &Arguments, backend: &str, name: &str, src: &str) -> cache::Key {
+    #[allow(unused_mut)]
+    let mut options = format!(
+        "{:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?}",
+        args.profile,
+        args.space_check,
+        args.matrix_layout,
+        args.bounds_check,
+        args.integer_overflow,
+        args.release,
+        args.namespaces,
+        args.instantiation_limit,
+        args.eval_step_limit,
+        args.max_mesh_vertices,
+        args.max_mesh_primitives,
+        args.overrides,
+        lint_levels(args),
+        args.reserve_bindings,
+        args.passes,
+        args.msl_argument_buffers,
+        args.split_entry_points
+    );
+    #[cfg(feature = "spirv-val")]
+    options.push_str(&format!(" {:?}", args.spirv_target_env));
+
+    let parts = [src, name, backend, &options];
+    cache::Key::new(parts.iter().map(|part| part.as_bytes()))
+}
+
+/// Add the problems spirv-val finds in the SPIR-V artifacts to the
+/// diagnostics of a backend.
+#[cfg(feature = "spirv-val")]
+fn validate_spirv(
+    mut output: thiol_backend::Output,
+    env: thiol_backend::spirv::TargetEnv,
+) -> thiol_backend::Output {
+    for artifact in &output.artifacts {
+        if thiol_backend::spirv::is_spirv(artifact) {
+            let diags = thiol_backend::spirv::validate(artifact, env);
+            output.diagnostics.extend(diags);
+        }
+    }
+    output
+}
+
+/// Print the artifacts, with the name of each one before it if there are
+/// several.
+fn print_artifacts(artifacts: &[Artifact]) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    for (i, artifact) in artifacts.iter().enumerate() {
+        if artifacts.len() > 1 {
+            if i > 0 {
+                writeln!(stdout)?;
+            }
+            writeln!(stdout, "// {}", artifact.name)?;
+        }
+        stdout.write_all(&artifact.contents)?;
+    }
+    Ok(())
+}
+
+fn colour_choice(coloured: bool) -> ColorChoice {
+    if coloured {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    }
+}
+
+fn emit<'a>(coloured: bool, files: &'a impl Files<'a, FileId = FileId>, diag: Diagnostic<FileId>) {
+    let mut writer = StandardStream::stderr(colour_choice(coloured));
+    let term_config = Config::default();
+
+    codespan_reporting::term::emit(&mut writer, &term_config, files, &diag).unwrap();
+}
+
+fn parse_error_to_diag(err: thiol_syntax::parser::ParseError) -> Diagnostic<FileId> {
+    use thiol_syntax::parser::ParseError;
+
+    let location = err.location();
+    let message = match &err {
+        ParseError::UnexpectedToken { .. } => "Unexpected token",
+        ParseError::UnexpectedEof { .. } => "Unexpected end of file",
+    };
+    let label = Label::primary(location.file, location.range()).with_message(message);
+    let mut notes = vec!["Expected one of the following:".to_string()];
+    notes.extend(err.expected().iter().map(|s| format!(" - {}", s)));
+    let note = notes.join("\n");
+
+    Diagnostic::error()
+        .with_message("parse error")
+        .with_labels(vec![label])
+        .with_notes(vec![note])
+}
+
+fn ast_lowering_error_to_diag(err: thiol_ast_lowering::Error) -> Diagnostic<FileId> {
+    use thiol_ast_lowering::Error;
+
+    match err {
+        Error::PositionalArgAfterNamedArg {
+            item: _,
+            named_arg,
+            pos_arg,
+        } => {
+            let prim = Label::primary(pos_arg.file, pos_arg.range())
+                .with_message("positional argument used here");
+            let other = Label::secondary(named_arg.file, named_arg.range())
+                .with_message("named argument used here");
+
+            Diagnostic::error()
+                .with_message("positional argument used after named argument")
+                .with_labels(vec![prim, other])
+        }
+        Error::CallOnNonFunction { item: _, base } => {
+            let prim =
+                Label::primary(base.file, base.range()).with_message("not a callable expression");
+            Diagnostic::error()
+                .with_message("function call on a non-callable value")
+                .with_labels(vec![prim])
+        }
+        Error::TypeConstructorInInvalidPosition { where_ } => {
+            let prim = Label::primary(where_.file, where_.range())
+                .with_message("invalid position for type constructor");
+            Diagnostic::error()
+                .with_message("type constructor used in an invalid position")
+                .with_labels(vec![prim])
+        }
+        Error::IntegerLiteralTooLarge { literal } => {
+            let prim = Label::primary(literal.file, literal.range())
+                .with_message("larger than any signed 128 bit integer");
+            Diagnostic::error()
+                .with_message("integer literal is too large")
+                .with_labels(vec![prim])
+        }
+        Error::UnresolvedPath { kind, path, loc } => {
+            let prim =
+                Label::primary(loc.file, loc.range()).with_message(format!("no such {}", kind));
+            Diagnostic::error()
+                .with_message(format!("{} `{}` not defined", kind, path))
+                .with_labels(vec![prim])
+        }
+        Error::PrivateItem {
+            kind,
+            name,
+            reference,
+            declaration,
+        } => {
+            let module = name.rsplit_once("::").map_or("", |(module, _)| module);
+            let prim = Label::primary(reference.file, reference.range())
+                .with_message(format!("private {}", kind));
+            let decl = Label::secondary(declaration.file, declaration.range())
+                .with_message(format!("{} declared here without `pub`", kind));
+            Diagnostic::error()
+                .with_message(format!("{} `{}` is private", kind, name))
+                .with_labels(vec![prim, decl])
+                .with_notes(vec![format!(
+                    "help: declare it `pub` to use it outside of module `{}`",
+                    module
+                )])
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Artifacts for single entry points.
+//!
+//! A backend translates a whole module, so every artifact contains the
+//! functions and types of all programs. With `--split-entry-points` the
+//! backend runs once for every program instead, on a module with only the
+//! program and the functions, types and constants it uses, directly or
+//! through the items it uses. Unlike the `dce` pass, buffers the program
+//! doesn't access are left out as well, their bindings stay the same.
+
+use std::collections::BTreeSet;
+
+use thiol_backend::{Backend, Input, Output};
+use thiol_hir as hir;
+use thiol_typeck as ty;
+
+use hir::{FileLocation, Program, TypeDefinition};
+use id_arena::Id;
+use ty::{Callable, Symbol};
+
+use crate::passes;
+
+/// The module of a program, with the items the program doesn't use left out.
+pub(crate) fn entry_point(
+    hir: &hir::Context,
+    ty: &ty::Context,
+    module: &hir::Module,
+    program: Id<Program>,
+) -> hir::Module {
+    let (used, consts) = passes::reachable(hir, ty, module, &[program], false);
+    let functions = module
+        .functions
+        .iter()
+        .copied()
+        .filter(|id| used.contains(&Callable::Function(*id)))
+        .collect::<Vec<_>>();
+
+    // the types named in the items that are kept, and the types those use
+    let spans = functions
+        .iter()
+        .map(|id| hir.function_fcs[id])
+        .chain(consts.iter().map(|id| hir.variable_def_fcs[id]))
+        .chain(Some(hir.program_fcs[&program]))
+        .collect::<Vec<_>>();
+    let in_spans = |loc: &FileLocation| spans.iter().any(|span| passes::contains(*span, *loc));
+    let mut types = module
+        .types
+        .iter()
+        .copied()
+        .filter(|id| {
+            ty.references
+                .references(Symbol::Type(*id))
+                .iter()
+                .any(in_spans)
+        })
+        .collect::<BTreeSet<Id<TypeDefinition>>>();
+    // users come after their dependencies, so going backwards reaches every
+    // type after the types that use it
+    for id in ty.type_order.iter().rev() {
+        if types.contains(id) {
+            types.extend(
+                ty.type_graph
+                    .dependencies(*id)
+                    .into_iter()
+                    .map(|(dep, _)| dep),
+            );
+        }
+    }
+
+    hir::Module {
+        types: module
+            .types
+            .iter()
+            .copied()
+            .filter(|id| types.contains(id))
+            .collect(),
+        consts: module
+            .consts
+            .iter()
+            .copied()
+            .filter(|id| consts.contains(id))
+            .collect(),
+        functions,
+        programs: vec![program],
+        spaces: module.spaces.clone(),
+        static_asserts: module.static_asserts.clone(),
+        uses: vec![],
+    }
+}
+
+/// The artifacts of every program of a module, emitted on their own. The
+/// artifacts of a program are named as if the module was named
+/// `<module>.<program>`.
+pub(crate) fn emit_entry_points(backend: &dyn Backend, input: &Input<'_>) -> Output {
+    let mut output = Output::default();
+    for program in &input.module.programs {
+        let module = entry_point(input.hir, input.ty, input.module, *program);
+        let program_name = &input.hir.identifiers[input.hir.programs[*program].name];
+        let name = format!("{}.{}", input.name, program_name);
+        let program_output = backend.emit(Input {
+            name: &name,
+            module: &module,
+            ..*input
+        });
+
+        output.artifacts.extend(program_output.artifacts);
+        // problems in the functions programs share are found once for every
+        // program
+        for diag in program_output.diagnostics {
+            if !output.diagnostics.contains(&diag) {
+                output.diagnostics.push(diag);
+            }
+        }
+    }
+    output
+}
@@ -0,0 +1,194 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Values of constants given when compiling.
+//!
+//! A constant can be declared without a value, like `const EXPOSURE:
+//! float;`, and get one with `--override EXPOSURE=1.5` instead, which is
+//! cheaper than a uniform for values that rarely change. The value is parsed
+//! as a thiol expression and lowered in the module of the constant, so it is
+//! type checked against the declared type like values in the source, and
+//! problems in it are reported in a file of its own. Values can only use
+//! literals and types, not other constants or functions.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFiles;
+use thiol_hir as hir;
+use thiol_syntax::{ast, parser, FileId};
+
+use hir::Expression;
+
+/// A constant and the value it gets, from `NAME=VALUE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConstantOverride {
+    /// the name of the constant, qualified with its module like
+    /// `lighting::EXPOSURE`
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for ConstantOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() && !value.trim().is_empty() => {
+                Ok(ConstantOverride {
+                    name: name.trim().to_string(),
+                    value: value.trim().to_string(),
+                })
+            }
+            _ => Err(format!("expected `NAME=VALUE`, found `{}`", s)),
+        }
+    }
+}
+
+/// Give the constants of the module the values of the overrides.
+pub(crate) fn apply(
+    overrides: &[ConstantOverride],
+    hir_ctx: &mut hir::Context,
+    module: &hir::Module,
+    file: &ast::File,
+    files: &mut SimpleFiles<String, String>,
+) -> Result<(), Vec<Diagnostic<FileId>>> {
+    let mut diags = vec![];
+    let mut overridden = HashSet::new();
+    for over in overrides {
+        let id = module
+            .consts
+            .iter()
+            .copied()
+            .find(|id| hir_ctx.identifiers[hir_ctx.variable_defs[*id].name] == over.name);
+        let id = match id {
+            Some(id) => id,
+            None => {
+                let names = module
+                    .consts
+                    .iter()
+                    .map(|id| hir_ctx.identifiers[hir_ctx.variable_defs[*id].name].as_str());
+                let notes = thiol_typeck::suggestions::similar_names(&over.name, names)
+                    .into_iter()
+                    .map(|name| format!("a constant with a similar name exists: `{}`", name))
+                    .collect();
+                diags.push(
+                    Diagnostic::error()
+                        .with_message(format!("override of unknown constant `{}`", over.name))
+                        .with_notes(notes),
+                );
+                continue;
+            }
+        };
+
+        let def = &hir_ctx.variable_defs[id];
+        let declaration = hir_ctx.identifier_fcs[&def.name];
+        if !overridden.insert(id) {
+            diags.push(
+                Diagnostic::error()
+                    .with_message(format!("constant `{}` is overridden twice", over.name)),
+            );
+            continue;
+        }
+        if let Some(rhs) = def.rhs {
+            let value = hir_ctx.expression_fcs[&rhs];
+            diags.push(
+                Diagnostic::error()
+                    .with_message(format!("constant `{}` already has a value", over.name))
+                    .with_labels(vec![
+                        Label::primary(value.file, value.range()).with_message("assigned here"),
+                        Label::secondary(declaration.file, declaration.range()),
+                    ])
+                    .with_notes(vec![
+                        "only constants declared without a value can be overridden".to_string(),
+                    ]),
+            );
+            continue;
+        }
+        if thiol_typeck::layout::buffer_class(hir_ctx, id).is_some() {
+            diags.push(
+                Diagnostic::error()
+                    .with_message(format!("buffer `{}` can't be overridden", over.name))
+                    .with_labels(vec![Label::primary(declaration.file, declaration.range())
+                        .with_message("declared as a buffer here")]),
+            );
+            continue;
+        }
+
+        let file_id = files.add(format!("--override {}", over.name), over.value.clone());
+        let expr = match parser::parse_expression(file_id, &over.value) {
+            Ok(expr) => expr,
+            Err(err) => {
+                diags.push(crate::parse_error_to_diag(err));
+                continue;
+            }
+        };
+        let path = match over.name.rsplit_once("::") {
+            Some((path, _)) => path.split("::").map(String::from).collect(),
+            None => vec![],
+        };
+        let first = hir_ctx.expressions.len();
+        let rhs = match thiol_ast_lowering::lower_expression(hir_ctx, file, &path, &expr) {
+            Ok(rhs) => rhs,
+            Err(errs) => {
+                diags.extend(errs.into_iter().map(crate::ast_lowering_error_to_diag));
+                continue;
+            }
+        };
+
+        // the items a value uses would have to be kept by the passes and the
+        // backends, even if nothing else uses them
+        let used = hir_ctx
+            .expressions
+            .iter()
+            .skip(first)
+            .filter_map(|(_, expr)| match expr {
+                Expression::Variable(name) | Expression::Call { name, .. } => Some(*name),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !used.is_empty() {
+            for name in used {
+                let loc = hir_ctx.identifier_fcs[&name];
+                diags.push(
+                    Diagnostic::error()
+                        .with_message("the value of an override can only use literals and types")
+                        .with_labels(vec![Label::primary(loc.file, loc.range()).with_message(
+                            format!("`{}` is used here", hir_ctx.identifiers[name]),
+                        )]),
+                );
+            }
+            continue;
+        }
+
+        hir_ctx.variable_defs[id].rhs = Some(rhs);
+    }
+
+    if diags.is_empty() {
+        Ok(())
+    } else {
+        Err(diags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "lighting::EXPOSURE = 1.5".parse(),
+            Ok(ConstantOverride {
+                name: "lighting::EXPOSURE".into(),
+                value: "1.5".into(),
+            })
+        );
+        assert_eq!(
+            "EXPOSURE".parse::<ConstantOverride>(),
+            Err("expected `NAME=VALUE`, found `EXPOSURE`".to_string())
+        );
+    }
+}
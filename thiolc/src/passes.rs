@@ -0,0 +1,1363 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Optimization passes over the HIR of a type checked module.
+//!
+//! The passes run after type checking and before the backends, in the order
+//! of the pipeline. They keep the tables of the type checker valid: an
+//! expression that is simplified keeps its id and its type, and expressions
+//! and statements a pass copies get the entries of the originals, see
+//! [`copy_expr`].
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use thiol_hir as hir;
+use thiol_typeck as ty;
+
+use hir::{
+    Expression, FileLocation, ForLoopType, Identifier, Literal, MatchPattern, ParamMode,
+    PrimitiveOp, Program, Statement, VariableDef,
+};
+use id_arena::Id;
+use ty::layout::buffer_class;
+use ty::{Callable, Symbol};
+
+/// The passes run when no pipeline is given
+pub(crate) const DEFAULT_PIPELINE: &[&str] = &["const-fold", "dce"];
+
+const PASSES: &[&str] = &["const-fold", "inline", "cse", "unroll", "dce"];
+
+/// The most iterations of a loop `unroll` replaces with copies of its body
+const UNROLL_LIMIT: usize = 8;
+
+fn pass(name: &str) -> Option<Box<dyn Pass>> {
+    match name {
+        "const-fold" => Some(Box::new(ConstFold)),
+        "inline" => Some(Box::new(Inline)),
+        "cse" => Some(Box::new(CommonSubexpressions)),
+        "unroll" => Some(Box::new(Unroll)),
+        "dce" => Some(Box::new(DeadCode)),
+        _ => None,
+    }
+}
+
+pub(crate) struct PassContext<'a> {
+    pub hir: &'a mut hir::Context,
+    pub ty: &'a mut ty::Context,
+    pub module: &'a mut hir::Module,
+}
+
+pub(crate) trait Pass {
+    fn name(&self) -> &'static str;
+
+    fn run(&self, cx: &mut PassContext<'_>);
+}
+
+/// Runs a pipeline of passes and records how long each of them took
+pub(crate) struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl PassManager {
+    /// A pipeline running the passes with the given names in order.
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let passes = names
+            .into_iter()
+            .map(|name| {
+                pass(name).ok_or_else(|| {
+                    format!(
+                        "unknown pass `{}`, the passes are {}",
+                        name,
+                        PASSES.join(", ")
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(PassManager {
+            passes,
+            timings: vec![],
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.passes.iter().any(|pass| pass.name() == name)
+    }
+
+    /// Run the pipeline, `after` is called with the name of every pass once
+    /// it has run.
+    pub fn run(&mut self, cx: &mut PassContext<'_>, mut after: impl FnMut(&str, &PassContext<'_>)) {
+        for pass in &self.passes {
+            let start = Instant::now();
+            pass.run(cx);
+            self.timings.push((pass.name(), start.elapsed()));
+            after(pass.name(), cx);
+        }
+    }
+
+    /// The time every pass took, in the order they ran.
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        &self.timings
+    }
+}
+
+/// The bodies of the functions and programs of a module.
+fn bodies(hir: &hir::Context, module: &hir::Module) -> Vec<Vec<Id<Statement>>> {
+    let functions = module.functions.iter().map(|id| &hir.functions[*id].body);
+    let programs = module.programs.iter().map(|id| &hir.programs[*id].body);
+    functions.chain(programs).cloned().collect()
+}
+
+/// Replaces arithmetic on literals with its result.
+struct ConstFold;
+
+impl Pass for ConstFold {
+    fn name(&self) -> &'static str {
+        "const-fold"
+    }
+
+    fn run(&self, cx: &mut PassContext<'_>) {
+        // operands come before the operations using them, so nested
+        // operations fold all the way up
+        let mut exprs = vec![];
+        for id in &cx.module.consts {
+            if let Some(rhs) = cx.hir.variable_defs[*id].rhs {
+                expression_tree(cx.hir, rhs, &mut exprs);
+            }
+        }
+        for body in bodies(cx.hir, cx.module) {
+            for stmt in body {
+                statement_expressions(cx.hir, stmt, &mut exprs);
+            }
+        }
+
+        for id in exprs {
+            if let Some(literal) = fold(cx.hir, cx.ty, id) {
+                cx.hir.expressions[id] = Expression::Literal(literal);
+            }
+        }
+    }
+}
+
+/// The value of a primitive operation of literals, if it is the same for
+/// every type the literals can have in their context. Integer literals may
+/// be `int` or `uint` values, so only results that both can hold without
+/// overflowing are folded, and float literals are only folded if the result
+/// is exact with the precision of `float`. Integer literals the type checker
+/// gave a float type are not folded.
+fn fold(hir: &hir::Context, types: &ty::Context, id: Id<Expression>) -> Option<Literal> {
+    use PrimitiveOp as PO;
+
+    let op = match &hir.expressions[id] {
+        Expression::PrimitiveOp(op) => &hir.prim_ops[*op],
+        _ => return None,
+    };
+    let literal = |e: &Id<Expression>| match hir.expressions[*e] {
+        Expression::Literal(Literal::Integer(_, _))
+            if !matches!(
+                types.expr_types.get(e).and_then(|t| types.types.get(*t)),
+                Some(ty::Type::Int) | Some(ty::Type::UInt) | None
+            ) =>
+        {
+            None
+        }
+        Expression::Literal(literal) => Some(literal),
+        _ => None,
+    };
+    let (a, b) = match op {
+        PO::Neg(a) | PO::Pos(a) => (literal(a)?, None),
+        PO::Add(a, b) | PO::Sub(a, b) | PO::Mul(a, b) | PO::Div(a, b) | PO::Mod(a, b) => {
+            (literal(a)?, Some(literal(b)?))
+        }
+        _ => return None,
+    };
+
+    match (a, b) {
+        (Literal::Integer(a, suffix), None) => match op {
+            PO::Pos(_) => Some(Literal::Integer(a, suffix)),
+            _ => None,
+        },
+        (Literal::Integer(a, suffix), Some(Literal::Integer(b, b_suffix)))
+            if suffix == b_suffix =>
+        {
+            let range = 0..=i128::from(i32::MAX);
+            if !range.contains(&a) || !range.contains(&b) {
+                return None;
+            }
+            let value = match op {
+                PO::Add(..) => a + b,
+                PO::Sub(..) => a - b,
+                PO::Mul(..) => a * b,
+                PO::Div(..) => a.checked_div(b)?,
+                PO::Mod(..) => a.checked_rem(b)?,
+                _ => return None,
+            };
+            Some(Literal::Integer(value, suffix)).filter(|_| range.contains(&value))
+        }
+        (Literal::Float(a, suffix), None) => match op {
+            PO::Neg(_) => Some(Literal::Float(-a, suffix)),
+            PO::Pos(_) => Some(Literal::Float(a, suffix)),
+            _ => None,
+        },
+        (Literal::Float(a, suffix), Some(Literal::Float(b, b_suffix))) if suffix == b_suffix => {
+            let value = match op {
+                PO::Add(..) => a + b,
+                PO::Sub(..) => a - b,
+                PO::Mul(..) => a * b,
+                PO::Div(..) => a / b,
+                _ => return None,
+            };
+            let single = match op {
+                PO::Add(..) => a as f32 + b as f32,
+                PO::Sub(..) => a as f32 - b as f32,
+                PO::Mul(..) => a as f32 * b as f32,
+                _ => a as f32 / b as f32,
+            };
+            let exact = value.is_finite() && value == f64::from(single);
+            Some(Literal::Float(value, suffix)).filter(|_| exact)
+        }
+        _ => None,
+    }
+}
+
+/// The expressions in a statement and the statements nested in it, every
+/// expression after the expressions it contains.
+fn statement_expressions(hir: &hir::Context, id: Id<Statement>, exprs: &mut Vec<Id<Expression>>) {
+    match &hir.statements[id] {
+        Statement::Var(def) => {
+            if let Some(rhs) = hir.variable_defs[*def].rhs {
+                expression_tree(hir, rhs, exprs);
+            }
+        }
+        Statement::Becomes { lhs, rhs } => {
+            expression_tree(hir, *lhs, exprs);
+            expression_tree(hir, *rhs, exprs);
+        }
+        Statement::Destructure { lhs, rhs } => {
+            for e in lhs {
+                expression_tree(hir, *e, exprs);
+            }
+            expression_tree(hir, *rhs, exprs);
+        }
+        Statement::Return(Some(e)) | Statement::Expr(e) => expression_tree(hir, *e, exprs),
+        Statement::Return(None) | Statement::Break | Statement::Continue | Statement::Discard => {}
+        Statement::If {
+            cond,
+            then_body,
+            else_body,
+        } => {
+            expression_tree(hir, *cond, exprs);
+            for stmt in then_body.iter().chain(else_body) {
+                statement_expressions(hir, *stmt, exprs);
+            }
+        }
+        Statement::Match { value, arms } => {
+            expression_tree(hir, *value, exprs);
+            for arm in arms {
+                for stmt in &arm.body {
+                    statement_expressions(hir, *stmt, exprs);
+                }
+            }
+        }
+        Statement::For { from, to, body, .. } => {
+            expression_tree(hir, *from, exprs);
+            expression_tree(hir, *to, exprs);
+            for stmt in body {
+                statement_expressions(hir, *stmt, exprs);
+            }
+        }
+    }
+}
+
+/// An expression and the expressions it contains, every expression after
+/// its operands.
+fn expression_tree(hir: &hir::Context, id: Id<Expression>, exprs: &mut Vec<Id<Expression>>) {
+    use PrimitiveOp as PO;
+
+    match &hir.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::LayoutQuery { .. } => {}
+        Expression::PrimitiveOp(op) => match &hir.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => expression_tree(hir, *e, exprs),
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => {
+                expression_tree(hir, *a, exprs);
+                expression_tree(hir, *b, exprs);
+            }
+            PO::Constructor {
+                pos_args, nam_args, ..
+            } => {
+                for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                    expression_tree(hir, *e, exprs);
+                }
+            }
+        },
+        Expression::Call {
+            pos_args, nam_args, ..
+        } => {
+            for e in pos_args.iter().chain(nam_args.iter().map(|(_, e)| e)) {
+                expression_tree(hir, *e, exprs);
+            }
+        }
+        Expression::Field { base, .. } | Expression::As { base, .. } => {
+            expression_tree(hir, *base, exprs)
+        }
+        Expression::Index { base, index } => {
+            expression_tree(hir, *base, exprs);
+            expression_tree(hir, *index, exprs);
+        }
+        Expression::Slice { base, lo, hi } => {
+            expression_tree(hir, *base, exprs);
+            expression_tree(hir, *lo, exprs);
+            expression_tree(hir, *hi, exprs);
+        }
+        Expression::Array(elements) => {
+            for e in elements {
+                expression_tree(hir, *e, exprs);
+            }
+        }
+    }
+    exprs.push(id);
+}
+
+/// The symbol a use of a name refers to.
+fn symbol(hir: &hir::Context, ty: &ty::Context, name: Id<Identifier>) -> Option<Symbol> {
+    ty.references.symbol(*hir.identifier_fcs.get(&name)?)
+}
+
+/// Copy an expression and the expressions it contains. The copies get the
+/// spans of the originals and their entries in the tables of the type
+/// checker. Variables whose symbol has a value in `values` are replaced with
+/// a copy of the value.
+fn copy_expr(
+    cx: &mut PassContext<'_>,
+    id: Id<Expression>,
+    values: &HashMap<Symbol, Id<Expression>>,
+) -> Id<Expression> {
+    use PrimitiveOp as PO;
+
+    let expr = match cx.hir.expressions[id].clone() {
+        Expression::Variable(name) => {
+            let value = symbol(cx.hir, cx.ty, name).and_then(|sym| values.get(&sym));
+            if let Some(value) = value {
+                return copy_expr(cx, *value, &HashMap::new());
+            }
+            Expression::Variable(name)
+        }
+        expr @ (Expression::Literal(_) | Expression::LayoutQuery { .. }) => expr,
+        Expression::PrimitiveOp(op) => {
+            let copy = match cx.hir.prim_ops[op].clone() {
+                PO::Neg(e) => PO::Neg(copy_expr(cx, e, values)),
+                PO::Pos(e) => PO::Pos(copy_expr(cx, e, values)),
+                PO::Add(a, b) => PO::Add(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Sub(a, b) => PO::Sub(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Mul(a, b) => PO::Mul(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Div(a, b) => PO::Div(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Mod(a, b) => PO::Mod(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Gt(a, b) => PO::Gt(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Gte(a, b) => PO::Gte(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Lt(a, b) => PO::Lt(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Lte(a, b) => PO::Lte(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Eq(a, b) => PO::Eq(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Neq(a, b) => PO::Neq(copy_expr(cx, a, values), copy_expr(cx, b, values)),
+                PO::Constructor {
+                    ty,
+                    pos_args,
+                    nam_args,
+                } => PO::Constructor {
+                    ty,
+                    pos_args: copy_exprs(cx, &pos_args, values),
+                    nam_args: nam_args
+                        .into_iter()
+                        .map(|(name, e)| (name, copy_expr(cx, e, values)))
+                        .collect(),
+                },
+            };
+            let loc = cx.hir.prim_op_fcs.get(&op).copied();
+            let copy = cx.hir.prim_ops.alloc(copy);
+            if let Some(loc) = loc {
+                cx.hir.prim_op_fcs.insert(copy, loc);
+            }
+            Expression::PrimitiveOp(copy)
+        }
+        Expression::Call {
+            name,
+            pos_args,
+            nam_args,
+        } => Expression::Call {
+            name,
+            pos_args: copy_exprs(cx, &pos_args, values),
+            nam_args: nam_args
+                .into_iter()
+                .map(|(name, e)| (name, copy_expr(cx, e, values)))
+                .collect(),
+        },
+        Expression::Field { base, name } => Expression::Field {
+            base: copy_expr(cx, base, values),
+            name,
+        },
+        Expression::Index { base, index } => Expression::Index {
+            base: copy_expr(cx, base, values),
+            index: copy_expr(cx, index, values),
+        },
+        Expression::Slice { base, lo, hi } => Expression::Slice {
+            base: copy_expr(cx, base, values),
+            lo: copy_expr(cx, lo, values),
+            hi: copy_expr(cx, hi, values),
+        },
+        Expression::As { base, ty } => Expression::As {
+            base: copy_expr(cx, base, values),
+            ty,
+        },
+        Expression::Array(elements) => Expression::Array(copy_exprs(cx, &elements, values)),
+    };
+    let copy = cx.hir.expressions.alloc(expr);
+    copy_entries(cx, id, copy);
+    copy
+}
+
+fn copy_exprs(
+    cx: &mut PassContext<'_>,
+    ids: &[Id<Expression>],
+    values: &HashMap<Symbol, Id<Expression>>,
+) -> Vec<Id<Expression>> {
+    ids.iter().map(|id| copy_expr(cx, *id, values)).collect()
+}
+
+/// Give an expression the spans and the entries in the tables of the type
+/// checker of another one.
+fn copy_entries(cx: &mut PassContext<'_>, from: Id<Expression>, to: Id<Expression>) {
+    let hir = &mut *cx.hir;
+    for fcs in [
+        &mut hir.expression_fcs,
+        &mut hir.parenthesized_fcs,
+        &mut hir.argument_list_fcs,
+    ] {
+        if let Some(loc) = fcs.get(&from).copied() {
+            fcs.insert(to, loc);
+        }
+    }
+
+    let ty = &mut *cx.ty;
+    for types in [&mut ty.expr_types, &mut ty.layout_types] {
+        if let Some(t) = types.get(&from).copied() {
+            types.insert(to, t);
+        }
+    }
+    if let Some(generics) = ty.call_generics.get(&from).cloned() {
+        ty.call_generics.insert(to, generics);
+    }
+    if let Some(intrinsic) = ty.call_intrinsics.get(&from).copied() {
+        ty.call_intrinsics.insert(to, intrinsic);
+    }
+    if let Some(transform) = ty.space_transforms.get(&from).cloned() {
+        ty.space_transforms.insert(to, transform);
+    }
+}
+
+/// Copy a statement that doesn't declare anything, see [`copyable`], and
+/// the statements nested in it.
+fn copy_statement(
+    cx: &mut PassContext<'_>,
+    id: Id<Statement>,
+    values: &HashMap<Symbol, Id<Expression>>,
+) -> Id<Statement> {
+    let stmt = match cx.hir.statements[id].clone() {
+        Statement::Becomes { lhs, rhs } => Statement::Becomes {
+            lhs: copy_expr(cx, lhs, values),
+            rhs: copy_expr(cx, rhs, values),
+        },
+        Statement::Destructure { lhs, rhs } => Statement::Destructure {
+            lhs: copy_exprs(cx, &lhs, values),
+            rhs: copy_expr(cx, rhs, values),
+        },
+        Statement::Return(e) => Statement::Return(e.map(|e| copy_expr(cx, e, values))),
+        Statement::Expr(e) => Statement::Expr(copy_expr(cx, e, values)),
+        Statement::If {
+            cond,
+            then_body,
+            else_body,
+        } => Statement::If {
+            cond: copy_expr(cx, cond, values),
+            then_body: then_body
+                .iter()
+                .map(|stmt| copy_statement(cx, *stmt, values))
+                .collect(),
+            else_body: else_body
+                .iter()
+                .map(|stmt| copy_statement(cx, *stmt, values))
+                .collect(),
+        },
+        // declarations are never copied, they would be declared twice
+        stmt => stmt,
+    };
+    let loc = cx.hir.statement_fcs.get(&id).copied();
+    let copy = cx.hir.statements.alloc(stmt);
+    if let Some(loc) = loc {
+        cx.hir.statement_fcs.insert(copy, loc);
+    }
+    copy
+}
+
+/// Whether the statements can be copied into the same block more than once:
+/// they don't declare variables, loops or `match` bindings, and don't
+/// `break` or `continue` a loop.
+fn copyable(hir: &hir::Context, body: &[Id<Statement>]) -> bool {
+    body.iter().all(|stmt| match &hir.statements[*stmt] {
+        Statement::Becomes { .. }
+        | Statement::Destructure { .. }
+        | Statement::Return(_)
+        | Statement::Expr(_)
+        | Statement::Discard => true,
+        Statement::If {
+            then_body,
+            else_body,
+            ..
+        } => copyable(hir, then_body) && copyable(hir, else_body),
+        Statement::Var(_)
+        | Statement::For { .. }
+        | Statement::Match { .. }
+        | Statement::Break
+        | Statement::Continue => false,
+    })
+}
+
+/// Replaces calls of functions whose body is a single `return` with the
+/// returned expression, where the parameters are the arguments. Only calls
+/// whose arguments are literals, variables or their fields are inlined, so
+/// that no argument is computed more or less often than before. The
+/// functions stay in the module, `dce` removes them once no program calls
+/// them.
+struct Inline;
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&self, cx: &mut PassContext<'_>) {
+        let mut exprs = vec![];
+        for body in bodies(cx.hir, cx.module) {
+            for stmt in body {
+                statement_expressions(cx.hir, stmt, &mut exprs);
+            }
+        }
+        for id in exprs {
+            inline_tree(cx, id);
+        }
+        // `dce` finds the functions that are still called in the call graph
+        cx.ty.call_graph = ty::graphs::call_graph(cx.hir, cx.module);
+    }
+}
+
+/// Inline the call, if it can be inlined, and then the calls in the
+/// inlined expression.
+fn inline_tree(cx: &mut PassContext<'_>, id: Id<Expression>) {
+    let (func, value) = match inlinable(cx, id) {
+        Some(inlinable) => inlinable,
+        None => return,
+    };
+    let args = match &cx.hir.expressions[id] {
+        Expression::Call { pos_args, .. } => pos_args.clone(),
+        _ => return,
+    };
+    let values = args
+        .into_iter()
+        .enumerate()
+        .map(|(index, arg)| (Symbol::Parameter { func, index }, arg))
+        .collect();
+    let copy = copy_expr(cx, value, &values);
+
+    // the call keeps its id and its type and becomes the returned expression
+    cx.hir.expressions[id] = cx.hir.expressions[copy].clone();
+    cx.ty.call_intrinsics.remove(&id);
+    copy_entries(cx, copy, id);
+
+    let mut inner = vec![];
+    expression_tree(cx.hir, id, &mut inner);
+    for e in inner {
+        inline_tree(cx, e);
+    }
+}
+
+/// The function a call calls and the expression its body returns, if the
+/// call can be inlined.
+fn inlinable(
+    cx: &PassContext<'_>,
+    id: Id<Expression>,
+) -> Option<(Id<hir::Function>, Id<Expression>)> {
+    let (name, pos_args, nam_args) = match &cx.hir.expressions[id] {
+        Expression::Call {
+            name,
+            pos_args,
+            nam_args,
+        } => (*name, pos_args, nam_args),
+        _ => return None,
+    };
+    if !nam_args.is_empty()
+        || cx.ty.call_intrinsics.contains_key(&id)
+        || cx.ty.call_generics.contains_key(&id)
+    {
+        return None;
+    }
+    let func = match symbol(cx.hir, cx.ty, name)? {
+        Symbol::Function(func) => func,
+        _ => return None,
+    };
+
+    // functions with a table, a profile label or a float mode of their own
+    // keep their calls
+    let function = &cx.hir.functions[func];
+    let callable = Callable::Function(func);
+    if !function.generics.is_empty()
+        || function.args.len() != pos_args.len()
+        || function
+            .args
+            .iter()
+            .any(|(_, _, mode)| *mode != ParamMode::In)
+        || cx.ty.lookup_tables.contains_key(&func)
+        || cx.ty.profile_labels.contains_key(&callable)
+        || cx.ty.float_modes.contains_key(&callable)
+    {
+        return None;
+    }
+    let value = match function.body.as_slice() {
+        [stmt] => match cx.hir.statements[*stmt] {
+            Statement::Return(Some(value)) => value,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if cx.ty.space_transforms.contains_key(&value)
+        || !cx.ty.expr_types.contains_key(&value)
+        || cx.ty.expr_types.get(&value) != cx.ty.expr_types.get(&id)
+    {
+        return None;
+    }
+
+    // arguments that need a conversion to the type of their parameter are
+    // left to the call
+    let args_fit = pos_args.iter().enumerate().all(|(index, arg)| {
+        let param = cx
+            .ty
+            .references
+            .symbol_type(Symbol::Parameter { func, index });
+        simple(cx.hir, *arg) && param.is_some() && cx.ty.expr_types.get(arg) == param.as_ref()
+    });
+    Some((func, value)).filter(|_| args_fit)
+}
+
+/// Whether an expression is a literal, a variable or a field of one.
+fn simple(hir: &hir::Context, id: Id<Expression>) -> bool {
+    match &hir.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) => true,
+        Expression::Field { base, .. } => simple(hir, *base),
+        _ => false,
+    }
+}
+
+/// Replaces expressions that compute the value a variable was declared
+/// with by the variable, as long as neither the variable nor the variables
+/// the value is computed from are assigned in between. Calls without
+/// effects are reused like operators, their value only depends on their
+/// arguments. Values with other calls are left alone, and every such call
+/// forgets the values of the variables.
+struct CommonSubexpressions;
+
+/// A variable and the value it was declared with
+#[derive(Clone)]
+struct Available {
+    var: Id<VariableDef>,
+    value: Id<Expression>,
+    /// the symbols the value is computed from
+    uses: Vec<Symbol>,
+}
+
+impl Pass for CommonSubexpressions {
+    fn name(&self) -> &'static str {
+        "cse"
+    }
+
+    fn run(&self, cx: &mut PassContext<'_>) {
+        for body in bodies(cx.hir, cx.module) {
+            cse_block(cx, &body, &mut vec![]);
+        }
+    }
+}
+
+fn cse_block(cx: &mut PassContext<'_>, body: &[Id<Statement>], available: &mut Vec<Available>) {
+    for stmt in body {
+        match cx.hir.statements[*stmt].clone() {
+            Statement::Var(var) => {
+                let def = &cx.hir.variable_defs[var];
+                let (name, rhs) = (def.name, def.rhs);
+                if let Some(rhs) = rhs {
+                    replace_available(cx, rhs, available);
+                    forget_after(cx, rhs, available);
+                }
+                // the variables it shadows can't be named anymore
+                forget_name(cx.hir, name, available);
+                if let Some(rhs) = rhs.filter(|rhs| reusable(cx, var, *rhs)) {
+                    let mut uses = vec![];
+                    symbols(cx, rhs, &mut uses);
+                    available.push(Available {
+                        var,
+                        value: rhs,
+                        uses,
+                    });
+                }
+            }
+            Statement::Becomes { lhs, rhs } => {
+                replace_available(cx, rhs, available);
+                forget_after(cx, rhs, available);
+                forget_assigned(cx, lhs, available);
+            }
+            Statement::Destructure { lhs, rhs } => {
+                replace_available(cx, rhs, available);
+                forget_after(cx, rhs, available);
+                for lhs in lhs {
+                    forget_assigned(cx, lhs, available);
+                }
+            }
+            Statement::Return(Some(e)) | Statement::Expr(e) => {
+                replace_available(cx, e, available);
+                forget_after(cx, e, available);
+            }
+            Statement::Return(None)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Discard => {}
+            Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                replace_available(cx, cond, available);
+                forget_after(cx, cond, available);
+                cse_block(cx, &then_body, &mut available.clone());
+                cse_block(cx, &else_body, &mut available.clone());
+                forget_in(cx, *stmt, available);
+            }
+            Statement::Match { value, arms } => {
+                replace_available(cx, value, available);
+                forget_after(cx, value, available);
+                for arm in arms {
+                    let mut inner = available.clone();
+                    if let MatchPattern::Some(name) = arm.pattern {
+                        forget_name(cx.hir, name, &mut inner);
+                    }
+                    cse_block(cx, &arm.body, &mut inner);
+                }
+                forget_in(cx, *stmt, available);
+            }
+            Statement::For {
+                iter_name,
+                from,
+                to,
+                body,
+                ..
+            } => {
+                replace_available(cx, from, available);
+                replace_available(cx, to, available);
+                forget_after(cx, from, available);
+                forget_after(cx, to, available);
+                // the body may run again after it assigned a variable
+                forget_in(cx, *stmt, available);
+                let mut inner = available.clone();
+                forget_name(cx.hir, iter_name, &mut inner);
+                cse_block(cx, &body, &mut inner);
+            }
+        }
+    }
+}
+
+/// Whether the value a variable is declared with can replace expressions
+/// computing the same value.
+fn reusable(cx: &PassContext<'_>, var: Id<VariableDef>, value: Id<Expression>) -> bool {
+    let computed = matches!(
+        cx.hir.expressions[value],
+        Expression::PrimitiveOp(_)
+            | Expression::Index { .. }
+            | Expression::As { .. }
+            | Expression::Call { .. }
+    );
+    let ty = cx.ty.references.symbol_type(Symbol::Local(var));
+    computed
+        && ty.is_some()
+        && ty.as_ref() == cx.ty.expr_types.get(&value)
+        && !cx.ty.relaxed_precision.contains(&var)
+        && pure_calls(cx, value)
+}
+
+/// Replace the expressions computing the value of an available variable,
+/// the outermost ones first, with the variable.
+fn replace_available(cx: &mut PassContext<'_>, id: Id<Expression>, available: &[Available]) {
+    let found = available
+        .iter()
+        .find(|a| same_value(cx, a.value, id))
+        .map(|a| cx.hir.variable_defs[a.var].name);
+    if let Some(name) = found {
+        cx.hir.expressions[id] = Expression::Variable(name);
+        return;
+    }
+
+    for e in operands_of(cx.hir, id) {
+        replace_available(cx, e, available);
+    }
+}
+
+/// The expressions an expression is computed from directly.
+fn operands_of(hir: &hir::Context, id: Id<Expression>) -> Vec<Id<Expression>> {
+    use PrimitiveOp as PO;
+
+    match &hir.expressions[id] {
+        Expression::Literal(_) | Expression::Variable(_) | Expression::LayoutQuery { .. } => vec![],
+        Expression::PrimitiveOp(op) => match &hir.prim_ops[*op] {
+            PO::Neg(e) | PO::Pos(e) => vec![*e],
+            PO::Add(a, b)
+            | PO::Sub(a, b)
+            | PO::Mul(a, b)
+            | PO::Div(a, b)
+            | PO::Mod(a, b)
+            | PO::Gt(a, b)
+            | PO::Gte(a, b)
+            | PO::Lt(a, b)
+            | PO::Lte(a, b)
+            | PO::Eq(a, b)
+            | PO::Neq(a, b) => vec![*a, *b],
+            PO::Constructor {
+                pos_args, nam_args, ..
+            } => pos_args
+                .iter()
+                .chain(nam_args.iter().map(|(_, e)| e))
+                .copied()
+                .collect(),
+        },
+        Expression::Call {
+            pos_args, nam_args, ..
+        } => pos_args
+            .iter()
+            .chain(nam_args.iter().map(|(_, e)| e))
+            .copied()
+            .collect(),
+        Expression::Field { base, .. } | Expression::As { base, .. } => vec![*base],
+        Expression::Index { base, index } => vec![*base, *index],
+        Expression::Slice { base, lo, hi } => vec![*base, *lo, *hi],
+        Expression::Array(elements) => elements.clone(),
+    }
+}
+
+/// Whether two expressions compute the same value of the same type.
+fn same_value(cx: &PassContext<'_>, a: Id<Expression>, b: Id<Expression>) -> bool {
+    use PrimitiveOp as PO;
+
+    let ty = cx.ty.expr_types.get(&a);
+    if a == b
+        || ty.is_none()
+        || ty != cx.ty.expr_types.get(&b)
+        || cx.ty.space_transforms.contains_key(&a)
+        || cx.ty.space_transforms.contains_key(&b)
+    {
+        return false;
+    }
+    let same = |x: &Id<Expression>, y: &Id<Expression>| same_value(cx, *x, *y);
+    match (&cx.hir.expressions[a], &cx.hir.expressions[b]) {
+        (Expression::Literal(x), Expression::Literal(y)) => match (x, y) {
+            (Literal::Integer(x, s), Literal::Integer(y, t)) => x == y && s == t,
+            (Literal::Float(x, s), Literal::Float(y, t)) => x.to_bits() == y.to_bits() && s == t,
+            (Literal::Bool(x), Literal::Bool(y)) => x == y,
+            _ => false,
+        },
+        (Expression::Variable(x), Expression::Variable(y)) => {
+            let x = symbol(cx.hir, cx.ty, *x);
+            x.is_some() && x == symbol(cx.hir, cx.ty, *y)
+        }
+        (Expression::PrimitiveOp(x), Expression::PrimitiveOp(y)) => {
+            match (&cx.hir.prim_ops[*x], &cx.hir.prim_ops[*y]) {
+                (PO::Neg(x), PO::Neg(y)) | (PO::Pos(x), PO::Pos(y)) => same(x, y),
+                (PO::Add(a, b), PO::Add(c, d))
+                | (PO::Sub(a, b), PO::Sub(c, d))
+                | (PO::Mul(a, b), PO::Mul(c, d))
+                | (PO::Div(a, b), PO::Div(c, d))
+                | (PO::Mod(a, b), PO::Mod(c, d))
+                | (PO::Gt(a, b), PO::Gt(c, d))
+                | (PO::Gte(a, b), PO::Gte(c, d))
+                | (PO::Lt(a, b), PO::Lt(c, d))
+                | (PO::Lte(a, b), PO::Lte(c, d))
+                | (PO::Eq(a, b), PO::Eq(c, d))
+                | (PO::Neq(a, b), PO::Neq(c, d)) => same(a, c) && same(b, d),
+                _ => false,
+            }
+        }
+        (
+            Expression::Field { base, name },
+            Expression::Field {
+                base: other,
+                name: other_name,
+            },
+        ) => cx.hir.identifiers[*name] == cx.hir.identifiers[*other_name] && same(base, other),
+        (
+            Expression::Index { base, index },
+            Expression::Index {
+                base: other,
+                index: other_index,
+            },
+        ) => same(base, other) && same(index, other_index),
+        (Expression::As { base, .. }, Expression::As { base: other, .. }) => same(base, other),
+        (
+            Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            },
+            Expression::Call {
+                name: other,
+                pos_args: other_pos,
+                nam_args: other_nam,
+            },
+        ) => {
+            let callee = match (cx.ty.call_intrinsics.get(&a), cx.ty.call_intrinsics.get(&b)) {
+                (Some(x), Some(y)) => x == y,
+                (None, None) => {
+                    let x = symbol(cx.hir, cx.ty, *name);
+                    x.is_some() && x == symbol(cx.hir, cx.ty, *other)
+                }
+                _ => false,
+            };
+            callee
+                && pos_args.len() == other_pos.len()
+                && pos_args.iter().zip(other_pos).all(|(x, y)| same(x, y))
+                && nam_args.len() == other_nam.len()
+                && nam_args.iter().zip(other_nam).all(|((x, e), (y, f))| {
+                    cx.hir.identifiers[*x] == cx.hir.identifiers[*y] && same(e, f)
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Whether all calls of an expression are calls without effects.
+fn pure_calls(cx: &PassContext<'_>, id: Id<Expression>) -> bool {
+    let mut exprs = vec![];
+    expression_tree(cx.hir, id, &mut exprs);
+    exprs.iter().all(|e| match cx.hir.expressions[*e] {
+        Expression::Call { .. } => {
+            matches!(cx.ty.call_effects(cx.hir, *e), Some(effects) if effects.is_pure())
+        }
+        _ => true,
+    })
+}
+
+/// The symbols of the variables an expression uses.
+fn symbols(cx: &PassContext<'_>, id: Id<Expression>, uses: &mut Vec<Symbol>) {
+    let mut exprs = vec![];
+    expression_tree(cx.hir, id, &mut exprs);
+    for e in exprs {
+        if let Expression::Variable(name) = cx.hir.expressions[e] {
+            uses.extend(symbol(cx.hir, cx.ty, name));
+        }
+    }
+}
+
+/// Forget every variable once an expression with a call with effects was
+/// computed.
+fn forget_after(cx: &PassContext<'_>, id: Id<Expression>, available: &mut Vec<Available>) {
+    if !pure_calls(cx, id) {
+        available.clear();
+    }
+}
+
+/// Forget the variables that are assigned or depend on a variable that is
+/// assigned by an assignment to `lhs`.
+fn forget_assigned(cx: &PassContext<'_>, lhs: Id<Expression>, available: &mut Vec<Available>) {
+    let mut target = lhs;
+    let sym = loop {
+        match &cx.hir.expressions[target] {
+            Expression::Variable(name) => break symbol(cx.hir, cx.ty, *name),
+            Expression::Field { base, .. }
+            | Expression::Index { base, .. }
+            | Expression::Slice { base, .. } => target = *base,
+            _ => break None,
+        }
+    };
+    match sym {
+        Some(sym) => available.retain(|a| Symbol::Local(a.var) != sym && !a.uses.contains(&sym)),
+        None => available.clear(),
+    }
+    forget_after(cx, lhs, available);
+}
+
+/// Forget the variables a statement and the statements in it may change.
+fn forget_in(cx: &PassContext<'_>, id: Id<Statement>, available: &mut Vec<Available>) {
+    let mut exprs = vec![];
+    statement_expressions(cx.hir, id, &mut exprs);
+    if exprs
+        .iter()
+        .any(|e| matches!(cx.hir.expressions[*e], Expression::Call { .. }))
+    {
+        available.clear();
+        return;
+    }
+    forget_assignments(cx, id, available);
+}
+
+fn forget_assignments(cx: &PassContext<'_>, id: Id<Statement>, available: &mut Vec<Available>) {
+    match &cx.hir.statements[id] {
+        Statement::Becomes { lhs, .. } => forget_assigned(cx, *lhs, available),
+        Statement::Destructure { lhs, .. } => {
+            for lhs in lhs {
+                forget_assigned(cx, *lhs, available);
+            }
+        }
+        Statement::If {
+            then_body,
+            else_body,
+            ..
+        } => {
+            for stmt in then_body.iter().chain(else_body) {
+                forget_assignments(cx, *stmt, available);
+            }
+        }
+        Statement::Match { arms, .. } => {
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                forget_assignments(cx, *stmt, available);
+            }
+        }
+        Statement::For { body, .. } => {
+            for stmt in body {
+                forget_assignments(cx, *stmt, available);
+            }
+        }
+        Statement::Var(_)
+        | Statement::Return(_)
+        | Statement::Expr(_)
+        | Statement::Break
+        | Statement::Continue
+        | Statement::Discard => {}
+    }
+}
+
+/// Forget the variables with the name, which a declaration shadows.
+fn forget_name(hir: &hir::Context, name: Id<Identifier>, available: &mut Vec<Available>) {
+    let name = &hir.identifiers[name];
+    available.retain(|a| hir.identifiers[hir.variable_defs[a.var].name] != *name);
+}
+
+/// Replaces `for` loops with literal bounds and at most [`UNROLL_LIMIT`]
+/// iterations with a copy of their body for every iteration, where the loop
+/// variable is a literal. Loops whose body can't be copied, see
+/// [`copyable`], are kept. Loops nested in a loop are unrolled first.
+struct Unroll;
+
+impl Pass for Unroll {
+    fn name(&self) -> &'static str {
+        "unroll"
+    }
+
+    fn run(&self, cx: &mut PassContext<'_>) {
+        for id in cx.module.functions.clone() {
+            let body = std::mem::take(&mut cx.hir.functions[id].body);
+            cx.hir.functions[id].body = unroll_block(cx, body);
+        }
+        for id in cx.module.programs.clone() {
+            let body = std::mem::take(&mut cx.hir.programs[id].body);
+            cx.hir.programs[id].body = unroll_block(cx, body);
+        }
+    }
+}
+
+fn unroll_block(cx: &mut PassContext<'_>, body: Vec<Id<Statement>>) -> Vec<Id<Statement>> {
+    let mut unrolled = vec![];
+    for stmt in body {
+        match &mut cx.hir.statements[stmt] {
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                let (then_body, else_body) = (std::mem::take(then_body), std::mem::take(else_body));
+                let then_body = unroll_block(cx, then_body);
+                let else_body = unroll_block(cx, else_body);
+                if let Statement::If {
+                    then_body: then_unrolled,
+                    else_body: else_unrolled,
+                    ..
+                } = &mut cx.hir.statements[stmt]
+                {
+                    *then_unrolled = then_body;
+                    *else_unrolled = else_body;
+                }
+            }
+            Statement::Match { arms, .. } => {
+                let mut arms = std::mem::take(arms);
+                for arm in &mut arms {
+                    arm.body = unroll_block(cx, std::mem::take(&mut arm.body));
+                }
+                if let Statement::Match { arms: unrolled, .. } = &mut cx.hir.statements[stmt] {
+                    *unrolled = arms;
+                }
+            }
+            Statement::For { body, .. } => {
+                let body = std::mem::take(body);
+                let body = unroll_block(cx, body);
+                if let Statement::For { body: unrolled, .. } = &mut cx.hir.statements[stmt] {
+                    *unrolled = body;
+                }
+            }
+            _ => {}
+        }
+
+        match iterations(cx, stmt) {
+            Some(values) => {
+                let body = match &cx.hir.statements[stmt] {
+                    Statement::For { body, .. } => body.clone(),
+                    _ => unreachable!("only loops have iterations"),
+                };
+                for value in values {
+                    let values = std::iter::once((Symbol::LoopVariable(stmt), value)).collect();
+                    for inner in &body {
+                        unrolled.push(copy_statement(cx, *inner, &values));
+                    }
+                }
+            }
+            None => unrolled.push(stmt),
+        }
+    }
+    unrolled
+}
+
+/// The literals the variable of a loop that can be unrolled has in its
+/// iterations.
+fn iterations(cx: &mut PassContext<'_>, id: Id<Statement>) -> Option<Vec<Id<Expression>>> {
+    let (loop_type, from, to, body) = match &cx.hir.statements[id] {
+        Statement::For {
+            loop_type,
+            from,
+            to,
+            body,
+            ..
+        } => (loop_type, *from, *to, body),
+        _ => return None,
+    };
+    let bound = |e: Id<Expression>| match cx.hir.expressions[e] {
+        Expression::Literal(Literal::Integer(value, _)) => Some(value),
+        _ => None,
+    };
+    let (lo, hi) = match loop_type {
+        ForLoopType::Up => (bound(from)?, bound(to)?),
+        ForLoopType::Down => (bound(to)?, bound(from)?),
+    };
+    let count = usize::try_from((hi - lo + 1).max(0)).ok()?;
+    if count > UNROLL_LIMIT || !copyable(cx.hir, body) {
+        return None;
+    }
+    let mut values = (lo..=hi).collect::<Vec<_>>();
+    if matches!(loop_type, ForLoopType::Down) {
+        values.reverse();
+    }
+
+    let ty = cx.ty.references.symbol_type(Symbol::LoopVariable(id))?;
+    let loc = cx.hir.expression_fcs.get(&from).copied();
+    let values = values
+        .into_iter()
+        .map(|value| {
+            let literal = cx
+                .hir
+                .expressions
+                .alloc(Expression::Literal(Literal::Integer(value, None)));
+            cx.ty.expr_types.insert(literal, ty);
+            if let Some(loc) = loc {
+                cx.hir.expression_fcs.insert(literal, loc);
+            }
+            literal
+        })
+        .collect();
+    Some(values)
+}
+
+/// Removes the statements after a `return`, `break` or `continue`, and the
+/// functions and constants the programs of the module don't use. Modules
+/// without programs keep all their functions and constants. Buffers are
+/// kept, they are part of the interface of the programs.
+struct DeadCode;
+
+impl Pass for DeadCode {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&self, cx: &mut PassContext<'_>) {
+        for id in cx.module.functions.clone() {
+            let body = std::mem::take(&mut cx.hir.functions[id].body);
+            cx.hir.functions[id].body = strip_block(cx.hir, body);
+        }
+        for id in cx.module.programs.clone() {
+            let body = std::mem::take(&mut cx.hir.programs[id].body);
+            cx.hir.programs[id].body = strip_block(cx.hir, body);
+        }
+
+        if cx.module.programs.is_empty() {
+            return;
+        }
+
+        let programs = cx.module.programs.clone();
+        let (used, kept) = reachable(cx.hir, cx.ty, cx.module, &programs, true);
+        cx.module
+            .functions
+            .retain(|id| used.contains(&Callable::Function(*id)));
+        cx.module.consts.retain(|id| kept.contains(id));
+    }
+}
+
+/// The functions and programs the given programs call, directly or
+/// indirectly, and the constants they use. Buffers are always used if
+/// `keep_buffers` is set, as they are part of the interface of the programs.
+pub(crate) fn reachable(
+    hir: &hir::Context,
+    ty: &ty::Context,
+    module: &hir::Module,
+    programs: &[Id<Program>],
+    keep_buffers: bool,
+) -> (Vec<Callable>, BTreeSet<Id<VariableDef>>) {
+    let mut used = programs
+        .iter()
+        .map(|id| Callable::Program(*id))
+        .collect::<Vec<_>>();
+    let mut kept = module
+        .consts
+        .iter()
+        .copied()
+        .filter(|id| keep_buffers && buffer_class(hir, *id).is_some())
+        .collect::<BTreeSet<_>>();
+
+    // the functions called by kept constants and the constants used by
+    // kept functions are kept as well
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut i = 0;
+        while i < used.len() {
+            for (callee, _) in ty.call_graph.dependencies(used[i]) {
+                if !used.contains(&callee) {
+                    used.push(callee);
+                }
+            }
+            i += 1;
+        }
+
+        let spans = used
+            .iter()
+            .map(|callable| match callable {
+                Callable::Function(id) => hir.function_fcs[id],
+                Callable::Program(id) => hir.program_fcs[id],
+            })
+            .chain(kept.iter().map(|id| hir.variable_def_fcs[id]))
+            .collect::<Vec<_>>();
+        for id in &module.consts {
+            let in_spans = |loc: FileLocation| spans.iter().any(|span| contains(*span, loc));
+            let name = &hir.identifiers[hir.variable_defs[*id].name];
+            // transforms between spaces use the constants without a
+            // reference in the source
+            let transformed = ty.space_transforms.iter().any(|(e, transform)| {
+                transform.steps.iter().any(|step| step.via == *name)
+                    && in_spans(hir.expression_fcs[e])
+            });
+            let referenced = transformed
+                || ty
+                    .references
+                    .references(Symbol::Constant(*id))
+                    .iter()
+                    .any(|loc| in_spans(*loc));
+            if !referenced || !kept.insert(*id) {
+                continue;
+            }
+            changed = true;
+
+            let mut exprs = vec![];
+            if let Some(rhs) = hir.variable_defs[*id].rhs {
+                expression_tree(hir, rhs, &mut exprs);
+            }
+            for e in exprs {
+                let name = match &hir.expressions[e] {
+                    Expression::Call { name, .. } => &hir.identifiers[*name],
+                    _ => continue,
+                };
+                if let Some(sig) = ty.function_sigs.get(name) {
+                    used.push(Callable::Function(sig.func_id));
+                }
+            }
+        }
+    }
+    (used, kept)
+}
+
+pub(crate) fn contains(outer: FileLocation, inner: FileLocation) -> bool {
+    outer.file == inner.file && outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// A block without the statements that can't be reached, the nested blocks
+/// are stripped as well.
+fn strip_block(hir: &mut hir::Context, mut body: Vec<Id<Statement>>) -> Vec<Id<Statement>> {
+    let end = body.iter().position(|stmt| {
+        matches!(
+            hir.statements[*stmt],
+            Statement::Return(_) | Statement::Break | Statement::Continue | Statement::Discard
+        )
+    });
+    if let Some(end) = end {
+        body.truncate(end + 1);
+    }
+
+    for stmt in &body {
+        match &mut hir.statements[*stmt] {
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => {
+                let then_stripped = std::mem::take(then_body);
+                let else_stripped = std::mem::take(else_body);
+                let then_stripped = strip_block(hir, then_stripped);
+                let else_stripped = strip_block(hir, else_stripped);
+                if let Statement::If {
+                    then_body,
+                    else_body,
+                    ..
+                } = &mut hir.statements[*stmt]
+                {
+                    *then_body = then_stripped;
+                    *else_body = else_stripped;
+                }
+            }
+            Statement::Match { arms, .. } => {
+                let mut stripped = std::mem::take(arms);
+                for arm in &mut stripped {
+                    arm.body = strip_block(hir, std::mem::take(&mut arm.body));
+                }
+                if let Statement::Match { arms, .. } = &mut hir.statements[*stmt] {
+                    *arms = stripped;
+                }
+            }
+            Statement::For { body, .. } => {
+                let stripped = std::mem::take(body);
+                let stripped = strip_block(hir, stripped);
+                if let Statement::For { body, .. } = &mut hir.statements[*stmt] {
+                    *body = stripped;
+                }
+            }
+            _ => {}
+        }
+    }
+    body
+}
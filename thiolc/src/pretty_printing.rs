@@ -2,6 +2,7 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
+use id_arena::Id;
 use pretty::RcDoc as Doc;
 use thiol_hir as hir;
 use thiol_typeck as ty;
@@ -23,21 +24,713 @@ pub(crate) fn dump_type_context<'a>(
     String::from_utf8_lossy(&v).to_string()
 }
 
+pub(crate) fn dump_vertex_formats(
+    hir: &thiol_hir::Context,
+    ctx: &thiol_typeck::Context,
+    module: &hir::Module,
+) -> String {
+    let doc = lines(module.programs.iter().map(|id| {
+        let inputs = lines(ctx.vertex_inputs(hir, *id).into_iter().map(|input| {
+            let format = match input.format {
+                Some(format) => format.to_string(),
+                None => "{none}".to_string(),
+            };
+            Doc::text(format!("{}: {} {}", input.location, input.name, format))
+        }));
+        Doc::text("program ")
+            .append(hir.identifiers[hir.programs[*id].name].clone())
+            .append(Doc::hardline().append(inputs).nest(4))
+    }));
+    let mut v = Vec::new();
+    doc.render(80, &mut v).unwrap();
+    String::from_utf8_lossy(&v).to_string()
+}
+
+pub(crate) fn dump_resources(
+    hir: &thiol_hir::Context,
+    ctx: &thiol_typeck::Context,
+    module: &hir::Module,
+) -> String {
+    let doc = lines(module.programs.iter().map(|id| {
+        let resources = lines(
+            ctx.program_resources(hir, module, *id)
+                .into_iter()
+                .map(|res| {
+                    let access = if res.written { "read write" } else { "read" };
+                    let binding = match res.binding {
+                        Some(b) if b.assigned => format!(
+                            ", set {}, binding {} (assigned)",
+                            b.binding.set, b.binding.binding
+                        ),
+                        Some(b) => {
+                            format!(", set {}, binding {}", b.binding.set, b.binding.binding)
+                        }
+                        None => String::new(),
+                    };
+                    // storage images, textures and samplers are named after
+                    // their type instead of the buffer class
+                    let class = match (res.image, res.texture) {
+                        (Some((dim, format, _)), _) => format!("{} {}", dim.type_name(), format),
+                        (None, Some(texture)) => texture.type_name(),
+                        (None, None) => res.class.to_string(),
+                    };
+                    Doc::text(format!("{}: {}{}, {}", res.name, class, binding, access))
+                }),
+        );
+        Doc::text("program ")
+            .append(hir.identifiers[hir.programs[*id].name].clone())
+            .append(Doc::hardline().append(resources).nest(4))
+    }));
+    let mut v = Vec::new();
+    doc.render(80, &mut v).unwrap();
+    String::from_utf8_lossy(&v).to_string()
+}
+
+pub(crate) fn dump_effects(
+    hir: &thiol_hir::Context,
+    ctx: &thiol_typeck::Context,
+    module: &hir::Module,
+) -> String {
+    let doc = lines(module.functions.iter().filter_map(|id| {
+        let name = &hir.identifiers[hir.functions[*id].name];
+        let sig = ctx
+            .function_sigs
+            .get(name)
+            .filter(|sig| sig.func_id == *id)?;
+        Some(Doc::text(format!("function {}: {}", name, sig.effects)))
+    }));
+    let mut v = Vec::new();
+    doc.render(80, &mut v).unwrap();
+    String::from_utf8_lossy(&v).to_string()
+}
+
+/// The profiling labels of every program and the functions it calls.
+pub(crate) fn dump_profile_labels(
+    hir: &thiol_hir::Context,
+    ctx: &thiol_typeck::Context,
+    module: &hir::Module,
+) -> String {
+    let doc =
+        lines(module.programs.iter().map(|id| {
+            let labels = lines(ctx.program_profile_labels(*id).into_iter().map(
+                |(label, callable)| {
+                    let item = match callable {
+                        ty::Callable::Function(id) => {
+                            format!("function {}", hir.identifiers[hir.functions[id].name])
+                        }
+                        ty::Callable::Program(id) => {
+                            format!("program {}", hir.identifiers[hir.programs[id].name])
+                        }
+                    };
+                    Doc::text(format!("{:?}: {}", label, item))
+                },
+            ));
+            Doc::text("program ")
+                .append(hir.identifiers[hir.programs[*id].name].clone())
+                .append(Doc::hardline().append(labels).nest(4))
+        }));
+    let mut v = Vec::new();
+    doc.render(80, &mut v).unwrap();
+    String::from_utf8_lossy(&v).to_string()
+}
+
+/// The instances of generic functions, in the order they were found in.
+pub(crate) fn dump_instances(hir: &thiol_hir::Context, ctx: &thiol_typeck::Context) -> String {
+    let doc = lines(ctx.instances.iter().map(|instance| {
+        let name = &hir.identifiers[hir.functions[instance.func].name];
+        Doc::text(format!(
+            "function {}",
+            ctx.function_instance_name(name, &instance.generics)
+        ))
+    }));
+    let mut v = Vec::new();
+    doc.render(80, &mut v).unwrap();
+    String::from_utf8_lossy(&v).to_string()
+}
+
+/// The module in the syntax of thiol, with every operation in parentheses.
+pub(crate) fn dump_module(hir: &hir::Context, module: &hir::Module) -> String {
+    let printer = HirPrinter { hir };
+    let mut sections = vec![];
+    if !module.spaces.is_empty() {
+        sections.push(lines(module.spaces.iter().map(|id| printer.space(*id))));
+    }
+    if !module.types.is_empty() {
+        let types = lines(module.types.iter().map(|id| printer.type_def(*id)));
+        sections.push(Doc::text("type").append(Doc::hardline().append(types).nest(4)));
+    }
+    if !module.consts.is_empty() {
+        let consts = lines(
+            module
+                .consts
+                .iter()
+                .map(|id| Doc::text(printer.variable_def(*id)).append(";")),
+        );
+        sections.push(Doc::text("const").append(Doc::hardline().append(consts).nest(4)));
+    }
+    sections.extend(module.functions.iter().map(|id| printer.function(*id)));
+    sections.extend(module.programs.iter().map(|id| printer.program(*id)));
+
+    let doc = Doc::intersperse(sections, Doc::hardline().append(Doc::hardline()));
+    let mut v = Vec::new();
+    doc.render(80, &mut v).unwrap();
+    String::from_utf8_lossy(&v).to_string()
+}
+
+struct HirPrinter<'a> {
+    hir: &'a hir::Context,
+}
+
+impl<'a> HirPrinter<'a> {
+    fn ident(&self, id: Id<hir::Identifier>) -> &'a str {
+        &self.hir.identifiers[id]
+    }
+
+    fn space(&self, id: Id<hir::SpaceDefinition>) -> Doc<'a> {
+        let def = &self.hir.spaces[id];
+        let parent = match def.parent {
+            Some((parent, via)) => {
+                format!(": parent {} via {}", self.ident(parent), self.ident(via))
+            }
+            None => String::new(),
+        };
+        Doc::text(format!("space {}{};", self.ident(def.name), parent))
+    }
+
+    fn type_def(&self, id: Id<hir::TypeDefinition>) -> Doc<'a> {
+        let def = &self.hir.type_defs[id];
+        let head = format!(
+            "{}{}{} = ",
+            self.attribute_prefix(&def.attrs),
+            self.ident(def.name),
+            self.generics(&def.generics)
+        );
+        match &self.hir.type_def_rhss[def.rhs] {
+            hir::TypeDefinitionRhs::Distinct(ty) => {
+                Doc::text(format!("{}distinct {};", head, self.type_ref(*ty)))
+            }
+            hir::TypeDefinitionRhs::Alias(ty) => {
+                Doc::text(format!("{}{};", head, self.type_ref(*ty)))
+            }
+            hir::TypeDefinitionRhs::Record { fields } => {
+                let fields = lines(
+                    fields
+                        .iter()
+                        .map(|id| Doc::text(self.variable_def(*id)).append(";")),
+                );
+                Doc::text(head)
+                    .append("record")
+                    .append(Doc::hardline().append(fields).nest(4))
+                    .append(Doc::hardline())
+                    .append("end")
+            }
+        }
+    }
+
+    fn generics(&self, generics: &[Id<hir::Identifier>]) -> String {
+        if generics.is_empty() {
+            return String::new();
+        }
+        let names = generics
+            .iter()
+            .map(|id| self.ident(*id))
+            .collect::<Vec<_>>();
+        format!("<{}>", names.join(", "))
+    }
+
+    fn function(&self, id: Id<hir::Function>) -> Doc<'a> {
+        let func = &self.hir.functions[id];
+        let args = func
+            .args
+            .iter()
+            .zip(&func.arg_attrs)
+            .map(|((name, ty, mode), attrs)| {
+                let mode = match mode {
+                    hir::ParamMode::In => "",
+                    hir::ParamMode::Out => "out ",
+                    hir::ParamMode::InOut => "in out ",
+                };
+                format!(
+                    "{}{}: {}{}",
+                    self.attribute_prefix(attrs),
+                    self.ident(*name),
+                    mode,
+                    self.type_ref(*ty)
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut doc = Doc::nil();
+        for attr in &func.attrs {
+            doc = doc.append(self.attribute(*attr)).append(Doc::hardline());
+        }
+        doc.append(format!(
+            "function {}{}({}) returns {}",
+            self.ident(func.name),
+            self.generics(&func.generics),
+            args.join(", "),
+            self.type_ref(func.ret_type)
+        ))
+        .append(Doc::hardline())
+        .append("begin")
+        .append(self.block(&func.body))
+        .append("end")
+    }
+
+    fn program(&self, id: Id<hir::Program>) -> Doc<'a> {
+        let prog = &self.hir.programs[id];
+        let mut doc = Doc::nil();
+        for attr in &prog.attrs {
+            doc = doc.append(self.attribute(*attr)).append(Doc::hardline());
+        }
+        doc = doc.append(format!("program {}", self.ident(prog.name)));
+        let sections = [
+            ("input", &prog.inputs),
+            ("output", &prog.outputs),
+            ("workgroup", &prog.workgroup),
+        ];
+        for (keyword, vars) in sections.iter() {
+            if vars.is_empty() {
+                continue;
+            }
+            let vars = lines(
+                vars.iter()
+                    .map(|id| Doc::text(self.variable_def(*id)).append(";")),
+            );
+            doc = doc
+                .append(Doc::hardline())
+                .append(*keyword)
+                .append(Doc::hardline().append(vars).nest(4));
+        }
+        doc.append(Doc::hardline())
+            .append("begin")
+            .append(self.block(&prog.body))
+            .append("end")
+    }
+
+    /// The statements of a block on lines of their own, indented.
+    fn block(&self, body: &[Id<hir::Statement>]) -> Doc<'a> {
+        if body.is_empty() {
+            return Doc::hardline();
+        }
+        let stmts = lines(body.iter().map(|id| self.statement(*id)));
+        Doc::hardline()
+            .append(stmts)
+            .nest(4)
+            .append(Doc::hardline())
+    }
+
+    fn statement(&self, id: Id<hir::Statement>) -> Doc<'a> {
+        match &self.hir.statements[id] {
+            hir::Statement::Var(def) => Doc::text(format!("var {};", self.variable_def(*def))),
+            hir::Statement::Becomes { lhs, rhs } => {
+                Doc::text(format!("{} := {};", self.expr(*lhs), self.expr(*rhs)))
+            }
+            hir::Statement::Destructure { lhs, rhs } => {
+                let lhs = lhs.iter().map(|e| self.expr(*e)).collect::<Vec<_>>();
+                Doc::text(format!("({}) := {};", lhs.join(", "), self.expr(*rhs)))
+            }
+            hir::Statement::Return(Some(e)) => Doc::text(format!("return {};", self.expr(*e))),
+            hir::Statement::Return(None) => Doc::text("return;"),
+            hir::Statement::Expr(e) => Doc::text(format!("{};", self.expr(*e))),
+            hir::Statement::Break => Doc::text("break;"),
+            hir::Statement::Continue => Doc::text("continue;"),
+            hir::Statement::Discard => Doc::text("discard;"),
+            hir::Statement::If {
+                cond,
+                then_body,
+                else_body,
+            } => {
+                let mut doc = Doc::text(format!("if {} then", self.expr(*cond)))
+                    .append(self.block(then_body));
+                if !else_body.is_empty() {
+                    doc = doc.append("else").append(self.block(else_body));
+                }
+                doc.append("end")
+            }
+            hir::Statement::Match { value, arms } => {
+                let mut doc =
+                    Doc::text(format!("match {}", self.expr(*value))).append(Doc::hardline());
+                for arm in arms {
+                    let pattern = match arm.pattern {
+                        hir::MatchPattern::Some(name) => format!("some({})", self.ident(name)),
+                        hir::MatchPattern::None => "none".to_string(),
+                    };
+                    doc = doc
+                        .append(format!("{} then", pattern))
+                        .append(self.block(&arm.body));
+                }
+                doc.append("end")
+            }
+            hir::Statement::For {
+                iter_name,
+                loop_type,
+                from,
+                to,
+                body,
+            } => {
+                let direction = match loop_type {
+                    hir::ForLoopType::Up => "to",
+                    hir::ForLoopType::Down => "downto",
+                };
+                Doc::text(format!(
+                    "for {} in {} {} {} do",
+                    self.ident(*iter_name),
+                    self.expr(*from),
+                    direction,
+                    self.expr(*to)
+                ))
+                .append(self.block(body))
+                .append("end")
+            }
+        }
+    }
+
+    fn attribute(&self, id: Id<hir::Attribute>) -> String {
+        let attr = &self.hir.attributes[id];
+        let args = self.args(&attr.pos_args, &attr.nam_args);
+        if args.is_empty() {
+            format!("@{}", self.ident(attr.name))
+        } else {
+            format!("@{}({})", self.ident(attr.name), args)
+        }
+    }
+
+    /// The attributes in front of a declaration on the same line.
+    fn attribute_prefix(&self, attrs: &[Id<hir::Attribute>]) -> String {
+        let mut s = String::new();
+        for attr in attrs {
+            s.push_str(&self.attribute(*attr));
+            s.push(' ');
+        }
+        s
+    }
+
+    fn variable_def(&self, id: Id<hir::VariableDef>) -> String {
+        let def = &self.hir.variable_defs[id];
+        let mut s = self.attribute_prefix(&def.attrs);
+        s.push_str(&format!(
+            "{}: {}",
+            self.ident(def.name),
+            self.type_ref(def.type_)
+        ));
+        if let Some(rhs) = def.rhs {
+            s.push_str(&format!(" := {}", self.expr(rhs)));
+        }
+        s
+    }
+
+    fn type_ref(&self, id: Id<hir::TypeReference>) -> String {
+        match &self.hir.type_refs[id] {
+            hir::TypeReference::Primitive(prim) => self.primitive(prim),
+            hir::TypeReference::OpenArray(base) => format!("array of {}", self.type_ref(*base)),
+            hir::TypeReference::Normalized(base) => {
+                format!("normalized<{}>", self.type_ref(*base))
+            }
+            hir::TypeReference::Optional(base) => format!("optional<{}>", self.type_ref(*base)),
+            hir::TypeReference::Record { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, ty)| format!("{}: {}", self.ident(*name), self.type_ref(*ty)))
+                    .collect::<Vec<_>>();
+                match fields.is_empty() {
+                    true => "record {}".to_string(),
+                    false => format!("record {{ {} }}", fields.join(", ")),
+                }
+            }
+            hir::TypeReference::Image {
+                dim,
+                format,
+                access,
+            } => format!(
+                "{}<{}, {}>",
+                thiol_typeck::ImageDim::from(*dim).type_name(),
+                self.ident(*format),
+                self.ident(*access)
+            ),
+            hir::TypeReference::Texture { dim, sampled } => {
+                let dim = thiol_typeck::TextureDim::from(*dim);
+                match sampled {
+                    Some(sampled) => format!("{}<{}>", dim.type_name(), self.type_ref(*sampled)),
+                    None => dim.depth_type_name().unwrap_or_default().to_string(),
+                }
+            }
+            hir::TypeReference::Sampler { comparison: false } => "sampler".to_string(),
+            hir::TypeReference::Sampler { comparison: true } => "sampler_comparison".to_string(),
+            hir::TypeReference::AccelerationStructure => "acceleration_structure".to_string(),
+            hir::TypeReference::Array { base, size } => {
+                let size = match size {
+                    hir::ArraySize::Literal(size) => size.to_string(),
+                    hir::ArraySize::Expression(size) => self.expr(*size),
+                };
+                format!("array[{}] of {}", size, self.type_ref(*base))
+            }
+            hir::TypeReference::Named { name, generics } if generics.is_empty() => {
+                self.ident(*name).to_string()
+            }
+            hir::TypeReference::Named { name, generics } => {
+                let generics = generics
+                    .iter()
+                    .map(|ty| self.type_ref(*ty))
+                    .collect::<Vec<_>>();
+                format!("{}<{}>", self.ident(*name), generics.join(", "))
+            }
+        }
+    }
+
+    fn primitive(&self, prim: &hir::PrimitiveType) -> String {
+        use hir::PrimitiveType as P;
+
+        let vector = |name: &str,
+                      components: &hir::VecSize,
+                      vtype: &Option<Id<hir::VecType>>,
+                      space: &Option<Id<hir::Identifier>>| {
+            let mut s = format!("{}{}", name, vec_size(components));
+            if let Some(vtype) = vtype {
+                let vtype = match self.hir.vec_types[*vtype] {
+                    hir::VecType::Point => "Point",
+                    hir::VecType::Vector => "Vector",
+                    hir::VecType::Colour => "Colour",
+                    hir::VecType::SrgbColour => "SrgbColour",
+                };
+                s.push_str(&format!(" is {}", vtype));
+            }
+            if let Some(space) = space {
+                s.push_str(&format!(" in {}", self.ident(*space)));
+            }
+            s
+        };
+        let matrix =
+            |name: &str,
+             cols: &hir::VecSize,
+             rows: &hir::VecSize,
+             transform: &Option<(Id<hir::Identifier>, Id<hir::Identifier>)>| {
+                let mut s = format!("{}{}x{}", name, vec_size(cols), vec_size(rows));
+                if let Some((from, to)) = transform {
+                    s.push_str(&format!(
+                        " from {} to {}",
+                        self.ident(*from),
+                        self.ident(*to)
+                    ));
+                }
+                s
+            };
+
+        match prim {
+            P::Bool => "bool".to_string(),
+            P::Int => "int".to_string(),
+            P::UInt => "uint".to_string(),
+            P::Long => "long".to_string(),
+            P::ULong => "ulong".to_string(),
+            P::Float => "float".to_string(),
+            P::Double => "double".to_string(),
+            P::Half => "half".to_string(),
+            P::Radians => "radians".to_string(),
+            P::Degrees => "degrees".to_string(),
+            P::AtomicInt => "atomic<int>".to_string(),
+            P::AtomicUInt => "atomic<uint>".to_string(),
+            P::BoolVec { components } => format!("bool{}", vec_size(components)),
+            P::IntVec {
+                components,
+                vtype,
+                space,
+            } => vector("int", components, vtype, space),
+            P::UIntVec {
+                components,
+                vtype,
+                space,
+            } => vector("uint", components, vtype, space),
+            P::LongVec {
+                components,
+                vtype,
+                space,
+            } => vector("long", components, vtype, space),
+            P::ULongVec {
+                components,
+                vtype,
+                space,
+            } => vector("ulong", components, vtype, space),
+            P::FloatVec {
+                components,
+                vtype,
+                space,
+            } => vector("float", components, vtype, space),
+            P::DoubleVec {
+                components,
+                vtype,
+                space,
+            } => vector("double", components, vtype, space),
+            P::HalfVec {
+                components,
+                vtype,
+                space,
+            } => vector("half", components, vtype, space),
+            P::FloatMat {
+                cols,
+                rows,
+                transform,
+            } => matrix("float", cols, rows, transform),
+            P::DoubleMat {
+                cols,
+                rows,
+                transform,
+            } => matrix("double", cols, rows, transform),
+            P::Packed { format } => {
+                let format = match format {
+                    hir::PackedFormat::Unorm8x4 => "unorm8x4",
+                    hir::PackedFormat::Snorm8x4 => "snorm8x4",
+                    hir::PackedFormat::Uint8x4 => "uint8x4",
+                    hir::PackedFormat::Sint8x4 => "sint8x4",
+                    hir::PackedFormat::Unorm16x2 => "unorm16x2",
+                    hir::PackedFormat::Snorm16x2 => "snorm16x2",
+                    hir::PackedFormat::Float16x2 => "float16x2",
+                    hir::PackedFormat::Float16x4 => "float16x4",
+                    hir::PackedFormat::Rgb10a2 => "rgb10a2",
+                };
+                format.to_string()
+            }
+        }
+    }
+
+    fn args(
+        &self,
+        pos_args: &[Id<hir::Expression>],
+        nam_args: &[(Id<hir::Identifier>, Id<hir::Expression>)],
+    ) -> String {
+        let pos_args = pos_args.iter().map(|e| self.expr(*e));
+        let nam_args = nam_args
+            .iter()
+            .map(|(name, e)| format!("{}: {}", self.ident(*name), self.expr(*e)));
+        pos_args.chain(nam_args).collect::<Vec<_>>().join(", ")
+    }
+
+    fn expr(&self, id: Id<hir::Expression>) -> String {
+        use hir::PrimitiveOp as PO;
+
+        match &self.hir.expressions[id] {
+            hir::Expression::Literal(hir::Literal::Integer(n, suffix)) => {
+                format!("{}{}", n, suffix_text(*suffix))
+            }
+            hir::Expression::Literal(hir::Literal::Float(f, suffix)) => {
+                format!("{:?}{}", f, suffix_text(*suffix))
+            }
+            hir::Expression::Literal(hir::Literal::Bool(b)) => b.to_string(),
+            hir::Expression::Literal(hir::Literal::String(s)) => {
+                format!("{:?}", self.hir.strings[*s])
+            }
+            hir::Expression::Variable(name) => self.ident(*name).to_string(),
+            hir::Expression::PrimitiveOp(op) => {
+                let binary = |a: &Id<hir::Expression>, op: &str, b: &Id<hir::Expression>| {
+                    format!("({} {} {})", self.expr(*a), op, self.expr(*b))
+                };
+                match &self.hir.prim_ops[*op] {
+                    PO::Neg(e) => format!("-{}", self.expr(*e)),
+                    PO::Pos(e) => format!("+{}", self.expr(*e)),
+                    PO::Add(a, b) => binary(a, "+", b),
+                    PO::Sub(a, b) => binary(a, "-", b),
+                    PO::Mul(a, b) => binary(a, "*", b),
+                    PO::Div(a, b) => binary(a, "/", b),
+                    PO::Mod(a, b) => binary(a, "mod", b),
+                    PO::Gt(a, b) => binary(a, ">", b),
+                    PO::Gte(a, b) => binary(a, ">=", b),
+                    PO::Lt(a, b) => binary(a, "<", b),
+                    PO::Lte(a, b) => binary(a, "<=", b),
+                    PO::Eq(a, b) => binary(a, "=", b),
+                    PO::Neq(a, b) => binary(a, "<>", b),
+                    PO::Constructor {
+                        ty,
+                        pos_args,
+                        nam_args,
+                    } => format!("{}({})", self.primitive(ty), self.args(pos_args, nam_args)),
+                }
+            }
+            hir::Expression::Call {
+                name,
+                pos_args,
+                nam_args,
+            } => format!("{}({})", self.ident(*name), self.args(pos_args, nam_args)),
+            hir::Expression::Field { base, name } => {
+                format!("{}.{}", self.expr(*base), self.ident(*name))
+            }
+            hir::Expression::Index { base, index } => {
+                format!("{}[{}]", self.expr(*base), self.expr(*index))
+            }
+            hir::Expression::Slice { base, lo, hi } => {
+                format!(
+                    "{}[{}..{}]",
+                    self.expr(*base),
+                    self.expr(*lo),
+                    self.expr(*hi)
+                )
+            }
+            hir::Expression::As { base, ty } => {
+                format!("({} as {})", self.expr(*base), self.type_ref(*ty))
+            }
+            hir::Expression::LayoutQuery { query, ty, rules } => {
+                let (name, mut args) = match query {
+                    hir::LayoutQuery::Size => ("sizeof", vec![self.type_ref(*ty)]),
+                    hir::LayoutQuery::Align => ("alignof", vec![self.type_ref(*ty)]),
+                    hir::LayoutQuery::Offset(field) => (
+                        "offsetof",
+                        vec![self.type_ref(*ty), self.ident(*field).to_string()],
+                    ),
+                };
+                args.extend(rules.map(|rules| self.ident(rules).to_string()));
+                format!("{}({})", name, args.join(", "))
+            }
+            hir::Expression::Array(elements) => {
+                let elements = elements.iter().map(|e| self.expr(*e)).collect::<Vec<_>>();
+                format!("[{}]", elements.join(", "))
+            }
+        }
+    }
+}
+
+fn vec_size(s: &hir::VecSize) -> &'static str {
+    match s {
+        hir::VecSize::VS2 => "2",
+        hir::VecSize::VS3 => "3",
+        hir::VecSize::VS4 => "4",
+    }
+}
+
 struct TypePrinter<'a> {
     _hir: &'a hir::Context,
     ty: &'a ty::Context,
 }
 
 impl<'a> TypePrinter<'a> {
-    fn print_type(&self, id: ty::TypeId) -> Doc {
-        let type_ = self.ty.types.get_by_right(&id).unwrap();
+    fn print_type(&self, id: ty::TypeId) -> Doc<'_> {
+        let type_ = self.ty.types.get(id).unwrap();
 
         match &type_ {
             ty::Type::Bool => Doc::text("bool"),
             ty::Type::Int => Doc::text("int"),
             ty::Type::UInt => Doc::text("uint"),
+            ty::Type::Long => Doc::text("long"),
+            ty::Type::ULong => Doc::text("ulong"),
             ty::Type::Float => Doc::text("float"),
             ty::Type::Double => Doc::text("double"),
+            ty::Type::Half => Doc::text("half"),
+            ty::Type::Radians => Doc::text("radians"),
+            ty::Type::Degrees => Doc::text("degrees"),
+            ty::Type::AtomicInt => Doc::text("atomic<int>"),
+            ty::Type::AtomicUInt => Doc::text("atomic<uint>"),
+            ty::Type::Image {
+                dim,
+                format,
+                access,
+            } => Doc::text(format!(
+                "{}<{}, {}>",
+                dim.type_name(),
+                format,
+                access.name()
+            )),
+            ty::Type::Texture { dim, sampled } => {
+                Doc::text(thiol_typeck::textures::texture_type_name(*dim, *sampled))
+            }
+            ty::Type::Sampler { comparison: false } => Doc::text("sampler"),
+            ty::Type::Sampler { comparison: true } => Doc::text("sampler_comparison"),
+            ty::Type::AccelerationStructure => Doc::text("acceleration_structure"),
             ty::Type::BoolVec { components } => Doc::text("bool").append(comp_size(components)),
             ty::Type::IntVec {
                 components,
@@ -49,7 +742,7 @@ impl<'a> TypePrinter<'a> {
                     .append(comp_type(vtype));
 
                 if let Some(space) = space {
-                    initial.append(format!("{{{}}}", space))
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
                 } else {
                     initial
                 }
@@ -64,7 +757,37 @@ impl<'a> TypePrinter<'a> {
                     .append(comp_type(vtype));
 
                 if let Some(space) = space {
-                    initial.append(format!("{{{}}}", space))
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
+                } else {
+                    initial
+                }
+            }
+            ty::Type::LongVec {
+                components,
+                vtype,
+                space,
+            } => {
+                let initial = Doc::text("long")
+                    .append(comp_size(components))
+                    .append(comp_type(vtype));
+
+                if let Some(space) = space {
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
+                } else {
+                    initial
+                }
+            }
+            ty::Type::ULongVec {
+                components,
+                vtype,
+                space,
+            } => {
+                let initial = Doc::text("ulong")
+                    .append(comp_size(components))
+                    .append(comp_type(vtype));
+
+                if let Some(space) = space {
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
                 } else {
                     initial
                 }
@@ -79,7 +802,7 @@ impl<'a> TypePrinter<'a> {
                     .append(comp_type(vtype));
 
                 if let Some(space) = space {
-                    initial.append(format!("{{{}}}", space))
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
                 } else {
                     initial
                 }
@@ -94,7 +817,22 @@ impl<'a> TypePrinter<'a> {
                     .append(comp_type(vtype));
 
                 if let Some(space) = space {
-                    initial.append(format!("{{{}}}", space))
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
+                } else {
+                    initial
+                }
+            }
+            ty::Type::HalfVec {
+                components,
+                vtype,
+                space,
+            } => {
+                let initial = Doc::text("half")
+                    .append(comp_size(components))
+                    .append(comp_type(vtype));
+
+                if let Some(space) = space {
+                    initial.append(format!("{{{}}}", self.ty.types.name(*space)))
                 } else {
                     initial
                 }
@@ -110,7 +848,11 @@ impl<'a> TypePrinter<'a> {
                     .append(comp_size(rows));
 
                 if let Some((from, to)) = transform {
-                    initial.append(format!("{{{} ⇒ {}}}", from, to))
+                    initial.append(format!(
+                        "{{{} ⇒ {}}}",
+                        self.ty.types.name(*from),
+                        self.ty.types.name(*to)
+                    ))
                 } else {
                     initial
                 }
@@ -126,11 +868,16 @@ impl<'a> TypePrinter<'a> {
                     .append(comp_size(rows));
 
                 if let Some((from, to)) = transform {
-                    initial.append(format!("{{{} ⇒ {}}}", from, to))
+                    initial.append(format!(
+                        "{{{} ⇒ {}}}",
+                        self.ty.types.name(*from),
+                        self.ty.types.name(*to)
+                    ))
                 } else {
                     initial
                 }
             }
+            ty::Type::Packed { format } => Doc::text(format.name()),
             ty::Type::Array { base, size } => Doc::text(format!("array[{}] of (", *size))
                 .append(self.print_type(*base))
                 .append(")"),
@@ -140,7 +887,7 @@ impl<'a> TypePrinter<'a> {
             ty::Type::Record { fields } => {
                 let fields = lines(fields.iter().map(|(name, id)| {
                     Doc::nil()
-                        .append(name)
+                        .append(self.ty.types.name(*name))
                         .append(" : ")
                         .append(self.print_type(*id))
                         .group()
@@ -155,6 +902,15 @@ impl<'a> TypePrinter<'a> {
             ty::Type::Distinct { distinct_id, inner } => {
                 Doc::text(format!("({}) ", distinct_id)).append(self.print_type(*inner))
             }
+            ty::Type::Normalized { inner } => Doc::text("normalized<")
+                .append(self.print_type(*inner))
+                .append(">"),
+            ty::Type::Optional { inner } => Doc::text("optional<")
+                .append(self.print_type(*inner))
+                .append(">"),
+            ty::Type::GenericParam { index: _, name } => Doc::text(name.clone()),
+            ty::Type::Var(id) => Doc::text(format!("?{}", id)),
+            ty::Type::Error => Doc::text("{error}"),
         }
     }
 }
@@ -173,6 +929,7 @@ fn comp_type(t: &ty::types::VecType) -> &'static str {
         VecType::Point => "[Point]",
         VecType::Vector => "[Vector]",
         VecType::Colour => "[Colour]",
+        VecType::SrgbColour => "[SrgbColour]",
     }
 }
 
@@ -184,3 +941,17 @@ fn lines<'a>(i: impl IntoIterator<Item = Doc<'a>>) -> Doc<'a> {
         ))
         .group()
 }
+
+fn suffix_text(suffix: Option<hir::LiteralSuffix>) -> &'static str {
+    match suffix {
+        None => "",
+        Some(hir::LiteralSuffix::Int) => "i",
+        Some(hir::LiteralSuffix::UInt) => "u",
+        Some(hir::LiteralSuffix::Long) => "l",
+        Some(hir::LiteralSuffix::ULong) => "ul",
+        Some(hir::LiteralSuffix::Float) => "f",
+        Some(hir::LiteralSuffix::Double) => "lf",
+        Some(hir::LiteralSuffix::Radians) => "rad",
+        Some(hir::LiteralSuffix::Degrees) => "deg",
+    }
+}
@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Sending hot reload events to engines, for `--watch --publish`.
+//!
+//! Engines connect to the address of `--publish` over TCP and receive the
+//! events of every module that compiles, in the format of
+//! [`ArtifactEvent::encode`]. An engine that doesn't take an event within
+//! [`WRITE_TIMEOUT`] has stopped reading or fallen behind and is
+//! disconnected, so that it can't hold up the compiler. Tools that run the
+//! compiler in the same process can subscribe to the events through a
+//! channel instead.
+
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use thiol_backend::hot_reload::ArtifactEvent;
+
+/// How long sending an event to an engine may block
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends events to the connected engines and the subscribed channels
+#[derive(Default)]
+pub struct Publisher {
+    listener: Option<TcpListener>,
+    clients: Vec<TcpStream>,
+    channels: Vec<Sender<ArtifactEvent>>,
+    write_timeout: Option<Duration>,
+}
+
+impl Publisher {
+    /// A publisher without a socket, see [`subscribe`](Self::subscribe).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A publisher accepting engines on an address, like `127.0.0.1:7171`.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Publisher {
+            listener: Some(listener),
+            write_timeout: Some(WRITE_TIMEOUT),
+            ..Self::default()
+        })
+    }
+
+    /// A channel receiving all events published from now on.
+    pub fn subscribe(&mut self) -> Receiver<ArtifactEvent> {
+        let (sender, receiver) = channel();
+        self.channels.push(sender);
+        receiver
+    }
+
+    /// Send events to everyone listening. Engines that disconnected or
+    /// didn't take an event in time and dropped channels are forgotten.
+    pub fn publish(&mut self, events: &[ArtifactEvent]) {
+        if let Some(listener) = &self.listener {
+            while let Ok((client, _)) = listener.accept() {
+                if client.set_nonblocking(false).is_ok()
+                    && client.set_write_timeout(self.write_timeout).is_ok()
+                {
+                    self.clients.push(client);
+                }
+            }
+        }
+
+        let frames = events.iter().map(ArtifactEvent::encode).collect::<Vec<_>>();
+        self.clients
+            .retain_mut(|client| frames.iter().all(|frame| client.write_all(frame).is_ok()));
+        self.channels.retain(|channel| {
+            events
+                .iter()
+                .all(|event| channel.send(event.clone()).is_ok())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use thiol_backend::hot_reload::Reflection;
+
+    #[test]
+    fn socket_and_channel() {
+        let event = ArtifactEvent {
+            module: "sky".to_string(),
+            entry_point: "sky_fs".to_string(),
+            stage: None,
+            artifact: "sky.metal".to_string(),
+            bytes: b"fragment".to_vec(),
+            reflection: Reflection::default(),
+        };
+
+        let mut publisher = Publisher::bind("127.0.0.1:0").unwrap();
+        let addr = publisher.listener.as_ref().unwrap().local_addr().unwrap();
+        let mut engine = TcpStream::connect(addr).unwrap();
+        let events = publisher.subscribe();
+
+        publisher.publish(std::slice::from_ref(&event));
+
+        let mut frame = vec![0; event.encode().len()];
+        engine.read_exact(&mut frame).unwrap();
+        assert_eq!(
+            ArtifactEvent::decode(&frame),
+            Some((event.clone(), frame.len()))
+        );
+        assert_eq!(events.try_recv(), Ok(event));
+    }
+
+    #[test]
+    fn drop_engines_that_fall_behind() {
+        let event = ArtifactEvent {
+            module: "sky".to_string(),
+            entry_point: "sky_fs".to_string(),
+            stage: None,
+            artifact: "sky.metal".to_string(),
+            // more than the buffers of the socket hold
+            bytes: vec![0; 64 << 20],
+            reflection: Reflection::default(),
+        };
+
+        let mut publisher = Publisher::bind("127.0.0.1:0").unwrap();
+        publisher.write_timeout = Some(Duration::from_millis(10));
+        let addr = publisher.listener.as_ref().unwrap().local_addr().unwrap();
+        let _engine = TcpStream::connect(addr).unwrap();
+
+        publisher.publish(std::slice::from_ref(&event));
+        assert!(publisher.clients.is_empty());
+    }
+}
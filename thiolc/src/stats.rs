@@ -0,0 +1,101 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Timings and sizes of a compilation, to find out which phase makes a build
+//! slow.
+
+use std::fmt;
+use std::time::Duration;
+
+/// How long the phases of a compilation took and how much they processed,
+/// summed over all files
+#[derive(Debug, Clone, Default)]
+pub struct CompilationStats {
+    /// the phases in the order they first ran, like `parse`, `typeck: effects`
+    /// or `backend: msl`
+    pub phases: Vec<(String, Duration)>,
+    pub files: usize,
+    /// files whose artifacts came from the cache, they are not checked again
+    pub cached: usize,
+    /// types interned by the type checker
+    pub types: usize,
+    pub functions: usize,
+    pub programs: usize,
+}
+
+impl CompilationStats {
+    /// Add the time of a phase, to the time of the phase with the same name
+    /// if it ran before.
+    pub fn record(&mut self, phase: impl Into<String>, time: Duration) {
+        let phase = phase.into();
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += time,
+            None => self.phases.push((phase, time)),
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, time)| *time).sum()
+    }
+}
+
+impl fmt::Display for CompilationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .phases
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(Some("total".len()))
+            .max()
+            .unwrap_or(0);
+        let millis = |time: Duration| time.as_secs_f64() * 1000.0;
+        for (name, time) in &self.phases {
+            writeln!(
+                f,
+                "{:width$}  {:>9.3}ms",
+                name,
+                millis(*time),
+                width = width
+            )?;
+        }
+        writeln!(
+            f,
+            "{:width$}  {:>9.3}ms",
+            "total",
+            millis(self.total()),
+            width = width
+        )?;
+        write!(f, "{} files", self.files)?;
+        if self.cached > 0 {
+            write!(f, " ({} cached)", self.cached)?;
+        }
+        write!(
+            f,
+            ", {} types interned, {} functions and {} programs checked",
+            self.types, self.functions, self.programs
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_of_several_files() {
+        let mut stats = CompilationStats::default();
+        stats.record("parse", Duration::from_millis(2));
+        stats.record("typeck: effects", Duration::from_millis(1));
+        stats.record("parse", Duration::from_millis(3));
+
+        assert_eq!(
+            stats.phases,
+            [
+                ("parse".to_string(), Duration::from_millis(5)),
+                ("typeck: effects".to_string(), Duration::from_millis(1))
+            ]
+        );
+        assert_eq!(stats.total(), Duration::from_millis(6));
+    }
+}
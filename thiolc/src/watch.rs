@@ -0,0 +1,122 @@
+// SPDX-FileCopyrightText: 2021 The thiol developers
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+//! Finding the source files that changed, for `--watch`.
+//!
+//! The watcher polls the modification times of the files, which works the
+//! same on every platform and file system, network shares included. A module
+//! doesn't depend on other files, so a change only requires compiling the
+//! file that changed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Watches source files, and directories for the `.rsh` files in them
+pub struct Watcher {
+    roots: Vec<PathBuf>,
+    interval: Duration,
+    /// modification times of the files at the last poll
+    seen: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(roots: impl IntoIterator<Item = PathBuf>) -> Self {
+        Watcher {
+            roots: roots.into_iter().collect(),
+            interval: Duration::from_millis(200),
+            seen: BTreeMap::new(),
+        }
+    }
+
+    /// Set how long [`wait`](Self::wait) sleeps between two polls.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The files that were added or modified since the last poll, in path
+    /// order. The first poll returns all files.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut files = vec![];
+        for root in &self.roots {
+            collect(root, true, &mut files);
+        }
+
+        let mut changed = vec![];
+        let mut seen = BTreeMap::new();
+        for (path, modified) in files {
+            if self.seen.get(&path) != Some(&modified) {
+                changed.push(path.clone());
+            }
+            seen.insert(path, modified);
+        }
+        self.seen = seen;
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+
+    /// Wait until files are added or modified and return them.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            std::thread::sleep(self.interval);
+            let changed = self.poll();
+            if !changed.is_empty() {
+                return changed;
+            }
+        }
+    }
+}
+
+/// Add the files under a path with their modification times. Files that are
+/// named explicitly are watched whatever their extension is.
+fn collect(path: &Path, root: bool, files: &mut Vec<(PathBuf, SystemTime)>) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+    if metadata.is_dir() {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            collect(&entry.path(), false, files);
+        }
+    } else if root || path.extension().is_some_and(|ext| ext == "rsh") {
+        if let Ok(modified) = metadata.modified() {
+            files.push((path.to_path_buf(), modified));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_files() {
+        let dir = std::env::temp_dir().join(format!("thiol-watch-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let a = dir.join("a.rsh");
+        let b = dir.join("lib/b.rsh");
+        fs::write(&a, "const A: int := 1;").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let mut watcher = Watcher::new(vec![dir.clone()]);
+        assert_eq!(watcher.poll(), std::slice::from_ref(&a));
+        assert!(watcher.poll().is_empty());
+
+        fs::write(&b, "const B: int := 2;").unwrap();
+        assert_eq!(watcher.poll(), [b]);
+
+        let file = fs::File::options().write(true).open(&a).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(watcher.poll(), [a]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
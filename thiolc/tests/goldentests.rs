@@ -2,10 +2,124 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use goldentests::{TestConfig, TestResult};
+//! Runs `thiolc` over every file below `tests/` and compares its output
+//! with the expectations written in the trailing comments of the file.
+//!
+//! A test file ends in a block of `// ` comments with an `args:` line and
+//! `expected stdout:` and `expected stderr:` sections, an output without a
+//! section is expected to be empty.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const PREFIX: &str = "// ";
+
+#[derive(Default)]
+struct Expectations {
+    args: Vec<String>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    Stdout,
+    Stderr,
+}
+
+fn parse_expectations(source: &str) -> Expectations {
+    let mut expectations = Expectations::default();
+    let mut section = None;
+
+    for line in source.lines() {
+        let rest = match line.strip_prefix(PREFIX) {
+            Some(rest) => rest,
+            None => {
+                section = None;
+                continue;
+            }
+        };
+
+        if let Some(args) = rest.strip_prefix("args:") {
+            expectations.args = args.split_whitespace().map(String::from).collect();
+            section = None;
+        } else if rest.starts_with("expected stdout:") {
+            section = Some(Section::Stdout);
+        } else if rest.starts_with("expected stderr:") {
+            section = Some(Section::Stderr);
+        } else if let Some(section) = section {
+            let output = match section {
+                Section::Stdout => &mut expectations.stdout,
+                Section::Stderr => &mut expectations.stderr,
+            };
+            output.push_str(rest);
+            output.push('\n');
+        }
+    }
+
+    expectations
+}
+
+fn collect_tests(dir: &Path, tests: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_tests(&path, tests);
+        } else if path.extension() == Some("rsh".as_ref()) {
+            tests.push(path);
+        }
+    }
+}
+
+fn run_test(path: &Path) -> Option<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expectations = parse_expectations(&source);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_thiolc"))
+        .args(&expectations.args)
+        .arg(path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let mut failure = String::new();
+    if stdout != expectations.stdout {
+        failure += &format!(
+            "expected stdout:\n{}\nactual stdout:\n{}\n",
+            expectations.stdout, stdout
+        );
+    }
+    if stderr != expectations.stderr {
+        failure += &format!(
+            "expected stderr:\n{}\nactual stderr:\n{}\n",
+            expectations.stderr, stderr
+        );
+    }
+
+    if failure.is_empty() {
+        None
+    } else {
+        Some(format!("{}:\n{}", path.display(), failure))
+    }
+}
 
 #[test]
-fn goldentests() -> TestResult<()> {
-    let config = TestConfig::new("../target/debug/thiolc", "../tests", "// ")?;
-    config.run_tests()
+fn goldentests() {
+    let mut tests = Vec::new();
+    collect_tests(Path::new("../tests"), &mut tests);
+    tests.sort();
+
+    let failures: Vec<_> = tests.iter().filter_map(|path| run_test(path)).collect();
+    for failure in &failures {
+        eprintln!("{}", failure);
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} golden tests failed",
+        failures.len(),
+        tests.len()
+    );
 }